@@ -0,0 +1,54 @@
+//! 已缓存缩略图的吞吐量：每次读文件与 `--thumb-cache-mb` 内存缓存对比。
+//!
+//! ```bash
+//! cargo bench --bench thumbnails
+//! ```
+
+use actix_web::{rt, test, App};
+use image::{Rgb, RgbImage};
+use pic_url::PicUrl;
+use std::time::{Duration, Instant};
+
+const IMAGES: usize = 200;
+const ROUNDS: usize = 50;
+
+/// 请求全部缩略图 `ROUNDS` 轮，返回每秒请求数
+async fn requests_per_sec(dir: &str, cache_mb: u64) -> f64 {
+    let gallery = PicUrl::builder()
+        .pic_dir(dir)
+        .prewarm(false)
+        .thumb_cache_mb(cache_mb)
+        .build()
+        .unwrap();
+    gallery.config().rescan();
+    let app = test::init_service(App::new().service(gallery)).await;
+    let uris: Vec<String> = (0..IMAGES).map(|i| format!("/thumb/{}.jpg", i)).collect();
+    // 先生成全部缩略图，之后只测缓存命中的读取
+    for uri in &uris {
+        test::call_and_read_body(&app, test::TestRequest::get().uri(uri).to_request()).await;
+    }
+    let started = Instant::now();
+    for _ in 0..ROUNDS {
+        for uri in &uris {
+            test::call_and_read_body(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        }
+    }
+    (IMAGES * ROUNDS) as f64 / started.elapsed().max(Duration::from_nanos(1)).as_secs_f64()
+}
+
+fn main() {
+    let dir = tempfile::tempdir().unwrap();
+    for i in 0..IMAGES {
+        let color = [(i * 7 % 256) as u8, (i * 13 % 256) as u8, (i * 29 % 256) as u8];
+        RgbImage::from_fn(800, 600, |x, y| Rgb([color[0] ^ (x as u8), color[1] ^ (y as u8), color[2]]))
+            .save(dir.path().join(format!("{}.jpg", i)))
+            .unwrap();
+    }
+    let dir = dir.path().to_string_lossy().to_string();
+    rt::System::new().block_on(async {
+        let read = requests_per_sec(&dir, 0).await;
+        let cached = requests_per_sec(&dir, 64).await;
+        println!("每次读文件:   {:>8.0} 请求/秒", read);
+        println!("内存缓存:     {:>8.0} 请求/秒 ({:.2}x)", cached, cached / read);
+    });
+}
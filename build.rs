@@ -0,0 +1,10 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    // gRPC 接口的代码由 proto/pic_url.proto 生成，使用自带的 protoc，不需要另外安装
+    #[cfg(feature = "grpc")]
+    {
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("protoc is not available for this platform");
+        std::env::set_var("PROTOC", protoc);
+        tonic_prost_build::compile_protos("proto/pic_url.proto").expect("failed to compile proto/pic_url.proto");
+    }
+}
@@ -0,0 +1,199 @@
+use actix_web::dev::ServiceRequest;
+use actix_web::middleware::Logger;
+use serde::Serialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use time::format_description::FormatItem;
+use time::OffsetDateTime;
+
+/// 访问日志使用的 log target，只有该 target 的记录会写入日志文件
+pub const ACCESS_LOG_TARGET: &str = "pic_url::access";
+
+/// Apache/Nginx combined 格式，末尾加上请求 ID
+const COMBINED_FORMAT: &str =
+    r#"%{client}xi - - [%{clf_time}xi] "%r" %s %b "%{Referer}i" "%{User-Agent}i" %{x-request-id}o"#;
+
+const CLF_TIME_FORMAT: &[FormatItem<'static>] = time::macros::format_description!(
+    "[day]/[month repr:short]/[year]:[hour]:[minute]:[second] +0000"
+);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RotatePolicy {
+    /// 文件超过大小上限时轮转
+    Size,
+    /// 每天（UTC）轮转一次，同时仍受大小上限约束
+    Daily,
+}
+
+impl RotatePolicy {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "size" => Some(RotatePolicy::Size),
+            "daily" => Some(RotatePolicy::Daily),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AccessLogConfig {
+    pub path: String,
+    pub max_size: u64,
+    pub rotate: RotatePolicy,
+    pub keep: usize,
+}
+
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    day: i32,
+    max_size: u64,
+    rotate: RotatePolicy,
+    keep: usize,
+}
+
+impl RotatingFile {
+    fn open(config: &AccessLogConfig) -> io::Result<Self> {
+        let path = PathBuf::from(&config.path);
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let meta = file.metadata()?;
+        let day = meta
+            .modified()
+            .map(|t| OffsetDateTime::from(t).to_julian_day())
+            .unwrap_or_else(|_| today());
+        Ok(Self {
+            path,
+            size: meta.len(),
+            file,
+            day,
+            max_size: config.max_size,
+            rotate: config.rotate,
+            keep: config.keep,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let incoming = line.len() as u64 + 1;
+        let day_changed = self.rotate == RotatePolicy::Daily && today() != self.day;
+        let too_large = self.max_size > 0 && self.size > 0 && self.size + incoming > self.max_size;
+        if day_changed || too_large {
+            self.rotate_now()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += incoming;
+        Ok(())
+    }
+
+    fn rotate_now(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let stamp = OffsetDateTime::now_utc();
+        let suffix = format!(
+            "{:04}{:02}{:02}-{:02}{:02}{:02}",
+            stamp.year(),
+            stamp.month() as u8,
+            stamp.day(),
+            stamp.hour(),
+            stamp.minute(),
+            stamp.second()
+        );
+        let mut rotated = PathBuf::from(format!("{}.{}", self.path.display(), suffix));
+        let mut n = 1;
+        while rotated.exists() {
+            rotated = PathBuf::from(format!("{}.{}-{}", self.path.display(), suffix, n));
+            n += 1;
+        }
+        fs::rename(&self.path, &rotated)?;
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.size = 0;
+        self.day = today();
+        self.prune();
+        Ok(())
+    }
+
+    /// 只保留最近 `keep` 个轮转出来的文件
+    fn prune(&self) {
+        if self.keep == 0 {
+            return;
+        }
+        let (dir, prefix) = match (self.path.parent(), self.path.file_name()) {
+            (Some(dir), Some(name)) => {
+                let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+                (dir, format!("{}.", name.to_string_lossy()))
+            }
+            _ => return,
+        };
+        let mut rotated: Vec<PathBuf> = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| {
+                    p.file_name()
+                        .map(|n| n.to_string_lossy().starts_with(&prefix))
+                        .unwrap_or(false)
+                })
+                .collect(),
+            Err(_) => return,
+        };
+        // 文件名中的时间戳按字典序即为时间顺序
+        rotated.sort();
+        if rotated.len() > self.keep {
+            for old in &rotated[..rotated.len() - self.keep] {
+                let _ = fs::remove_file(old);
+            }
+        }
+    }
+}
+
+fn today() -> i32 {
+    OffsetDateTime::now_utc().to_julian_day()
+}
+
+/// 打开的访问日志文件，由 [`logging`](crate::logging) 中的日志写入器持有，
+/// `access_logger()` 产生的记录写入这里
+pub struct AccessLogFile(Mutex<RotatingFile>);
+
+impl AccessLogFile {
+    pub fn open(config: &AccessLogConfig) -> io::Result<Self> {
+        Ok(Self(Mutex::new(RotatingFile::open(config)?)))
+    }
+
+    pub fn write_line(&self, line: &str) {
+        if let Ok(mut file) = self.0.lock() {
+            // 这里不能再经过日志写入器
+            if let Err(e) = file.write_line(line) {
+                eprintln!("写入访问日志失败: {}", e);
+            }
+        }
+    }
+
+    pub fn flush(&self) {
+        if let Ok(mut file) = self.0.lock() {
+            let _ = file.file.flush();
+        }
+    }
+}
+
+/// 生成 combined 格式的访问日志中间件
+pub fn access_logger() -> Logger {
+    Logger::new(COMBINED_FORMAT)
+        .log_target(ACCESS_LOG_TARGET)
+        .custom_request_replace("client", |req: &ServiceRequest| {
+            req.peer_addr()
+                .map(|addr| addr.ip().to_string())
+                .unwrap_or_else(|| "-".to_string())
+        })
+        .custom_request_replace("clf_time", |_req: &ServiceRequest| {
+            OffsetDateTime::now_utc()
+                .format(CLF_TIME_FORMAT)
+                .unwrap_or_else(|_| "-".to_string())
+        })
+}
@@ -0,0 +1,251 @@
+use crate::api_keys::{ApiKey, Scope};
+use crate::{auth_log, session};
+use crate::errors::ApiError;
+use crate::maintenance::Writable;
+use crate::proxy::PROXY_DIR;
+use crate::scan::ScheduledRescanStatus;
+use crate::tiers::FolderStatus;
+use crate::{AppConfig, Config};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::dev::Payload;
+use base64::Engine;
+use actix_web::{get, post, web, FromRequest, HttpRequest, HttpResponse, ResponseError, Result};
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::future::{ready, Ready};
+use std::path::Path;
+use std::time::Instant;
+
+/// 管理接口鉴权失败的原因
+#[derive(Debug)]
+pub enum AdminAuthError {
+    /// 未配置 `--admin-token`，管理接口处于关闭状态
+    Disabled,
+    /// 缺少或携带了错误的令牌
+    Unauthorized,
+    /// [API 密钥](crate::api_keys)有效，但权限不包括这个请求
+    Forbidden(&'static str),
+}
+
+impl fmt::Display for AdminAuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdminAuthError::Disabled => write!(f, "admin API is disabled"),
+            AdminAuthError::Unauthorized => write!(f, "invalid or missing admin token"),
+            AdminAuthError::Forbidden(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl ResponseError for AdminAuthError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        match self {
+            AdminAuthError::Disabled | AdminAuthError::Forbidden(_) => actix_web::http::StatusCode::FORBIDDEN,
+            AdminAuthError::Unauthorized => actix_web::http::StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut res = ApiError::new(self.status_code(), self.to_string()).error_response();
+        if let AdminAuthError::Unauthorized = self {
+            let headers = res.headers_mut();
+            headers.append(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            headers.append(header::WWW_AUTHENTICATE, HeaderValue::from_static("Basic realm=\"pic_url\""));
+        }
+        res
+    }
+}
+
+/// 提取器：请求必须携带 `Authorization: Bearer <admin-token>`，
+/// 或者使用 Basic 认证（用户名任意，密码为令牌），以兼容 WebDAV 客户端；
+/// 浏览器中也可以使用 `/login` 得到的[会话](crate::session)，脚本和设备可以使用权限受限的
+/// [API 密钥](crate::api_keys)
+pub struct AdminAuth {
+    /// 使用 API 密钥时为该密钥，使用管理令牌或会话时为 `None`
+    pub key: Option<ApiKey>,
+}
+
+impl AdminAuth {
+    /// 是否拥有全部管理权限：管理令牌、会话，或不限目录的 `full` 密钥
+    pub fn is_full(&self) -> bool {
+        self.key.as_ref().is_none_or(|key| key.scope == Scope::Full && key.folders.is_empty())
+    }
+}
+
+impl FromRequest for AdminAuth {
+    type Error = AdminAuthError;
+    type Future = Ready<std::result::Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let Some(config) = req.app_data::<web::Data<AppConfig>>() else {
+            return ready(Err(AdminAuthError::Disabled));
+        };
+        if config.settings.admin_token.is_none() {
+            return ready(Err(AdminAuthError::Disabled));
+        }
+
+        match identify(config, req) {
+            Identity::Owner => ready(Ok(AdminAuth { key: None })),
+            Identity::Key(key) => {
+                let result = key.permits(req).map_err(AdminAuthError::Forbidden);
+                ready(result.map(|_| AdminAuth { key: Some(key) }))
+            }
+            Identity::Anonymous => ready(Err(AdminAuthError::Unauthorized)),
+            Identity::Invalid => {
+                // 只记录带了凭据的请求：WebDAV 客户端总是先不带凭据请求一次，收到 401 后再重试
+                auth_log::record(req);
+                ready(Err(AdminAuthError::Unauthorized))
+            }
+        }
+    }
+}
+
+/// 请求携带的凭据
+#[derive(Clone)]
+pub enum Identity {
+    /// 没有凭据，或未配置管理令牌
+    Anonymous,
+    /// 管理令牌，或用它登录的会话
+    Owner,
+    /// API 密钥，或用它登录的会话
+    Key(ApiKey),
+    /// `Authorization` 头中的令牌错误
+    Invalid,
+}
+
+/// 识别请求携带的凭据，不检查权限：`Authorization` 头优先，没有时使用会话 Cookie。
+/// 中间件和提取器都会调用，同一个请求只识别一次
+pub fn identify(config: &AppConfig, req: &HttpRequest) -> Identity {
+    crate::request_cached(req, config, || identify_uncached(config, req))
+}
+
+fn identify_uncached(config: &AppConfig, req: &HttpRequest) -> Identity {
+    if config.settings.admin_token.is_none() {
+        return Identity::Anonymous;
+    }
+    let Some(header) = req.headers().get(header::AUTHORIZATION) else {
+        return session::identify(config, req).unwrap_or(Identity::Anonymous);
+    };
+    identify_authorization(config, header.to_str().ok())
+}
+
+/// 识别 `Authorization` 头（或 gRPC 的 `authorization` metadata）的值，`None` 表示值不是有效的文本
+pub fn identify_authorization(config: &AppConfig, value: Option<&str>) -> Identity {
+    let Some(expected) = config.settings.admin_token.as_deref() else {
+        return Identity::Anonymous;
+    };
+    match value.and_then(token_from_header) {
+        Some(token) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => Identity::Owner,
+        Some(token) => config.api_keys.authenticate(&token).map_or(Identity::Invalid, Identity::Key),
+        None => Identity::Invalid,
+    }
+}
+
+fn token_from_header(value: &str) -> Option<String> {
+    if let Some(token) = value.strip_prefix("Bearer ") {
+        return Some(token.trim().to_string());
+    }
+    let encoded = value.strip_prefix("Basic ")?;
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    decoded.split_once(':').map(|(_, password)| password.to_string())
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[derive(Serialize)]
+struct RescanResponse {
+    count: usize,
+    duration_ms: u128,
+}
+
+#[post("/api/admin/rescan")]
+pub async fn rescan(_auth: AdminAuth, _writable: Writable, config: web::Data<AppConfig>) -> Result<HttpResponse> {
+    let started = Instant::now();
+    let images = web::block(move || config.rescan()).await?;
+
+    Ok(HttpResponse::Ok().json(RescanResponse {
+        count: images.len(),
+        duration_ms: started.elapsed().as_millis(),
+    }))
+}
+
+#[derive(Serialize, Default)]
+struct PurgeResponse {
+    files: u64,
+    bytes: u64,
+}
+
+/// 删除目录下的所有文件和子目录，但保留目录本身
+fn purge_dir(dir: &Path, stats: &mut PurgeResponse) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let meta = entry.metadata()?;
+        if meta.is_dir() {
+            purge_dir(&path, stats)?;
+            fs::remove_dir(&path)?;
+        } else {
+            stats.files += 1;
+            stats.bytes += meta.len();
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+#[post("/api/admin/thumbs/purge")]
+pub async fn purge_thumbnails(
+    _auth: AdminAuth,
+    _writable: Writable,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let stats = web::block(move || {
+        let mut stats = PurgeResponse::default();
+        purge_dir(Path::new(config.thumb_dir.as_str()), &mut stats)?;
+        let proxy_dir = Path::new(config.cache_dir.as_str()).join(PROXY_DIR);
+        if proxy_dir.is_dir() {
+            purge_dir(&proxy_dir, &mut stats)?;
+        }
+        Ok::<_, std::io::Error>(stats)
+    })
+    .await??;
+
+    Ok(HttpResponse::Ok().json(stats))
+}
+
+#[derive(Serialize)]
+struct InfoResponse<'a> {
+    version: &'static str,
+    uptime_secs: u64,
+    thumb_dir: &'a str,
+    thumb_size: u32,
+    image_count: usize,
+    /// 未配置 `--rescan-cron` 时为 `null`
+    scheduled_rescan: Option<ScheduledRescanStatus>,
+    /// 归档目录和预计的读取耗时
+    archive: Vec<FolderStatus>,
+    config: &'a Config,
+}
+
+#[get("/api/admin/info")]
+pub async fn info(_auth: AdminAuth, config: web::Data<AppConfig>) -> HttpResponse {
+    HttpResponse::Ok().json(InfoResponse {
+        version: env!("CARGO_PKG_VERSION"),
+        uptime_secs: config.started_at.elapsed().as_secs(),
+        thumb_dir: config.thumb_dir.as_str(),
+        thumb_size: config.settings.thumb_size,
+        image_count: config.index.len(),
+        scheduled_rescan: config.scheduled_rescan.as_ref().map(|schedule| schedule.status()),
+        archive: config.tiers.status(),
+        config: &config.settings,
+    })
+}
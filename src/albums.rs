@@ -0,0 +1,189 @@
+//! 智能相册：把一组筛选条件（目录、标签、格式、关键字、拍摄日期范围）以名称保存在数据目录的
+//! `albums.json` 中。相册不记录图片，每次访问时按条件筛选，新加入的图片自动出现在相册中
+
+use crate::admin::AdminAuth;
+use crate::homes::Viewer;
+use crate::errors::ApiError;
+use crate::listing::Filter;
+use crate::AppConfig;
+use crate::maintenance::Writable;
+use actix_web::{delete, get, put, web, HttpResponse, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// 相册名称的最大长度（字符数）
+const MAX_NAME_LEN: usize = 100;
+
+pub struct Albums {
+    path: PathBuf,
+    by_name: Mutex<BTreeMap<String, Filter>>,
+}
+
+impl Albums {
+    /// 从 `<data_dir>/albums.json` 加载
+    pub fn load(data_dir: &str) -> Self {
+        let path = Path::new(data_dir).join("albums.json");
+        let by_name = fs::read(&path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            by_name: Mutex::new(by_name),
+        }
+    }
+
+    fn write(&self, by_name: &BTreeMap<String, Filter>, to: &Path) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_vec_pretty(by_name).map_err(io::Error::other)?;
+        fs::write(to, data)
+    }
+
+    fn save(&self, by_name: &BTreeMap<String, Filter>) -> io::Result<()> {
+        let tmp = self.path.with_extension("json.tmp");
+        self.write(by_name, &tmp)?;
+        fs::rename(&tmp, &self.path)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Filter> {
+        self.by_name.lock().ok()?.get(name).cloned()
+    }
+
+    pub fn list(&self) -> Vec<(String, Filter)> {
+        self.by_name
+            .lock()
+            .map(|by_name| by_name.iter().map(|(name, filter)| (name.clone(), filter.clone())).collect())
+            .unwrap_or_default()
+    }
+
+    /// 新建或替换相册
+    pub fn set(&self, name: &str, filter: Filter) -> io::Result<()> {
+        let mut by_name = self.by_name.lock().unwrap_or_else(|e| e.into_inner());
+        by_name.insert(name.to_string(), filter);
+        self.save(&by_name)
+    }
+
+    /// 删除相册，不存在时返回 `false`
+    pub fn remove(&self, name: &str) -> io::Result<bool> {
+        let mut by_name = self.by_name.lock().unwrap_or_else(|e| e.into_inner());
+        if by_name.remove(name).is_none() {
+            return Ok(false);
+        }
+        self.save(&by_name)?;
+        Ok(true)
+    }
+
+    /// 全部相册，用于[备份元数据](crate::metadb)
+    pub fn contents(&self) -> BTreeMap<String, Filter> {
+        self.by_name.lock().map(|by_name| by_name.clone()).unwrap_or_default()
+    }
+
+    /// 恢复备份的第一步：把备份中的相册写到临时文件，全部数据都写好后再 [`commit`](Self::commit)
+    pub fn stage(&self, contents: &BTreeMap<String, Filter>) -> io::Result<PathBuf> {
+        let staged = self.path.with_extension("json.restore");
+        self.write(contents, &staged)?;
+        Ok(staged)
+    }
+
+    /// 换上 [`stage`](Self::stage) 写好的文件，用备份中的内容替换全部相册
+    pub fn commit(&self, staged: &Path, contents: BTreeMap<String, Filter>) -> io::Result<()> {
+        let mut by_name = self.by_name.lock().unwrap_or_else(|e| e.into_inner());
+        fs::rename(staged, &self.path)?;
+        *by_name = contents;
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct AlbumInfo {
+    name: String,
+    filter: Filter,
+    /// 当前符合条件的图片数
+    count: usize,
+    /// 第一张图片，用作封面
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cover: Option<String>,
+}
+
+fn album_info(config: &AppConfig, images: &[String], name: String, filter: Filter) -> AlbumInfo {
+    let matcher = filter.matcher();
+    let mut matching = images.iter().filter(|path| matcher.matches(config, path));
+    let cover = matching.next().cloned();
+    let count = cover.as_ref().map_or(0, |_| 1 + matching.count());
+    AlbumInfo {
+        name,
+        filter,
+        count,
+        cover,
+    }
+}
+
+/// 全部相册及其当前的图片数
+#[get("/api/albums")]
+pub async fn list(viewer: Viewer, config: web::Data<AppConfig>) -> Result<HttpResponse> {
+    let config = config.into_inner();
+    let albums = web::block(move || {
+        let images = viewer.filter(config.current_images());
+        config
+            .albums
+            .list()
+            .into_iter()
+            .map(|(name, filter)| album_info(&config, &images, name, filter))
+            .collect::<Vec<_>>()
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(albums))
+}
+
+/// 新建或修改相册，请求体为筛选条件，如 `{"tag": "wedding", "from": "2024-05-01"}`
+#[put("/api/admin/albums/{name}")]
+pub async fn save(
+    _auth: AdminAuth,
+    _writable: Writable,
+    name: web::Path<String>,
+    filter: web::Json<Filter>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let name = name.trim().to_string();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LEN || name.chars().any(char::is_control) {
+        return Err(ApiError::bad_request(format!("album name must be 1 to {} characters", MAX_NAME_LEN)).into());
+    }
+    let filter = filter.into_inner();
+    filter.validate().map_err(ApiError::bad_request)?;
+    if filter.is_empty() {
+        return Err(ApiError::bad_request("an album needs at least one condition").into());
+    }
+    let config = config.into_inner();
+    let info = web::block(move || -> io::Result<AlbumInfo> {
+        config.albums.set(&name, filter.clone())?;
+        Ok(album_info(&config, &config.current_images(), name, filter))
+    })
+    .await??;
+    Ok(HttpResponse::Ok().json(info))
+}
+
+#[delete("/api/admin/albums/{name}")]
+pub async fn remove(
+    _auth: AdminAuth,
+    _writable: Writable,
+    name: web::Path<String>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let name = name.into_inner();
+    let config = config.into_inner();
+    let removed = {
+        let name = name.clone();
+        web::block(move || config.albums.remove(&name)).await??
+    };
+    if removed {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(ApiError::not_found(format!("album not found: {}", name)).into())
+    }
+}
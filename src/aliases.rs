@@ -0,0 +1,245 @@
+//! 目录别名 (`--alias 名称=目录`)：把图片目录之外的目录当作图片目录中的子目录扫描和访问，
+//! 如 `screenshots=/home/me/Pictures/Screenshots`，不需要在图片目录中建立符号链接。
+//!
+//! 相对路径以别名开头时由 [`Aliases::fs_path`] 换成别名指向的目录，
+//! 扫描时每个别名目录单独遍历；同名的真实子目录被别名遮住。每个别名可以有自己的包含和排除规则
+//! (`--alias-include`/`--alias-exclude`)，只有符合规则的图片进入图库
+
+use crate::names;
+use std::path::{Path, PathBuf};
+
+/// 解析 `名称=值`（别名或规则），名称无效或值为空时返回 `None`
+pub fn parse(spec: &str) -> Option<(String, String)> {
+    let (name, value) = spec.split_once('=')?;
+    let name = name.trim().trim_matches('/');
+    let value = value.trim();
+    (!value.is_empty() && crate::is_valid_relative(name)).then(|| (name.to_string(), value.to_string()))
+}
+
+/// 检查别名和规则：格式正确、名称不重复、目录存在，且与图片目录互不包含（否则图片会被扫描两次）
+pub fn check(pic_dir: &str, specs: &[String], include: &[String], exclude: &[String]) -> Result<(), String> {
+    let mut names = Vec::new();
+    let pic_dir = std::fs::canonicalize(pic_dir).ok();
+    for spec in specs {
+        let Some((name, dir)) = parse(spec) else {
+            return Err(format!("invalid alias: {}", spec));
+        };
+        if names.contains(&name) {
+            return Err(format!("duplicate alias: {}", name));
+        }
+        let dir = match std::fs::canonicalize(&dir) {
+            Ok(dir) if dir.is_dir() => dir,
+            _ => return Err(format!("alias directory not found: {}", dir)),
+        };
+        if pic_dir.as_ref().is_some_and(|pic_dir| dir.starts_with(pic_dir) || pic_dir.starts_with(&dir)) {
+            return Err(format!("alias directory must not contain or be inside the picture directory: {}", spec));
+        }
+        names.push(name);
+    }
+    for rule in include.iter().chain(exclude) {
+        match parse(rule) {
+            Some((name, _)) if names.contains(&name) => {}
+            Some((name, _)) => return Err(format!("rule for unknown alias {}: {}", name, rule)),
+            None => return Err(format!("invalid alias rule: {}", rule)),
+        }
+    }
+    Ok(())
+}
+
+/// 路径模式：`*` 匹配一段中的任意字符，`?` 匹配一个字符，`**` 匹配任意多段；
+/// 不含 `/` 的模式只匹配文件名。不区分大小写
+struct Pattern {
+    segments: Vec<String>,
+    name_only: bool,
+}
+
+impl Pattern {
+    fn new(pattern: &str) -> Self {
+        let pattern = pattern.trim_matches('/').to_lowercase();
+        Self {
+            name_only: !pattern.contains('/'),
+            segments: pattern.split('/').map(str::to_string).collect(),
+        }
+    }
+
+    /// `path` 为相对于别名目录的路径
+    fn matches(&self, path: &str) -> bool {
+        let path = path.to_lowercase();
+        let segments: Vec<&str> = path.split('/').collect();
+        if self.name_only {
+            return segments.last().is_some_and(|name| segment_matches(&self.segments[0], name));
+        }
+        let pattern: Vec<&str> = self.segments.iter().map(String::as_str).collect();
+        path_matches(&pattern, &segments)
+    }
+}
+
+fn path_matches(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| path_matches(rest, &path[skip..])),
+        Some((first, rest)) => path
+            .split_first()
+            .is_some_and(|(segment, tail)| segment_matches(first, segment) && path_matches(rest, tail)),
+    }
+}
+
+fn segment_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    // 回溯到最近一个 `*` 的位置重试
+    let (mut p, mut t) = (0, 0);
+    let mut star = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+struct Alias {
+    name: String,
+    dir: PathBuf,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl Alias {
+    /// 别名目录中的图片 `path` 是否符合规则：有包含规则时至少符合一条，且不符合任何排除规则
+    fn includes(&self, path: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| p.matches(path)))
+            && !self.exclude.iter().any(|p| p.matches(path))
+    }
+}
+
+#[derive(Default)]
+pub struct Aliases {
+    /// 长的在前，嵌套的别名按最近的一个计算
+    aliases: Vec<Alias>,
+}
+
+impl Aliases {
+    /// 按配置创建，无效的项在读取配置时已经检查过，这里跳过
+    pub fn new(specs: &[String], include: &[String], exclude: &[String]) -> Self {
+        let rules = |specs: &[String], name: &str| {
+            specs
+                .iter()
+                .filter_map(|spec| parse(spec))
+                .filter(|(alias, _)| alias == name)
+                .map(|(_, pattern)| Pattern::new(&pattern))
+                .collect()
+        };
+        let mut aliases: Vec<Alias> = specs
+            .iter()
+            .filter_map(|spec| parse(spec))
+            .map(|(name, dir)| Alias {
+                include: rules(include, &name),
+                exclude: rules(exclude, &name),
+                dir: PathBuf::from(dir),
+                name,
+            })
+            .collect();
+        aliases.sort_by_key(|alias| std::cmp::Reverse(alias.name.len()));
+        Self { aliases }
+    }
+
+    /// 各个别名指向的目录
+    pub fn dirs(&self) -> impl Iterator<Item = &Path> {
+        self.aliases.iter().map(|alias| alias.dir.as_path())
+    }
+
+    /// 路径所在的别名和别名之后的部分
+    fn alias_of<'a>(&self, relative: &'a str) -> Option<(&Alias, &'a str)> {
+        self.aliases.iter().find_map(|alias| {
+            let rest = relative.strip_prefix(alias.name.as_str())?;
+            match rest.strip_prefix('/') {
+                Some(rest) => Some((alias, rest)),
+                None => rest.is_empty().then_some((alias, rest)),
+            }
+        })
+    }
+
+    /// 路径是否在别名目录中
+    pub fn is_aliased(&self, relative: &str) -> bool {
+        self.alias_of(relative).is_some()
+    }
+
+    /// 图片是否符合所在别名的规则；不在别名目录中时总是 `true`
+    pub fn includes(&self, relative: &str) -> bool {
+        self.alias_of(relative).is_none_or(|(alias, rest)| alias.includes(rest))
+    }
+
+    /// 别名目录中的路径对应的目录和剩余的相对路径
+    fn resolve<'a>(&self, relative: &'a str) -> Option<(PathBuf, &'a str)> {
+        self.alias_of(relative.trim_start_matches('/')).map(|(alias, rest)| (alias.dir.clone(), rest))
+    }
+
+    /// 索引中的相对路径在图片目录 `base` 下对应的文件，别名目录中的路径换成别名指向的目录
+    pub fn fs_path(&self, base: &Path, relative: &str) -> PathBuf {
+        match self.resolve(relative) {
+            Some((dir, rest)) => names::fs_path(dir, rest),
+            None => names::fs_path(base, relative),
+        }
+    }
+
+    /// 遍历各个别名目录，把符合规则的图片加入 `images`；先去掉图片目录中被别名遮住的部分
+    pub fn walk(
+        &self,
+        images: &mut Vec<(String, u64)>,
+        errors: &mut Vec<crate::AppError>,
+        progress: &dyn Fn(usize),
+    ) {
+        images.retain(|(relative, _)| !self.is_aliased(relative));
+        for alias in &self.aliases {
+            let start = images.len();
+            crate::walk_images(&alias.dir, &alias.dir, &alias.name, images, errors, progress);
+            let mut found = images.split_off(start);
+            // 外层别名目录中被内层别名遮住的部分
+            found.retain(|(relative, _)| {
+                self.alias_of(relative).is_some_and(|(owner, rest)| owner.name == alias.name && owner.includes(rest))
+            });
+            images.extend(found);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_aliases_and_rules() {
+        let aliases = Aliases::new(
+            &["shots=/mnt/shots".to_string(), "shots/old=/mnt/old".to_string()],
+            &["shots=*.png".to_string(), "shots=2024/**/*.jpg".to_string()],
+            &["shots=tmp/**".to_string()],
+        );
+        assert_eq!(aliases.resolve("shots/a.png"), Some((PathBuf::from("/mnt/shots"), "a.png")));
+        assert_eq!(aliases.resolve("shots/old/a.png"), Some((PathBuf::from("/mnt/old"), "a.png")));
+        assert_eq!(aliases.resolve("shots"), Some((PathBuf::from("/mnt/shots"), "")));
+        assert_eq!(aliases.resolve("shotsx/a.png"), None);
+        assert_eq!(aliases.fs_path(Path::new("/pic"), "shots/old/a.png"), PathBuf::from("/mnt/old/a.png"));
+        assert_eq!(aliases.fs_path(Path::new("/pic"), "shotsx/a.png"), PathBuf::from("/pic/shotsx/a.png"));
+        assert!(aliases.includes("shots/x/A.PNG"));
+        assert!(aliases.includes("shots/2024/05/01/a.jpg"));
+        assert!(!aliases.includes("shots/2023/a.jpg"));
+        assert!(!aliases.includes("shots/tmp/a.png"));
+        assert!(aliases.includes("shots/old/a.jpg"));
+        assert!(aliases.includes("other/a.jpg"));
+        assert!(segment_matches("img_*.j?g", "img_0001.jpg"));
+        assert!(!segment_matches("img_*.jpg", "img_0001.png"));
+        assert_eq!(parse("../x=/tmp"), None);
+        assert_eq!(parse("shots="), None);
+    }
+}
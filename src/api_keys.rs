@@ -0,0 +1,470 @@
+//! API 密钥：每个脚本或设备使用自己的密钥，而不是共用管理令牌，不再需要时单独吊销。
+//! 密钥形如 `pk_<ID>_<随机串>`，和管理令牌一样放在 `Authorization: Bearer` 或 Basic 认证的密码中。
+//! 数据目录的 `api_keys.json` 只保存密钥的 SHA-256，明文只在创建时返回一次。
+//!
+//! 每个密钥有一个权限范围，也就是它的角色，后一个角色包含前一个的全部权限：
+//!
+//! - `read`：只读请求（GET、HEAD、PROPFIND 等）和打包下载
+//! - `upload`：上传者，只能添加新图片（`POST /api/upload`，或 WebDAV 的 `PUT` 和 `MKCOL`，不能覆盖已有文件）
+//! - `edit`：编辑者，还可以旋转、编辑、恢复历史版本、打标签和管理相册，但不能删除或移动图片
+//! - `full`：管理员，与管理令牌相同，但不能管理 API 密钥
+//!
+//! 各个路由需要的角色见 [`policy`](crate::policy)。
+//! 还可以限定目录 (`folders`)，这样的密钥只能用于上传到这些目录，或通过 WebDAV 访问这些目录。
+//! 带 `user` 的密钥属于该用户，开启[私有目录](crate::homes)时只能看到公共目录和自己的目录
+
+use crate::admin::AdminAuth;
+use crate::dav::{self, DAV_PREFIX};
+use crate::errors::ApiError;
+use crate::maintenance::Writable;
+use crate::{format_time, is_valid_relative, mount_path, AppConfig};
+use actix_web::http::header;
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 密钥的前缀，用来和管理令牌区分
+pub const PREFIX: &str = "pk_";
+/// 名称的最大长度（字符数）
+const MAX_NAME_LEN: usize = 100;
+/// 用户名的最大长度
+const MAX_USER_LEN: usize = 64;
+/// 最近使用时间写入文件的最小间隔，避免每个请求都写一次
+const LAST_USED_SAVE_INTERVAL: u64 = 60;
+
+/// 按权限从小到大排列
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Read,
+    Upload,
+    Edit,
+    Full,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StoredKey {
+    name: String,
+    /// 完整密钥的 SHA-256（十六进制）
+    hash: String,
+    scope: Scope,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    folders: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    user: Option<String>,
+    /// Unix 时间（秒）
+    created: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_used: Option<u64>,
+}
+
+/// 通过验证的密钥，由 [`AdminAuth`] 携带
+#[derive(Clone, Debug)]
+pub struct ApiKey {
+    pub id: String,
+    pub scope: Scope,
+    pub folders: Vec<String>,
+    /// 密钥所属的用户，没有时代表管理员自己的脚本或设备
+    pub user: Option<String>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn hash(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+fn random_hex(bytes: usize) -> io::Result<String> {
+    let mut buf = vec![0u8; bytes];
+    getrandom::fill(&mut buf).map_err(io::Error::other)?;
+    Ok(hex::encode(buf))
+}
+
+pub struct ApiKeys {
+    path: PathBuf,
+    by_id: Mutex<BTreeMap<String, StoredKey>>,
+}
+
+impl ApiKeys {
+    /// 从 `<data_dir>/api_keys.json` 加载
+    pub fn load(data_dir: &str) -> Self {
+        let path = Path::new(data_dir).join("api_keys.json");
+        let by_id = fs::read(&path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            by_id: Mutex::new(by_id),
+        }
+    }
+
+    fn write(&self, by_id: &BTreeMap<String, StoredKey>, to: &Path) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_vec_pretty(by_id).map_err(io::Error::other)?;
+        fs::write(to, data)
+    }
+
+    fn save(&self, by_id: &BTreeMap<String, StoredKey>) -> io::Result<()> {
+        let tmp = self.path.with_extension("json.tmp");
+        self.write(by_id, &tmp)?;
+        fs::rename(&tmp, &self.path)
+    }
+
+    /// 验证请求携带的密钥并记录使用时间；不是 API 密钥、不存在或不匹配时返回 `None`
+    pub fn authenticate(self: &Arc<Self>, key: &str) -> Option<ApiKey> {
+        let (id, _) = key.strip_prefix(PREFIX)?.split_once('_')?;
+        let mut by_id = self.by_id.lock().ok()?;
+        let stored = by_id.get_mut(id)?;
+        if !crate::admin::constant_time_eq(hash(key).as_bytes(), stored.hash.as_bytes()) {
+            return None;
+        }
+        let now = now_secs();
+        let save = stored.last_used.is_none_or(|last| now >= last + LAST_USED_SAVE_INTERVAL);
+        stored.last_used = Some(now);
+        let key = stored.grant(id);
+        if save {
+            let keys = self.clone();
+            let snapshot = by_id.clone();
+            actix_web::rt::task::spawn_blocking(move || {
+                if let Err(e) = keys.save(&snapshot) {
+                    log::warn!("保存 API 密钥的使用时间失败: {}", e);
+                }
+            });
+        }
+        Some(key)
+    }
+
+    /// 按 ID 查找仍然有效的密钥，供[会话](crate::session)使用
+    pub fn get(&self, id: &str) -> Option<ApiKey> {
+        self.by_id.lock().ok()?.get(id).map(|stored| stored.grant(id))
+    }
+
+    fn list(&self) -> Vec<KeyInfo> {
+        self.by_id
+            .lock()
+            .map(|by_id| by_id.iter().map(|(id, stored)| KeyInfo::new(id, stored)).collect())
+            .unwrap_or_default()
+    }
+
+    /// 新建密钥，返回密钥信息和明文密钥
+    fn create(&self, new: NewKey) -> io::Result<(KeyInfo, String)> {
+        let mut by_id = self.by_id.lock().unwrap_or_else(|e| e.into_inner());
+        let id = loop {
+            let id = random_hex(4)?;
+            if !by_id.contains_key(&id) {
+                break id;
+            }
+        };
+        let key = format!("{}{}_{}", PREFIX, id, random_hex(24)?);
+        let stored = StoredKey {
+            name: new.name,
+            hash: hash(&key),
+            scope: new.scope,
+            folders: new.folders,
+            user: new.user,
+            created: now_secs(),
+            last_used: None,
+        };
+        let info = KeyInfo::new(&id, &stored);
+        by_id.insert(id, stored);
+        self.save(&by_id)?;
+        Ok((info, key))
+    }
+
+    /// 修改名称、权限或目录，不存在时返回 `None`
+    fn update(&self, id: &str, change: KeyChange) -> io::Result<Option<KeyInfo>> {
+        let mut by_id = self.by_id.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(stored) = by_id.get_mut(id) else {
+            return Ok(None);
+        };
+        if let Some(name) = change.name {
+            stored.name = name;
+        }
+        if let Some(scope) = change.scope {
+            stored.scope = scope;
+        }
+        if let Some(folders) = change.folders {
+            stored.folders = folders;
+        }
+        if let Some(user) = change.user {
+            stored.user = (!user.is_empty()).then_some(user);
+        }
+        let info = KeyInfo::new(id, stored);
+        self.save(&by_id)?;
+        Ok(Some(info))
+    }
+
+    /// 吊销密钥，不存在时返回 `false`
+    fn revoke(&self, id: &str) -> io::Result<bool> {
+        let mut by_id = self.by_id.lock().unwrap_or_else(|e| e.into_inner());
+        if by_id.remove(id).is_none() {
+            return Ok(false);
+        }
+        self.save(&by_id)?;
+        Ok(true)
+    }
+
+    /// 全部密钥（只有哈希），用于[备份元数据](crate::metadb)
+    pub fn contents(&self) -> BTreeMap<String, StoredKey> {
+        self.by_id.lock().map(|by_id| by_id.clone()).unwrap_or_default()
+    }
+
+    /// 恢复备份的第一步：把备份中的密钥写到临时文件，全部数据都写好后再 [`commit`](Self::commit)
+    pub fn stage(&self, contents: &BTreeMap<String, StoredKey>) -> io::Result<PathBuf> {
+        let staged = self.path.with_extension("json.restore");
+        self.write(contents, &staged)?;
+        Ok(staged)
+    }
+
+    /// 换上 [`stage`](Self::stage) 写好的文件，用备份中的内容替换全部密钥，不在备份中的密钥随之吊销
+    pub fn commit(&self, staged: &Path, contents: BTreeMap<String, StoredKey>) -> io::Result<()> {
+        let mut by_id = self.by_id.lock().unwrap_or_else(|e| e.into_inner());
+        fs::rename(staged, &self.path)?;
+        *by_id = contents;
+        Ok(())
+    }
+}
+
+impl StoredKey {
+    fn grant(&self, id: &str) -> ApiKey {
+        ApiKey {
+            id: id.to_string(),
+            scope: self.scope,
+            folders: self.folders.clone(),
+            user: self.user.clone(),
+        }
+    }
+}
+
+/// 路径是否位于允许的目录之一中
+fn within(path: &str, folders: &[String]) -> bool {
+    folders
+        .iter()
+        .any(|folder| path == folder || path.strip_prefix(folder.as_str()).is_some_and(|rest| rest.starts_with('/')))
+}
+
+/// WebDAV 请求对应的相对路径，含有 `.` 或 `..` 时返回 `None`
+pub fn dav_relative(mount: &str, uri: &str) -> Option<String> {
+    dav::relative_path(mount, uri).filter(|path| path.split('/').all(|segment| segment != "." && segment != ".."))
+}
+
+impl ApiKey {
+    /// 限定了目录的密钥能否用于这个请求，角色由 [`policy::enforce`](crate::policy::enforce) 检查
+    pub fn permits(&self, req: &HttpRequest) -> std::result::Result<(), &'static str> {
+        if self.folders.is_empty() {
+            return Ok(());
+        }
+        let mount = mount_path(req);
+        let path = req.path().strip_prefix(mount.as_str()).unwrap_or(req.path());
+        let is_dav = path == DAV_PREFIX || path.starts_with(&format!("{}/", DAV_PREFIX));
+        let allowed = if path == "/api/upload" {
+            let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).ok();
+            let folder = query.and_then(|q| q.get("folder").cloned()).unwrap_or_default();
+            within(folder.trim_matches('/'), &self.folders)
+        } else if is_dav {
+            let destination = req.headers().get(header::HeaderName::from_static("destination"));
+            let destination_allowed = destination.is_none_or(|value| {
+                value
+                    .to_str()
+                    .ok()
+                    .and_then(|uri| dav_relative(&mount, uri))
+                    .is_some_and(|target| within(&target, &self.folders))
+            });
+            let target = dav_relative(&mount, req.path());
+            target.is_some_and(|target| within(&target, &self.folders)) && destination_allowed
+        } else {
+            false
+        };
+        if allowed {
+            Ok(())
+        } else {
+            Err("this API key is limited to other folders")
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct KeyInfo {
+    id: String,
+    name: String,
+    scope: Scope,
+    folders: Vec<String>,
+    user: Option<String>,
+    created_at: String,
+    last_used_at: Option<String>,
+}
+
+impl KeyInfo {
+    fn new(id: &str, stored: &StoredKey) -> Self {
+        let time = |secs: u64| format_time(UNIX_EPOCH + Duration::from_secs(secs));
+        Self {
+            id: id.to_string(),
+            name: stored.name.clone(),
+            scope: stored.scope,
+            folders: stored.folders.clone(),
+            user: stored.user.clone(),
+            created_at: time(stored.created),
+            last_used_at: stored.last_used.map(time),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct CreatedKey {
+    #[serde(flatten)]
+    info: KeyInfo,
+    /// 明文密钥，只返回这一次
+    key: String,
+}
+
+fn validate_name(name: &str) -> std::result::Result<String, ApiError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LEN || name.chars().any(char::is_control) {
+        return Err(ApiError::bad_request(format!("key name must be 1 to {} characters", MAX_NAME_LEN)));
+    }
+    Ok(name.to_string())
+}
+
+/// 用户名同时是私有目录名，只允许字母、数字和 `-`、`_`、`.`
+fn validate_user(user: &str) -> std::result::Result<(), ApiError> {
+    let valid = (1..=MAX_USER_LEN).contains(&user.len())
+        && user.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && !user.starts_with('.');
+    if !valid {
+        return Err(ApiError::bad_request(format!(
+            "user must be 1 to {} letters, digits, '-', '_' or '.' and not start with '.'",
+            MAX_USER_LEN
+        )));
+    }
+    Ok(())
+}
+
+fn validate_folders(folders: Vec<String>) -> std::result::Result<Vec<String>, ApiError> {
+    let mut folders = folders
+        .into_iter()
+        .map(|folder| folder.trim_matches('/').to_string())
+        .collect::<Vec<_>>();
+    if let Some(folder) = folders.iter().find(|folder| !is_valid_relative(folder)) {
+        return Err(ApiError::bad_request(format!("invalid folder: {}", folder)));
+    }
+    folders.sort();
+    folders.dedup();
+    Ok(folders)
+}
+
+/// 管理 API 密钥只能使用管理令牌或登录会话
+fn require_owner(auth: &AdminAuth) -> std::result::Result<(), ApiError> {
+    match auth.key {
+        Some(_) => Err(ApiError::new(
+            actix_web::http::StatusCode::FORBIDDEN,
+            "API keys cannot manage API keys",
+        )),
+        None => Ok(()),
+    }
+}
+
+#[get("/api/admin/keys")]
+pub async fn list(auth: AdminAuth, config: web::Data<AppConfig>) -> Result<HttpResponse> {
+    require_owner(&auth)?;
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(config.api_keys.list()))
+}
+
+#[derive(Deserialize)]
+pub struct NewKey {
+    name: String,
+    scope: Scope,
+    #[serde(default)]
+    folders: Vec<String>,
+    user: Option<String>,
+}
+
+/// 新建密钥，如 `{"name": "phone", "scope": "upload", "folders": ["camera"]}`
+#[post("/api/admin/keys")]
+pub async fn create(
+    auth: AdminAuth,
+    _writable: Writable,
+    body: web::Json<NewKey>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    require_owner(&auth)?;
+    let mut new = body.into_inner();
+    new.name = validate_name(&new.name)?;
+    new.folders = validate_folders(new.folders)?;
+    if let Some(user) = &new.user {
+        validate_user(user)?;
+    }
+    let (info, key) = web::block(move || config.api_keys.create(new)).await??;
+    Ok(HttpResponse::Created()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(CreatedKey { info, key }))
+}
+
+#[derive(Deserialize)]
+pub struct KeyChange {
+    name: Option<String>,
+    scope: Option<Scope>,
+    folders: Option<Vec<String>>,
+    user: Option<String>,
+}
+
+/// 修改密钥的名称、权限、目录或用户，只需要包含要修改的字段；`"folders": []` 取消目录限制，
+/// `"user": ""` 取消所属的用户
+#[put("/api/admin/keys/{id}")]
+pub async fn update(
+    auth: AdminAuth,
+    _writable: Writable,
+    id: web::Path<String>,
+    body: web::Json<KeyChange>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    require_owner(&auth)?;
+    let mut change = body.into_inner();
+    change.name = change.name.as_deref().map(validate_name).transpose()?;
+    change.folders = change.folders.map(validate_folders).transpose()?;
+    if let Some(user) = change.user.as_deref().filter(|user| !user.is_empty()) {
+        validate_user(user)?;
+    }
+    let id = id.into_inner();
+    let updated = {
+        let id = id.clone();
+        web::block(move || config.api_keys.update(&id, change)).await??
+    };
+    match updated {
+        Some(info) => Ok(HttpResponse::Ok().json(info)),
+        None => Err(ApiError::not_found(format!("API key not found: {}", id)).into()),
+    }
+}
+
+/// 吊销密钥，之后使用它的请求都返回 401
+#[delete("/api/admin/keys/{id}")]
+pub async fn revoke(
+    auth: AdminAuth,
+    _writable: Writable,
+    id: web::Path<String>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    require_owner(&auth)?;
+    let id = id.into_inner();
+    let removed = {
+        let id = id.clone();
+        web::block(move || config.api_keys.revoke(&id)).await??
+    };
+    if removed {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(ApiError::not_found(format!("API key not found: {}", id)).into())
+    }
+}
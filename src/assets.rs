@@ -0,0 +1,76 @@
+//! 页面的 CSS/JS 和图标：编译时嵌入程序，由 `/static/` 提供。模板中引用的地址带内容哈希，
+//! 内容不变时浏览器一直使用缓存，升级后地址随之变化
+
+use actix_web::http::header;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use rust_embed::RustEmbed;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+#[derive(RustEmbed)]
+#[folder = "static/"]
+struct Assets;
+
+#[derive(Deserialize)]
+pub struct AssetQuery {
+    /// 内容哈希，与当前内容一致时允许长期缓存
+    v: Option<String>,
+}
+
+/// 内容哈希（SHA-256 的前 12 位十六进制）
+fn version(file: &rust_embed::EmbeddedFile) -> String {
+    hex::encode(&file.metadata.sha256_hash()[..6])
+}
+
+/// 文件名到带哈希地址的映射，供模板中 `assets["gallery.css"]` 使用
+pub fn urls() -> &'static HashMap<String, String> {
+    static URLS: OnceLock<HashMap<String, String>> = OnceLock::new();
+    URLS.get_or_init(|| {
+        Assets::iter()
+            .filter_map(|name| {
+                let file = Assets::get(&name)?;
+                Some((name.to_string(), format!("/static/{}?v={}", name, version(&file))))
+            })
+            .collect()
+    })
+}
+
+#[get("/static/{name:.*}")]
+pub async fn asset(name: web::Path<String>, query: web::Query<AssetQuery>, req: HttpRequest) -> HttpResponse {
+    let Some(file) = Assets::get(&name) else {
+        return HttpResponse::NotFound().body("Not found");
+    };
+    let version = version(&file);
+    let etag = format!("\"{}\"", version);
+    // 带旧哈希或不带哈希的请求需要每次验证，避免升级后一直使用旧文件
+    let cache_control = if query.v.as_deref() == Some(version.as_str()) {
+        "public, max-age=31536000, immutable"
+    } else {
+        "no-cache"
+    };
+
+    let not_modified = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|tag| tag.trim() == etag));
+    if not_modified {
+        return HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .insert_header((header::CACHE_CONTROL, cache_control))
+            .finish();
+    }
+
+    let mime = mime_guess::from_path(name.as_str()).first_or_octet_stream();
+    let content_type = if mime.type_() == mime_guess::mime::TEXT || mime.subtype() == mime_guess::mime::JAVASCRIPT {
+        format!("{}; charset=utf-8", mime)
+    } else {
+        mime.to_string()
+    };
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header((header::ETAG, etag))
+        .insert_header((header::CACHE_CONTROL, cache_control))
+        .body(file.data.into_owned())
+}
@@ -0,0 +1,58 @@
+//! 认证失败日志 (`--auth-log`)：携带了错误管理令牌的请求按固定格式追加到单独的文件，
+//! 供 fail2ban 等工具封禁暴力尝试的 IP。每行一条：
+//!
+//! ```text
+//! 2026-10-16T09:01:30Z pic_url auth failure: ip=192.168.1.10 method=POST route=/api/admin/rescan
+//! ```
+//!
+//! 时间为 UTC，`route` 为请求的路径（不含查询参数）。格式是对外的约定，修改时需要同步更新 README 中的过滤规则。
+//! 每次写入都重新打开文件，logrotate 移走文件后不需要通知服务
+
+use crate::AppConfig;
+use actix_web::{web, HttpRequest};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use time::format_description::FormatItem;
+use time::OffsetDateTime;
+
+const TIME_FORMAT: &[FormatItem<'static>] =
+    time::macros::format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]Z");
+
+/// 启动时检查日志文件可以写入，必要时创建所在目录
+pub fn check(path: &str) -> io::Result<()> {
+    if let Some(parent) = Path::new(path).parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    OpenOptions::new().create(true).append(true).open(path).map(|_| ())
+}
+
+fn line(ip: &str, method: &str, route: &str) -> String {
+    let timestamp = OffsetDateTime::now_utc()
+        .format(TIME_FORMAT)
+        .unwrap_or_else(|_| "-".to_string());
+    format!("{} pic_url auth failure: ip={} method={} route={}", timestamp, ip, method, route)
+}
+
+/// 记录一次认证失败，未配置 `--auth-log` 时不做任何事
+pub fn record(req: &HttpRequest) {
+    let Some(path) = req
+        .app_data::<web::Data<AppConfig>>()
+        .and_then(|config| config.settings.auth_log.clone())
+    else {
+        return;
+    };
+    let ip = req.peer_addr().map(|addr| addr.ip().to_string()).unwrap_or_else(|| "-".to_string());
+    let line = line(&ip, req.method().as_str(), req.path());
+    let written = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(format!("{}\n", line).as_bytes()));
+    if let Err(e) = written {
+        log::warn!("写入认证失败日志 {} 失败: {}", path, e);
+    }
+}
+
@@ -0,0 +1,290 @@
+//! 定时备份：把图片和 `.pic_url` 数据目录打包成 `.tar.gz` 写到备份目录，按数量保留
+
+use crate::admin::AdminAuth;
+use crate::aliases::Aliases;
+use crate::errors::ApiError;
+use crate::maintenance::Maintenance;
+use crate::maintenance::Writable;
+use crate::{cas, collect_library, format_time, names, AppConfig};
+use actix_web::http::StatusCode;
+use actix_web::{get, post, web, HttpResponse, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use time::OffsetDateTime;
+
+const ARCHIVE_PREFIX: &str = "pic_url-";
+const ARCHIVE_SUFFIX: &str = ".tar.gz";
+
+#[derive(Serialize, Clone, Default)]
+pub struct BackupReport {
+    started_at: String,
+    finished_at: Option<String>,
+    success: bool,
+    archive: Option<String>,
+    files: u64,
+    bytes: u64,
+    error: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct BackupStatus {
+    enabled: bool,
+    dir: String,
+    interval_secs: u64,
+    keep: usize,
+    running: bool,
+    last_run: Option<BackupReport>,
+    last_success_at: Option<String>,
+    next_run_at: Option<String>,
+    archives: Vec<String>,
+}
+
+pub struct Backup {
+    dir: PathBuf,
+    interval: Duration,
+    keep: usize,
+    /// 防止定时任务和手动触发同时执行
+    lock: Mutex<()>,
+    status: Mutex<BackupStatus>,
+}
+
+impl Backup {
+    pub fn new(dir: &str, interval: Duration, keep: usize) -> Self {
+        Self {
+            dir: PathBuf::from(dir),
+            interval,
+            keep,
+            lock: Mutex::new(()),
+            status: Mutex::new(BackupStatus {
+                enabled: true,
+                dir: dir.to_string(),
+                interval_secs: interval.as_secs(),
+                keep,
+                running: false,
+                last_run: None,
+                last_success_at: None,
+                next_run_at: None,
+                archives: Vec::new(),
+            }),
+        }
+    }
+
+    pub fn status(&self) -> BackupStatus {
+        let mut current = self
+            .status
+            .lock()
+            .map(|s| s.clone())
+            .unwrap_or_else(|e| e.into_inner().clone());
+        current.archives = self.archives();
+        current
+    }
+
+    fn update(&self, f: impl FnOnce(&mut BackupStatus)) {
+        if let Ok(mut guard) = self.status.lock() {
+            f(&mut guard);
+        }
+    }
+
+    /// 备份目录中已有的归档文件名，按时间从旧到新排列
+    fn archives(&self) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(&self.dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.file_name().to_string_lossy().to_string())
+                    .filter(|name| name.starts_with(ARCHIVE_PREFIX) && name.ends_with(ARCHIVE_SUFFIX))
+                    .collect()
+            })
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+
+    /// 执行一次备份，阻塞直到完成；已有备份在进行时返回 `None`。别名目录中的图片也打包在 `pic/` 下
+    pub fn run_once(&self, pic_dir: &str, aliases: &Aliases, data_dir: &str) -> Option<BackupReport> {
+        let _guard = self.lock.try_lock().ok()?;
+        let mut report = BackupReport {
+            started_at: format_time(SystemTime::now()),
+            ..Default::default()
+        };
+        self.update(|s| {
+            s.running = true;
+            s.next_run_at = None;
+        });
+
+        let result = self.write_archive(pic_dir, aliases, data_dir, &mut report);
+        report.success = result.is_ok();
+        report.error = result.err().map(|e| e.to_string());
+        report.finished_at = Some(format_time(SystemTime::now()));
+        if report.success {
+            self.prune();
+        }
+
+        let next = format_time(SystemTime::now() + self.interval);
+        self.update(|s| {
+            s.running = false;
+            if report.success {
+                s.last_success_at = report.finished_at.clone();
+            }
+            s.last_run = Some(report.clone());
+            s.next_run_at = Some(next);
+        });
+        Some(report)
+    }
+
+    fn write_archive(
+        &self,
+        pic_dir: &str,
+        aliases: &Aliases,
+        data_dir: &str,
+        report: &mut BackupReport,
+    ) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let stamp = OffsetDateTime::now_utc();
+        let name = format!(
+            "{}{:04}{:02}{:02}-{:02}{:02}{:02}{}",
+            ARCHIVE_PREFIX,
+            stamp.year(),
+            stamp.month() as u8,
+            stamp.day(),
+            stamp.hour(),
+            stamp.minute(),
+            stamp.second(),
+            ARCHIVE_SUFFIX
+        );
+        let path = self.dir.join(&name);
+        // 先写临时文件，完成后再改名，避免留下不完整的归档
+        let partial = self.dir.join(format!(".{}.partial", name));
+
+        let result = (|| {
+            let file = BufWriter::new(File::create(&partial)?);
+            let mut tar = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+            tar.follow_symlinks(false);
+
+            let base = Path::new(pic_dir);
+            let mut images = Vec::new();
+            // 有目录读不了时备份失败，而不是生成一个缺少图片的归档
+            collect_library(base, aliases, &mut images).map_err(io::Error::other)?;
+            for relative in images {
+                tar.append_path_with_name(aliases.fs_path(base, &relative), names::fs_path("pic", &relative))?;
+                report.files += 1;
+            }
+
+            let data = Path::new(data_dir);
+            if data.is_dir() {
+                // 去重存储的内容文件与上面的图片是同一份数据，不重复打包
+                for entry in fs::read_dir(data)? {
+                    let entry = entry?;
+                    if entry.file_name() == cas::BLOBS_DIR {
+                        continue;
+                    }
+                    let name = Path::new(crate::DATA_DIR_NAME).join(entry.file_name());
+                    if entry.file_type()?.is_dir() {
+                        tar.append_dir_all(name, entry.path())?;
+                    } else {
+                        tar.append_path_with_name(entry.path(), name)?;
+                    }
+                }
+            }
+
+            tar.into_inner()?.finish()?.into_inner().map_err(|e| e.into_error())?.sync_all()
+        })();
+        if let Err(e) = result {
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+
+        fs::rename(&partial, &path)?;
+        report.bytes = fs::metadata(&path)?.len();
+        report.archive = Some(name);
+        Ok(())
+    }
+
+    /// 只保留最近 `keep` 个归档
+    fn prune(&self) {
+        let archives = self.archives();
+        if archives.len() <= self.keep {
+            return;
+        }
+        for name in &archives[..archives.len() - self.keep] {
+            if let Err(e) = fs::remove_file(self.dir.join(name)) {
+                log::warn!("删除旧备份 {} 失败: {}", name, e);
+            }
+        }
+    }
+
+    /// 距离下一次备份的时间：没有备份时立即执行，否则从最近一个归档的时间开始计算，
+    /// 避免频繁重启时不断产生新的备份把旧备份挤掉
+    fn first_delay(&self) -> Duration {
+        let latest = self.archives().pop().and_then(|name| {
+            fs::metadata(self.dir.join(name)).and_then(|m| m.modified()).ok()
+        });
+        match latest.and_then(|t| t.elapsed().ok()) {
+            Some(age) => self.interval.saturating_sub(age),
+            None => Duration::ZERO,
+        }
+    }
+}
+
+/// 启动后台定时备份任务；维护模式下跳过，避免备份到整理了一半的目录
+pub fn spawn(
+    backup: Arc<Backup>,
+    pic_dir: String,
+    aliases: Arc<Aliases>,
+    data_dir: String,
+    maintenance: Arc<Maintenance>,
+) {
+    let delay = backup.first_delay();
+    let interval = backup.interval;
+    backup.update(|s| s.next_run_at = Some(format_time(SystemTime::now() + delay)));
+    crate::scheduler::every("备份", delay, interval, move || {
+        if maintenance.is_enabled() {
+            log::info!("维护模式中，跳过本次备份");
+            return;
+        }
+        match backup.run_once(&pic_dir, &aliases, &data_dir) {
+            Some(report) if report.success => log::info!(
+                "备份完成: {} ({} 个文件)",
+                report.archive.unwrap_or_default(),
+                report.files
+            ),
+            Some(report) => log::error!("备份失败: {}", report.error.unwrap_or_default()),
+            None => {}
+        }
+    });
+}
+
+#[get("/api/admin/backup")]
+pub async fn status(_auth: AdminAuth, config: web::Data<AppConfig>) -> HttpResponse {
+    match &config.backup {
+        Some(backup) => HttpResponse::Ok().json(backup.status()),
+        None => HttpResponse::Ok().json(serde_json::json!({ "enabled": false })),
+    }
+}
+
+#[post("/api/admin/backup")]
+pub async fn run(_auth: AdminAuth, _writable: Writable, config: web::Data<AppConfig>) -> Result<HttpResponse> {
+    let backup = match &config.backup {
+        Some(backup) => backup.clone(),
+        None => return Err(ApiError::not_found("backup is disabled").into()),
+    };
+    let pic_dir = config.pic_dir.clone();
+    let data_dir = config.data_dir.clone();
+    let aliases = config.aliases.clone();
+    let report = web::block(move || backup.run_once(&pic_dir, &aliases, &data_dir)).await?;
+    match report {
+        Some(report) if report.success => Ok(HttpResponse::Ok().json(report)),
+        // 完整的备份结果放在 `error.details` 中
+        Some(report) => {
+            let message = report.error.clone().unwrap_or_else(|| "backup failed".to_string());
+            Err(ApiError::internal(message).with_details(&report).into())
+        }
+        None => Err(ApiError::new(StatusCode::CONFLICT, "backup already running").into()),
+    }
+}
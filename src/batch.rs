@@ -0,0 +1,543 @@
+//! 批量操作：画廊选择模式下一次处理多张图片
+//!
+//! - `POST /api/zip`（或 `GET`，参数放在查询字符串中）：把选中的图片打包成 ZIP 边读边发，
+//!   不在内存或磁盘上生成完整文件（开启原图签名时需管理令牌）；响应带有长度，支持 `Range` 断点续传
+//! - `POST /api/admin/batch`：批量删除、移动、添加/移除标签（需管理令牌；API 密钥打标签需要 `edit`，
+//!   删除和移动需要 `full`）
+
+use crate::admin::AdminAuth;
+use crate::api_keys::Scope;
+use crate::homes::Viewer;
+use crate::errors::ApiError;
+use crate::maintenance::Writable;
+use crate::{is_valid_relative, limit, policy, range, sidecars, tags, thumbs, versions, AppConfig};
+use actix_web::body::SizedStream;
+use actix_web::http::{header, StatusCode};
+use actix_web::{post, web, FromRequest, HttpRequest, HttpResponse, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::convert::Infallible;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, DateTime, ZipWriter};
+
+/// 一次最多处理的图片数
+const MAX_BATCH: usize = 10_000;
+/// 每次发送给客户端的块大小
+const CHUNK_SIZE: usize = 64 * 1024;
+/// 打包表单的大小上限，默认的 16KB 只够几百个路径
+const ZIP_FORM_LIMIT: usize = 4 * 1024 * 1024;
+
+/// 注册批量操作的路由
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/api/zip")
+            .app_data(web::FormConfig::default().limit(ZIP_FORM_LIMIT))
+            .route(web::post().to(zip_form))
+            .route(web::get().to(zip_query)),
+    )
+    .service(batch);
+}
+
+/// 把写入的数据按块发送到响应流，客户端断开后写入失败，打包随之停止。
+/// 只发送 `skip` 之后的 `remaining` 个字节，用于 `Range` 请求
+struct ChannelWriter {
+    sender: mpsc::Sender<web::Bytes>,
+    buffer: Vec<u8>,
+    skip: u64,
+    remaining: u64,
+    /// 已经发完或客户端已断开，之后的写入（如 `ZipWriter` 释放时补写的目录）直接丢弃
+    closed: bool,
+}
+
+impl ChannelWriter {
+    fn send(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = web::Bytes::from(std::mem::replace(&mut self.buffer, Vec::with_capacity(CHUNK_SIZE)));
+        self.sender.blocking_send(chunk).map_err(|_| {
+            self.closed = true;
+            io::Error::new(io::ErrorKind::BrokenPipe, "client disconnected")
+        })
+    }
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.closed {
+            return Ok(data.len());
+        }
+        let skipped = self.skip.min(data.len() as u64) as usize;
+        self.skip -= skipped as u64;
+        let wanted = &data[skipped..];
+        let taken = self.remaining.min(wanted.len() as u64) as usize;
+        self.buffer.extend_from_slice(&wanted[..taken]);
+        self.remaining -= taken as u64;
+        if self.buffer.len() >= CHUNK_SIZE || self.remaining == 0 {
+            self.send()?;
+        }
+        if self.remaining == 0 {
+            // 请求的部分已经发完，不必再读后面的图片
+            self.closed = true;
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "response complete"));
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send()
+    }
+}
+
+/// 只统计写入的字节数
+#[derive(Default)]
+struct CountingWriter(u64);
+
+impl Write for CountingWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.0 += data.len() as u64;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// ZIP 中记录的修改时间
+fn zip_time(modified: Option<SystemTime>) -> Option<DateTime> {
+    let t = time::OffsetDateTime::from(modified?);
+    DateTime::from_date_and_time(
+        t.year().try_into().ok()?,
+        t.month().into(),
+        t.day(),
+        t.hour(),
+        t.minute(),
+        t.second(),
+    )
+    .ok()
+}
+
+/// 打包的一张图片。大小和修改时间在开始发送前确定，同样的图片总是生成同样的 ZIP，
+/// 因此可以事先给出长度，续传时重新生成并跳过已经发送的部分
+struct ZipEntry {
+    relative: String,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+/// 要打包的图片，读不到大小的跳过
+fn zip_entries(config: &AppConfig, paths: &[String]) -> Vec<ZipEntry> {
+    paths
+        .iter()
+        .filter_map(|relative| {
+            let (size, modified) = match &config.webdav {
+                Some(source) => (source.size(relative)?, source.modified(relative)),
+                None => {
+                    let meta = fs::metadata(config.fs_path(relative)).ok()?;
+                    (meta.len(), meta.modified().ok())
+                }
+            };
+            Some(ZipEntry {
+                relative: relative.clone(),
+                size,
+                modified,
+            })
+        })
+        .collect()
+}
+
+/// 这组图片对应的 ZIP 的 ETag，图片改变后续传请求的 `If-Range` 不再匹配
+fn zip_etag(entries: &[ZipEntry]) -> String {
+    let mut hasher = Sha256::new();
+    for entry in entries {
+        let modified = entry.modified.and_then(|t| t.duration_since(UNIX_EPOCH).ok()).unwrap_or_default();
+        hasher.update(format!("{}\0{}\0{}\0", entry.relative, entry.size, modified.as_nanos()).as_bytes());
+    }
+    format!("\"zip-{}\"", hex::encode(&hasher.finalize()[..16]))
+}
+
+fn write_zip<W: Write>(
+    entries: &[ZipEntry],
+    writer: W,
+    copy: impl Fn(&ZipEntry, &mut dyn Write) -> io::Result<()>,
+) -> zip::result::ZipResult<W> {
+    let mut zip = ZipWriter::new_stream(writer);
+    for entry in entries {
+        // 图片本身已经压缩过，直接存储即可
+        let mut options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .large_file(entry.size >= u32::MAX as u64);
+        if let Some(time) = zip_time(entry.modified) {
+            options = options.last_modified_time(time);
+        }
+        zip.start_file(entry.relative.as_str(), options)?;
+        copy(entry, &mut zip)?;
+    }
+    let mut writer = zip.finish()?.into_inner();
+    writer.flush()?;
+    Ok(writer)
+}
+
+/// ZIP 的长度：用同样长度的空数据代替图片生成一遍，图片内容不影响 ZIP 的结构
+fn zip_length(entries: &[ZipEntry]) -> zip::result::ZipResult<u64> {
+    let counted = write_zip(entries, CountingWriter::default(), |entry, out| {
+        io::copy(&mut io::repeat(0).take(entry.size), out).map(drop)
+    })?;
+    Ok(counted.0)
+}
+
+/// 写入图片内容；与确定长度时的大小不同（打包过程中被修改）时出错，避免发出与长度不符的 ZIP
+fn copy_image(config: &AppConfig, entry: &ZipEntry, out: &mut dyn Write) -> io::Result<()> {
+    let copied = match &config.webdav {
+        Some(source) => {
+            let data = source.fetch(&entry.relative)?;
+            out.write_all(&data)?;
+            data.len() as u64
+        }
+        None => {
+            let file = File::open(config.fs_path(&entry.relative))?;
+            if file.metadata()?.len() != entry.size {
+                0
+            } else {
+                io::copy(&mut file.take(entry.size), out)?
+            }
+        }
+    };
+    if copied != entry.size {
+        return Err(io::Error::other(format!("{} changed while packing", entry.relative)));
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct ZipForm {
+    /// 换行分隔的图片路径；用表单提交或链接是为了让浏览器直接下载，而不是先读进内存
+    paths: String,
+    /// 下载的文件名（不含 `.zip`）
+    name: Option<String>,
+}
+
+async fn zip_form(
+    req: HttpRequest,
+    form: web::Form<ZipForm>,
+    viewer: Viewer,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    download_zip(req, form.into_inner(), viewer, config).await
+}
+
+/// 路径放在查询参数中，浏览器和下载工具可以像普通文件一样暂停和续传
+async fn zip_query(
+    req: HttpRequest,
+    query: web::Query<ZipForm>,
+    viewer: Viewer,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    download_zip(req, query.into_inner(), viewer, config).await
+}
+
+async fn download_zip(
+    req: HttpRequest,
+    form: ZipForm,
+    viewer: Viewer,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    // 原图需要签名时打包下载会绕过签名，只对管理员开放
+    if config.settings.originals_secret.is_some() {
+        AdminAuth::extract(&req).await?;
+    }
+    let mut paths: Vec<String> = form
+        .paths
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|path| !path.is_empty())
+        .collect();
+    paths.sort();
+    paths.dedup();
+    if paths.is_empty() || paths.len() > MAX_BATCH {
+        return Err(ApiError::bad_request(format!("select between 1 and {} images", MAX_BATCH)).into());
+    }
+    let missing = {
+        let config = config.clone();
+        let paths = paths.clone();
+        web::block(move || {
+            let visible = |path: &String| is_valid_relative(path) && viewer.can_see(path) && config.has_image(path);
+            paths.into_iter().find(|path| !visible(path))
+        })
+        .await?
+    };
+    if let Some(path) = missing {
+        return Err(ApiError::not_found(format!("Image not found: {}", path)).into());
+    }
+    let Some(permit) = config.limiter.try_acquire() else {
+        return Ok(limit::busy());
+    };
+
+    let name: String = form
+        .name
+        .as_deref()
+        .unwrap_or("images")
+        .chars()
+        .filter(|c| !c.is_control() && !matches!(c, '"' | '/' | '\\'))
+        .collect();
+    let name = if name.trim().is_empty() { "images".to_string() } else { name };
+    let config = config.into_inner();
+    let (entries, total) = {
+        let config = config.clone();
+        web::block(move || {
+            let entries = zip_entries(&config, &paths);
+            zip_length(&entries).map(|total| (entries, total))
+        })
+        .await?
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    };
+    let etag = zip_etag(&entries);
+    let (skip, length) = match range::requested(&req, total, Some(&etag)) {
+        range::Requested::Full => (0, total),
+        range::Requested::Partial { start, length } => (start, length),
+        range::Requested::Unsatisfiable => return Ok(range::unsatisfiable(total)),
+    };
+
+    let (sender, receiver) = mpsc::channel(4);
+    tokio::task::spawn_blocking(move || {
+        let _permit = permit;
+        let writer = ChannelWriter {
+            sender,
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+            skip,
+            remaining: length,
+            closed: false,
+        };
+        match write_zip(&entries, writer, |entry, out| copy_image(&config, entry, out)) {
+            Ok(_) => {}
+            // 客户端断开，或请求的部分已经发完
+            Err(zip::result::ZipError::Io(e)) if e.kind() == io::ErrorKind::BrokenPipe => {}
+            Err(e) => log::warn!("打包 ZIP 失败: {}", e),
+        }
+    });
+
+    let body = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        let chunk = receiver.recv().await?;
+        Some((Ok::<_, Infallible>(chunk), receiver))
+    });
+    let mut response = if length == total {
+        HttpResponse::Ok()
+    } else {
+        let mut response = HttpResponse::PartialContent();
+        response.insert_header((header::CONTENT_RANGE, range::content_range(skip, length, total)));
+        response
+    };
+    Ok(response
+        .content_type("application/zip")
+        .insert_header(("Content-Disposition", crate::attachment(&format!("{}.zip", name))))
+        .insert_header((header::ACCEPT_RANGES, "bytes"))
+        .insert_header((header::ETAG, etag))
+        .body(SizedStream::new(length, body)))
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum BatchAction {
+    Delete { paths: Vec<String> },
+    /// 移动到 `to` 目录，空字符串表示根目录
+    Move { paths: Vec<String>, to: String },
+    Tag { paths: Vec<String>, tags: Vec<String> },
+    Untag { paths: Vec<String>, tags: Vec<String> },
+}
+
+#[derive(Serialize)]
+pub(crate) struct BatchFailure {
+    pub(crate) path: String,
+    pub(crate) error: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct BatchResponse {
+    pub(crate) ok: usize,
+    pub(crate) failed: Vec<BatchFailure>,
+    /// 随图片一起删除或移动的附属文件数，见 [`sidecars`]
+    pub(crate) sidecars: usize,
+}
+
+/// 删除图片、它的缩略图和附属文件，返回删除的附属文件数；附属文件删除失败只记录日志
+fn delete_image(config: &AppConfig, finder: &mut sidecars::Finder, relative: &str) -> io::Result<usize> {
+    let sidecars = finder.find(relative);
+    fs::remove_file(config.fs_path(relative))?;
+    finder.forget(relative);
+    thumbs::remove(&config.thumb_dir, relative);
+    let _ = config.tags.forget(relative);
+    versions::forget(config, relative);
+    let mut removed = 0;
+    for sidecar in sidecars {
+        match fs::remove_file(config.fs_path(&sidecar)) {
+            Ok(()) => removed += 1,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("删除附属文件 {} 失败: {}", sidecar, e),
+        }
+        finder.forget(&sidecar);
+    }
+    Ok(removed)
+}
+
+/// 把图片和它的附属文件移动到 `folder` 目录下，返回新路径和移动的附属文件数；缩略图一起移动，避免重新生成。
+/// 目标目录中已有同名的图片或附属文件时不移动
+fn move_image(
+    config: &AppConfig,
+    finder: &mut sidecars::Finder,
+    relative: &str,
+    folder: &str,
+) -> io::Result<(String, usize)> {
+    let target_of = |relative: &str| {
+        let name = relative.rsplit('/').next().unwrap_or(relative);
+        if folder.is_empty() { name.to_string() } else { format!("{}/{}", folder, name) }
+    };
+    let target = target_of(relative);
+    if target == relative {
+        return Ok((target, 0));
+    }
+    let sidecars = finder.find(relative);
+    let sidecar_targets: Vec<String> = sidecars.iter().map(|sidecar| target_of(sidecar)).collect();
+    if let Some(taken) = std::iter::once(&target)
+        .chain(&sidecar_targets)
+        .find(|path| config.fs_path(path).exists())
+    {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", taken)));
+    }
+    let to = config.fs_path(&target);
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(config.fs_path(relative), &to)?;
+    finder.forget(relative);
+    thumbs::rename(&config.thumb_dir, relative, &target);
+    let _ = config.tags.rename(relative, &target);
+    versions::rename(config, relative, &target);
+    let mut moved = 0;
+    for (sidecar, to) in sidecars.iter().zip(&sidecar_targets) {
+        match fs::rename(config.fs_path(sidecar), config.fs_path(to)) {
+            Ok(()) => moved += 1,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("移动附属文件 {} 失败: {}", sidecar, e),
+        }
+        finder.forget(sidecar);
+    }
+    Ok((target, moved))
+}
+
+/// 执行批量操作，需要在阻塞线程中调用；请求本身有问题时返回错误说明
+pub(crate) fn run_batch(
+    config: &AppConfig,
+    viewer: &Viewer,
+    action: BatchAction,
+) -> std::result::Result<BatchResponse, String> {
+    let paths = match &action {
+        BatchAction::Delete { paths }
+        | BatchAction::Move { paths, .. }
+        | BatchAction::Tag { paths, .. }
+        | BatchAction::Untag { paths, .. } => paths,
+    };
+    if paths.is_empty() || paths.len() > MAX_BATCH {
+        return Err(format!("select between 1 and {} images", MAX_BATCH));
+    }
+    let mut response = BatchResponse {
+        ok: 0,
+        failed: Vec::new(),
+        sidecars: 0,
+    };
+    let pic_dir = Path::new(config.pic_dir.as_str());
+    let mut finder = sidecars::Finder::new(pic_dir, &config.aliases, config.settings.sidecars);
+    let mut valid = Vec::new();
+    for path in paths {
+        if is_valid_relative(path) && viewer.can_see(path) && config.has_image(path) {
+            valid.push(path.clone());
+        } else {
+            response.failed.push(BatchFailure {
+                path: path.clone(),
+                error: "image not found".to_string(),
+            });
+        }
+    }
+
+    match &action {
+        BatchAction::Tag { tags, .. } | BatchAction::Untag { tags, .. } => {
+            let tags: Vec<String> = tags.iter().filter_map(|tag| tags::normalize(tag)).collect();
+            if tags.is_empty() {
+                return Err("tags must not be empty".to_string());
+            }
+            let result = match action {
+                BatchAction::Tag { .. } => config.tags.add(&valid, &tags),
+                _ => config.tags.remove(&valid, &tags),
+            };
+            result.map_err(|e| e.to_string())?;
+            response.ok = valid.len();
+            // 标签不影响文件，不需要重新扫描
+            return Ok(response);
+        }
+        BatchAction::Delete { .. } | BatchAction::Move { .. } if config.webdav.is_some() => {
+            return Err("delete and move are not supported for a WebDAV source".to_string());
+        }
+        BatchAction::Move { to, .. } => {
+            let folder = to.trim_matches('/');
+            if !folder.is_empty() && !is_valid_relative(folder) {
+                return Err(format!("invalid folder: {}", to));
+            }
+            if !viewer.can_see(folder) {
+                return Err(format!("folder belongs to another user: {}", to));
+            }
+            for path in valid {
+                match move_image(config, &mut finder, &path, folder) {
+                    Ok((_, sidecars)) => {
+                        response.ok += 1;
+                        response.sidecars += sidecars;
+                    }
+                    Err(e) => response.failed.push(BatchFailure {
+                        path,
+                        error: e.to_string(),
+                    }),
+                }
+            }
+        }
+        BatchAction::Delete { .. } => {
+            for path in valid {
+                match delete_image(config, &mut finder, &path) {
+                    Ok(sidecars) => {
+                        response.ok += 1;
+                        response.sidecars += sidecars;
+                    }
+                    Err(e) => response.failed.push(BatchFailure {
+                        path,
+                        error: e.to_string(),
+                    }),
+                }
+            }
+        }
+    }
+    // 重新扫描，让画廊、Webhook 和命令钩子收到增删事件；移动的图片按指纹沿用原来的短 ID
+    config.rescan();
+    Ok(response)
+}
+
+#[post("/api/admin/batch")]
+async fn batch(
+    auth: AdminAuth,
+    _writable: Writable,
+    action: web::Json<BatchAction>,
+    viewer: Viewer,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let action = action.into_inner();
+    if matches!(action, BatchAction::Delete { .. } | BatchAction::Move { .. }) {
+        policy::require(&auth, Scope::Full)?;
+    }
+    let result = web::block(move || run_batch(&config, &viewer, action)).await?;
+    match result {
+        Ok(response) => Ok(HttpResponse::Ok().json(response)),
+        Err(message) => Err(ApiError::bad_request(message).into()),
+    }
+}
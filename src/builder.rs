@@ -0,0 +1,318 @@
+//! 把画廊嵌入到其他 actix-web 程序中：用 [`PicUrl::builder`] 在代码中配置，
+//! 得到的 [`PicUrl`] 作为服务挂在应用的任意路径下
+
+use crate::{
+    errors, folding, homes, missing, payload, policy, request_id, thumbs, versioning, AppConfig, Config, PathMatch,
+    SidecarPolicy, ThumbCheck,
+};
+use actix_web::dev::{AppService, HttpServiceFactory};
+use actix_web::middleware::{self, ErrorHandlers};
+use actix_web::{guard, web, HttpResponse};
+use std::io;
+use std::sync::Arc;
+
+/// 可以嵌入其他程序的画廊服务：
+///
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use actix_web::{App, HttpServer};
+/// use pic_url::PicUrl;
+///
+/// let gallery = PicUrl::builder()
+///     .pic_dir("/srv/photos")
+///     .mount_path("/photos")
+///     .thumb_size(300)
+///     .auth("secret-token")
+///     .build()?;
+/// HttpServer::new(move || App::new().service(gallery.clone()))
+///     .bind(("127.0.0.1", 8080))?
+///     .run()
+///     .await
+/// # }
+/// ```
+///
+/// 克隆的实例共用同一份索引和缓存，每个 worker 注册一份即可
+#[derive(Clone)]
+pub struct PicUrl {
+    config: web::Data<AppConfig>,
+    /// 图片目录的文件监听，所有实例释放后停止
+    _watcher: Option<Arc<notify::RecommendedWatcher>>,
+}
+
+impl PicUrl {
+    pub fn builder() -> PicUrlBuilder {
+        PicUrlBuilder::default()
+    }
+
+    /// 画廊的共享状态，可以用来主动触发扫描或读取图片列表
+    pub fn config(&self) -> &AppConfig {
+        &self.config
+    }
+}
+
+impl HttpServiceFactory for PicUrl {
+    fn register(self, config: &mut AppService) {
+        let mount = self.config.settings.mount_path.clone();
+        let mut scope = web::scope(&mount)
+            .app_data(self.config.clone())
+            .app_data(payload::json(&self.config))
+            .app_data(payload::form(&self.config))
+            .wrap(middleware::from_fn(homes::guard))
+            .wrap(middleware::from_fn(folding::canonicalize))
+            .wrap(middleware::from_fn(policy::enforce))
+            .wrap(ErrorHandlers::new().default_handler(errors::handle))
+            .wrap(middleware::from_fn(versioning::middleware))
+            .wrap(middleware::from_fn(request_id::middleware));
+        if let Some((first, rest)) = self.config.settings.hosts.split_first() {
+            let hosts = rest.iter().fold(guard::Any(guard::Host(first)), |any, host| any.or(guard::Host(host)));
+            scope = scope.guard(hosts);
+        }
+        // 不带结尾斜杠的挂载路径跳转到画廊首页
+        if !mount.is_empty() {
+            let home = format!("{}/", mount);
+            scope = scope.service(web::resource("").to(move || {
+                let home = home.clone();
+                async move { HttpResponse::Found().insert_header(("Location", home)).finish() }
+            }));
+        }
+        scope.configure(crate::configure).register(config);
+    }
+}
+
+/// [`PicUrl`] 的配置，未设置的项与命令行启动时的默认值相同
+#[derive(Default)]
+pub struct PicUrlBuilder {
+    settings: Config,
+}
+
+impl PicUrlBuilder {
+    /// 以完整的配置开始，用于 [`galleries`](crate::galleries)
+    pub(crate) fn with_settings(settings: Config) -> Self {
+        Self { settings }
+    }
+
+    /// 图片目录，默认 `./pic`
+    pub fn pic_dir(mut self, dir: impl Into<String>) -> Self {
+        self.settings.pic_dir = dir.into();
+        self
+    }
+
+    /// 挂载的路径前缀，如 `/photos`；页面、脚本和分享链接中的地址都带上这个前缀
+    pub fn mount_path(mut self, path: impl Into<String>) -> Self {
+        self.settings.mount_path = path.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// 只响应 `Host` 为这个域名的请求（不含端口），可以多次调用；不调用时不限制。
+    /// 同一个应用中可以注册几个挂在同一路径下、域名不同的画廊
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.settings.hosts.push(host.into().to_ascii_lowercase());
+        self
+    }
+
+    /// 缩略图长边的像素数，16 到 2048，默认 200
+    pub fn thumb_size(mut self, size: u32) -> Self {
+        self.settings.thumb_size = size;
+        self
+    }
+
+    /// 常用缩略图的内存缓存容量 (MB)，默认 0 即不缓存；请求量很大时开启，省去每次读文件
+    pub fn thumb_cache_mb(mut self, mb: u64) -> Self {
+        self.settings.thumb_cache_mb = mb;
+        self
+    }
+
+    /// 原图不存在时缩略图返回的占位图：`placeholder`（默认，内置）、`off`（返回文本）或图片文件的路径
+    pub fn missing_thumb(mut self, value: impl Into<String>) -> Self {
+        self.settings.missing_thumb = value.into();
+        self
+    }
+
+    /// 判断缩略图是否过期的方式，默认 [`ThumbCheck::Mtime`]
+    pub fn thumb_check(mut self, check: ThumbCheck) -> Self {
+        self.settings.thumb_check = check;
+        self
+    }
+
+    /// 管理令牌，设置后启用 `/api/admin/*`、上传和编辑
+    pub fn auth(mut self, token: impl Into<String>) -> Self {
+        self.settings.admin_token = Some(token.into());
+        self
+    }
+
+    /// 认证失败日志文件，携带错误管理令牌的请求按固定格式追加到该文件，供 fail2ban 使用
+    pub fn auth_log(mut self, path: impl Into<String>) -> Self {
+        self.settings.auth_log = Some(path.into());
+        self
+    }
+
+    /// 浏览器登录会话的有效期 (秒)，默认 7 天
+    pub fn session_lifetime(mut self, secs: u64) -> Self {
+        self.settings.session_lifetime = secs.max(1);
+        self
+    }
+
+    /// 开启用户私有目录：`users/<用户名>/` 只有属于该用户的 API 密钥和管理员可以访问
+    pub fn user_homes(mut self, enabled: bool) -> Self {
+        self.settings.user_homes = enabled;
+        self
+    }
+
+    /// 上传前把文件交给 clamd 扫描，发现威胁时拒绝；地址为 Unix 套接字路径或 `host:port`
+    pub fn clamd(mut self, address: impl Into<String>) -> Self {
+        self.settings.clamd = Some(address.into());
+        self
+    }
+
+    /// 上传前用外部命令扫描，参数为文件路径，退出码 0 为通过、1 为发现威胁，其他视为扫描失败
+    pub fn scan_command(mut self, command: impl Into<String>) -> Self {
+        self.settings.scan_command = Some(command.into());
+        self
+    }
+
+    /// 单个上传文件的上限 (MB)，默认 100；只影响上传，其他接口的请求体由 [`max_body_kb`](Self::max_body_kb) 限制
+    pub fn max_upload_mb(mut self, mb: u64) -> Self {
+        self.settings.max_upload_mb = mb.max(1);
+        self
+    }
+
+    /// 接口 JSON 和表单请求体的上限 (KB)，默认 1024
+    pub fn max_body_kb(mut self, kb: u64) -> Self {
+        self.settings.max_body_kb = kb.max(1);
+        self
+    }
+
+    /// 按内容去重存储，重复的图片是同一份内容的硬链接，见 `pic_url storage migrate`；只支持 Unix
+    pub fn content_addressed(mut self, enabled: bool) -> Self {
+        self.settings.cas = enabled;
+        self
+    }
+
+    /// 通过接口删除或移动图片时如何处理同目录下的 XMP、JSON、RAW 等附属文件，默认 [`SidecarPolicy::Metadata`]
+    pub fn sidecars(mut self, policy: SidecarPolicy) -> Self {
+        self.settings.sidecars = policy;
+        self
+    }
+
+    /// 把目录标记为归档（放在慢速存储上）：缩略图总是预生成，已有缩略图时不访问原图。
+    /// `spec` 为 `目录` 或 `目录=毫秒`，毫秒数是还没有读取过原图时显示的预计等待时间；可以多次调用
+    pub fn archive(mut self, spec: impl Into<String>) -> Self {
+        self.settings.archive.push(spec.into());
+        self
+    }
+
+    /// 目录别名：把图片目录之外的 `dir` 作为子目录 `name` 扫描和访问，同名的真实子目录被遮住；可以多次调用
+    pub fn alias(mut self, name: &str, dir: impl AsRef<str>) -> Self {
+        self.settings.aliases.push(format!("{}={}", name, dir.as_ref()));
+        self
+    }
+
+    /// 别名 `name` 的目录中只收录符合 `pattern` 的图片（`*`、`?`、`**`，不含 `/` 时只匹配文件名）；可以多次调用
+    pub fn alias_include(mut self, name: &str, pattern: &str) -> Self {
+        self.settings.alias_include.push(format!("{}={}", name, pattern));
+        self
+    }
+
+    /// 别名 `name` 的目录中不收录符合 `pattern` 的图片，模式同 [`alias_include`](Self::alias_include)
+    pub fn alias_exclude(mut self, name: &str, pattern: &str) -> Self {
+        self.settings.alias_exclude.push(format!("{}={}", name, pattern));
+        self
+    }
+
+    /// 按路径访问时如何匹配文件名，默认 [`PathMatch::Exact`]；`Unicode` 时 NFC 和 NFD 形式不同的路径、
+    /// `Nocase` 时再加上大小写不同的路径也能访问到图片
+    pub fn path_match(mut self, path_match: PathMatch) -> Self {
+        self.settings.path_match = path_match;
+        self
+    }
+
+    /// 导出目录，设置后可以用 `POST /api/export` 把选中的图片导出为其中的子目录或 ZIP；不能位于图片目录中
+    pub fn export_dir(mut self, dir: impl Into<String>) -> Self {
+        self.settings.export_dir = Some(dir.into());
+        self
+    }
+
+    /// 原图签名密钥，设置后 `/pic` 和 `/i` 必须带未过期的签名，页面和分享链接中的原图地址自动签名
+    pub fn originals_secret(mut self, secret: impl Into<String>) -> Self {
+        self.settings.originals_secret = Some(secret.into());
+        self
+    }
+
+    /// 页面和分享链接中原图签名的有效期 (秒)，默认 3600
+    pub fn originals_ttl(mut self, secs: u64) -> Self {
+        self.settings.originals_ttl = secs.max(1);
+        self
+    }
+
+    /// 对外访问地址（含挂载路径），用于生成分享链接；不设置时按请求的地址和挂载路径生成
+    pub fn base_url(mut self, url: impl Into<String>) -> Self {
+        self.settings.base_url = Some(url.into().trim_end_matches('/').to_string());
+        self
+    }
+
+    /// 是否在后台预生成缩略图，默认开启
+    pub fn prewarm(mut self, prewarm: bool) -> Self {
+        self.settings.prewarm = prewarm;
+        self
+    }
+
+    /// 自定义页面模板目录，其中的同名文件替换内置模板
+    pub fn templates_dir(mut self, dir: impl Into<String>) -> Self {
+        self.settings.templates_dir = Some(dir.into());
+        self
+    }
+
+    /// 检查配置，创建目录并在后台开始扫描。需要在 actix 运行时中调用
+    pub fn build(self) -> io::Result<PicUrl> {
+        let settings = self.settings;
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+        if !thumbs::is_valid_size(settings.thumb_size) {
+            return Err(invalid(format!("thumb_size must be between 16 and 2048: {}", settings.thumb_size)));
+        }
+        if !missing::is_valid(&settings.missing_thumb) {
+            return Err(invalid(format!("invalid missing_thumb: {}", settings.missing_thumb)));
+        }
+        if settings.cas && !crate::cas::SUPPORTED {
+            return Err(invalid("content_addressed storage requires hard links (Unix)".to_string()));
+        }
+        if let Some(spec) = settings.archive.iter().find(|spec| crate::tiers::parse(spec).is_none()) {
+            return Err(invalid(format!("invalid archive folder: {}", spec)));
+        }
+        crate::aliases::check(&settings.pic_dir, &settings.aliases, &settings.alias_include, &settings.alias_exclude)
+            .map_err(invalid)?;
+        if settings.auth_log.is_some() && settings.admin_token.is_none() {
+            return Err(invalid("auth_log requires auth".to_string()));
+        }
+        if settings.user_homes && settings.admin_token.is_none() {
+            return Err(invalid("user_homes requires auth".to_string()));
+        }
+        if let Some(command) = &settings.scan_command {
+            if settings.clamd.is_some() {
+                return Err(invalid("clamd and scan_command cannot be used together".to_string()));
+            }
+            if crate::hooks::split_command(command).is_none() {
+                return Err(invalid(format!("invalid scan_command: {}", command)));
+            }
+        }
+        if let Some(host) = settings.hosts.iter().find(|host| !crate::galleries::is_valid_host(host)) {
+            return Err(invalid(format!("invalid host: {}", host)));
+        }
+        // 挂载路径会原样写进页面和脚本，只允许不需要转义的字符
+        let mount = &settings.mount_path;
+        let valid_mount = mount.is_empty()
+            || (mount.starts_with('/')
+                && mount[1..].split('/').all(|segment| !matches!(segment, "" | "." | ".."))
+                && mount.chars().all(|c| c.is_ascii_alphanumeric() || "/-_.~".contains(c)));
+        if !valid_mount {
+            return Err(invalid(format!("invalid mount path: {}", mount)));
+        }
+
+        let config = AppConfig::load(settings)?;
+        crate::prepare(&config)?;
+        let watcher = crate::watch_changes(&config);
+        Ok(PicUrl {
+            config: web::Data::new(config),
+            _watcher: watcher.map(Arc::new),
+        })
+    }
+}
@@ -0,0 +1,355 @@
+//! 按内容寻址的去重存储 (`--storage cas`)：每份内容按 SHA-256 在数据目录的 `blobs` 下存一次，
+//! 图片目录中的文件是指向它的硬链接，`storage.json` 记录路径到内容的对应关系。重复上传的图片
+//! 不占用额外的空间，改名和移动只修改目录项，其余按路径读写文件的功能不需要改变。
+//! 内容文件设为只读，应用自身的写入都是写临时文件后改名，不会改到共享同一内容的其他图片。
+//! 依赖硬链接和链接计数，只支持 Unix 和本地图片目录
+
+use crate::optimize::format_bytes;
+use crate::{collect_images, names, DATA_DIR_NAME};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// 内容文件在数据目录中的位置
+pub const BLOBS_DIR: &str = "blobs";
+const INDEX_FILE: &str = "storage.json";
+
+/// 当前平台是否支持
+pub const SUPPORTED: bool = cfg!(unix);
+
+/// 收入一个文件的结果
+#[derive(Debug, PartialEq, Eq)]
+pub enum Ingested {
+    /// 新的内容
+    Stored,
+    /// 内容已存在，换成了硬链接，节省了这么多字节
+    Duplicate(u64),
+    /// 已经是内容文件的硬链接
+    Unchanged,
+}
+
+/// 一次同步或迁移的统计
+#[derive(Debug, Default)]
+pub struct Report {
+    pub stored: usize,
+    pub duplicates: usize,
+    pub saved_bytes: u64,
+    /// 不再被任何图片引用而删除的内容
+    pub removed: usize,
+    pub failed: usize,
+}
+
+pub struct Store {
+    pic_dir: PathBuf,
+    blobs: PathBuf,
+    index_path: PathBuf,
+    /// 相对路径 → 内容的 SHA-256
+    index: Mutex<BTreeMap<String, String>>,
+    /// 同一时间只有一个同步
+    syncing: Mutex<()>,
+}
+
+impl Store {
+    pub fn new(pic_dir: &str, data_dir: &str) -> Self {
+        let index_path = Path::new(data_dir).join(INDEX_FILE);
+        let index = fs::read(&index_path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        Self {
+            pic_dir: PathBuf::from(pic_dir),
+            blobs: Path::new(data_dir).join(BLOBS_DIR),
+            index_path,
+            index: Mutex::new(index),
+            syncing: Mutex::new(()),
+        }
+    }
+
+    fn save(&self, index: &BTreeMap<String, String>) -> io::Result<()> {
+        if let Some(parent) = self.index_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_vec(index).map_err(io::Error::other)?;
+        let tmp = self.index_path.with_extension("json.tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &self.index_path)
+    }
+
+    fn blob_path(&self, hash: &str) -> PathBuf {
+        self.blobs.join(&hash[..2]).join(&hash[2..])
+    }
+
+    /// 索引中记录的内容哈希；只在路径仍是指向该内容文件的硬链接时返回，不读取文件
+    pub fn hash_of(&self, relative: &str) -> Option<String> {
+        let hash = self.index.lock().ok()?.get(relative)?.clone();
+        let file = fs::metadata(names::fs_path(&self.pic_dir, relative)).ok()?;
+        let blob = fs::metadata(self.blob_path(&hash)).ok()?;
+        same_file(&file, &blob).then_some(hash)
+    }
+
+    /// 计算内容的哈希：内容已存在时把路径换成指向它的硬链接，否则把文件本身链接为新的内容
+    fn store_file(&self, relative: &str) -> io::Result<(String, Ingested)> {
+        let path = names::fs_path(&self.pic_dir, relative);
+        let before = fs::metadata(&path)?;
+        let hash = hash_file(&path)?;
+        // 计算期间文件被修改时放弃，下次扫描再收入
+        let unchanged = |path: &Path| -> io::Result<()> {
+            let now = fs::metadata(path)?;
+            if same_file(&before, &now) && now.len() == before.len() && now.modified()? == before.modified()? {
+                Ok(())
+            } else {
+                Err(io::Error::other(format!("{} 在收入时被修改", relative)))
+            }
+        };
+        let blob = self.blob_path(&hash);
+        let outcome = match fs::metadata(&blob) {
+            Ok(existing) if same_file(&existing, &before) => Ingested::Unchanged,
+            Ok(existing) => {
+                // 先链接到同目录的临时文件再改名覆盖，替换过程中路径一直有完整的文件
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let tmp = path.with_file_name(format!(".{}.cas.tmp", name));
+                let _ = fs::remove_file(&tmp);
+                fs::hard_link(&blob, &tmp)?;
+                let replaced = unchanged(&path).and_then(|()| fs::rename(&tmp, &path));
+                if replaced.is_err() {
+                    let _ = fs::remove_file(&tmp);
+                }
+                replaced?;
+                Ingested::Duplicate(existing.len())
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                if let Some(parent) = blob.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::hard_link(&path, &blob)?;
+                if let Err(e) = unchanged(&blob) {
+                    let _ = fs::remove_file(&blob);
+                    return Err(e);
+                }
+                Ingested::Stored
+            }
+            Err(e) => return Err(e),
+        };
+        set_writable(&blob, false)?;
+        Ok((hash, outcome))
+    }
+
+    /// 扫描之后调用：收入新增和被替换的图片，重建路径索引，删除不再被引用的内容
+    pub fn sync(&self, images: &[String]) -> Report {
+        let mut report = Report::default();
+        if !SUPPORTED {
+            return report;
+        }
+        let _syncing = self.syncing.lock().unwrap_or_else(|e| e.into_inner());
+        let known = self.blob_ids();
+        let mut index = BTreeMap::new();
+        for relative in images {
+            let Ok(meta) = fs::metadata(names::fs_path(&self.pic_dir, relative)) else {
+                continue;
+            };
+            if let Some(hash) = file_id(&meta).and_then(|id| known.get(&id)) {
+                index.insert(relative.clone(), hash.clone());
+                continue;
+            }
+            match self.store_file(relative) {
+                Ok((hash, outcome)) => {
+                    match outcome {
+                        Ingested::Stored => report.stored += 1,
+                        Ingested::Duplicate(bytes) => {
+                            report.duplicates += 1;
+                            report.saved_bytes += bytes;
+                        }
+                        Ingested::Unchanged => {}
+                    }
+                    index.insert(relative.clone(), hash);
+                }
+                Err(e) => {
+                    log::warn!("收入 {} 失败: {}", relative, e);
+                    report.failed += 1;
+                }
+            }
+        }
+        report.removed = self.collect_garbage();
+        let mut current = self.index.lock().unwrap_or_else(|e| e.into_inner());
+        *current = index;
+        if let Err(e) = self.save(&current) {
+            log::warn!("保存存储索引失败: {}", e);
+        }
+        report
+    }
+
+    /// 全部内容文件，(设备, inode) → 哈希
+    fn blob_ids(&self) -> HashMap<(u64, u64), String> {
+        let mut ids = HashMap::new();
+        self.each_blob(|hash, meta, _| {
+            if let Some(id) = file_id(meta) {
+                ids.insert(id, hash);
+            }
+        });
+        ids
+    }
+
+    /// 删除链接数为 1（只剩内容文件本身）的内容，返回删除的个数
+    fn collect_garbage(&self) -> usize {
+        let mut removed = 0;
+        self.each_blob(|hash, meta, path| {
+            if links(meta) <= 1 {
+                match fs::remove_file(path) {
+                    Ok(()) => removed += 1,
+                    Err(e) => log::warn!("删除内容 {} 失败: {}", hash, e),
+                }
+            }
+        });
+        removed
+    }
+
+    fn each_blob(&self, mut f: impl FnMut(String, &Metadata, &Path)) {
+        let Ok(prefixes) = fs::read_dir(&self.blobs) else {
+            return;
+        };
+        for prefix in prefixes.flatten() {
+            let Ok(entries) = fs::read_dir(prefix.path()) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                // 不跟随符号链接，取内容文件本身的元数据
+                if let Ok(meta) = fs::symlink_metadata(&path) {
+                    if meta.is_file() {
+                        let hash = format!(
+                            "{}{}",
+                            prefix.file_name().to_string_lossy(),
+                            entry.file_name().to_string_lossy()
+                        );
+                        f(hash, &meta, &path);
+                    }
+                }
+            }
+        }
+    }
+
+    /// 原地写入之前调用（WebDAV 覆盖已有的文件）：把路径换成独立的可写副本，
+    /// 写入不会改到内容相同的其他图片。路径不存在时什么也不做
+    pub fn detach(&self, relative: &str) -> io::Result<()> {
+        let path = names::fs_path(&self.pic_dir, relative);
+        match fs::metadata(&path) {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let tmp = path.with_file_name(format!(".{}.cas.tmp", name));
+        let copied = fs::copy(&path, &tmp)
+            .and_then(|_| set_writable(&tmp, true))
+            .and_then(|()| fs::rename(&tmp, &path));
+        if copied.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        copied
+    }
+}
+
+/// 文件内容的 SHA-256（十六进制）
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(unix)]
+fn file_id(meta: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// 两份元数据是否属于同一个文件（互为硬链接）；无法判断时为 `false`
+pub fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    file_id(a).is_some() && file_id(a) == file_id(b)
+}
+
+#[cfg(unix)]
+fn links(meta: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.nlink()
+}
+
+#[cfg(not(unix))]
+fn links(_meta: &Metadata) -> u64 {
+    // 无法判断时当作仍被引用，不删除
+    u64::MAX
+}
+
+/// 只修改所有者的写权限
+#[cfg(unix)]
+fn set_writable(path: &Path, writable: bool) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = fs::metadata(path)?.permissions();
+    let mode = permissions.mode();
+    let updated = if writable { mode | 0o200 } else { mode & !0o222 };
+    if updated != mode {
+        permissions.set_mode(updated);
+        fs::set_permissions(path, permissions)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_writable(_path: &Path, _writable: bool) -> io::Result<()> {
+    Ok(())
+}
+
+/// `pic_url storage migrate [-d 目录]`：把普通目录中的图片收入内容存储，重复的图片换成硬链接后退出。
+/// 之后以 `--storage cas` 启动服务，新上传的图片也会去重
+pub fn run_command(args: &[String]) -> ! {
+    let usage = || -> ! {
+        eprintln!("用法: pic_url storage migrate [-d 图片目录]");
+        std::process::exit(1);
+    };
+    let mut pic_dir = std::env::var("PIC_DIR").unwrap_or_else(|_| "./pic".to_string());
+    if args.first().map(String::as_str) != Some("migrate") {
+        usage();
+    }
+    let mut i = 1;
+    while i < args.len() {
+        match (args[i].as_str(), args.get(i + 1)) {
+            ("-d" | "--dir", Some(value)) => {
+                pic_dir = value.clone();
+                i += 1;
+            }
+            _ => usage(),
+        }
+        i += 1;
+    }
+    if !SUPPORTED {
+        eprintln!("错误: 内容寻址存储依赖硬链接，当前平台不支持");
+        std::process::exit(1);
+    }
+    if !Path::new(&pic_dir).is_dir() {
+        eprintln!("错误: 图片目录 '{}' 不存在", pic_dir);
+        std::process::exit(1);
+    }
+
+    let mut images = Vec::new();
+    if let Err(e) = collect_images(Path::new(&pic_dir), Path::new(&pic_dir), &mut images) {
+        eprintln!("警告: 跳过无法读取的目录 {}", e);
+    }
+    println!("正在收入 {} 张图片...", images.len());
+    let store = Store::new(&pic_dir, &format!("{}/{}", pic_dir, DATA_DIR_NAME));
+    let report = store.sync(&images);
+    println!(
+        "完成: 新内容 {} 个, 重复 {} 个, 节省 {}, 失败 {} 个",
+        report.stored,
+        report.duplicates,
+        format_bytes(report.saved_bytes),
+        report.failed
+    );
+    std::process::exit(if report.failed == 0 { 0 } else { 1 });
+}
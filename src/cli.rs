@@ -0,0 +1,1322 @@
+//! 命令行参数和子命令
+
+use crate::access_log::{AccessLogConfig, RotatePolicy};
+use crate::logging::LogFormat;
+use crate::cron::Cron;
+use crate::limit::Limiter;
+use crate::{
+    cas, dedupe, doctor, galleries, hooks, import, missing, optimize, payload, proxy, session, signed, theme, thumbs,
+    tiers, Config, PathMatch, SidecarPolicy, ThumbCheck, DEFAULT_MAP_TILES, THUMB_SIZE,
+};
+use std::env;
+use std::path::Path;
+
+fn print_usage() {
+    println!("用法: pic_url [选项]");
+    println!();
+    println!("选项:");
+    println!("  -p, --port <端口>      设置服务端口 (默认: 2020)");
+    println!("  -d, --dir <目录>       设置图片目录 (默认: ./pic)");
+    println!("  --base-url <URL>       对外访问地址，用于生成分享链接等绝对地址 (反向代理后使用)");
+    println!("  --webdav-url <URL>     从 WebDAV 服务器读取原图，-d 目录仅用于缓存缩略图");
+    println!("  --webdav-user <用户>   WebDAV 用户名");
+    println!("  --webdav-password <密码>");
+    println!("                         WebDAV 密码 (建议使用环境变量)");
+    println!("  --admin-token <令牌>   启用管理接口 /api/admin/*，并设置访问令牌");
+    println!("  --auth-log <文件>      将携带错误令牌的请求按固定格式写入文件，供 fail2ban 使用");
+    println!("  --session-lifetime <秒>");
+    println!("                         浏览器登录会话的有效期 (默认: 604800，即 7 天)");
+    println!("  --user-homes           users/<用户名>/ 只对该用户和管理员可见 (需管理令牌)");
+    println!("  --dav                  通过 WebDAV 在 /dav/ 暴露图片目录 (需管理令牌)");
+    println!("  --proxy-allow <域名,...>");
+    println!("                         启用 /proxy 远程图片代理，只允许这些域名及其子域名");
+    println!("  --proxy-secret <密钥>  要求 /proxy 请求携带 HMAC 签名");
+    println!("  --originals-secret <密钥>");
+    println!("                         要求 /pic 和 /i 原图请求携带带过期时间的 HMAC 签名");
+    println!("  --originals-ttl <秒>   页面和分享链接中原图签名的有效期 (默认: 3600)");
+    println!("  --replicate-to <目标>  定时把新增/修改的图片同步到 WebDAV 地址或 rsync 目标");
+    println!("  --replicate-interval <秒>");
+    println!("                         同步间隔 (默认: 3600)");
+    println!("  --replicate-user <用户>");
+    println!("                         同步目标 WebDAV 用户名");
+    println!("  --replicate-password <密码>");
+    println!("                         同步目标 WebDAV 密码 (建议使用环境变量)");
+    println!("  --backup-dir <目录>    定时把图片和数据目录打包备份到该目录");
+    println!("  --backup-interval <秒> 备份间隔 (默认: 86400)");
+    println!("  --backup-keep <数量>   保留的备份数量 (默认: 7)");
+    println!("  --export-dir <目录>    导出选中的图片 (POST /api/export) 时写入的目录");
+    println!("  --webhook <URL,...>    图片新增/删除/上传时推送 JSON 到这些地址 (逗号分隔)");
+    println!("  --webhook-secret <密钥>");
+    println!("                         Webhook 签名密钥 (HMAC-SHA256)");
+    println!("  --on-upload <命令>     上传或新增图片后执行，参数为图片路径");
+    println!("  --on-delete <命令>     删除图片后执行，参数为图片路径");
+    println!("  --on-index-complete <命令>");
+    println!("                         扫描完成且图片有变化时执行，参数为图片目录");
+    println!("  --hook-timeout <秒>    钩子命令超时时间 (默认: 30)");
+    println!("  --clamd <地址>         上传前交给 ClamAV 扫描: Unix 套接字路径或 host:port");
+    println!("  --scan-command <命令>  上传前用该命令扫描，参数为文件路径，退出码 1 表示发现威胁");
+    println!("  --max-upload-mb <MB>   单个上传文件的大小上限 (默认: 100)");
+    println!("  --max-body-kb <KB>     接口 JSON 和表单请求体的大小上限 (默认: 1024)");
+    println!("  --storage <plain|cas>  存储方式: 普通目录 (默认) 或按内容去重 (cas，需要 Unix)");
+    println!("  --sidecars <off|metadata|all>");
+    println!("                         删除或移动图片时一起处理的附属文件: 不处理、XMP/JSON 等元数据 (默认) 或再加上 RAW");
+    println!("  --dlna                 作为 DLNA 媒体服务器在局域网内广播 (需 dlna feature)");
+    println!("  --dlna-name <名称>     DLNA 设备名称 (默认: pic_url)");
+    println!("  --grpc <地址:端口>     在该地址上提供 gRPC 管理接口，需同时设置 --admin-token (需 grpc feature)");
+    println!("  --no-prewarm           不在后台预生成缩略图");
+    println!("  --archive <目录[=毫秒],...>");
+    println!("                         归档目录 (放在慢速存储上): 缩略图总是预生成，可附上读取原图的预计毫秒数");
+    println!("  --alias <名称=目录,...>");
+    println!("                         目录别名: 把图片目录之外的目录作为同名子目录扫描和访问");
+    println!("  --alias-include <名称=模式,...>");
+    println!("                         别名目录中只收录符合模式的图片，如 shots=*.png 或 shots=2024/**");
+    println!("  --alias-exclude <名称=模式,...>");
+    println!("                         别名目录中不收录符合模式的图片");
+    println!("  --path-match <exact|unicode|nocase>");
+    println!("                         按路径访问时的文件名匹配: 完全相同 (默认)、Unicode 规范化后相同或再忽略大小写");
+    println!("  --galleries <文件>     其他画廊的配置文件 (JSON)，各自挂在 /g/<名称>/ 下");
+    println!("  --rescan-cron <表达式>  按 cron 表达式 (UTC) 定时完整扫描，如 \"0 3 * * *\"");
+    println!("  --thumb-check <方式>   判断缩略图是否过期: mtime (默认)、hash 或 strict");
+    println!("  --thumb-size <像素>    缩略图长边的像素数，16 到 2048 (默认: 200)");
+    println!("  --thumb-cache-mb <MB>  常用缩略图的内存缓存容量，0 为不缓存 (默认: 0)");
+    println!("  --missing-thumb <placeholder|off|文件>");
+    println!("                         原图不存在时缩略图返回的占位图：内置、不返回或使用图片文件 (默认: placeholder)");
+    println!("  --max-concurrent <数量>");
+    println!("                         同时生成缩略图/代理缩放的上限，超出返回 503 (默认: CPU 核数)");
+    println!("  --robots <allow|disallow|文件>");
+    println!("                         robots.txt 内容：允许收录、禁止收录或使用自定义文件 (默认: allow)");
+    println!("  --map-tiles <URL 模板|off>");
+    println!("                         信息面板中 GPS 小地图的瓦片地址，含 {{z}}/{{x}}/{{y}} (默认: OpenStreetMap)");
+    println!("  --templates-dir <目录>");
+    println!("                         自定义页面模板目录，其中的同名文件替换内置模板");
+    println!("  --theme <dark|light|auto>");
+    println!("                         页面默认主题，auto 跟随系统设置 (默认: dark)");
+    println!("  --accent <颜色>        强调色，#rgb 或 #rrggbb 格式 (默认: #3b82f6)");
+    println!("  --tls-cert <文件>      PEM 格式的证书链，与 --tls-key 一起启用 HTTPS 和 HTTP/2 (需 tls feature)");
+    println!("  --tls-key <文件>       PEM 格式的私钥");
+    println!("  --otlp-endpoint <URL>  通过 OTLP/HTTP 导出链路追踪 (需 otel feature)");
+    println!("  --access-log <文件>    将访问日志 (combined 格式) 写入文件");
+    println!("  --access-log-rotate <size|daily>");
+    println!("                         访问日志轮转方式 (默认: size)");
+    println!("  --access-log-max-size <MB>");
+    println!("                         单个日志文件大小上限 (默认: 10)");
+    println!("  --access-log-keep <数量>");
+    println!("                         保留的历史日志文件数 (默认: 7)");
+    println!("  --log-format <text|json>");
+    println!("                         服务器日志格式，json 为每行一个 JSON 对象 (默认: text)");
+    println!("  -h, --help             显示帮助信息");
+    println!();
+    println!("环境变量:");
+    println!("  PIC_PORT               设置服务端口");
+    println!("  PIC_DIR                设置图片目录");
+    println!("  PIC_WEBDAV_URL         设置 WebDAV 地址");
+    println!("  PIC_WEBDAV_USER        设置 WebDAV 用户名");
+    println!("  PIC_WEBDAV_PASSWORD    设置 WebDAV 密码");
+    println!("  PIC_ADMIN_TOKEN        设置管理接口访问令牌");
+    println!("  PIC_AUTH_LOG           设置认证失败日志文件");
+    println!("  PIC_SESSION_LIFETIME   设置登录会话的有效期 (秒)");
+    println!("  PIC_USER_HOMES=1       启用用户私有目录");
+    println!("  PIC_DAV=1              启用 WebDAV 服务");
+    println!("  PIC_PROXY_ALLOW        设置代理域名白名单 (逗号分隔)");
+    println!("  PIC_PROXY_SECRET       设置代理签名密钥");
+    println!("  PIC_ORIGINALS_SECRET   设置原图签名密钥");
+    println!("  PIC_ORIGINALS_TTL      设置原图签名的有效期 (秒)");
+    println!("  PIC_REPLICATE_TO       设置同步目标");
+    println!("  PIC_REPLICATE_INTERVAL 设置同步间隔 (秒)");
+    println!("  PIC_REPLICATE_USER     设置同步目标用户名");
+    println!("  PIC_REPLICATE_PASSWORD 设置同步目标密码");
+    println!("  PIC_BACKUP_DIR         设置备份目录");
+    println!("  PIC_BACKUP_INTERVAL    设置备份间隔 (秒)");
+    println!("  PIC_BACKUP_KEEP        设置保留的备份数量");
+    println!("  PIC_EXPORT_DIR         设置导出目录");
+    println!("  PIC_WEBHOOKS           设置 Webhook 地址 (逗号分隔)");
+    println!("  PIC_WEBHOOK_SECRET     设置 Webhook 签名密钥");
+    println!("  PIC_ON_UPLOAD          设置上传钩子命令");
+    println!("  PIC_ON_DELETE          设置删除钩子命令");
+    println!("  PIC_ON_INDEX_COMPLETE  设置扫描完成钩子命令");
+    println!("  PIC_HOOK_TIMEOUT       设置钩子命令超时时间 (秒)");
+    println!("  PIC_CLAMD              设置 clamd 地址");
+    println!("  PIC_SCAN_COMMAND       设置上传扫描命令");
+    println!("  PIC_MAX_UPLOAD_MB      设置单个上传文件的大小上限 (MB)");
+    println!("  PIC_MAX_BODY_KB        设置接口请求体的大小上限 (KB)");
+    println!("  PIC_STORAGE            设置存储方式 (plain 或 cas)");
+    println!("  PIC_SIDECARS           设置删除或移动图片时如何处理附属文件");
+    println!("  PIC_DLNA=1             启用 DLNA 媒体服务器");
+    println!("  PIC_DLNA_NAME          设置 DLNA 设备名称");
+    println!("  PIC_BASE_URL           设置对外访问地址");
+    println!("  PIC_NO_PREWARM=1       不在后台预生成缩略图");
+    println!("  PIC_ARCHIVE            设置归档目录 (逗号分隔)");
+    println!("  PIC_ALIASES            设置目录别名 (逗号分隔)");
+    println!("  PIC_ALIAS_INCLUDE      设置别名目录的包含规则 (逗号分隔)");
+    println!("  PIC_ALIAS_EXCLUDE      设置别名目录的排除规则 (逗号分隔)");
+    println!("  PIC_PATH_MATCH         设置按路径访问时的文件名匹配方式");
+    println!("  PIC_GALLERIES          设置其他画廊的配置文件");
+    println!("  PIC_RESCAN_CRON        设置定时完整扫描的 cron 表达式");
+    println!("  PIC_THUMB_CHECK        设置缩略图过期的判断方式");
+    println!("  PIC_THUMB_SIZE         设置缩略图长边的像素数");
+    println!("  PIC_THUMB_CACHE_MB     设置缩略图内存缓存的容量 (MB)");
+    println!("  PIC_MISSING_THUMB      设置原图不存在时缩略图返回的占位图");
+    println!("  PIC_MAX_CONCURRENT     设置同时生成缩略图/代理缩放的上限");
+    println!("  PIC_ROBOTS             设置 robots.txt 内容");
+    println!("  PIC_MAP_TILES          设置 GPS 小地图的瓦片地址");
+    println!("  PIC_TEMPLATES_DIR      设置自定义页面模板目录");
+    println!("  PIC_THEME              设置页面默认主题");
+    println!("  PIC_ACCENT             设置强调色");
+    println!("  PIC_TLS_CERT           设置 TLS 证书文件");
+    println!("  PIC_TLS_KEY            设置 TLS 私钥文件");
+    println!("  PIC_OTLP_ENDPOINT      设置 OTLP 链路追踪导出地址");
+    println!("  PIC_ACCESS_LOG         设置访问日志文件");
+    println!("  PIC_ACCESS_LOG_ROTATE  设置访问日志轮转方式");
+    println!("  PIC_ACCESS_LOG_MAX_SIZE  设置单个日志文件大小上限 (MB)");
+    println!("  PIC_ACCESS_LOG_KEEP    设置保留的历史日志文件数");
+    println!("  PIC_LOG_FORMAT         设置服务器日志格式");
+    println!();
+    println!("示例:");
+    println!("  pic_url                        使用默认配置");
+    println!("  pic_url -p 8080                使用端口 8080");
+    println!("  pic_url -d /home/user/images   指定图片目录");
+    println!("  pic_url -p 8080 -d ./photos    同时指定端口和目录");
+    println!("  PIC_PORT=9000 PIC_DIR=/data pic_url  通过环境变量配置");
+    println!("  pic_url --access-log ./logs/access.log --access-log-rotate daily");
+    println!();
+    println!("子命令:");
+    println!("  pic_url sign <URL> [宽度]      生成带签名的 /proxy 路径 (密钥取自 PIC_PROXY_SECRET)");
+    println!("  pic_url sign --pic <图片路径> [有效秒数]");
+    println!("                                 生成带签名的 /pic 路径 (密钥取自 PIC_ORIGINALS_SECRET)");
+    println!("  pic_url optimize [-d 目录] [--webp] [--dry-run]");
+    println!("                                 无损优化全部图片以节省空间，--webp 把 PNG/BMP 转换为 WebP");
+    println!("  pic_url thumbs [-d 目录] [--folder 子目录] [--force]");
+    println!("                                 生成缺少或过期的缩略图，--force 全部重新生成");
+    println!("  pic_url storage migrate [-d 目录]");
+    println!("                                 把图片收入去重存储，重复的图片换成硬链接");
+    println!("  pic_url import <源目录> [-d 目录] [--move] [--date-folders] [--rename 模板] [--takeout] [--dry-run]");
+    println!("                                 导入已有的照片目录，跳过内容重复的图片");
+    println!("  pic_url dedupe [-d 目录] [--keep shortest|oldest] [--hardlink|--symlink|--delete] [--json 文件]");
+    println!("                                 找出内容相同的图片，换成链接或删除多余的副本，--dry-run 只预览");
+    println!("  pic_url doctor [选项]          检查图片目录、缩略图目录、端口和图片格式，不启动服务");
+}
+
+/// 读取选项后面的参数值，缺失时报错退出
+fn option_value(args: &[String], i: usize, flag: &str, what: &str) -> String {
+    match args.get(i + 1) {
+        Some(v) => v.clone(),
+        None => {
+            eprintln!("错误: {} 需要指定{}", flag, what);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// 解析数值型参数，失败时报错退出
+fn parse_number<T: std::str::FromStr>(value: &str, source: &str) -> T {
+    match value.parse::<T>() {
+        Ok(v) => v,
+        Err(_) => {
+            eprintln!("错误: {} 无效: '{}'", source, value);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// 拆分逗号分隔的列表，忽略空项
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+fn parse_thumb_check(value: &str, source: &str) -> ThumbCheck {
+    match ThumbCheck::parse(value) {
+        Some(check) => check,
+        None => {
+            eprintln!("错误: {} 必须是 mtime、hash 或 strict: '{}'", source, value);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn parse_sidecars(value: &str, source: &str) -> SidecarPolicy {
+    match SidecarPolicy::parse(value) {
+        Some(policy) => policy,
+        None => {
+            eprintln!("错误: {} 必须是 off、metadata 或 all: '{}'", source, value);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn parse_path_match(value: &str, source: &str) -> PathMatch {
+    match PathMatch::parse(value) {
+        Some(path_match) => path_match,
+        None => {
+            eprintln!("错误: {} 必须是 exact、unicode 或 nocase: '{}'", source, value);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn parse_thumb_size(value: &str, source: &str) -> u32 {
+    match thumbs::parse_size(value) {
+        Some(size) => size,
+        None => {
+            eprintln!("错误: {} 必须是 16 到 2048 之间的整数: '{}'", source, value);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// 检查 cron 表达式，返回去掉首尾空白的表达式
+fn parse_cron(value: &str, source: &str) -> String {
+    match Cron::parse(value) {
+        Ok(cron) => cron.to_string(),
+        Err(e) => {
+            eprintln!("错误: {} 不是有效的 cron 表达式 '{}': {}", source, value, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn parse_rotate(value: &str, source: &str) -> RotatePolicy {
+    match RotatePolicy::parse(value) {
+        Some(policy) => policy,
+        None => {
+            eprintln!("错误: {} 必须是 size 或 daily: '{}'", source, value);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn parse_log_format(value: &str, source: &str) -> LogFormat {
+    match LogFormat::parse(value) {
+        Some(format) => format,
+        None => {
+            eprintln!("错误: {} 必须是 text 或 json: '{}'", source, value);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// 签名有效期，至少 1 秒
+fn parse_ttl(value: &str, source: &str) -> u64 {
+    match value.parse::<u64>() {
+        Ok(ttl) if ttl > 0 => ttl,
+        _ => {
+            eprintln!("错误: {} 必须是正整数: '{}'", source, value);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `pic_url sign --pic <图片路径> [有效秒数]`：打印带签名的原图路径
+fn run_sign_pic(args: &[String]) -> ! {
+    let relative = match args.first() {
+        Some(path) => path.trim_matches('/'),
+        None => {
+            eprintln!("用法: pic_url sign --pic <图片路径> [有效秒数]");
+            std::process::exit(1);
+        }
+    };
+    let ttl = args.get(1).map_or(signed::DEFAULT_TTL, |ttl| parse_ttl(ttl, "有效秒数"));
+    match env::var("PIC_ORIGINALS_SECRET") {
+        Ok(secret) if !secret.is_empty() => {
+            println!("{}", signed::signed_path(&secret, relative, signed::expires_in(ttl)));
+            std::process::exit(0);
+        }
+        _ => {
+            eprintln!("错误: 需要通过环境变量 PIC_ORIGINALS_SECRET 提供签名密钥");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `pic_url sign <URL> [宽度]`：打印带签名的代理路径
+fn run_sign(args: &[String]) -> ! {
+    if args.first().is_some_and(|a| a == "--pic") {
+        run_sign_pic(&args[1..]);
+    }
+    let url = match args.first() {
+        Some(url) => url,
+        None => {
+            eprintln!("用法: pic_url sign <URL> [宽度]");
+            std::process::exit(1);
+        }
+    };
+    let w = args.get(1).map(|w| parse_number::<u32>(w, "宽度"));
+    match env::var("PIC_PROXY_SECRET") {
+        Ok(secret) if !secret.is_empty() => {
+            println!("{}", proxy::signed_path(&secret, url, w));
+            std::process::exit(0);
+        }
+        _ => {
+            eprintln!("错误: 需要通过环境变量 PIC_PROXY_SECRET 提供签名密钥");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// 读取命令行参数和环境变量；遇到子命令、`--help` 或无效参数时处理后直接退出
+pub fn parse_args() -> Config {
+    let mut args: Vec<String> = env::args().collect();
+
+    match args.get(1).map(String::as_str) {
+        Some("sign") => run_sign(&args[2..]),
+        Some("optimize") => optimize::run_command(&args[2..]),
+        Some("thumbs") => thumbs::run_command(&args[2..]),
+        Some("storage") => cas::run_command(&args[2..]),
+        Some("import") => import::run_command(&args[2..]),
+        Some("dedupe") => dedupe::run_command(&args[2..]),
+        _ => {}
+    }
+    // doctor 接受与启动服务相同的选项，读取完配置后只运行自检
+    let doctor = args.get(1).is_some_and(|a| a == "doctor");
+    if doctor {
+        args.remove(1);
+    }
+    let default_port: u16 = 2020;
+    let default_dir = String::from("./pic");
+
+    // 检查帮助参数
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        print_usage();
+        std::process::exit(0);
+    }
+
+    let mut port: Option<u16> = None;
+    let mut pic_dir: Option<String> = None;
+    let mut webdav_url: Option<String> = None;
+    let mut webdav_user: Option<String> = None;
+    let mut webdav_password: Option<String> = None;
+    let mut admin_token: Option<String> = None;
+    let mut auth_log: Option<String> = None;
+    let mut session_lifetime: Option<u64> = None;
+    let mut user_homes = false;
+    let mut dav = false;
+    let mut proxy_allow: Option<Vec<String>> = None;
+    let mut proxy_secret: Option<String> = None;
+    let mut originals_secret: Option<String> = None;
+    let mut originals_ttl: Option<u64> = None;
+    let mut replicate_to: Option<String> = None;
+    let mut replicate_interval: Option<u64> = None;
+    let mut replicate_user: Option<String> = None;
+    let mut replicate_password: Option<String> = None;
+    let mut backup_dir: Option<String> = None;
+    let mut export_dir: Option<String> = None;
+    let mut backup_interval: Option<u64> = None;
+    let mut backup_keep: Option<usize> = None;
+    let mut webhooks: Option<Vec<String>> = None;
+    let mut archive: Option<Vec<String>> = None;
+    let mut aliases: Option<Vec<String>> = None;
+    let mut alias_include: Option<Vec<String>> = None;
+    let mut alias_exclude: Option<Vec<String>> = None;
+    let mut path_match: Option<PathMatch> = None;
+    let mut galleries_file: Option<String> = None;
+    let mut webhook_secret: Option<String> = None;
+    let mut on_upload: Option<String> = None;
+    let mut on_delete: Option<String> = None;
+    let mut on_index_complete: Option<String> = None;
+    let mut hook_timeout: Option<u64> = None;
+    let mut clamd: Option<String> = None;
+    let mut storage: Option<String> = None;
+    let mut sidecars: Option<SidecarPolicy> = None;
+    let mut scan_command: Option<String> = None;
+    let mut max_upload_mb: Option<u64> = None;
+    let mut max_body_kb: Option<u64> = None;
+    let mut dlna = false;
+    let mut dlna_name: Option<String> = None;
+    let mut grpc: Option<String> = None;
+    let mut base_url: Option<String> = None;
+    let mut no_prewarm = false;
+    let mut rescan_cron: Option<String> = None;
+    let mut thumb_check: Option<ThumbCheck> = None;
+    let mut thumb_size: Option<u32> = None;
+    let mut thumb_cache_mb: Option<u64> = None;
+    let mut max_concurrent: Option<usize> = None;
+    let mut robots: Option<String> = None;
+    let mut missing_thumb: Option<String> = None;
+    let mut map_tiles: Option<String> = None;
+    let mut templates_dir: Option<String> = None;
+    let mut theme: Option<String> = None;
+    let mut accent: Option<String> = None;
+    let mut tls_cert: Option<String> = None;
+    let mut tls_key: Option<String> = None;
+    let mut otlp_endpoint: Option<String> = None;
+    let mut access_log_path: Option<String> = None;
+    let mut access_log_rotate: Option<RotatePolicy> = None;
+    let mut access_log_max_size: Option<u64> = None;
+    let mut access_log_keep: Option<usize> = None;
+    let mut log_format: Option<LogFormat> = None;
+
+    // 从命令行参数解析
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "-p" | "--port" => {
+                if i + 1 < args.len() {
+                    match args[i + 1].parse::<u16>() {
+                        Ok(p) if p > 0 => port = Some(p),
+                        Ok(_) => {
+                            eprintln!("错误: 端口必须大于 0");
+                            std::process::exit(1);
+                        }
+                        Err(_) => {
+                            eprintln!("错误: 无效的端口号 '{}'", args[i + 1]);
+                            std::process::exit(1);
+                        }
+                    }
+                    i += 2;
+                } else {
+                    eprintln!("错误: -p/--port 需要指定端口号");
+                    std::process::exit(1);
+                }
+            }
+            "-d" | "--dir" => {
+                if i + 1 < args.len() {
+                    pic_dir = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    eprintln!("错误: -d/--dir 需要指定目录路径");
+                    std::process::exit(1);
+                }
+            }
+            "--webdav-url" => {
+                webdav_url = Some(option_value(&args, i, "--webdav-url", "WebDAV 地址"));
+                i += 2;
+            }
+            "--webdav-user" => {
+                webdav_user = Some(option_value(&args, i, "--webdav-user", "用户名"));
+                i += 2;
+            }
+            "--webdav-password" => {
+                webdav_password = Some(option_value(&args, i, "--webdav-password", "密码"));
+                i += 2;
+            }
+            "--admin-token" => {
+                admin_token = Some(option_value(&args, i, "--admin-token", "访问令牌"));
+                i += 2;
+            }
+            "--auth-log" => {
+                auth_log = Some(option_value(&args, i, "--auth-log", "日志文件路径"));
+                i += 2;
+            }
+            "--session-lifetime" => {
+                let value = option_value(&args, i, "--session-lifetime", "秒数");
+                session_lifetime = Some(parse_ttl(&value, "--session-lifetime"));
+                i += 2;
+            }
+            "--user-homes" => {
+                user_homes = true;
+                i += 1;
+            }
+            "--dav" => {
+                dav = true;
+                i += 1;
+            }
+            "--proxy-allow" => {
+                let value = option_value(&args, i, "--proxy-allow", "域名列表");
+                proxy_allow = Some(split_list(&value));
+                i += 2;
+            }
+            "--proxy-secret" => {
+                proxy_secret = Some(option_value(&args, i, "--proxy-secret", "签名密钥"));
+                i += 2;
+            }
+            "--originals-secret" => {
+                originals_secret = Some(option_value(&args, i, "--originals-secret", "签名密钥"));
+                i += 2;
+            }
+            "--originals-ttl" => {
+                let value = option_value(&args, i, "--originals-ttl", "秒数");
+                originals_ttl = Some(parse_ttl(&value, "--originals-ttl"));
+                i += 2;
+            }
+            "--replicate-to" => {
+                replicate_to = Some(option_value(&args, i, "--replicate-to", "同步目标"));
+                i += 2;
+            }
+            "--replicate-interval" => {
+                let value = option_value(&args, i, "--replicate-interval", "间隔 (秒)");
+                replicate_interval = Some(parse_number(&value, "--replicate-interval"));
+                i += 2;
+            }
+            "--replicate-user" => {
+                replicate_user = Some(option_value(&args, i, "--replicate-user", "用户名"));
+                i += 2;
+            }
+            "--replicate-password" => {
+                replicate_password = Some(option_value(&args, i, "--replicate-password", "密码"));
+                i += 2;
+            }
+            "--backup-dir" => {
+                backup_dir = Some(option_value(&args, i, "--backup-dir", "目录"));
+                i += 2;
+            }
+            "--export-dir" => {
+                export_dir = Some(option_value(&args, i, "--export-dir", "目录"));
+                i += 2;
+            }
+            "--backup-interval" => {
+                let value = option_value(&args, i, "--backup-interval", "间隔 (秒)");
+                backup_interval = Some(parse_number(&value, "--backup-interval"));
+                i += 2;
+            }
+            "--backup-keep" => {
+                let value = option_value(&args, i, "--backup-keep", "数量");
+                backup_keep = Some(parse_number(&value, "--backup-keep"));
+                i += 2;
+            }
+            "--archive" => {
+                let value = option_value(&args, i, "--archive", "目录");
+                archive.get_or_insert_with(Vec::new).extend(split_list(&value));
+                i += 2;
+            }
+            "--alias" => {
+                let value = option_value(&args, i, "--alias", "名称=目录");
+                aliases.get_or_insert_with(Vec::new).extend(split_list(&value));
+                i += 2;
+            }
+            "--alias-include" => {
+                let value = option_value(&args, i, "--alias-include", "名称=模式");
+                alias_include.get_or_insert_with(Vec::new).extend(split_list(&value));
+                i += 2;
+            }
+            "--alias-exclude" => {
+                let value = option_value(&args, i, "--alias-exclude", "名称=模式");
+                alias_exclude.get_or_insert_with(Vec::new).extend(split_list(&value));
+                i += 2;
+            }
+            "--path-match" => {
+                let value = option_value(&args, i, "--path-match", "匹配方式");
+                path_match = Some(parse_path_match(&value, "--path-match"));
+                i += 2;
+            }
+            "--galleries" => {
+                galleries_file = Some(option_value(&args, i, "--galleries", "文件"));
+                i += 2;
+            }
+            "--webhook" => {
+                let value = option_value(&args, i, "--webhook", "URL");
+                webhooks.get_or_insert_with(Vec::new).extend(split_list(&value));
+                i += 2;
+            }
+            "--webhook-secret" => {
+                webhook_secret = Some(option_value(&args, i, "--webhook-secret", "密钥"));
+                i += 2;
+            }
+            "--on-upload" => {
+                on_upload = Some(option_value(&args, i, "--on-upload", "命令"));
+                i += 2;
+            }
+            "--on-delete" => {
+                on_delete = Some(option_value(&args, i, "--on-delete", "命令"));
+                i += 2;
+            }
+            "--on-index-complete" => {
+                on_index_complete = Some(option_value(&args, i, "--on-index-complete", "命令"));
+                i += 2;
+            }
+            "--hook-timeout" => {
+                let value = option_value(&args, i, "--hook-timeout", "超时时间 (秒)");
+                hook_timeout = Some(parse_number(&value, "--hook-timeout"));
+                i += 2;
+            }
+            "--clamd" => {
+                clamd = Some(option_value(&args, i, "--clamd", "地址"));
+                i += 2;
+            }
+            "--scan-command" => {
+                scan_command = Some(option_value(&args, i, "--scan-command", "命令"));
+                i += 2;
+            }
+            "--max-upload-mb" => {
+                let value = option_value(&args, i, "--max-upload-mb", "MB");
+                max_upload_mb = Some(parse_number(&value, "--max-upload-mb"));
+                i += 2;
+            }
+            "--max-body-kb" => {
+                let value = option_value(&args, i, "--max-body-kb", "KB");
+                max_body_kb = Some(parse_number(&value, "--max-body-kb"));
+                i += 2;
+            }
+            "--sidecars" => {
+                let value = option_value(&args, i, "--sidecars", "处理方式");
+                sidecars = Some(parse_sidecars(&value, "--sidecars"));
+                i += 2;
+            }
+            "--storage" => {
+                storage = Some(option_value(&args, i, "--storage", "存储方式"));
+                i += 2;
+            }
+            "--dlna" => {
+                dlna = true;
+                i += 1;
+            }
+            "--dlna-name" => {
+                dlna_name = Some(option_value(&args, i, "--dlna-name", "名称"));
+                i += 2;
+            }
+            "--grpc" => {
+                grpc = Some(option_value(&args, i, "--grpc", "监听地址"));
+                i += 2;
+            }
+            "--base-url" => {
+                base_url = Some(option_value(&args, i, "--base-url", "访问地址"));
+                i += 2;
+            }
+            "--thumb-check" => {
+                let value = option_value(&args, i, "--thumb-check", "检查方式");
+                thumb_check = Some(parse_thumb_check(&value, "--thumb-check"));
+                i += 2;
+            }
+            "--thumb-size" => {
+                let value = option_value(&args, i, "--thumb-size", "像素");
+                thumb_size = Some(parse_thumb_size(&value, "--thumb-size"));
+                i += 2;
+            }
+            "--thumb-cache-mb" => {
+                let value = option_value(&args, i, "--thumb-cache-mb", "MB");
+                thumb_cache_mb = Some(parse_number(&value, "--thumb-cache-mb"));
+                i += 2;
+            }
+            "--no-prewarm" => {
+                no_prewarm = true;
+                i += 1;
+            }
+            "--rescan-cron" => {
+                let value = option_value(&args, i, "--rescan-cron", "cron 表达式");
+                rescan_cron = Some(parse_cron(&value, "--rescan-cron"));
+                i += 2;
+            }
+            "--max-concurrent" => {
+                let value = option_value(&args, i, "--max-concurrent", "数量");
+                max_concurrent = Some(parse_number(&value, "--max-concurrent"));
+                i += 2;
+            }
+            "--robots" => {
+                robots = Some(option_value(&args, i, "--robots", "robots.txt 设置"));
+                i += 2;
+            }
+            "--missing-thumb" => {
+                missing_thumb = Some(option_value(&args, i, "--missing-thumb", "占位图"));
+                i += 2;
+            }
+            "--map-tiles" => {
+                map_tiles = Some(option_value(&args, i, "--map-tiles", "瓦片地址"));
+                i += 2;
+            }
+            "--templates-dir" => {
+                templates_dir = Some(option_value(&args, i, "--templates-dir", "模板目录"));
+                i += 2;
+            }
+            "--theme" => {
+                theme = Some(option_value(&args, i, "--theme", "主题"));
+                i += 2;
+            }
+            "--accent" => {
+                accent = Some(option_value(&args, i, "--accent", "颜色"));
+                i += 2;
+            }
+            "--tls-cert" => {
+                tls_cert = Some(option_value(&args, i, "--tls-cert", "证书文件"));
+                i += 2;
+            }
+            "--tls-key" => {
+                tls_key = Some(option_value(&args, i, "--tls-key", "私钥文件"));
+                i += 2;
+            }
+            "--otlp-endpoint" => {
+                otlp_endpoint = Some(option_value(&args, i, "--otlp-endpoint", "导出地址"));
+                i += 2;
+            }
+            "--access-log" => {
+                access_log_path = Some(option_value(&args, i, "--access-log", "日志文件路径"));
+                i += 2;
+            }
+            "--access-log-rotate" => {
+                let value = option_value(&args, i, "--access-log-rotate", "轮转方式");
+                access_log_rotate = Some(parse_rotate(&value, "--access-log-rotate"));
+                i += 2;
+            }
+            "--access-log-max-size" => {
+                let value = option_value(&args, i, "--access-log-max-size", "大小 (MB)");
+                access_log_max_size = Some(parse_number(&value, "--access-log-max-size"));
+                i += 2;
+            }
+            "--access-log-keep" => {
+                let value = option_value(&args, i, "--access-log-keep", "文件数量");
+                access_log_keep = Some(parse_number(&value, "--access-log-keep"));
+                i += 2;
+            }
+            "--log-format" => {
+                let value = option_value(&args, i, "--log-format", "日志格式");
+                log_format = Some(parse_log_format(&value, "--log-format"));
+                i += 2;
+            }
+            _ => {
+                eprintln!("错误: 未知参数 '{}'", args[i]);
+                eprintln!("使用 --help 查看帮助信息");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // 从环境变量解析（命令行参数优先）
+    if port.is_none() {
+        if let Ok(port_str) = env::var("PIC_PORT") {
+            match port_str.parse::<u16>() {
+                Ok(p) if p > 0 => port = Some(p),
+                Ok(_) => {
+                    eprintln!("错误: 环境变量 PIC_PORT 必须大于 0");
+                    std::process::exit(1);
+                }
+                Err(_) => {
+                    eprintln!("错误: 环境变量 PIC_PORT 无效: '{}'", port_str);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+
+    if pic_dir.is_none() {
+        if let Ok(dir) = env::var("PIC_DIR") {
+            pic_dir = Some(dir);
+        }
+    }
+
+    if webdav_url.is_none() {
+        webdav_url = env::var("PIC_WEBDAV_URL").ok();
+    }
+    if webdav_user.is_none() {
+        webdav_user = env::var("PIC_WEBDAV_USER").ok();
+    }
+    if webdav_password.is_none() {
+        webdav_password = env::var("PIC_WEBDAV_PASSWORD").ok();
+    }
+    if let Some(url) = &webdav_url {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            eprintln!("错误: WebDAV 地址必须以 http:// 或 https:// 开头: '{}'", url);
+            std::process::exit(1);
+        }
+    }
+
+    if admin_token.is_none() {
+        admin_token = env::var("PIC_ADMIN_TOKEN").ok();
+    }
+    if admin_token.as_deref() == Some("") {
+        eprintln!("错误: 管理接口令牌不能为空");
+        std::process::exit(1);
+    }
+    if auth_log.is_none() {
+        auth_log = env::var("PIC_AUTH_LOG").ok().filter(|path| !path.is_empty());
+    }
+    if session_lifetime.is_none() {
+        if let Ok(value) = env::var("PIC_SESSION_LIFETIME") {
+            session_lifetime = Some(parse_ttl(&value, "环境变量 PIC_SESSION_LIFETIME"));
+        }
+    }
+    if auth_log.is_some() && admin_token.is_none() {
+        eprintln!("错误: 认证失败日志需要同时设置 --admin-token");
+        std::process::exit(1);
+    }
+
+    if !user_homes {
+        user_homes = matches!(env::var("PIC_USER_HOMES").as_deref(), Ok("1") | Ok("true"));
+    }
+    if user_homes && admin_token.is_none() {
+        eprintln!("错误: 用户私有目录需要同时设置 --admin-token");
+        std::process::exit(1);
+    }
+
+    if !dav {
+        dav = matches!(env::var("PIC_DAV").as_deref(), Ok("1") | Ok("true"));
+    }
+    if dav && admin_token.is_none() {
+        eprintln!("错误: 启用 WebDAV 服务需要同时设置 --admin-token");
+        std::process::exit(1);
+    }
+    if dav && webdav_url.is_some() {
+        eprintln!("错误: WebDAV 服务只能用于本地图片目录，不能与 --webdav-url 同时使用");
+        std::process::exit(1);
+    }
+
+    if proxy_allow.is_none() {
+        proxy_allow = env::var("PIC_PROXY_ALLOW").ok().map(|v| split_list(&v));
+    }
+    if proxy_secret.is_none() {
+        proxy_secret = env::var("PIC_PROXY_SECRET").ok();
+    }
+    if originals_secret.is_none() {
+        originals_secret = env::var("PIC_ORIGINALS_SECRET").ok().filter(|s| !s.is_empty());
+    }
+    if originals_ttl.is_none() {
+        if let Ok(value) = env::var("PIC_ORIGINALS_TTL") {
+            originals_ttl = Some(parse_ttl(&value, "环境变量 PIC_ORIGINALS_TTL"));
+        }
+    }
+
+    if replicate_to.is_none() {
+        replicate_to = env::var("PIC_REPLICATE_TO").ok();
+    }
+    if replicate_interval.is_none() {
+        if let Ok(value) = env::var("PIC_REPLICATE_INTERVAL") {
+            replicate_interval = Some(parse_number(&value, "环境变量 PIC_REPLICATE_INTERVAL"));
+        }
+    }
+    if replicate_user.is_none() {
+        replicate_user = env::var("PIC_REPLICATE_USER").ok();
+    }
+    if replicate_password.is_none() {
+        replicate_password = env::var("PIC_REPLICATE_PASSWORD").ok();
+    }
+    if replicate_interval == Some(0) {
+        eprintln!("错误: 同步间隔必须大于 0");
+        std::process::exit(1);
+    }
+    if replicate_to.is_some() && webdav_url.is_some() {
+        eprintln!("错误: 同步只能用于本地图片目录，不能与 --webdav-url 同时使用");
+        std::process::exit(1);
+    }
+
+    if backup_dir.is_none() {
+        backup_dir = env::var("PIC_BACKUP_DIR").ok();
+    }
+    if backup_interval.is_none() {
+        if let Ok(value) = env::var("PIC_BACKUP_INTERVAL") {
+            backup_interval = Some(parse_number(&value, "环境变量 PIC_BACKUP_INTERVAL"));
+        }
+    }
+    if backup_keep.is_none() {
+        if let Ok(value) = env::var("PIC_BACKUP_KEEP") {
+            backup_keep = Some(parse_number(&value, "环境变量 PIC_BACKUP_KEEP"));
+        }
+    }
+    if backup_interval == Some(0) {
+        eprintln!("错误: 备份间隔必须大于 0");
+        std::process::exit(1);
+    }
+    if backup_keep == Some(0) {
+        eprintln!("错误: 保留的备份数量必须大于 0");
+        std::process::exit(1);
+    }
+    if backup_dir.is_some() && webdav_url.is_some() {
+        eprintln!("错误: 备份只能用于本地图片目录，不能与 --webdav-url 同时使用");
+        std::process::exit(1);
+    }
+
+    if export_dir.is_none() {
+        export_dir = env::var("PIC_EXPORT_DIR").ok();
+    }
+
+    if archive.is_none() {
+        archive = env::var("PIC_ARCHIVE").ok().map(|v| split_list(&v));
+    }
+    let archive = archive.unwrap_or_default();
+    if let Some(spec) = archive.iter().find(|spec| tiers::parse(spec).is_none()) {
+        eprintln!("错误: 无效的归档目录 (应为 目录 或 目录=毫秒): {}", spec);
+        std::process::exit(1);
+    }
+    if !archive.is_empty() && webdav_url.is_some() {
+        eprintln!("错误: --archive 只能用于本地图片目录，不能与 --webdav-url 同时使用");
+        std::process::exit(1);
+    }
+
+    if aliases.is_none() {
+        aliases = env::var("PIC_ALIASES").ok().map(|v| split_list(&v));
+    }
+    if alias_include.is_none() {
+        alias_include = env::var("PIC_ALIAS_INCLUDE").ok().map(|v| split_list(&v));
+    }
+    if alias_exclude.is_none() {
+        alias_exclude = env::var("PIC_ALIAS_EXCLUDE").ok().map(|v| split_list(&v));
+    }
+    let (aliases, alias_include, alias_exclude) =
+        (aliases.unwrap_or_default(), alias_include.unwrap_or_default(), alias_exclude.unwrap_or_default());
+    let alias_base = pic_dir.as_deref().unwrap_or(&default_dir);
+    if let Err(e) = crate::aliases::check(alias_base, &aliases, &alias_include, &alias_exclude) {
+        eprintln!("错误: 目录别名无效: {}", e);
+        std::process::exit(1);
+    }
+    if !aliases.is_empty() && webdav_url.is_some() {
+        eprintln!("错误: --alias 只能用于本地图片目录，不能与 --webdav-url 同时使用");
+        std::process::exit(1);
+    }
+    if path_match.is_none() {
+        if let Ok(value) = env::var("PIC_PATH_MATCH") {
+            path_match = Some(parse_path_match(&value, "环境变量 PIC_PATH_MATCH"));
+        }
+    }
+    if galleries_file.is_none() {
+        galleries_file = env::var("PIC_GALLERIES").ok();
+    }
+    let main_dir = webdav_url.is_none().then(|| pic_dir.as_deref().unwrap_or(&default_dir));
+    let galleries = match galleries_file.as_deref().map(|file| galleries::load(file, main_dir)) {
+        Some(Ok(galleries)) => galleries,
+        Some(Err(e)) => {
+            eprintln!("错误: 画廊配置无效: {}", e);
+            std::process::exit(1);
+        }
+        None => Vec::new(),
+    };
+
+    if webhooks.is_none() {
+        webhooks = env::var("PIC_WEBHOOKS").ok().map(|v| split_list(&v));
+    }
+    if webhook_secret.is_none() {
+        webhook_secret = env::var("PIC_WEBHOOK_SECRET").ok();
+    }
+    let webhooks = webhooks.unwrap_or_default();
+    if let Some(url) = webhooks
+        .iter()
+        .find(|url| !url.starts_with("http://") && !url.starts_with("https://"))
+    {
+        eprintln!("错误: Webhook 地址必须以 http:// 或 https:// 开头: {}", url);
+        std::process::exit(1);
+    }
+
+    if on_upload.is_none() {
+        on_upload = env::var("PIC_ON_UPLOAD").ok();
+    }
+    if on_delete.is_none() {
+        on_delete = env::var("PIC_ON_DELETE").ok();
+    }
+    if on_index_complete.is_none() {
+        on_index_complete = env::var("PIC_ON_INDEX_COMPLETE").ok();
+    }
+    if hook_timeout.is_none() {
+        if let Ok(value) = env::var("PIC_HOOK_TIMEOUT") {
+            hook_timeout = Some(parse_number(&value, "环境变量 PIC_HOOK_TIMEOUT"));
+        }
+    }
+    if clamd.is_none() {
+        clamd = env::var("PIC_CLAMD").ok();
+    }
+    if scan_command.is_none() {
+        scan_command = env::var("PIC_SCAN_COMMAND").ok();
+    }
+    if clamd.is_some() && scan_command.is_some() {
+        eprintln!("错误: --clamd 和 --scan-command 只能使用一个");
+        std::process::exit(1);
+    }
+    if max_upload_mb.is_none() {
+        if let Ok(value) = env::var("PIC_MAX_UPLOAD_MB") {
+            max_upload_mb = Some(parse_number(&value, "环境变量 PIC_MAX_UPLOAD_MB"));
+        }
+    }
+    if max_body_kb.is_none() {
+        if let Ok(value) = env::var("PIC_MAX_BODY_KB") {
+            max_body_kb = Some(parse_number(&value, "环境变量 PIC_MAX_BODY_KB"));
+        }
+    }
+    if max_upload_mb == Some(0) || max_body_kb == Some(0) {
+        eprintln!("错误: 请求体大小上限必须大于 0");
+        std::process::exit(1);
+    }
+    if storage.is_none() {
+        storage = env::var("PIC_STORAGE").ok();
+    }
+    let cas = match storage.as_deref() {
+        None | Some("plain") => false,
+        Some("cas") => true,
+        Some(other) => {
+            eprintln!("错误: --storage 必须是 plain 或 cas: {}", other);
+            std::process::exit(1);
+        }
+    };
+    if cas && !cas::SUPPORTED {
+        eprintln!("错误: 去重存储依赖硬链接，当前平台不支持");
+        std::process::exit(1);
+    }
+    if cas && webdav_url.is_some() {
+        eprintln!("错误: --storage cas 只能用于本地图片目录，不能与 --webdav-url 同时使用");
+        std::process::exit(1);
+    }
+    if sidecars.is_none() {
+        if let Ok(value) = env::var("PIC_SIDECARS") {
+            sidecars = Some(parse_sidecars(&value, "环境变量 PIC_SIDECARS"));
+        }
+    }
+    for (flag, line) in [
+        ("--on-upload", &on_upload),
+        ("--on-delete", &on_delete),
+        ("--on-index-complete", &on_index_complete),
+        ("--scan-command", &scan_command),
+    ] {
+        if let Some(line) = line {
+            if hooks::split_command(line).is_none() {
+                eprintln!("错误: {} 的命令为空或引号不匹配: {}", flag, line);
+                std::process::exit(1);
+            }
+        }
+    }
+    if hook_timeout == Some(0) {
+        eprintln!("错误: 钩子超时时间必须大于 0");
+        std::process::exit(1);
+    }
+    if webdav_url.is_some() && (on_upload.is_some() || on_delete.is_some()) {
+        eprintln!("错误: --on-upload/--on-delete 只能用于本地图片目录，不能与 --webdav-url 同时使用");
+        std::process::exit(1);
+    }
+
+    if !dlna {
+        dlna = matches!(env::var("PIC_DLNA").as_deref(), Ok("1") | Ok("true"));
+    }
+    if dlna_name.is_none() {
+        dlna_name = env::var("PIC_DLNA_NAME").ok();
+    }
+    if dlna && !cfg!(feature = "dlna") {
+        eprintln!("错误: DLNA 媒体服务器需要使用 `--features dlna` 重新编译");
+        std::process::exit(1);
+    }
+
+    if grpc.is_none() {
+        grpc = env::var("PIC_GRPC").ok().filter(|address| !address.is_empty());
+    }
+    if let Some(address) = &grpc {
+        if !cfg!(feature = "grpc") {
+            eprintln!("错误: gRPC 接口需要使用 `--features grpc` 重新编译");
+            std::process::exit(1);
+        }
+        if address.parse::<std::net::SocketAddr>().is_err() {
+            eprintln!("错误: --grpc 需要 地址:端口，如 127.0.0.1:50051: {}", address);
+            std::process::exit(1);
+        }
+        if admin_token.is_none() {
+            eprintln!("错误: gRPC 接口需要同时设置 --admin-token");
+            std::process::exit(1);
+        }
+    }
+
+    if base_url.is_none() {
+        base_url = env::var("PIC_BASE_URL").ok();
+    }
+    let base_url = base_url.map(|url| url.trim_end_matches('/').to_string());
+    if let Some(url) = &base_url {
+        let valid = ["http://", "https://"]
+            .iter()
+            .any(|scheme| url.len() > scheme.len() && url.starts_with(scheme));
+        if !valid {
+            eprintln!("错误: --base-url 必须是 http:// 或 https:// 开头的地址: {}", url);
+            std::process::exit(1);
+        }
+    }
+
+    if thumb_check.is_none() {
+        if let Ok(value) = env::var("PIC_THUMB_CHECK") {
+            thumb_check = Some(parse_thumb_check(&value, "环境变量 PIC_THUMB_CHECK"));
+        }
+    }
+    if thumb_size.is_none() {
+        if let Ok(value) = env::var("PIC_THUMB_SIZE") {
+            thumb_size = Some(parse_thumb_size(&value, "环境变量 PIC_THUMB_SIZE"));
+        }
+    }
+    if thumb_cache_mb.is_none() {
+        if let Ok(value) = env::var("PIC_THUMB_CACHE_MB") {
+            thumb_cache_mb = Some(parse_number(&value, "环境变量 PIC_THUMB_CACHE_MB"));
+        }
+    }
+    if !no_prewarm {
+        no_prewarm = matches!(env::var("PIC_NO_PREWARM").as_deref(), Ok("1") | Ok("true"));
+    }
+    if rescan_cron.is_none() {
+        if let Ok(value) = env::var("PIC_RESCAN_CRON") {
+            if !value.is_empty() {
+                rescan_cron = Some(parse_cron(&value, "环境变量 PIC_RESCAN_CRON"));
+            }
+        }
+    }
+
+    if max_concurrent.is_none() {
+        if let Ok(value) = env::var("PIC_MAX_CONCURRENT") {
+            max_concurrent = Some(parse_number(&value, "环境变量 PIC_MAX_CONCURRENT"));
+        }
+    }
+    if max_concurrent == Some(0) {
+        eprintln!("错误: 并发上限必须大于 0");
+        std::process::exit(1);
+    }
+
+    if robots.is_none() {
+        robots = env::var("PIC_ROBOTS").ok();
+    }
+    let robots = robots.unwrap_or_else(|| "allow".to_string());
+    if robots != "allow" && robots != "disallow" && !Path::new(&robots).is_file() {
+        eprintln!("错误: --robots 必须是 allow、disallow 或已存在的文件: {}", robots);
+        std::process::exit(1);
+    }
+
+    if missing_thumb.is_none() {
+        missing_thumb = env::var("PIC_MISSING_THUMB").ok();
+    }
+    let missing_thumb = missing_thumb.unwrap_or_else(|| missing::BUILTIN.to_string());
+    if !missing::is_valid(&missing_thumb) {
+        eprintln!("错误: --missing-thumb 必须是 placeholder、off 或已存在的图片文件: {}", missing_thumb);
+        std::process::exit(1);
+    }
+
+    if map_tiles.is_none() {
+        map_tiles = env::var("PIC_MAP_TILES").ok();
+    }
+    let map_tiles = match map_tiles.as_deref() {
+        None => Some(DEFAULT_MAP_TILES.to_string()),
+        Some("off") => None,
+        Some(url) if ["{z}", "{x}", "{y}"].iter().all(|p| url.contains(p)) => Some(url.to_string()),
+        Some(url) => {
+            eprintln!("错误: --map-tiles 必须包含 {{z}}、{{x}}、{{y}} 或者为 off: {}", url);
+            std::process::exit(1);
+        }
+    };
+
+    if templates_dir.is_none() {
+        templates_dir = env::var("PIC_TEMPLATES_DIR").ok();
+    }
+    if let Some(dir) = &templates_dir {
+        if !Path::new(dir).is_dir() {
+            eprintln!("错误: 模板目录不存在: {}", dir);
+            std::process::exit(1);
+        }
+    }
+
+    if theme.is_none() {
+        theme = env::var("PIC_THEME").ok();
+    }
+    let theme = theme.unwrap_or_else(|| "dark".to_string());
+    if !theme::THEMES.contains(&theme.as_str()) {
+        eprintln!("错误: --theme 必须是 dark、light 或 auto: {}", theme);
+        std::process::exit(1);
+    }
+    if accent.is_none() {
+        accent = env::var("PIC_ACCENT").ok();
+    }
+    let accent = accent.unwrap_or_else(|| theme::DEFAULT_ACCENT.to_string());
+    if !theme::is_valid_color(&accent) {
+        eprintln!("错误: --accent 必须是 #rgb 或 #rrggbb 格式的颜色: {}", accent);
+        std::process::exit(1);
+    }
+
+    if tls_cert.is_none() {
+        tls_cert = env::var("PIC_TLS_CERT").ok();
+    }
+    if tls_key.is_none() {
+        tls_key = env::var("PIC_TLS_KEY").ok();
+    }
+    if tls_cert.is_some() != tls_key.is_some() {
+        eprintln!("错误: --tls-cert 和 --tls-key 必须同时设置");
+        std::process::exit(1);
+    }
+    if tls_cert.is_some() && !cfg!(feature = "tls") {
+        eprintln!("错误: HTTPS 需要使用 `--features tls` 重新编译");
+        std::process::exit(1);
+    }
+
+    if otlp_endpoint.is_none() {
+        otlp_endpoint = env::var("PIC_OTLP_ENDPOINT").ok();
+    }
+    if otlp_endpoint.is_some() && !cfg!(feature = "otel") {
+        eprintln!("错误: 链路追踪导出需要使用 `--features otel` 重新编译");
+        std::process::exit(1);
+    }
+
+    if access_log_path.is_none() {
+        access_log_path = env::var("PIC_ACCESS_LOG").ok();
+    }
+    if access_log_rotate.is_none() {
+        if let Ok(value) = env::var("PIC_ACCESS_LOG_ROTATE") {
+            access_log_rotate = Some(parse_rotate(&value, "环境变量 PIC_ACCESS_LOG_ROTATE"));
+        }
+    }
+    if access_log_max_size.is_none() {
+        if let Ok(value) = env::var("PIC_ACCESS_LOG_MAX_SIZE") {
+            access_log_max_size = Some(parse_number(&value, "环境变量 PIC_ACCESS_LOG_MAX_SIZE"));
+        }
+    }
+    if access_log_keep.is_none() {
+        if let Ok(value) = env::var("PIC_ACCESS_LOG_KEEP") {
+            access_log_keep = Some(parse_number(&value, "环境变量 PIC_ACCESS_LOG_KEEP"));
+        }
+    }
+
+    if log_format.is_none() {
+        if let Ok(value) = env::var("PIC_LOG_FORMAT") {
+            log_format = Some(parse_log_format(&value, "环境变量 PIC_LOG_FORMAT"));
+        }
+    }
+
+    let access_log = access_log_path.map(|path| AccessLogConfig {
+        path,
+        max_size: access_log_max_size.unwrap_or(10) * 1024 * 1024,
+        rotate: access_log_rotate.unwrap_or(RotatePolicy::Size),
+        keep: access_log_keep.unwrap_or(7),
+    });
+
+    let config = Config {
+        port: port.unwrap_or(default_port),
+        pic_dir: pic_dir.unwrap_or(default_dir),
+        webdav_url,
+        webdav_user,
+        webdav_password,
+        admin_token,
+        auth_log,
+        session_lifetime: session_lifetime.unwrap_or(session::DEFAULT_LIFETIME),
+        user_homes,
+        dav,
+        proxy_allow: proxy_allow.unwrap_or_default(),
+        proxy_secret,
+        originals_secret,
+        originals_ttl: originals_ttl.unwrap_or(signed::DEFAULT_TTL),
+        replicate_to,
+        replicate_interval: replicate_interval.unwrap_or(3600),
+        replicate_user,
+        replicate_password,
+        backup_dir,
+        backup_interval: backup_interval.unwrap_or(86400),
+        backup_keep: backup_keep.unwrap_or(7),
+        export_dir,
+        webhooks,
+        webhook_secret,
+        on_upload,
+        on_delete,
+        on_index_complete,
+        hook_timeout: hook_timeout.unwrap_or(30),
+        clamd,
+        scan_command,
+        cas,
+        sidecars: sidecars.unwrap_or(SidecarPolicy::Metadata),
+        archive,
+        aliases,
+        alias_include,
+        alias_exclude,
+        path_match: path_match.unwrap_or(PathMatch::Exact),
+        galleries,
+        hosts: Vec::new(),
+        max_upload_mb: max_upload_mb.unwrap_or(payload::DEFAULT_UPLOAD_MB),
+        max_body_kb: max_body_kb.unwrap_or(payload::DEFAULT_BODY_KB),
+        dlna,
+        dlna_name: dlna_name.unwrap_or_else(|| "pic_url".to_string()),
+        grpc,
+        base_url,
+        prewarm: !no_prewarm,
+        rescan_cron,
+        thumb_check: thumb_check.unwrap_or(ThumbCheck::Mtime),
+        thumb_size: thumb_size.unwrap_or(THUMB_SIZE),
+        thumb_cache_mb: thumb_cache_mb.unwrap_or(0),
+        mount_path: String::new(),
+        max_concurrent: max_concurrent.unwrap_or_else(Limiter::default_max),
+        robots,
+        missing_thumb,
+        map_tiles,
+        templates_dir,
+        theme,
+        accent,
+        tls_cert,
+        tls_key,
+        otlp_endpoint,
+        access_log,
+        log_format: log_format.unwrap_or_default(),
+    };
+    if doctor {
+        doctor::run_command(&config);
+    }
+    config
+}
@@ -0,0 +1,155 @@
+//! 五段式 cron 表达式：`分 时 日 月 周`，按 UTC 计算。
+//! 每段支持 `*`、数字、范围 `a-b`、列表 `a,b` 和步长 `*/n`、`a-b/n`；
+//! 日和周都不是 `*` 时满足其一即可，与常见的 cron 实现一致
+
+use std::fmt;
+use time::{Duration, OffsetDateTime, Time};
+
+/// 查找下一次执行时间时最多向后查找的天数，超过后认为表达式永远不会触发（如 2 月 30 日）
+const MAX_LOOKAHEAD_DAYS: i64 = 366 * 5;
+
+#[derive(Clone)]
+pub struct Cron {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// 日、周两段是否为 `*`，决定两者是“且”还是“或”
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let expression = expression.trim();
+        let expanded = match expression {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("需要 5 段（分 时 日 月 周），实际为 {} 段", fields.len()));
+        };
+        let mut weekdays = parse_field(weekday, 0, 7, "周")?;
+        // 0 和 7 都表示周日
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        let cron = Self {
+            expression: expression.to_string(),
+            minutes: parse_field(minute, 0, 59, "分")?,
+            hours: parse_field(hour, 0, 23, "时")?,
+            days: parse_field(day, 1, 31, "日")?,
+            months: parse_field(month, 1, 12, "月")?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        };
+        if cron.next_after(OffsetDateTime::UNIX_EPOCH).is_none() {
+            return Err("指定的日期不存在，永远不会执行".to_string());
+        }
+        Ok(cron)
+    }
+
+    fn day_matches(&self, t: OffsetDateTime) -> bool {
+        let day = self.days & (1 << t.day()) != 0;
+        let weekday = self.weekdays & (1 << t.weekday().number_days_from_sunday()) != 0;
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    /// `after` 之后（不含）的第一个触发时间
+    pub fn next_after(&self, after: OffsetDateTime) -> Option<OffsetDateTime> {
+        let start = after.replace_second(0).ok()?.replace_nanosecond(0).ok()? + Duration::minutes(1);
+        let limit = start + Duration::days(MAX_LOOKAHEAD_DAYS);
+        let mut t = start;
+        while t < limit {
+            if self.months & (1 << u8::from(t.month())) == 0 || !self.day_matches(t) {
+                t = t.replace_time(Time::MIDNIGHT) + Duration::days(1);
+            } else if self.hours & (1 << t.hour()) == 0 {
+                t = t.replace_minute(0).ok()? + Duration::hours(1);
+            } else if self.minutes & (1 << t.minute()) == 0 {
+                t += Duration::minutes(1);
+            } else {
+                return Some(t);
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Display for Cron {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+/// 解析一段，返回按位表示的取值集合
+fn parse_field(field: &str, min: u32, max: u32, name: &str) -> Result<u64, String> {
+    let invalid = || format!("{}字段无效: '{}'（取值 {} 到 {}）", name, field, min, max);
+    let number = |s: &str| s.parse::<u32>().ok().filter(|n| (min..=max).contains(n));
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|&s| s > 0).ok_or_else(invalid)?),
+            None => (part, 1),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((a, b)) => (number(a).ok_or_else(invalid)?, number(b).ok_or_else(invalid)?),
+                // `5/15` 表示从 5 开始每 15 个
+                None if part.contains('/') => (number(range).ok_or_else(invalid)?, max),
+                None => {
+                    let n = number(range).ok_or_else(invalid)?;
+                    (n, n)
+                }
+            },
+        };
+        if from > to {
+            return Err(invalid());
+        }
+        for n in (from..=to).step_by(step as usize) {
+            bits |= 1 << n;
+        }
+    }
+    Ok(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn finds_next_run() {
+        let now = datetime!(2026-10-16 12:32:10 UTC);
+        for (expression, expected) in [
+            ("0 3 * * *", datetime!(2026-10-17 03:00 UTC)),
+            ("*/15 * * * *", datetime!(2026-10-16 12:45 UTC)),
+            ("5/20 * * * *", datetime!(2026-10-16 12:45 UTC)),
+            ("0 0 * * 7", datetime!(2026-10-18 00:00 UTC)),
+            // 日和周都指定时满足其一即可
+            ("0 0 1 * 1", datetime!(2026-10-19 00:00 UTC)),
+            ("30 2 29 2 *", datetime!(2028-02-29 02:30 UTC)),
+            ("0 9-17/4 * 1-3,12 1-5", datetime!(2026-12-01 09:00 UTC)),
+            ("@monthly", datetime!(2026-11-01 00:00 UTC)),
+        ] {
+            assert_eq!(Cron::parse(expression).unwrap().next_after(now), Some(expected), "{}", expression);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for expression in ["", "* * *", "60 * * * *", "*/0 * * * *", "5-1 * * * *", "0 0 30 2 *"] {
+            assert!(Cron::parse(expression).is_err(), "{}", expression);
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! 通过 WebDAV 暴露图片目录 (`/dav/...`)，便于桌面和手机客户端挂载后直接管理文件。
+//! 配置了[病毒扫描](crate::scanner)时，`PUT` 写入的文件在响应之前扫描，发现威胁时删除并返回 422；
+//! 超过[上传大小上限](crate::payload)的文件返回 413。使用[去重存储](crate::cas)时，覆盖已有的文件之前先换成独立的副本
+
+use crate::admin::AdminAuth;
+use crate::errors::ApiError;
+use crate::maintenance;
+use crate::webhook::EventKind;
+use crate::{api_keys, mount_path, payload, AppConfig};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest};
+use dav_server::actix::{DavRequest, DavResponse};
+use dav_server::localfs::LocalFs;
+use dav_server::memls::MemLs;
+use dav_server::DavHandler;
+use percent_encoding::percent_decode_str;
+use std::fs;
+use std::path::Path;
+
+pub const DAV_PREFIX: &str = "/dav";
+
+pub fn handler(pic_dir: &str) -> DavHandler {
+    DavHandler::builder()
+        .filesystem(LocalFs::new(pic_dir, false, false, false))
+        .locksystem(MemLs::new())
+        .strip_prefix(DAV_PREFIX)
+        .hide_symlinks(true)
+        .build_handler()
+}
+
+async fn dav(
+    _auth: AdminAuth,
+    http_req: HttpRequest,
+    req: DavRequest,
+    handler: web::Data<DavHandler>,
+    config: web::Data<AppConfig>,
+) -> Result<DavResponse, ApiError> {
+    // 维护模式下只允许浏览和下载
+    let read_only = matches!(req.request.method().as_str(), "GET" | "HEAD" | "OPTIONS" | "PROPFIND");
+    let status = config.maintenance.status();
+    if status.enabled && !read_only {
+        return Err(maintenance::rejection(&status));
+    }
+    let is_put = req.request.method() == "PUT";
+    let uploaded = is_put.then(|| uploaded_path(req.request.uri().path())).flatten();
+    let written = api_keys::dav_relative(&mount_path(&http_req), req.request.uri().path()).filter(|_| is_put);
+    // 与网页上传的大小上限相同：带 Content-Length 时在写入之前拒绝，分块上传的在写完之后检查
+    let limit = payload::upload_limit(&config);
+    let length = req.request.headers().get("content-length").and_then(|value| value.to_str().ok()?.parse::<u64>().ok());
+    if is_put && length.is_some_and(|length| length > limit) {
+        return Err(too_large(limit));
+    }
+    // 去重存储中的文件与内容相同的图片共用一份数据，覆盖之前换成独立的副本
+    if let Some((store, relative)) = config.cas.clone().zip(written.clone()) {
+        web::block(move || store.detach(&relative))
+            .await
+            .map_err(|e| ApiError::internal(e.to_string()))?
+            .map_err(|e| ApiError::internal(e.to_string()))?;
+    }
+    // 配置了病毒扫描时，写入的文件在返回之前扫描，发现威胁就删掉
+    let scanned = config.scanner.clone().zip(written.clone());
+    let response = handler.handle(req.request).await;
+    if let Some(relative) = written.filter(|_| length.is_none() && response.status().is_success()) {
+        let path = Path::new(config.pic_dir.as_str()).join(relative);
+        let oversized = web::block(move || {
+            let oversized = fs::metadata(&path).is_ok_and(|meta| meta.len() > limit);
+            if oversized {
+                let _ = fs::remove_file(&path);
+            }
+            oversized
+        })
+        .await
+        .unwrap_or(false);
+        if oversized {
+            return Err(too_large(limit));
+        }
+    }
+    if let Some((scanner, relative)) = scanned.filter(|_| response.status().is_success()) {
+        let path = Path::new(config.pic_dir.as_str()).join(&relative);
+        let checked = web::block(move || {
+            let checked = scanner.check(&path, &relative);
+            if checked.is_err() {
+                let _ = fs::remove_file(&path);
+            }
+            checked
+        })
+        .await
+        .unwrap_or_else(|_| Err("virus scan failed".to_string()));
+        if let Err(reason) = checked {
+            return Err(ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, reason));
+        }
+    }
+    if let Some(path) = uploaded {
+        if response.status().is_success() {
+            config.notify(EventKind::Uploaded, &path);
+        }
+    }
+    Ok(response.into())
+}
+
+fn too_large(limit: u64) -> ApiError {
+    ApiError::new(StatusCode::PAYLOAD_TOO_LARGE, format!("file is larger than {} MB", limit / 1024 / 1024))
+}
+
+/// 上传请求对应的图片相对路径，非图片文件返回 `None`
+fn uploaded_path(uri_path: &str) -> Option<String> {
+    let relative = uri_path.strip_prefix(DAV_PREFIX)?;
+    let relative = percent_decode_str(relative).decode_utf8().ok()?;
+    let relative = relative.trim_start_matches('/');
+    if crate::is_image_file(Path::new(relative)) {
+        Some(relative.to_string())
+    } else {
+        None
+    }
+}
+
+/// WebDAV 请求路径或 `Destination` 头中的 URL（挂载路径之下）对应的相对路径
+pub fn relative_path(mount: &str, uri: &str) -> Option<String> {
+    let path = match uri.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or("/", |i| &rest[i..]),
+        None => uri,
+    };
+    let path = path.strip_prefix(mount)?.strip_prefix(DAV_PREFIX)?;
+    Some(percent_decode_str(path).decode_utf8_lossy().trim_matches('/').to_string())
+}
+
+/// 注册 `/dav` 路由，所有请求都需要管理令牌
+pub fn configure(cfg: &mut web::ServiceConfig, handler: DavHandler) {
+    cfg.app_data(web::Data::new(handler))
+        .service(web::resource(DAV_PREFIX).to(dav))
+        .service(web::resource(format!("{}/{{tail:.*}}", DAV_PREFIX)).to(dav));
+}
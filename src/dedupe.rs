@@ -0,0 +1,349 @@
+//! `pic_url dedupe`：按内容找出图片目录中完全相同的图片并列出，可以按保留规则（路径最短或
+//! 最早修改）每组留下一份，把其余的换成硬链接、符号链接或删除。`--dry-run` 只列出计划，
+//! `--json` 输出机器可读的报告。先按大小分组，只计算大小相同的图片的哈希
+
+use crate::optimize::format_bytes;
+use crate::{cas, collect_images, names};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// 每组保留哪一份
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Keep {
+    /// 路径最短的，一样长时按路径排序的第一个
+    Shortest,
+    /// 修改时间最早的，相同时按路径最短
+    Oldest,
+}
+
+impl Keep {
+    fn name(self) -> &'static str {
+        match self {
+            Keep::Shortest => "shortest",
+            Keep::Oldest => "oldest",
+        }
+    }
+}
+
+/// 对多余的副本做什么
+#[derive(Clone, Copy, PartialEq)]
+enum Action {
+    Report,
+    Hardlink,
+    Symlink,
+    Delete,
+}
+
+impl Action {
+    fn name(self) -> &'static str {
+        match self {
+            Action::Report => "report",
+            Action::Hardlink => "hardlink",
+            Action::Symlink => "symlink",
+            Action::Delete => "delete",
+        }
+    }
+}
+
+/// 一张图片
+struct Entry {
+    relative: String,
+    meta: Metadata,
+}
+
+impl Entry {
+    fn modified(&self) -> SystemTime {
+        self.meta.modified().unwrap_or(SystemTime::UNIX_EPOCH)
+    }
+}
+
+#[derive(Serialize)]
+struct Group {
+    hash: String,
+    size: u64,
+    keep: String,
+    duplicates: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Failure {
+    path: String,
+    error: String,
+}
+
+#[derive(Serialize)]
+struct DedupeReport {
+    action: &'static str,
+    keep: &'static str,
+    dry_run: bool,
+    groups: Vec<Group>,
+    /// 多余的副本数
+    duplicates: usize,
+    /// 处理后能释放的空间，已经是同一个文件的硬链接不计入
+    reclaimable_bytes: u64,
+    /// 已处理（或预计处理）的副本数
+    processed: usize,
+    failures: Vec<Failure>,
+}
+
+/// 按规则排序，保留的一份排在最前面
+fn sort_group(group: &mut [Entry], keep: Keep) {
+    let shortest = |a: &Entry, b: &Entry| {
+        (a.relative.chars().count(), &a.relative).cmp(&(b.relative.chars().count(), &b.relative))
+    };
+    match keep {
+        Keep::Shortest => group.sort_by(shortest),
+        Keep::Oldest => group.sort_by(|a, b| a.modified().cmp(&b.modified()).then_with(|| shortest(a, b))),
+    }
+}
+
+/// 从 `from` 所在目录指向 `to` 的相对路径，两者都相对于图片目录；图片目录整体移动后链接仍然有效
+fn link_target(from: &str, to: &str) -> String {
+    let depth = from.matches('/').count();
+    format!("{}{}", "../".repeat(depth), to)
+}
+
+/// 图片目录中的图片按内容分组，只返回有重复的组，每组按保留规则排好序。
+/// 符号链接不是副本，跳过
+fn find_duplicates(pic_dir: &Path, keep: Keep, verbose: bool) -> Vec<(String, Vec<Entry>)> {
+    let mut images = Vec::new();
+    if let Err(e) = collect_images(pic_dir, pic_dir, &mut images) {
+        eprintln!("警告: 跳过无法读取的目录 {}", e);
+    }
+    let mut by_size: HashMap<u64, Vec<Entry>> = HashMap::new();
+    for relative in images {
+        match fs::symlink_metadata(names::fs_path(pic_dir, &relative)) {
+            Ok(meta) if meta.is_file() => by_size.entry(meta.len()).or_default().push(Entry { relative, meta }),
+            _ => {}
+        }
+    }
+    let candidates: Vec<Entry> = by_size.into_values().filter(|same| same.len() > 1).flatten().collect();
+    if verbose {
+        println!("正在计算 {} 张大小相同的图片的哈希...", candidates.len());
+    }
+
+    let mut by_hash: HashMap<String, Vec<Entry>> = HashMap::new();
+    for entry in candidates {
+        match cas::hash_file(&names::fs_path(pic_dir, &entry.relative)) {
+            Ok(hash) => by_hash.entry(hash).or_default().push(entry),
+            Err(e) => eprintln!("  无法读取 {}: {}", entry.relative, e),
+        }
+    }
+    let mut groups: Vec<(String, Vec<Entry>)> = by_hash.into_iter().filter(|(_, group)| group.len() > 1).collect();
+    for (_, group) in &mut groups {
+        sort_group(group, keep);
+    }
+    groups.sort_by(|a, b| a.1[0].relative.cmp(&b.1[0].relative));
+    groups
+}
+
+/// 先在同目录下创建临时的链接再改名，替换过程中路径一直存在
+fn replace_with_link(pic_dir: &Path, keep: &str, copy: &str, action: Action) -> io::Result<()> {
+    let path = names::fs_path(pic_dir, copy);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = path.with_file_name(format!(".{}.dedupe.tmp", name));
+    let _ = fs::remove_file(&tmp);
+    match action {
+        Action::Hardlink => fs::hard_link(names::fs_path(pic_dir, keep), &tmp)?,
+        Action::Symlink => symlink(&link_target(copy, keep), &tmp)?,
+        Action::Report | Action::Delete => unreachable!(),
+    }
+    let replaced = fs::rename(&tmp, &path);
+    if replaced.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    replaced
+}
+
+#[cfg(unix)]
+fn symlink(target: &str, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(not(unix))]
+fn symlink(_target: &str, _link: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "symbolic links are only supported on Unix"))
+}
+
+fn run(pic_dir: &Path, action: Action, keep: Keep, dry_run: bool, verbose: bool) -> DedupeReport {
+    let groups = find_duplicates(pic_dir, keep, verbose);
+    let mut report = DedupeReport {
+        action: action.name(),
+        keep: keep.name(),
+        dry_run,
+        groups: Vec::new(),
+        duplicates: 0,
+        reclaimable_bytes: 0,
+        processed: 0,
+        failures: Vec::new(),
+    };
+    for (hash, group) in groups {
+        let size = group[0].meta.len();
+        let keeper = &group[0];
+        if verbose {
+            println!("{} 份相同 ({}): 保留 {}", group.len(), format_bytes(size), keeper.relative);
+        }
+        for (i, copy) in group.iter().enumerate().skip(1) {
+            // 与保留的或前面的副本已经是同一个文件（硬链接）时不占用额外的空间
+            let linked = group[..i].iter().any(|other| cas::same_file(&other.meta, &copy.meta));
+            if !linked {
+                report.reclaimable_bytes += size;
+            }
+            let already = match action {
+                Action::Report => true,
+                Action::Hardlink => cas::same_file(&keeper.meta, &copy.meta),
+                Action::Symlink | Action::Delete => false,
+            };
+            if verbose {
+                println!("  - {}{}", copy.relative, if linked { " (硬链接)" } else { "" });
+            }
+            if already {
+                continue;
+            }
+            let result = match action {
+                _ if dry_run => Ok(()),
+                Action::Delete => fs::remove_file(names::fs_path(pic_dir, &copy.relative)),
+                _ => replace_with_link(pic_dir, &keeper.relative, &copy.relative, action),
+            };
+            match result {
+                Ok(()) => report.processed += 1,
+                Err(e) => {
+                    eprintln!("  失败: {}: {}", copy.relative, e);
+                    report.failures.push(Failure {
+                        path: copy.relative.clone(),
+                        error: e.to_string(),
+                    });
+                }
+            }
+        }
+        report.duplicates += group.len() - 1;
+        report.groups.push(Group {
+            hash,
+            size,
+            keep: keeper.relative.clone(),
+            duplicates: group[1..].iter().map(|entry| entry.relative.clone()).collect(),
+        });
+    }
+    report
+}
+
+/// `pic_url dedupe [-d 图片目录] [--keep shortest|oldest] [--hardlink|--symlink|--delete] [--dry-run] [--json 文件]`
+pub fn run_command(args: &[String]) -> ! {
+    let usage = || -> ! {
+        eprintln!(
+            "用法: pic_url dedupe [-d 图片目录] [--keep shortest|oldest] [--hardlink|--symlink|--delete] \
+             [--dry-run] [--json 文件|-]"
+        );
+        std::process::exit(1);
+    };
+    let mut pic_dir = std::env::var("PIC_DIR").unwrap_or_else(|_| "./pic".to_string());
+    let mut keep = Keep::Shortest;
+    let mut action = Action::Report;
+    let mut dry_run = false;
+    let mut json: Option<PathBuf> = None;
+    let mut i = 0;
+    while i < args.len() {
+        let mut set_action = |value: Action| {
+            if action != Action::Report {
+                usage();
+            }
+            action = value;
+        };
+        match (args[i].as_str(), args.get(i + 1)) {
+            ("-d" | "--dir", Some(value)) => {
+                pic_dir = value.clone();
+                i += 1;
+            }
+            ("--keep", Some(value)) => {
+                keep = match value.as_str() {
+                    "shortest" => Keep::Shortest,
+                    "oldest" => Keep::Oldest,
+                    _ => usage(),
+                };
+                i += 1;
+            }
+            ("--json", Some(value)) => {
+                json = Some(PathBuf::from(value));
+                i += 1;
+            }
+            ("--hardlink", _) => set_action(Action::Hardlink),
+            ("--symlink", _) => set_action(Action::Symlink),
+            ("--delete", _) => set_action(Action::Delete),
+            ("--dry-run", _) => dry_run = true,
+            _ => usage(),
+        }
+        i += 1;
+    }
+    if action == Action::Symlink && !cfg!(unix) {
+        eprintln!("错误: --symlink 只支持 Unix");
+        std::process::exit(1);
+    }
+    if !Path::new(&pic_dir).is_dir() {
+        eprintln!("错误: 图片目录 '{}' 不存在", pic_dir);
+        std::process::exit(1);
+    }
+
+    // 报告输出到标准输出时不打印其他内容
+    let to_stdout = json.as_deref() == Some(Path::new("-"));
+    let report = run(Path::new(&pic_dir), action, keep, dry_run, !to_stdout);
+    if let Some(path) = &json {
+        let data = serde_json::to_string_pretty(&report).unwrap_or_default();
+        if to_stdout {
+            println!("{}", data);
+        } else if let Err(e) = fs::write(path, data) {
+            eprintln!("错误: 无法写入报告 {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+    if !to_stdout {
+        let done = match action {
+            Action::Report => String::new(),
+            Action::Hardlink => format!(", 换成硬链接 {} 份", report.processed),
+            Action::Symlink => format!(", 换成符号链接 {} 份", report.processed),
+            Action::Delete => format!(", 删除 {} 份", report.processed),
+        };
+        println!(
+            "{}: {} 组重复, 多余 {} 份, 可释放 {}{}, 失败 {} 份",
+            if dry_run { "预计" } else { "完成" },
+            report.groups.len(),
+            report.duplicates,
+            format_bytes(report.reclaimable_bytes),
+            done,
+            report.failures.len()
+        );
+    }
+    std::process::exit(if report.failures.is_empty() { 0 } else { 1 });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_by_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let entry = |relative: &str, age: u64| {
+            let path = dir.path().join(relative);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, "same").unwrap();
+            let modified = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000 - age);
+            fs::File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+            Entry {
+                relative: relative.to_string(),
+                meta: fs::metadata(&path).unwrap(),
+            }
+        };
+        let mut group = vec![entry("old/copy of a.jpg", 300), entry("b.jpg", 100), entry("a.jpg", 200)];
+        sort_group(&mut group, Keep::Shortest);
+        assert_eq!(group[0].relative, "a.jpg");
+        sort_group(&mut group, Keep::Oldest);
+        assert_eq!(group[0].relative, "old/copy of a.jpg");
+
+        assert_eq!(link_target("copies/2024/a.jpg", "a.jpg"), "../../a.jpg");
+        assert_eq!(link_target("b.jpg", "trips/a.jpg"), "trips/a.jpg");
+    }
+}
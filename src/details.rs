@@ -0,0 +1,246 @@
+//! 单张图片的详细信息：`/api/info/{path}` 返回尺寸、大小、所在的存储层等文件信息，
+//! `/api/exif/{path}` 返回拍摄时间、相机、曝光参数和 GPS 坐标，供大图查看器的信息面板使用
+
+use crate::errors::{ApiError, AppError};
+use crate::tiers::Storage;
+use crate::{dimensions, format_time, sniff, AppConfig};
+use actix_web::{get, web, HttpResponse, Result};
+use exif::{In, Reader, Tag, Value};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufReader, Cursor};
+
+#[derive(Serialize)]
+struct InfoResponse {
+    path: String,
+    name: String,
+    mime: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    modified: Option<String>,
+    /// WebDAV 来源不读取尺寸
+    #[serde(skip_serializing_if = "Option::is_none")]
+    width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<u32>,
+    /// 所在的存储层和读取原图的预计等待时间
+    storage: Storage,
+}
+
+#[derive(Serialize)]
+struct Gps {
+    latitude: f64,
+    longitude: f64,
+    /// 海拔（米）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    altitude: Option<f64>,
+}
+
+/// 图片中的 EXIF 信息，没有的字段不返回
+#[derive(Serialize, Default)]
+struct ExifResponse {
+    /// 拍摄时间，带时区时为 RFC 3339 格式，否则为相机记录的本地时间
+    #[serde(skip_serializing_if = "Option::is_none")]
+    taken_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    make: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lens: Option<String>,
+    /// 曝光时间（秒），如 `1/250`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exposure_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    f_number: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iso: Option<u32>,
+    /// 焦距（毫米）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    focal_length: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    focal_length_35mm: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    orientation: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gps: Option<Gps>,
+}
+
+#[get("/api/info/{path:.*}")]
+pub async fn image_info(path: web::Path<String>, config: web::Data<AppConfig>) -> Result<HttpResponse> {
+    let relative = path.into_inner();
+    if !config.image_exists(&relative).await? {
+        return Err(ApiError::not_found("Image not found").into());
+    }
+    let name = relative.rsplit('/').next().unwrap_or(&relative).to_string();
+
+    // WebDAV 来源不为了类型下载原图，按扩展名判断
+    let (mime, size, modified, dimensions) = match &config.webdav {
+        Some(source) => (
+            mime_guess::from_path(&relative).first_or_octet_stream().to_string(),
+            source.size(&relative),
+            source.modified(&relative),
+            None,
+        ),
+        None => {
+            let file = config.fs_path(&relative);
+            // 打开失败（没有权限等）时返回具体的原因，而不是缺少字段的信息
+            let (meta, mime, dimensions) = web::block(move || {
+                let meta = File::open(&file)?.metadata()?;
+                Ok::<_, io::Error>((meta, sniff::file_mime(&file)?, dimensions::read(&file)))
+            })
+            .await?
+            .map_err(|e| AppError::io(&relative, e))?;
+            let mime = mime.unwrap_or(sniff::UNKNOWN).to_string();
+            (mime, Some(meta.len()), meta.modified().ok(), dimensions)
+        }
+    };
+
+    let storage = config.tiers.storage(&relative);
+    Ok(HttpResponse::Ok().json(InfoResponse {
+        path: relative,
+        name,
+        mime,
+        size,
+        modified: modified.map(format_time),
+        width: dimensions.map(|(w, _)| w),
+        height: dimensions.map(|(_, h)| h),
+        storage,
+    }))
+}
+
+#[get("/api/exif/{path:.*}")]
+pub async fn image_exif(path: web::Path<String>, config: web::Data<AppConfig>) -> Result<HttpResponse> {
+    let relative = path.into_inner();
+    if !config.image_exists(&relative).await? {
+        return Err(ApiError::not_found("Image not found").into());
+    }
+    let config = config.into_inner();
+    let exif = web::block(move || read_exif(&config, &relative)).await?;
+    Ok(HttpResponse::Ok().json(exif.map(|exif| parse(&exif)).unwrap_or_default()))
+}
+
+/// 读取 EXIF，没有或无法解析时返回 `None`
+pub fn read_exif(config: &AppConfig, relative: &str) -> Option<exif::Exif> {
+    let reader = Reader::new();
+    match &config.webdav {
+        Some(source) => reader.read_from_container(&mut Cursor::new(source.fetch(relative).ok()?)).ok(),
+        None => {
+            let file = File::open(config.fs_path(relative)).ok()?;
+            reader.read_from_container(&mut BufReader::new(file)).ok()
+        }
+    }
+}
+
+fn value(exif: &exif::Exif, tag: Tag) -> Option<&Value> {
+    exif.get_field(tag, In::PRIMARY).map(|field| &field.value)
+}
+
+fn ascii(exif: &exif::Exif, tag: Tag) -> Option<String> {
+    match value(exif, tag)? {
+        Value::Ascii(values) => values
+            .first()
+            .map(|v| String::from_utf8_lossy(v).trim().to_string())
+            .filter(|s| !s.is_empty()),
+        _ => None,
+    }
+}
+
+fn rational(exif: &exif::Exif, tag: Tag) -> Option<f64> {
+    match value(exif, tag)? {
+        Value::Rational(values) => values.first().filter(|r| r.denom != 0).map(|r| r.to_f64()),
+        _ => None,
+    }
+}
+
+/// 保留有限位小数，避免 `2.8000000000000003` 这样的数字
+fn round(value: f64, digits: i32) -> f64 {
+    let factor = 10f64.powi(digits);
+    (value * factor).round() / factor
+}
+
+/// 度、分、秒转换为带符号的十进制度数
+fn coordinate(exif: &exif::Exif, tag: Tag, reference: Tag, negative: &str) -> Option<f64> {
+    let Value::Rational(dms) = value(exif, tag)? else {
+        return None;
+    };
+    if dms.len() < 3 || dms.iter().any(|r| r.denom == 0) {
+        return None;
+    }
+    let degrees = dms[0].to_f64() + dms[1].to_f64() / 60.0 + dms[2].to_f64() / 3600.0;
+    let sign = if ascii(exif, reference).as_deref() == Some(negative) { -1.0 } else { 1.0 };
+    Some(round(sign * degrees, 6))
+}
+
+/// 拍摄时间，带时区时为 RFC 3339 格式，否则为相机记录的本地时间
+pub fn taken_at(exif: &exif::Exif) -> Option<String> {
+    [Tag::DateTimeOriginal, Tag::DateTime].iter().find_map(|&tag| {
+        let Value::Ascii(values) = value(exif, tag)? else {
+            return None;
+        };
+        let t = exif::DateTime::from_ascii(values.first()?).ok()?;
+        let local = format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            t.year, t.month, t.day, t.hour, t.minute, t.second
+        );
+        let offset = match tag {
+            Tag::DateTimeOriginal => ascii(exif, Tag::OffsetTimeOriginal),
+            _ => ascii(exif, Tag::OffsetTime),
+        };
+        Some(local + offset.as_deref().unwrap_or(""))
+    })
+}
+
+/// 相机型号；型号中已经包含厂商名时不再重复，如 `Canon` + `Canon EOS R6`
+pub fn camera(exif: &exif::Exif) -> Option<String> {
+    let make = ascii(exif, Tag::Make).filter(|make| !make.is_empty());
+    let model = ascii(exif, Tag::Model).filter(|model| !model.is_empty());
+    match (make, model) {
+        (Some(make), Some(model)) if model.to_lowercase().starts_with(&make.to_lowercase()) => Some(model),
+        (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+        (make, model) => make.or(model),
+    }
+}
+
+fn parse(exif: &exif::Exif) -> ExifResponse {
+    let taken_at = taken_at(exif);
+
+    let exposure_time = match value(exif, Tag::ExposureTime) {
+        Some(Value::Rational(values)) => values.first().filter(|r| r.num != 0 && r.denom != 0).map(|r| {
+            let seconds = r.to_f64();
+            if seconds < 1.0 {
+                format!("1/{}", (1.0 / seconds).round())
+            } else {
+                round(seconds, 1).to_string()
+            }
+        }),
+        _ => None,
+    };
+
+    let gps = coordinate(exif, Tag::GPSLatitude, Tag::GPSLatitudeRef, "S")
+        .zip(coordinate(exif, Tag::GPSLongitude, Tag::GPSLongitudeRef, "W"))
+        .map(|(latitude, longitude)| {
+            // GPSAltitudeRef 为 1 时表示海平面以下
+            let below = value(exif, Tag::GPSAltitudeRef).and_then(|v| v.get_uint(0)) == Some(1);
+            Gps {
+                latitude,
+                longitude,
+                altitude: rational(exif, Tag::GPSAltitude).map(|a| round(if below { -a } else { a }, 1)),
+            }
+        });
+
+    ExifResponse {
+        taken_at,
+        make: ascii(exif, Tag::Make),
+        model: ascii(exif, Tag::Model),
+        lens: ascii(exif, Tag::LensModel),
+        exposure_time,
+        f_number: rational(exif, Tag::FNumber).map(|f| round(f, 1)),
+        iso: value(exif, Tag::PhotographicSensitivity).and_then(|v| v.get_uint(0)),
+        focal_length: rational(exif, Tag::FocalLength).map(|f| round(f, 1)),
+        focal_length_35mm: value(exif, Tag::FocalLengthIn35mmFilm).and_then(|v| v.get_uint(0)),
+        orientation: value(exif, Tag::Orientation).and_then(|v| v.get_uint(0)),
+        gps,
+    }
+}
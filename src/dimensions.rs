@@ -0,0 +1,61 @@
+//! 图片尺寸缓存：列表接口返回宽高，画廊按原始比例排版。
+//! 只读取文件头，按修改时间缓存，文件被替换后重新读取
+
+use exif::{In, Reader, Tag};
+use image::ImageReader;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// 显示时的宽高（只读取文件头）：EXIF 方向为旋转 90° 或 270° 时交换宽高
+pub fn read(file: &Path) -> Option<(u32, u32)> {
+    let (width, height) = ImageReader::open(file).ok()?.with_guessed_format().ok()?.into_dimensions().ok()?;
+    let ext = file.extension().map(|ext| ext.to_string_lossy().to_lowercase()).unwrap_or_default();
+    // 只有 JPEG 和 WebP 带有方向标记，其他格式不必再打开一次
+    if !matches!(ext.as_str(), "jpg" | "jpeg" | "webp") {
+        return Some((width, height));
+    }
+    let turned = File::open(file)
+        .ok()
+        .and_then(|f| Reader::new().read_from_container(&mut BufReader::new(f)).ok())
+        .and_then(|exif| exif.get_field(Tag::Orientation, In::PRIMARY)?.value.get_uint(0))
+        .is_some_and(|orientation| (5..=8).contains(&orientation));
+    Some(if turned { (height, width) } else { (width, height) })
+}
+
+/// 读取时文件的修改时间和宽高
+type Entry = (SystemTime, Option<(u32, u32)>);
+
+#[derive(Default)]
+pub struct Dimensions {
+    cache: Mutex<HashMap<String, Entry>>,
+}
+
+impl Dimensions {
+    /// `relative` 对应的本地文件 `file` 的宽高，无法识别的格式返回 `None`（同样缓存）
+    pub fn get(&self, file: &Path, relative: &str) -> Option<(u32, u32)> {
+        let modified = fs::metadata(file).and_then(|m| m.modified()).ok()?;
+        if let Ok(cache) = self.cache.lock() {
+            if let Some((cached_at, size)) = cache.get(relative) {
+                if *cached_at == modified {
+                    return *size;
+                }
+            }
+        }
+        let size = read(file);
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(relative.to_string(), (modified, size));
+        }
+        size
+    }
+
+    /// 图片删除或移走后清除缓存
+    pub fn forget(&self, relative: &str) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.remove(relative);
+        }
+    }
+}
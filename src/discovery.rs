@@ -0,0 +1,260 @@
+//! 给 API 客户端的首页：`/` 按 `Accept` 选择内容，浏览器得到画廊页面，`Accept: application/json`
+//! 的客户端得到一份接口说明（版本、可用的接口和开启的功能），客户端据此判断这台服务器支持什么。
+//! `/api/v1/capabilities` 另外列出编译时包含的功能和支持的图片格式，通用的客户端据此调整界面
+
+use crate::{versioning, AppConfig, IMAGE_EXTENSIONS};
+use actix_web::http::header::{self, Header};
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use serde::Serialize;
+
+/// `/api/images` 支持的筛选参数
+const SEARCH_PARAMS: [&str; 7] = ["q", "tag", "folder", "ext", "from", "to", "album"];
+
+/// 接口需要的功能，未开启时不列出
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Requires {
+    Nothing,
+    /// 管理令牌
+    Auth,
+    /// 管理令牌和本地图片目录
+    Uploads,
+    Export,
+    Proxy,
+    Dav,
+    Graphql,
+}
+
+impl Requires {
+    /// 需要管理令牌、API 密钥或登录
+    fn auth(self) -> bool {
+        !matches!(self, Requires::Nothing | Requires::Proxy | Requires::Graphql)
+    }
+}
+
+struct Endpoint {
+    method: &'static str,
+    path: &'static str,
+    description: &'static str,
+    requires: Requires,
+}
+
+const fn endpoint(method: &'static str, path: &'static str, description: &'static str, requires: Requires) -> Endpoint {
+    Endpoint {
+        method,
+        path,
+        description,
+        requires,
+    }
+}
+
+const ENDPOINTS: &[Endpoint] = &[
+    endpoint("GET", "/", "gallery page, or this document for Accept: application/json", Requires::Nothing),
+    endpoint("GET", "/api/v1/capabilities", "compiled and enabled features, supported formats", Requires::Nothing),
+    endpoint("GET", "/api/v1/images", "image list with paging, sorting and filters", Requires::Nothing),
+    endpoint("GET", "/api/v1/folders", "folders containing images", Requires::Nothing),
+    endpoint("GET", "/api/v1/albums", "smart albums", Requires::Nothing),
+    endpoint("GET", "/api/v1/export.json", "library snapshot: hashes, metadata, tags and albums", Requires::Nothing),
+    endpoint("GET", "/api/v1/timeline", "image counts by date", Requires::Nothing),
+    endpoint("GET", "/api/v1/stats", "library statistics", Requires::Nothing),
+    endpoint("GET", "/api/v1/info/{path}", "image size, dimensions and type", Requires::Nothing),
+    endpoint("GET", "/api/v1/exif/{path}", "image EXIF metadata", Requires::Nothing),
+    endpoint("GET", "/api/v1/links/{path}", "ready-made links in several formats", Requires::Nothing),
+    endpoint("GET", "/pic/{path}", "original image", Requires::Nothing),
+    endpoint("GET", "/thumb/{path}", "thumbnail", Requires::Nothing),
+    endpoint("GET", "/i/{id}", "original image by short id", Requires::Nothing),
+    endpoint("GET", "/api/v1/events", "library change events (Server-Sent Events)", Requires::Nothing),
+    endpoint("GET", "/api/v1/changes/wait", "long polling for library changes", Requires::Nothing),
+    endpoint("GET", "/ws", "WebSocket events and commands", Requires::Nothing),
+    endpoint("GET", "/api/v1/scan/status", "scan and thumbnail progress", Requires::Nothing),
+    endpoint("POST", "/api/v1/zip", "download selected images as ZIP", Requires::Nothing),
+    endpoint("POST", "/api/v1/upload", "upload images (multipart)", Requires::Uploads),
+    endpoint("POST", "/api/v1/admin/batch", "delete, move and tag images", Requires::Auth),
+    endpoint("POST", "/api/v1/images/{path}/rotate", "rotate an image", Requires::Uploads),
+    endpoint("POST", "/api/v1/images/{path}/edit", "crop or resize an image", Requires::Uploads),
+    endpoint("GET", "/api/v1/images/{path}/versions", "previous versions of an image", Requires::Uploads),
+    endpoint("PUT", "/api/v1/admin/albums/{name}", "save a smart album", Requires::Auth),
+    endpoint("POST", "/api/v1/admin/rescan", "rescan the library", Requires::Auth),
+    endpoint("GET", "/api/v1/admin/info", "version and configuration", Requires::Auth),
+    endpoint("GET", "/api/v1/admin/keys", "API keys", Requires::Auth),
+    endpoint("GET", "/api/v1/admin/db/backup", "download tags, albums, short ids and API keys", Requires::Auth),
+    endpoint("POST", "/api/v1/admin/db/restore", "replace metadata from a backup", Requires::Auth),
+    endpoint("POST", "/api/v1/export", "export selected images", Requires::Export),
+    endpoint("POST", "/graphql", "GraphQL queries over images, folders, albums and tags", Requires::Graphql),
+    endpoint("GET", "/proxy", "remote image proxy", Requires::Proxy),
+    endpoint("PROPFIND", "/dav/", "WebDAV access to the library", Requires::Dav),
+];
+
+/// 开启的功能
+#[derive(Serialize)]
+struct Features {
+    /// 配置了管理令牌，可以登录和使用管理接口
+    auth: bool,
+    uploads: bool,
+    user_homes: bool,
+    signed_originals: bool,
+    proxy: bool,
+    export: bool,
+    dav: bool,
+    /// 图片来自 WebDAV 服务器，只读
+    webdav_source: bool,
+    /// 按路径、标签和日期筛选 (`/api/images` 的 `q`、`tag` 等参数)
+    search: bool,
+    tls: bool,
+    dlna: bool,
+    telemetry: bool,
+    grpc: bool,
+    graphql: bool,
+}
+
+impl Features {
+    fn of(config: &AppConfig) -> Self {
+        let settings = &config.settings;
+        let auth = settings.admin_token.is_some();
+        Self {
+            auth,
+            uploads: auth && config.webdav.is_none(),
+            user_homes: settings.user_homes,
+            signed_originals: settings.originals_secret.is_some(),
+            proxy: !settings.proxy_allow.is_empty(),
+            export: auth && settings.export_dir.is_some(),
+            // WebDAV 服务只挂在根路径的主画廊中
+            dav: settings.dav && settings.mount_path.is_empty(),
+            webdav_source: config.webdav.is_some(),
+            search: true,
+            tls: cfg!(feature = "tls") && settings.tls_cert.is_some(),
+            dlna: cfg!(feature = "dlna") && settings.dlna && settings.mount_path.is_empty(),
+            telemetry: cfg!(feature = "otel") && settings.otlp_endpoint.is_some(),
+            grpc: cfg!(feature = "grpc") && settings.grpc.is_some(),
+            graphql: cfg!(feature = "graphql"),
+        }
+    }
+
+    fn has(&self, requires: Requires) -> bool {
+        match requires {
+            Requires::Nothing => true,
+            Requires::Auth => self.auth,
+            Requires::Uploads => self.uploads,
+            Requires::Export => self.export,
+            Requires::Proxy => self.proxy,
+            Requires::Dav => self.dav,
+            Requires::Graphql => self.graphql,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct EndpointInfo {
+    method: &'static str,
+    /// 含挂载路径，`{path}` 等为参数
+    path: String,
+    description: &'static str,
+    auth: bool,
+}
+
+#[derive(Serialize)]
+pub struct Document {
+    name: &'static str,
+    version: &'static str,
+    /// 挂载路径，根路径时为空
+    base: String,
+    /// 当前版本的接口前缀，接口地址见 `endpoints`
+    api: &'static str,
+    features: Features,
+    endpoints: Vec<EndpointInfo>,
+}
+
+/// 这台服务器的接口说明
+pub fn document(config: &AppConfig) -> Document {
+    let base = config.settings.mount_path.clone();
+    let features = Features::of(config);
+    let endpoints = ENDPOINTS
+        .iter()
+        .filter(|endpoint| features.has(endpoint.requires))
+        .map(|endpoint| EndpointInfo {
+            method: endpoint.method,
+            // DAV 不在挂载路径下
+            path: match endpoint.requires {
+                Requires::Dav => endpoint.path.to_string(),
+                _ => format!("{}{}", base, endpoint.path),
+            },
+            description: endpoint.description,
+            auth: endpoint.requires.auth(),
+        })
+        .collect();
+    Document {
+        name: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+        base,
+        api: versioning::V1,
+        features,
+        endpoints,
+    }
+}
+
+/// 编译时包含的可选功能；HEIC 和视频这个版本不支持，总是 `false`
+#[derive(Serialize)]
+struct Compiled {
+    tls: bool,
+    dlna: bool,
+    otel: bool,
+    grpc: bool,
+    graphql: bool,
+    heic: bool,
+    video: bool,
+}
+
+#[derive(Serialize)]
+struct Capabilities {
+    version: &'static str,
+    compiled: Compiled,
+    /// 当前配置下开启的功能，与接口说明中的 `features` 相同
+    enabled: Features,
+    /// 可以浏览和生成缩略图的图片格式（扩展名）
+    formats: &'static [&'static str],
+    search: &'static [&'static str],
+}
+
+/// 编译时包含和当前开启的功能
+#[get("/api/capabilities")]
+pub async fn capabilities(config: web::Data<AppConfig>) -> HttpResponse {
+    HttpResponse::Ok().json(Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        compiled: Compiled {
+            tls: cfg!(feature = "tls"),
+            dlna: cfg!(feature = "dlna"),
+            otel: cfg!(feature = "otel"),
+            grpc: cfg!(feature = "grpc"),
+            graphql: cfg!(feature = "graphql"),
+            heic: false,
+            video: false,
+        },
+        enabled: Features::of(&config),
+        formats: &IMAGE_EXTENSIONS,
+        search: &SEARCH_PARAMS,
+    })
+}
+
+/// 客户端是否更想要 JSON：`Accept` 中 `application/json` 的优先级最高。
+/// 浏览器和 `*/*` 仍然得到页面
+pub fn wants_json(req: &HttpRequest) -> bool {
+    header::Accept::parse(req).is_ok_and(|accept| accept.preference() == mime_guess::mime::APPLICATION_JSON)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn negotiates_json_only_when_preferred() {
+        let wants = |accept: &str| {
+            let req = TestRequest::default().insert_header(("Accept", accept)).to_http_request();
+            wants_json(&req)
+        };
+        assert!(wants("application/json"));
+        assert!(wants("application/json, text/html;q=0.9"));
+        assert!(!wants("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"));
+        assert!(!wants("*/*"));
+        assert!(!wants_json(&TestRequest::default().to_http_request()));
+    }
+}
@@ -0,0 +1,556 @@
+//! DLNA/UPnP 媒体服务器：通过 SSDP 在局域网内广播，智能电视和播放器可以直接浏览文件夹、查看照片
+//!
+//! 只实现了浏览照片所需的 ContentDirectory `Browse` 和 ConnectionManager `GetProtocolInfo`。
+
+use crate::homes::Viewer;
+use crate::{html_escape, AppConfig};
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use sha2::{Digest, Sha256};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{Ipv4Addr, SocketAddrV4, UdpSocket};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+pub const DLNA_PREFIX: &str = "/dlna";
+
+const SSDP_ADDR: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+const SSDP_PORT: u16 = 1900;
+/// 广播的有效期，期间每隔 ANNOUNCE_INTERVAL 重新广播一次
+const MAX_AGE: u64 = 1800;
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(600);
+
+const DEVICE_TYPE: &str = "urn:schemas-upnp-org:device:MediaServer:1";
+const CDS_TYPE: &str = "urn:schemas-upnp-org:service:ContentDirectory:1";
+const CMS_TYPE: &str = "urn:schemas-upnp-org:service:ConnectionManager:1";
+
+const ROOT_ID: &str = "0";
+/// 对象 ID：根目录为 `0`，文件夹为 `d/<路径>`，图片为 `i/<路径>`
+const DIR_PREFIX: &str = "d/";
+const IMAGE_PREFIX: &str = "i/";
+
+const SERVER: &str = concat!("Linux/1.0 UPnP/1.0 pic_url/", env!("CARGO_PKG_VERSION"));
+
+const CDS_SCPD: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<scpd xmlns="urn:schemas-upnp-org:service-1-0">
+  <specVersion><major>1</major><minor>0</minor></specVersion>
+  <actionList>
+    <action><name>Browse</name><argumentList>
+      <argument><name>ObjectID</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_ObjectID</relatedStateVariable></argument>
+      <argument><name>BrowseFlag</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_BrowseFlag</relatedStateVariable></argument>
+      <argument><name>Filter</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_Filter</relatedStateVariable></argument>
+      <argument><name>StartingIndex</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_Index</relatedStateVariable></argument>
+      <argument><name>RequestedCount</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_Count</relatedStateVariable></argument>
+      <argument><name>SortCriteria</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_SortCriteria</relatedStateVariable></argument>
+      <argument><name>Result</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_Result</relatedStateVariable></argument>
+      <argument><name>NumberReturned</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_Count</relatedStateVariable></argument>
+      <argument><name>TotalMatches</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_Count</relatedStateVariable></argument>
+      <argument><name>UpdateID</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_UpdateID</relatedStateVariable></argument>
+    </argumentList></action>
+    <action><name>GetSearchCapabilities</name><argumentList>
+      <argument><name>SearchCaps</name><direction>out</direction><relatedStateVariable>SearchCapabilities</relatedStateVariable></argument>
+    </argumentList></action>
+    <action><name>GetSortCapabilities</name><argumentList>
+      <argument><name>SortCaps</name><direction>out</direction><relatedStateVariable>SortCapabilities</relatedStateVariable></argument>
+    </argumentList></action>
+    <action><name>GetSystemUpdateID</name><argumentList>
+      <argument><name>Id</name><direction>out</direction><relatedStateVariable>SystemUpdateID</relatedStateVariable></argument>
+    </argumentList></action>
+  </actionList>
+  <serviceStateTable>
+    <stateVariable sendEvents="no"><name>A_ARG_TYPE_ObjectID</name><dataType>string</dataType></stateVariable>
+    <stateVariable sendEvents="no"><name>A_ARG_TYPE_BrowseFlag</name><dataType>string</dataType>
+      <allowedValueList><allowedValue>BrowseMetadata</allowedValue><allowedValue>BrowseDirectChildren</allowedValue></allowedValueList></stateVariable>
+    <stateVariable sendEvents="no"><name>A_ARG_TYPE_Filter</name><dataType>string</dataType></stateVariable>
+    <stateVariable sendEvents="no"><name>A_ARG_TYPE_Index</name><dataType>ui4</dataType></stateVariable>
+    <stateVariable sendEvents="no"><name>A_ARG_TYPE_Count</name><dataType>ui4</dataType></stateVariable>
+    <stateVariable sendEvents="no"><name>A_ARG_TYPE_SortCriteria</name><dataType>string</dataType></stateVariable>
+    <stateVariable sendEvents="no"><name>A_ARG_TYPE_Result</name><dataType>string</dataType></stateVariable>
+    <stateVariable sendEvents="no"><name>A_ARG_TYPE_UpdateID</name><dataType>ui4</dataType></stateVariable>
+    <stateVariable sendEvents="no"><name>SearchCapabilities</name><dataType>string</dataType></stateVariable>
+    <stateVariable sendEvents="no"><name>SortCapabilities</name><dataType>string</dataType></stateVariable>
+    <stateVariable sendEvents="yes"><name>SystemUpdateID</name><dataType>ui4</dataType></stateVariable>
+  </serviceStateTable>
+</scpd>"#;
+
+const CMS_SCPD: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<scpd xmlns="urn:schemas-upnp-org:service-1-0">
+  <specVersion><major>1</major><minor>0</minor></specVersion>
+  <actionList>
+    <action><name>GetProtocolInfo</name><argumentList>
+      <argument><name>Source</name><direction>out</direction><relatedStateVariable>SourceProtocolInfo</relatedStateVariable></argument>
+      <argument><name>Sink</name><direction>out</direction><relatedStateVariable>SinkProtocolInfo</relatedStateVariable></argument>
+    </argumentList></action>
+    <action><name>GetCurrentConnectionIDs</name><argumentList>
+      <argument><name>ConnectionIDs</name><direction>out</direction><relatedStateVariable>CurrentConnectionIDs</relatedStateVariable></argument>
+    </argumentList></action>
+  </actionList>
+  <serviceStateTable>
+    <stateVariable sendEvents="yes"><name>SourceProtocolInfo</name><dataType>string</dataType></stateVariable>
+    <stateVariable sendEvents="yes"><name>SinkProtocolInfo</name><dataType>string</dataType></stateVariable>
+    <stateVariable sendEvents="yes"><name>CurrentConnectionIDs</name><dataType>string</dataType></stateVariable>
+  </serviceStateTable>
+</scpd>"#;
+
+const PROTOCOL_INFO: &str = "http-get:*:image/jpeg:*,http-get:*:image/png:*,http-get:*:image/gif:*,\
+http-get:*:image/webp:*,http-get:*:image/bmp:*";
+
+/// 设备信息，UUID 由图片目录的绝对路径得出，重启后保持不变
+#[derive(Clone)]
+pub struct Device {
+    uuid: String,
+    name: String,
+    port: u16,
+}
+
+impl Device {
+    pub fn new(name: &str, pic_dir: &str, port: u16) -> Self {
+        let absolute = std::path::absolute(pic_dir).unwrap_or_else(|_| pic_dir.into());
+        let hash = hex::encode(Sha256::digest(absolute.to_string_lossy().as_bytes()));
+        // 按 UUID 第 5 版的格式设置版本号和变体位
+        let variant = (u8::from_str_radix(&hash[16..17], 16).unwrap_or(0) & 0x3) | 0x8;
+        let uuid = format!(
+            "{}-{}-5{}-{:x}{}-{}",
+            &hash[0..8],
+            &hash[8..12],
+            &hash[13..16],
+            variant,
+            &hash[17..20],
+            &hash[20..32]
+        );
+        Self {
+            uuid,
+            name: name.to_string(),
+            port,
+        }
+    }
+
+    fn usn(&self, nt: &str) -> String {
+        if nt.starts_with("uuid:") {
+            nt.to_string()
+        } else {
+            format!("uuid:{}::{}", self.uuid, nt)
+        }
+    }
+
+    /// 需要广播的通知类型
+    fn notification_types(&self) -> Vec<String> {
+        vec![
+            "upnp:rootdevice".to_string(),
+            format!("uuid:{}", self.uuid),
+            DEVICE_TYPE.to_string(),
+            CDS_TYPE.to_string(),
+            CMS_TYPE.to_string(),
+        ]
+    }
+
+    fn location(&self, ip: Ipv4Addr) -> String {
+        format!("http://{}:{}{}/description.xml", ip, self.port, DLNA_PREFIX)
+    }
+
+    fn description(&self) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0" xmlns:dlna="urn:schemas-dlna-org:device-1-0">
+  <specVersion><major>1</major><minor>0</minor></specVersion>
+  <device>
+    <deviceType>{device_type}</deviceType>
+    <friendlyName>{name}</friendlyName>
+    <manufacturer>pic_url</manufacturer>
+    <modelName>pic_url</modelName>
+    <modelNumber>{version}</modelNumber>
+    <UDN>uuid:{uuid}</UDN>
+    <dlna:X_DLNADOC>DMS-1.50</dlna:X_DLNADOC>
+    <serviceList>
+      <service>
+        <serviceType>{cds}</serviceType>
+        <serviceId>urn:upnp-org:serviceId:ContentDirectory</serviceId>
+        <SCPDURL>{prefix}/cds.xml</SCPDURL>
+        <controlURL>{prefix}/control/cds</controlURL>
+        <eventSubURL>{prefix}/event/cds</eventSubURL>
+      </service>
+      <service>
+        <serviceType>{cms}</serviceType>
+        <serviceId>urn:upnp-org:serviceId:ConnectionManager</serviceId>
+        <SCPDURL>{prefix}/cms.xml</SCPDURL>
+        <controlURL>{prefix}/control/cms</controlURL>
+        <eventSubURL>{prefix}/event/cms</eventSubURL>
+      </service>
+    </serviceList>
+    <presentationURL>/</presentationURL>
+  </device>
+</root>"#,
+            device_type = DEVICE_TYPE,
+            name = html_escape(&self.name),
+            version = env!("CARGO_PKG_VERSION"),
+            uuid = self.uuid,
+            cds = CDS_TYPE,
+            cms = CMS_TYPE,
+            prefix = DLNA_PREFIX,
+        )
+    }
+}
+
+fn notify_message(device: &Device, ip: Ipv4Addr, nt: &str, alive: bool) -> String {
+    if alive {
+        format!(
+            "NOTIFY * HTTP/1.1\r\nHOST: {}:{}\r\nCACHE-CONTROL: max-age={}\r\nLOCATION: {}\r\nNT: {}\r\nNTS: ssdp:alive\r\nSERVER: {}\r\nUSN: {}\r\n\r\n",
+            SSDP_ADDR,
+            SSDP_PORT,
+            MAX_AGE,
+            device.location(ip),
+            nt,
+            SERVER,
+            device.usn(nt)
+        )
+    } else {
+        format!(
+            "NOTIFY * HTTP/1.1\r\nHOST: {}:{}\r\nNT: {}\r\nNTS: ssdp:byebye\r\nUSN: {}\r\n\r\n",
+            SSDP_ADDR,
+            SSDP_PORT,
+            nt,
+            device.usn(nt)
+        )
+    }
+}
+
+fn search_response(device: &Device, ip: Ipv4Addr, st: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age={}\r\nDATE: {}\r\nEXT:\r\nLOCATION: {}\r\nSERVER: {}\r\nST: {}\r\nUSN: {}\r\n\r\n",
+        MAX_AGE,
+        httpdate::fmt_http_date(std::time::SystemTime::now()),
+        device.location(ip),
+        SERVER,
+        st,
+        device.usn(st)
+    )
+}
+
+fn announce(device: &Device, alive: bool) {
+    let Some(ip) = crate::lan_ipv4() else {
+        return;
+    };
+    let Ok(socket) = UdpSocket::bind((ip, 0)) else {
+        return;
+    };
+    for nt in device.notification_types() {
+        let _ = socket.send_to(notify_message(device, ip, &nt, alive).as_bytes(), (SSDP_ADDR, SSDP_PORT));
+    }
+}
+
+/// 监听 1900 端口的组播套接字，允许与其它 DLNA 服务共用端口
+fn ssdp_socket() -> std::io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, SSDP_PORT).into())?;
+    let socket: UdpSocket = socket.into();
+    socket.join_multicast_v4(&SSDP_ADDR, &Ipv4Addr::UNSPECIFIED)?;
+    Ok(socket)
+}
+
+/// 启动 SSDP 广播和搜索应答线程
+pub fn start(device: Device) -> std::io::Result<()> {
+    let socket = ssdp_socket()?;
+
+    let announcer = device.clone();
+    thread::Builder::new().name("ssdp-notify".to_string()).spawn(move || loop {
+        announce(&announcer, true);
+        thread::sleep(ANNOUNCE_INTERVAL);
+    })?;
+
+    thread::Builder::new().name("ssdp-search".to_string()).spawn(move || {
+        let mut buf = [0u8; 2048];
+        loop {
+            let (len, peer) = match socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(_) => continue,
+            };
+            let message = String::from_utf8_lossy(&buf[..len]);
+            if !message.starts_with("M-SEARCH") {
+                continue;
+            }
+            let st = message
+                .lines()
+                .find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    name.trim().eq_ignore_ascii_case("ST").then(|| value.trim().to_string())
+                })
+                .unwrap_or_default();
+            let targets = device.notification_types();
+            let matched: Vec<String> = if st == "ssdp:all" {
+                targets
+            } else if targets.contains(&st) {
+                vec![st]
+            } else {
+                continue;
+            };
+            let Some(ip) = crate::lan_ipv4() else {
+                continue;
+            };
+            for st in matched {
+                let _ = socket.send_to(search_response(&device, ip, &st).as_bytes(), peer);
+            }
+        }
+    })?;
+    Ok(())
+}
+
+/// 退出前广播 `ssdp:byebye`，让客户端尽快移除本设备
+pub fn stop(device: &Device) {
+    announce(device, false);
+}
+
+/// 某个目录下的直接子目录和图片，`images` 必须已排序
+fn children(images: &[String], dir: &str) -> (Vec<String>, Vec<String>) {
+    let prefix = if dir.is_empty() { String::new() } else { format!("{}/", dir) };
+    let mut dirs: Vec<String> = Vec::new();
+    let mut files = Vec::new();
+    for image in images.iter().filter(|p| p.starts_with(&prefix)) {
+        let rest = &image[prefix.len()..];
+        match rest.split_once('/') {
+            Some((name, _)) => {
+                let sub = format!("{}{}", prefix, name);
+                if dirs.last() != Some(&sub) {
+                    dirs.push(sub);
+                }
+            }
+            None => files.push(image.clone()),
+        }
+    }
+    (dirs, files)
+}
+
+fn parent_id(path: &str) -> String {
+    match path.rsplit_once('/') {
+        Some((parent, _)) => format!("{}{}", DIR_PREFIX, parent),
+        None => ROOT_ID.to_string(),
+    }
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+fn container_xml(id: &str, parent: &str, title: &str, child_count: usize) -> String {
+    format!(
+        r#"<container id="{}" parentID="{}" restricted="1" childCount="{}"><dc:title>{}</dc:title><upnp:class>object.container.storageFolder</upnp:class></container>"#,
+        html_escape(id),
+        html_escape(parent),
+        child_count,
+        html_escape(title)
+    )
+}
+
+fn item_xml(config: &AppConfig, base_url: &str, path: &str) -> String {
+    let mime = mime_guess::from_path(Path::new(path)).first_or_octet_stream();
+    let encoded = crate::url_path(path);
+    format!(
+        r#"<item id="{}{}" parentID="{}" restricted="1"><dc:title>{}</dc:title><upnp:class>object.item.imageItem.photo</upnp:class><upnp:albumArtURI>{}</upnp:albumArtURI><res protocolInfo="http-get:*:{}:*">{}</res></item>"#,
+        IMAGE_PREFIX,
+        html_escape(path),
+        html_escape(&parent_id(path)),
+        html_escape(file_name(path)),
+        html_escape(&format!("{}/thumb/{}", base_url, encoded)),
+        mime.essence_str(),
+        html_escape(&format!("{}{}", base_url, config.pic_url(path)))
+    )
+}
+
+fn didl(body: &str) -> String {
+    format!(
+        r#"<DIDL-Lite xmlns="urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:upnp="urn:schemas-upnp-org:metadata-1-0/upnp/">{}</DIDL-Lite>"#,
+        body
+    )
+}
+
+fn soap_response(service: &str, action: &str, body: &str) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/xml; charset=\"utf-8\"")
+        .insert_header(("EXT", ""))
+        .insert_header(("Server", SERVER))
+        .body(format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/"><s:Body><u:{action}Response xmlns:u="{service}">{body}</u:{action}Response></s:Body></s:Envelope>"#
+        ))
+}
+
+/// UPnP 错误 401 (Invalid Action)、701 (No such object) 等
+fn soap_fault(code: u16, description: &str) -> HttpResponse {
+    HttpResponse::InternalServerError()
+        .content_type("text/xml; charset=\"utf-8\"")
+        .body(format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/"><s:Body><s:Fault><faultcode>s:Client</faultcode><faultstring>UPnPError</faultstring><detail><UPnPError xmlns="urn:schemas-upnp-org:control-1-0"><errorCode>{}</errorCode><errorDescription>{}</errorDescription></UPnPError></detail></s:Fault></s:Body></s:Envelope>"#,
+            code, description
+        ))
+}
+
+/// 解析 SOAP 请求，返回动作名和参数
+fn parse_action(body: &str) -> Option<(String, Vec<(String, String)>)> {
+    let doc = roxmltree::Document::parse(body).ok()?;
+    let soap_body = doc.descendants().find(|n| n.tag_name().name() == "Body")?;
+    let action = soap_body.children().find(|n| n.is_element())?;
+    let args = action
+        .children()
+        .filter(|n| n.is_element())
+        .map(|n| (n.tag_name().name().to_string(), n.text().unwrap_or("").to_string()))
+        .collect();
+    Some((action.tag_name().name().to_string(), args))
+}
+
+fn browse(config: &AppConfig, images: &[String], base_url: &str, args: &[(String, String)]) -> HttpResponse {
+    let arg = |name: &str| {
+        args.iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+            .unwrap_or("")
+    };
+    let object_id = arg("ObjectID");
+    let start: usize = arg("StartingIndex").parse().unwrap_or(0);
+    let requested: usize = arg("RequestedCount").parse().unwrap_or(0);
+
+    let (result, returned, total) = if arg("BrowseFlag") == "BrowseMetadata" {
+        let entry = if object_id == ROOT_ID {
+            let (dirs, files) = children(images, "");
+            container_xml(ROOT_ID, "-1", &config.settings.dlna_name, dirs.len() + files.len())
+        } else if let Some(dir) = object_id.strip_prefix(DIR_PREFIX) {
+            let (dirs, files) = children(images, dir);
+            if dirs.is_empty() && files.is_empty() {
+                return soap_fault(701, "No such object");
+            }
+            container_xml(object_id, &parent_id(dir), file_name(dir), dirs.len() + files.len())
+        } else if let Some(path) = object_id.strip_prefix(IMAGE_PREFIX).filter(|p| images.binary_search_by(|i| i.as_str().cmp(p)).is_ok()) {
+            item_xml(config, base_url, path)
+        } else {
+            return soap_fault(701, "No such object");
+        };
+        (didl(&entry), 1, 1)
+    } else {
+        let dir = if object_id == ROOT_ID {
+            ""
+        } else if let Some(dir) = object_id.strip_prefix(DIR_PREFIX) {
+            dir
+        } else {
+            return soap_fault(701, "No such object");
+        };
+        let (dirs, files) = children(images, dir);
+        if !dir.is_empty() && dirs.is_empty() && files.is_empty() {
+            return soap_fault(701, "No such object");
+        }
+        let total = dirs.len() + files.len();
+        let count = if requested == 0 { total } else { requested };
+        let mut body = String::new();
+        let mut returned = 0;
+        for (i, entry) in dirs.iter().chain(files.iter()).enumerate().skip(start).take(count) {
+            if i < dirs.len() {
+                let (sub_dirs, sub_files) = children(images, entry);
+                body.push_str(&container_xml(
+                    &format!("{}{}", DIR_PREFIX, entry),
+                    &parent_id(entry),
+                    file_name(entry),
+                    sub_dirs.len() + sub_files.len(),
+                ));
+            } else {
+                body.push_str(&item_xml(config, base_url, entry));
+            }
+            returned += 1;
+        }
+        (didl(&body), returned, total)
+    };
+
+    soap_response(
+        CDS_TYPE,
+        "Browse",
+        &format!(
+            "<Result>{}</Result><NumberReturned>{}</NumberReturned><TotalMatches>{}</TotalMatches><UpdateID>{}</UpdateID>",
+            html_escape(&result),
+            returned,
+            total,
+            system_update_id(config)
+        ),
+    )
+}
+
+/// 图片列表变化时客户端需要刷新缓存，用扫描到的图片数量近似
+fn system_update_id(config: &AppConfig) -> usize {
+    config.index.len()
+}
+
+async fn description(device: web::Data<Device>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/xml; charset=\"utf-8\"")
+        .body(device.description())
+}
+
+async fn cds_scpd() -> HttpResponse {
+    HttpResponse::Ok().content_type("text/xml; charset=\"utf-8\"").body(CDS_SCPD)
+}
+
+async fn cms_scpd() -> HttpResponse {
+    HttpResponse::Ok().content_type("text/xml; charset=\"utf-8\"").body(CMS_SCPD)
+}
+
+async fn cds_control(req: HttpRequest, body: String, config: web::Data<AppConfig>) -> Result<HttpResponse> {
+    let Some((action, args)) = parse_action(&body) else {
+        return Ok(soap_fault(401, "Invalid Action"));
+    };
+    Ok(match action.as_str() {
+        "Browse" => {
+            // 播放设备都在局域网内，使用它们实际访问的地址而不是 `--base-url`
+            let base_url = {
+                let info = req.connection_info();
+                format!("{}://{}", info.scheme(), info.host())
+            };
+            let scan = config.clone();
+            // 播放设备没有身份，只能浏览公共目录
+            let images = web::block(move || Viewer::public(&scan).filter(scan.current_images())).await?;
+            browse(&config, &images, &base_url, &args)
+        }
+        "GetSearchCapabilities" => soap_response(CDS_TYPE, &action, "<SearchCaps></SearchCaps>"),
+        "GetSortCapabilities" => soap_response(CDS_TYPE, &action, "<SortCaps></SortCaps>"),
+        "GetSystemUpdateID" => soap_response(
+            CDS_TYPE,
+            &action,
+            &format!("<Id>{}</Id>", system_update_id(&config)),
+        ),
+        _ => soap_fault(401, "Invalid Action"),
+    })
+}
+
+async fn cms_control(body: String) -> HttpResponse {
+    match parse_action(&body).map(|(action, _)| action).as_deref() {
+        Some("GetProtocolInfo") => soap_response(
+            CMS_TYPE,
+            "GetProtocolInfo",
+            &format!("<Source>{}</Source><Sink></Sink>", PROTOCOL_INFO),
+        ),
+        Some("GetCurrentConnectionIDs") => soap_response(
+            CMS_TYPE,
+            "GetCurrentConnectionIDs",
+            "<ConnectionIDs>0</ConnectionIDs>",
+        ),
+        _ => soap_fault(401, "Invalid Action"),
+    }
+}
+
+/// 不支持事件订阅，但部分电视会先订阅，返回成功以免它们放弃连接
+async fn event_subscribe() -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(("SID", "uuid:00000000-0000-0000-0000-000000000000"))
+        .insert_header(("TIMEOUT", "Second-1800"))
+        .finish()
+}
+
+/// 注册 `/dlna` 路由
+pub fn configure(cfg: &mut web::ServiceConfig, device: Device) {
+    cfg.app_data(web::Data::new(device))
+        .route(&format!("{}/description.xml", DLNA_PREFIX), web::get().to(description))
+        .route(&format!("{}/cds.xml", DLNA_PREFIX), web::get().to(cds_scpd))
+        .route(&format!("{}/cms.xml", DLNA_PREFIX), web::get().to(cms_scpd))
+        .route(&format!("{}/control/cds", DLNA_PREFIX), web::post().to(cds_control))
+        .route(&format!("{}/control/cms", DLNA_PREFIX), web::post().to(cms_control))
+        .route(
+            &format!("{}/event/{{service}}", DLNA_PREFIX),
+            web::route().to(event_subscribe),
+        );
+}
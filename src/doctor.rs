@@ -0,0 +1,167 @@
+//! 启动自检：图片目录、缩略图目录、端口和图片解码器。
+//! 启动时先检查，有错误时打印原因后退出；`pic_url doctor` 只运行检查，不启动服务
+
+use crate::webdav::WebDavSource;
+use crate::{dir_writable, Config, IMAGE_EXTENSIONS, THUMB_DIR_NAME};
+use std::fs;
+use std::net::TcpListener;
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Level {
+    Ok,
+    Warn,
+    Error,
+}
+
+struct Check {
+    name: &'static str,
+    level: Level,
+    detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, level: Level, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            level,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// 图片目录存在且可以列出；不存在时启动会自动创建，只要上级目录可写
+fn check_pic_dir(settings: &Config) -> Check {
+    const NAME: &str = "图片目录";
+    if let Some(url) = &settings.webdav_url {
+        let source = WebDavSource::new(url, settings.webdav_user.as_deref(), settings.webdav_password.as_deref());
+        return if source.ping() {
+            Check::new(NAME, Level::Ok, format!("WebDAV {} 可以访问", url))
+        } else {
+            // 服务器可能稍后才上线，启动后会定期重试
+            Check::new(NAME, Level::Warn, format!("WebDAV {} 暂时无法访问", url))
+        };
+    }
+    let dir = Path::new(&settings.pic_dir);
+    match fs::metadata(dir) {
+        Ok(meta) if !meta.is_dir() => Check::new(NAME, Level::Error, format!("{} 不是目录", settings.pic_dir)),
+        Ok(_) => match fs::read_dir(dir) {
+            Ok(_) => Check::new(NAME, Level::Ok, format!("{} 可以读取", settings.pic_dir)),
+            Err(e) => Check::new(NAME, Level::Error, format!("{} 无法读取: {}", settings.pic_dir, e)),
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => match creatable(dir) {
+            true => Check::new(NAME, Level::Ok, format!("{} 不存在，启动时创建", settings.pic_dir)),
+            false => Check::new(NAME, Level::Error, format!("{} 不存在且无法创建", settings.pic_dir)),
+        },
+        Err(e) => Check::new(NAME, Level::Error, format!("{} 无法访问: {}", settings.pic_dir, e)),
+    }
+}
+
+/// 缩略图目录可写；不存在时检查能否创建
+fn check_thumb_dir(settings: &Config) -> Check {
+    const NAME: &str = "缩略图目录";
+    let dir = Path::new(&settings.pic_dir).join(THUMB_DIR_NAME);
+    let display = dir.display();
+    if dir.is_dir() {
+        if dir_writable(&dir) {
+            Check::new(NAME, Level::Ok, format!("{} 可以写入", display))
+        } else {
+            Check::new(NAME, Level::Error, format!("{} 无法写入", display))
+        }
+    } else if creatable(&dir) {
+        Check::new(NAME, Level::Ok, format!("{} 不存在，启动时创建", display))
+    } else {
+        Check::new(NAME, Level::Error, format!("{} 不存在且无法创建", display))
+    }
+}
+
+/// 最近的已存在的上级目录可写
+fn creatable(dir: &Path) -> bool {
+    dir.ancestors()
+        .skip(1)
+        .map(|parent| if parent.as_os_str().is_empty() { Path::new(".") } else { parent })
+        .find(|parent| parent.exists())
+        .is_some_and(|parent| parent.is_dir() && dir_writable(parent))
+}
+
+fn check_port(settings: &Config) -> Check {
+    const NAME: &str = "端口";
+    match TcpListener::bind(("0.0.0.0", settings.port)) {
+        Ok(_) => Check::new(NAME, Level::Ok, format!("{} 可以监听", settings.port)),
+        Err(e) => Check::new(NAME, Level::Error, format!("{} 无法监听: {}", settings.port, e)),
+    }
+}
+
+/// 支持的扩展名都能解码原图并编码缩略图
+fn check_decoders() -> Check {
+    const NAME: &str = "图片格式";
+    let missing: Vec<&str> = IMAGE_EXTENSIONS
+        .iter()
+        .copied()
+        .filter(|ext| {
+            !image::ImageFormat::from_extension(ext).is_some_and(|f| f.reading_enabled() && f.writing_enabled())
+        })
+        .collect();
+    if missing.is_empty() {
+        Check::new(NAME, Level::Ok, IMAGE_EXTENSIONS.join(", "))
+    } else {
+        Check::new(NAME, Level::Warn, format!("{} 无法生成缩略图 (image 库未启用对应格式)", missing.join(", ")))
+    }
+}
+
+fn run_checks(settings: &Config) -> Vec<Check> {
+    vec![check_pic_dir(settings), check_thumb_dir(settings), check_port(settings), check_decoders()]
+}
+
+fn check_line(check: &Check) -> String {
+    let label = match check.level {
+        Level::Ok => "正常",
+        Level::Warn => "警告",
+        Level::Error => "错误",
+    };
+    format!("  [{}] {}: {}", label, check.name, check.detail)
+}
+
+fn print_checks(checks: &[Check]) {
+    for check in checks {
+        if check.level == Level::Ok {
+            println!("{}", check_line(check));
+        } else {
+            eprintln!("{}", check_line(check));
+        }
+    }
+}
+
+/// 启动前自检，结果写入服务器日志，有错误时全部记录后退出
+pub fn check_startup(settings: &Config) {
+    let checks = run_checks(settings);
+    log::info!("启动自检:");
+    for check in &checks {
+        match check.level {
+            Level::Ok => log::info!("{}", check_line(check)),
+            Level::Warn => log::warn!("{}", check_line(check)),
+            Level::Error => log::error!("{}", check_line(check)),
+        }
+    }
+    if checks.iter().any(|check| check.level == Level::Error) {
+        log::error!("错误: 自检未通过，请修正上面的问题后重新启动，或运行 `pic_url doctor` 查看详情");
+        std::process::exit(1);
+    }
+}
+
+/// `pic_url doctor [选项]`：使用与启动服务相同的选项和环境变量，只运行检查。
+/// 全部通过时退出码为 0，有错误时为 1
+pub fn run_command(settings: &Config) -> ! {
+    let checks = run_checks(settings);
+    println!("pic_url doctor");
+    print_checks(&checks);
+    let errors = checks.iter().filter(|check| check.level == Level::Error).count();
+    let warnings = checks.iter().filter(|check| check.level == Level::Warn).count();
+    println!();
+    if errors == 0 {
+        println!("检查通过 ({} 个警告)", warnings);
+        std::process::exit(0);
+    }
+    eprintln!("{} 个错误，{} 个警告", errors, warnings);
+    std::process::exit(1);
+}
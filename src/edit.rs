@@ -0,0 +1,262 @@
+//! 修改原图（需管理令牌）：
+//!
+//! - `POST /api/images/{path}/rotate`：顺时针旋转 90°/180°/270°。JPEG 只改写 EXIF 方向标记，
+//!   图像数据不重新压缩；其他格式解码旋转后按原格式保存
+//! - `POST /api/images/{path}/edit`：裁剪和/或缩小，另存为同目录下的新文件或覆盖原图
+
+use crate::admin::AdminAuth;
+use crate::errors::{ApiError, AppError};
+use crate::maintenance::Writable;
+use crate::upload::unique_name;
+use crate::versions;
+use crate::{decode_oriented, is_valid_relative, jpeg, open_oriented, thumbs, AppConfig, ImageInfo};
+use actix_web::{post, web, HttpResponse, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::ImageReader;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+
+/// 重新编码 JPEG 时的质量
+const JPEG_QUALITY: u8 = 95;
+
+#[derive(Deserialize)]
+pub struct RotateRequest {
+    /// 顺时针旋转的角度：90、180 或 270
+    degrees: u16,
+}
+
+/// 裁剪区域，按转正后的像素坐标
+#[derive(Deserialize)]
+pub struct Crop {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+#[derive(Deserialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SaveMode {
+    /// 另存为同目录下的 `<原名>-edited.<扩展名>`
+    #[default]
+    Copy,
+    /// 覆盖原图
+    Replace,
+}
+
+#[derive(Deserialize)]
+pub struct EditRequest {
+    crop: Option<Crop>,
+    /// 等比缩小到不超过这个宽高，不会放大
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+    #[serde(default)]
+    save: SaveMode,
+}
+
+#[derive(Serialize)]
+struct RotateResponse {
+    #[serde(flatten)]
+    image: ImageInfo,
+    /// 是否没有重新压缩图像数据
+    lossless: bool,
+}
+
+fn is_jpeg(relative: &str) -> bool {
+    let ext = relative.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()).unwrap_or_default();
+    ext == "jpg" || ext == "jpeg"
+}
+
+/// 先写入同目录下的临时文件再改名，写到一半失败时原来的文件不受影响；
+/// 覆盖已有的图片时先把它保存为历史版本
+pub fn write_image(config: &AppConfig, relative: &str, data: &[u8]) -> io::Result<()> {
+    let path = config.fs_path(relative);
+    let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    // 以点开头、不是图片扩展名，扫描和文件监听都会忽略
+    let tmp = path.with_file_name(format!(".{}.edit.tmp", name));
+    fs::write(&tmp, data)?;
+    if path.exists() {
+        if let Err(e) = versions::save(config, relative) {
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
+    }
+    if let Err(e) = fs::rename(&tmp, &path) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    forget(config, relative);
+    Ok(())
+}
+
+/// 原图改变后删除缩略图和缓存的尺寸、拍摄信息，下次访问时重新生成
+fn forget(config: &AppConfig, relative: &str) {
+    thumbs::remove(&config.thumb_dir, relative);
+    config.dimensions.forget(relative);
+    config.captures.forget(relative);
+    config.quarantine.forget(relative);
+}
+
+/// 按原图的格式编码；JPEG 不经过 `DynamicImage::save` 是为了指定质量
+fn encode(img: &image::DynamicImage, relative: &str) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    let result = if is_jpeg(relative) {
+        img.to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY))
+    } else {
+        let format = image::ImageFormat::from_path(relative).map_err(io::Error::other)?;
+        img.write_to(&mut io::Cursor::new(&mut data), format)
+    };
+    result.map_err(io::Error::other)?;
+    Ok(data)
+}
+
+/// 按原图的格式重新编码，JPEG 保留原图 `original` 中的 EXIF
+pub fn reencode(img: &image::DynamicImage, relative: &str, original: &[u8]) -> io::Result<Vec<u8>> {
+    let encoded = encode(img, relative)?;
+    if is_jpeg(relative) {
+        return Ok(jpeg::copy_exif(original, &encoded).unwrap_or(encoded));
+    }
+    Ok(encoded)
+}
+
+/// 旋转原图，返回是否无损
+fn rotate_image(config: &AppConfig, relative: &str, quarter_turns: u8) -> io::Result<bool> {
+    let path = config.fs_path(relative);
+    if is_jpeg(relative) {
+        let data = fs::read(&path)?;
+        if let Some(rotated) = jpeg::rotate(&data, quarter_turns) {
+            write_image(config, relative, &rotated)?;
+            return Ok(true);
+        }
+    }
+    // 解码时已经按原来的方向标记转正，保存的文件不再带有方向标记
+    let img = open_oriented(&path).map_err(io::Error::other)?;
+    let img = match quarter_turns {
+        1 => img.rotate90(),
+        2 => img.rotate180(),
+        _ => img.rotate270(),
+    };
+    write_image(config, relative, &encode(&img, relative)?)?;
+    Ok(false)
+}
+
+/// 检查图片能否修改：WebDAV 来源只读，GIF 和 ICO 重新编码会丢失动画和多个尺寸
+async fn check_editable(config: &AppConfig, relative: &str) -> Result<()> {
+    if config.webdav.is_some() {
+        return Err(ApiError::bad_request("editing is not supported for a WebDAV source").into());
+    }
+    if !is_valid_relative(relative) || !config.image_exists(relative).await? {
+        return Err(ApiError::not_found("Image not found").into());
+    }
+    let ext = relative.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()).unwrap_or_default();
+    if ext == "gif" || ext == "ico" {
+        return Err(ApiError::bad_request(format!("editing {} images is not supported", ext)).into());
+    }
+    Ok(())
+}
+
+#[post("/api/images/{path:.*}/rotate")]
+pub async fn rotate(
+    _auth: AdminAuth,
+    _writable: Writable,
+    path: web::Path<String>,
+    body: web::Json<RotateRequest>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let relative = path.into_inner();
+    let quarter_turns = match body.degrees {
+        90 => 1,
+        180 => 2,
+        270 => 3,
+        _ => return Err(ApiError::bad_request("degrees must be 90, 180 or 270").into()),
+    };
+    check_editable(&config, &relative).await?;
+    let config = config.into_inner();
+    let path = relative.clone();
+    let response = web::block(move || -> io::Result<RotateResponse> {
+        let lossless = rotate_image(&config, &relative, quarter_turns)?;
+        Ok(RotateResponse {
+            image: ImageInfo::new(&config, &relative),
+            lossless,
+        })
+    })
+    .await?
+    .map_err(|e| AppError::io(&path, e))?;
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// 裁剪、缩小并保存，返回保存的路径；区域超出图片时返回 `InvalidInput`
+fn edit_image(config: &AppConfig, relative: &str, request: &EditRequest) -> io::Result<String> {
+    let path = config.fs_path(relative);
+    let data = fs::read(&path)?;
+    let reader = ImageReader::new(io::Cursor::new(&data)).with_guessed_format()?;
+    let mut img = decode_oriented(reader).map_err(io::Error::other)?;
+    if let Some(crop) = &request.crop {
+        let inside = crop.x.checked_add(crop.width).is_some_and(|right| right <= img.width())
+            && crop.y.checked_add(crop.height).is_some_and(|bottom| bottom <= img.height());
+        if !inside {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("crop rectangle is outside the {}x{} image", img.width(), img.height()),
+            ));
+        }
+        img = img.crop_imm(crop.x, crop.y, crop.width, crop.height);
+    }
+    let max_width = request.max_width.unwrap_or(u32::MAX);
+    let max_height = request.max_height.unwrap_or(u32::MAX);
+    if img.width() > max_width || img.height() > max_height {
+        img = img.resize(max_width, max_height, FilterType::Lanczos3);
+    }
+
+    let encoded = reencode(&img, relative, &data)?;
+    let target = match request.save {
+        SaveMode::Replace => relative.to_string(),
+        SaveMode::Copy => {
+            let (folder, name) = relative.rsplit_once('/').unwrap_or(("", relative));
+            let (stem, ext) = name.rsplit_once('.').unwrap_or((name, ""));
+            let dir = config.fs_path(folder);
+            let name = unique_name(&dir, &format!("{}-edited.{}", stem, ext));
+            if folder.is_empty() { name } else { format!("{}/{}", folder, name) }
+        }
+    };
+    write_image(config, &target, &encoded)?;
+    if target != relative {
+        // 新文件立即加入索引，分配短 ID 并推送 `image.added`
+        config.rescan();
+    }
+    Ok(target)
+}
+
+#[post("/api/images/{path:.*}/edit")]
+pub async fn edit(
+    _auth: AdminAuth,
+    _writable: Writable,
+    path: web::Path<String>,
+    body: web::Json<EditRequest>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let relative = path.into_inner();
+    let request = body.into_inner();
+    if request.crop.is_none() && request.max_width.is_none() && request.max_height.is_none() {
+        return Err(ApiError::bad_request("specify crop, max_width or max_height").into());
+    }
+    if request.crop.as_ref().is_some_and(|crop| crop.width == 0 || crop.height == 0)
+        || request.max_width == Some(0)
+        || request.max_height == Some(0)
+    {
+        return Err(ApiError::bad_request("width and height must be positive").into());
+    }
+    check_editable(&config, &relative).await?;
+    let config = config.into_inner();
+    let path = relative.clone();
+    let image = web::block(move || -> io::Result<ImageInfo> {
+        let target = edit_image(&config, &relative, &request)?;
+        Ok(ImageInfo::new(&config, &target))
+    })
+    .await?
+    .map_err(|e| AppError::io(&path, e))?;
+    Ok(HttpResponse::Ok().json(image))
+}
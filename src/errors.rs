@@ -0,0 +1,285 @@
+//! 统一的错误响应：`/api` 下的接口出错时返回
+//! `{"error": {"code": "...", "message": "..."}}`，浏览器访问不存在的图片或页面时显示 404 页面
+
+use crate::logging::{self, RequestError};
+use crate::{page_context, request_id, AppConfig};
+use actix_web::body::MessageBody;
+use actix_web::dev::ServiceResponse;
+use actix_web::http::{header, StatusCode};
+use actix_web::middleware::ErrorHandlerResponse;
+use actix_web::error::BlockingError;
+use actix_web::{web, HttpMessage, HttpResponse, ResponseError};
+use image::ImageError;
+use serde::Serialize;
+use std::io;
+
+/// 接口错误，`code` 由状态码得出（如 `not_found`、`bad_request`）
+#[derive(Debug, thiserror::Error)]
+#[error("{message}")]
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+    /// 附加信息，如上传失败的文件列表
+    details: Option<serde_json::Value>,
+    /// 请求 ID，由 [`handle`] 填入
+    request_id: Option<String>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            details: None,
+            request_id: None,
+        }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, message)
+    }
+
+    pub fn with_details(mut self, details: impl Serialize) -> Self {
+        self.details = serde_json::to_value(details).ok();
+        self
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut error = serde_json::json!({
+            "code": code(self.status),
+            "message": self.message,
+        });
+        if let Some(details) = &self.details {
+            error["details"] = details.clone();
+        }
+        if let Some(id) = &self.request_id {
+            error["request_id"] = id.clone().into();
+        }
+        HttpResponse::build(self.status).json(serde_json::json!({ "error": error }))
+    }
+}
+
+/// 处理请求时遇到的错误。读写或解码某个文件失败时带上它的相对路径，
+/// 按失败原因返回对应的状态码（没有权限 403、文件损坏 422 等），而不是笼统的 500
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error(transparent)]
+    Api(#[from] ApiError),
+    #[error("{path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("{path}: {source}")]
+    Image {
+        path: String,
+        #[source]
+        source: ImageError,
+    },
+    /// 阻塞任务没有执行完（服务正在停止）
+    #[error("server is shutting down")]
+    Canceled(#[from] BlockingError),
+}
+
+impl AppError {
+    pub fn io(path: &str, source: io::Error) -> Self {
+        Self::Io {
+            path: path.to_string(),
+            source,
+        }
+    }
+
+    pub fn image(path: &str, source: ImageError) -> Self {
+        match source {
+            ImageError::IoError(source) => Self::io(path, source),
+            source => Self::Image {
+                path: path.to_string(),
+                source,
+            },
+        }
+    }
+}
+
+fn io_status(kind: io::ErrorKind) -> StatusCode {
+    match kind {
+        io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        io::ErrorKind::PermissionDenied => StatusCode::FORBIDDEN,
+        io::ErrorKind::AlreadyExists => StatusCode::CONFLICT,
+        io::ErrorKind::InvalidInput => StatusCode::BAD_REQUEST,
+        // 内容无法识别的文件，例如只写了一半或已损坏的图片
+        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => StatusCode::UNPROCESSABLE_ENTITY,
+        io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded => StatusCode::INSUFFICIENT_STORAGE,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::Api(e) => e.status,
+            AppError::Io { source, .. } => io_status(source.kind()),
+            AppError::Image { source, .. } => match source {
+                ImageError::Decoding(_) | ImageError::Unsupported(_) | ImageError::Limits(_) => {
+                    StatusCode::UNPROCESSABLE_ENTITY
+                }
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            AppError::Canceled(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            AppError::Api(e) => e.error_response(),
+            _ => ApiError::new(self.status_code(), self.to_string()).error_response(),
+        }
+    }
+}
+
+/// 状态码对应的错误代码：`Not Found` → `not_found`
+pub fn code(status: StatusCode) -> String {
+    status
+        .canonical_reason()
+        .unwrap_or("error")
+        .to_ascii_lowercase()
+        .replace([' ', '-'], "_")
+}
+
+/// 所有 4xx/5xx 响应都经过这里：带错误的写入日志；`/api` 下还不是 JSON 的（提取器、文件读取等框架产生的错误）
+/// 改成统一格式，JSON 错误加上请求 ID；其他路径的 404 在浏览器访问时显示 404 页面
+pub fn handle<B: MessageBody + 'static>(res: ServiceResponse<B>) -> actix_web::Result<ErrorHandlerResponse<B>> {
+    let request_id = request_id::of(res.request());
+    if let Some(error) = res.response().error() {
+        // JSON 日志中错误随请求事件一起输出
+        if logging::is_json() {
+            res.request().extensions_mut().insert(RequestError(error.to_string()));
+        } else {
+            let level = if res.status().is_server_error() { log::Level::Error } else { log::Level::Warn };
+            log::log!(
+                level,
+                "[{}] {} {} -> {}: {}",
+                request_id.as_deref().unwrap_or("-"),
+                res.request().method(),
+                res.request().path(),
+                res.status().as_u16(),
+                error
+            );
+        }
+    }
+    let content_type = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let api = res
+        .request()
+        .path()
+        .strip_prefix(crate::mount_path(res.request()).as_str())
+        .is_some_and(|path| path.starts_with("/api/"));
+    let wants_html = res
+        .request()
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"));
+    let convert = if api {
+        !content_type.starts_with("application/json")
+    } else {
+        res.status() == StatusCode::NOT_FOUND && wants_html && !content_type.starts_with("text/html")
+    };
+    if !convert {
+        return Ok(match request_id {
+            Some(id) if content_type.starts_with("application/json") => with_request_id(res, &id),
+            _ => ErrorHandlerResponse::Response(res.map_into_left_body()),
+        });
+    }
+
+    let status = res.status();
+    let (req, res) = res.into_parts();
+    let error = res.error().map(|e| e.to_string());
+    let mut headers = res.headers().clone();
+    headers.remove(header::CONTENT_TYPE);
+    headers.remove(header::CONTENT_LENGTH);
+    // 没有附带错误时使用处理函数写入的纯文本正文作为说明
+    let message = error
+        .or_else(|| {
+            res.into_body()
+                .try_into_bytes()
+                .ok()
+                .map(|bytes| String::from_utf8_lossy(&bytes).trim().to_string())
+        })
+        .filter(|message| !message.is_empty());
+
+    let mut new_res = if api {
+        let message = message.unwrap_or_else(|| status.canonical_reason().unwrap_or("Error").to_string());
+        let mut error = ApiError::new(status, message);
+        error.request_id = request_id;
+        error.error_response()
+    } else {
+        // 模板渲染失败时仍然返回原来的纯文本
+        not_found_page(&req, message.as_deref())
+            .unwrap_or_else(|| HttpResponse::NotFound().body(message.unwrap_or_default()))
+    };
+    for (name, value) in headers.iter() {
+        new_res.headers_mut().append(name.clone(), value.clone());
+    }
+    Ok(ErrorHandlerResponse::Response(
+        ServiceResponse::new(req, new_res).map_into_right_body(),
+    ))
+}
+
+/// 在处理函数返回的 JSON 错误中加上 `error.request_id`，正文不是统一格式时原样返回
+fn with_request_id<B: MessageBody + 'static>(res: ServiceResponse<B>, id: &str) -> ErrorHandlerResponse<B> {
+    let (req, res) = res.into_parts();
+    let (head, body) = res.into_parts();
+    let bytes = match body.try_into_bytes() {
+        Ok(bytes) => bytes,
+        Err(body) => {
+            return ErrorHandlerResponse::Response(ServiceResponse::new(req, head.set_body(body)).map_into_left_body())
+        }
+    };
+    let body = match serde_json::from_slice::<serde_json::Value>(&bytes) {
+        Ok(mut value) if value["error"].is_object() => {
+            value["error"]["request_id"] = id.into();
+            serde_json::to_vec(&value).map(web::Bytes::from).unwrap_or(bytes)
+        }
+        _ => bytes,
+    };
+    let res = head.set_body(body).map_into_boxed_body();
+    ErrorHandlerResponse::Response(ServiceResponse::new(req, res).map_into_right_body())
+}
+
+/// 按主题渲染的 404 页面，模板渲染失败时返回 `None`
+fn not_found_page(req: &actix_web::HttpRequest, message: Option<&str>) -> Option<HttpResponse> {
+    let config = req.app_data::<web::Data<AppConfig>>()?;
+    let mut context = page_context(config);
+    context.insert("path", req.path());
+    context.insert("message", message.unwrap_or("Page not found"));
+    match config.templates.render("404.html", &context) {
+        Ok(html) => Some(
+            HttpResponse::NotFound()
+                .content_type("text/html; charset=utf-8")
+                .body(html),
+        ),
+        Err(e) => {
+            log::error!("页面渲染失败: {}", e);
+            None
+        }
+    }
+}
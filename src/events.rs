@@ -0,0 +1,284 @@
+//! 图库实时事件：扫描发现的增删通过 `GET /api/events` (Server-Sent Events) 推送给画廊页面，
+//! 本地目录的变化由文件监听触发扫描，不再需要前端轮询；
+//! 无法使用 SSE/WebSocket 的客户端可以用 `GET /api/changes/wait` 长轮询
+
+use crate::homes::Viewer;
+use crate::webhook::EventKind;
+use crate::{is_reserved_dir, AppConfig};
+use actix_web::{get, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// 每个连接最多积压的事件数，超过后客户端会收到 `resync`，需要重新获取完整列表
+const CHANNEL_CAPACITY: usize = 1024;
+/// 没有事件时发送注释行的间隔，防止代理和浏览器断开空闲连接
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(25);
+/// 文件变化后等待目录安静下来再扫描，批量复制时只扫描一次
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+/// 长轮询默认和最长的等待时间（秒）
+const DEFAULT_WAIT_SECS: u64 = 30;
+const MAX_WAIT_SECS: u64 = 120;
+
+#[derive(Serialize, Clone)]
+pub struct LibraryEvent {
+    /// 递增的事件序号，长轮询用它作为游标
+    pub seq: u64,
+    #[serde(skip)]
+    pub kind: EventKind,
+    pub path: String,
+    pub name: String,
+}
+
+/// 最近的事件，供长轮询补齐两次请求之间的变化
+struct History {
+    seq: u64,
+    events: VecDeque<LibraryEvent>,
+}
+
+impl Default for History {
+    /// 序号从启动时的毫秒时间戳开始，重启前拿到的游标一定小于它，会被识别为过期
+    fn default() -> Self {
+        let seq = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        Self {
+            seq,
+            events: VecDeque::new(),
+        }
+    }
+}
+
+pub struct EventBus {
+    sender: broadcast::Sender<LibraryEvent>,
+    history: Mutex<History>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(CHANNEL_CAPACITY).0,
+            history: Mutex::new(History::default()),
+        }
+    }
+}
+
+impl EventBus {
+    pub fn subscribe(&self) -> broadcast::Receiver<LibraryEvent> {
+        self.sender.subscribe()
+    }
+
+    /// 发布事件；没有订阅者时只记入历史
+    pub fn publish(&self, kind: EventKind, path: &str) {
+        // 在锁内分配序号并发送，保证订阅者收到的顺序与序号一致
+        let Ok(mut history) = self.history.lock() else {
+            return;
+        };
+        history.seq += 1;
+        let event = LibraryEvent {
+            seq: history.seq,
+            kind,
+            path: path.to_string(),
+            name: path.rsplit('/').next().unwrap_or(path).to_string(),
+        };
+        if history.events.len() == CHANNEL_CAPACITY {
+            history.events.pop_front();
+        }
+        history.events.push_back(event.clone());
+        let _ = self.sender.send(event);
+    }
+
+    /// 序号大于 `since` 的事件和当前游标；`since` 早于保留的历史
+    /// 或来自重启前的进程时返回 `None`，客户端需要重新获取完整列表
+    fn since(&self, since: u64) -> (u64, Option<Vec<LibraryEvent>>) {
+        let Ok(history) = self.history.lock() else {
+            return (0, None);
+        };
+        let oldest = history.events.front().map(|e| e.seq).unwrap_or(history.seq + 1);
+        if since > history.seq || since + 1 < oldest {
+            return (history.seq, None);
+        }
+        let events = history.events.iter().filter(|e| e.seq > since).cloned().collect();
+        (history.seq, Some(events))
+    }
+}
+
+/// 格式化一条 SSE 消息
+fn message(event: &str, data: &str) -> web::Bytes {
+    web::Bytes::from(format!("event: {}\ndata: {}\n\n", event, data))
+}
+
+#[get("/api/events")]
+pub async fn stream(viewer: Viewer, config: web::Data<AppConfig>) -> HttpResponse {
+    let receivers = (config.events.subscribe(), config.scan.subscribe(), config.maintenance.subscribe());
+    // 断线后浏览器 3 秒后自动重连，重连后页面会重新获取完整列表
+    let hello = futures_util::stream::once(async {
+        Ok::<_, actix_web::Error>(web::Bytes::from_static(b"retry: 3000\n\n"))
+    });
+    let state = (receivers, viewer);
+    let events = futures_util::stream::unfold(state, |(receivers, viewer)| async move {
+        let (mut events, mut progress, mut maintenance) = receivers;
+        let chunk = tokio::select! {
+            event = events.recv() => match event {
+                // 看不到的图片不推送，空的块不会发给客户端
+                Ok(event) if !viewer.can_see(&event.path) => web::Bytes::new(),
+                Ok(event) => {
+                    let data = serde_json::to_string(&event).unwrap_or_default();
+                    message(event.kind.as_str(), &data)
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => message("resync", "{}"),
+                Err(broadcast::error::RecvError::Closed) => return None,
+            },
+            // 扫描进度只关心最新状态，落后时丢弃旧的即可
+            Ok(status) = progress.recv() => message("scan", &serde_json::to_string(&status).unwrap_or_default()),
+            Ok(status) = maintenance.recv() => {
+                message("maintenance", &serde_json::to_string(&status).unwrap_or_default())
+            }
+            _ = tokio::time::sleep(KEEPALIVE_INTERVAL) => web::Bytes::from_static(b": keepalive\n\n"),
+        };
+        Some((Ok::<_, actix_web::Error>(chunk), ((events, progress, maintenance), viewer)))
+    });
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        // 关闭 Nginx 的响应缓冲，否则事件会被攒到一起才发出
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(futures_util::StreamExt::chain(hello, events))
+}
+
+#[derive(Deserialize)]
+pub struct WaitQuery {
+    since: Option<u64>,
+    timeout: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct ChangeEvent {
+    seq: u64,
+    event: &'static str,
+    path: String,
+    name: String,
+}
+
+#[derive(Serialize)]
+struct ChangesResponse {
+    /// 下一次请求作为 `since` 传回的游标
+    cursor: u64,
+    /// 为 `true` 时 `since` 已失效（太旧或服务重启过），需要重新获取 `/api/images`
+    resync: bool,
+    events: Vec<ChangeEvent>,
+}
+
+impl ChangesResponse {
+    fn new(cursor: u64, events: Option<Vec<LibraryEvent>>, viewer: &Viewer) -> Self {
+        Self {
+            cursor,
+            resync: events.is_none(),
+            events: events
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|e| viewer.can_see(&e.path))
+                .map(|e| ChangeEvent {
+                    seq: e.seq,
+                    event: e.kind.as_str(),
+                    path: e.path,
+                    name: e.name,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// 长轮询：有序号大于 `since` 的事件时立即返回，否则最多等待 `timeout` 秒；
+/// 不带 `since` 时立即返回当前游标
+#[get("/api/changes/wait")]
+pub async fn wait(query: web::Query<WaitQuery>, viewer: Viewer, config: web::Data<AppConfig>) -> HttpResponse {
+    let bus = &config.events;
+    let Some(since) = query.since else {
+        let cursor = bus.history.lock().map(|h| h.seq).unwrap_or(0);
+        return HttpResponse::Ok().json(ChangesResponse::new(cursor, Some(Vec::new()), &viewer));
+    };
+    // 先订阅再查历史，避免两者之间发布的事件被漏掉
+    let mut receiver = bus.subscribe();
+    let (cursor, events) = bus.since(since);
+    if events.as_ref().is_none_or(|events| !events.is_empty()) {
+        return HttpResponse::Ok().json(ChangesResponse::new(cursor, events, &viewer));
+    }
+
+    let timeout = Duration::from_secs(query.timeout.unwrap_or(DEFAULT_WAIT_SECS).min(MAX_WAIT_SECS));
+    let _ = tokio::time::timeout(timeout, async {
+        loop {
+            match receiver.recv().await {
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => return,
+                Err(broadcast::error::RecvError::Closed) => std::future::pending::<()>().await,
+            }
+        }
+    })
+    .await;
+    // 稍等片刻，让同一次扫描产生的其它事件一起返回
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let (cursor, events) = bus.since(since);
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(ChangesResponse::new(cursor, events, &viewer))
+}
+
+/// 变化是否只发生在缩略图缓存、数据目录等程序自己管理的目录中
+fn is_internal(pic_dir: &Path, path: &Path) -> bool {
+    path.strip_prefix(pic_dir)
+        .map(|relative| {
+            relative
+                .components()
+                .next()
+                .map(|first| is_reserved_dir(&first.as_os_str().to_string_lossy()))
+                .unwrap_or(false)
+        })
+        .unwrap_or(false)
+}
+
+/// 监听本地图片目录，有文件新增、删除或改名时重新扫描。返回的监听器需要一直持有
+pub fn watch(config: AppConfig) -> notify::Result<notify::RecommendedWatcher> {
+    use notify::event::{EventKind as FsEventKind, ModifyKind};
+    use notify::Watcher;
+
+    let pic_dir = std::path::absolute(config.pic_dir.as_str())?;
+    let (tx, rx) = mpsc::channel::<()>();
+    let root = pic_dir.clone();
+    let mut watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+        let Ok(event) = result else {
+            return;
+        };
+        let relevant = matches!(
+            event.kind,
+            FsEventKind::Create(_)
+                | FsEventKind::Remove(_)
+                | FsEventKind::Modify(ModifyKind::Name(_))
+                | FsEventKind::Any
+        );
+        if relevant && !event.paths.iter().all(|path| is_internal(&root, path)) {
+            let _ = tx.send(());
+        }
+    })?;
+    watcher.watch(&pic_dir, notify::RecursiveMode::Recursive)?;
+    // 别名目录不在图片目录中，单独监听；监听失败时其中的变化要等下次扫描才能发现
+    for dir in config.aliases.dirs() {
+        if let Err(e) = watcher.watch(dir, notify::RecursiveMode::Recursive) {
+            log::warn!("无法监听别名目录 {}: {}", dir.display(), e);
+        }
+    }
+
+    thread::Builder::new()
+        .name("watcher".to_string())
+        .spawn(move || {
+            while rx.recv().is_ok() {
+                while rx.recv_timeout(WATCH_DEBOUNCE).is_ok() {}
+                config.rescan();
+            }
+        })?;
+    Ok(watcher)
+}
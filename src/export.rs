@@ -0,0 +1,370 @@
+//! 导出选中的图片 (`POST /api/export`，需管理令牌)：把一组路径、智能相册或筛选结果复制到服务器上的
+//! 导出目录 (`--export-dir`)，保存为子目录或 ZIP，可以同时缩小到指定的长边，用于整理交给冲印服务的
+//! 精选集。导出在后台进行，进度和结果通过 `GET /api/export` 查询；先写入 `.名称.partial`，完成后改名
+
+use crate::admin::AdminAuth;
+use crate::edit;
+use crate::errors::ApiError;
+use crate::homes::Viewer;
+use crate::listing::Filter;
+use crate::maintenance::Writable;
+use crate::{decode_oriented, format_time, is_valid_relative, names, resize_image, AppConfig};
+use actix_web::http::StatusCode;
+use actix_web::{get, post, rt, web, HttpResponse, Result};
+use image::ImageReader;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// 报告中最多保留的失败记录数
+const MAX_FAILURES: usize = 100;
+/// 导出名称的最大长度（字符）
+const MAX_NAME_LEN: usize = 100;
+
+#[derive(Deserialize)]
+pub struct ExportRequest {
+    /// 要导出的图片；为空时按 `album` 和筛选条件选择
+    #[serde(default)]
+    paths: Vec<String>,
+    /// 智能相册的名称，相册保存的条件与其他筛选条件同时生效
+    album: Option<String>,
+    #[serde(flatten)]
+    filter: Filter,
+    /// 导出目录中的子目录名，`zip` 时为 ZIP 的文件名（自动加上 `.zip`）
+    name: String,
+    #[serde(default)]
+    zip: bool,
+    /// 长边超过这个像素数的图片缩小后导出，不会放大
+    max_size: Option<u32>,
+}
+
+#[derive(Serialize, Clone)]
+struct Failure {
+    path: String,
+    error: String,
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct ExportReport {
+    started_at: String,
+    finished_at: Option<String>,
+    /// 导出目录中的子目录或 ZIP 文件名
+    target: String,
+    zip: bool,
+    max_size: Option<u32>,
+    total: usize,
+    exported: usize,
+    /// 缩小过的图片数
+    resized: usize,
+    failed: usize,
+    /// 写入的图片字节数
+    bytes: u64,
+    /// 整个导出失败（如无法写入目标）的原因，此时不保留导出的内容
+    error: Option<String>,
+    failures: Vec<Failure>,
+}
+
+impl ExportReport {
+    fn record(&mut self, relative: &str, result: &io::Result<(u64, bool)>) {
+        match result {
+            Ok((bytes, resized)) => {
+                self.exported += 1;
+                self.bytes += bytes;
+                if *resized {
+                    self.resized += 1;
+                }
+            }
+            Err(e) => {
+                self.failed += 1;
+                if self.failures.len() < MAX_FAILURES {
+                    self.failures.push(Failure {
+                        path: relative.to_string(),
+                        error: e.to_string(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// 导出的内容写到哪里
+enum Output {
+    Dir(PathBuf),
+    Zip(Box<ZipWriter<File>>),
+}
+
+impl Output {
+    fn write(&mut self, relative: &str, data: &[u8]) -> io::Result<()> {
+        match self {
+            Output::Dir(dir) => {
+                let path = names::fs_path(dir, relative);
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(path, data)
+            }
+            Output::Zip(zip) => {
+                // 图片本身已经压缩过，直接存储即可
+                let options = SimpleFileOptions::default()
+                    .compression_method(CompressionMethod::Stored)
+                    .large_file(data.len() as u64 >= u32::MAX as u64);
+                zip.start_file(relative, options)?;
+                zip.write_all(data)
+            }
+        }
+    }
+
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Output::Dir(_) => Ok(()),
+            Output::Zip(zip) => zip.finish()?.sync_all(),
+        }
+    }
+}
+
+/// 服务中的导出任务和最近一次的结果
+pub struct Exporter {
+    dir: PathBuf,
+    running: AtomicBool,
+    report: Mutex<Option<ExportReport>>,
+}
+
+#[derive(Serialize)]
+struct ExportStatus {
+    running: bool,
+    /// 进行中或最近一次的结果
+    report: Option<ExportReport>,
+}
+
+/// 检查导出名称：不能为空、以 `.` 开头、包含路径分隔符或控制字符
+fn valid_name(name: &str) -> bool {
+    !name.trim().is_empty()
+        && name.chars().count() <= MAX_NAME_LEN
+        && !name.starts_with('.')
+        && !name.chars().any(|c| c.is_control() || matches!(c, '/' | '\\'))
+}
+
+fn read_image(config: &AppConfig, relative: &str) -> io::Result<Vec<u8>> {
+    match &config.webdav {
+        Some(source) => source.fetch(relative),
+        None => fs::read(config.fs_path(relative)),
+    }
+}
+
+/// 长边超过 `max_size` 时缩小并按原来的格式重新编码；不需要缩小或无法缩小（GIF、ICO、
+/// 不能解码的格式）时返回 `None`，原样导出
+fn shrink(data: &[u8], relative: &str, max_size: u32) -> io::Result<Option<Vec<u8>>> {
+    let ext = relative.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()).unwrap_or_default();
+    if ext == "gif" || ext == "ico" {
+        return Ok(None);
+    }
+    let reader = ImageReader::new(io::Cursor::new(data)).with_guessed_format()?;
+    match reader.into_dimensions() {
+        Ok((width, height)) if width.max(height) > max_size => {}
+        _ => return Ok(None),
+    }
+    let reader = ImageReader::new(io::Cursor::new(data)).with_guessed_format()?;
+    let img = decode_oriented(reader).map_err(io::Error::other)?;
+    edit::reencode(&resize_image(&img, max_size), relative, data).map(Some)
+}
+
+impl Exporter {
+    pub fn new(dir: &str) -> Self {
+        Self {
+            dir: PathBuf::from(dir),
+            running: AtomicBool::new(false),
+            report: Mutex::new(None),
+        }
+    }
+
+    fn status(&self) -> ExportStatus {
+        ExportStatus {
+            running: self.running.load(Ordering::SeqCst),
+            report: self.report.lock().map(|r| r.clone()).unwrap_or_else(|e| e.into_inner().clone()),
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut ExportReport)) {
+        if let Ok(mut guard) = self.report.lock() {
+            if let Some(report) = guard.as_mut() {
+                f(report);
+            }
+        }
+    }
+
+    /// 导出目录，不存在时创建；不能在图片目录中，否则导出的图片会被扫描进图库
+    fn prepare_dir(&self, config: &AppConfig) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        if config.webdav.is_none() {
+            let pic_dir = fs::canonicalize(config.pic_dir.as_str())?;
+            if fs::canonicalize(&self.dir)?.starts_with(pic_dir) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "export directory must not be inside the picture directory",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// 导出全部图片，阻塞直到完成
+    fn run(&self, config: &AppConfig, images: &[String], target: &str, zip: bool, max_size: Option<u32>) {
+        if let Ok(mut guard) = self.report.lock() {
+            *guard = Some(ExportReport {
+                started_at: format_time(SystemTime::now()),
+                target: target.to_string(),
+                zip,
+                max_size,
+                total: images.len(),
+                ..Default::default()
+            });
+        }
+        let partial = self.dir.join(format!(".{}.partial", target));
+        let result = (|| -> io::Result<()> {
+            // 上次中断时留下的临时内容
+            let _ = fs::remove_dir_all(&partial);
+            let mut output = if zip {
+                Output::Zip(Box::new(ZipWriter::new(File::create(&partial)?)))
+            } else {
+                fs::create_dir(&partial)?;
+                Output::Dir(partial.clone())
+            };
+            for relative in images {
+                let prepared = read_image(config, relative).and_then(|data| {
+                    let shrunk = match max_size {
+                        Some(max_size) => shrink(&data, relative, max_size)?,
+                        None => None,
+                    };
+                    Ok(match shrunk {
+                        Some(shrunk) => (shrunk, true),
+                        None => (data, false),
+                    })
+                });
+                let result = match prepared {
+                    Ok((data, resized)) => {
+                        // 写入失败（如磁盘已满）时导出已经不完整，不再继续
+                        output.write(relative, &data)?;
+                        Ok((data.len() as u64, resized))
+                    }
+                    Err(e) => Err(e),
+                };
+                self.update(|report| report.record(relative, &result));
+            }
+            output.finish()?;
+            fs::rename(&partial, self.dir.join(target))
+        })();
+        if let Err(e) = result {
+            log::warn!("导出 {} 失败: {}", target, e);
+            let _ = if zip { fs::remove_file(&partial) } else { fs::remove_dir_all(&partial) };
+            self.update(|report| report.error = Some(e.to_string()));
+        }
+        self.update(|report| report.finished_at = Some(format_time(SystemTime::now())));
+        self.running.store(false, Ordering::SeqCst);
+    }
+}
+
+#[get("/api/export")]
+pub async fn status(_auth: AdminAuth, config: web::Data<AppConfig>) -> Result<HttpResponse> {
+    let Some(exporter) = &config.exporter else {
+        return Err(ApiError::bad_request("exporting is not enabled (set --export-dir)").into());
+    };
+    Ok(HttpResponse::Ok().json(exporter.status()))
+}
+
+/// 选出要导出的图片：指定了路径时每一张都必须存在且可见，否则按相册和筛选条件从当前图片中选择
+fn select(config: &AppConfig, viewer: &Viewer, request: &ExportRequest) -> Result<Vec<String>, ApiError> {
+    if !request.paths.is_empty() {
+        let mut paths = request.paths.clone();
+        paths.sort();
+        paths.dedup();
+        let visible = |path: &String| is_valid_relative(path) && viewer.can_see(path) && config.has_image(path);
+        if let Some(path) = paths.iter().find(|path| !visible(path)) {
+            return Err(ApiError::not_found(format!("Image not found: {}", path)));
+        }
+        return Ok(paths);
+    }
+    request.filter.validate().map_err(ApiError::bad_request)?;
+    let album = match request.album.as_deref() {
+        Some(name) => match config.albums.get(name) {
+            Some(filter) => Some(filter.matcher()),
+            None => return Err(ApiError::not_found(format!("album not found: {}", name))),
+        },
+        None if request.filter.is_empty() => {
+            return Err(ApiError::bad_request("select images with paths, album or filters"));
+        }
+        None => None,
+    };
+    let matcher = request.filter.matcher();
+    Ok(config
+        .current_images()
+        .into_iter()
+        .filter(|path| viewer.can_see(path))
+        .filter(|path| album.as_ref().is_none_or(|album| album.matches(config, path)))
+        .filter(|path| matcher.matches(config, path))
+        .collect())
+}
+
+/// 在后台开始导出，立即返回 202 和图片数；进度通过 `GET /api/export` 查询
+#[post("/api/export")]
+pub async fn run(
+    _auth: AdminAuth,
+    _writable: Writable,
+    viewer: Viewer,
+    body: web::Json<ExportRequest>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let Some(exporter) = config.exporter.clone() else {
+        return Err(ApiError::bad_request("exporting is not enabled (set --export-dir)").into());
+    };
+    let request = body.into_inner();
+    let name = request.name.trim();
+    if !valid_name(name) {
+        return Err(ApiError::bad_request("name must be a file name without path separators").into());
+    }
+    if request.max_size.is_some_and(|size| size < 16) {
+        return Err(ApiError::bad_request("max_size must be at least 16").into());
+    }
+    let target = if request.zip && !name.to_ascii_lowercase().ends_with(".zip") {
+        format!("{}.zip", name)
+    } else {
+        name.to_string()
+    };
+    let (zip, max_size) = (request.zip, request.max_size);
+
+    let config = config.into_inner();
+    let images = {
+        let config = config.clone();
+        web::block(move || select(&config, &viewer, &request)).await??
+    };
+    if images.is_empty() {
+        return Err(ApiError::bad_request("no images match the selection").into());
+    }
+    let exists = {
+        let (exporter, config, target) = (exporter.clone(), config.clone(), target.clone());
+        web::block(move || exporter.prepare_dir(&config).map(|()| exporter.dir.join(&target).exists()))
+            .await?
+            .map_err(|e| ApiError::bad_request(e.to_string()))?
+    };
+    if exists {
+        return Err(ApiError::new(StatusCode::CONFLICT, format!("{} already exists", target)).into());
+    }
+    if exporter.running.swap(true, Ordering::SeqCst) {
+        return Err(ApiError::new(StatusCode::CONFLICT, "export already running").into());
+    }
+    let count = images.len();
+    rt::spawn(async move {
+        let task = exporter.clone();
+        if let Err(e) = web::block(move || task.run(&config, &images, &target, zip, max_size)).await {
+            log::error!("导出任务异常: {}", e);
+            exporter.running.store(false, Ordering::SeqCst);
+        }
+    });
+    Ok(HttpResponse::Accepted().json(serde_json::json!({ "running": true, "images": count })))
+}
+
@@ -0,0 +1,118 @@
+//! 路径匹配方式 (`--path-match`)：macOS 创建的文件名中的 `é` 是分解形式 (NFD)，Windows 和 Linux
+//! 上通常是组合形式 (NFC)，不同设备同步过来的大小写也可能不同。开启后按路径访问图片时，
+//! 索引中没有完全相同的路径就按规范化（以及忽略大小写）后的路径查找，链接不会因为文件是哪台设备
+//! 创建的而失效。
+//!
+//! 中间件 [`canonicalize`] 在路由之前把按路径访问的地址（`/pic/`、`/thumb/`、`/api/info/` 等）
+//! 改写为索引中的实际路径，之后的处理与普通请求相同。完全相同的路径总是优先；规范化后相同的
+//! 多个文件按路径排序取第一个
+
+use crate::homes::PATH_ROUTES;
+use crate::{mount_path, url_path, AppConfig};
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Uri;
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use icu_normalizer::ComposingNormalizerBorrowed;
+use percent_encoding::percent_decode_str;
+use serde::Serialize;
+use std::borrow::Cow;
+
+/// 按路径访问图片时如何匹配文件名
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PathMatch {
+    /// 只接受完全相同的路径
+    Exact,
+    /// Unicode 规范化 (NFC) 后相同即可
+    Unicode,
+    /// 规范化后再忽略大小写
+    Nocase,
+}
+
+impl PathMatch {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "exact" => Some(PathMatch::Exact),
+            "unicode" => Some(PathMatch::Unicode),
+            "nocase" => Some(PathMatch::Nocase),
+            _ => None,
+        }
+    }
+
+    /// 比较时使用的形式，`Exact` 时原样返回
+    pub fn key<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        match self {
+            PathMatch::Exact => Cow::Borrowed(path),
+            PathMatch::Unicode => ComposingNormalizerBorrowed::new_nfc().normalize(path),
+            PathMatch::Nocase => Cow::Owned(ComposingNormalizerBorrowed::new_nfc().normalize(path).to_lowercase()),
+        }
+    }
+}
+
+/// 按路径访问的地址中的路径部分（相对画廊地址的前缀和转义的路径）
+fn split_target(path: &str) -> Option<(&str, &str)> {
+    // 带版本的缩略图 `/th/<版本>/<路径>`
+    if let Some(rest) = path.strip_prefix("/th/") {
+        let (version, _) = rest.split_once('/')?;
+        return Some(path.split_at("/th/".len() + version.len() + 1));
+    }
+    PATH_ROUTES.iter().find(|prefix| path.starts_with(*prefix)).map(|prefix| path.split_at(prefix.len()))
+}
+
+/// 中间件：按路径访问的图片或目录不在索引中时，改写为规范化后相同的实际路径
+pub async fn canonicalize(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let rewritten = req.app_data::<web::Data<AppConfig>>().and_then(|config| {
+        if config.settings.path_match == PathMatch::Exact {
+            return None;
+        }
+        let mount = mount_path(req.request());
+        let path = req.path().strip_prefix(mount.as_str())?;
+        let (prefix, target) = split_target(path)?;
+        let target = percent_decode_str(target).decode_utf8().ok()?;
+        // `/api/images/<路径>/versions` 等地址的路径后面还有别的部分，从长到短尝试
+        let segments: Vec<&str> = target.split('/').collect();
+        let (actual, rest) = (1..=segments.len()).rev().find_map(|n| {
+            let candidate = segments[..n].join("/");
+            if config.index.contains(&candidate) || config.index.contains_folder(&candidate) {
+                return Some(None);
+            }
+            config.index.canonical(&candidate).map(|actual| Some((actual, &segments[n..])))
+        })??;
+        let mut uri = format!("{}{}{}", mount, prefix, url_path(&actual));
+        for segment in rest {
+            uri.push('/');
+            uri.push_str(segment);
+        }
+        if let Some(query) = req.uri().query() {
+            uri.push('?');
+            uri.push_str(query);
+        }
+        uri.parse::<Uri>().ok()
+    });
+    if let Some(uri) = rewritten {
+        req.match_info_mut().get_mut().update(&uri);
+        req.head_mut().uri = uri;
+    }
+    next.call(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folds_paths() {
+        let nfd = "Cafe\u{301}/IMG_1.JPG";
+        assert_eq!(PathMatch::Exact.key(nfd), nfd);
+        assert_eq!(PathMatch::Unicode.key(nfd), "Caf\u{e9}/IMG_1.JPG");
+        assert_eq!(PathMatch::Nocase.key(nfd), "caf\u{e9}/img_1.jpg");
+        assert_eq!(split_target("/th/abc/a/b.png"), Some(("/th/abc/", "a/b.png")));
+        assert_eq!(split_target("/pic/a.png"), Some(("/pic/", "a.png")));
+        assert_eq!(split_target("/api/images"), None);
+    }
+}
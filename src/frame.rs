@@ -0,0 +1,289 @@
+//! 电子相框模式：`/frame` 是没有工具栏的全屏幻灯片，打开即自动播放，
+//! 适合让树莓派 + 显示器的相框在 kiosk 模式下直接指向服务器
+
+use crate::{is_valid_relative, AppConfig};
+use actix_web::{get, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+/// 默认每张图片显示的秒数
+const DEFAULT_INTERVAL: u64 = 15;
+
+#[derive(Deserialize)]
+pub struct FrameQuery {
+    /// 只播放这个目录（含子目录）中的图片
+    folder: Option<String>,
+    /// 只播放带有这个标签的图片
+    tag: Option<String>,
+    /// 每张显示的秒数
+    interval: Option<u64>,
+    /// `1` 显示时钟
+    clock: Option<String>,
+    /// `0` 按名称顺序播放，默认随机
+    shuffle: Option<String>,
+    /// `contain`（默认，完整显示）或 `cover`（铺满屏幕）
+    fit: Option<String>,
+}
+
+/// 传给页面脚本的设置
+#[derive(Serialize)]
+struct FrameSettings {
+    /// 画廊挂载的路径前缀
+    base: String,
+    folder: String,
+    tag: String,
+    interval: u64,
+    clock: bool,
+    shuffle: bool,
+    cover: bool,
+}
+
+fn flag(value: &Option<String>, default: bool) -> bool {
+    match value.as_deref() {
+        Some("1") | Some("true") | Some("on") => true,
+        Some("0") | Some("false") | Some("off") => false,
+        _ => default,
+    }
+}
+
+#[get("/frame")]
+pub async fn frame(query: web::Query<FrameQuery>, config: web::Data<AppConfig>) -> HttpResponse {
+    let folder = query.folder.as_deref().unwrap_or("").trim_matches('/').to_string();
+    if !folder.is_empty() && !is_valid_relative(&folder) {
+        return HttpResponse::BadRequest().body(format!("Invalid folder: {}", folder));
+    }
+    let settings = FrameSettings {
+        base: config.settings.mount_path.clone(),
+        folder,
+        tag: query.tag.clone().unwrap_or_default(),
+        // 太快会让树莓派一直在解码大图
+        interval: query.interval.unwrap_or(DEFAULT_INTERVAL).clamp(3, 24 * 3600),
+        clock: flag(&query.clock, false),
+        shuffle: flag(&query.shuffle, true),
+        cover: query.fit.as_deref() == Some("cover"),
+    };
+    // 设置来自查询参数，转义 `<` 以免提前结束 script 标签
+    let settings = serde_json::to_string(&settings)
+        .unwrap_or_else(|_| "{}".to_string())
+        .replace('<', "\\u003c");
+
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <meta name="robots" content="noindex">
+    <title>pic_url frame</title>
+    <style>
+        * {{ margin: 0; padding: 0; box-sizing: border-box; }}
+        html, body {{
+            width: 100%;
+            height: 100%;
+            overflow: hidden;
+            background: #000;
+            cursor: none;
+            font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif;
+        }}
+        .layer {{
+            position: absolute;
+            inset: 0;
+            width: 100%;
+            height: 100%;
+            object-fit: contain;
+            opacity: 0;
+            transition: opacity 1.5s ease-in-out;
+        }}
+        body.cover .layer {{ object-fit: cover; }}
+        .layer.visible {{ opacity: 1; }}
+        /* 时钟每分钟换一个位置，长时间显示也不会烙印在屏幕上 */
+        .clock {{
+            position: absolute;
+            color: rgba(255, 255, 255, 0.75);
+            text-shadow: 0 1px 6px rgba(0, 0, 0, 0.8);
+            text-align: right;
+            transition: opacity 1s;
+        }}
+        .clock .time {{ font-size: 4rem; font-weight: 200; line-height: 1; }}
+        .clock .date {{ font-size: 1.1rem; margin-top: 4px; }}
+        .message {{
+            position: absolute;
+            inset: 0;
+            display: flex;
+            align-items: center;
+            justify-content: center;
+            color: #475569;
+            font-size: 1.2rem;
+        }}
+    </style>
+</head>
+<body>
+    <img class="layer" id="layer0" alt="">
+    <img class="layer" id="layer1" alt="">
+    <div class="clock" id="clock" hidden><div class="time" id="clockTime"></div><div class="date" id="clockDate"></div></div>
+    <div class="message" id="message">Loading…</div>
+    <script>
+        const FRAME = {settings};
+        const layers = [document.getElementById('layer0'), document.getElementById('layer1')];
+        let visible = 0;
+        let order = [];
+        // 服务器开启原图签名时列表中带有签名过的地址
+        const urls = new Map();
+        let position = -1;
+        let timer = null;
+        let playing = false;
+
+        if (FRAME.cover) document.body.classList.add('cover');
+
+        function encodePath(path) {{
+            return path.split('/').map(encodeURIComponent).join('/');
+        }}
+
+        function source(path) {{
+            return FRAME.base + (urls.get(path) || '/pic/' + encodePath(path));
+        }}
+
+        function shuffle(list) {{
+            for (let i = list.length - 1; i > 0; i--) {{
+                const j = Math.floor(Math.random() * (i + 1));
+                [list[i], list[j]] = [list[j], list[i]];
+            }}
+            return list;
+        }}
+
+        // 获取完整列表；图片有增删时保留已播放的顺序，新图片排在后面
+        async function loadList() {{
+            const params = new URLSearchParams();
+            if (FRAME.folder) params.set('folder', FRAME.folder);
+            if (FRAME.tag) params.set('tag', FRAME.tag);
+            try {{
+                const data = await (await fetch(FRAME.base + '/api/images?' + params)).json();
+                const paths = data.images.map(img => img.path);
+                urls.clear();
+                data.images.forEach(img => {{ if (img.url) urls.set(img.path, img.url); }});
+                const present = new Set(paths);
+                const current = order[position];
+                const kept = order.filter(path => present.has(path));
+                const known = new Set(kept);
+                const added = paths.filter(path => !known.has(path));
+                order = kept.concat(FRAME.shuffle ? shuffle(added) : added);
+                if (!FRAME.shuffle) order.sort();
+                position = current ? order.indexOf(current) : -1;
+                document.getElementById('message').hidden = order.length > 0;
+                document.getElementById('message').textContent = 'No images';
+                if (!playing && order.length > 0) showNext();
+            }} catch (error) {{
+                // 服务器暂时不可用时继续播放已有的列表
+                console.error('获取图片列表失败:', error);
+            }}
+        }}
+
+        function showNext() {{
+            clearTimeout(timer);
+            timer = null;
+            playing = order.length > 0;
+            if (!playing) return;
+            position++;
+            if (position >= order.length) {{
+                position = 0;
+                if (FRAME.shuffle && order.length > 2) {{
+                    // 新一轮重新打乱，但不让上一张紧接着再出现
+                    const last = order[order.length - 1];
+                    shuffle(order);
+                    if (order[0] === last) order.push(order.shift());
+                }}
+            }}
+            const next = layers[1 - visible];
+            next.onload = () => {{
+                next.classList.add('visible');
+                layers[visible].classList.remove('visible');
+                visible = 1 - visible;
+                timer = setTimeout(showNext, FRAME.interval * 1000);
+                preload();
+            }};
+            // 加载失败（已删除、服务器繁忙）时跳过
+            next.onerror = () => {{ timer = setTimeout(showNext, 2000); }};
+            next.src = source(order[position]);
+        }}
+
+        // 提前下载下一张，切换时不用等待
+        let preloaded = null;
+        function preload() {{
+            const path = order[(position + 1) % order.length];
+            if (!path) return;
+            preloaded = new Image();
+            preloaded.src = source(path);
+        }}
+
+        function updateClock() {{
+            const now = new Date();
+            document.getElementById('clockTime').textContent = now.toLocaleTimeString([], {{ hour: '2-digit', minute: '2-digit' }});
+            document.getElementById('clockDate').textContent = now.toLocaleDateString([], {{ weekday: 'long', month: 'long', day: 'numeric' }});
+            const clock = document.getElementById('clock');
+            const margin = 24 + Math.floor(Math.random() * 40);
+            const side = Math.random() < 0.5 ? 'left' : 'right';
+            clock.style.left = clock.style.right = '';
+            clock.style[side] = margin + 'px';
+            clock.style.textAlign = side;
+            clock.style.bottom = (24 + Math.floor(Math.random() * 40)) + 'px';
+        }}
+
+        if (FRAME.clock) {{
+            document.getElementById('clock').hidden = false;
+            updateClock();
+            setTimeout(() => {{
+                updateClock();
+                setInterval(updateClock, 60000);
+            }}, (60 - new Date().getSeconds()) * 1000);
+        }}
+
+        // 保持屏幕常亮（支持 Screen Wake Lock 的浏览器）
+        async function keepAwake() {{
+            try {{
+                if (navigator.wakeLock) await navigator.wakeLock.request('screen');
+            }} catch (e) {{}}
+        }}
+        keepAwake();
+        document.addEventListener('visibilitychange', () => {{
+            if (document.visibilityState === 'visible') keepAwake();
+        }});
+
+        // 点击进入全屏，键盘左右键手动切换
+        document.addEventListener('click', () => {{
+            if (!document.fullscreenElement && document.documentElement.requestFullscreen) {{
+                document.documentElement.requestFullscreen().catch(() => {{}});
+            }}
+        }});
+        document.addEventListener('keydown', e => {{
+            if (e.key === 'ArrowRight' || e.key === ' ') {{
+                showNext();
+            }} else if (e.key === 'ArrowLeft' && order.length > 0) {{
+                position = (position - 2 + order.length) % order.length;
+                showNext();
+            }}
+        }});
+
+        loadList();
+        // 图片增删时更新列表；不支持 EventSource 时每 5 分钟刷新一次
+        let reloadTimer = null;
+        const scheduleReload = () => {{
+            clearTimeout(reloadTimer);
+            reloadTimer = setTimeout(loadList, 2000);
+        }};
+        if (window.EventSource) {{
+            const events = new EventSource(FRAME.base + '/api/events');
+            events.addEventListener('image.added', scheduleReload);
+            events.addEventListener('image.removed', scheduleReload);
+            events.addEventListener('resync', scheduleReload);
+        }} else {{
+            setInterval(loadList, 5 * 60 * 1000);
+        }}
+    </script>
+</body>
+</html>"#,
+        settings = settings,
+    );
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html)
+}
@@ -0,0 +1,232 @@
+//! 多个画廊 (`--galleries <文件>`)：同一个进程、同一个端口中，除了主画廊之外再提供几个独立的画廊，
+//! 挂在 `/g/<名称>/` 下，或者设置了 `hosts` 时按请求的域名选择、挂在根路径下。每个画廊有自己的
+//! 图片目录、管理令牌、主题和模板，索引、缩略图和数据文件互不相干；其他设置与主画廊相同。
+//!
+//! 配置文件为 JSON 数组：
+//!
+//! ```json
+//! [
+//!   { "name": "portfolio", "pic_dir": "/data/portfolio", "theme": "light" },
+//!   { "name": "archive", "pic_dir": "/data/archive", "auth": "secret", "accent": "#e11d48" },
+//!   { "name": "family", "pic_dir": "/data/family", "hosts": ["family.example.com"] }
+//! ]
+//! ```
+
+use crate::builder::{PicUrl, PicUrlBuilder};
+use crate::{theme, Config};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 画廊挂载路径的前缀
+pub const PREFIX: &str = "/g";
+
+/// 配置文件中的一个画廊，未设置的项与主画廊相同（管理令牌除外）
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Gallery {
+    /// 挂载路径 `/g/<名称>/` 中的名称
+    pub name: String,
+    pub pic_dir: String,
+    /// 管理令牌，不设置时这个画廊只读
+    #[serde(default, skip_serializing)]
+    pub auth: Option<String>,
+    #[serde(default)]
+    pub theme: Option<String>,
+    #[serde(default)]
+    pub accent: Option<String>,
+    #[serde(default)]
+    pub templates_dir: Option<String>,
+    /// 设置后只响应这些域名的请求，画廊挂在根路径下，不再出现在 `/g/<名称>/`
+    #[serde(default)]
+    pub hosts: Vec<String>,
+}
+
+impl Gallery {
+    pub fn mount_path(&self) -> String {
+        if self.hosts.is_empty() {
+            format!("{}/{}", PREFIX, self.name)
+        } else {
+            String::new()
+        }
+    }
+}
+
+/// 名称直接出现在地址中，只允许字母、数字、`-` 和 `_`
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= 64 && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// 域名（不含端口），只接受小写，与浏览器发送的 `Host` 相同
+pub fn is_valid_host(host: &str) -> bool {
+    !host.is_empty()
+        && host.len() <= 253
+        && host.split('.').all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+        })
+}
+
+/// 比较目录时使用的路径，目录还不存在时按原样比较
+fn normalized(dir: &str) -> PathBuf {
+    fs::canonicalize(dir).unwrap_or_else(|_| PathBuf::from(dir))
+}
+
+/// 读取配置文件并检查，见 [`check`]
+pub fn load(path: &str, pic_dir: Option<&str>) -> Result<Vec<Gallery>, String> {
+    let data = fs::read(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    let galleries: Vec<Gallery> = serde_json::from_slice(&data).map_err(|e| format!("invalid {}: {}", path, e))?;
+    check(pic_dir, &galleries)?;
+    Ok(galleries)
+}
+
+/// 检查名称和域名不重复、主题有效；各画廊和主画廊 (`pic_dir`，WebDAV 来源时为 `None`) 的图片目录
+/// 互不包含，否则缩略图和数据文件会被另一个画廊当作图片扫描
+pub fn check(pic_dir: Option<&str>, galleries: &[Gallery]) -> Result<(), String> {
+    let mut dirs: Vec<(String, PathBuf)> = Vec::new();
+    if let Some(dir) = pic_dir {
+        dirs.push(("main gallery".to_string(), normalized(dir)));
+    }
+    for gallery in galleries {
+        let name = &gallery.name;
+        if !is_valid_name(name) {
+            return Err(format!("invalid gallery name: {}", name));
+        }
+        if galleries.iter().filter(|other| &other.name == name).count() > 1 {
+            return Err(format!("duplicate gallery: {}", name));
+        }
+        if let Some(theme) = gallery.theme.as_deref().filter(|t| !theme::THEMES.contains(t)) {
+            return Err(format!("gallery {}: theme must be dark, light or auto: {}", name, theme));
+        }
+        if let Some(accent) = gallery.accent.as_deref().filter(|c| !theme::is_valid_color(c)) {
+            return Err(format!("gallery {}: invalid accent color: {}", name, accent));
+        }
+        if gallery.auth.as_deref().is_some_and(|token| token.trim().is_empty()) {
+            return Err(format!("gallery {}: auth must not be empty", name));
+        }
+        for host in &gallery.hosts {
+            if !is_valid_host(host) {
+                return Err(format!("gallery {}: invalid host: {}", name, host));
+            }
+            if galleries.iter().flat_map(|other| &other.hosts).filter(|other| *other == host).count() > 1 {
+                return Err(format!("duplicate host: {}", host));
+            }
+        }
+        let dir = normalized(&gallery.pic_dir);
+        if let Some((other, _)) = dirs.iter().find(|(_, other)| dir.starts_with(other) || other.starts_with(&dir)) {
+            return Err(format!("gallery {}: pic_dir overlaps with {}: {}", name, other, gallery.pic_dir));
+        }
+        dirs.push((format!("gallery {}", name), dir));
+    }
+    Ok(())
+}
+
+/// 画廊的配置：以主画廊的配置为基础，换成它自己的目录、令牌和主题。
+/// WebDAV 服务、DLNA、gRPC 接口、异地同步、定时备份和目录别名只属于主画廊；
+/// 钩子 (`--on-upload` 等)、Webhook、导出目录和上传的病毒扫描沿用主画廊的设置，
+/// 钩子在这个画廊的图片目录中运行，导出的图片与主画廊放在同一个导出目录
+pub fn settings(base: &Config, gallery: &Gallery) -> Config {
+    let mut settings = base.clone();
+    settings.pic_dir = gallery.pic_dir.clone();
+    settings.mount_path = gallery.mount_path();
+    settings.hosts = gallery.hosts.clone();
+    // 按域名选择的画廊按请求的地址生成链接
+    settings.base_url = if gallery.hosts.is_empty() {
+        base.base_url.as_ref().map(|url| format!("{}{}", url, settings.mount_path))
+    } else {
+        None
+    };
+    settings.admin_token = gallery.auth.clone();
+    if settings.admin_token.is_none() {
+        settings.auth_log = None;
+        settings.user_homes = false;
+    }
+    if let Some(theme) = &gallery.theme {
+        settings.theme = theme.clone();
+    }
+    if let Some(accent) = &gallery.accent {
+        settings.accent = accent.clone();
+    }
+    if gallery.templates_dir.is_some() {
+        settings.templates_dir = gallery.templates_dir.clone();
+    }
+    settings.webdav_url = None;
+    settings.webdav_user = None;
+    settings.webdav_password = None;
+    settings.dav = false;
+    settings.dlna = false;
+    settings.grpc = None;
+    settings.replicate_to = None;
+    settings.backup_dir = None;
+    settings.aliases.clear();
+    settings.alias_include.clear();
+    settings.alias_exclude.clear();
+    settings.galleries.clear();
+    settings
+}
+
+/// 创建主画廊配置中的各个画廊并开始扫描，需要在 actix 运行时中调用。
+/// 按域名选择的画廊排在前面，要先于其他画廊注册
+pub fn build(base: &Config) -> io::Result<Vec<PicUrl>> {
+    let mut galleries = base
+        .galleries
+        .iter()
+        .map(|gallery| {
+            let settings = settings(base, gallery);
+            let built = PicUrlBuilder::with_settings(settings).build()?;
+            let pic_dir = Path::new(&gallery.pic_dir).display();
+            if gallery.hosts.is_empty() {
+                log::info!("画廊: {}/ ({})", gallery.mount_path(), pic_dir);
+            } else {
+                log::info!("画廊: {} ({})", gallery.hosts.join(", "), pic_dir);
+            }
+            Ok(built)
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    galleries.sort_by_key(|gallery| gallery.config().settings.hosts.is_empty());
+    Ok(galleries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gallery(name: &str, pic_dir: &str) -> Gallery {
+        Gallery {
+            name: name.to_string(),
+            pic_dir: pic_dir.to_string(),
+            auth: None,
+            theme: None,
+            accent: None,
+            templates_dir: None,
+            hosts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn checks_gallery_names_and_directories() {
+        let galleries = [gallery("portfolio", "/srv/portfolio"), gallery("archive", "/srv/archive")];
+        assert!(check(Some("/srv/pic"), &galleries).is_ok());
+        assert!(check(Some("/srv/pic"), &[gallery("a/b", "/srv/x")]).is_err());
+        assert!(check(Some("/srv/pic"), &[gallery("a", "/srv/x"), gallery("a", "/srv/y")]).is_err());
+        assert!(check(Some("/srv/pic"), &[gallery("a", "/srv/pic/a")]).is_err());
+        assert!(check(Some("/srv/pic/a"), &[gallery("a", "/srv/pic")]).is_err());
+        assert!(check(None, &[gallery("a", "/srv/pic"), gallery("b", "/srv/pic")]).is_err());
+        let mut dark = gallery("a", "/srv/x");
+        dark.theme = Some("dim".to_string());
+        assert!(check(None, &[dark]).is_err());
+        let mut family = gallery("family", "/srv/family");
+        family.hosts = vec!["family.example.com".to_string()];
+        assert_eq!(family.mount_path(), "");
+        assert!(check(None, &[family.clone()]).is_ok());
+        let mut again = gallery("again", "/srv/again");
+        again.hosts = family.hosts.clone();
+        assert!(check(None, &[family, again]).is_err());
+        assert!(is_valid_host("pics.example.com"));
+        assert!(!is_valid_host("Pics.example.com"));
+        assert!(!is_valid_host("pics.example.com:8080"));
+    }
+}
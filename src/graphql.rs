@@ -0,0 +1,430 @@
+//! GraphQL 接口 (`POST /graphql`，需 `graphql` feature)：自定义前端按需查询图片，以及图片和目录、
+//! 智能相册、标签之间的关系，只返回选择的字段，不必像 `/api/images` 那样取回整条记录。
+//!
+//! 只有查询，没有修改。与画廊页面一样按请求者的身份只包含能看到的图片；
+//! 查询的嵌套层数和复杂度有上限，一个请求不能把整个图库展开很多次
+
+use crate::homes::Viewer;
+use crate::listing::{self, Filter};
+use crate::{format_time, ws, AppConfig};
+use actix_web::{post, web, HttpResponse, Result};
+use async_graphql::{Context, EmptyMutation, EmptySubscription, InputObject, Object, Schema, SimpleObject};
+use std::collections::BTreeMap;
+use std::sync::{Arc, LazyLock};
+
+/// 每页默认和最多返回的图片数
+const DEFAULT_FIRST: usize = 100;
+const MAX_FIRST: usize = 1000;
+const MAX_DEPTH: usize = 12;
+const MAX_COMPLEXITY: usize = 5000;
+
+static SCHEMA: LazyLock<Schema<Query, EmptyMutation, EmptySubscription>> = LazyLock::new(|| {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+});
+
+/// 一次请求中查询的图库：请求者能看到的全部图片
+struct Library {
+    config: web::Data<AppConfig>,
+    images: Vec<String>,
+}
+
+fn library<'a>(ctx: &Context<'a>) -> &'a Arc<Library> {
+    ctx.data_unchecked::<Arc<Library>>()
+}
+
+impl Library {
+    /// 同时符合 `filter` 和 `scope`（所在的目录、相册或标签）的图片；日期条件需要读取 EXIF，在阻塞线程中筛选
+    async fn select(self: &Arc<Self>, filter: Filter, scope: Option<Filter>) -> async_graphql::Result<Vec<String>> {
+        filter.validate()?;
+        if filter.is_empty() && scope.as_ref().is_none_or(Filter::is_empty) {
+            return Ok(self.images.clone());
+        }
+        let library = self.clone();
+        let selected = web::block(move || {
+            let own = filter.matcher();
+            let scope = scope.map(|scope| scope.matcher());
+            library
+                .images
+                .iter()
+                .filter(|path| {
+                    own.matches(&library.config, path)
+                        && scope.as_ref().is_none_or(|scope| scope.matches(&library.config, path))
+                })
+                .cloned()
+                .collect()
+        })
+        .await?;
+        Ok(selected)
+    }
+
+    /// 符合条件的图片中的一页
+    async fn page(
+        self: &Arc<Self>,
+        filter: Option<ImageFilter>,
+        scope: Option<Filter>,
+        first: Option<usize>,
+        offset: Option<usize>,
+    ) -> async_graphql::Result<ImageList> {
+        let paths = self.select(filter.map(Filter::from).unwrap_or_default(), scope).await?;
+        Ok(ImageList {
+            count: paths.len(),
+            images: paths
+                .into_iter()
+                .skip(offset.unwrap_or(0))
+                .take(first.unwrap_or(DEFAULT_FIRST).min(MAX_FIRST))
+                .map(|path| Image { path })
+                .collect(),
+        })
+    }
+}
+
+fn parent(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+/// 筛选条件，与 `/api/images` 的同名参数相同
+#[derive(InputObject, Default)]
+struct ImageFilter {
+    /// 只包含这个目录（含子目录）下的图片
+    folder: Option<String>,
+    /// 路径中包含的文字，不区分大小写
+    q: Option<String>,
+    tag: Option<String>,
+    /// 逗号分隔的扩展名
+    ext: Option<String>,
+    /// 拍摄日期不早于 YYYY-MM-DD
+    from: Option<String>,
+    /// 拍摄日期不晚于 YYYY-MM-DD
+    to: Option<String>,
+}
+
+impl From<ImageFilter> for Filter {
+    fn from(filter: ImageFilter) -> Self {
+        Filter {
+            folder: filter.folder,
+            ext: filter.ext,
+            q: filter.q,
+            tag: filter.tag,
+            from: filter.from,
+            to: filter.to,
+        }
+    }
+}
+
+/// 一页图片
+#[derive(SimpleObject)]
+struct ImageList {
+    /// 符合条件的图片总数
+    count: usize,
+    images: Vec<Image>,
+}
+
+struct Image {
+    path: String,
+}
+
+impl Image {
+    /// 需要读取文件的字段在阻塞线程中计算
+    async fn blocking<T: Send + 'static>(
+        &self,
+        ctx: &Context<'_>,
+        f: impl FnOnce(&AppConfig, &str) -> T + Send + 'static,
+    ) -> async_graphql::Result<T> {
+        let (config, path) = (library(ctx).config.clone(), self.path.clone());
+        Ok(web::block(move || f(&config, &path)).await?)
+    }
+
+    async fn dimensions(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<(u32, u32)>> {
+        self.blocking(ctx, |config, path| match config.webdav {
+            Some(_) => None,
+            None => config.dimensions.get(&config.fs_path(path), path),
+        })
+        .await
+    }
+}
+
+#[Object]
+impl Image {
+    /// 相对图片目录的路径
+    async fn path(&self) -> &str {
+        &self.path
+    }
+
+    async fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+
+    /// 短 ID，可通过 `/i/{id}` 访问
+    async fn id(&self, ctx: &Context<'_>) -> Option<String> {
+        library(ctx).config.ids.id_of(&self.path)
+    }
+
+    /// 原图宽度，WebDAV 来源没有
+    async fn width(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<u32>> {
+        Ok(self.dimensions(ctx).await?.map(|(width, _)| width))
+    }
+
+    async fn height(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<u32>> {
+        Ok(self.dimensions(ctx).await?.map(|(_, height)| height))
+    }
+
+    /// 文件大小（字节）
+    async fn bytes(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<u64>> {
+        self.blocking(ctx, |config, path| listing::metadata(config, path).1).await
+    }
+
+    /// 修改时间 (RFC 3339)
+    async fn modified(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<String>> {
+        self.blocking(ctx, |config, path| listing::metadata(config, path).0.map(format_time)).await
+    }
+
+    /// EXIF 中的拍摄时间，没有时为修改时间
+    async fn taken(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<String>> {
+        self.blocking(ctx, |config, path| config.captures.taken(config, path)).await
+    }
+
+    /// 带版本的缩略图地址（相对画廊地址），可以长期缓存
+    async fn thumb(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<String>> {
+        self.blocking(ctx, |config, path| {
+            config
+                .thumb_version(path)
+                .map(|version| format!("/th/{}/{}", version, crate::url_path(path)))
+        })
+        .await
+    }
+
+    /// 原图地址（相对画廊地址），开启原图签名时带签名
+    async fn url(&self, ctx: &Context<'_>) -> String {
+        library(ctx).config.pic_url(&self.path)
+    }
+
+    async fn folder(&self) -> Folder {
+        Folder {
+            path: parent(&self.path).to_string(),
+        }
+    }
+
+    async fn tags(&self, ctx: &Context<'_>) -> Vec<Tag> {
+        library(ctx).config.tags.tags_of(&self.path).into_iter().map(|name| Tag { name }).collect()
+    }
+
+    /// 包含这张图片的智能相册
+    async fn albums(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Album>> {
+        let albums = library(ctx).config.albums.list();
+        self.blocking(ctx, move |config, path| {
+            albums
+                .into_iter()
+                .filter(|(_, filter)| filter.matcher().matches(config, path))
+                .map(|(name, filter)| Album { name, filter })
+                .collect()
+        })
+        .await
+    }
+}
+
+/// 目录，根目录的路径为空字符串
+struct Folder {
+    path: String,
+}
+
+#[Object]
+impl Folder {
+    async fn path(&self) -> &str {
+        &self.path
+    }
+
+    async fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+
+    /// 其中（含子目录）的图片数
+    async fn count(&self, ctx: &Context<'_>) -> usize {
+        library(ctx).images.iter().filter(|path| ws::in_folder(&self.path, path)).count()
+    }
+
+    /// 上级目录，根目录没有
+    async fn parent(&self) -> Option<Folder> {
+        (!self.path.is_empty()).then(|| Folder {
+            path: parent(&self.path).to_string(),
+        })
+    }
+
+    /// 直接的子目录
+    async fn folders(&self, ctx: &Context<'_>) -> Vec<Folder> {
+        listing::folders(&library(ctx).images)
+            .into_iter()
+            .filter(|(path, _)| parent(path) == self.path)
+            .map(|(path, _)| Folder { path })
+            .collect()
+    }
+
+    /// 其中（含子目录）的图片
+    async fn images(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<ImageFilter>,
+        first: Option<usize>,
+        offset: Option<usize>,
+    ) -> async_graphql::Result<ImageList> {
+        let scope = Filter {
+            folder: Some(self.path.clone()),
+            ..Filter::default()
+        };
+        library(ctx).page(filter, Some(scope), first, offset).await
+    }
+}
+
+/// 智能相册
+struct Album {
+    name: String,
+    filter: Filter,
+}
+
+#[Object]
+impl Album {
+    async fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn count(&self, ctx: &Context<'_>) -> async_graphql::Result<usize> {
+        Ok(library(ctx).select(self.filter.clone(), None).await?.len())
+    }
+
+    async fn images(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<ImageFilter>,
+        first: Option<usize>,
+        offset: Option<usize>,
+    ) -> async_graphql::Result<ImageList> {
+        library(ctx).page(filter, Some(self.filter.clone()), first, offset).await
+    }
+}
+
+struct Tag {
+    name: String,
+}
+
+impl Tag {
+    fn scope(&self) -> Filter {
+        Filter {
+            tag: Some(self.name.clone()),
+            ..Filter::default()
+        }
+    }
+}
+
+#[Object]
+impl Tag {
+    async fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn count(&self, ctx: &Context<'_>) -> async_graphql::Result<usize> {
+        Ok(library(ctx).select(self.scope(), None).await?.len())
+    }
+
+    async fn images(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<ImageFilter>,
+        first: Option<usize>,
+        offset: Option<usize>,
+    ) -> async_graphql::Result<ImageList> {
+        library(ctx).page(filter, Some(self.scope()), first, offset).await
+    }
+}
+
+struct Query;
+
+#[Object]
+impl Query {
+    /// 图片列表，可以再限定在一个智能相册中
+    async fn images(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<ImageFilter>,
+        album: Option<String>,
+        first: Option<usize>,
+        offset: Option<usize>,
+    ) -> async_graphql::Result<ImageList> {
+        let library = library(ctx);
+        let scope = match album {
+            Some(name) => match library.config.albums.get(&name) {
+                Some(filter) => Some(filter),
+                None => return Err(format!("album not found: {}", name).into()),
+            },
+            None => None,
+        };
+        library.page(filter, scope, first, offset).await
+    }
+
+    async fn image(&self, ctx: &Context<'_>, path: String) -> Option<Image> {
+        library(ctx).images.contains(&path).then_some(Image { path })
+    }
+
+    /// 目录，不指定时为根目录；没有图片的目录不存在
+    async fn folder(&self, ctx: &Context<'_>, path: Option<String>) -> Option<Folder> {
+        let path = path.unwrap_or_default().trim_matches('/').to_string();
+        let exists = path.is_empty() || library(ctx).images.iter().any(|image| ws::in_folder(&path, image));
+        exists.then_some(Folder { path })
+    }
+
+    /// 所有包含图片的目录，按路径排序
+    async fn folders(&self, ctx: &Context<'_>) -> Vec<Folder> {
+        listing::folders(&library(ctx).images)
+            .into_iter()
+            .map(|(path, _)| Folder { path })
+            .collect()
+    }
+
+    async fn albums(&self, ctx: &Context<'_>) -> Vec<Album> {
+        library(ctx)
+            .config
+            .albums
+            .list()
+            .into_iter()
+            .map(|(name, filter)| Album { name, filter })
+            .collect()
+    }
+
+    async fn album(&self, ctx: &Context<'_>, name: String) -> Option<Album> {
+        library(ctx).config.albums.get(&name).map(|filter| Album { name, filter })
+    }
+
+    /// 能看到的图片上用到的所有标签，按名称排序
+    async fn tags(&self, ctx: &Context<'_>) -> Vec<Tag> {
+        let library = library(ctx);
+        let mut names = BTreeMap::new();
+        for path in &library.images {
+            for name in library.config.tags.tags_of(path) {
+                names.insert(name, ());
+            }
+        }
+        names.into_keys().map(|name| Tag { name }).collect()
+    }
+
+    async fn tag(&self, name: String) -> Tag {
+        Tag { name }
+    }
+}
+
+/// 执行查询，请求体为标准的 GraphQL JSON (`query`、`variables`、`operationName`)；
+/// 查询中的错误与 GraphQL 的约定一样放在响应的 `errors` 中，状态码仍为 200
+#[post("/graphql")]
+pub async fn query(
+    viewer: Viewer,
+    config: web::Data<AppConfig>,
+    request: web::Json<async_graphql::Request>,
+) -> Result<HttpResponse> {
+    let images = {
+        let config = config.clone();
+        web::block(move || viewer.filter(config.current_images())).await?
+    };
+    let library = Arc::new(Library { config, images });
+    let response = SCHEMA.execute(request.into_inner().data(library)).await;
+    Ok(HttpResponse::Ok().json(response))
+}
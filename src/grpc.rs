@@ -0,0 +1,343 @@
+//! gRPC 管理接口 (`--grpc <地址>`，需 `grpc` feature)：同步程序等集成通过它列出、上传和删除图片，
+//! 并接收图库变化事件。接口定义见 crate 中的 `proto/pic_url.proto`。
+//!
+//! 在单独的端口和线程中运行，只服务主画廊；每个请求都要在 metadata 中携带
+//! `authorization: Bearer <管理令牌或 API 密钥>`，API 密钥的角色要求与对应的 HTTP 接口相同
+
+use crate::admin::{self, AdminAuth, Identity};
+use crate::api_keys::Scope;
+use crate::batch::{self, BatchAction};
+use crate::homes::Viewer;
+use crate::listing::Filter;
+use crate::{maintenance, policy, upload, ws, AppConfig, ImageInfo};
+use actix_web::http::StatusCode;
+use actix_web::web::{self, Bytes};
+use actix_web::ResponseError;
+use futures_util::{Stream, StreamExt};
+use std::io;
+use std::pin::Pin;
+use std::thread;
+use tokio::sync::broadcast::error::RecvError;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status, Streaming};
+
+pub mod proto {
+    tonic::include_proto!("pic_url.v1");
+}
+
+use proto::library_server::{Library, LibraryServer};
+use proto::upload_image_request::Part;
+
+/// 每页默认和最多返回的图片数，与 WebSocket 的 `list` 命令相同
+const DEFAULT_PER_PAGE: usize = 100;
+const MAX_PER_PAGE: usize = 1000;
+
+impl From<ImageInfo> for proto::Image {
+    fn from(info: ImageInfo) -> Self {
+        proto::Image {
+            path: info.path,
+            name: info.name,
+            id: info.id,
+            tags: info.tags,
+            width: info.width,
+            height: info.height,
+            thumb: info.thumb,
+            url: info.url,
+        }
+    }
+}
+
+/// HTTP 接口的错误按状态码换成对应的 gRPC 状态
+fn status(error: &dyn ResponseError) -> Status {
+    let message = error.to_string();
+    match error.status_code() {
+        StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => Status::invalid_argument(message),
+        StatusCode::UNAUTHORIZED => Status::unauthenticated(message),
+        StatusCode::FORBIDDEN => Status::permission_denied(message),
+        StatusCode::NOT_FOUND => Status::not_found(message),
+        StatusCode::CONFLICT => Status::already_exists(message),
+        StatusCode::PAYLOAD_TOO_LARGE | StatusCode::INSUFFICIENT_STORAGE => Status::resource_exhausted(message),
+        StatusCode::SERVICE_UNAVAILABLE => Status::unavailable(message),
+        _ => Status::internal(message),
+    }
+}
+
+struct LibraryService {
+    config: web::Data<AppConfig>,
+}
+
+impl LibraryService {
+    /// 检查 metadata 中的凭据：管理令牌，或角色不低于 `scope` 的 API 密钥。
+    /// 限定了目录或用户的密钥不能使用，这个接口面向整个图库
+    fn authorize<T>(&self, request: &Request<T>, scope: Scope) -> Result<(), Status> {
+        let value = request.metadata().get("authorization").map(|value| value.to_str().ok());
+        let Some(value) = value else {
+            return Err(Status::unauthenticated("missing admin token"));
+        };
+        match admin::identify_authorization(&self.config, value) {
+            Identity::Owner => Ok(()),
+            Identity::Key(key) if !key.folders.is_empty() || key.user.is_some() => Err(Status::permission_denied(
+                "API keys limited to folders or a user cannot use the gRPC API",
+            )),
+            Identity::Key(key) => policy::require(&AdminAuth { key: Some(key) }, scope).map_err(|e| status(&e)),
+            Identity::Anonymous | Identity::Invalid => Err(Status::unauthenticated("invalid admin token")),
+        }
+    }
+
+    /// 维护模式下拒绝写操作
+    fn writable(&self) -> Result<(), Status> {
+        let current = self.config.maintenance.status();
+        if current.enabled {
+            return Err(status(&maintenance::rejection(&current)));
+        }
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl Library for LibraryService {
+    async fn list_images(
+        &self,
+        request: Request<proto::ListImagesRequest>,
+    ) -> Result<Response<proto::ListImagesResponse>, Status> {
+        self.authorize(&request, Scope::Read)?;
+        let request = request.into_inner();
+        let filter = Filter {
+            folder: request.folder,
+            ext: request.ext,
+            q: request.q,
+            tag: request.tag,
+            from: request.from,
+            to: request.to,
+        };
+        filter.validate().map_err(Status::invalid_argument)?;
+        let album = match request.album {
+            Some(name) => match self.config.albums.get(&name) {
+                Some(album) => Some(album),
+                None => return Err(Status::not_found(format!("album not found: {}", name))),
+            },
+            None => None,
+        };
+        let page = request.page.max(1);
+        let per_page = match request.per_page {
+            0 => DEFAULT_PER_PAGE,
+            n => (n as usize).min(MAX_PER_PAGE),
+        };
+        let config = self.config.clone();
+        let (count, images) = web::block(move || {
+            let own = filter.matcher();
+            let album = album.map(|album| album.matcher());
+            let matching: Vec<String> = config
+                .current_images()
+                .into_iter()
+                .filter(|path| {
+                    own.matches(&config, path) && album.as_ref().is_none_or(|album| album.matches(&config, path))
+                })
+                .collect();
+            let images = matching
+                .iter()
+                .skip((page as usize - 1).saturating_mul(per_page))
+                .take(per_page)
+                .map(|path| ImageInfo::new(&config, path).into())
+                .collect();
+            (matching.len(), images)
+        })
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(proto::ListImagesResponse {
+            count: count as u64,
+            page,
+            per_page: per_page as u32,
+            images,
+        }))
+    }
+
+    async fn upload_image(
+        &self,
+        request: Request<Streaming<proto::UploadImageRequest>>,
+    ) -> Result<Response<proto::Image>, Status> {
+        self.authorize(&request, Scope::Upload)?;
+        self.writable()?;
+        let mut parts = request.into_inner();
+        let header = match parts.message().await? {
+            Some(proto::UploadImageRequest {
+                part: Some(Part::Header(header)),
+            }) => header,
+            _ => return Err(Status::invalid_argument("the first message must be the upload header")),
+        };
+        let name = upload::sanitize_name(&header.name)
+            .ok_or_else(|| Status::invalid_argument(format!("not a supported image file: {}", header.name)))?;
+        let (folder, dir) = upload::prepare_folder(&self.config, &header.folder)
+            .await
+            .map_err(|e| status(e.as_response_error()))?;
+        let mut chunks = parts.map(|part| match part?.part {
+            Some(Part::Chunk(chunk)) => Ok(Bytes::from(chunk)),
+            _ => Err(Status::invalid_argument("only file content may follow the upload header")),
+        });
+        let path = upload::save(&self.config, &mut chunks, &dir, &folder, &name)
+            .await
+            .map_err(Status::invalid_argument)?;
+        let uploaded = upload::finish(self.config.clone(), vec![path])
+            .await
+            .map_err(|e| status(e.as_response_error()))?;
+        let image = uploaded.into_iter().next().ok_or_else(|| Status::internal("upload was not saved"))?;
+        Ok(Response::new(image.into()))
+    }
+
+    async fn delete_images(
+        &self,
+        request: Request<proto::DeleteImagesRequest>,
+    ) -> Result<Response<proto::DeleteImagesResponse>, Status> {
+        self.authorize(&request, Scope::Full)?;
+        self.writable()?;
+        let paths = request.into_inner().paths;
+        let config = self.config.clone();
+        let result = web::block(move || batch::run_batch(&config, &Viewer::All, BatchAction::Delete { paths }))
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let response = result.map_err(Status::invalid_argument)?;
+        Ok(Response::new(proto::DeleteImagesResponse {
+            deleted: response.ok as u32,
+            failed: response
+                .failed
+                .into_iter()
+                .map(|failure| proto::DeleteFailure {
+                    path: failure.path,
+                    error: failure.error,
+                })
+                .collect(),
+            sidecars: response.sidecars as u32,
+        }))
+    }
+
+    type WatchEventsStream = Pin<Box<dyn Stream<Item = Result<proto::Event, Status>> + Send>>;
+
+    async fn watch_events(
+        &self,
+        request: Request<proto::WatchEventsRequest>,
+    ) -> Result<Response<Self::WatchEventsStream>, Status> {
+        self.authorize(&request, Scope::Read)?;
+        let folder = request.into_inner().folder.trim_matches('/').to_string();
+        let events = self.config.events.subscribe();
+        let stream = futures_util::stream::unfold((events, folder), |(mut events, folder)| async move {
+            loop {
+                let event = match events.recv().await {
+                    Ok(event) if !ws::in_folder(&folder, &event.path) => continue,
+                    Ok(event) => proto::Event {
+                        seq: event.seq,
+                        kind: event.kind.as_str().to_string(),
+                        path: event.path,
+                        name: event.name,
+                    },
+                    Err(RecvError::Lagged(_)) => proto::Event {
+                        kind: "resync".to_string(),
+                        ..Default::default()
+                    },
+                    Err(RecvError::Closed) => return None,
+                };
+                return Some((Ok(event), (events, folder)));
+            }
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+async fn serve(listener: tokio::net::TcpListener, config: web::Data<AppConfig>) -> io::Result<()> {
+    Server::builder()
+        .add_service(LibraryServer::new(LibraryService { config }))
+        .serve_with_incoming(TcpIncoming::from(listener))
+        .await
+        .map_err(io::Error::other)
+}
+
+/// 在 `address` 上监听并在单独的线程中提供服务；端口被占用等错误立即返回
+pub fn start(address: &str, config: web::Data<AppConfig>) -> io::Result<()> {
+    let listener = std::net::TcpListener::bind(address)?;
+    listener.set_nonblocking(true)?;
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+    thread::Builder::new().name("grpc".to_string()).spawn(move || {
+        let served = runtime.block_on(async move { serve(tokio::net::TcpListener::from_std(listener)?, config).await });
+        if let Err(e) = served {
+            log::error!("gRPC 接口已停止: {}", e);
+        }
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use proto::library_client::LibraryClient;
+    use std::io::Cursor;
+
+    fn png() -> Vec<u8> {
+        let mut out = Cursor::new(Vec::new());
+        image::RgbImage::from_pixel(8, 8, image::Rgb([1, 2, 3]))
+            .write_to(&mut out, image::ImageFormat::Png)
+            .unwrap();
+        out.into_inner()
+    }
+
+    fn authorized<T>(message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        request.metadata_mut().insert("authorization", "Bearer secret".parse().unwrap());
+        request
+    }
+
+    #[actix_web::test]
+    async fn lists_uploads_and_deletes_images() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("trips")).unwrap();
+        std::fs::write(dir.path().join("trips/beach.png"), png()).unwrap();
+        let config = web::Data::new(
+            AppConfig::load(Config {
+                pic_dir: dir.path().to_string_lossy().to_string(),
+                admin_token: Some("secret".to_string()),
+                prewarm: false,
+                ..Config::default()
+            })
+            .unwrap(),
+        );
+        config.rescan();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, config));
+        let mut client = LibraryClient::connect(format!("http://{}", address)).await.unwrap();
+
+        let denied = client.list_images(proto::ListImagesRequest::default()).await.unwrap_err();
+        assert_eq!(denied.code(), tonic::Code::Unauthenticated);
+
+        let parts = vec![
+            proto::UploadImageRequest {
+                part: Some(Part::Header(proto::UploadHeader {
+                    name: "new.png".to_string(),
+                    folder: "trips".to_string(),
+                })),
+            },
+            proto::UploadImageRequest {
+                part: Some(Part::Chunk(png())),
+            },
+        ];
+        let image = client.upload_image(authorized(futures_util::stream::iter(parts))).await.unwrap().into_inner();
+        assert_eq!(image.path, "trips/new.png");
+        assert_eq!(image.width, Some(8));
+
+        let list = proto::ListImagesRequest {
+            folder: Some("trips".to_string()),
+            ..Default::default()
+        };
+        let listed = client.list_images(authorized(list.clone())).await.unwrap().into_inner();
+        assert_eq!(listed.count, 2);
+
+        let delete = proto::DeleteImagesRequest {
+            paths: vec!["trips/beach.png".to_string(), "missing.png".to_string()],
+        };
+        let deleted = client.delete_images(authorized(delete)).await.unwrap().into_inner();
+        assert_eq!(deleted.deleted, 1);
+        assert_eq!(deleted.failed[0].path, "missing.png");
+        let listed = client.list_images(authorized(list)).await.unwrap().into_inner();
+        assert_eq!(listed.images.iter().map(|image| image.path.as_str()).collect::<Vec<_>>(), ["trips/new.png"]);
+    }
+}
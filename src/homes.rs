@@ -0,0 +1,151 @@
+//! 用户私有目录 (`--user-homes`)：图片目录下的 `users/<用户名>/` 只有该用户和管理员可以浏览、查看和上传，
+//! 其他目录仍然对所有人公开。用户是带 `user` 的 [API 密钥](crate::api_keys)，浏览器中用这样的密钥
+//! [登录](crate::session)；管理令牌、管理员的会话和不属于任何用户的密钥可以看到全部图片。
+//!
+//! 图片列表、目录、时间线、事件等按 [`Viewer`] 过滤；按路径访问图片的路由（原图、缩略图、分享页、
+//! 编辑、WebDAV 等）由 [`guard`] 统一检查，看不到的图片与不存在的一样返回 404
+
+use crate::admin::{self, Identity};
+use crate::dav::{self, DAV_PREFIX};
+use crate::errors::ApiError;
+use crate::{mount_path, AppConfig};
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, FromRequest, HttpRequest, ResponseError};
+use percent_encoding::percent_decode_str;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::{ready, Ready};
+
+/// 私有目录所在的顶层目录
+pub const HOMES_DIR: &str = "users";
+
+/// 按路径访问图片的路由，前缀之后是图片或目录的相对路径
+pub const PATH_ROUTES: &[&str] = &[
+    "/pic/",
+    "/thumb/",
+    "/view/",
+    "/gallery/",
+    "/api/info/",
+    "/api/exif/",
+    "/api/links/",
+    "/api/images/",
+];
+
+/// 私有目录中的路径所属的用户，公共目录返回 `None`
+pub fn owner(path: &str) -> Option<&str> {
+    let mut segments = path.split('/').filter(|segment| !segment.is_empty());
+    if segments.next()? != HOMES_DIR {
+        return None;
+    }
+    segments.next()
+}
+
+/// 请求者能看到哪些图片
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Viewer {
+    /// 管理员，或未开启私有目录：全部图片
+    All,
+    /// 用户：公共目录和自己的目录
+    User(String),
+    /// 未登录：只有公共目录
+    Public,
+}
+
+impl Viewer {
+    /// 不针对某个请求时（站点地图、DLNA、每日一图等）使用，只包含公共目录
+    pub fn public(config: &AppConfig) -> Self {
+        if config.settings.user_homes {
+            Viewer::Public
+        } else {
+            Viewer::All
+        }
+    }
+
+    /// 请求者的身份，同一个请求只识别一次
+    pub fn of(req: &HttpRequest) -> Self {
+        let Some(config) = req.app_data::<web::Data<AppConfig>>() else {
+            return Viewer::Public;
+        };
+        if !config.settings.user_homes {
+            return Viewer::All;
+        }
+        crate::request_cached(req, config, || match admin::identify(config, req) {
+            Identity::Owner => Viewer::All,
+            Identity::Key(key) => key.user.map_or(Viewer::All, Viewer::User),
+            Identity::Anonymous | Identity::Invalid => Viewer::Public,
+        })
+    }
+
+    pub fn can_see(&self, path: &str) -> bool {
+        match (self, owner(path)) {
+            (Viewer::All, _) | (_, None) => true,
+            (Viewer::User(user), Some(owner)) => user == owner,
+            (Viewer::Public, Some(_)) => false,
+        }
+    }
+
+    /// 去掉看不到的图片
+    pub fn filter(&self, mut images: Vec<String>) -> Vec<String> {
+        if *self != Viewer::All {
+            images.retain(|path| self.can_see(path));
+        }
+        images
+    }
+}
+
+impl FromRequest for Viewer {
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(Ok(Viewer::of(req)))
+    }
+}
+
+/// 请求按路径访问的图片或目录，不按路径访问时返回空
+fn targets(req: &ServiceRequest) -> Vec<String> {
+    let mount = mount_path(req.request());
+    let path = req.path().strip_prefix(mount.as_str()).unwrap_or(req.path());
+    if path == "/api/upload" {
+        let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).ok();
+        return query.and_then(|q| q.get("folder").cloned()).into_iter().collect();
+    }
+    if path == DAV_PREFIX || path.starts_with(&format!("{}/", DAV_PREFIX)) {
+        let destination = req
+            .headers()
+            .get("Destination")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|uri| dav::relative_path(&mount, uri));
+        return dav::relative_path(&mount, req.path()).into_iter().chain(destination).collect();
+    }
+    // 带版本的缩略图 `/th/<版本>/<路径>`
+    let relative = match path.strip_prefix("/th/") {
+        Some(rest) => rest.split_once('/').map(|(_, relative)| relative),
+        None => PATH_ROUTES.iter().find_map(|prefix| path.strip_prefix(prefix)),
+    };
+    relative
+        .map(|relative| percent_decode_str(relative).decode_utf8_lossy().into_owned())
+        .into_iter()
+        .collect()
+}
+
+/// 中间件：按路径访问别人的私有目录时返回 404，上传到别人的目录返回 403
+pub async fn guard(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let viewer = Viewer::of(req.request());
+    if viewer != Viewer::All && targets(&req).iter().any(|target| !viewer.can_see(target)) {
+        let error = if req.path().ends_with("/api/upload") {
+            ApiError::new(StatusCode::FORBIDDEN, "this folder belongs to another user")
+        } else {
+            ApiError::not_found("not found")
+        };
+        let res = error.error_response();
+        return Ok(req.into_response(res).map_into_right_body());
+    }
+    next.call(req).await.map(ServiceResponse::map_into_left_body)
+}
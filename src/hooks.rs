@@ -0,0 +1,202 @@
+//! 外部命令钩子：上传、删除图片和扫描完成后执行用户配置的命令，
+//! 便于接入 exiftool、图片压缩等后处理工具
+
+use crate::aliases::Aliases;
+use crate::webhook::EventKind;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 传给钩子命令的环境变量白名单，其余环境变量（包括各种令牌和密码）都会被清除
+pub const PASSTHROUGH_ENV: [&str; 4] = ["PATH", "HOME", "LANG", "TZ"];
+
+struct Job {
+    name: &'static str,
+    command: Vec<String>,
+    path: PathBuf,
+}
+
+pub struct Hooks {
+    on_upload: Option<Vec<String>>,
+    on_delete: Option<Vec<String>>,
+    on_index_complete: Option<Vec<String>>,
+    pic_dir: PathBuf,
+    aliases: Arc<Aliases>,
+    /// 已经因 `/dav` 上传执行过 `on_upload` 的图片，随后扫描发现它们时不再重复执行
+    uploaded: Mutex<HashSet<String>>,
+    queue: Sender<Job>,
+}
+
+/// 按空白拆分命令行，支持单引号和双引号，不经过 shell
+pub fn split_command(line: &str) -> Option<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote: Option<char> = None;
+    for c in line.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => current.push(c),
+            None if c == '\'' || c == '"' => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            None if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            None => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return None;
+    }
+    if in_arg {
+        args.push(current);
+    }
+    if args.is_empty() {
+        None
+    } else {
+        Some(args)
+    }
+}
+
+impl Hooks {
+    pub fn new(
+        on_upload: Option<Vec<String>>,
+        on_delete: Option<Vec<String>>,
+        on_index_complete: Option<Vec<String>>,
+        pic_dir: &str,
+        aliases: Arc<Aliases>,
+        timeout: Duration,
+    ) -> Self {
+        let (queue, rx) = mpsc::channel::<Job>();
+        // 钩子在图片目录中执行，传给它们的路径必须是绝对路径
+        let pic_dir = std::path::absolute(pic_dir).unwrap_or_else(|_| PathBuf::from(pic_dir));
+        let cwd = pic_dir.clone();
+        // 钩子按顺序在单独的线程中执行，不会阻塞请求，也不会同时跑很多进程
+        thread::Builder::new()
+            .name("hooks".to_string())
+            .spawn(move || {
+                for job in rx {
+                    run(&job, &cwd, timeout);
+                }
+            })
+            .expect("failed to spawn hook thread");
+        Self {
+            on_upload,
+            on_delete,
+            on_index_complete,
+            pic_dir,
+            aliases,
+            uploaded: Mutex::new(HashSet::new()),
+            queue,
+        }
+    }
+
+    fn enqueue(&self, name: &'static str, command: &Option<Vec<String>>, path: PathBuf) {
+        if let Some(command) = command {
+            let _ = self.queue.send(Job {
+                name,
+                command: command.clone(),
+                path,
+            });
+        }
+    }
+
+    /// 处理图片事件，`path` 为相对于图片目录的路径
+    pub fn handle(&self, event: EventKind, path: &str) {
+        let full = self.aliases.fs_path(&self.pic_dir, path);
+        match event {
+            EventKind::Uploaded => {
+                if let Ok(mut uploaded) = self.uploaded.lock() {
+                    uploaded.insert(path.to_string());
+                }
+                self.enqueue("on_upload", &self.on_upload, full);
+            }
+            EventKind::Added => {
+                let seen = self
+                    .uploaded
+                    .lock()
+                    .map(|mut uploaded| uploaded.remove(path))
+                    .unwrap_or(false);
+                if !seen {
+                    self.enqueue("on_upload", &self.on_upload, full);
+                }
+            }
+            EventKind::Removed => {
+                if let Ok(mut uploaded) = self.uploaded.lock() {
+                    uploaded.remove(path);
+                }
+                self.enqueue("on_delete", &self.on_delete, full);
+            }
+        }
+    }
+
+    /// 扫描完成且图片有变化（或首次扫描）时调用，参数为图片目录
+    pub fn index_complete(&self) {
+        self.enqueue("on_index_complete", &self.on_index_complete, self.pic_dir.clone());
+    }
+}
+
+fn run(job: &Job, cwd: &Path, timeout: Duration) {
+    let mut command = Command::new(&job.command[0]);
+    command
+        .args(&job.command[1..])
+        .arg(&job.path)
+        .current_dir(cwd)
+        .env_clear()
+        .env("PIC_HOOK", job.name)
+        .env("PIC_DIR", cwd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null());
+    for key in PASSTHROUGH_ENV {
+        if let Some(value) = std::env::var_os(key) {
+            command.env(key, value);
+        }
+    }
+
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            log::warn!("钩子 {} 启动失败 ({}): {}", job.name, job.command[0], e);
+            return;
+        }
+    };
+    let started = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                if !status.success() {
+                    log::warn!("钩子 {} 处理 {:?} 失败: {}", job.name, job.path, status);
+                }
+                return;
+            }
+            Ok(None) if started.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                log::warn!(
+                    "钩子 {} 处理 {:?} 超过 {} 秒，已终止",
+                    job.name,
+                    job.path,
+                    timeout.as_secs()
+                );
+                return;
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(50)),
+            Err(e) => {
+                log::warn!("钩子 {} 等待失败: {}", job.name, e);
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,241 @@
+//! 短 ID：给每张图片分配一个稳定的短 ID，通过 `/i/{id}` 访问。
+//! ID 保存在数据目录的 `ids.json` 中，图片改名或移动到其它目录后 ID 保持不变，
+//! 已经分享出去的短链接不会失效
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const ID_LEN: usize = 7;
+const ALPHABET: &[u8] = b"0123456789abcdefghijkmnopqrstuvwxyzABCDEFGHJKLMNPQRSTUVWXYZ";
+/// 计算指纹时读取的文件头长度
+const FINGERPRINT_BYTES: u64 = 64 * 1024;
+/// 图片消失后保留其 ID 的时间，期间同样内容的图片重新出现时沿用原来的 ID
+const ORPHAN_TTL: Duration = Duration::from_secs(30 * 24 * 3600);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Entry {
+    path: String,
+    /// 文件大小和文件头的哈希，用于识别改名/移动后的同一张图片；WebDAV 来源没有指纹
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fingerprint: Option<String>,
+    /// 图片从索引中消失的时间 (Unix 秒)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    missing_since: Option<u64>,
+}
+
+#[derive(Default)]
+struct IdState {
+    by_id: HashMap<String, Entry>,
+    by_path: HashMap<String, String>,
+}
+
+pub struct ShortIds {
+    path: PathBuf,
+    state: Mutex<IdState>,
+}
+
+/// 文件指纹：大小加上前 64 KiB 内容的 SHA-256
+pub fn fingerprint(path: &Path) -> Option<String> {
+    let file = File::open(path).ok()?;
+    let size = file.metadata().ok()?.len();
+    let mut hasher = Sha256::new();
+    let mut head = Vec::new();
+    file.take(FINGERPRINT_BYTES).read_to_end(&mut head).ok()?;
+    hasher.update(&head);
+    Some(format!("{}:{}", size, hex::encode(&hasher.finalize()[..16])))
+}
+
+/// 仍存在的图片的路径 → ID
+fn current_paths(by_id: &HashMap<String, Entry>) -> HashMap<String, String> {
+    by_id
+        .iter()
+        .filter(|(_, entry)| entry.missing_since.is_none())
+        .map(|(id, entry)| (entry.path.clone(), id.clone()))
+        .collect()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// 由路径、当前时间和重试次数派生一个新 ID
+fn generate(path: &str, attempt: u32) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(path.as_bytes());
+    hasher.update(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0)
+            .to_le_bytes(),
+    );
+    hasher.update(attempt.to_le_bytes());
+    hasher
+        .finalize()
+        .iter()
+        .take(ID_LEN)
+        .map(|b| ALPHABET[*b as usize % ALPHABET.len()] as char)
+        .collect()
+}
+
+impl ShortIds {
+    /// 从 `<data_dir>/ids.json` 加载已分配的 ID
+    pub fn load(data_dir: &str) -> Self {
+        let path = Path::new(data_dir).join("ids.json");
+        let by_id: HashMap<String, Entry> = fs::read(&path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        let by_path = current_paths(&by_id);
+        Self {
+            path,
+            state: Mutex::new(IdState { by_id, by_path }),
+        }
+    }
+
+    fn write(&self, by_id: &HashMap<String, Entry>, to: &Path) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_vec(by_id).map_err(io::Error::other)?;
+        fs::write(to, data)
+    }
+
+    fn save(&self, by_id: &HashMap<String, Entry>) -> io::Result<()> {
+        let tmp = self.path.with_extension("json.tmp");
+        self.write(by_id, &tmp)?;
+        fs::rename(&tmp, &self.path)
+    }
+
+    /// 根据最新的图片列表更新 ID：新图片的指纹与已消失图片相同时视为改名，沿用原 ID，
+    /// 否则分配新 ID。`fingerprint` 对新图片调用，返回 `None` 时不做改名识别；
+    /// `progress` 在处理新图片时以 (已处理, 总数) 调用
+    pub fn sync(
+        &self,
+        images: &[String],
+        fingerprint: impl Fn(&str) -> Option<String>,
+        progress: impl Fn(usize, usize),
+    ) {
+        let Ok(mut guard) = self.state.lock() else {
+            return;
+        };
+        let state = &mut *guard;
+        let current: HashSet<&str> = images.iter().map(String::as_str).collect();
+        let now = unix_now();
+        let mut changed = false;
+
+        // 标记已消失的图片，过期的直接删除
+        let gone: Vec<String> = state
+            .by_path
+            .keys()
+            .filter(|path| !current.contains(path.as_str()))
+            .cloned()
+            .collect();
+        for path in gone {
+            if let Some(id) = state.by_path.remove(&path) {
+                if let Some(entry) = state.by_id.get_mut(&id) {
+                    entry.missing_since = Some(now);
+                }
+            }
+            changed = true;
+        }
+        let before = state.by_id.len();
+        state.by_id.retain(|_, entry| match entry.missing_since {
+            Some(since) => now.saturating_sub(since) < ORPHAN_TTL.as_secs(),
+            None => true,
+        });
+        changed |= state.by_id.len() != before;
+
+        let mut orphans: HashMap<String, String> = state
+            .by_id
+            .iter()
+            .filter(|(_, entry)| entry.missing_since.is_some())
+            .filter_map(|(id, entry)| Some((entry.fingerprint.clone()?, id.clone())))
+            .collect();
+        let new_paths: Vec<&String> = images
+            .iter()
+            .filter(|path| !state.by_path.contains_key(*path))
+            .collect();
+        if !new_paths.is_empty() {
+            progress(0, new_paths.len());
+        }
+        for (i, path) in new_paths.iter().enumerate() {
+            let print = fingerprint(path);
+            let id = match print.as_ref().and_then(|p| orphans.remove(p)) {
+                Some(id) => id,
+                None => {
+                    let mut attempt = 0;
+                    let mut id = generate(path, attempt);
+                    while state.by_id.contains_key(&id) {
+                        attempt += 1;
+                        id = generate(path, attempt);
+                    }
+                    id
+                }
+            };
+            state.by_id.insert(
+                id.clone(),
+                Entry {
+                    path: path.to_string(),
+                    fingerprint: print,
+                    missing_since: None,
+                },
+            );
+            state.by_path.insert(path.to_string(), id);
+            changed = true;
+            progress(i + 1, new_paths.len());
+        }
+
+        if changed {
+            if let Err(e) = self.save(&state.by_id) {
+                log::warn!("保存短 ID 失败: {}", e);
+            }
+        }
+    }
+
+    /// 图片当前的短 ID
+    pub fn id_of(&self, path: &str) -> Option<String> {
+        self.state.lock().ok()?.by_path.get(path).cloned()
+    }
+
+    /// 短 ID 对应的图片路径，图片已消失时返回 `None`
+    pub fn resolve(&self, id: &str) -> Option<String> {
+        let state = self.state.lock().ok()?;
+        state
+            .by_id
+            .get(id)
+            .filter(|entry| entry.missing_since.is_none())
+            .map(|entry| entry.path.clone())
+    }
+
+    /// 全部已分配的 ID，用于[备份元数据](crate::metadb)
+    pub fn contents(&self) -> HashMap<String, Entry> {
+        self.state.lock().map(|state| state.by_id.clone()).unwrap_or_default()
+    }
+
+    /// 恢复备份的第一步：把备份中的 ID写到临时文件，全部数据都写好后再 [`commit`](Self::commit)
+    pub fn stage(&self, contents: &HashMap<String, Entry>) -> io::Result<PathBuf> {
+        let staged = self.path.with_extension("json.restore");
+        self.write(contents, &staged)?;
+        Ok(staged)
+    }
+
+    /// 换上 [`stage`](Self::stage) 写好的文件，用备份中的内容替换全部 ID；
+    /// 路径与当前图片的对应在下一次扫描时更新
+    pub fn commit(&self, staged: &Path, contents: HashMap<String, Entry>) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        fs::rename(staged, &self.path)?;
+        state.by_path = current_paths(&contents);
+        state.by_id = contents;
+        Ok(())
+    }
+}
@@ -0,0 +1,404 @@
+//! `pic_url import <源目录>`：把已有的照片目录导入图片目录。按内容去重（与图片目录中已有的或
+//! 本次已导入的相同时跳过），可以按拍摄日期放入 `年/月` 目录、按命名模板改名，`--dry-run` 只列出
+//! 计划而不修改文件，最后打印统计。导入时先写临时文件再改名，服务运行时也可以导入。
+//!
+//! `--takeout` 导入 Google Takeout 导出的照片：不保留 Takeout 的目录结构（年份目录和相册目录中的
+//! 同一张照片按内容去重），有原图时跳过 `-edited` 副本，拍摄时间、位置和说明取自 JSON 元数据，
+//! 写入没有 EXIF 的 JPEG，导入后文件的修改时间为拍摄时间
+
+use crate::aliases::Aliases;
+use crate::optimize::format_bytes;
+use crate::sidecars::{Finder, SidecarPolicy};
+use crate::takeout::{self, Meta};
+use crate::{cas, collect_images, jpeg, names, timeline};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// 命名模板中可用的占位符
+const PLACEHOLDERS: [&str; 7] = ["{name}", "{date}", "{time}", "{year}", "{month}", "{day}", "{hash}"];
+
+struct Options {
+    source: PathBuf,
+    pic_dir: PathBuf,
+    /// 导入到图片目录下的这个子目录
+    folder: String,
+    move_files: bool,
+    date_folders: bool,
+    /// 不含扩展名的文件名模板，如 `{date}_{time}`
+    rename: Option<String>,
+    dry_run: bool,
+    /// 源目录是 Google Takeout 导出的照片
+    takeout: bool,
+}
+
+#[derive(Default)]
+struct Report {
+    imported: usize,
+    imported_bytes: u64,
+    duplicates: usize,
+    duplicate_bytes: u64,
+    /// 有原图而跳过的编辑后的副本
+    edited: usize,
+    /// 写入了 Takeout 元数据的图片
+    tagged: usize,
+    failed: usize,
+}
+
+/// 命名模板是否可用：不能为空，不能包含路径分隔符，只能使用已知的占位符
+fn valid_template(template: &str) -> bool {
+    let mut rest = template.to_string();
+    for placeholder in PLACEHOLDERS {
+        rest = rest.replace(placeholder, "");
+    }
+    !template.trim().is_empty() && !template.contains(['/', '\\']) && !rest.contains(['{', '}'])
+}
+
+/// 按模板生成文件名（不含扩展名）；`taken` 为 `YYYY-MM-DDTHH:MM:SS`
+fn apply_template(template: &str, stem: &str, taken: &str, hash: &str) -> String {
+    let date = taken.get(..10).unwrap_or_default();
+    let time = taken.get(11..19).unwrap_or_default().replace(':', "");
+    let name = template
+        .replace("{name}", stem)
+        .replace("{date}", date)
+        .replace("{time}", &time)
+        .replace("{year}", date.get(..4).unwrap_or_default())
+        .replace("{month}", date.get(5..7).unwrap_or_default())
+        .replace("{day}", date.get(8..10).unwrap_or_default())
+        .replace("{hash}", hash.get(..8).unwrap_or_default());
+    let name = name.trim();
+    // 结果为空或以 `.` 开头（会成为隐藏文件）时保留原来的文件名
+    if name.is_empty() || name.starts_with('.') {
+        stem.to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// 拍摄时间，没有 EXIF 时使用 `fallback`（Takeout 元数据中的时间），再没有时使用修改时间
+fn taken(path: &Path, fallback: Option<SystemTime>) -> io::Result<String> {
+    let modified = match fallback {
+        Some(time) => time,
+        None => fs::metadata(path)?.modified()?,
+    };
+    let exif = exif::Reader::new().read_from_container(&mut BufReader::new(File::open(path)?)).ok();
+    Ok(timeline::taken_or_modified(exif.as_ref(), modified))
+}
+
+/// 图片目录中已有的内容。文件较多时全部计算哈希很慢，只计算与导入的文件大小相同的
+struct Existing {
+    by_size: HashMap<u64, Vec<PathBuf>>,
+    /// 哈希 → 相对路径（图片目录中的或本次导入的）
+    hashes: HashMap<String, String>,
+    hashed: HashSet<PathBuf>,
+}
+
+impl Existing {
+    fn scan(pic_dir: &Path) -> Self {
+        let mut images = Vec::new();
+        if let Err(e) = collect_images(pic_dir, pic_dir, &mut images) {
+            eprintln!("警告: 跳过无法读取的目录 {}", e);
+        }
+        let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+        for relative in images {
+            let path = names::fs_path(pic_dir, &relative);
+            if let Ok(meta) = fs::metadata(&path) {
+                by_size.entry(meta.len()).or_default().push(path);
+            }
+        }
+        Self {
+            by_size,
+            hashes: HashMap::new(),
+            hashed: HashSet::new(),
+        }
+    }
+
+    /// 与 `hash` 内容相同的已有图片
+    fn find(&mut self, pic_dir: &Path, size: u64, hash: &str) -> Option<String> {
+        for path in self.by_size.get(&size).into_iter().flatten() {
+            if self.hashed.insert(path.clone()) {
+                if let Ok(existing) = cas::hash_file(path) {
+                    let relative = path.strip_prefix(pic_dir).map(names::relative).unwrap_or_default();
+                    self.hashes.entry(existing).or_insert(relative);
+                }
+            }
+        }
+        self.hashes.get(hash).cloned()
+    }
+}
+
+/// 先复制到目标目录中的临时文件再改名，保留修改时间
+fn copy_file(source: &Path, target: &Path) -> io::Result<()> {
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = target.with_file_name(format!(".{}.importing", name));
+    let copied = (|| {
+        fs::copy(source, &tmp)?;
+        let modified = fs::metadata(source)?.modified()?;
+        File::options().write(true).open(&tmp)?.set_modified(modified)?;
+        fs::rename(&tmp, target)
+    })();
+    if copied.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    copied
+}
+
+/// 移动文件，不在同一个文件系统时复制后删除源文件
+fn move_file(source: &Path, target: &Path) -> io::Result<()> {
+    if fs::rename(source, target).is_ok() {
+        return Ok(());
+    }
+    copy_file(source, target)?;
+    fs::remove_file(source)
+}
+
+/// 把 `data` 经临时文件写入 `target`，修改时间设为 `modified`
+fn write_file(data: &[u8], target: &Path, modified: SystemTime) -> io::Result<()> {
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = target.with_file_name(format!(".{}.importing", name));
+    let written = (|| {
+        fs::write(&tmp, data)?;
+        File::options().write(true).open(&tmp)?.set_modified(modified)?;
+        fs::rename(&tmp, target)
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    written
+}
+
+/// 给没有 EXIF 的 JPEG 写入 Takeout 元数据后的内容；其他图片原样导入，返回 `None`
+fn with_metadata(source: &Path, meta: &Meta) -> io::Result<Option<Vec<u8>>> {
+    let is_jpeg = source
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("jpg") || ext.eq_ignore_ascii_case("jpeg"));
+    let Some(tiff) = meta.exif_tiff().filter(|_| is_jpeg) else {
+        return Ok(None);
+    };
+    Ok(jpeg::add_exif(&fs::read(source)?, &tiff))
+}
+
+fn run(options: &Options) -> Report {
+    let mut report = Report::default();
+    let mut sources = Vec::new();
+    if let Err(e) = collect_images(&options.source, &options.source, &mut sources) {
+        eprintln!("警告: 跳过无法读取的目录 {}", e);
+    }
+    println!("找到 {} 张图片，正在检查图片目录中已有的内容...", sources.len());
+    let mut existing = Existing::scan(&options.pic_dir);
+    // 本次计划写入的路径，预览时文件还不存在，也要避开
+    let mut planned = HashSet::new();
+    let all: HashSet<&str> = sources.iter().map(String::as_str).collect();
+    // 导入的来源目录没有别名
+    let no_aliases = Aliases::default();
+    let mut finder = Finder::new(&options.source, &no_aliases, SidecarPolicy::Metadata);
+
+    for relative in &sources {
+        if options.takeout {
+            if let Some(original) = takeout::edited_original(relative).filter(|o| all.contains(o.as_str())) {
+                println!("  跳过: {} (编辑后的副本，保留 {})", relative, original);
+                report.edited += 1;
+                continue;
+            }
+        }
+        let source = names::fs_path(&options.source, relative);
+        let result = (|| -> io::Result<()> {
+            let size = fs::metadata(&source)?.len();
+            let hash = cas::hash_file(&source)?;
+            let meta = if options.takeout { Meta::find(&mut finder, &options.source, relative) } else { None };
+            let prepared = match &meta {
+                Some(meta) => with_metadata(&source, meta)?,
+                None => None,
+            };
+            // 写入元数据前后的内容都可能已经导入过
+            let (source_size, source_hash) = (size, hash);
+            let (size, hash) = match &prepared {
+                Some(data) => (data.len() as u64, hex::encode(Sha256::digest(data))),
+                None => (size, source_hash.clone()),
+            };
+            let same = existing
+                .find(&options.pic_dir, source_size, &source_hash)
+                .or_else(|| existing.find(&options.pic_dir, size, &hash));
+            if let Some(same) = same {
+                println!("  重复: {} (与 {} 相同)", relative, same);
+                report.duplicates += 1;
+                report.duplicate_bytes += source_size;
+                return Ok(());
+            }
+            let taken_time = meta.as_ref().and_then(|meta| meta.taken);
+
+            let taken = taken(&source, taken_time)?;
+            let (parent, name) = relative.rsplit_once('/').unwrap_or(("", relative));
+            let folder = if options.date_folders {
+                format!("{}/{}", taken.get(..4).unwrap_or("0000"), taken.get(5..7).unwrap_or("00"))
+            } else if options.takeout {
+                // Takeout 的年份目录和相册目录没有意义，放在一起
+                String::new()
+            } else {
+                parent.to_string()
+            };
+            let folder = [options.folder.as_str(), folder.as_str()]
+                .into_iter()
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>()
+                .join("/");
+            let name = match (&options.rename, name.rsplit_once('.')) {
+                (Some(template), Some((stem, ext))) => {
+                    format!("{}.{}", apply_template(template, stem, &taken, &hash), ext)
+                }
+                _ => name.to_string(),
+            };
+            let target = free_path(&options.pic_dir, &folder, &name, &planned);
+            planned.insert(target.clone());
+            existing.hashes.insert(hash, target.clone());
+
+            println!("  {} -> {}{}", relative, target, if prepared.is_some() { " (写入元数据)" } else { "" });
+            if !options.dry_run {
+                fs::create_dir_all(names::fs_path(&options.pic_dir, &folder))?;
+                let target = names::fs_path(&options.pic_dir, &target);
+                match &prepared {
+                    Some(data) => {
+                        write_file(data, &target, taken_time.unwrap_or(fs::metadata(&source)?.modified()?))?;
+                        if options.move_files {
+                            fs::remove_file(&source)?;
+                        }
+                    }
+                    None if options.move_files => move_file(&source, &target)?,
+                    None => copy_file(&source, &target)?,
+                }
+                // 没有 EXIF 时图库按修改时间排序和归入时间线
+                if let Some(taken_time) = taken_time {
+                    File::options().write(true).open(&target)?.set_modified(taken_time)?;
+                }
+            }
+            if prepared.is_some() {
+                report.tagged += 1;
+            }
+            report.imported += 1;
+            report.imported_bytes += size;
+            Ok(())
+        })();
+        if let Err(e) = result {
+            eprintln!("  失败: {}: {}", relative, e);
+            report.failed += 1;
+        }
+    }
+    report
+}
+
+/// `folder` 下可用的相对路径：同名文件已存在或本次已计划写入时依次尝试 `name (1).ext`、`name (2).ext`……
+fn free_path(pic_dir: &Path, folder: &str, name: &str, planned: &HashSet<String>) -> String {
+    let path = |name: &str| if folder.is_empty() { name.to_string() } else { format!("{}/{}", folder, name) };
+    let free = |relative: &String| !planned.contains(relative) && !names::fs_path(pic_dir, relative).exists();
+    let (stem, ext) = name.rsplit_once('.').unwrap_or((name, ""));
+    std::iter::once(path(name))
+        .chain((1..).map(|n| path(&format!("{} ({}).{}", stem, n, ext))))
+        .find(free)
+        .expect("总能找到可用的文件名")
+}
+
+/// `pic_url import <源目录> [-d 图片目录] [--folder 子目录] [--move] [--date-folders] [--rename 模板] [--takeout]
+/// [--dry-run]`
+pub fn run_command(args: &[String]) -> ! {
+    let usage = || -> ! {
+        eprintln!(
+            "用法: pic_url import <源目录> [-d 图片目录] [--folder 子目录] [--move] [--date-folders] \
+             [--rename 模板] [--takeout] [--dry-run]"
+        );
+        eprintln!("命名模板可用: {}", PLACEHOLDERS.join(" "));
+        std::process::exit(1);
+    };
+    let mut pic_dir = std::env::var("PIC_DIR").unwrap_or_else(|_| "./pic".to_string());
+    let mut source = None;
+    let mut folder = String::new();
+    let (mut move_files, mut date_folders, mut dry_run, mut takeout) = (false, false, false, false);
+    let mut rename = None;
+    let mut i = 0;
+    while i < args.len() {
+        match (args[i].as_str(), args.get(i + 1)) {
+            ("-d" | "--dir", Some(value)) => {
+                pic_dir = value.clone();
+                i += 1;
+            }
+            ("--folder", Some(value)) => {
+                folder = value.trim_matches('/').to_string();
+                i += 1;
+            }
+            ("--rename", Some(value)) => {
+                if !valid_template(value) {
+                    usage();
+                }
+                rename = Some(value.clone());
+                i += 1;
+            }
+            ("--move", _) => move_files = true,
+            ("--date-folders", _) => date_folders = true,
+            ("--dry-run", _) => dry_run = true,
+            ("--takeout", _) => takeout = true,
+            (value, _) if source.is_none() && !value.starts_with('-') => source = Some(PathBuf::from(value)),
+            _ => usage(),
+        }
+        i += 1;
+    }
+    let Some(source) = source else { usage() };
+    if !folder.is_empty() && !crate::is_valid_relative(&folder) {
+        usage();
+    }
+    let (Ok(source), Ok(pic_dir)) = (fs::canonicalize(&source), fs::canonicalize(&pic_dir)) else {
+        eprintln!("错误: 源目录或图片目录不存在");
+        std::process::exit(1);
+    };
+    // 一个在另一个里面时，导入的文件会再被扫描到
+    if source.starts_with(&pic_dir) || pic_dir.starts_with(&source) {
+        eprintln!("错误: 源目录和图片目录不能互相包含");
+        std::process::exit(1);
+    }
+
+    let options = Options {
+        source,
+        pic_dir,
+        folder,
+        move_files,
+        date_folders,
+        rename,
+        dry_run,
+        takeout,
+    };
+    let report = run(&options);
+    println!(
+        "{}: 导入 {} 张 ({}), 跳过重复 {} 张 ({}), 失败 {} 张",
+        if options.dry_run { "预计" } else { "完成" },
+        report.imported,
+        format_bytes(report.imported_bytes),
+        report.duplicates,
+        format_bytes(report.duplicate_bytes),
+        report.failed
+    );
+    if options.takeout {
+        println!("Takeout: 写入元数据 {} 张, 跳过编辑后的副本 {} 张", report.tagged, report.edited);
+    }
+    std::process::exit(if report.failed == 0 { 0 } else { 1 });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_rename_templates() {
+        let taken = "2024-05-01T12:34:56";
+        let hash = "3f9a1c0e7b2d4a68";
+        assert_eq!(apply_template("{date}_{time}", "IMG_0001", taken, hash), "2024-05-01_123456");
+        assert_eq!(apply_template("{year}{month}{day}-{name}", "IMG_0001", taken, hash), "20240501-IMG_0001");
+        assert_eq!(apply_template("{hash}", "IMG_0001", taken, hash), "3f9a1c0e");
+        assert_eq!(apply_template(" ", "IMG_0001", taken, hash), "IMG_0001");
+        assert!(valid_template("{date} {name}"));
+        assert!(!valid_template("{year}/{name}"));
+        assert!(!valid_template("{camera}"));
+        assert!(!valid_template(""));
+    }
+}
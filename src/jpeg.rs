@@ -0,0 +1,331 @@
+//! JPEG 的 EXIF 处理：旋转时只改写方向标记（Orientation），不重新压缩图像数据，
+//! 浏览器、缩略图和常见的看图软件都会按这个标记转正显示；
+//! 裁剪、缩放后把原图的 EXIF 复制到重新编码的文件中；
+//! 导入 Google Takeout 时给没有 EXIF 的照片写入 JSON 中的元数据；
+//! 优化时去掉 EXIF 中嵌入的缩略图和注释段
+
+/// 各个 EXIF 方向对应的变换：（是否先水平翻转，再顺时针旋转几个 90°）
+const TRANSFORMS: [(bool, u8); 8] = [
+    (false, 0),
+    (true, 0),
+    (false, 2),
+    (true, 2),
+    (true, 3),
+    (false, 1),
+    (true, 1),
+    (false, 3),
+];
+
+const ORIENTATION_TAG: u16 = 0x0112;
+/// IFD1 中缩略图的偏移和长度
+const THUMBNAIL_OFFSET_TAG: u16 = 0x0201;
+const THUMBNAIL_LENGTH_TAG: u16 = 0x0202;
+/// 指向 Exif、GPS、互操作性子 IFD 的字段
+const SUB_IFD_TAGS: [u16; 3] = [0x8769, 0x8825, 0xA005];
+/// TIFF 字段类型 SHORT
+const SHORT: u16 = 3;
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+
+/// 在原来的方向上再顺时针旋转 `quarter_turns` 个 90° 后的方向
+fn rotated(orientation: u16, quarter_turns: u8) -> u16 {
+    let (flip, turns) = (orientation as usize)
+        .checked_sub(1)
+        .and_then(|i| TRANSFORMS.get(i))
+        .copied()
+        .unwrap_or((false, 0));
+    let target = (flip, (turns + quarter_turns) % 4);
+    TRANSFORMS.iter().position(|t| *t == target).map_or(1, |i| i as u16 + 1)
+}
+
+/// 按 TIFF 头声明的字节序读写
+struct Endian {
+    big: bool,
+}
+
+impl Endian {
+    fn u16(&self, data: &[u8], at: usize) -> Option<u16> {
+        let bytes: [u8; 2] = data.get(at..at + 2)?.try_into().ok()?;
+        Some(if self.big { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    }
+
+    fn u32(&self, data: &[u8], at: usize) -> Option<u32> {
+        let bytes: [u8; 4] = data.get(at..at + 4)?.try_into().ok()?;
+        Some(if self.big { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+
+    fn u16_bytes(&self, value: u16) -> [u8; 2] {
+        if self.big { value.to_be_bytes() } else { value.to_le_bytes() }
+    }
+
+    fn u32_bytes(&self, value: u32) -> [u8; 4] {
+        if self.big { value.to_be_bytes() } else { value.to_le_bytes() }
+    }
+}
+
+/// 只含方向标记的 TIFF 数据
+fn minimal_tiff(orientation: u16) -> Vec<u8> {
+    // 大端 TIFF 头，IFD0 紧随其后
+    let mut tiff = b"MM\0\x2a\0\0\0\x08".to_vec();
+    tiff.extend_from_slice(&1u16.to_be_bytes());
+    tiff.extend_from_slice(&ORIENTATION_TAG.to_be_bytes());
+    tiff.extend_from_slice(&SHORT.to_be_bytes());
+    tiff.extend_from_slice(&1u32.to_be_bytes());
+    tiff.extend_from_slice(&orientation.to_be_bytes());
+    tiff.extend_from_slice(&[0, 0]);
+    // 没有下一个 IFD
+    tiff.extend_from_slice(&[0, 0, 0, 0]);
+    tiff
+}
+
+/// 在 TIFF 数据中把方向改为 `update(原来的方向)`：已有标记时原地改写；
+/// 没有时在末尾写一份加上方向标记的 IFD0 并让 TIFF 头指向它，
+/// 其他字段的偏移都相对于 TIFF 头，不受影响
+fn set_orientation(tiff: &mut Vec<u8>, update: impl Fn(u16) -> u16) -> Option<()> {
+    let endian = match tiff.get(..2)? {
+        b"II" => Endian { big: false },
+        b"MM" => Endian { big: true },
+        _ => return None,
+    };
+    let ifd = endian.u32(tiff, 4)? as usize;
+    let count = endian.u16(tiff, ifd)? as usize;
+    let entries = ifd + 2;
+    let next = endian.u32(tiff, entries + count * 12)?;
+    let mut insert_at = count;
+    for i in 0..count {
+        let entry = entries + i * 12;
+        let tag = endian.u16(tiff, entry)?;
+        if tag == ORIENTATION_TAG {
+            if endian.u16(tiff, entry + 2)? != SHORT {
+                return None;
+            }
+            let value = update(endian.u16(tiff, entry + 8)?);
+            tiff[entry + 8..entry + 10].copy_from_slice(&endian.u16_bytes(value));
+            return Some(());
+        }
+        if tag > ORIENTATION_TAG && insert_at == count {
+            insert_at = i;
+        }
+    }
+
+    let old_entries = tiff[entries..entries + count * 12].to_vec();
+    // IFD 需要从偶数偏移开始
+    if tiff.len() % 2 == 1 {
+        tiff.push(0);
+    }
+    let new_ifd = u32::try_from(tiff.len()).ok()?;
+    tiff.extend_from_slice(&endian.u16_bytes(u16::try_from(count + 1).ok()?));
+    tiff.extend_from_slice(&old_entries[..insert_at * 12]);
+    tiff.extend_from_slice(&endian.u16_bytes(ORIENTATION_TAG));
+    tiff.extend_from_slice(&endian.u16_bytes(SHORT));
+    tiff.extend_from_slice(&endian.u32_bytes(1));
+    tiff.extend_from_slice(&endian.u16_bytes(update(1)));
+    tiff.extend_from_slice(&[0, 0]);
+    tiff.extend_from_slice(&old_entries[insert_at * 12..]);
+    tiff.extend_from_slice(&endian.u32_bytes(next));
+    tiff[4..8].copy_from_slice(&endian.u32_bytes(new_ifd));
+    Some(())
+}
+
+/// 各个 TIFF 字段类型每个值的字节数
+fn type_size(field_type: u16) -> Option<usize> {
+    match field_type {
+        1 | 2 | 6 | 7 => Some(1),
+        3 | 8 => Some(2),
+        4 | 9 | 11 => Some(4),
+        5 | 10 | 12 => Some(8),
+        _ => None,
+    }
+}
+
+/// IFD 及其子 IFD 占用的数据（包括放在 IFD 之外的字段值）的最大结束位置
+fn ifd_end(tiff: &[u8], endian: &Endian, ifd: usize, depth: u8) -> Option<usize> {
+    if depth > 4 {
+        return None;
+    }
+    let count = endian.u16(tiff, ifd)? as usize;
+    let mut end = ifd + 2 + count * 12 + 4;
+    for i in 0..count {
+        let entry = ifd + 2 + i * 12;
+        let tag = endian.u16(tiff, entry)?;
+        let size = type_size(endian.u16(tiff, entry + 2)?)?.checked_mul(endian.u32(tiff, entry + 4)? as usize)?;
+        let value = endian.u32(tiff, entry + 8)? as usize;
+        if size > 4 {
+            end = end.max(value.checked_add(size)?);
+        }
+        if SUB_IFD_TAGS.contains(&tag) {
+            end = end.max(ifd_end(tiff, endian, value, depth + 1)?);
+        }
+    }
+    Some(end)
+}
+
+/// 去掉 TIFF 数据末尾的 IFD1 和缩略图。只处理它们位于最后、
+/// 其他字段的数据都在它们之前的常见布局，否则返回 `None`
+fn drop_thumbnail(tiff: &mut Vec<u8>) -> Option<()> {
+    let endian = match tiff.get(..2)? {
+        b"II" => Endian { big: false },
+        b"MM" => Endian { big: true },
+        _ => return None,
+    };
+    let ifd0 = endian.u32(tiff, 4)? as usize;
+    let next_at = ifd0 + 2 + endian.u16(tiff, ifd0)? as usize * 12;
+    let ifd1 = endian.u32(tiff, next_at)? as usize;
+    if ifd1 == 0 {
+        return None;
+    }
+    let count = endian.u16(tiff, ifd1)? as usize;
+    let mut thumbnail = (None, None);
+    for i in 0..count {
+        let entry = ifd1 + 2 + i * 12;
+        match endian.u16(tiff, entry)? {
+            THUMBNAIL_OFFSET_TAG => thumbnail.0 = Some(endian.u32(tiff, entry + 8)? as usize),
+            THUMBNAIL_LENGTH_TAG => thumbnail.1 = Some(endian.u32(tiff, entry + 8)? as usize),
+            _ => {}
+        }
+    }
+    let (Some(offset), Some(length)) = thumbnail else {
+        return None;
+    };
+    let start = ifd1.min(offset);
+    // IFD1 之后还有它的下一个 IFD 时不处理
+    if endian.u32(tiff, ifd1 + 2 + count * 12)? != 0
+        || ifd_end(tiff, &endian, ifd1, 0)?.max(offset.checked_add(length)?) + 1 < tiff.len()
+        || ifd_end(tiff, &endian, ifd0, 0)? > start
+    {
+        return None;
+    }
+    tiff.truncate(start);
+    tiff[next_at..next_at + 4].copy_from_slice(&[0, 0, 0, 0]);
+    Some(())
+}
+
+/// 文件中的位置：没有 EXIF 时新段应插入的位置，以及 EXIF 段的起止（含段头）
+struct Layout {
+    insert_at: usize,
+    exif: Option<(usize, usize)>,
+}
+
+fn layout(data: &[u8]) -> Option<Layout> {
+    if data.get(..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+    // 新段放在 SOI 之后，有 JFIF (APP0) 段时放在它后面
+    let mut insert_at = 2;
+    let mut pos = 2;
+    loop {
+        if *data.get(pos)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(pos + 1)?;
+        // 图像数据开始（SOS）之后不会再有元数据
+        if marker == 0xDA || marker == 0xD9 {
+            return Some(Layout { insert_at, exif: None });
+        }
+        let len = u16::from_be_bytes(data.get(pos + 2..pos + 4)?.try_into().ok()?) as usize;
+        let end = pos + 2 + len;
+        let content = data.get(pos + 4..end)?;
+        if marker == 0xE1 && content.starts_with(EXIF_HEADER) {
+            return Some(Layout {
+                insert_at,
+                exif: Some((pos, end)),
+            });
+        }
+        if marker == 0xE0 && insert_at == pos {
+            insert_at = end;
+        }
+        pos = end;
+    }
+}
+
+/// 用 `tiff` 组成的 EXIF 段替换 `data[start..end]`（`start == end` 时为插入）
+fn splice_exif(data: &[u8], start: usize, end: usize, tiff: &[u8]) -> Option<Vec<u8>> {
+    let segment_len = u16::try_from(2 + EXIF_HEADER.len() + tiff.len()).ok()?;
+    let mut out = Vec::with_capacity(data.len() + tiff.len() + EXIF_HEADER.len() + 4);
+    out.extend_from_slice(&data[..start]);
+    out.extend_from_slice(&[0xFF, 0xE1]);
+    out.extend_from_slice(&segment_len.to_be_bytes());
+    out.extend_from_slice(EXIF_HEADER);
+    out.extend_from_slice(tiff);
+    out.extend_from_slice(&data[end..]);
+    Some(out)
+}
+
+/// 把 JPEG 顺时针旋转 `quarter_turns` 个 90°，返回新的文件内容；
+/// 文件结构无法识别或 EXIF 段放不下时返回 `None`，由调用方重新编码
+pub fn rotate(data: &[u8], quarter_turns: u8) -> Option<Vec<u8>> {
+    let layout = layout(data)?;
+    let update = |orientation| rotated(orientation, quarter_turns);
+    match layout.exif {
+        Some((start, end)) => {
+            let mut tiff = data[start + 4 + EXIF_HEADER.len()..end].to_vec();
+            set_orientation(&mut tiff, update)?;
+            splice_exif(data, start, end, &tiff)
+        }
+        None => {
+            splice_exif(data, layout.insert_at, layout.insert_at, &minimal_tiff(update(1)))
+        }
+    }
+}
+
+/// 把 `original` 的 EXIF 复制到重新编码的 `encoded` 中，保留拍摄时间、相机等信息；
+/// 新图已经转正，方向改为 1。`original` 没有 EXIF 或无法识别时返回 `None`
+pub fn copy_exif(original: &[u8], encoded: &[u8]) -> Option<Vec<u8>> {
+    let (start, end) = layout(original)?.exif?;
+    let mut tiff = original[start + 4 + EXIF_HEADER.len()..end].to_vec();
+    set_orientation(&mut tiff, |_| 1)?;
+    let target = layout(encoded)?;
+    let (start, end) = target.exif.unwrap_or((target.insert_at, target.insert_at));
+    splice_exif(encoded, start, end, &tiff)
+}
+
+/// 给没有 EXIF 的 JPEG 加上由 `tiff` 组成的 EXIF 段；已有 EXIF 或文件结构无法识别时返回 `None`
+pub fn add_exif(data: &[u8], tiff: &[u8]) -> Option<Vec<u8>> {
+    let layout = layout(data)?;
+    if layout.exif.is_some() {
+        return None;
+    }
+    splice_exif(data, layout.insert_at, layout.insert_at, tiff)
+}
+
+/// 去掉 EXIF 中嵌入的缩略图和注释段（COM），图像数据和其他元数据不变；
+/// 没有可以去掉的内容或文件结构无法识别时返回 `None`
+pub fn strip(data: &[u8]) -> Option<Vec<u8>> {
+    if data.get(..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(&data[..2]);
+    let mut changed = false;
+    let mut pos = 2;
+    loop {
+        if *data.get(pos)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(pos + 1)?;
+        if marker == 0xDA || marker == 0xD9 {
+            break;
+        }
+        let len = u16::from_be_bytes(data.get(pos + 2..pos + 4)?.try_into().ok()?) as usize;
+        let end = pos + 2 + len;
+        let content = data.get(pos + 4..end)?;
+        if marker == 0xFE {
+            changed = true;
+        } else if marker == 0xE1 && content.starts_with(EXIF_HEADER) {
+            let mut tiff = content[EXIF_HEADER.len()..].to_vec();
+            if drop_thumbnail(&mut tiff).is_some() {
+                out.extend_from_slice(&splice_exif(&[], 0, 0, &tiff)?);
+                changed = true;
+            } else {
+                out.extend_from_slice(&data[pos..end]);
+            }
+        } else {
+            out.extend_from_slice(&data[pos..end]);
+        }
+        pos = end;
+    }
+    if !changed {
+        return None;
+    }
+    out.extend_from_slice(&data[pos..]);
+    Some(out)
+}
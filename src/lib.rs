@@ -0,0 +1,2285 @@
+//! 本地图床服务器。除了作为独立程序运行（[`run`]），也可以通过 [`PicUrl`] 把画廊挂到
+//! 其他 actix-web 应用中，或在集成测试中直接调用处理函数
+
+use actix_files::NamedFile;
+use actix_web::dev::Service;
+use actix_web::http::header;
+use actix_web::middleware::{self, ErrorHandlers};
+use actix_web::{get, web, App, HttpMessage, HttpRequest, HttpResponse, HttpServer, Result};
+use image::imageops::FilterType;
+use image::{GenericImageView, ImageDecoder, ImageReader};
+use serde::Serialize;
+use std::collections::hash_map::{Entry, HashMap};
+use std::fs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+mod access_log;
+mod admin;
+mod aliases;
+mod albums;
+mod api_keys;
+mod assets;
+mod auth_log;
+mod backup;
+mod batch;
+mod builder;
+mod cas;
+mod cli;
+mod cron;
+mod dedupe;
+mod dav;
+mod details;
+mod dimensions;
+mod discovery;
+mod edit;
+mod errors;
+mod export;
+mod folding;
+mod frame;
+mod galleries;
+#[cfg(feature = "dlna")]
+mod dlna;
+#[cfg(feature = "grpc")]
+mod grpc;
+#[cfg(feature = "graphql")]
+mod graphql;
+mod doctor;
+mod events;
+mod homes;
+mod hooks;
+mod ids;
+mod import;
+mod jpeg;
+mod limit;
+mod listing;
+mod logging;
+mod maintenance;
+mod memcache;
+mod metadb;
+mod metrics;
+mod missing;
+mod names;
+mod optimize;
+mod payload;
+mod png;
+mod policy;
+mod popularity;
+mod potd;
+mod proxy;
+mod pwa;
+mod qr;
+mod quarantine;
+mod range;
+mod replication;
+mod request_id;
+mod scan;
+mod scanner;
+mod scheduler;
+mod session;
+mod sidecars;
+mod share;
+mod signed;
+mod sitemap;
+mod snapshot;
+mod sniff;
+mod stats;
+mod tags;
+mod takeout;
+mod timeline;
+mod templates;
+mod theme;
+mod thumbs;
+mod tiers;
+mod upload;
+mod usage;
+mod versioning;
+mod versions;
+#[cfg(feature = "otel")]
+mod telemetry;
+#[cfg(feature = "tls")]
+mod tls;
+mod webdav;
+mod webhook;
+mod ws;
+
+use access_log::AccessLogConfig;
+use logging::LogFormat;
+use albums::Albums;
+use aliases::Aliases;
+use homes::Viewer;
+use api_keys::ApiKeys;
+use backup::Backup;
+use maintenance::Maintenance;
+use memcache::MemoryCache;
+use export::Exporter;
+use optimize::Optimizer;
+use popularity::Popularity;
+use quarantine::{Quarantine, Stamp};
+pub use builder::{PicUrl, PicUrlBuilder};
+pub use cli::parse_args;
+pub use errors::{ApiError, AppError};
+pub use folding::PathMatch;
+pub use sidecars::SidecarPolicy;
+pub use thumbs::{get_thumbnail_path, ThumbCheck};
+use events::EventBus;
+use hooks::Hooks;
+use scanner::Scanner;
+use ids::ShortIds;
+use tags::Tags;
+use templates::Templates;
+use tiers::Tiers;
+use dimensions::Dimensions;
+use timeline::Captures;
+use limit::Limiter;
+use listing::ImageListQuery;
+use metrics::METRICS;
+use replication::Replicator;
+use scan::{Phase, ScanProgress, ScheduledRescan};
+use session::Sessions;
+use tracing::Instrument;
+use usage::FolderUsage;
+use webdav::WebDavSource;
+use webhook::{EventKind, Webhooks};
+
+/// 缩略图长边的默认像素数，可用 `--thumb-size` 修改
+const THUMB_SIZE: u32 = 200;
+
+/// 缩略图缓存目录名
+const THUMB_DIR_NAME: &str = ".thumbnails";
+/// 程序自身状态（同步记录等）的目录名
+const DATA_DIR_NAME: &str = ".pic_url";
+/// 被旋转、裁剪等操作覆盖的原图的历史版本目录名
+const VERSIONS_DIR_NAME: &str = ".versions";
+
+/// WebDAV 模式下列表接口复用索引的时间，避免每次轮询都递归 PROPFIND
+const WEBDAV_RESCAN_INTERVAL: Duration = Duration::from_secs(60);
+/// 后台定期扫描图片目录的间隔，用于发现文件监听遗漏的变化（如网络文件系统）
+const BACKGROUND_SCAN_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 所有处理函数共享的状态，通过 `web::Data<AppConfig>` 注册到应用中
+#[derive(Clone)]
+pub struct AppConfig {
+    pic_dir: Arc<String>,
+    /// 缓存目录 `.thumbnails`，远程图片代理的缓存也在这里
+    cache_dir: Arc<String>,
+    /// 当前尺寸的缩略图目录 `.thumbnails/<尺寸>`
+    thumb_dir: Arc<String>,
+    data_dir: Arc<String>,
+    index: Arc<ImageIndex>,
+    ids: Arc<ShortIds>,
+    tags: Arc<Tags>,
+    /// 智能相册（保存的筛选条件）
+    albums: Arc<Albums>,
+    /// 列表接口返回的图片宽高
+    dimensions: Arc<Dimensions>,
+    /// 时间线和统计使用的拍摄时间、相机型号
+    captures: Arc<Captures>,
+    /// 图库快照中的内容哈希
+    hashes: Arc<snapshot::Hashes>,
+    /// 多次生成缩略图失败的图片
+    quarantine: Arc<Quarantine>,
+    /// 最近的浏览热度，决定缩略图预生成的顺序
+    popularity: Arc<Popularity>,
+    /// 常用缩略图的内存缓存
+    memcache: Arc<MemoryCache>,
+    events: Arc<EventBus>,
+    scan: Arc<ScanProgress>,
+    /// `--rescan-cron` 定时完整扫描
+    scheduled_rescan: Option<Arc<ScheduledRescan>>,
+    maintenance: Arc<Maintenance>,
+    /// 生成缩略图、代理缩放等昂贵操作的并发上限
+    limiter: Arc<Limiter>,
+    settings: Arc<Config>,
+    started_at: Instant,
+    webdav: Option<Arc<WebDavSource>>,
+    replicator: Option<Arc<Replicator>>,
+    backup: Option<Arc<Backup>>,
+    /// 手动触发的图片优化任务
+    optimizer: Arc<Optimizer>,
+    /// 正在重新生成缩略图，同一画廊同一时间只进行一次
+    rebuilding: Arc<AtomicBool>,
+    /// 导出选中的图片，未设置导出目录时为 `None`
+    exporter: Option<Arc<Exporter>>,
+    webhooks: Option<Arc<Webhooks>>,
+    hooks: Option<Arc<Hooks>>,
+    /// 上传的病毒扫描，未配置时为 `None`
+    scanner: Option<Arc<Scanner>>,
+    /// 去重存储，未启用时为 `None`
+    cas: Option<Arc<cas::Store>>,
+    /// 归档目录和读取原图的耗时
+    tiers: Arc<Tiers>,
+    /// 指向图片目录之外的别名目录
+    aliases: Arc<Aliases>,
+    templates: Arc<Templates>,
+    /// 已注销的登录会话
+    sessions: Arc<Sessions>,
+    api_keys: Arc<ApiKeys>,
+}
+
+impl AppConfig {
+    /// 按配置创建，自定义模板有语法错误时返回错误
+    pub fn load(settings: Config) -> std::io::Result<Self> {
+        let templates = Templates::load(settings.templates_dir.as_deref())?;
+        Ok(Self::new(settings, templates))
+    }
+
+    fn new(settings: Config, templates: Templates) -> Self {
+        let cache_dir = format!("{}/{}", settings.pic_dir, THUMB_DIR_NAME);
+        let thumb_dir = thumbs::size_dir(&cache_dir, settings.thumb_size);
+        let data_dir = format!("{}/{}", settings.pic_dir, DATA_DIR_NAME);
+        let webdav = settings.webdav_url.as_deref().map(|url| {
+            Arc::new(WebDavSource::new(
+                url,
+                settings.webdav_user.as_deref(),
+                settings.webdav_password.as_deref(),
+            ))
+        });
+        let replicator = settings.replicate_to.as_deref().map(|target| {
+            Arc::new(Replicator::new(
+                target,
+                settings.replicate_user.as_deref(),
+                settings.replicate_password.as_deref(),
+                Duration::from_secs(settings.replicate_interval),
+                &data_dir,
+            ))
+        });
+        let exporter = settings.export_dir.as_deref().map(|dir| Arc::new(Exporter::new(dir)));
+        let backup = settings.backup_dir.as_deref().map(|dir| {
+            Arc::new(Backup::new(
+                dir,
+                Duration::from_secs(settings.backup_interval),
+                settings.backup_keep,
+            ))
+        });
+        let webhooks = if settings.webhooks.is_empty() {
+            None
+        } else {
+            Some(Arc::new(Webhooks::new(
+                &settings.webhooks,
+                settings.webhook_secret.as_deref(),
+                settings.base_url.as_deref(),
+                settings.originals_secret.as_deref(),
+                settings.originals_ttl,
+            )))
+        };
+        let aliases = Arc::new(Aliases::new(&settings.aliases, &settings.alias_include, &settings.alias_exclude));
+        let command = |line: &Option<String>| line.as_deref().and_then(hooks::split_command);
+        let hooks = if settings.on_upload.is_some()
+            || settings.on_delete.is_some()
+            || settings.on_index_complete.is_some()
+        {
+            Some(Arc::new(Hooks::new(
+                command(&settings.on_upload),
+                command(&settings.on_delete),
+                command(&settings.on_index_complete),
+                &settings.pic_dir,
+                aliases.clone(),
+                Duration::from_secs(settings.hook_timeout),
+            )))
+        } else {
+            None
+        };
+        let scanner = match (&settings.clamd, &settings.scan_command) {
+            (Some(address), _) => Some(Arc::new(Scanner::Clamd(address.clone()))),
+            (None, line) => command(line).map(|command| Arc::new(Scanner::Command(command))),
+        };
+        let scheduled_rescan = settings
+            .rescan_cron
+            .as_deref()
+            .and_then(|expression| cron::Cron::parse(expression).ok())
+            .map(|cron| Arc::new(ScheduledRescan::new(cron)));
+        let tiers = Arc::new(Tiers::new(&settings.archive));
+        let cas = settings.cas.then(|| Arc::new(cas::Store::new(&settings.pic_dir, &data_dir)));
+        let ids = Arc::new(ShortIds::load(&data_dir));
+        let tags = Arc::new(Tags::load(&data_dir));
+        let popularity = Arc::new(Popularity::load(&data_dir));
+        let albums = Arc::new(Albums::load(&data_dir));
+        let sessions = Arc::new(Sessions::load(&data_dir));
+        let api_keys = Arc::new(ApiKeys::load(&data_dir));
+        let hashes = Arc::new(snapshot::Hashes::load(&data_dir));
+        Self {
+            pic_dir: Arc::new(settings.pic_dir.clone()),
+            cache_dir: Arc::new(cache_dir),
+            thumb_dir: Arc::new(thumb_dir),
+            data_dir: Arc::new(data_dir),
+            index: Arc::new(ImageIndex::new(settings.path_match)),
+            ids,
+            tags,
+            albums,
+            dimensions: Arc::new(Dimensions::default()),
+            captures: Arc::new(Captures::default()),
+            hashes,
+            events: Arc::new(EventBus::default()),
+            scan: Arc::new(ScanProgress::default()),
+            scheduled_rescan,
+            maintenance: Arc::new(Maintenance::default()),
+            quarantine: Arc::new(Quarantine::default()),
+            popularity,
+            memcache: Arc::new(MemoryCache::new(settings.thumb_cache_mb)),
+            limiter: Arc::new(Limiter::new(settings.max_concurrent)),
+            settings: Arc::new(settings),
+            started_at: Instant::now(),
+            webdav,
+            replicator,
+            backup,
+            optimizer: Arc::new(Optimizer::new()),
+            rebuilding: Arc::new(AtomicBool::new(false)),
+            exporter,
+            webhooks,
+            hooks,
+            scanner,
+            cas,
+            tiers,
+            aliases,
+            templates: Arc::new(templates),
+            sessions,
+            api_keys,
+        }
+    }
+
+    /// 重新扫描图片来源并更新索引，返回排序后的相对路径列表；开启去重存储时同时收入新的文件
+    pub fn rescan(&self) -> Vec<String> {
+        self.scan_library(true)
+    }
+
+    /// 扫描并更新索引。`ingest` 为 `false` 时不收入去重存储：列表等只读请求不需要登录，
+    /// 不能由它们计算哈希、改写图库中的文件；收入由上传、文件监听和定时扫描调用的 [`rescan`](Self::rescan) 完成
+    fn scan_library(&self, ingest: bool) -> Vec<String> {
+        // 维护模式下存储可能正在调整，扫描到的只是中间状态，保留原来的索引
+        if self.maintenance.is_enabled() {
+            return self.index.images();
+        }
+        // 扫描依次进行：先开始、后完成的扫描会用过时的列表替换索引，把存在的图片当作删除再重新加入。
+        // 等待期间已有在这之后才开始的扫描完成时，只读的扫描直接使用它的结果
+        let requested = Instant::now();
+        let _scanning = self.index.scanning.lock().unwrap_or_else(|e| e.into_inner());
+        if !ingest && self.index.walked_since(requested) {
+            return self.index.images();
+        }
+        let started = Instant::now();
+        // 同时有多个扫描时只有一个报告进度
+        let reporter = self.scan.try_report();
+        if let Some(reporter) = &reporter {
+            reporter.phase(Phase::Scanning, None);
+        }
+        let mut images: Vec<(String, u64)> = match &self.webdav {
+            Some(source) => match source.list_images(|p| is_image_file(Path::new(p))) {
+                Ok(images) => images
+                    .into_iter()
+                    .map(|relative| {
+                        let size = source.size(&relative).unwrap_or(0);
+                        (relative, size)
+                    })
+                    .collect(),
+                Err(e) => {
+                    log::warn!("扫描 WebDAV 目录失败: {}", e);
+                    return self.index.images();
+                }
+            },
+            None => {
+                let pic_path = Path::new(self.pic_dir.as_str());
+                // 图片目录本身读不了时（没有权限、磁盘未挂载）保留原来的索引，而不是清空画廊
+                if let Err(e) = fs::read_dir(pic_path) {
+                    log::warn!("无法读取图片目录 {}: {}", self.pic_dir, e);
+                    self.scan.set_unreadable(vec![AppError::io(".", e).to_string()]);
+                    return self.index.images();
+                }
+                let mut images = Vec::new();
+                let mut errors = Vec::new();
+                walk_library(pic_path, &self.aliases, &mut images, &mut errors, &|found| {
+                    if let Some(reporter) = &reporter {
+                        reporter.advance(found);
+                    }
+                });
+                for e in &errors {
+                    log::warn!("跳过无法读取的目录 {}", e);
+                }
+                self.scan.set_unreadable(errors.iter().map(|e| e.to_string()).collect());
+                if let Some(store) = self.cas.as_ref().filter(|_| ingest) {
+                    // 别名目录通常在其他文件系统上，不能硬链接到去重存储
+                    let paths: Vec<String> = images
+                        .iter()
+                        .map(|(relative, _)| relative.clone())
+                        .filter(|relative| !self.aliases.is_aliased(relative))
+                        .collect();
+                    let report = store.sync(&paths);
+                    if report.stored + report.duplicates + report.removed + report.failed > 0 {
+                        log::info!(
+                            "去重存储: 新内容 {} 个, 重复 {} 个 (节省 {}), 删除无引用的内容 {} 个, 失败 {} 个",
+                            report.stored,
+                            report.duplicates,
+                            optimize::format_bytes(report.saved_bytes),
+                            report.removed,
+                            report.failed
+                        );
+                    }
+                }
+                images
+            }
+        };
+        images.sort();
+        let (images, sizes): (Vec<String>, Vec<u64>) = images.into_iter().unzip();
+        let local = self.webdav.is_none();
+        self.ids.sync(
+            &images,
+            |p| local.then(|| ids::fingerprint(&self.fs_path(p))).flatten(),
+            |done, total| {
+                if let Some(reporter) = &reporter {
+                    if done == 0 {
+                        reporter.phase(Phase::Indexing, Some(total));
+                    }
+                    reporter.advance(done);
+                }
+            },
+        );
+        if let Some(reporter) = &reporter {
+            reporter.scanned(images.len());
+        }
+        drop(reporter);
+        let changes = self.index.replace(images.clone(), sizes, started);
+        let changed = match &changes {
+            Some(changes) => {
+                for path in &changes.added {
+                    self.notify(EventKind::Added, path);
+                }
+                for path in &changes.removed {
+                    self.notify(EventKind::Removed, path);
+                }
+                !changes.added.is_empty() || !changes.removed.is_empty()
+            }
+            None => true,
+        };
+        if let (true, Some(hooks)) = (changed, &self.hooks) {
+            hooks.index_complete();
+        }
+        let added = changes.as_ref().is_none_or(|changes| !changes.added.is_empty());
+        if added && local && (self.settings.prewarm || self.tiers.has_archive()) {
+            self.scan.request_prewarm();
+        }
+        images
+    }
+
+    /// 图片是否存在；拒绝包含 `..` 的路径
+    fn has_image(&self, relative: &str) -> bool {
+        if relative.split('/').any(|segment| segment == "..") || !is_image_file(Path::new(relative)) {
+            return false;
+        }
+        if !self.aliases.includes(relative) {
+            return false;
+        }
+        if self.webdav.is_some() {
+            self.index.contains(relative)
+        } else {
+            self.fs_path(relative).is_file()
+        }
+    }
+
+    /// 图片当前的缩略图版本，见 [`thumbs::version`]；按内容哈希检查缩略图时由内容得出，见 [`thumbs::content_version`]。
+    /// 图片不存在时为 `None`。需要读取元数据（`strict` 时读取整个原图），在阻塞线程中调用
+    fn thumb_version(&self, relative: &str) -> Option<String> {
+        let size = self.settings.thumb_size;
+        if let Some(source) = &self.webdav {
+            if !self.index.contains(relative) {
+                return None;
+            }
+            return Some(thumbs::version(relative, source.size(relative), source.modified(relative), size));
+        }
+        if !is_image_file(Path::new(relative)) {
+            return None;
+        }
+        let src_path = self.fs_path(relative);
+        let meta = fs::metadata(&src_path).ok().filter(|m| m.is_file())?;
+        let thumb_path = get_thumbnail_path(&self.thumb_dir, relative);
+        if let Some(sha256) = thumbs::content_hash(self.settings.thumb_check, &src_path, &thumb_path) {
+            return Some(thumbs::content_version(relative, &sha256, size));
+        }
+        Some(thumbs::version(relative, Some(meta.len()), meta.modified().ok(), size))
+    }
+
+    /// 索引中的相对路径对应的本地文件，别名目录中的路径换成别名指向的目录
+    fn fs_path(&self, relative: &str) -> PathBuf {
+        self.aliases.fs_path(Path::new(self.pic_dir.as_str()), relative)
+    }
+
+    /// 原图的地址（不含挂载前缀），开启 `--originals-secret` 时带上签名
+    fn pic_url(&self, relative: &str) -> String {
+        signed::pic_url(self.settings.originals_secret.as_deref(), self.settings.originals_ttl, relative)
+    }
+
+    /// 短 ID 的地址，签名同 [`pic_url`](Self::pic_url)
+    fn short_url(&self, id: &str) -> String {
+        let path = format!("/i/{}", id);
+        match &self.settings.originals_secret {
+            Some(secret) => {
+                let expires = signed::expires_for(self.settings.originals_ttl);
+                format!("{}?{}", path, signed::query(secret, &path, expires))
+            }
+            None => path,
+        }
+    }
+
+    /// 同 [`has_image`](Self::has_image)，在阻塞线程池中检查，供请求处理函数使用：
+    /// 网络文件系统上一次 stat 可能要很久，不能占住 worker 线程
+    async fn image_exists(&self, relative: &str) -> Result<bool> {
+        let config = self.clone();
+        let relative = relative.to_string();
+        Ok(web::block(move || config.has_image(&relative)).await?)
+    }
+
+    /// 把图片事件分发给画廊页面、Webhook 和外部命令钩子
+    fn notify(&self, event: EventKind, path: &str) {
+        if event == EventKind::Removed {
+            self.dimensions.forget(path);
+            self.captures.forget(path);
+            self.hashes.forget(path);
+            self.quarantine.forget(path);
+            self.popularity.forget(path);
+        } else if event == EventKind::Added && self.webdav.is_none() && self.settings.prewarm {
+            // 只有预生成会取走这些记录
+            self.popularity.added(path);
+        }
+        self.events.publish(event, path);
+        if let Some(webhooks) = &self.webhooks {
+            webhooks.emit(event, path);
+        }
+        if let Some(hooks) = &self.hooks {
+            hooks.handle(event, path);
+        }
+    }
+
+    /// 列表接口使用的图片列表：本地目录每次重新扫描（不收入去重存储），WebDAV 来源定期刷新。
+    /// 首次扫描进行中时直接返回（空的）索引，不再重复扫描
+    pub fn current_images(&self) -> Vec<String> {
+        if !self.index.is_loaded() && self.scan.is_running() {
+            return self.index.images();
+        }
+        if self.webdav.is_some() && !self.index.is_stale(WEBDAV_RESCAN_INTERVAL) {
+            self.index.images()
+        } else {
+            self.scan_library(false)
+        }
+    }
+}
+
+/// 最近一次扫描得到的图片列表
+#[derive(Default)]
+struct ImageIndex {
+    state: RwLock<IndexState>,
+    /// 扫描时持有，见 [`AppConfig::rescan`]
+    scanning: Mutex<()>,
+    /// 不是 [`PathMatch::Exact`] 时另外记录规范化后的路径，见 [`folding`]
+    path_match: Option<PathMatch>,
+}
+
+/// 两次扫描之间的差异
+struct IndexChanges {
+    added: Vec<String>,
+    removed: Vec<String>,
+}
+
+#[derive(Default)]
+struct IndexState {
+    images: Vec<String>,
+    /// 每个顶层目录的占用空间，扫描时随索引一起更新
+    usage: Vec<FolderUsage>,
+    scanned_at: Option<SystemTime>,
+    /// 最近一次扫描开始遍历的时间
+    walked_at: Option<Instant>,
+    /// 规范化后的路径 → 实际的图片或目录路径
+    folded: HashMap<String, String>,
+}
+
+impl ImageIndex {
+    fn new(path_match: PathMatch) -> Self {
+        Self {
+            path_match: (path_match != PathMatch::Exact).then_some(path_match),
+            ..Self::default()
+        }
+    }
+
+    /// 用新的（已排序的）扫描结果替换索引内容，`sizes` 为对应图片的字节数，`walked_at` 为开始遍历的时间；
+    /// 返回与上次扫描相比的变化，第一次扫描没有可比较的内容，返回 `None`
+    fn replace(&self, images: Vec<String>, sizes: Vec<u64>, walked_at: Instant) -> Option<IndexChanges> {
+        let mut state = self.state.write().ok()?;
+        let changes = state.scanned_at.map(|_| {
+            let (old, new) = (&state.images, &images);
+            IndexChanges {
+                added: new.iter().filter(|p| old.binary_search(p).is_err()).cloned().collect(),
+                removed: old.iter().filter(|p| new.binary_search(p).is_err()).cloned().collect(),
+            }
+        });
+        state.usage = usage::by_folder(&images, &sizes);
+        state.folded = self.path_match.map(|mode| fold(mode, &images)).unwrap_or_default();
+        state.images = images;
+        state.scanned_at = Some(SystemTime::now());
+        state.walked_at = Some(walked_at);
+        changes
+    }
+
+    /// 索引是否来自 `time` 之后才开始的扫描
+    fn walked_since(&self, time: Instant) -> bool {
+        self.state
+            .read()
+            .is_ok_and(|state| state.walked_at.is_some_and(|walked_at| walked_at >= time))
+    }
+
+    /// 最近一次扫描时各顶层目录的占用空间和扫描时间
+    fn usage(&self) -> (Vec<FolderUsage>, Option<SystemTime>) {
+        self.state
+            .read()
+            .map(|state| (state.usage.clone(), state.scanned_at))
+            .unwrap_or_default()
+    }
+
+    fn images(&self) -> Vec<String> {
+        self.state
+            .read()
+            .map(|state| state.images.clone())
+            .unwrap_or_default()
+    }
+
+    fn contains(&self, relative_path: &str) -> bool {
+        self.state
+            .read()
+            .map(|state| state.images.binary_search_by(|p| p.as_str().cmp(relative_path)).is_ok())
+            .unwrap_or(false)
+    }
+
+    /// 是否有图片在目录 `folder` 中（包括子目录）
+    fn contains_folder(&self, folder: &str) -> bool {
+        let prefix = format!("{}/", folder.trim_end_matches('/'));
+        self.state
+            .read()
+            .map(|state| {
+                let start = state.images.partition_point(|p| p.as_str() < prefix.as_str());
+                state.images.get(start).is_some_and(|p| p.starts_with(&prefix))
+            })
+            .unwrap_or(false)
+    }
+
+    /// 规范化后与 `relative` 相同的图片或目录的实际路径，未开启 `--path-match` 时总是 `None`
+    fn canonical(&self, relative: &str) -> Option<String> {
+        let mode = self.path_match?;
+        let key = mode.key(relative.trim_end_matches('/'));
+        self.state.read().ok()?.folded.get(key.as_ref()).cloned()
+    }
+
+    fn is_stale(&self, max_age: Duration) -> bool {
+        self.state
+            .read()
+            .ok()
+            .and_then(|state| state.scanned_at)
+            .and_then(|t| t.elapsed().ok())
+            .map(|age| age > max_age)
+            .unwrap_or(true)
+    }
+
+    fn len(&self) -> usize {
+        self.state.read().map(|state| state.images.len()).unwrap_or(0)
+    }
+
+    fn is_loaded(&self) -> bool {
+        self.state
+            .read()
+            .map(|state| state.scanned_at.is_some())
+            .unwrap_or(false)
+    }
+}
+
+/// 图片及其所在各级目录规范化后的路径，规范化后相同的按路径排序取第一个
+fn fold(mode: PathMatch, images: &[String]) -> HashMap<String, String> {
+    let mut folded = HashMap::new();
+    for image in images {
+        let mut end = image.len();
+        loop {
+            let path = &image[..end];
+            match folded.entry(mode.key(path).into_owned()) {
+                Entry::Occupied(_) if end < image.len() => break,
+                Entry::Occupied(_) => {}
+                Entry::Vacant(entry) => {
+                    entry.insert(path.to_string());
+                }
+            }
+            match path.rfind('/') {
+                Some(slash) => end = slash,
+                None => break,
+            }
+        }
+    }
+    folded
+}
+
+#[derive(Serialize)]
+struct ImageInfo {
+    path: String,
+    name: String,
+    /// 短 ID，可通过 `/i/{id}` 访问
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    /// 原图宽高，WebDAV 来源不返回
+    #[serde(skip_serializing_if = "Option::is_none")]
+    width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<u32>,
+    /// 带版本的缩略图地址（相对画廊地址），内容不变，可以长期缓存
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thumb: Option<String>,
+    /// 开启原图签名时带签名的原图地址（相对画廊地址），未开启时为 `None`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+}
+
+impl ImageInfo {
+    /// 需要读取图片文件头获取宽高，在阻塞线程中调用
+    fn new(config: &AppConfig, path: &str) -> Self {
+        let dimensions = match config.webdav {
+            Some(_) => None,
+            None => config
+                .dimensions
+                .get(&config.fs_path(path), path),
+        };
+        ImageInfo {
+            path: path.to_string(),
+            name: Path::new(path)
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            id: config.ids.id_of(path),
+            tags: config.tags.tags_of(path),
+            width: dimensions.map(|(w, _)| w),
+            height: dimensions.map(|(_, h)| h),
+            thumb: config
+                .thumb_version(path)
+                .map(|version| format!("/th/{}/{}", version, url_path(path))),
+            url: config.settings.originals_secret.is_some().then(|| config.pic_url(path)),
+        }
+    }
+
+    /// 按比例排版时缩略图的宽高比；全景图和长图限制在 4:1 以内，不知道尺寸时为 `None`
+    fn tile_ratio(&self) -> Option<f64> {
+        let (width, height) = (self.width?, self.height?);
+        if height == 0 {
+            return None;
+        }
+        let ratio = (width as f64 / height as f64).clamp(0.25, 4.0);
+        Some((ratio * 1000.0).round() / 1000.0)
+    }
+}
+
+#[derive(Serialize)]
+struct ImageListResponse {
+    /// 图片总数，分页时也是全部图片的数量
+    count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    page: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    per_page: Option<usize>,
+    #[serde(flatten)]
+    images: ImageList,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ImageList {
+    Images(Vec<ImageInfo>),
+    /// `group=folder` 时按目录分组
+    Groups(Vec<ImageGroup>),
+}
+
+#[derive(Serialize)]
+struct ImageGroup {
+    /// 所在目录，根目录为 `""`
+    folder: String,
+    /// 该目录（不含子目录）中符合条件的图片总数，分页时也是全部图片的数量
+    count: usize,
+    images: Vec<ImageInfo>,
+}
+
+/// 画廊首页直接渲染的图片数，其余的在滚动时通过 `/api/images?page=` 分批加载
+const GALLERY_PAGE_SIZE: usize = 200;
+
+/// 信息面板中 GPS 小地图默认使用的瓦片
+const DEFAULT_MAP_TILES: &str = "https://tile.openstreetmap.org/{z}/{x}/{y}.png";
+
+/// 支持的图片扩展名（小写）
+const IMAGE_EXTENSIONS: [&str; 7] = ["jpg", "jpeg", "png", "gif", "webp", "bmp", "ico"];
+
+/// 按扩展名判断是否是支持的图片格式
+pub fn is_image_file(path: &Path) -> bool {
+    if let Some(ext) = path.extension() {
+        let ext = ext.to_string_lossy().to_lowercase();
+        IMAGE_EXTENSIONS.contains(&ext.as_str())
+    } else {
+        false
+    }
+}
+
+/// 解码图片并按 EXIF 方向转正，旋转过的 JPEG 只改了方向标记
+fn decode_oriented<R: std::io::BufRead + std::io::Seek>(
+    reader: ImageReader<R>,
+) -> image::ImageResult<image::DynamicImage> {
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut img = image::DynamicImage::from_decoder(decoder)?;
+    img.apply_orientation(orientation);
+    Ok(img)
+}
+
+/// 按文件内容判断格式，扩展名不对的图片也能解码
+fn open_oriented(path: &Path) -> image::ImageResult<image::DynamicImage> {
+    decode_oriented(ImageReader::open(path)?.with_guessed_format()?)
+}
+
+/// 生成长边为 `size` 的缩略图写到 `thumb_path`，并按 `check` 记录原图的信息供之后判断是否过期
+pub fn generate_thumbnail(check: ThumbCheck, size: u32, src_path: &Path, thumb_path: &Path) -> image::ImageResult<()> {
+    let img = tracing::info_span!("thumbnail.decode").in_scope(|| open_oriented(src_path))?;
+    save_thumbnail(&img, size, thumb_path)?;
+    thumbs::remember(check, src_path, thumb_path);
+    Ok(())
+}
+
+/// 按最长边缩放到 `max_size`
+fn resize_image(img: &image::DynamicImage, max_size: u32) -> image::DynamicImage {
+    let (width, height) = img.dimensions();
+
+    let ratio = max_size as f32 / width.max(height) as f32;
+    let new_width = (width as f32 * ratio) as u32;
+    let new_height = (height as f32 * ratio) as u32;
+
+    tracing::info_span!("thumbnail.resize", width, height)
+        .in_scope(|| img.resize(new_width, new_height, FilterType::Lanczos3))
+}
+
+fn save_thumbnail(img: &image::DynamicImage, size: u32, thumb_path: &Path) -> image::ImageResult<()> {
+    let thumbnail = resize_image(img, size);
+
+    if let Some(parent) = thumb_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    tracing::info_span!("thumbnail.encode").in_scope(|| thumbnail.save(thumb_path))
+}
+
+/// 缩略图存在且不比原图旧
+fn thumbnail_is_newer(src_path: &Path, thumb_path: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    match (modified(src_path), modified(thumb_path)) {
+        (Some(src_time), Some(thumb_time)) => thumb_time >= src_time,
+        _ => false,
+    }
+}
+
+/// 缩略图存在且未过期，判断方式见 [`ThumbCheck`]
+fn thumbnail_is_fresh(check: ThumbCheck, src_path: &Path, thumb_path: &Path) -> bool {
+    match check {
+        ThumbCheck::Mtime => thumbnail_is_newer(src_path, thumb_path),
+        _ => thumbs::matches_source(check, src_path, thumb_path),
+    }
+}
+
+/// 检查缓存的缩略图是否可用，并计入命中率
+fn thumbnail_is_cached(check: ThumbCheck, src_path: &Path, thumb_path: &Path) -> bool {
+    let _check = tracing::info_span!("thumbnail.cache_check").entered();
+    let fresh = thumbnail_is_fresh(check, src_path, thumb_path);
+    if fresh {
+        METRICS.thumb_cache_hit();
+    } else {
+        METRICS.thumb_cache_miss();
+    }
+    fresh
+}
+
+/// 生成缩略图并记录耗时，失败时计入 `quarantine`
+fn refresh_thumbnail(
+    check: ThumbCheck,
+    size: u32,
+    src_path: &Path,
+    thumb_path: PathBuf,
+    relative_path: &str,
+    quarantine: &Quarantine,
+) -> image::ImageResult<PathBuf> {
+    let started = Instant::now();
+    let result = generate_thumbnail(check, size, src_path, &thumb_path);
+    METRICS.observe_thumb_generation(started.elapsed());
+
+    match result {
+        Ok(_) => {
+            quarantine.forget(relative_path);
+            Ok(thumb_path)
+        }
+        Err(e) => {
+            log::warn!("Failed to generate thumbnail for {:?}: {}", src_path, e);
+            METRICS.thumb_failure();
+            quarantine.record(relative_path, &e, Stamp::local(src_path));
+            Err(e)
+        }
+    }
+}
+
+/// 缩略图缓存可用时直接返回路径，否则重新生成
+pub fn ensure_thumbnail(
+    check: ThumbCheck,
+    size: u32,
+    thumb_dir: &str,
+    src_path: &Path,
+    relative_path: &str,
+    quarantine: &Quarantine,
+) -> image::ImageResult<PathBuf> {
+    let _span = tracing::info_span!("thumbnail.ensure", path = relative_path).entered();
+    let thumb_path = get_thumbnail_path(thumb_dir, relative_path);
+    if thumbnail_is_cached(check, src_path, &thumb_path) {
+        return Ok(thumb_path);
+    }
+    refresh_thumbnail(check, size, src_path, thumb_path, relative_path, quarantine)
+}
+
+/// 缩略图存在且不比列目录时得到的远程修改时间旧
+fn remote_thumbnail_is_fresh(source: &WebDavSource, relative_path: &str, thumb_path: &Path) -> bool {
+    match (fs::metadata(thumb_path).and_then(|m| m.modified()), source.modified(relative_path)) {
+        (Ok(thumb_time), Some(src_time)) => thumb_time >= src_time,
+        _ => false,
+    }
+}
+
+/// WebDAV 来源的缩略图：按列目录时得到的修改时间判断缓存是否过期，过期时下载原图重新生成
+fn ensure_remote_thumbnail(
+    thumb_dir: &str,
+    size: u32,
+    source: &WebDavSource,
+    relative_path: &str,
+    quarantine: &Quarantine,
+) -> image::ImageResult<PathBuf> {
+    let _span = tracing::info_span!("thumbnail.ensure", path = relative_path).entered();
+    let thumb_path = get_thumbnail_path(thumb_dir, relative_path);
+
+    if remote_thumbnail_is_fresh(source, relative_path, &thumb_path) {
+        METRICS.thumb_cache_hit();
+        return Ok(thumb_path);
+    }
+
+    METRICS.thumb_cache_miss();
+    let started = Instant::now();
+    let result = tracing::info_span!("thumbnail.fetch")
+        .in_scope(|| source.fetch(relative_path))
+        .map_err(image::ImageError::IoError)
+        .and_then(|data| {
+            let img = tracing::info_span!("thumbnail.decode").in_scope(|| {
+                decode_oriented(ImageReader::new(std::io::Cursor::new(&data)).with_guessed_format()?)
+            })?;
+            save_thumbnail(&img, size, &thumb_path)
+        });
+    METRICS.observe_thumb_generation(started.elapsed());
+
+    match result {
+        Ok(_) => {
+            quarantine.forget(relative_path);
+            Ok(thumb_path)
+        }
+        Err(e) => {
+            log::warn!("Failed to generate thumbnail for {}{}: {}", source.base_url(), relative_path, e);
+            METRICS.thumb_failure();
+            quarantine.record(relative_path, &e, Stamp::remote(source, relative_path));
+            Err(e)
+        }
+    }
+}
+
+/// 缩略图响应；生成失败时按原因返回错误，例如原图损坏时返回 422
+async fn thumbnail_response(
+    config: &AppConfig,
+    relative_path: &str,
+    thumb_path: image::ImageResult<PathBuf>,
+) -> Result<HttpResponse> {
+    let thumb_path = thumb_path.map_err(|e| AppError::image(relative_path, e))?;
+    let data = {
+        let (memcache, thumb_path) = (config.memcache.clone(), thumb_path.clone());
+        web::block(move || tracing::info_span!("thumbnail.read").in_scope(|| memcache.read(&thumb_path)))
+            .await?
+            .map_err(|e| AppError::io(relative_path, e))?
+    };
+    let mime = mime_guess::from_path(&thumb_path).first_or_octet_stream();
+    Ok(HttpResponse::Ok()
+        .content_type(mime.to_string())
+        .body(data))
+}
+
+#[get("/thumb/{path:.*}")]
+async fn serve_thumbnail(
+    path: web::Path<String>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    thumbnail(config, path.into_inner()).await
+}
+
+/// 带版本的缩略图地址，内容不会改变，可以让浏览器和 CDN 长期缓存而不必重新验证。
+/// 版本与原图当前的不一致时（原图已被替换）跳转到新地址
+#[get("/th/{version}/{path:.*}")]
+async fn serve_versioned_thumbnail(
+    path: web::Path<(String, String)>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let (version, relative_path) = path.into_inner();
+    if !is_valid_relative(&relative_path) {
+        return missing::response(&config).await;
+    }
+    let current = {
+        let (config, relative) = (config.clone(), relative_path.clone());
+        web::block(move || config.thumb_version(&relative)).await?
+    };
+    let Some(current) = current else {
+        return missing::response(&config).await;
+    };
+    if current != version {
+        let location = format!("{}/th/{}/{}", config.settings.mount_path, current, url_path(&relative_path));
+        return Ok(HttpResponse::Found()
+            .insert_header((header::LOCATION, location))
+            .insert_header((header::CACHE_CONTROL, "no-cache"))
+            .finish());
+    }
+    let mut response = thumbnail(config, relative_path).await?;
+    // 占位图等自带缓存设置的响应不改
+    if response.status().is_success() && !response.headers().contains_key(header::CACHE_CONTROL) {
+        response.headers_mut().insert(
+            header::CACHE_CONTROL,
+            header::HeaderValue::from_static("public, max-age=31536000, immutable"),
+        );
+    }
+    Ok(response)
+}
+
+async fn thumbnail(config: web::Data<AppConfig>, relative_path: String) -> Result<HttpResponse> {
+    let size = config.settings.thumb_size;
+    // 路径中的 `%2e%2e` 解码后是 `..`，不能借此读取图片目录以外的文件；别名目录中不收录的图片同样不提供
+    if !is_valid_relative(&relative_path) || !config.aliases.includes(&relative_path) {
+        return missing::response(&config).await;
+    }
+
+    if let Some(source) = config.webdav.clone() {
+        if !is_image_file(Path::new(&relative_path)) || !config.index.contains(&relative_path) {
+            return missing::response(&config).await;
+        }
+        config.popularity.view(&relative_path);
+        if config.quarantine.holds(&relative_path, Stamp::remote(&source, &relative_path)) {
+            return Ok(quarantine::placeholder());
+        }
+        // 缓存命中不受并发上限限制，只有需要下载原图生成时才占用名额
+        let thumb_path = get_thumbnail_path(&config.thumb_dir, &relative_path);
+        let permit = if remote_thumbnail_is_fresh(&source, &relative_path, &thumb_path) {
+            None
+        } else {
+            match config.limiter.try_acquire() {
+                Some(permit) => Some(permit),
+                None => return Ok(limit::busy()),
+            }
+        };
+        let thumb_dir = config.thumb_dir.clone();
+        let relative = relative_path.clone();
+        let quarantine = config.quarantine.clone();
+        let thumb_path = web::block(move || {
+            let _permit = permit;
+            ensure_remote_thumbnail(&thumb_dir, size, &source, &relative, &quarantine)
+        })
+        .await?;
+        return thumbnail_response(&config, &relative_path, thumb_path).await;
+    }
+
+    // 归档目录的原图在慢速存储上，已有缩略图时直接返回而不访问原图，是否过期由后台预生成检查
+    if config.tiers.is_archived(&relative_path) && config.index.contains(&relative_path) {
+        let thumb_path = get_thumbnail_path(&config.thumb_dir, &relative_path);
+        let cached = {
+            let thumb_path = thumb_path.clone();
+            web::block(move || thumb_path.is_file()).await?
+        };
+        if cached {
+            METRICS.thumb_cache_hit();
+            config.popularity.view(&relative_path);
+            return thumbnail_response(&config, &relative_path, Ok(thumb_path)).await;
+        }
+    }
+
+    let src_path = config.fs_path(&relative_path);
+    let exists = {
+        let src_path = src_path.clone();
+        web::block(move || src_path.exists()).await?
+    };
+    if !exists || !is_image_file(&src_path) {
+        return missing::response(&config).await;
+    }
+    config.popularity.view(&relative_path);
+
+    // 多次生成失败的图片不再解码，直接返回占位图；原图被替换后重新尝试
+    if config.quarantine.contains(&relative_path) {
+        let quarantine = config.quarantine.clone();
+        let (src_path, relative) = (src_path.clone(), relative_path.clone());
+        if web::block(move || quarantine.holds(&relative, Stamp::local(&src_path))).await? {
+            return Ok(quarantine::placeholder());
+        }
+    }
+
+    // 按哈希检查时要读取原图，检查也在阻塞线程池中进行；
+    // 缓存命中不受并发上限限制，只有需要生成时才占用名额，名额已满时返回 `None`
+    let check = config.settings.thumb_check;
+    let limiter = config.limiter.clone();
+    let quarantine = config.quarantine.clone();
+    let thumb_path = get_thumbnail_path(&config.thumb_dir, &relative_path);
+    let relative = relative_path.clone();
+    let thumb_path = web::block(move || {
+        let _span = tracing::info_span!("thumbnail.ensure", path = relative.as_str()).entered();
+        if thumbnail_is_cached(check, &src_path, &thumb_path) {
+            return Some(Ok(thumb_path));
+        }
+        let _permit = limiter.try_acquire()?;
+        Some(refresh_thumbnail(check, size, &src_path, thumb_path, &relative, &quarantine))
+    })
+    .await?;
+    match thumb_path {
+        Some(thumb_path) => thumbnail_response(&config, &relative_path, thumb_path).await,
+        None => Ok(limit::busy()),
+    }
+}
+
+#[get("/pic/{path:.*}")]
+async fn serve_image(
+    req: HttpRequest,
+    path: web::Path<String>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let relative_path = path.into_inner();
+    let signature = match check_signature(&req, &config, &format!("/pic/{}", relative_path)) {
+        Ok(signature) => signature,
+        Err(response) => return Ok(response),
+    };
+    image_response(&req, &config, relative_path, signature).await
+}
+
+/// 通过短 ID 访问图片，图片改名或移动后链接仍然有效。短 ID 中看不出路径，[`homes::guard`]
+/// 检查不到，这里再按请求者的身份检查：其他用户私有目录中的图片与不存在的一样返回 404
+#[get("/i/{id}")]
+async fn serve_short_id(
+    req: HttpRequest,
+    id: web::Path<String>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let signature = match check_signature(&req, &config, &format!("/i/{}", id)) {
+        Ok(signature) => signature,
+        Err(response) => return Ok(response),
+    };
+    match config.ids.resolve(&id).filter(|path| Viewer::of(&req).can_see(path)) {
+        Some(relative_path) => image_response(&req, &config, relative_path, signature).await,
+        None => Ok(HttpResponse::NotFound().body("Image not found")),
+    }
+}
+
+/// 开启原图签名时检查请求的签名，通过时返回剩余的有效秒数，不通过时返回 403 响应
+fn check_signature(req: &HttpRequest, config: &AppConfig, path: &str) -> Result<Option<u64>, HttpResponse> {
+    let Some(secret) = &config.settings.originals_secret else {
+        return Ok(None);
+    };
+    let verified = match web::Query::<signed::SignedQuery>::from_query(req.query_string()) {
+        Ok(query) => signed::verify(secret, path, &query),
+        Err(_) => Err("Invalid signature"),
+    };
+    verified
+        .map(Some)
+        .map_err(|message| HttpResponse::Forbidden().body(message))
+}
+
+/// 返回原图；签名过的请求按签名剩余的有效期设置缓存时间，CDN 不会在签名过期后继续提供。
+/// 带 `?download=1` 时以附件返回，点击链接直接保存而不是在浏览器中打开
+async fn image_response(
+    req: &HttpRequest,
+    config: &AppConfig,
+    relative_path: String,
+    signature: Option<u64>,
+) -> Result<HttpResponse> {
+    let name = relative_path.rsplit('/').next().unwrap_or_default().to_string();
+    let mut response = original_response(req, config, relative_path).await?;
+    if response.status().is_success() && wants_download(req) {
+        if let Ok(value) = header::HeaderValue::from_str(&attachment(&name)) {
+            response.headers_mut().insert(header::CONTENT_DISPOSITION, value);
+        }
+    }
+    if let Some(remaining) = signature {
+        if response.status().is_success() {
+            response.headers_mut().insert(
+                header::CACHE_CONTROL,
+                header::HeaderValue::from_str(&format!("public, max-age={}", remaining))
+                    .expect("数字组成的响应头"),
+            );
+        }
+    }
+    Ok(response)
+}
+
+async fn original_response(req: &HttpRequest, config: &AppConfig, relative_path: String) -> Result<HttpResponse> {
+    if !is_valid_relative(&relative_path) || !config.aliases.includes(&relative_path) {
+        return Ok(HttpResponse::NotFound().body("Image not found"));
+    }
+    if let Some(source) = config.webdav.clone() {
+        if !config.index.contains(&relative_path) {
+            return Ok(HttpResponse::NotFound().body("Image not found"));
+        }
+        let relative = relative_path.clone();
+        let started = Instant::now();
+        let data = web::block(move || source.fetch(&relative))
+            .await?
+            .map_err(|e| AppError::io(&relative_path, e))?;
+        config.tiers.observe(&relative_path, started.elapsed());
+        config.popularity.view(&relative_path);
+        let mime = sniff::image_mime(&data);
+        let mut response = range::bytes_response(req, data, mime.unwrap_or(sniff::UNKNOWN));
+        typed_by_content(&mut response, mime, &relative_path);
+        return Ok(response);
+    }
+
+    let file_path = config.fs_path(&relative_path);
+    // 打开到读出开头的耗时即首字节耗时，判断类型时正好要读开头
+    let started = Instant::now();
+    match NamedFile::open_async(&file_path).await {
+        Ok(file) => {
+            let mime = web::block(move || sniff::file_mime(&file_path))
+                .await?
+                .map_err(|e| AppError::io(&relative_path, e))?;
+            config.tiers.observe(&relative_path, started.elapsed());
+            config.popularity.view(&relative_path);
+            let mut response = file.into_response(req);
+            typed_by_content(&mut response, mime, &relative_path);
+            Ok(response)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HttpResponse::NotFound().body("Image not found")),
+        Err(e) => Err(AppError::io(&relative_path, e).into()),
+    }
+}
+
+/// 按内容判断出的类型设置原图的 `Content-Type`，并禁止浏览器自行猜测；
+/// 内容不是支持的图片时作为附件返回，不会在浏览器中打开
+fn typed_by_content(response: &mut HttpResponse, mime: Option<&'static str>, relative_path: &str) {
+    let headers = response.headers_mut();
+    headers.insert(header::X_CONTENT_TYPE_OPTIONS, header::HeaderValue::from_static("nosniff"));
+    if headers.contains_key(header::CONTENT_TYPE) {
+        headers.insert(header::CONTENT_TYPE, header::HeaderValue::from_static(mime.unwrap_or(sniff::UNKNOWN)));
+    }
+    if mime.is_none() {
+        let name = relative_path.rsplit('/').next().unwrap_or_default();
+        if let Ok(value) = header::HeaderValue::from_str(&attachment(name)) {
+            headers.insert(header::CONTENT_DISPOSITION, value);
+        }
+    }
+}
+
+/// 查询参数 `download` 为 `1` 或 `true`
+fn wants_download(req: &HttpRequest) -> bool {
+    web::Query::<std::collections::HashMap<String, String>>::from_query(req.query_string())
+        .is_ok_and(|query| matches!(query.get("download").map(String::as_str), Some("1" | "true")))
+}
+
+/// 下载用的 `Content-Disposition`：去掉文件名中的控制字符、引号和斜杠，
+/// `filename` 中的非 ASCII 字符换成 `_`，完整的 UTF-8 文件名放在 `filename*` 中
+fn attachment(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_control() || matches!(c, '"' | '\\' | '/') { '_' } else { c })
+        .collect();
+    let name = match name.trim() {
+        "" => "download",
+        name => name,
+    };
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        name.replace(|c: char| !c.is_ascii(), "_"),
+        percent_encoding::utf8_percent_encode(name, percent_encoding::NON_ALPHANUMERIC)
+    )
+}
+
+/// 格式化为 RFC 3339 (UTC) 时间字符串
+fn format_time(t: SystemTime) -> String {
+    time::OffsetDateTime::from(t)
+        .format(&time::format_description::well_known::Rfc3339)
+        .unwrap_or_default()
+}
+
+/// 把图片相对路径逐段转义，用于拼接 URL
+fn url_path(relative: &str) -> String {
+    relative
+        .split('/')
+        .map(|segment| percent_encoding::utf8_percent_encode(segment, webdav::SEGMENT).to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// 转义 HTML/XML 文本和属性值
+fn html_escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// 外部访问使用的地址（不含末尾斜杠）：优先使用 `--base-url`，否则取自请求的 Host 和协议
+fn base_url(req: &HttpRequest) -> String {
+    if let Some(url) = req
+        .app_data::<web::Data<AppConfig>>()
+        .and_then(|config| config.settings.base_url.clone())
+    {
+        return url;
+    }
+    let info = req.connection_info();
+    format!("{}://{}{}", info.scheme(), info.host(), mount_path(req))
+}
+
+/// 画廊挂载的路径前缀（如 `/photos`），挂在根路径时为空
+fn mount_path(req: &HttpRequest) -> String {
+    req.app_data::<web::Data<AppConfig>>()
+        .map(|config| config.settings.mount_path.clone())
+        .unwrap_or_default()
+}
+
+/// 同一个请求中只计算一次的结果（请求者的身份等）。按图片目录分开保存：同一个应用中有多个画廊时，
+/// 外层画廊的中间件可能也经过了内层画廊的请求，得到的是外层画廊的结果
+fn request_cached<T: Clone + 'static>(req: &HttpRequest, config: &AppConfig, compute: impl FnOnce() -> T) -> T {
+    struct Cached<T>(Arc<String>, T);
+    if let Some(Cached(pic_dir, value)) = req.extensions().get::<Cached<T>>() {
+        if *pic_dir == config.pic_dir {
+            return value.clone();
+        }
+    }
+    let value = compute();
+    req.extensions_mut().insert(Cached(config.pic_dir.clone(), value.clone()));
+    value
+}
+
+/// 局域网地址：向组播地址 "连接" 一个 UDP 套接字（不会真正发包），取系统选择的本地地址
+fn lan_ipv4() -> Option<std::net::Ipv4Addr> {
+    let socket = std::net::UdpSocket::bind(("0.0.0.0", 0)).ok()?;
+    socket.connect(("239.255.255.250", 1900)).ok()?;
+    match socket.local_addr().ok()? {
+        std::net::SocketAddr::V4(addr) if !addr.ip().is_unspecified() => Some(*addr.ip()),
+        _ => None,
+    }
+}
+
+/// 程序自己管理的目录，扫描时跳过
+fn is_reserved_dir(name: &str) -> bool {
+    name == THUMB_DIR_NAME || name == DATA_DIR_NAME || name == VERSIONS_DIR_NAME
+}
+
+/// 能否作为图片目录内的相对路径：不能为空、不能包含 `..`，也不能位于保留目录中
+fn is_valid_relative(path: &str) -> bool {
+    !path.is_empty()
+        && !path.starts_with('/')
+        && path.split('/').all(|segment| !segment.is_empty() && segment != "." && segment != "..")
+        && path.split('/').next().is_some_and(|first| !is_reserved_dir(first))
+}
+
+/// 收集 `dir` 下的全部图片；有目录无法读取时仍收集其余的图片，并返回第一个错误
+pub fn collect_images(dir: &Path, base: &Path, images: &mut Vec<String>) -> std::result::Result<(), AppError> {
+    let mut errors = Vec::new();
+    let mut found = Vec::new();
+    walk_images(dir, base, "", &mut found, &mut errors, &|_| {});
+    images.extend(found.into_iter().map(|(relative, _)| relative));
+    errors.into_iter().next().map_or(Ok(()), Err)
+}
+
+/// 同 [`collect_images`]，收集图片目录 `base` 和其中的[别名目录](aliases)下的全部图片
+fn collect_library(base: &Path, aliases: &Aliases, images: &mut Vec<String>) -> std::result::Result<(), AppError> {
+    let mut errors = Vec::new();
+    let mut found = Vec::new();
+    walk_library(base, aliases, &mut found, &mut errors, &|_| {});
+    images.extend(found.into_iter().map(|(relative, _)| relative));
+    errors.into_iter().next().map_or(Ok(()), Err)
+}
+
+/// 图片目录 `base` 和其中的[别名目录](aliases)下的全部图片，其余参数同 [`walk_images`]
+fn walk_library(
+    base: &Path,
+    aliases: &Aliases,
+    images: &mut Vec<(String, u64)>,
+    errors: &mut Vec<AppError>,
+    progress: &dyn Fn(usize),
+) {
+    walk_images(base, base, "", images, errors, progress);
+    aliases.walk(images, errors, progress);
+}
+
+/// 递归收集图片的相对路径和文件大小，每读完一个目录以已找到的图片数调用 `progress`。
+/// 无法读取的目录（如没有权限）记入 `errors` 后跳过，路径相对于 `base` 再加上前缀 `prefix`，
+/// `base` 本身为 `prefix` 或 `.`
+fn walk_images(
+    dir: &Path,
+    base: &Path,
+    prefix: &str,
+    images: &mut Vec<(String, u64)>,
+    errors: &mut Vec<AppError>,
+    progress: &dyn Fn(usize),
+) {
+    match fs::read_dir(dir) {
+        Ok(entries) => {
+            for entry in entries.filter_map(|e| e.ok()) {
+                let path = entry.path();
+                // 判断目录时已经读取了元数据，顺便记下大小，不需要额外的系统调用
+                let meta = fs::metadata(&path).ok();
+                if meta.as_ref().is_some_and(|meta| meta.is_dir()) {
+                    if path.file_name().map(|n| !is_reserved_dir(&n.to_string_lossy())).unwrap_or(false) {
+                        walk_images(&path, base, prefix, images, errors, progress);
+                    }
+                } else if is_image_file(&path) {
+                    if let Ok(relative) = path.strip_prefix(base) {
+                        images.push((prefixed(prefix, names::relative(relative)), meta.map_or(0, |meta| meta.len())));
+                    }
+                }
+            }
+        }
+        Err(e) => {
+            let relative = prefixed(prefix, dir.strip_prefix(base).map(names::relative).unwrap_or_default());
+            let relative = if relative.is_empty() { ".".to_string() } else { relative };
+            errors.push(AppError::io(&relative, e));
+        }
+    }
+    progress(images.len());
+}
+
+/// 在相对路径前加上 `prefix` 目录
+fn prefixed(prefix: &str, relative: String) -> String {
+    match (prefix.is_empty(), relative.is_empty()) {
+        (true, _) => relative,
+        (false, true) => prefix.to_string(),
+        (false, false) => format!("{}/{}", prefix, relative),
+    }
+}
+
+/// 图片列表；可按 `sort`/`order` 排序，按 `folder`/`ext`/`q` 筛选，
+/// 带 `page` 时分页返回，每页 `per_page` 张（默认 200），`count` 为符合条件的总数；
+/// `group=folder` 时按目录分组返回 `groups`
+#[get("/api/images")]
+async fn api_images(
+    query: web::Query<ImageListQuery>,
+    viewer: Viewer,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    query.validate(&config)?;
+    let query = query.into_inner();
+    let page = query.page.map(|page| page.max(1));
+    let per_page = page.map(|_| query.per_page.unwrap_or(GALLERY_PAGE_SIZE).max(1));
+    let scan = config.clone();
+    // 后续页直接使用索引，滚动加载时不会每一批都重新扫描整个目录
+    let (count, images) = web::block(move || {
+        let images = match page {
+            Some(page) if page > 1 && scan.index.is_loaded() => scan.index.images(),
+            _ => scan.current_images(),
+        };
+        let image_paths = query.apply(&scan, viewer.filter(images));
+        let selected: &[String] = match (page, per_page) {
+            (Some(page), Some(per_page)) => {
+                let start = (page - 1).saturating_mul(per_page).min(image_paths.len());
+                let end = start.saturating_add(per_page).min(image_paths.len());
+                &image_paths[start..end]
+            }
+            _ => &image_paths,
+        };
+        let images = if query.grouped() {
+            ImageList::Groups(
+                listing::group_by_folder(&image_paths, selected)
+                    .into_iter()
+                    .map(|(folder, count, paths)| ImageGroup {
+                        folder,
+                        count,
+                        images: paths.iter().map(|img| ImageInfo::new(&scan, img)).collect(),
+                    })
+                    .collect(),
+            )
+        } else {
+            ImageList::Images(selected.iter().map(|img| ImageInfo::new(&scan, img)).collect())
+        };
+        (image_paths.len(), images)
+    })
+    .await?;
+
+    let response = ImageListResponse {
+        count,
+        page,
+        per_page,
+        images,
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .json(response))
+}
+
+#[derive(Serialize)]
+struct FolderInfo {
+    path: String,
+    count: usize,
+}
+
+/// 所有包含图片的目录及其中（含子目录）的图片数，供画廊的目录筛选使用
+#[get("/api/folders")]
+async fn api_folders(viewer: Viewer, config: web::Data<AppConfig>) -> Result<HttpResponse> {
+    let images = web::block(move || viewer.filter(config.current_images())).await?;
+    let folders: Vec<FolderInfo> = listing::folders(&images)
+        .into_iter()
+        .map(|(path, count)| FolderInfo { path, count })
+        .collect();
+    Ok(HttpResponse::Ok().json(folders))
+}
+
+#[get("/metrics")]
+async fn metrics_endpoint(config: web::Data<AppConfig>) -> Result<HttpResponse> {
+    let body = web::block(move || {
+        METRICS.render(
+            Path::new(config.pic_dir.as_str()),
+            Path::new(config.cache_dir.as_str()),
+            &config.limiter,
+            config.index.len(),
+        )
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(body))
+}
+
+#[derive(Serialize)]
+struct ReadinessChecks {
+    pic_dir: bool,
+    index: bool,
+    thumb_dir: bool,
+}
+
+#[derive(Serialize)]
+struct HealthResponse {
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    checks: Option<ReadinessChecks>,
+}
+
+/// 检查目录是否可写：创建并删除一个探测文件
+fn dir_writable(dir: &Path) -> bool {
+    let probe = dir.join(".pic_url_probe");
+    match fs::write(&probe, b"") {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+#[get("/healthz")]
+async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().json(HealthResponse {
+        status: "ok",
+        checks: None,
+    })
+}
+
+#[get("/readyz")]
+async fn readyz(config: web::Data<AppConfig>) -> Result<HttpResponse> {
+    let checks = web::block(move || ReadinessChecks {
+        pic_dir: match &config.webdav {
+            Some(source) => source.ping(),
+            None => fs::read_dir(config.pic_dir.as_str()).is_ok(),
+        },
+        index: config.index.is_loaded(),
+        thumb_dir: dir_writable(Path::new(config.thumb_dir.as_str())),
+    })
+    .await?;
+
+    let ready = checks.pic_dir && checks.index && checks.thumb_dir;
+    let body = HealthResponse {
+        status: if ready { "ok" } else { "unavailable" },
+        checks: Some(checks),
+    };
+    if ready {
+        Ok(HttpResponse::Ok().json(body))
+    } else {
+        Ok(HttpResponse::ServiceUnavailable().json(body))
+    }
+}
+
+#[get("/")]
+async fn index(req: HttpRequest, viewer: Viewer, config: web::Data<AppConfig>) -> HttpResponse {
+    let mut res = if discovery::wants_json(&req) {
+        HttpResponse::Ok().json(discovery::document(&config))
+    } else {
+        gallery_page(config, viewer, String::new()).await
+    };
+    res.headers_mut().insert(header::VARY, header::HeaderValue::from_static("Accept"));
+    res
+}
+
+/// 只显示一个目录（含子目录）中图片的画廊，可以单独收藏和分享
+#[get("/gallery/{dir:.*}")]
+async fn folder_gallery(dir: web::Path<String>, viewer: Viewer, config: web::Data<AppConfig>) -> HttpResponse {
+    let folder = dir.trim_matches('/').to_string();
+    if folder.is_empty() {
+        let home = format!("{}/", config.settings.mount_path);
+        return HttpResponse::Found().insert_header(("Location", home)).finish();
+    }
+    if !is_valid_relative(&folder) {
+        return HttpResponse::NotFound().body("Folder not found");
+    }
+    gallery_page(config, viewer, folder).await
+}
+
+/// 画廊页面，`folder` 为空时显示全部图片
+async fn gallery_page(config: web::Data<AppConfig>, viewer: Viewer, folder: String) -> HttpResponse {
+    // 只渲染第一页，图片很多时页面也能立即打开
+    let (count, items, missing) = {
+        let config = config.clone();
+        let prefix = format!("{}/", folder);
+        let dir = (!folder.is_empty() && config.webdav.is_none())
+            .then(|| config.fs_path(&folder));
+        web::block(move || {
+            let mut images = viewer.filter(config.current_images());
+            if prefix != "/" {
+                images.retain(|img| img.starts_with(&prefix));
+            }
+            let items: Vec<_> = images
+                .iter()
+                .take(GALLERY_PAGE_SIZE)
+                .map(|img| {
+                    let info = ImageInfo::new(&config, img);
+                    let ratio = info.tile_ratio();
+                    serde_json::json!({
+                        "path": info.path, "name": info.name, "ratio": ratio, "thumb": info.thumb, "url": info.url,
+                    })
+                })
+                .collect();
+            // 空目录仍然可以打开（比如准备上传），不存在的目录返回 404
+            let missing = images.is_empty() && !dir.is_some_and(|dir| dir.is_dir());
+            (images.len(), items, missing)
+        })
+        .await
+        .unwrap_or_default()
+    };
+    if missing && !folder.is_empty() {
+        return HttpResponse::NotFound().body("Folder not found");
+    }
+
+    // 面包屑：逐级链接到上层目录的页面
+    let mut breadcrumbs = Vec::new();
+    let mut end = 0;
+    for segment in folder.split('/').filter(|_| !folder.is_empty()) {
+        end += segment.len();
+        breadcrumbs.push(serde_json::json!({
+            "name": segment,
+            "url": format!("{}/gallery/{}", config.settings.mount_path, url_path(&folder[..end])),
+        }));
+        end += 1;
+    }
+
+    let mut context = page_context(&config);
+    context.insert("images", &items);
+    context.insert("count", &count);
+    context.insert("pic_dir", config.pic_dir.as_str());
+    context.insert("page_size", &GALLERY_PAGE_SIZE);
+    context.insert("folder", &folder);
+    context.insert("breadcrumbs", &breadcrumbs);
+    // 时间线同样只显示这个目录
+    let timeline_url = if folder.is_empty() {
+        format!("{}/timeline", config.settings.mount_path)
+    } else {
+        format!("{}/timeline?folder={}", config.settings.mount_path, url_path(&folder))
+    };
+    context.insert("timeline_url", &timeline_url);
+    // 瓦片地址由管理员配置，仍然转义 `<` 以免提前结束 script 标签
+    context.insert(
+        "map_tiles",
+        &serde_json::to_string(&config.settings.map_tiles)
+            .unwrap_or_else(|_| "null".to_string())
+            .replace('<', "\\u003c"),
+    );
+    config
+        .templates
+        .respond("index.html", &context, "text/html; charset=utf-8")
+}
+
+/// 页面模板共用的变量：主题、挂载路径和内置资源地址
+fn page_context(config: &AppConfig) -> tera::Context {
+    let mut context = tera::Context::new();
+    context.insert("base", &config.settings.mount_path);
+    context.insert("theme", &config.settings.theme);
+    context.insert("theme_css", &theme::variables(&config.settings.accent));
+    context.insert("theme_color", theme::background(&config.settings.theme));
+    context.insert("assets", assets::urls());
+    context.insert("maintenance", &config.maintenance.status());
+    context
+}
+
+/// 启动参数，由 [`parse_args`] 从命令行和环境变量读取
+#[derive(Clone, Serialize)]
+pub struct Config {
+    port: u16,
+    pic_dir: String,
+    webdav_url: Option<String>,
+    webdav_user: Option<String>,
+    #[serde(skip)]
+    webdav_password: Option<String>,
+    #[serde(skip)]
+    admin_token: Option<String>,
+    /// 认证失败日志文件，见 [`auth_log`]
+    auth_log: Option<String>,
+    /// 登录会话的有效期 (秒)
+    session_lifetime: u64,
+    /// `users/<用户名>/` 只对该用户和管理员可见，见 [`homes`]
+    user_homes: bool,
+    dav: bool,
+    proxy_allow: Vec<String>,
+    #[serde(skip)]
+    proxy_secret: Option<String>,
+    /// 设置后 `/pic` 和 `/i` 需要签名，见 [`signed`]
+    #[serde(skip)]
+    originals_secret: Option<String>,
+    /// 服务器生成的原图链接的有效期 (秒)
+    originals_ttl: u64,
+    replicate_to: Option<String>,
+    replicate_interval: u64,
+    replicate_user: Option<String>,
+    #[serde(skip)]
+    replicate_password: Option<String>,
+    backup_dir: Option<String>,
+    backup_interval: u64,
+    backup_keep: usize,
+    /// 导出选中的图片的目录，见 [`export`]
+    export_dir: Option<String>,
+    webhooks: Vec<String>,
+    #[serde(skip)]
+    webhook_secret: Option<String>,
+    on_upload: Option<String>,
+    on_delete: Option<String>,
+    on_index_complete: Option<String>,
+    hook_timeout: u64,
+    /// 上传前用 clamd 扫描，见 [`scanner`]
+    clamd: Option<String>,
+    /// 上传前用这个命令扫描
+    scan_command: Option<String>,
+    /// 按内容寻址的去重存储，见 [`cas`]
+    cas: bool,
+    /// 删除或移动图片时如何处理附属文件，见 [`sidecars`]
+    sidecars: SidecarPolicy,
+    /// 归档目录（`目录` 或 `目录=预计毫秒`），见 [`tiers`]
+    archive: Vec<String>,
+    /// 目录别名（`名称=目录`）和各个别名的包含、排除规则（`名称=模式`），见 [`aliases`]
+    aliases: Vec<String>,
+    alias_include: Vec<String>,
+    alias_exclude: Vec<String>,
+    /// 按路径访问时如何匹配文件名（Unicode 规范化、大小写），见 [`folding`]
+    path_match: PathMatch,
+    /// 挂在 `/g/<名称>/` 下或按域名选择的其他画廊，见 [`galleries`]
+    galleries: Vec<galleries::Gallery>,
+    /// 只响应 `Host` 为这些域名的请求，为空时不限制
+    hosts: Vec<String>,
+    /// 单个上传文件的上限 (MB)，见 [`payload`]
+    max_upload_mb: u64,
+    /// 接口 JSON 和表单请求体的上限 (KB)
+    max_body_kb: u64,
+    dlna: bool,
+    dlna_name: String,
+    /// gRPC 接口的监听地址，见 [`grpc`]
+    grpc: Option<String>,
+    base_url: Option<String>,
+    prewarm: bool,
+    /// 定时完整扫描的 cron 表达式 (UTC)
+    rescan_cron: Option<String>,
+    /// 判断缩略图是否过期的方式
+    thumb_check: ThumbCheck,
+    /// 缩略图长边的像素数
+    thumb_size: u32,
+    /// 常用缩略图内存缓存的容量 (MB)，0 表示不缓存
+    thumb_cache_mb: u64,
+    /// 画廊挂载的路径前缀，只能通过 [`PicUrl::builder`] 设置，命令行启动时为空
+    mount_path: String,
+    max_concurrent: usize,
+    robots: String,
+    /// 缩略图的原图不存在时返回的图片，见 [`missing`]
+    missing_thumb: String,
+    /// GPS 小地图的瓦片地址模板，`None` 表示不显示地图
+    map_tiles: Option<String>,
+    templates_dir: Option<String>,
+    /// 页面默认主题：`dark`、`light` 或 `auto`
+    theme: String,
+    accent: String,
+    tls_cert: Option<String>,
+    tls_key: Option<String>,
+    otlp_endpoint: Option<String>,
+    access_log: Option<AccessLogConfig>,
+    log_format: LogFormat,
+}
+
+/// 与不带任何参数启动时相同的配置
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            port: 2020,
+            pic_dir: "./pic".to_string(),
+            webdav_url: None,
+            webdav_user: None,
+            webdav_password: None,
+            admin_token: None,
+            auth_log: None,
+            session_lifetime: session::DEFAULT_LIFETIME,
+            user_homes: false,
+            dav: false,
+            proxy_allow: Vec::new(),
+            proxy_secret: None,
+            originals_secret: None,
+            originals_ttl: signed::DEFAULT_TTL,
+            replicate_to: None,
+            replicate_interval: 3600,
+            replicate_user: None,
+            replicate_password: None,
+            backup_dir: None,
+            backup_interval: 86400,
+            backup_keep: 7,
+            export_dir: None,
+            webhooks: Vec::new(),
+            webhook_secret: None,
+            on_upload: None,
+            on_delete: None,
+            on_index_complete: None,
+            hook_timeout: 30,
+            clamd: None,
+            scan_command: None,
+            cas: false,
+            sidecars: SidecarPolicy::Metadata,
+            archive: Vec::new(),
+            aliases: Vec::new(),
+            alias_include: Vec::new(),
+            alias_exclude: Vec::new(),
+            path_match: PathMatch::Exact,
+            galleries: Vec::new(),
+            hosts: Vec::new(),
+            max_upload_mb: payload::DEFAULT_UPLOAD_MB,
+            max_body_kb: payload::DEFAULT_BODY_KB,
+            dlna: false,
+            dlna_name: "pic_url".to_string(),
+            grpc: None,
+            base_url: None,
+            prewarm: true,
+            rescan_cron: None,
+            thumb_check: ThumbCheck::Mtime,
+            thumb_size: THUMB_SIZE,
+            thumb_cache_mb: 0,
+            mount_path: String::new(),
+            max_concurrent: Limiter::default_max(),
+            robots: "allow".to_string(),
+            missing_thumb: missing::BUILTIN.to_string(),
+            map_tiles: Some(DEFAULT_MAP_TILES.to_string()),
+            templates_dir: None,
+            theme: "dark".to_string(),
+            accent: theme::DEFAULT_ACCENT.to_string(),
+            tls_cert: None,
+            tls_key: None,
+            otlp_endpoint: None,
+            access_log: None,
+            log_format: LogFormat::Text,
+        }
+    }
+}
+
+/// 注册画廊的全部路由。只注册路由，用户私有目录、API 密钥的权限等检查在中间件中，
+/// 由调用方（[`run`] 和 [`PicUrl`]）在外层的 scope 上添加，不能单独使用
+pub(crate) fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(index)
+        .service(discovery::capabilities)
+        .service(folder_gallery)
+        .service(assets::asset)
+        .service(pwa::manifest)
+        .service(pwa::service_worker)
+        .service(api_images)
+        .service(api_folders)
+        .service(session::login_page)
+        .service(session::login)
+        .service(session::logout)
+        .service(session::status)
+        .service(api_keys::list)
+        .service(api_keys::create)
+        .service(api_keys::update)
+        .service(api_keys::revoke)
+        .service(albums::list)
+        .service(albums::save)
+        .service(albums::remove)
+        .service(events::stream)
+        .service(events::wait)
+        .service(scan::status)
+        .service(ws::connect)
+        .service(metrics_endpoint)
+        .service(proxy::proxy)
+        .service(qr::qrcode_png)
+        .service(share::view)
+        .service(share::oembed)
+        .service(share::links)
+        .service(details::image_info)
+        .service(details::image_exif)
+        .service(frame::frame)
+        .service(timeline::timeline)
+        .service(timeline::api_timeline)
+        .service(stats::stats)
+        .service(stats::api_stats)
+        .service(usage::api_usage)
+        .service(potd::potd)
+        .service(potd::api_potd)
+        .service(edit::rotate)
+        .service(edit::edit)
+        .service(versions::list)
+        .service(versions::show)
+        .service(versions::restore)
+        .service(sitemap::sitemap)
+        .service(sitemap::sitemap_page)
+        .service(sitemap::robots)
+        .service(replication::status)
+        .service(healthz)
+        .service(readyz)
+        .service(admin::rescan)
+        .service(admin::purge_thumbnails)
+        .service(admin::info)
+        .service(maintenance::show)
+        .service(maintenance::update)
+        .configure(batch::configure)
+        .service(upload::upload)
+        .service(backup::status)
+        .service(backup::run)
+        .configure(metadb::configure)
+        .service(optimize::status)
+        .service(optimize::run)
+        .service(export::status)
+        .service(export::run)
+        .service(snapshot::export_json)
+        .service(thumbs::rebuild_thumbnails)
+        .service(thumbs::stats)
+        .service(quarantine::failures)
+        .service(serve_thumbnail)
+        .service(serve_versioned_thumbnail)
+        .service(serve_image)
+        .service(serve_short_id);
+    #[cfg(feature = "graphql")]
+    cfg.service(graphql::query);
+}
+
+/// 检查认证失败日志可以写入，创建图片目录和缩略图目录，整理缩略图缓存，在后台开始首次扫描和缩略图预生成
+fn prepare(app_config: &AppConfig) -> std::io::Result<()> {
+    if let Some(path) = &app_config.settings.auth_log {
+        auth_log::check(path)?;
+    }
+    if !Path::new(app_config.pic_dir.as_str()).exists() {
+        fs::create_dir_all(app_config.pic_dir.as_str())?;
+        log::info!("已创建图片目录: {}", app_config.pic_dir);
+    }
+    // 旧版本的缩略图直接放在 `.thumbnails` 下，迁移到按尺寸分开的目录，并删除其他尺寸的缓存
+    let cache_dir = Path::new(app_config.cache_dir.as_str());
+    let size = app_config.settings.thumb_size;
+    let mut migration = thumbs::migrate_layout(cache_dir, size)?;
+    thumbs::remove_other_sizes(cache_dir, size, &mut migration)?;
+    if let Some(summary) = thumbs::migration_summary(&migration) {
+        log::info!("{}", summary);
+    }
+    if !Path::new(app_config.thumb_dir.as_str()).exists() {
+        fs::create_dir_all(app_config.thumb_dir.as_str())?;
+        log::info!("已创建缩略图目录: {}", app_config.thumb_dir);
+    }
+
+    // 大型图库的首次扫描可能需要很久，在后台进行，服务先启动；页面上显示扫描进度
+    let config = app_config.clone();
+    std::thread::Builder::new()
+        .name("initial-scan".to_string())
+        .spawn(move || {
+            let started = Instant::now();
+            let images = config.rescan();
+            log::info!("扫描完成: {} 张图片 (耗时 {} 毫秒)", images.len(), started.elapsed().as_millis());
+        })?;
+    // 归档目录的缩略图总是预生成
+    if app_config.webdav.is_none() && (app_config.settings.prewarm || app_config.tiers.has_archive()) {
+        scan::spawn_prewarm(app_config.clone());
+    }
+    Ok(())
+}
+
+/// 本地目录的变化由文件监听立即发现；定期扫描作为兜底，也负责 WebDAV 来源。
+/// 返回的监听器停止前需要一直持有，定期扫描需要在 actix 运行时中启动
+fn watch_changes(app_config: &AppConfig) -> Option<notify::RecommendedWatcher> {
+    let watcher = match &app_config.webdav {
+        Some(_) => None,
+        None => match events::watch(app_config.clone()) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                log::warn!("文件监听启动失败，改为每 {} 秒扫描: {}", BACKGROUND_SCAN_INTERVAL.as_secs(), e);
+                None
+            }
+        },
+    };
+    let config = app_config.clone();
+    scheduler::every("扫描", BACKGROUND_SCAN_INTERVAL, BACKGROUND_SCAN_INTERVAL, move || {
+        config.current_images();
+    });
+    let popularity = app_config.popularity.clone();
+    scheduler::every("保存浏览热度", popularity::SAVE_INTERVAL, popularity::SAVE_INTERVAL, move || {
+        if let Err(e) = popularity.save() {
+            log::warn!("保存浏览热度失败: {}", e);
+        }
+    });
+    if let Some(schedule) = &app_config.scheduled_rescan {
+        scan::spawn_scheduled_rescan(app_config.clone(), schedule.clone());
+    }
+    watcher
+}
+
+/// 按配置启动独立的服务器，直到收到停止信号
+pub async fn run(args: Config) -> std::io::Result<()> {
+    let host = "0.0.0.0";
+    logging::init(args.log_format, args.access_log.as_ref())?;
+    // 没有其他 tracing subscriber 时 span 会经 `log` 输出，设置一个空的；导出链路追踪时由 telemetry 设置
+    if args.otlp_endpoint.is_none() {
+        let _ = tracing::subscriber::set_global_default(tracing::subscriber::NoSubscriber::default());
+    }
+    // 目录不可用、端口被占用等配置问题在启动前报错退出
+    doctor::check_startup(&args);
+    // 模板有语法错误时在启动前报错退出
+    let app_config = AppConfig::load(args.clone()).unwrap_or_else(|e| {
+        log::error!("错误: {}", e);
+        std::process::exit(1);
+    });
+    if let Some(dir) = &args.templates_dir {
+        let overridden = Templates::overridden(dir);
+        if !overridden.is_empty() {
+            log::info!("使用自定义模板: {} ({})", overridden.join(", "), dir);
+        }
+    }
+    // 证书有问题时在启动前报错退出
+    #[cfg(feature = "tls")]
+    let tls_config = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert), Some(key)) => Some(tls::load(cert, key)?),
+        _ => None,
+    };
+    let scheme = if args.tls_cert.is_some() { "https" } else { "http" };
+
+    prepare(&app_config)?;
+
+    log::info!("本地图床已启动");
+    match &args.webdav_url {
+        Some(url) => log::info!("图片来源: WebDAV {}", url),
+        None => log::info!("图片目录: {}", args.pic_dir),
+    }
+    log::info!("缩略图目录: {}", app_config.thumb_dir);
+    log::info!("缩略图预生成: {}", if args.prewarm && args.webdav_url.is_none() { "已启用" } else { "已关闭" });
+    if let Some(schedule) = &app_config.scheduled_rescan {
+        log::info!("定时扫描: {} (UTC)，下次 {}", schedule.cron(), schedule.next_run_at().unwrap_or_default());
+    }
+    log::info!("并发上限: 同时最多生成 {} 张缩略图", args.max_concurrent);
+    if args.thumb_cache_mb > 0 {
+        log::info!("缩略图内存缓存: {} MB", args.thumb_cache_mb);
+    }
+    if args.originals_secret.is_some() {
+        log::info!("原图签名: 已启用，生成的链接有效期 {} 秒", args.originals_ttl);
+    }
+    log::info!("访问地址: {}://{}:{}/", scheme, host, args.port);
+    if args.tls_cert.is_some() {
+        log::info!("HTTPS: 已启用 (HTTP/2)");
+    }
+    log::info!("自动刷新: 已启用 (实时推送)");
+    // 配置了对外地址时二维码指向它，否则指向局域网地址
+    let public_url = match &args.base_url {
+        Some(url) => {
+            log::info!("对外地址: {}/", url);
+            Some(format!("{}/", url))
+        }
+        None => lan_ipv4().map(|ip| {
+            let url = format!("{}://{}:{}/", scheme, ip, args.port);
+            log::info!("局域网地址: {}", url);
+            url
+        }),
+    };
+    // 只在交互式终端中打印，避免写进 systemd/docker 日志
+    if let Some(url) = public_url.filter(|_| std::io::stdout().is_terminal() && !logging::is_json()) {
+        qr::print_terminal(&url);
+    }
+
+    if let Some(log_config) = &args.access_log {
+        log::info!("访问日志: {}", log_config.path);
+    }
+    if args.admin_token.is_some() {
+        log::info!("管理接口: 已启用 (/api/admin)");
+    }
+    if let Some(path) = &args.auth_log {
+        log::info!("认证失败日志: {}", path);
+    }
+    if args.user_homes {
+        log::info!("用户私有目录: {}/<用户名>/", homes::HOMES_DIR);
+    }
+    if let Some(replicator) = &app_config.replicator {
+        fs::create_dir_all(app_config.data_dir.as_str())?;
+        log::info!(
+            "异地同步: 每 {} 秒同步到 {}",
+            args.replicate_interval,
+            replication::display_target(args.replicate_to.as_deref().unwrap_or_default())
+        );
+        replication::spawn(
+            replicator.clone(),
+            args.pic_dir.clone(),
+            app_config.aliases.clone(),
+            app_config.maintenance.clone(),
+        );
+    }
+    if let Some(backup) = &app_config.backup {
+        let dir = args.backup_dir.as_deref().unwrap_or_default();
+        if Path::new(dir).starts_with(app_config.data_dir.as_str()) {
+            log::error!("错误: 备份目录不能位于 {} 中", app_config.data_dir);
+            std::process::exit(1);
+        }
+        log::info!(
+            "定时备份: 每 {} 秒备份到 {}，保留 {} 份",
+            args.backup_interval, dir, args.backup_keep
+        );
+        backup::spawn(
+            backup.clone(),
+            args.pic_dir.clone(),
+            app_config.aliases.clone(),
+            app_config.data_dir.to_string(),
+            app_config.maintenance.clone(),
+        );
+    }
+    if !args.webhooks.is_empty() {
+        log::info!("Webhook: 已启用，推送到 {} 个地址", args.webhooks.len());
+    }
+    if app_config.hooks.is_some() {
+        log::info!("命令钩子: 已启用 (超时 {} 秒)", args.hook_timeout);
+    }
+    if let Some(address) = &args.clamd {
+        log::info!("上传病毒扫描: clamd ({})", address);
+    } else if let Some(command) = &args.scan_command {
+        log::info!("上传病毒扫描: {}", command);
+    }
+    let _watcher = watch_changes(&app_config);
+    if !args.proxy_allow.is_empty() {
+        log::info!("图片代理: 已启用，允许域名 {}", args.proxy_allow.join(", "));
+    }
+    let dav_handler = if args.dav {
+        log::info!("WebDAV 服务: http://{}:{}{}/", host, args.port, dav::DAV_PREFIX);
+        Some(dav::handler(&args.pic_dir))
+    } else {
+        None
+    };
+    #[cfg(feature = "dlna")]
+    let dlna_device = if args.dlna {
+        let device = dlna::Device::new(&args.dlna_name, &args.pic_dir, args.port);
+        match dlna::start(device.clone()) {
+            Ok(()) => log::info!("DLNA 媒体服务器: 已启用 ({})", args.dlna_name),
+            Err(e) => log::warn!("DLNA 广播启动失败 (UDP 1900): {}", e),
+        }
+        Some(device)
+    } else {
+        None
+    };
+    #[cfg(feature = "dlna")]
+    let dlna_goodbye = dlna_device.clone();
+    #[cfg(feature = "otel")]
+    let _telemetry = match &args.otlp_endpoint {
+        Some(endpoint) => {
+            let telemetry = telemetry::init(endpoint)?;
+            log::info!("链路追踪: 导出到 {}", endpoint);
+            Some(telemetry)
+        }
+        None => None,
+    };
+
+    let galleries = galleries::build(&args)?;
+    let config_data = web::Data::new(app_config);
+    #[cfg(feature = "grpc")]
+    if let Some(address) = &args.grpc {
+        grpc::start(address, config_data.clone())?;
+        log::info!("gRPC 接口: {} (proto/pic_url.proto)", address);
+    }
+
+    let server = HttpServer::new(move || {
+        // 主画廊的中间件只作用于它自己的路由，不经过其他画廊的请求
+        let main = web::scope("")
+            .app_data(config_data.clone())
+            .app_data(payload::json(&config_data))
+            .app_data(payload::form(&config_data))
+            .wrap(middleware::from_fn(homes::guard))
+            .wrap(middleware::from_fn(folding::canonicalize))
+            .wrap(middleware::from_fn(policy::enforce))
+            .wrap(ErrorHandlers::new().default_handler(errors::handle))
+            .wrap(middleware::from_fn(versioning::middleware))
+            .configure(|cfg| {
+                if let Some(handler) = &dav_handler {
+                    dav::configure(cfg, handler.clone());
+                }
+                #[cfg(feature = "dlna")]
+                if let Some(device) = &dlna_device {
+                    dlna::configure(cfg, device.clone());
+                }
+            })
+            .configure(configure);
+        App::new()
+            .wrap_fn(|req, srv| {
+                let started = Instant::now();
+                let method = req.method().to_string();
+                let request_id = request_id::of(req.request()).unwrap_or_default();
+                let span = tracing::info_span!(
+                    "http.request",
+                    http.method = %method,
+                    http.target = %req.path(),
+                    http.request_id = %request_id,
+                    http.route = tracing::field::Empty,
+                    http.status_code = tracing::field::Empty,
+                );
+                let fut = span.in_scope(|| srv.call(req));
+                async move {
+                    let res = fut.await?;
+                    let route = res
+                        .request()
+                        .match_pattern()
+                        .unwrap_or_else(|| "unmatched".to_string());
+                    let status = res.status().as_u16();
+                    let span = tracing::Span::current();
+                    span.record("http.route", route.as_str());
+                    span.record("http.status_code", status);
+                    METRICS.record_request(&route, &method, status, started.elapsed());
+                    logging::log_request(res.request(), &route, status, started.elapsed());
+                    Ok(res)
+                }
+                .instrument(span)
+            })
+            .wrap(middleware::from_fn(request_id::middleware))
+            .wrap(access_log::access_logger())
+            .configure(|cfg| {
+                // 在主画廊之前注册，按域名选择的画廊最先
+                for gallery in &galleries {
+                    cfg.service(gallery.clone());
+                }
+            })
+            .service(main)
+    });
+    // 明文端口同时接受 HTTP/1.1 和 h2c（prior knowledge），反向代理可以用 HTTP/2 连接后端
+    #[cfg(feature = "tls")]
+    let server = match tls_config {
+        Some(tls_config) => server.bind_rustls_0_23((host, args.port), tls_config)?,
+        None => server.bind_auto_h2c((host, args.port))?,
+    };
+    #[cfg(not(feature = "tls"))]
+    let server = server.bind_auto_h2c((host, args.port))?;
+    server.run().await?;
+
+    #[cfg(feature = "dlna")]
+    if let Some(device) = &dlna_goodbye {
+        dlna::stop(device);
+    }
+    Ok(())
+}
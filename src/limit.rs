@@ -0,0 +1,83 @@
+//! 昂贵操作的并发上限：生成缩略图、代理缩放等同一时间最多执行 `--max-concurrent` 个，
+//! 超出的请求立即返回 `503` 和 `Retry-After`，避免冷缓存时的一大波请求拖垮树莓派这类小机器
+
+use actix_web::HttpResponse;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+
+/// 返回 503 时建议客户端等待的秒数
+const RETRY_AFTER_SECS: u64 = 2;
+
+pub struct Limiter {
+    max: usize,
+    active: Mutex<usize>,
+    released: Condvar,
+    rejected: AtomicU64,
+}
+
+/// 持有期间占用一个名额，drop 时归还
+pub struct Permit(Arc<Limiter>);
+
+impl Limiter {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            active: Mutex::new(0),
+            released: Condvar::new(),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// 默认上限为 CPU 核数
+    pub fn default_max() -> usize {
+        std::thread::available_parallelism().map(|n| n.get()).unwrap_or(2)
+    }
+
+    /// 有空闲名额时占用一个，已满时返回 `None` 并计入拒绝次数
+    pub fn try_acquire(self: &Arc<Self>) -> Option<Permit> {
+        let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        if *active >= self.max {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        *active += 1;
+        Some(Permit(self.clone()))
+    }
+
+    /// 等到没有请求在执行昂贵操作，供后台预生成缩略图使用：后台任务不占用名额，
+    /// 但有页面请求在生成时让路，最多比上限多出一个
+    pub fn wait_idle(&self) {
+        let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        while *active > 0 {
+            active = self.released.wait(active).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    pub fn active(&self) -> usize {
+        *self.active.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut active = self.0.active.lock().unwrap_or_else(|e| e.into_inner());
+        *active -= 1;
+        self.0.released.notify_all();
+    }
+}
+
+/// 名额已满时的响应
+pub fn busy() -> HttpResponse {
+    HttpResponse::ServiceUnavailable()
+        .insert_header(("Retry-After", RETRY_AFTER_SECS.to_string()))
+        .insert_header(("Cache-Control", "no-store"))
+        .body("Server busy, retry later")
+}
@@ -0,0 +1,314 @@
+//! 图片列表的排序和筛选：`/api/images` 的 `sort`、`order`、`folder`、`ext`、`q`、`tag`、
+//! `from`/`to`、`album` 和 `group` 参数
+
+use crate::errors::ApiError;
+use crate::AppConfig;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fs;
+use std::time::SystemTime;
+
+#[derive(Deserialize)]
+pub struct ImageListQuery {
+    pub page: Option<usize>,
+    pub per_page: Option<usize>,
+    /// `name`（默认）、`date` 或 `size`
+    sort: Option<String>,
+    /// `asc`（默认）或 `desc`
+    order: Option<String>,
+    /// 只列出这个目录（含子目录）下的图片
+    folder: Option<String>,
+    /// 逗号分隔的扩展名，`jpg` 同时匹配 `.jpeg`
+    ext: Option<String>,
+    /// 路径中包含的文字，不区分大小写
+    q: Option<String>,
+    /// 只列出带有这个标签的图片
+    tag: Option<String>,
+    /// 拍摄日期不早于 `YYYY-MM-DD`
+    from: Option<String>,
+    /// 拍摄日期不晚于 `YYYY-MM-DD`
+    to: Option<String>,
+    /// 智能相册的名称，相册保存的条件与其他参数同时生效
+    album: Option<String>,
+    /// `folder` 按所在目录分组返回
+    group: Option<String>,
+}
+
+/// 筛选条件，智能相册保存的也是它
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct Filter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ext: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub q: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SortKey {
+    Name,
+    Date,
+    Size,
+}
+
+impl ImageListQuery {
+    /// 检查参数，`album` 不存在时返回 404
+    pub fn validate(&self, config: &AppConfig) -> Result<(), ApiError> {
+        self.sort_key().map_err(ApiError::bad_request)?;
+        match self.order.as_deref() {
+            None | Some("asc") | Some("desc") => {}
+            Some(_) => return Err(ApiError::bad_request("order must be asc or desc")),
+        }
+        match self.group.as_deref() {
+            None | Some("folder") => {}
+            Some(_) => return Err(ApiError::bad_request("group must be folder")),
+        }
+        self.filter().validate().map_err(ApiError::bad_request)?;
+        match &self.album {
+            Some(name) if config.albums.get(name).is_none() => {
+                Err(ApiError::not_found(format!("album not found: {}", name)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn sort_key(&self) -> Result<SortKey, &'static str> {
+        match self.sort.as_deref() {
+            None | Some("name") => Ok(SortKey::Name),
+            Some("date") => Ok(SortKey::Date),
+            Some("size") => Ok(SortKey::Size),
+            Some(_) => Err("sort must be name, date or size"),
+        }
+    }
+
+    fn filter(&self) -> Filter {
+        Filter {
+            folder: self.folder.clone(),
+            ext: self.ext.clone(),
+            q: self.q.clone(),
+            tag: self.tag.clone(),
+            from: self.from.clone(),
+            to: self.to.clone(),
+        }
+    }
+
+    /// 是否按目录分组（`group=folder`）
+    pub fn grouped(&self) -> bool {
+        self.group.as_deref() == Some("folder")
+    }
+
+    /// 按查询条件筛选并排序（已排序的）图片列表；参数需先经过 `validate`。
+    /// 分组时同一目录的图片排在一起，目录按路径排序，目录内保持请求的顺序
+    pub fn apply(&self, config: &AppConfig, images: Vec<String>) -> Vec<String> {
+        let own = self.filter().matcher();
+        // 相册在检查参数之后被删除时没有图片
+        let album = match &self.album {
+            Some(name) => match config.albums.get(name) {
+                Some(filter) => Some(filter.matcher()),
+                None => return Vec::new(),
+            },
+            None => None,
+        };
+        let mut images: Vec<String> = images
+            .into_iter()
+            .filter(|path| {
+                own.matches(config, path) && album.as_ref().is_none_or(|album| album.matches(config, path))
+            })
+            .collect();
+
+        let key = self.sort_key().unwrap_or(SortKey::Name);
+        let desc = self.order.as_deref() == Some("desc");
+        if key != SortKey::Name {
+            let mut keyed: Vec<(Option<u64>, String)> = images
+                .into_iter()
+                .map(|path| {
+                    let (modified, size) = metadata(config, &path);
+                    let value = match key {
+                        SortKey::Date => modified
+                            .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+                            .map(|d| d.as_secs()),
+                        _ => size,
+                    };
+                    (value, path)
+                })
+                .collect();
+            // 取不到时间或大小的总是排在最后，相同时按路径排序
+            keyed.sort_by(|(a, pa), (b, pb)| match (a, b) {
+                (Some(a), Some(b)) => {
+                    let ordering = a.cmp(b);
+                    if desc { ordering.reverse() } else { ordering }.then_with(|| pa.cmp(pb))
+                }
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => pa.cmp(pb),
+            });
+            images = keyed.into_iter().map(|(_, path)| path).collect();
+        } else if desc {
+            images.reverse();
+        }
+        if self.grouped() {
+            images.sort_by(|a, b| parent(a).cmp(parent(b)));
+        }
+        images
+    }
+}
+
+/// 图片所在的目录，根目录下的图片为 `""`
+pub fn parent(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+/// 把（已按目录排在一起的）`selected` 按目录分组，每组带上该目录在 `all` 中的图片数（不含子目录），
+/// 分页时同一目录可能跨越多页，数量仍是整个目录的
+pub fn group_by_folder<'a>(
+    all: &[String],
+    selected: &'a [String],
+) -> Vec<(String, usize, &'a [String])> {
+    let mut counts = std::collections::HashMap::new();
+    for path in all {
+        *counts.entry(parent(path)).or_insert(0) += 1;
+    }
+    selected
+        .chunk_by(|a, b| parent(a) == parent(b))
+        .map(|chunk| {
+            let folder = parent(&chunk[0]);
+            (folder.to_string(), counts.get(folder).copied().unwrap_or(0), chunk)
+        })
+        .collect()
+}
+
+impl Filter {
+    /// 检查日期格式
+    pub fn validate(&self) -> Result<(), &'static str> {
+        for date in [&self.from, &self.to].into_iter().flatten() {
+            if !is_date(date) {
+                return Err("from and to must be YYYY-MM-DD");
+            }
+        }
+        Ok(())
+    }
+
+    /// 没有任何条件
+    pub fn is_empty(&self) -> bool {
+        [&self.folder, &self.ext, &self.q, &self.tag, &self.from, &self.to]
+            .iter()
+            .all(|value| value.as_deref().is_none_or(|v| v.trim().is_empty()))
+    }
+
+    /// 预先整理好条件，逐张匹配时不再重复处理
+    pub fn matcher(&self) -> Matcher {
+        let non_empty = |value: &Option<String>| {
+            value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string)
+        };
+        Matcher {
+            folder: self.folder.as_deref().unwrap_or("").trim_matches('/').to_string(),
+            exts: self
+                .ext
+                .as_deref()
+                .unwrap_or("")
+                .split(',')
+                .map(|e| normalize_ext(e.trim().trim_start_matches('.')))
+                .filter(|e| !e.is_empty())
+                .collect(),
+            q: self.q.as_deref().unwrap_or("").trim().to_lowercase(),
+            tag: non_empty(&self.tag),
+            from: non_empty(&self.from),
+            to: non_empty(&self.to),
+        }
+    }
+}
+
+pub struct Matcher {
+    folder: String,
+    exts: Vec<String>,
+    q: String,
+    tag: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+}
+
+impl Matcher {
+    pub fn matches(&self, config: &AppConfig, path: &str) -> bool {
+        if !self.folder.is_empty()
+            && !path
+                .strip_prefix(self.folder.as_str())
+                .map(|rest| rest.starts_with('/'))
+                .unwrap_or(false)
+        {
+            return false;
+        }
+        if !self.exts.is_empty() {
+            let ext = path.rsplit_once('.').map(|(_, e)| normalize_ext(e)).unwrap_or_default();
+            if !self.exts.contains(&ext) {
+                return false;
+            }
+        }
+        if let Some(tag) = &self.tag {
+            if !config.tags.has(path, tag) {
+                return false;
+            }
+        }
+        if !self.q.is_empty() && !path.to_lowercase().contains(&self.q) {
+            return false;
+        }
+        // 日期条件最后检查，需要读取 EXIF（有缓存）
+        if self.from.is_some() || self.to.is_some() {
+            let Some(taken) = config.captures.taken(config, path) else {
+                return false;
+            };
+            let date = &taken[..10];
+            if self.from.as_deref().is_some_and(|from| date < from) || self.to.as_deref().is_some_and(|to| date > to) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+pub fn is_date(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    bytes.len() == 10
+        && bytes[4] == b'-'
+        && bytes[7] == b'-'
+        && bytes.iter().enumerate().all(|(i, b)| i == 4 || i == 7 || b.is_ascii_digit())
+}
+
+/// 小写的扩展名，`jpeg` 视为 `jpg`
+pub fn normalize_ext(ext: &str) -> String {
+    match ext.to_lowercase().as_str() {
+        "jpeg" => "jpg".to_string(),
+        ext => ext.to_string(),
+    }
+}
+
+/// 图片的修改时间和大小，WebDAV 来源取自最近一次列目录的结果
+pub fn metadata(config: &AppConfig, relative: &str) -> (Option<SystemTime>, Option<u64>) {
+    match &config.webdav {
+        Some(source) => (source.modified(relative), source.size(relative)),
+        None => match fs::metadata(config.fs_path(relative)) {
+            Ok(meta) => (meta.modified().ok(), Some(meta.len())),
+            Err(_) => (None, None),
+        },
+    }
+}
+
+/// 图片所在的全部目录（含上级目录）及其中（含子目录）的图片数，按路径排序
+pub fn folders(images: &[String]) -> Vec<(String, usize)> {
+    let mut counts = std::collections::BTreeMap::new();
+    for path in images {
+        let mut end = 0;
+        while let Some(i) = path[end..].find('/') {
+            end += i;
+            *counts.entry(path[..end].to_string()).or_insert(0) += 1;
+            end += 1;
+        }
+    }
+    counts.into_iter().collect()
+}
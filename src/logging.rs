@@ -0,0 +1,155 @@
+//! 服务器运行时的日志，通过 `log` 输出：
+//!
+//! - `text`（默认）：提示写到标准输出，警告和错误写到标准错误，只有消息本身
+//! - `json` (`--log-format json`)：每条日志是一行 JSON，全部写到标准输出，Loki、Elasticsearch
+//!   等可以直接采集；每个请求完成时另有一条，带路由、路径、状态码、耗时和请求 ID
+//!
+//! 访问日志 (`--access-log`) 仍按 combined 格式写入文件，不受影响。只处理本程序的日志，
+//! actix 等依赖库的日志不输出
+
+use crate::access_log::{AccessLogFile, ACCESS_LOG_TARGET};
+use crate::format_time;
+use crate::request_id::RequestId;
+use actix_web::{HttpMessage, HttpRequest};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+
+/// 请求事件使用的 log target，消息本身就是完整的 JSON
+const REQUEST_LOG_TARGET: &str = "pic_url::request";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "text" => Some(LogFormat::Text),
+            "json" => Some(LogFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+static FORMAT: OnceLock<LogFormat> = OnceLock::new();
+
+/// 是否输出 JSON 日志；嵌入其他程序、没有调用 [`init`] 时为 `false`
+pub fn is_json() -> bool {
+    FORMAT.get() == Some(&LogFormat::Json)
+}
+
+struct Logger {
+    format: LogFormat,
+    access: Option<AccessLogFile>,
+}
+
+#[derive(Serialize)]
+struct Event<'a> {
+    timestamp: String,
+    level: &'a str,
+    target: &'a str,
+    message: String,
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        match metadata.target() {
+            ACCESS_LOG_TARGET => self.access.is_some(),
+            target => target.starts_with("pic_url") && metadata.level() <= log::Level::Info,
+        }
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if record.target() == ACCESS_LOG_TARGET {
+            if let Some(access) = &self.access {
+                access.write_line(&record.args().to_string());
+            }
+            return;
+        }
+        match self.format {
+            LogFormat::Text if record.target() == REQUEST_LOG_TARGET => {}
+            LogFormat::Text if record.level() == log::Level::Info => println!("{}", record.args()),
+            LogFormat::Text => eprintln!("{}", record.args()),
+            LogFormat::Json if record.target() == REQUEST_LOG_TARGET => println!("{}", record.args()),
+            LogFormat::Json => {
+                let event = Event {
+                    timestamp: format_time(SystemTime::now()),
+                    level: level_name(record.level()),
+                    target: record.target(),
+                    message: record.args().to_string(),
+                };
+                if let Ok(line) = serde_json::to_string(&event) {
+                    println!("{}", line);
+                }
+            }
+        }
+    }
+
+    fn flush(&self) {
+        let _ = io::stdout().flush();
+        if let Some(access) = &self.access {
+            access.flush();
+        }
+    }
+}
+
+fn level_name(level: log::Level) -> &'static str {
+    match level {
+        log::Level::Error => "error",
+        log::Level::Warn => "warn",
+        log::Level::Info => "info",
+        log::Level::Debug => "debug",
+        log::Level::Trace => "trace",
+    }
+}
+
+/// 安装日志写入器，指定了访问日志时同时打开日志文件
+pub fn init(format: LogFormat, access: Option<&crate::AccessLogConfig>) -> io::Result<()> {
+    let access = access.map(AccessLogFile::open).transpose()?;
+    log::set_boxed_logger(Box::new(Logger { format, access })).map_err(io::Error::other)?;
+    log::set_max_level(log::LevelFilter::Info);
+    let _ = FORMAT.set(format);
+    Ok(())
+}
+
+/// 请求处理出错的原因，由 [`errors::handle`](crate::errors::handle) 记录在请求扩展中，
+/// 随请求的日志事件一起输出
+pub struct RequestError(pub String);
+
+/// 请求完成时的日志事件，只在 JSON 格式时输出
+pub fn log_request(req: &HttpRequest, route: &str, status: u16, duration: Duration) {
+    if !is_json() {
+        return;
+    }
+    let extensions = req.extensions();
+    let error = extensions.get::<RequestError>().map(|e| e.0.as_str());
+    let level = match status {
+        500.. => log::Level::Error,
+        400.. if error.is_some() => log::Level::Warn,
+        _ => log::Level::Info,
+    };
+    let line = serde_json::json!({
+        "timestamp": format_time(SystemTime::now()),
+        "level": level_name(level),
+        "target": REQUEST_LOG_TARGET,
+        "message": format!("{} {} {}", req.method(), req.path(), status),
+        "request_id": extensions.get::<RequestId>().map(|id| id.0.as_str()),
+        "method": req.method().as_str(),
+        "route": route,
+        "path": req.path(),
+        "status": status,
+        "duration_ms": (duration.as_secs_f64() * 1_000_000.0).round() / 1000.0,
+        "client": req.peer_addr().map(|addr| addr.ip().to_string()),
+        "error": error,
+    });
+    log::log!(target: REQUEST_LOG_TARGET, level, "{}", line);
+}
@@ -0,0 +1,126 @@
+//! 维护模式：整理底层存储时打开。读取照常，上传、编辑、删除等写操作返回 `503`，
+//! 扫描、缩略图预生成、备份和同步暂停；页面顶部显示提示，状态变化通过 `/api/events` 推送
+
+use crate::admin::AdminAuth;
+use crate::errors::ApiError;
+use crate::{format_time, AppConfig};
+use actix_web::dev::Payload;
+use actix_web::http::StatusCode;
+use actix_web::{get, post, rt, web, FromRequest, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use std::future::{ready, Ready};
+use std::sync::RwLock;
+use std::time::SystemTime;
+use tokio::sync::broadcast;
+
+#[derive(Serialize, Clone, Default)]
+pub struct MaintenanceStatus {
+    pub enabled: bool,
+    /// 显示在页面提示中的说明
+    pub message: Option<String>,
+    pub since: Option<String>,
+}
+
+pub struct Maintenance {
+    status: RwLock<MaintenanceStatus>,
+    sender: broadcast::Sender<MaintenanceStatus>,
+}
+
+impl Default for Maintenance {
+    fn default() -> Self {
+        Self {
+            status: RwLock::new(MaintenanceStatus::default()),
+            sender: broadcast::channel(16).0,
+        }
+    }
+}
+
+impl Maintenance {
+    pub fn status(&self) -> MaintenanceStatus {
+        self.status.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.status.read().map(|status| status.enabled).unwrap_or(false)
+    }
+
+    /// 打开或关闭维护模式，返回新的状态；已经打开时只更新说明
+    fn set(&self, enabled: bool, message: Option<String>) -> MaintenanceStatus {
+        let mut status = self.status.write().unwrap_or_else(|e| e.into_inner());
+        *status = MaintenanceStatus {
+            enabled,
+            message: message.filter(|m| enabled && !m.trim().is_empty()),
+            since: match (enabled, status.since.take()) {
+                (false, _) => None,
+                (true, Some(since)) => Some(since),
+                (true, None) => Some(format_time(SystemTime::now())),
+            },
+        };
+        let _ = self.sender.send(status.clone());
+        status.clone()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<MaintenanceStatus> {
+        self.sender.subscribe()
+    }
+}
+
+/// 提取器：维护模式下拒绝写操作，返回 `503`
+pub struct Writable;
+
+impl FromRequest for Writable {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let status = req.app_data::<web::Data<AppConfig>>().map(|config| config.maintenance.status());
+        match status {
+            Some(status) if status.enabled => ready(Err(rejection(&status))),
+            _ => ready(Ok(Writable)),
+        }
+    }
+}
+
+/// 维护模式下写操作的错误，说明放在 `error.details` 中
+pub fn rejection(status: &MaintenanceStatus) -> ApiError {
+    ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "server is in maintenance mode, writes are paused").with_details(status)
+}
+
+#[get("/api/maintenance")]
+pub async fn show(config: web::Data<AppConfig>) -> HttpResponse {
+    HttpResponse::Ok().json(config.maintenance.status())
+}
+
+#[derive(Deserialize)]
+pub struct MaintenanceRequest {
+    enabled: bool,
+    message: Option<String>,
+}
+
+#[post("/api/admin/maintenance")]
+pub async fn update(
+    _auth: AdminAuth,
+    config: web::Data<AppConfig>,
+    body: web::Json<MaintenanceRequest>,
+) -> HttpResponse {
+    let body = body.into_inner();
+    let was_enabled = config.maintenance.is_enabled();
+    let status = config.maintenance.set(body.enabled, body.message);
+    if status.enabled && !was_enabled {
+        log::info!("已进入维护模式");
+    } else if !status.enabled && was_enabled {
+        log::info!("已退出维护模式，重新扫描图片目录");
+        // 维护期间暂停的扫描补上一次，预生成随后继续
+        let config = config.into_inner();
+        rt::spawn(async move {
+            let _ = web::block(move || {
+                config.rescan();
+                if config.webdav.is_none() && config.settings.prewarm {
+                    config.scan.request_prewarm();
+                }
+            })
+            .await;
+        });
+    }
+    HttpResponse::Ok().json(status)
+}
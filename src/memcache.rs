@@ -0,0 +1,114 @@
+//! 常用缩略图的内存缓存 (`--thumb-cache-mb`)：缩略图读入后保存为 `Bytes`，
+//! 之后的请求直接引用同一块内存作为响应体，不再读文件、也不复制。
+//! 每次仍检查文件的大小和修改时间，缩略图重新生成后自动失效；超过容量时淘汰最久没有访问的
+
+use actix_web::web::Bytes;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+struct Entry {
+    data: Bytes,
+    modified: Option<SystemTime>,
+    /// 最近一次访问的序号，用于淘汰
+    used: u64,
+}
+
+#[derive(Default)]
+struct State {
+    entries: HashMap<PathBuf, Entry>,
+    bytes: u64,
+    clock: u64,
+}
+
+pub struct MemoryCache {
+    /// 容量，字节；为 0 时不缓存
+    capacity: u64,
+    state: Mutex<State>,
+    hits: AtomicU64,
+}
+
+#[derive(Serialize)]
+pub struct MemoryCacheStats {
+    entries: usize,
+    bytes: u64,
+    capacity: u64,
+    hits: u64,
+}
+
+impl MemoryCache {
+    pub fn new(capacity_mb: u64) -> Self {
+        Self {
+            capacity: capacity_mb * 1024 * 1024,
+            state: Mutex::new(State::default()),
+            hits: AtomicU64::new(0),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// 读取缩略图，缓存中的内容与文件一致时直接返回
+    pub fn read(&self, path: &Path) -> io::Result<Bytes> {
+        if !self.is_enabled() {
+            return fs::read(path).map(Bytes::from);
+        }
+        let meta = fs::metadata(path)?;
+        let modified = meta.modified().ok();
+        if let Ok(mut state) = self.state.lock() {
+            state.clock += 1;
+            let clock = state.clock;
+            if let Some(entry) = state.entries.get_mut(path) {
+                if entry.data.len() as u64 == meta.len() && entry.modified == modified {
+                    entry.used = clock;
+                    self.hits.fetch_add(1, Ordering::Relaxed);
+                    return Ok(entry.data.clone());
+                }
+            }
+        }
+        let data = Bytes::from(fs::read(path)?);
+        self.insert(path, data.clone(), modified);
+        Ok(data)
+    }
+
+    fn insert(&self, path: &Path, data: Bytes, modified: Option<SystemTime>) {
+        let size = data.len() as u64;
+        // 单个文件占去太多容量时不缓存，免得把其他缩略图都挤出去
+        if size > self.capacity / 8 {
+            return;
+        }
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let used = state.clock;
+        if let Some(old) = state.entries.insert(path.to_path_buf(), Entry { data, modified, used }) {
+            state.bytes -= old.data.len() as u64;
+        }
+        state.bytes += size;
+        while state.bytes > self.capacity {
+            let Some(oldest) = state.entries.iter().min_by_key(|(_, entry)| entry.used).map(|(path, _)| path.clone())
+            else {
+                break;
+            };
+            if let Some(entry) = state.entries.remove(&oldest) {
+                state.bytes -= entry.data.len() as u64;
+            }
+        }
+    }
+
+    pub fn stats(&self) -> MemoryCacheStats {
+        let (entries, bytes) = self.state.lock().map(|state| (state.entries.len(), state.bytes)).unwrap_or_default();
+        MemoryCacheStats {
+            entries,
+            bytes,
+            capacity: self.capacity,
+            hits: self.hits.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! 元数据备份和恢复 (`GET /api/admin/db/backup`、`POST /api/admin/db/restore`)：
+//! 标签、智能相册、短 ID（分享出去的 `/i/{id}` 链接）和 API 密钥保存在数据目录的几个 JSON 文件中，
+//! 迁移服务器时只复制图片会丢掉它们。备份把这些数据合成一个 JSON 文件下载，恢复时整体替换。
+//!
+//! 备份取的是内存中的数据，每一类在各自的锁内复制，不会读到写了一半的文件；
+//! 恢复先检查整个文件，全部有效后把四类数据都写到临时文件，全部写好才逐个改名换上并替换内存中的内容，
+//! 写到一半失败时现有数据不变；之后重新扫描，让短 ID 对应到新服务器上的图片。
+//! 备份中含有 API 密钥的哈希，两个接口都只能使用管理令牌或登录会话
+
+use crate::admin::AdminAuth;
+use crate::api_keys::StoredKey;
+use crate::errors::ApiError;
+use crate::ids;
+use crate::listing::Filter;
+use crate::maintenance::Writable;
+use crate::{attachment, format_time, AppConfig};
+use actix_web::http::{header, StatusCode};
+use actix_web::{get, web, HttpResponse, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use time::OffsetDateTime;
+
+/// 备份格式的版本，字段有不兼容的变化时增加
+const FORMAT: u32 = 1;
+/// 恢复时请求体的上限：短 ID 每张图片一条，大型图库的备份远超 `--max-body-kb` 的默认值
+const RESTORE_LIMIT: usize = 256 * 1024 * 1024;
+
+/// 注册备份和恢复的路由
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(backup).service(
+        web::resource("/api/admin/db/restore")
+            .app_data(web::JsonConfig::default().limit(RESTORE_LIMIT))
+            .route(web::post().to(restore)),
+    );
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Database {
+    format: u32,
+    /// 生成备份的服务器版本
+    version: String,
+    created_at: String,
+    /// 图片路径 → 标签
+    tags: BTreeMap<String, BTreeSet<String>>,
+    /// 相册名称 → 筛选条件
+    albums: BTreeMap<String, Filter>,
+    /// 短 ID → 图片
+    ids: HashMap<String, ids::Entry>,
+    api_keys: BTreeMap<String, StoredKey>,
+}
+
+#[derive(Serialize)]
+struct RestoreReport {
+    tagged_images: usize,
+    albums: usize,
+    ids: usize,
+    api_keys: usize,
+}
+
+/// 备份中有 API 密钥的哈希，不能用 API 密钥读写
+fn require_owner(auth: &AdminAuth) -> std::result::Result<(), ApiError> {
+    match auth.key {
+        Some(_) => Err(ApiError::new(StatusCode::FORBIDDEN, "API keys cannot back up or restore metadata")),
+        None => Ok(()),
+    }
+}
+
+fn dump(config: &AppConfig) -> Database {
+    Database {
+        format: FORMAT,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: format_time(SystemTime::now()),
+        tags: config.tags.contents(),
+        albums: config.albums.contents(),
+        ids: config.ids.contents(),
+        api_keys: config.api_keys.contents(),
+    }
+}
+
+/// 恢复时写好的临时文件，没有换上就失败时删除
+#[derive(Default)]
+struct Staged(Vec<PathBuf>);
+
+impl Staged {
+    fn add(&mut self, staged: PathBuf) -> PathBuf {
+        self.0.push(staged.clone());
+        staged
+    }
+}
+
+impl Drop for Staged {
+    fn drop(&mut self) {
+        for path in &self.0 {
+            if path.exists() {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+fn restore_all(config: &AppConfig, mut database: Database) -> io::Result<RestoreReport> {
+    // 没有标签的图片不保存
+    database.tags.retain(|_, tags| !tags.is_empty());
+    let report = RestoreReport {
+        tagged_images: database.tags.len(),
+        albums: database.albums.len(),
+        ids: database.ids.len(),
+        api_keys: database.api_keys.len(),
+    };
+    let mut staged = Staged::default();
+    let tags = staged.add(config.tags.stage(&database.tags)?);
+    let albums = staged.add(config.albums.stage(&database.albums)?);
+    let ids = staged.add(config.ids.stage(&database.ids)?);
+    let api_keys = staged.add(config.api_keys.stage(&database.api_keys)?);
+    // 改名只修改同一目录中的目录项，不再写入数据
+    config.tags.commit(&tags, database.tags)?;
+    config.albums.commit(&albums, database.albums)?;
+    config.ids.commit(&ids, database.ids)?;
+    config.api_keys.commit(&api_keys, database.api_keys)?;
+    // 短 ID 按路径和指纹对应到当前的图片，新服务器上改了名的图片也能沿用原来的 ID
+    config.rescan();
+    Ok(report)
+}
+
+/// 下载元数据备份，文件名如 `pic_url-metadata-20240501-120000.json`
+#[get("/api/admin/db/backup")]
+async fn backup(auth: AdminAuth, config: web::Data<AppConfig>) -> Result<HttpResponse> {
+    require_owner(&auth)?;
+    let body = web::block(move || serde_json::to_vec_pretty(&dump(&config))).await??;
+    let stamp = OffsetDateTime::now_utc();
+    let name = format!(
+        "pic_url-metadata-{:04}{:02}{:02}-{:02}{:02}{:02}.json",
+        stamp.year(),
+        stamp.month() as u8,
+        stamp.day(),
+        stamp.hour(),
+        stamp.minute(),
+        stamp.second()
+    );
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .insert_header((header::CONTENT_DISPOSITION, attachment(&name)))
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .body(body))
+}
+
+/// 用备份替换标签、相册、短 ID 和 API 密钥，请求体为 [`backup`] 下载的文件
+async fn restore(
+    auth: AdminAuth,
+    _writable: Writable,
+    database: web::Json<Database>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    require_owner(&auth)?;
+    let database = database.into_inner();
+    if database.format != FORMAT {
+        return Err(ApiError::bad_request(format!("unsupported backup format: {}", database.format)).into());
+    }
+    for (name, filter) in &database.albums {
+        filter
+            .validate()
+            .map_err(|e| ApiError::bad_request(format!("album {}: {}", name, e)))?;
+    }
+    let report = web::block(move || restore_all(&config, database)).await??;
+    log::info!(
+        "已从备份恢复元数据: {} 张图片的标签, {} 个相册, {} 个短 ID, {} 个 API 密钥",
+        report.tagged_images,
+        report.albums,
+        report.ids,
+        report.api_keys
+    );
+    Ok(HttpResponse::Ok().json(report))
+}
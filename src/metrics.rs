@@ -0,0 +1,236 @@
+use crate::limit::Limiter;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// 全局指标，由 `/metrics` 以 Prometheus 文本格式导出
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+#[derive(Clone)]
+struct Histogram {
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            counts: vec![0; LATENCY_BUCKETS.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, seconds: f64) {
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(self.counts.iter_mut()) {
+            if seconds <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let sep = if labels.is_empty() { "" } else { "," };
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(self.counts.iter()) {
+            let _ = writeln!(out, "{}_bucket{{{}{}le=\"{}\"}} {}", name, labels, sep, bound, count);
+        }
+        let _ = writeln!(out, "{}_bucket{{{}{}le=\"+Inf\"}} {}", name, labels, sep, self.count);
+        let braces = if labels.is_empty() { String::new() } else { format!("{{{}}}", labels) };
+        let _ = writeln!(out, "{}_sum{} {}", name, braces, self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, braces, self.count);
+    }
+}
+
+pub struct Metrics {
+    requests: Mutex<BTreeMap<(String, String, u16), u64>>,
+    latency: Mutex<BTreeMap<String, Histogram>>,
+    thumb_hits: AtomicU64,
+    thumb_misses: AtomicU64,
+    thumb_generation: Mutex<Histogram>,
+    thumb_failures: AtomicU64,
+}
+
+/// 启动以来的缩略图缓存命中、生成耗时和失败次数
+pub struct ThumbMetrics {
+    pub hits: u64,
+    pub misses: u64,
+    pub generated: u64,
+    pub generation_secs: f64,
+    pub failures: u64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            requests: Mutex::new(BTreeMap::new()),
+            latency: Mutex::new(BTreeMap::new()),
+            thumb_hits: AtomicU64::new(0),
+            thumb_misses: AtomicU64::new(0),
+            thumb_generation: Mutex::new(Histogram::new()),
+            thumb_failures: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn record_request(&self, route: &str, method: &str, status: u16, elapsed: Duration) {
+        if let Ok(mut requests) = self.requests.lock() {
+            *requests
+                .entry((route.to_string(), method.to_string(), status))
+                .or_insert(0) += 1;
+        }
+        if let Ok(mut latency) = self.latency.lock() {
+            latency
+                .entry(route.to_string())
+                .or_insert_with(Histogram::new)
+                .observe(elapsed.as_secs_f64());
+        }
+    }
+
+    pub fn thumb_cache_hit(&self) {
+        self.thumb_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn thumb_cache_miss(&self) {
+        self.thumb_misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn observe_thumb_generation(&self, elapsed: Duration) {
+        if let Ok(mut hist) = self.thumb_generation.lock() {
+            hist.observe(elapsed.as_secs_f64());
+        }
+    }
+
+    /// 记录一次缩略图生成失败；失败的图片和原因属于各个画廊，记在 [`Quarantine`](crate::quarantine::Quarantine) 中
+    pub fn thumb_failure(&self) {
+        self.thumb_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn thumb_metrics(&self) -> ThumbMetrics {
+        let (generated, generation_secs) = self
+            .thumb_generation
+            .lock()
+            .map(|hist| (hist.count, hist.sum))
+            .unwrap_or_default();
+        ThumbMetrics {
+            hits: self.thumb_hits.load(Ordering::Relaxed),
+            misses: self.thumb_misses.load(Ordering::Relaxed),
+            generated,
+            generation_secs,
+            failures: self.thumb_failures.load(Ordering::Relaxed),
+        }
+    }
+
+    /// 以 Prometheus 文本格式输出所有指标，磁盘占用在此时统计；
+    /// 索引大小和目录占用属于请求的画廊，由调用方传入
+    pub fn render(&self, pic_dir: &Path, thumb_dir: &Path, limiter: &Limiter, index_size: usize) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP pic_url_http_requests_total Total HTTP requests by route, method and status.\n");
+        out.push_str("# TYPE pic_url_http_requests_total counter\n");
+        if let Ok(requests) = self.requests.lock() {
+            for ((route, method, status), count) in requests.iter() {
+                let _ = writeln!(
+                    out,
+                    "pic_url_http_requests_total{{route=\"{}\",method=\"{}\",status=\"{}\"}} {}",
+                    escape_label(route),
+                    method,
+                    status,
+                    count
+                );
+            }
+        }
+
+        out.push_str("# HELP pic_url_http_request_duration_seconds HTTP request latency by route.\n");
+        out.push_str("# TYPE pic_url_http_request_duration_seconds histogram\n");
+        if let Ok(latency) = self.latency.lock() {
+            for (route, hist) in latency.iter() {
+                let labels = format!("route=\"{}\"", escape_label(route));
+                hist.render(&mut out, "pic_url_http_request_duration_seconds", &labels);
+            }
+        }
+
+        out.push_str("# HELP pic_url_thumbnail_cache_requests_total Thumbnail cache lookups by result.\n");
+        out.push_str("# TYPE pic_url_thumbnail_cache_requests_total counter\n");
+        let _ = writeln!(
+            out,
+            "pic_url_thumbnail_cache_requests_total{{result=\"hit\"}} {}",
+            self.thumb_hits.load(Ordering::Relaxed)
+        );
+        let _ = writeln!(
+            out,
+            "pic_url_thumbnail_cache_requests_total{{result=\"miss\"}} {}",
+            self.thumb_misses.load(Ordering::Relaxed)
+        );
+
+        out.push_str("# HELP pic_url_thumbnail_generation_seconds Time spent generating thumbnails.\n");
+        out.push_str("# TYPE pic_url_thumbnail_generation_seconds histogram\n");
+        if let Ok(hist) = self.thumb_generation.lock() {
+            hist.render(&mut out, "pic_url_thumbnail_generation_seconds", "");
+        }
+        out.push_str("# HELP pic_url_thumbnail_failures_total Thumbnails that could not be generated.\n");
+        out.push_str("# TYPE pic_url_thumbnail_failures_total counter\n");
+        let _ = writeln!(out, "pic_url_thumbnail_failures_total {}", self.thumb_failures.load(Ordering::Relaxed));
+
+        out.push_str("# HELP pic_url_expensive_in_flight Thumbnail generations, proxy resizes and ZIP downloads running now.\n");
+        out.push_str("# TYPE pic_url_expensive_in_flight gauge\n");
+        let _ = writeln!(out, "pic_url_expensive_in_flight {}", limiter.active());
+        out.push_str("# HELP pic_url_expensive_limit Maximum concurrent thumbnail generations, proxy resizes and ZIP downloads.\n");
+        out.push_str("# TYPE pic_url_expensive_limit gauge\n");
+        let _ = writeln!(out, "pic_url_expensive_limit {}", limiter.max());
+        out.push_str("# HELP pic_url_busy_rejections_total Requests rejected with 503 because the limit was reached.\n");
+        out.push_str("# TYPE pic_url_busy_rejections_total counter\n");
+        let _ = writeln!(out, "pic_url_busy_rejections_total {}", limiter.rejected());
+
+        out.push_str("# HELP pic_url_index_images Number of images found by the last scan.\n");
+        out.push_str("# TYPE pic_url_index_images gauge\n");
+        let _ = writeln!(out, "pic_url_index_images {}", index_size);
+
+        out.push_str("# HELP pic_url_disk_usage_bytes Disk usage of the picture and thumbnail directories.\n");
+        out.push_str("# TYPE pic_url_disk_usage_bytes gauge\n");
+        let _ = writeln!(
+            out,
+            "pic_url_disk_usage_bytes{{dir=\"pic_dir\"}} {}",
+            dir_size(pic_dir, Some(thumb_dir))
+        );
+        let _ = writeln!(
+            out,
+            "pic_url_disk_usage_bytes{{dir=\"thumbnails\"}} {}",
+            dir_size(thumb_dir, None)
+        );
+
+        out
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// 递归统计目录下文件的总字节数，`skip` 指定的子目录不计入
+fn dir_size(dir: &Path, skip: Option<&Path>) -> u64 {
+    let mut total = 0;
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if skip.map(|s| s == path).unwrap_or(false) {
+                continue;
+            }
+            match entry.metadata() {
+                Ok(meta) if meta.is_dir() => total += dir_size(&path, skip),
+                Ok(meta) => total += meta.len(),
+                Err(_) => {}
+            }
+        }
+    }
+    total
+}
@@ -0,0 +1,64 @@
+//! 缩略图的原图不存在时返回的占位图 (`--missing-thumb`)：列表加载之后原图被删除或移走，
+//! 请求缩略图时仍返回 404，但响应体是一张图片，画廊中显示占位图而不是浏览器的“图片损坏”图标。
+//! 默认使用内置的占位图，也可以指定图片文件，`off` 时与其他 404 一样返回文本
+
+use crate::{sniff, AppConfig};
+use actix_web::http::header;
+use actix_web::{web, HttpResponse, Result};
+use std::fs;
+use std::path::Path;
+
+/// 使用内置占位图
+pub const BUILTIN: &str = "placeholder";
+/// 不返回占位图
+pub const OFF: &str = "off";
+
+const PLACEHOLDER: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="200" viewBox="0 0 200 200">
+<rect width="200" height="200" fill="#eee"/>
+<path d="M55 60h90v56l-16-14-18 20-14-12-24 22H55z" fill="none" stroke="#bbb" stroke-width="6" stroke-linejoin="round"/>
+<text x="100" y="180" font-family="sans-serif" font-size="16" fill="#999" text-anchor="middle">image not found</text>
+</svg>
+"##;
+
+/// 配置的值是否可用：`placeholder`、`off`，或已存在的图片文件（含 SVG）
+pub fn is_valid(value: &str) -> bool {
+    if value == BUILTIN || value == OFF {
+        return true;
+    }
+    let path = Path::new(value);
+    path.is_file() && (is_svg(value) || sniff::file_mime(path).is_ok_and(|mime| mime.is_some()))
+}
+
+fn is_svg(file: &str) -> bool {
+    file.to_ascii_lowercase().ends_with(".svg")
+}
+
+/// 缩略图路由的 404 响应；不缓存，原图恢复后浏览器能立即拿到缩略图
+pub async fn response(config: &AppConfig) -> Result<HttpResponse> {
+    let mut response = HttpResponse::NotFound();
+    response
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .insert_header(("X-Thumbnail-Status", "missing"));
+    Ok(match config.settings.missing_thumb.as_str() {
+        OFF => HttpResponse::NotFound().body("Image not found"),
+        BUILTIN => response.content_type("image/svg+xml").body(PLACEHOLDER),
+        // 每次请求时读取，替换文件后不需要重启；读取失败时使用内置的占位图
+        file => {
+            let path = file.to_string();
+            match web::block(move || fs::read(path)).await? {
+                Ok(data) => {
+                    let content_type = if is_svg(file) {
+                        "image/svg+xml"
+                    } else {
+                        sniff::image_mime(&data).unwrap_or(sniff::UNKNOWN)
+                    };
+                    response.content_type(content_type).body(data)
+                }
+                Err(e) => {
+                    log::warn!("读取占位图 {} 失败: {}", file, e);
+                    response.content_type("image/svg+xml").body(PLACEHOLDER)
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,90 @@
+//! 文件名与索引中相对路径之间的可逆转换。
+//!
+//! 相对路径以 `/` 分隔的字符串保存和传输；合法 UTF-8 的文件名原样使用（`%`、`?`、`#` 等字符
+//! 由 URL 编码处理）。无法用 UTF-8 表示的文件名加上 `~x~` 前缀，其中的非法字节写成 `~` 加两位小写
+//! 十六进制，`~` 本身写成 `~7e`，例如 `bad\xff.png` → `~x~bad~ff.png`，扩展名保持不变。
+//! 本身以 `~x~` 开头的文件名同样转义，保证不同的文件名不会得到相同的路径
+
+use std::ffi::{OsStr, OsString};
+use std::path::{Component, Path, PathBuf};
+
+const PREFIX: &str = "~x~";
+
+fn escape(bytes: &[u8]) -> String {
+    let mut out = String::from(PREFIX);
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '~' => out.push_str("~7e"),
+                _ => out.push(c),
+            }
+        }
+        for b in chunk.invalid() {
+            out.push_str(&format!("~{:02x}", b));
+        }
+    }
+    out
+}
+
+/// 带前缀的路径段还原出的原始字节。只接受 [`encode`] 的规范输出（`~x~a.png` 不是 `a.png` 的别名），
+/// 且结果必须是单个文件名（非空、不是 `.`/`..`、不含 `/` 和 NUL），避免借转义跳出图片目录
+fn unescape(segment: &str) -> Option<Vec<u8>> {
+    let body = segment.strip_prefix(PREFIX)?;
+    let mut bytes = Vec::with_capacity(body.len());
+    let mut rest = body.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'~' {
+            bytes.extend(hex::decode(tail.get(..2)?).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    let valid = !matches!(bytes.as_slice(), b"" | b"." | b"..") && !bytes.iter().any(|&b| b == b'/' || b == 0);
+    let escaped = std::str::from_utf8(&bytes).map_or(true, |name| name.starts_with(PREFIX));
+    (valid && escaped && escape(&bytes) == segment).then_some(bytes)
+}
+
+/// 单个文件名对应的路径段
+pub fn encode(name: &OsStr) -> String {
+    match name.to_str() {
+        Some(name) if !name.starts_with(PREFIX) => name.to_string(),
+        _ => escape(name.as_encoded_bytes()),
+    }
+}
+
+/// 路径段对应的文件名，与 [`encode`] 互逆
+pub fn decode(segment: &str) -> OsString {
+    match unescape(segment) {
+        #[cfg(unix)]
+        Some(bytes) => std::os::unix::ffi::OsStringExt::from_vec(bytes),
+        // 其他平台只能还原出合法 UTF-8 的文件名
+        #[cfg(not(unix))]
+        Some(bytes) => match String::from_utf8(bytes) {
+            Ok(name) => OsString::from(name),
+            Err(_) => OsString::from(segment),
+        },
+        None => OsString::from(segment),
+    }
+}
+
+/// 相对于图片目录的路径转为索引中使用的字符串
+pub fn relative(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(encode(name)),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// 索引中的相对路径在 `base` 下对应的文件
+pub fn fs_path(base: impl AsRef<Path>, relative: &str) -> PathBuf {
+    let mut path = base.as_ref().to_path_buf();
+    for segment in relative.split('/').filter(|segment| !segment.is_empty()) {
+        path.push(decode(segment));
+    }
+    path
+}
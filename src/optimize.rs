@@ -0,0 +1,394 @@
+//! 优化已有图片以节省空间（需手动触发）：`pic_url optimize` 命令或 `POST /api/admin/optimize`。
+//!
+//! - JPEG：去掉 EXIF 中嵌入的缩略图和注释段，图像数据不变
+//! - PNG：用最高级别重新压缩图像数据，像素和其他数据块不变
+//! - 指定 `webp` 时，PNG 和 BMP 转换为无损 WebP，原图保存为新文件的历史版本
+//!
+//! 只在结果更小时替换，并保留原来的修改时间，时间线和按时间排序不受影响
+
+use crate::admin::AdminAuth;
+use crate::aliases::Aliases;
+use crate::errors::ApiError;
+use crate::maintenance::Writable;
+use crate::tags::Tags;
+use crate::{format_time, jpeg, png, thumbs, versions, AppConfig};
+use actix_web::http::StatusCode;
+use actix_web::{get, post, rt, web, HttpResponse, Result};
+use image::codecs::webp::WebPEncoder;
+use image::{ColorType, ImageReader};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// 报告中最多保留的失败记录数
+const MAX_FAILURES: usize = 100;
+
+#[derive(Deserialize, Default, Clone, Copy)]
+pub struct Options {
+    /// 把 PNG 和 BMP 转换为无损 WebP
+    #[serde(default)]
+    pub webp: bool,
+    /// 只统计能节省的空间，不修改文件
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Clone)]
+struct Failure {
+    path: String,
+    error: String,
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct OptimizeReport {
+    started_at: String,
+    finished_at: Option<String>,
+    webp: bool,
+    dry_run: bool,
+    total: usize,
+    processed: usize,
+    /// 变小的图片数，包括转换为 WebP 的
+    optimized: usize,
+    converted: usize,
+    failed: usize,
+    bytes_before: u64,
+    bytes_saved: u64,
+    /// 正在处理的图片
+    current: Option<String>,
+    failures: Vec<Failure>,
+}
+
+/// 一张图片的处理结果
+struct Outcome {
+    /// 处理后的路径，转换为 WebP 时与原路径不同
+    path: String,
+    before: u64,
+    after: u64,
+}
+
+impl OptimizeReport {
+    fn new(options: Options, total: usize) -> Self {
+        Self {
+            started_at: format_time(SystemTime::now()),
+            webp: options.webp,
+            dry_run: options.dry_run,
+            total,
+            ..Default::default()
+        }
+    }
+
+    fn record(&mut self, relative: &str, result: &io::Result<Outcome>) {
+        self.processed += 1;
+        match result {
+            Ok(outcome) => {
+                self.bytes_before += outcome.before;
+                if outcome.after < outcome.before {
+                    self.optimized += 1;
+                    self.bytes_saved += outcome.before - outcome.after;
+                }
+                if outcome.path != relative {
+                    self.converted += 1;
+                }
+            }
+            Err(e) => {
+                self.failed += 1;
+                if self.failures.len() < MAX_FAILURES {
+                    self.failures.push(Failure {
+                        path: relative.to_string(),
+                        error: e.to_string(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// 优化用到的目录和标签，服务和命令行共用
+struct Library<'a> {
+    pic_dir: &'a str,
+    /// 图片目录中的别名，命令行不知道服务的设置，没有别名
+    aliases: &'a Aliases,
+    /// 要删除旧缩略图的目录：服务只有当前尺寸一个，命令行不知道服务的设置，为全部尺寸
+    thumb_dirs: &'a [String],
+    tags: &'a Tags,
+}
+
+impl Library<'_> {
+    fn fs_path(&self, relative: &str) -> PathBuf {
+        self.aliases.fs_path(Path::new(self.pic_dir), relative)
+    }
+}
+
+fn extension(relative: &str) -> String {
+    relative.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()).unwrap_or_default()
+}
+
+/// 先写入同目录下的临时文件再改名，并设置为指定的修改时间
+fn write_file(path: &Path, data: &[u8], modified: SystemTime) -> io::Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let tmp = path.with_file_name(format!(".{}.optimize.tmp", name));
+    let result = fs::write(&tmp, data)
+        .and_then(|_| File::options().write(true).open(&tmp)?.set_modified(modified))
+        .and_then(|_| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// 无损 WebP 编码；16 位等 WebP 无法无损表示的颜色返回 `None`
+fn to_webp(data: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    let img = ImageReader::new(io::Cursor::new(data))
+        .with_guessed_format()?
+        .decode()
+        .map_err(io::Error::other)?;
+    if !matches!(img.color(), ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8) {
+        return Ok(None);
+    }
+    let mut encoded = Vec::new();
+    img.write_with_encoder(WebPEncoder::new_lossless(&mut encoded)).map_err(io::Error::other)?;
+    Ok(Some(encoded))
+}
+
+/// 转换为同名的 `.webp`：原图保存为新文件的历史版本后删除，标签随之移动
+fn convert(library: &Library, relative: &str, target: &str, webp: &[u8], modified: SystemTime) -> io::Result<()> {
+    let original = library.fs_path(relative);
+    write_file(&library.fs_path(target), webp, modified)?;
+    if let Err(e) = versions::store(library.pic_dir, target, &original) {
+        let _ = fs::remove_file(library.fs_path(target));
+        return Err(e);
+    }
+    fs::remove_file(&original)?;
+    for thumb_dir in library.thumb_dirs {
+        thumbs::remove(thumb_dir, relative);
+    }
+    let _ = library.tags.rename(relative, target);
+    Ok(())
+}
+
+fn optimize_image(library: &Library, relative: &str, options: Options) -> io::Result<Outcome> {
+    let path = library.fs_path(relative);
+    let data = fs::read(&path)?;
+    let modified = fs::metadata(&path)?.modified()?;
+    let ext = extension(relative);
+    let mut outcome = Outcome {
+        path: relative.to_string(),
+        before: data.len() as u64,
+        after: data.len() as u64,
+    };
+
+    if options.webp && (ext == "png" || ext == "bmp") {
+        let target = format!("{}.webp", relative.rsplit_once('.').map_or(relative, |(stem, _)| stem));
+        // 已有同名的 WebP 时只做无损压缩
+        if !library.fs_path(&target).exists() {
+            if let Some(webp) = to_webp(&data)?.filter(|webp| webp.len() < data.len()) {
+                if !options.dry_run {
+                    convert(library, relative, &target, &webp, modified)?;
+                }
+                outcome.after = webp.len() as u64;
+                outcome.path = target;
+                return Ok(outcome);
+            }
+        }
+    }
+
+    let optimized = match ext.as_str() {
+        "jpg" | "jpeg" => jpeg::strip(&data),
+        "png" => png::recompress(&data),
+        _ => None,
+    };
+    if let Some(optimized) = optimized.filter(|optimized| optimized.len() < data.len()) {
+        if !options.dry_run {
+            write_file(&path, &optimized, modified)?;
+        }
+        outcome.after = optimized.len() as u64;
+    }
+    Ok(outcome)
+}
+
+/// 服务中的优化任务和最近一次的结果
+pub struct Optimizer {
+    running: AtomicBool,
+    report: Mutex<Option<OptimizeReport>>,
+}
+
+#[derive(Serialize)]
+struct OptimizeStatus {
+    running: bool,
+    /// 进行中或最近一次的结果
+    report: Option<OptimizeReport>,
+}
+
+impl Optimizer {
+    pub fn new() -> Self {
+        Self {
+            running: AtomicBool::new(false),
+            report: Mutex::new(None),
+        }
+    }
+
+    fn status(&self) -> OptimizeStatus {
+        OptimizeStatus {
+            running: self.running.load(Ordering::SeqCst),
+            report: self.report.lock().map(|r| r.clone()).unwrap_or_else(|e| e.into_inner().clone()),
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut OptimizeReport)) {
+        if let Ok(mut guard) = self.report.lock() {
+            if let Some(report) = guard.as_mut() {
+                f(report);
+            }
+        }
+    }
+
+    /// 处理全部图片，阻塞直到完成
+    fn run(&self, config: &AppConfig, options: Options) {
+        let images = config.current_images();
+        if let Ok(mut guard) = self.report.lock() {
+            *guard = Some(OptimizeReport::new(options, images.len()));
+        }
+        let library = Library {
+            pic_dir: &config.pic_dir,
+            aliases: &config.aliases,
+            thumb_dirs: std::slice::from_ref(config.thumb_dir.as_ref()),
+            tags: &config.tags,
+        };
+        for relative in &images {
+            self.update(|report| report.current = Some(relative.clone()));
+            let result = optimize_image(&library, relative, options);
+            if let Ok(outcome) = &result {
+                if outcome.after < outcome.before && !options.dry_run {
+                    config.dimensions.forget(relative);
+                    config.captures.forget(relative);
+                }
+            }
+            self.update(|report| report.record(relative, &result));
+        }
+        self.update(|report| {
+            report.current = None;
+            report.finished_at = Some(format_time(SystemTime::now()));
+        });
+        if options.webp && !options.dry_run {
+            // 转换后的图片重新加入索引，推送增删事件
+            config.rescan();
+        }
+        self.running.store(false, Ordering::SeqCst);
+    }
+}
+
+#[get("/api/admin/optimize")]
+pub async fn status(_auth: AdminAuth, config: web::Data<AppConfig>) -> HttpResponse {
+    HttpResponse::Ok().json(config.optimizer.status())
+}
+
+/// 在后台开始优化，立即返回 202；进度通过 `GET /api/admin/optimize` 查询
+#[post("/api/admin/optimize")]
+pub async fn run(
+    _auth: AdminAuth,
+    _writable: Writable,
+    body: Option<web::Json<Options>>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    if config.webdav.is_some() {
+        return Err(ApiError::bad_request("optimizing is not supported for a WebDAV source").into());
+    }
+    let options = body.map(|body| body.into_inner()).unwrap_or_default();
+    let optimizer = config.optimizer.clone();
+    if optimizer.running.swap(true, Ordering::SeqCst) {
+        return Err(ApiError::new(StatusCode::CONFLICT, "optimization already running").into());
+    }
+    let config = config.into_inner();
+    rt::spawn(async move {
+        let task = optimizer.clone();
+        if let Err(e) = web::block(move || task.run(&config, options)).await {
+            log::error!("图片优化任务异常: {}", e);
+            optimizer.running.store(false, Ordering::SeqCst);
+        }
+    });
+    Ok(HttpResponse::Accepted().json(serde_json::json!({ "running": true })))
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// `pic_url optimize [-d 目录] [--webp] [--dry-run]`：优化图片目录中的全部图片后退出。
+/// 转换为 WebP 会修改标签数据，应在服务停止时运行，服务运行时请使用管理接口
+pub fn run_command(args: &[String]) -> ! {
+    let mut pic_dir = std::env::var("PIC_DIR").unwrap_or_else(|_| "./pic".to_string());
+    let mut options = Options::default();
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "-d" | "--dir" if i + 1 < args.len() => {
+                pic_dir = args[i + 1].clone();
+                i += 1;
+            }
+            "--webp" => options.webp = true,
+            "--dry-run" => options.dry_run = true,
+            _ => {
+                eprintln!("用法: pic_url optimize [-d 目录] [--webp] [--dry-run]");
+                std::process::exit(1);
+            }
+        }
+        i += 1;
+    }
+    if !Path::new(&pic_dir).is_dir() {
+        eprintln!("错误: 图片目录 '{}' 不存在", pic_dir);
+        std::process::exit(1);
+    }
+
+    let base = Path::new(&pic_dir);
+    let mut images = Vec::new();
+    if let Err(e) = crate::collect_images(base, base, &mut images) {
+        eprintln!("警告: 跳过无法读取的目录 {}", e);
+    }
+    images.sort();
+    let tags = Tags::load(&format!("{}/{}", pic_dir, crate::DATA_DIR_NAME));
+    let thumb_dirs = thumbs::size_dirs(&format!("{}/{}", pic_dir, crate::THUMB_DIR_NAME));
+    let aliases = Aliases::default();
+    let library = Library {
+        pic_dir: &pic_dir,
+        aliases: &aliases,
+        thumb_dirs: &thumb_dirs,
+        tags: &tags,
+    };
+    let mut report = OptimizeReport::new(options, images.len());
+    for (i, relative) in images.iter().enumerate() {
+        let result = optimize_image(&library, relative, options);
+        match &result {
+            Ok(outcome) if outcome.after < outcome.before => println!(
+                "[{}/{}] {}: {} -> {}{}",
+                i + 1,
+                images.len(),
+                relative,
+                format_bytes(outcome.before),
+                format_bytes(outcome.after),
+                if outcome.path != *relative { format!(" ({})", outcome.path) } else { String::new() }
+            ),
+            Ok(_) => {}
+            Err(e) => eprintln!("[{}/{}] {}: 失败: {}", i + 1, images.len(), relative, e),
+        }
+        report.record(relative, &result);
+    }
+    println!(
+        "{}: 共 {} 张，优化 {} 张 (转换为 WebP {} 张)，{}节省 {}，失败 {} 张",
+        if options.dry_run { "预计" } else { "完成" },
+        report.total,
+        report.optimized,
+        report.converted,
+        if options.dry_run { "可" } else { "" },
+        format_bytes(report.bytes_saved),
+        report.failed
+    );
+    std::process::exit(if report.failed > 0 { 1 } else { 0 });
+}
@@ -0,0 +1,30 @@
+//! 请求体大小上限，上传和接口分开设置：`--max-upload-mb` 限制上传 (`/api/upload`、WebDAV `PUT`)
+//! 的单个文件，`--max-body-kb` 限制其他接口的 JSON 和表单。放开大文件上传不会同时放开接口的请求体，
+//! 超出时返回 413
+
+use crate::AppConfig;
+use actix_web::web;
+
+/// 单个上传文件的默认上限 (MB)
+pub const DEFAULT_UPLOAD_MB: u64 = 100;
+/// JSON 和表单请求体的默认上限 (KB)
+pub const DEFAULT_BODY_KB: u64 = 1024;
+
+/// 单个上传文件的上限（字节）
+pub fn upload_limit(config: &AppConfig) -> u64 {
+    config.settings.max_upload_mb.saturating_mul(1024 * 1024)
+}
+
+fn body_limit(config: &AppConfig) -> usize {
+    usize::try_from(config.settings.max_body_kb.saturating_mul(1024)).unwrap_or(usize::MAX)
+}
+
+/// `web::Json` 的请求体上限
+pub fn json(config: &AppConfig) -> web::JsonConfig {
+    web::JsonConfig::default().limit(body_limit(config))
+}
+
+/// `web::Form` 的请求体上限；打包下载的路径列表另有固定的上限
+pub fn form(config: &AppConfig) -> web::FormConfig {
+    web::FormConfig::default().limit(body_limit(config))
+}
@@ -0,0 +1,68 @@
+//! PNG 的无损压缩：用最高级别重新压缩图像数据（IDAT），
+//! 扫描行和滤波方式不变，其他数据块（调色板、色彩配置、文字说明等）原样保留
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::{Compression, Crc};
+use std::io::{Read, Write};
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+fn chunk(kind: &[u8], body: &[u8]) -> Vec<u8> {
+    let mut crc = Crc::new();
+    crc.update(kind);
+    crc.update(body);
+    let mut out = Vec::with_capacity(body.len() + 12);
+    out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(body);
+    out.extend_from_slice(&crc.sum().to_be_bytes());
+    out
+}
+
+/// 重新压缩后的文件内容，合并为一个 IDAT 块；文件结构无法识别时返回 `None`。
+/// 结果不一定更小，由调用方比较
+pub fn recompress(data: &[u8]) -> Option<Vec<u8>> {
+    if !data.starts_with(SIGNATURE) {
+        return None;
+    }
+    // 第一个 IDAT 块的起点和最后一个的终点，IDAT 必须连续
+    let mut idat: Option<(usize, usize)> = None;
+    let mut compressed = Vec::new();
+    let mut pos = SIGNATURE.len();
+    loop {
+        let len = u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?) as usize;
+        let kind = data.get(pos + 4..pos + 8)?;
+        let end = pos.checked_add(12)?.checked_add(len)?;
+        let body = data.get(pos + 8..end - 4)?;
+        if kind == b"IDAT" {
+            match &mut idat {
+                None => idat = Some((pos, end)),
+                Some((_, last)) if *last == pos => *last = end,
+                Some(_) => return None,
+            }
+            compressed.extend_from_slice(body);
+        }
+        pos = end;
+        if kind == b"IEND" {
+            break;
+        }
+    }
+    let (start, end) = idat?;
+
+    let mut raw = Vec::new();
+    ZlibDecoder::new(compressed.as_slice()).read_to_end(&mut raw).ok()?;
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(&raw).ok()?;
+    let recompressed = encoder.finish().ok()?;
+    if recompressed.len() > i32::MAX as usize {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(&data[..start]);
+    out.extend_from_slice(&chunk(b"IDAT", &recompressed));
+    // 其余数据块和 IEND 之后的附加数据原样保留
+    out.extend_from_slice(&data[end..]);
+    Some(out)
+}
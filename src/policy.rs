@@ -0,0 +1,111 @@
+//! 按角色限制操作：[API 密钥](crate::api_keys)的权限范围 (`scope`) 就是它的角色。上传者只能添加新图片，
+//! 编辑者可以修改图片但不能删除或移动，删除、移动、清除缩略图等破坏性操作只有管理员 (`full`) 可以执行。
+//! 管理令牌和用它登录的会话总是管理员。
+//!
+//! 写操作路由需要的角色在 [`POLICIES`] 中声明，没有声明的写操作只对管理员开放，只读请求只需要 `read`；
+//! 中间件 [`enforce`] 在处理请求之前统一检查，角色不够时返回 403 和 JSON 错误。
+//! 一个路由中不同操作需要不同角色时（如批量操作中的删除），由处理函数再用 [`require`] 检查
+
+use crate::admin::{self, AdminAuth, AdminAuthError, Identity};
+use crate::api_keys::{self, Scope};
+use crate::dav::DAV_PREFIX;
+use crate::{mount_path, AppConfig};
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, ResponseError};
+use std::path::Path;
+
+/// 写操作路由（方法、路由模式）需要的角色
+pub const POLICIES: &[(&str, &str, Scope)] = &[
+    ("POST", "/login", Scope::Read),
+    ("POST", "/logout", Scope::Read),
+    ("POST", "/api/zip", Scope::Read),
+    // 只有查询
+    ("POST", "/graphql", Scope::Read),
+    ("POST", "/api/upload", Scope::Upload),
+    ("POST", "/api/images/{path:.*}/rotate", Scope::Edit),
+    ("POST", "/api/images/{path:.*}/edit", Scope::Edit),
+    ("POST", "/api/images/{path:.*}/versions/{id}/restore", Scope::Edit),
+    ("PUT", "/api/admin/albums/{name}", Scope::Edit),
+    ("DELETE", "/api/admin/albums/{name}", Scope::Edit),
+    // 删除和移动由处理函数要求 `full`
+    ("POST", "/api/admin/batch", Scope::Edit),
+];
+
+fn is_read_only(method: &Method) -> bool {
+    matches!(method.as_str(), "GET" | "HEAD" | "OPTIONS" | "PROPFIND")
+}
+
+/// WebDAV 请求需要的角色：新建文件和目录算上传，覆盖、删除、移动等需要管理员
+fn dav_policy(req: &ServiceRequest, mount: &str, config: &AppConfig) -> Scope {
+    let method = req.method();
+    if is_read_only(method) {
+        return Scope::Read;
+    }
+    match method.as_str() {
+        "MKCOL" => Scope::Upload,
+        "PUT" => {
+            let pic_dir = Path::new(config.pic_dir.as_str());
+            let target = api_keys::dav_relative(mount, req.path());
+            if target.is_some_and(|target| !pic_dir.join(target).exists()) {
+                Scope::Upload
+            } else {
+                Scope::Full
+            }
+        }
+        _ => Scope::Full,
+    }
+}
+
+/// 请求需要的角色
+fn required(req: &ServiceRequest, config: &AppConfig) -> Scope {
+    let mount = mount_path(req.request());
+    let path = req.path().strip_prefix(mount.as_str()).unwrap_or(req.path());
+    if path == DAV_PREFIX || path.starts_with(&format!("{}/", DAV_PREFIX)) {
+        return dav_policy(req, &mount, config);
+    }
+    let method = req.method();
+    let pattern = req.match_pattern();
+    let pattern = pattern.as_deref().map(|pattern| pattern.strip_prefix(mount.as_str()).unwrap_or(pattern));
+    POLICIES
+        .iter()
+        .find(|(m, p, _)| *m == method.as_str() && Some(*p) == pattern)
+        .map(|(_, _, scope)| *scope)
+        .unwrap_or(if is_read_only(method) { Scope::Read } else { Scope::Full })
+}
+
+fn forbidden(required: Scope) -> AdminAuthError {
+    AdminAuthError::Forbidden(match required {
+        Scope::Read => "this operation requires the read role",
+        Scope::Upload => "this operation requires the upload role",
+        Scope::Edit => "this operation requires the edit role",
+        Scope::Full => "this operation requires the full role",
+    })
+}
+
+/// 处理函数中再检查一次角色，用于同一个路由中需要更高角色的操作
+pub fn require(auth: &AdminAuth, scope: Scope) -> Result<(), AdminAuthError> {
+    match &auth.key {
+        Some(key) if key.scope < scope => Err(forbidden(scope)),
+        _ => Ok(()),
+    }
+}
+
+/// 中间件：API 密钥的角色低于路由需要的角色时返回 403
+pub async fn enforce(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    if let Some(config) = req.app_data::<web::Data<AppConfig>>() {
+        if let Identity::Key(key) = admin::identify(config, req.request()) {
+            let required = required(&req, config);
+            if key.scope < required {
+                let res = forbidden(required).error_response();
+                return Ok(req.into_response(res).map_into_right_body());
+            }
+        }
+    }
+    next.call(req).await.map(ServiceResponse::map_into_left_body)
+}
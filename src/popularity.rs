@@ -0,0 +1,165 @@
+//! 最近的浏览热度：按图片记录访问原图和缩略图的次数，随时间衰减（半衰期 7 天）。
+//! 后台预生成缩略图时先处理新加入的图片，再按所在目录和图片本身的热度从高到低处理，
+//! 大型图库中常看的目录先生成好，冷门的归档放到最后。定期保存在数据目录的 `views.json` 中
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 热度减半的时间
+const HALF_LIFE_SECS: f64 = 7.0 * 24.0 * 3600.0;
+/// 衰减到这个值以下的记录在保存时去掉
+const MIN_SCORE: f64 = 0.01;
+/// 有新的访问时保存的间隔
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(300);
+
+/// `at` 时的热度，单位为秒的 Unix 时间
+#[derive(Serialize, Deserialize, Clone, Copy)]
+struct Score {
+    score: f64,
+    at: u64,
+}
+
+impl Score {
+    fn decayed(self, now: u64) -> f64 {
+        self.score * 0.5f64.powf(now.saturating_sub(self.at) as f64 / HALF_LIFE_SECS)
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn folder_of(relative: &str) -> &str {
+    relative.rsplit_once('/').map_or("", |(folder, _)| folder)
+}
+
+pub struct Popularity {
+    path: PathBuf,
+    scores: Mutex<HashMap<String, Score>>,
+    /// 上次预生成之后新加入的图片
+    added: Mutex<HashSet<String>>,
+    dirty: AtomicBool,
+}
+
+impl Popularity {
+    /// 从 `<data_dir>/views.json` 加载
+    pub fn load(data_dir: &str) -> Self {
+        let path = Path::new(data_dir).join("views.json");
+        let scores = fs::read(&path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            scores: Mutex::new(scores),
+            added: Mutex::new(HashSet::new()),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    /// 记录一次访问
+    pub fn view(&self, relative: &str) {
+        let now = now_secs();
+        if let Ok(mut scores) = self.scores.lock() {
+            let score = scores.get(relative).map_or(0.0, |s| s.decayed(now)) + 1.0;
+            scores.insert(relative.to_string(), Score { score, at: now });
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// 图片删除或移走后清除记录
+    pub fn forget(&self, relative: &str) {
+        if let Ok(mut scores) = self.scores.lock() {
+            if scores.remove(relative).is_some() {
+                self.dirty.store(true, Ordering::Relaxed);
+            }
+        }
+        if let Ok(mut added) = self.added.lock() {
+            added.remove(relative);
+        }
+    }
+
+    pub fn added(&self, relative: &str) {
+        if let Ok(mut added) = self.added.lock() {
+            added.insert(relative.to_string());
+        }
+    }
+
+    /// 是否有还没排进预生成顺序的新图片
+    pub fn has_added(&self) -> bool {
+        self.added.lock().is_ok_and(|added| !added.is_empty())
+    }
+
+    /// 把待预生成的图片排成处理顺序：新加入的在前，其次按目录热度、图片热度从高到低，
+    /// 相同时保持原来的顺序。新加入的记录随之清空
+    pub fn prioritize(&self, images: &mut [String]) {
+        let now = now_secs();
+        let added = self.added.lock().map(|mut added| std::mem::take(&mut *added)).unwrap_or_default();
+        let scores: HashMap<String, f64> = self
+            .scores
+            .lock()
+            .map(|scores| scores.iter().map(|(path, score)| (path.clone(), score.decayed(now))).collect())
+            .unwrap_or_default();
+        let mut folders: HashMap<&str, f64> = HashMap::new();
+        for (path, score) in &scores {
+            *folders.entry(folder_of(path)).or_default() += score;
+        }
+        let key = |relative: &String| {
+            (
+                added.contains(relative),
+                folders.get(folder_of(relative)).copied().unwrap_or(0.0),
+                scores.get(relative).copied().unwrap_or(0.0),
+            )
+        };
+        images.sort_by(|a, b| {
+            let (a, b) = (key(a), key(b));
+            b.0.cmp(&a.0).then(b.1.total_cmp(&a.1)).then(b.2.total_cmp(&a.2))
+        });
+    }
+
+    /// 有新的访问时保存，去掉已经衰减到可以忽略的记录
+    pub fn save(&self) -> io::Result<()> {
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+        let now = now_secs();
+        let data = {
+            let mut scores = self.scores.lock().unwrap_or_else(|e| e.into_inner());
+            scores.retain(|_, score| score.decayed(now) >= MIN_SCORE);
+            serde_json::to_vec(&*scores).map_err(io::Error::other)?
+        };
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_images_first_then_popular_folders() {
+        let popularity = Popularity::load("/nonexistent");
+        for relative in ["旅行/a.jpg", "旅行/b.jpg", "旅行/b.jpg", "家/c.jpg"] {
+            popularity.view(relative);
+        }
+        popularity.added("归档/new.jpg");
+        let mut images: Vec<String> = ["归档/old.jpg", "家/d.jpg", "旅行/a.jpg", "旅行/b.jpg", "归档/new.jpg"]
+            .map(String::from)
+            .to_vec();
+        popularity.prioritize(&mut images);
+        assert_eq!(images, ["归档/new.jpg", "旅行/b.jpg", "旅行/a.jpg", "家/d.jpg", "归档/old.jpg"]);
+        // 新加入的记录只用一次
+        assert!(!popularity.has_added());
+    }
+}
@@ -0,0 +1,118 @@
+//! 每日一图：按日期确定地选出一张图片，同一天内每次访问得到同一张。
+//! `/potd` 跳转到图片原图，`/api/potd` 返回图片信息，适合浏览器起始页和状态屏
+
+use crate::errors::ApiError;
+use crate::homes::Viewer;
+use crate::listing::{self, ImageListQuery};
+use crate::{base_url, url_path, AppConfig, ImageInfo};
+use actix_web::http::header;
+use actix_web::{get, web, HttpRequest, HttpResponse, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+
+#[derive(Deserialize)]
+pub struct PotdQuery {
+    /// 指定日期 `YYYY-MM-DD`，默认为今天（UTC）
+    date: Option<String>,
+    /// `1` 跳转到缩略图
+    thumb: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Potd {
+    date: String,
+    image: ImageInfo,
+    url: String,
+    thumb_url: String,
+}
+
+fn date_of(query: &PotdQuery) -> Result<String, ApiError> {
+    match &query.date {
+        Some(date) if listing::is_date(date) => Ok(date.clone()),
+        Some(_) => Err(ApiError::bad_request("date must be YYYY-MM-DD")),
+        None => {
+            let today = OffsetDateTime::now_utc().date();
+            Ok(format!("{:04}-{:02}-{:02}", today.year(), u8::from(today.month()), today.day()))
+        }
+    }
+}
+
+/// 当天的图片：符合筛选条件的图片按路径排序，以日期的哈希取其中一张。
+/// 图库有增减时当天的选择可能变化，但不依赖扫描顺序
+fn pick(config: &AppConfig, filter: &ImageListQuery, date: &str) -> Option<String> {
+    // 每个人看到的都是同一张，只从公共目录中选
+    let mut images = filter.apply(config, Viewer::public(config).filter(config.current_images()));
+    if images.is_empty() {
+        return None;
+    }
+    images.sort();
+    let hash = Sha256::digest(format!("potd:{}", date).as_bytes());
+    let seed = u64::from_be_bytes(hash[..8].try_into().expect("SHA-256 至少 8 字节"));
+    Some(images.swap_remove((seed % images.len() as u64) as usize))
+}
+
+async fn pick_for(
+    filter: web::Query<ImageListQuery>,
+    query: &PotdQuery,
+    config: web::Data<AppConfig>,
+) -> Result<(String, Option<String>)> {
+    filter.validate(&config)?;
+    let date = date_of(query)?;
+    let filter = filter.into_inner();
+    let config = config.into_inner();
+    let picked = {
+        let date = date.clone();
+        web::block(move || pick(&config, &filter, &date)).await?
+    };
+    Ok((date, picked))
+}
+
+/// 跳转到当天的图片，支持与 `/api/images` 相同的筛选参数，如 `/potd?folder=风景`
+#[get("/potd")]
+pub async fn potd(
+    filter: web::Query<ImageListQuery>,
+    query: web::Query<PotdQuery>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let (_, picked) = pick_for(filter, &query, config.clone()).await?;
+    let Some(path) = picked else {
+        return Ok(HttpResponse::NotFound().body("Image not found"));
+    };
+    let target = if query.thumb.as_deref() == Some("1") {
+        format!("/thumb/{}", url_path(&path))
+    } else {
+        config.pic_url(&path)
+    };
+    // 跳转不缓存，第二天访问同一地址时得到新的图片
+    Ok(HttpResponse::Found()
+        .insert_header((header::LOCATION, format!("{}{}", config.settings.mount_path, target)))
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .finish())
+}
+
+#[get("/api/potd")]
+pub async fn api_potd(
+    req: HttpRequest,
+    filter: web::Query<ImageListQuery>,
+    query: web::Query<PotdQuery>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let (date, picked) = pick_for(filter, &query, config.clone()).await?;
+    let Some(path) = picked else {
+        return Err(ApiError::not_found("no images match the filter").into());
+    };
+    let base = base_url(&req);
+    let encoded = url_path(&path);
+    let url = format!("{}{}", base, config.pic_url(&path));
+    let thumb_url = format!("{}/thumb/{}", base, encoded);
+    let image = web::block(move || ImageInfo::new(&config, &path)).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .json(Potd {
+            date,
+            image,
+            url,
+            thumb_url,
+        }))
+}
@@ -0,0 +1,236 @@
+//! 远程图片代理：`/proxy?url=...&w=...&sig=...`
+//!
+//! 从白名单域名下载图片，按需缩放后缓存在 `.thumbnails/.proxy` 下，
+//! 相当于给博客等外部站点用的私有图片代理/缩放服务。
+
+use crate::{limit, resize_image, AppConfig, METRICS};
+use actix_web::{get, web, HttpResponse, Result};
+use hmac::{Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{self, Cursor};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// 远程图片大小上限
+const MAX_REMOTE_SIZE: u64 = 50 * 1024 * 1024;
+/// 允许请求的最大缩放尺寸
+const MAX_WIDTH: u32 = 4096;
+
+#[derive(Deserialize)]
+pub struct ProxyQuery {
+    url: String,
+    w: Option<u32>,
+    sig: Option<String>,
+}
+
+enum ProxyError {
+    BadRequest(&'static str),
+    Forbidden(&'static str),
+    Upstream(String),
+}
+
+impl ProxyError {
+    fn into_response(self) -> HttpResponse {
+        match self {
+            ProxyError::BadRequest(msg) => HttpResponse::BadRequest().body(msg),
+            ProxyError::Forbidden(msg) => HttpResponse::Forbidden().body(msg),
+            ProxyError::Upstream(msg) => {
+                log::warn!("代理请求失败: {}", msg);
+                HttpResponse::BadGateway().body("Failed to fetch remote image")
+            }
+        }
+    }
+}
+
+/// 签名内容为 `"{w}:{url}"`，未指定 `w` 时为 0
+fn mac(secret: &str, url: &str, w: Option<u32>) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("{}:{}", w.unwrap_or(0), url).as_bytes());
+    mac
+}
+
+fn sign(secret: &str, url: &str, w: Option<u32>) -> String {
+    hex::encode(mac(secret, url, w).finalize().into_bytes())
+}
+
+fn verify_signature(secret: &str, url: &str, w: Option<u32>, sig: &str) -> bool {
+    match hex::decode(sig) {
+        Ok(sig) => mac(secret, url, w).verify_slice(&sig).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// 生成带签名的代理路径，供 `pic_url sign` 使用
+pub fn signed_path(secret: &str, url: &str, w: Option<u32>) -> String {
+    let mut path = format!("/proxy?url={}", utf8_percent_encode(url, NON_ALPHANUMERIC));
+    if let Some(w) = w {
+        path.push_str(&format!("&w={}", w));
+    }
+    path.push_str(&format!("&sig={}", sign(secret, url, w)));
+    path
+}
+
+/// 取出 URL 中的主机名（不含端口和用户信息）
+fn host_of(url: &str) -> Option<String> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?;
+    let host = if host.starts_with('[') {
+        host.split(']').next()?.trim_start_matches('[')
+    } else {
+        host.split(':').next()?
+    };
+    if host.is_empty() {
+        None
+    } else {
+        Some(host.to_ascii_lowercase())
+    }
+}
+
+/// 白名单中的域名同时允许其子域名
+fn host_allowed(host: &str, allow: &[String]) -> bool {
+    allow.iter().any(|domain| {
+        let domain = domain.to_ascii_lowercase();
+        host == domain || host.ends_with(&format!(".{}", domain))
+    })
+}
+
+/// 缓存目录下存放代理图片的子目录
+pub const PROXY_DIR: &str = ".proxy";
+
+fn cache_path(cache_dir: &str, url: &str, w: Option<u32>) -> PathBuf {
+    let key = hex::encode(Sha256::digest(url.as_bytes()));
+    let name = w.map(|w| w.to_string()).unwrap_or_else(|| "orig".to_string());
+    PathBuf::from(cache_dir).join(PROXY_DIR).join(&key[..2]).join(key).join(name)
+}
+
+fn fetch(url: &str) -> io::Result<Vec<u8>> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .http_status_as_error(false)
+        // 不跟随跳转，避免绕过域名白名单
+        .max_redirects(0)
+        .timeout_global(Some(Duration::from_secs(30)))
+        .build()
+        .into();
+    let mut response = agent.get(url).call().map_err(io::Error::other)?;
+    let status = response.status().as_u16();
+    if status != 200 {
+        return Err(io::Error::other(format!("GET {} 返回 {}", url, status)));
+    }
+    response
+        .body_mut()
+        .with_config()
+        .limit(MAX_REMOTE_SIZE)
+        .read_to_vec()
+        .map_err(io::Error::other)
+}
+
+/// 返回缓存的（或新生成的）图片数据
+fn load(cache_dir: &str, url: &str, w: Option<u32>) -> std::result::Result<Vec<u8>, ProxyError> {
+    let path = cache_path(cache_dir, url, w);
+    if let Ok(data) = fs::read(&path) {
+        METRICS.thumb_cache_hit();
+        return Ok(data);
+    }
+    METRICS.thumb_cache_miss();
+
+    let original = tracing::info_span!("proxy.fetch", url)
+        .in_scope(|| fetch(url))
+        .map_err(|e| ProxyError::Upstream(e.to_string()))?;
+    let format = image::guess_format(&original)
+        .map_err(|_| ProxyError::Upstream(format!("{} 不是图片", url)))?;
+
+    let data = match w {
+        None => original,
+        Some(w) => {
+            let started = Instant::now();
+            let img = tracing::info_span!("thumbnail.decode")
+                .in_scope(|| image::load_from_memory_with_format(&original, format))
+                .map_err(|e| ProxyError::Upstream(e.to_string()))?;
+            let (width, height) = (img.width(), img.height());
+            // 不放大比目标尺寸小的图片
+            let resized = if width.max(height) > w { resize_image(&img, w) } else { img };
+            let mut out = Cursor::new(Vec::new());
+            tracing::info_span!("thumbnail.encode")
+                .in_scope(|| resized.write_to(&mut out, format))
+                .map_err(|e| ProxyError::Upstream(e.to_string()))?;
+            METRICS.observe_thumb_generation(started.elapsed());
+            out.into_inner()
+        }
+    };
+
+    if let Some(parent) = path.parent() {
+        if let Err(e) = fs::create_dir_all(parent).and_then(|_| fs::write(&path, &data)) {
+            log::warn!("写入代理缓存失败 {:?}: {}", path, e);
+        }
+    }
+    Ok(data)
+}
+
+#[get("/proxy")]
+pub async fn proxy(query: web::Query<ProxyQuery>, config: web::Data<AppConfig>) -> Result<HttpResponse> {
+    let settings = &config.settings;
+    if settings.proxy_allow.is_empty() {
+        return Ok(HttpResponse::NotFound().body("Proxy is disabled"));
+    }
+
+    let ProxyQuery { url, w, sig } = query.into_inner();
+    if let Some(w) = w {
+        if w == 0 || w > MAX_WIDTH {
+            return Ok(ProxyError::BadRequest("w must be between 1 and 4096").into_response());
+        }
+    }
+    let host = match host_of(&url) {
+        Some(host) => host,
+        None => return Ok(ProxyError::BadRequest("url must be an http(s) URL").into_response()),
+    };
+    if !host_allowed(&host, &settings.proxy_allow) {
+        return Ok(ProxyError::Forbidden("Domain not allowed").into_response());
+    }
+    if let Some(secret) = &settings.proxy_secret {
+        let valid = sig
+            .as_deref()
+            .map(|sig| verify_signature(secret, &url, w, sig))
+            .unwrap_or(false);
+        if !valid {
+            return Ok(ProxyError::Forbidden("Invalid signature").into_response());
+        }
+    }
+
+    // 已缓存的图片直接返回，只有需要下载和缩放时才占用并发名额
+    let cached = {
+        let path = cache_path(&config.cache_dir, &url, w);
+        web::block(move || path.is_file()).await?
+    };
+    let permit = if cached {
+        None
+    } else {
+        match config.limiter.try_acquire() {
+            Some(permit) => Some(permit),
+            None => return Ok(limit::busy()),
+        }
+    };
+    let cache_dir = config.cache_dir.clone();
+    let result = web::block(move || {
+        let _permit = permit;
+        load(&cache_dir, &url, w)
+    })
+    .await?;
+    match result {
+        Ok(data) => {
+            let mime = image::guess_format(&data)
+                .map(|f| f.to_mime_type())
+                .unwrap_or("application/octet-stream");
+            Ok(HttpResponse::Ok()
+                .content_type(mime)
+                .insert_header(("Cache-Control", "public, max-age=86400"))
+                .body(data))
+        }
+        Err(e) => Ok(e.into_response()),
+    }
+}
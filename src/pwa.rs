@@ -0,0 +1,63 @@
+//! 把画廊安装到手机主屏幕（PWA）：`/manifest.webmanifest` 描述应用，
+//! `/sw.js` 缓存页面框架和最近浏览的缩略图，离线时也能打开
+
+use crate::{assets, theme, AppConfig};
+use actix_web::http::header;
+use actix_web::{get, web, HttpResponse};
+use sha2::{Digest, Sha256};
+
+#[get("/manifest.webmanifest")]
+pub async fn manifest(config: web::Data<AppConfig>) -> HttpResponse {
+    let base = &config.settings.mount_path;
+    let urls = assets::urls();
+    let icons: Vec<_> = [("icon-192.png", "192x192"), ("icon-512.png", "512x512")]
+        .iter()
+        .filter_map(|(name, sizes)| {
+            Some(serde_json::json!({
+                "src": format!("{}{}", base, urls.get(*name)?),
+                "sizes": sizes,
+                "type": "image/png",
+                // 图标内容都在中间的安全区域内，可以被系统裁成圆形
+                "purpose": "any maskable",
+            }))
+        })
+        .collect();
+    let background = theme::background(&config.settings.theme);
+    let manifest = serde_json::json!({
+        "name": "Gallery",
+        "short_name": "Gallery",
+        "start_url": format!("{}/", base),
+        "scope": format!("{}/", base),
+        "display": "standalone",
+        "background_color": background,
+        "theme_color": background,
+        "icons": icons,
+    });
+    HttpResponse::Ok()
+        .content_type("application/manifest+json")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .body(manifest.to_string())
+}
+
+#[get("/sw.js")]
+pub async fn service_worker(config: web::Data<AppConfig>) -> HttpResponse {
+    let base = &config.settings.mount_path;
+    let mut shell: Vec<String> = assets::urls().values().map(|url| format!("{}{}", base, url)).collect();
+    shell.sort();
+    shell.insert(0, format!("{}/", base));
+    let shell = serde_json::to_string(&shell).unwrap_or_else(|_| "[]".to_string());
+    let version = hex::encode(&Sha256::digest(shell.as_bytes())[..6]);
+
+    let mut context = tera::Context::new();
+    context.insert("shell", &shell);
+    context.insert("version", &version);
+    context.insert("base", base);
+    let mut response = config
+        .templates
+        .respond("sw.js", &context, "text/javascript; charset=utf-8");
+    // 浏览器每次都检查更新，资源哈希变化后立即安装新版本
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, header::HeaderValue::from_static("no-cache"));
+    response
+}
@@ -0,0 +1,77 @@
+//! 二维码：启动时在终端打印访问地址，`/api/qrcode` 返回 PNG，方便用手机打开画廊
+
+use crate::errors::ApiError;
+use actix_web::{get, web, HttpRequest, HttpResponse, ResponseError};
+use image::{GrayImage, ImageFormat, Luma};
+use qrcode::render::unicode::Dense1x2;
+use qrcode::{Color, QrCode};
+use serde::Deserialize;
+use std::io::Cursor;
+
+/// 每个模块的默认像素数和上限
+const DEFAULT_SCALE: u32 = 8;
+const MAX_SCALE: u32 = 32;
+/// 二维码四周的空白（以模块计）
+const QUIET_ZONE: u32 = 4;
+
+#[derive(Deserialize)]
+pub struct QrQuery {
+    /// 图片路径，指定时生成该图片分享页的链接，否则为画廊首页
+    path: Option<String>,
+    scale: Option<u32>,
+}
+
+/// 在终端中打印二维码，深色背景的终端也能正常扫描
+pub fn print_terminal(url: &str) {
+    if let Ok(code) = QrCode::new(url.as_bytes()) {
+        let rendered = code
+            .render::<Dense1x2>()
+            .dark_color(Dense1x2::Light)
+            .light_color(Dense1x2::Dark)
+            .build();
+        println!("{}", rendered);
+    }
+}
+
+fn render_png(data: &str, scale: u32) -> Option<Vec<u8>> {
+    let code = QrCode::new(data.as_bytes()).ok()?;
+    let width = code.width() as u32;
+    let size = (width + QUIET_ZONE * 2) * scale;
+    let colors = code.to_colors();
+    let img = GrayImage::from_fn(size, size, |x, y| {
+        let (mx, my) = (x / scale, y / scale);
+        let dark = mx >= QUIET_ZONE
+            && my >= QUIET_ZONE
+            && mx < width + QUIET_ZONE
+            && my < width + QUIET_ZONE
+            && colors[((my - QUIET_ZONE) * width + mx - QUIET_ZONE) as usize] == Color::Dark;
+        if dark {
+            Luma([0])
+        } else {
+            Luma([255])
+        }
+    });
+    let mut out = Cursor::new(Vec::new());
+    img.write_to(&mut out, ImageFormat::Png).ok()?;
+    Some(out.into_inner())
+}
+
+#[get("/api/qrcode")]
+pub async fn qrcode_png(req: HttpRequest, query: web::Query<QrQuery>) -> HttpResponse {
+    let scale = query.scale.unwrap_or(DEFAULT_SCALE);
+    if scale == 0 || scale > MAX_SCALE {
+        return ApiError::bad_request("scale must be between 1 and 32").error_response();
+    }
+    let base = crate::base_url(&req);
+    let url = match query.path.as_deref().map(|p| p.trim_matches('/')) {
+        Some(path) if !path.is_empty() => format!("{}/view/{}", base, crate::url_path(path)),
+        _ => format!("{}/", base),
+    };
+    match render_png(&url, scale) {
+        Some(png) => HttpResponse::Ok()
+            .content_type("image/png")
+            .insert_header(("Cache-Control", "public, max-age=3600"))
+            .body(png),
+        None => ApiError::bad_request("URL is too long for a QR code").error_response(),
+    }
+}
@@ -0,0 +1,204 @@
+//! 缩略图生成失败的图片：损坏或格式不支持的原图每次请求都要重新读取、解码一遍。
+//! 连续失败 [`MAX_ATTEMPTS`] 次后不再尝试，`/thumb` 直接返回“图片损坏”占位图，预生成也跳过；
+//! 原图被替换（大小或修改时间变化）后自动重新尝试。`GET /api/thumbs/failures` 列出失败的图片
+
+use crate::homes::Viewer;
+use crate::webdav::WebDavSource;
+use crate::{format_time, AppConfig};
+use actix_web::http::header;
+use actix_web::{get, web, HttpResponse};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// 连续失败多少次后不再尝试生成
+pub const MAX_ATTEMPTS: u32 = 3;
+/// 保留最近几次缩略图生成失败的原因
+const RECENT_FAILURES: usize = 10;
+
+/// 图片损坏时代替缩略图返回的占位图
+const PLACEHOLDER: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="200" viewBox="0 0 200 200">
+<rect width="200" height="200" fill="#eee"/>
+<path d="M55 60h90v56l-16-14-18 20-14-12-24 22H55z" fill="none" stroke="#999" stroke-width="6" stroke-linejoin="round"/>
+<path d="M60 150l80-80" stroke="#c33" stroke-width="8" stroke-linecap="round"/>
+<text x="100" y="180" font-family="sans-serif" font-size="16" fill="#777" text-anchor="middle">broken image</text>
+</svg>
+"##;
+
+/// 失败时原图的大小和修改时间，变化后说明原图被替换，重新计数
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stamp {
+    size: Option<u64>,
+    modified: Option<SystemTime>,
+}
+
+impl Stamp {
+    pub fn local(src_path: &Path) -> Self {
+        let meta = fs::metadata(src_path).ok();
+        Self {
+            size: meta.as_ref().map(|m| m.len()),
+            modified: meta.and_then(|m| m.modified().ok()),
+        }
+    }
+
+    /// WebDAV 来源按列目录时得到的信息，不发请求
+    pub fn remote(source: &WebDavSource, relative: &str) -> Self {
+        Self {
+            size: source.size(relative),
+            modified: source.modified(relative),
+        }
+    }
+}
+
+struct Entry {
+    attempts: u32,
+    error: String,
+    failed_at: SystemTime,
+    stamp: Stamp,
+}
+
+/// 最近的一次失败，见 `/api/thumbs/stats`
+#[derive(Serialize, Clone)]
+pub struct ThumbFailure {
+    pub path: String,
+    error: String,
+    at: String,
+}
+
+#[derive(Default)]
+pub struct Quarantine {
+    entries: Mutex<HashMap<String, Entry>>,
+    /// 最近几次失败，图片恢复正常后仍然保留
+    recent: Mutex<VecDeque<ThumbFailure>>,
+}
+
+impl Quarantine {
+    /// 记录一次失败，达到上限时返回 `true`
+    pub fn record(&self, relative: &str, error: &dyn Display, stamp: Stamp) -> bool {
+        if let Ok(mut recent) = self.recent.lock() {
+            if recent.len() == RECENT_FAILURES {
+                recent.pop_front();
+            }
+            recent.push_back(ThumbFailure {
+                path: relative.to_string(),
+                error: error.to_string(),
+                at: format_time(SystemTime::now()),
+            });
+        }
+        let Ok(mut entries) = self.entries.lock() else {
+            return false;
+        };
+        let entry = entries.entry(relative.to_string()).or_insert(Entry {
+            attempts: 0,
+            error: String::new(),
+            failed_at: SystemTime::now(),
+            stamp,
+        });
+        if entry.stamp != stamp {
+            entry.attempts = 0;
+            entry.stamp = stamp;
+        }
+        entry.attempts += 1;
+        entry.error = error.to_string();
+        entry.failed_at = SystemTime::now();
+        if entry.attempts == MAX_ATTEMPTS {
+            log::warn!("{} 连续 {} 次无法生成缩略图，不再重试，直到原图被替换", relative, MAX_ATTEMPTS);
+        }
+        entry.attempts >= MAX_ATTEMPTS
+    }
+
+    /// 生成成功、原图改变或删除后清除记录
+    pub fn forget(&self, relative: &str) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.remove(relative);
+        }
+    }
+
+    /// 是否已经不再尝试，只查内存，不检查原图是否被替换
+    pub fn contains(&self, relative: &str) -> bool {
+        self.entries
+            .lock()
+            .is_ok_and(|entries| entries.get(relative).is_some_and(|entry| entry.attempts >= MAX_ATTEMPTS))
+    }
+
+    /// 同 [`contains`](Self::contains)，原图已被替换时清除记录并返回 `false`
+    pub fn holds(&self, relative: &str, stamp: Stamp) -> bool {
+        let Ok(mut entries) = self.entries.lock() else {
+            return false;
+        };
+        match entries.get(relative) {
+            Some(entry) if entry.stamp != stamp => {
+                entries.remove(relative);
+                false
+            }
+            Some(entry) => entry.attempts >= MAX_ATTEMPTS,
+            None => false,
+        }
+    }
+
+    /// 最近几次失败，最新的在前
+    pub fn recent(&self) -> Vec<ThumbFailure> {
+        self.recent
+            .lock()
+            .map(|recent| recent.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn list(&self) -> Vec<FailedImage> {
+        let Ok(entries) = self.entries.lock() else {
+            return Vec::new();
+        };
+        let mut images: Vec<FailedImage> = entries
+            .iter()
+            .map(|(path, entry)| FailedImage {
+                path: path.clone(),
+                attempts: entry.attempts,
+                quarantined: entry.attempts >= MAX_ATTEMPTS,
+                error: entry.error.clone(),
+                failed_at: format_time(entry.failed_at),
+            })
+            .collect();
+        images.sort_by(|a, b| a.path.cmp(&b.path));
+        images
+    }
+}
+
+/// 代替缩略图返回的占位图；不缓存，原图被替换后浏览器能立即拿到新的缩略图
+pub fn placeholder() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("image/svg+xml")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .insert_header(("X-Thumbnail-Status", "quarantined"))
+        .body(PLACEHOLDER)
+}
+
+#[derive(Serialize)]
+struct FailedImage {
+    path: String,
+    /// 原图上次改变后连续失败的次数
+    attempts: u32,
+    /// 已达到上限，不再尝试
+    quarantined: bool,
+    error: String,
+    failed_at: String,
+}
+
+#[derive(Serialize)]
+struct FailuresResponse {
+    max_attempts: u32,
+    images: Vec<FailedImage>,
+}
+
+#[get("/api/thumbs/failures")]
+pub async fn failures(viewer: Viewer, config: web::Data<AppConfig>) -> HttpResponse {
+    let mut images = config.quarantine.list();
+    images.retain(|image| viewer.can_see(&image.path));
+    HttpResponse::Ok().json(FailuresResponse {
+        max_attempts: MAX_ATTEMPTS,
+        images,
+    })
+}
@@ -0,0 +1,71 @@
+//! 字节范围请求 (`Range`)，下载工具可以断点续传，播放器和看图软件可以只取需要的部分。
+//! 本地原图和历史版本由 `NamedFile` 处理；内存中的数据（WebDAV 来源的原图）和边生成边发送的 ZIP
+//! 用这里的函数。只支持单个范围，请求多个范围时返回完整内容
+
+use actix_files::HttpRange;
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse};
+
+/// 请求要的部分
+#[derive(Debug, PartialEq, Eq)]
+pub enum Requested {
+    /// 没有 `Range`、有多个范围，或 `If-Range` 与当前内容不符
+    Full,
+    /// 从 `start` 开始的 `length` 个字节
+    Partial { start: u64, length: u64 },
+    /// 范围无效或超出内容长度，返回 416
+    Unsatisfiable,
+}
+
+/// 解析 `Range`；`etag` 为内容的强校验值，请求带有 `If-Range` 而不等于它时返回完整内容
+pub fn requested(req: &HttpRequest, total: u64, etag: Option<&str>) -> Requested {
+    let Some(range) = req.headers().get(header::RANGE).and_then(|value| value.to_str().ok()) else {
+        return Requested::Full;
+    };
+    if let Some(if_range) = req.headers().get(header::IF_RANGE) {
+        if etag.is_none_or(|etag| if_range.as_bytes() != etag.as_bytes()) {
+            return Requested::Full;
+        }
+    }
+    match HttpRange::parse(range, total).as_deref() {
+        Ok([range]) => Requested::Partial {
+            start: range.start,
+            length: range.length,
+        },
+        Ok(_) => Requested::Full,
+        Err(_) => Requested::Unsatisfiable,
+    }
+}
+
+/// `Content-Range` 的值
+pub fn content_range(start: u64, length: u64, total: u64) -> String {
+    format!("bytes {}-{}/{}", start, start + length - 1, total)
+}
+
+/// 416 响应，告诉客户端内容的实际长度
+pub fn unsatisfiable(total: u64) -> HttpResponse {
+    HttpResponse::RangeNotSatisfiable()
+        .insert_header((header::CONTENT_RANGE, format!("bytes */{}", total)))
+        .insert_header((header::ACCEPT_RANGES, "bytes"))
+        .finish()
+}
+
+/// 返回内存中的数据，支持 `Range`
+pub fn bytes_response(req: &HttpRequest, data: Vec<u8>, content_type: &str) -> HttpResponse {
+    let total = data.len() as u64;
+    match requested(req, total, None) {
+        Requested::Full => HttpResponse::Ok()
+            .content_type(content_type)
+            .insert_header((header::ACCEPT_RANGES, "bytes"))
+            .body(data),
+        Requested::Partial { start, length } => {
+            let body = data[start as usize..(start + length) as usize].to_vec();
+            HttpResponse::PartialContent()
+                .content_type(content_type)
+                .insert_header((header::ACCEPT_RANGES, "bytes"))
+                .insert_header((header::CONTENT_RANGE, content_range(start, length, total)))
+                .body(body)
+        }
+        Requested::Unsatisfiable => unsatisfiable(total),
+    }
+}
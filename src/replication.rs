@@ -0,0 +1,283 @@
+//! 定时把新增/修改的图片同步到异地：另一个 pic_url 实例（或任意 WebDAV 服务器），或 rsync 目标
+
+use crate::aliases::Aliases;
+use crate::maintenance::Maintenance;
+use crate::webdav::WebDavSource;
+use crate::{collect_library, format_time, AppConfig};
+use actix_web::{get, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+enum Target {
+    /// 通过 WebDAV PUT 推送，例如另一个实例的 `http://nas:2020/dav/`
+    WebDav(Box<WebDavSource>),
+    /// 交给 rsync 处理增量，例如 `user@host:/backup/pic`
+    Rsync(String),
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct RunReport {
+    started_at: String,
+    finished_at: Option<String>,
+    success: bool,
+    files_pushed: u64,
+    bytes_pushed: u64,
+    error: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ReplicationStatus {
+    enabled: bool,
+    target: String,
+    interval_secs: u64,
+    running: bool,
+    last_run: Option<RunReport>,
+    last_success_at: Option<String>,
+    next_run_at: Option<String>,
+}
+
+/// 上次成功推送时文件的大小和修改时间
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    size: u64,
+    mtime: u64,
+}
+
+pub struct Replicator {
+    target: Target,
+    interval: Duration,
+    manifest_path: PathBuf,
+    status: Mutex<ReplicationStatus>,
+}
+
+/// 隐藏 URL 中的用户信息，避免在状态接口中泄露
+pub fn display_target(target: &str) -> String {
+    match target.split_once("://") {
+        Some((scheme, rest)) => {
+            let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+            let host = authority.rsplit('@').next().unwrap_or(authority);
+            format!("{}://{}{}", scheme, host, path)
+        }
+        None => target.to_string(),
+    }
+}
+
+impl Replicator {
+    pub fn new(
+        target: &str,
+        user: Option<&str>,
+        password: Option<&str>,
+        interval: Duration,
+        data_dir: &str,
+    ) -> Self {
+        let kind = if target.starts_with("http://") || target.starts_with("https://") {
+            Target::WebDav(Box::new(WebDavSource::new(target, user, password)))
+        } else {
+            Target::Rsync(target.to_string())
+        };
+        Self {
+            target: kind,
+            interval,
+            manifest_path: Path::new(data_dir).join("replication.json"),
+            status: Mutex::new(ReplicationStatus {
+                enabled: true,
+                target: display_target(target),
+                interval_secs: interval.as_secs(),
+                running: false,
+                last_run: None,
+                last_success_at: None,
+                next_run_at: None,
+            }),
+        }
+    }
+
+    pub fn status(&self) -> ReplicationStatus {
+        self.status.lock().map(|s| s.clone()).unwrap_or_else(|e| e.into_inner().clone())
+    }
+
+    fn update(&self, f: impl FnOnce(&mut ReplicationStatus)) {
+        if let Ok(mut guard) = self.status.lock() {
+            f(&mut guard);
+        }
+    }
+
+    fn load_manifest(&self) -> HashMap<String, FileStamp> {
+        fs::read(&self.manifest_path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default()
+    }
+
+    fn save_manifest(&self, manifest: &HashMap<String, FileStamp>) -> io::Result<()> {
+        if let Some(parent) = self.manifest_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_vec(manifest).map_err(io::Error::other)?;
+        let tmp = self.manifest_path.with_extension("json.tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &self.manifest_path)
+    }
+
+    /// 执行一次同步，阻塞直到完成
+    pub fn run_once(&self, pic_dir: &str, aliases: &Aliases) -> RunReport {
+        let mut report = RunReport {
+            started_at: format_time(SystemTime::now()),
+            ..Default::default()
+        };
+        self.update(|s| {
+            s.running = true;
+            s.next_run_at = None;
+        });
+
+        let result = match &self.target {
+            Target::WebDav(client) => self.push_webdav(client, pic_dir, aliases, &mut report),
+            Target::Rsync(dest) => push_rsync(dest, pic_dir),
+        };
+        report.success = result.is_ok();
+        report.error = result.err().map(|e| e.to_string());
+        report.finished_at = Some(format_time(SystemTime::now()));
+
+        let next = format_time(SystemTime::now() + self.interval);
+        self.update(|s| {
+            s.running = false;
+            if report.success {
+                s.last_success_at = report.finished_at.clone();
+            }
+            s.last_run = Some(report.clone());
+            s.next_run_at = Some(next);
+        });
+        report
+    }
+
+    fn push_webdav(
+        &self,
+        client: &WebDavSource,
+        pic_dir: &str,
+        aliases: &Aliases,
+        report: &mut RunReport,
+    ) -> io::Result<()> {
+        let base = Path::new(pic_dir);
+        let mut images = Vec::new();
+        // 读不了的目录跳过，同步其余图片后报告失败
+        let unreadable = collect_library(base, aliases, &mut images).err();
+
+        let mut manifest = self.load_manifest();
+        let mut created_dirs: HashSet<String> = HashSet::new();
+        let mut first_error = unreadable.map(|e| {
+            log::warn!("同步时跳过无法读取的目录 {}", e);
+            io::Error::other(e)
+        });
+
+        for relative in images {
+            let path = aliases.fs_path(base, &relative);
+            let meta = match fs::metadata(&path) {
+                Ok(meta) => meta,
+                Err(_) => continue,
+            };
+            let stamp = FileStamp {
+                size: meta.len(),
+                mtime: meta
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0),
+            };
+            if manifest.get(&relative) == Some(&stamp) {
+                continue;
+            }
+
+            let result = ensure_parents(client, &relative, &mut created_dirs)
+                .and_then(|_| fs::read(&path))
+                .and_then(|data| client.put(&relative, data));
+            match result {
+                Ok(()) => {
+                    report.files_pushed += 1;
+                    report.bytes_pushed += stamp.size;
+                    manifest.insert(relative, stamp);
+                }
+                Err(e) => {
+                    log::warn!("同步 {} 失败: {}", relative, e);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        self.save_manifest(&manifest)?;
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+/// 依次创建远程父目录，同一次同步中只创建一次
+fn ensure_parents(client: &WebDavSource, relative: &str, created: &mut HashSet<String>) -> io::Result<()> {
+    let mut current = String::new();
+    let parts: Vec<&str> = relative.split('/').collect();
+    for part in &parts[..parts.len().saturating_sub(1)] {
+        if !current.is_empty() {
+            current.push('/');
+        }
+        current.push_str(part);
+        if created.insert(current.clone()) {
+            client.mkcol(&current)?;
+        }
+    }
+    Ok(())
+}
+
+fn push_rsync(dest: &str, pic_dir: &str) -> io::Result<()> {
+    let source = format!("{}/", pic_dir.trim_end_matches('/'));
+    let output = Command::new("rsync")
+        .arg("-a")
+        .arg(format!("--exclude=/{}", crate::THUMB_DIR_NAME))
+        .arg(format!("--exclude=/{}", crate::DATA_DIR_NAME))
+        .arg(format!("--exclude=/{}", crate::VERSIONS_DIR_NAME))
+        .arg(&source)
+        .arg(dest)
+        .output()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => io::Error::new(e.kind(), "未找到 rsync 命令"),
+            _ => e,
+        })?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "rsync 退出码 {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// 启动后台定时同步任务，启动后立即执行第一次；维护模式下跳过
+pub fn spawn(replicator: Arc<Replicator>, pic_dir: String, aliases: Arc<Aliases>, maintenance: Arc<Maintenance>) {
+    let interval = replicator.interval;
+    crate::scheduler::every("同步", Duration::ZERO, interval, move || {
+        if maintenance.is_enabled() {
+            return;
+        }
+        let report = replicator.run_once(&pic_dir, &aliases);
+        if !report.success {
+            log::error!("同步失败: {}", report.error.unwrap_or_default());
+        } else if report.files_pushed > 0 {
+            log::info!("同步完成: {} 个文件", report.files_pushed);
+        }
+    });
+}
+
+#[get("/api/replication/status")]
+pub async fn status(config: web::Data<AppConfig>) -> HttpResponse {
+    match &config.replicator {
+        Some(replicator) => HttpResponse::Ok().json(replicator.status()),
+        None => HttpResponse::Ok().json(serde_json::json!({ "enabled": false })),
+    }
+}
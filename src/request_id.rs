@@ -0,0 +1,68 @@
+//! 请求 ID：每个请求都有一个 `X-Request-Id`，沿用客户端或反向代理传入的，没有时生成。
+//! ID 出现在响应头、JSON 错误响应的 `error.request_id`、访问日志和出错请求的日志中，
+//! 有人反馈“某张缩略图时好时坏”时，可以按 ID 在服务器日志中找到当时的错误
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpMessage, HttpRequest};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// 保存在请求扩展中的 ID
+#[derive(Clone)]
+pub struct RequestId(pub String);
+
+/// 当前请求的 ID，不经过中间件的请求为 `None`
+pub fn of(req: &HttpRequest) -> Option<String> {
+    req.extensions().get::<RequestId>().map(|id| id.0.clone())
+}
+
+/// 传入的 ID 只接受常见的字符，不让换行等内容进入日志
+fn is_valid(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 128
+        && id.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':'))
+}
+
+/// 进程启动时间戳加序号，与 Webhook 的投递 ID 格式相同
+fn generate() -> String {
+    static BOOT: OnceLock<u64> = OnceLock::new();
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    let boot = BOOT.get_or_init(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    });
+    format!("{:x}-{}", boot, NEXT.fetch_add(1, Ordering::Relaxed))
+}
+
+/// 分配请求 ID 的中间件，需要在错误处理 ([`errors::handle`](crate::errors::handle)) 的外层
+pub async fn middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    // 外层已经分配过时沿用
+    let existing = req.extensions().get::<RequestId>().map(|id| id.0.clone());
+    let id = existing.unwrap_or_else(|| {
+        let id = req
+            .headers()
+            .get(&HEADER)
+            .and_then(|v| v.to_str().ok())
+            .filter(|id| is_valid(id))
+            .map(str::to_string)
+            .unwrap_or_else(generate);
+        req.extensions_mut().insert(RequestId(id.clone()));
+        id
+    });
+    let mut res = next.call(req).await?;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        res.headers_mut().insert(HEADER, value);
+    }
+    Ok(res)
+}
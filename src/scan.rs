@@ -0,0 +1,392 @@
+//! 扫描进度：扫描大型图库、为新图片建立短 ID 和后台预生成缩略图时报告进度，
+//! 通过 `GET /api/scan/status` 查询，并通过 `/api/events` 推送给画廊页面
+
+use crate::cron::Cron;
+use crate::{ensure_thumbnail, format_time, get_thumbnail_path, scheduler, thumbnail_is_fresh, AppConfig};
+use actix_web::{get, web, HttpResponse};
+use serde::Serialize;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use time::OffsetDateTime;
+use tokio::sync::broadcast;
+
+/// 运行超过这个时间的任务才推送进度，避免每次小扫描都让进度条闪一下
+const REPORT_AFTER: Duration = Duration::from_secs(1);
+/// 两次推送之间的最小间隔
+const REPORT_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    Idle,
+    /// 遍历图片目录
+    Scanning,
+    /// 计算新图片的指纹，分配短 ID
+    Indexing,
+    /// 后台预生成缩略图
+    Thumbnails,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ScanStatus {
+    phase: Phase,
+    /// 当前阶段已完成的数量
+    done: u64,
+    /// 当前阶段的总数，遍历目录时未知
+    total: Option<u64>,
+    /// 按当前速度估算的剩余秒数
+    eta_secs: Option<u64>,
+    /// 最近一次扫描找到的图片数，扫描中为已找到的数量
+    files_scanned: u64,
+    /// 启动以来后台预生成的缩略图数
+    thumbnails_generated: u64,
+    last_scan_at: Option<String>,
+    last_scan_ms: Option<u128>,
+    /// 最近一次扫描时无法读取的目录及原因，例如 `2024/私密: Permission denied (os error 13)`
+    unreadable: Vec<String>,
+}
+
+struct State {
+    status: ScanStatus,
+    /// 当前任务开始的时间
+    started: Instant,
+    /// 当前阶段开始的时间，用于估算剩余时间
+    phase_started: Instant,
+    last_push: Option<Instant>,
+    /// 任务进行中有人通过 `/api/scan/status` 查询过状态
+    observed: bool,
+}
+
+pub struct ScanProgress {
+    state: Mutex<State>,
+    /// 同一时间只有一个任务报告进度，其它并发的扫描静默执行
+    reporter: Mutex<()>,
+    /// 是否需要（再次）预生成缩略图
+    prewarm: Mutex<bool>,
+    prewarm_wakeup: Condvar,
+    sender: broadcast::Sender<ScanStatus>,
+}
+
+impl Default for ScanProgress {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(State {
+                status: ScanStatus {
+                    phase: Phase::Idle,
+                    done: 0,
+                    total: None,
+                    eta_secs: None,
+                    files_scanned: 0,
+                    thumbnails_generated: 0,
+                    last_scan_at: None,
+                    last_scan_ms: None,
+                    unreadable: Vec::new(),
+                },
+                started: Instant::now(),
+                phase_started: Instant::now(),
+                last_push: None,
+                observed: false,
+            }),
+            reporter: Mutex::new(()),
+            prewarm: Mutex::new(false),
+            prewarm_wakeup: Condvar::new(),
+            sender: broadcast::channel(16).0,
+        }
+    }
+}
+
+impl ScanProgress {
+    pub fn status(&self) -> ScanStatus {
+        self.state
+            .lock()
+            .map(|state| state.status.clone())
+            .unwrap_or_else(|e| e.into_inner().status.clone())
+    }
+
+    /// 供状态接口使用：记录有人看到了进行中的状态，任务结束时需要推送 `idle`
+    fn observe(&self) -> ScanStatus {
+        let Ok(mut state) = self.state.lock() else {
+            return self.status();
+        };
+        state.observed = state.status.phase != Phase::Idle;
+        state.status.clone()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ScanStatus> {
+        self.sender.subscribe()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.status().phase != Phase::Idle
+    }
+
+    /// 开始报告进度；已有任务在报告时返回 `None`
+    pub fn try_report(&self) -> Option<Reporter<'_>> {
+        let guard = self.reporter.try_lock().ok()?;
+        Some(Reporter::new(self, guard))
+    }
+
+    /// 等待正在报告的任务结束后开始报告
+    pub fn report(&self) -> Reporter<'_> {
+        let guard = self.reporter.lock().unwrap_or_else(|e| e.into_inner());
+        Reporter::new(self, guard)
+    }
+
+    /// 修改状态，满足节流条件时推送给订阅者
+    fn update(&self, f: impl FnOnce(&mut State)) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        f(&mut state);
+        let now = Instant::now();
+        let due = state.started.elapsed() >= REPORT_AFTER
+            && state.last_push.is_none_or(|t| now - t >= REPORT_INTERVAL);
+        if due {
+            state.last_push = Some(now);
+            let _ = self.sender.send(state.status.clone());
+        }
+    }
+
+    /// 记录最近一次扫描时无法读取的目录
+    pub fn set_unreadable(&self, unreadable: Vec<String>) {
+        if let Ok(mut state) = self.state.lock() {
+            state.status.unreadable = unreadable;
+        }
+    }
+
+    /// 请求后台预生成缩略图
+    pub fn request_prewarm(&self) {
+        if let Ok(mut pending) = self.prewarm.lock() {
+            *pending = true;
+            self.prewarm_wakeup.notify_one();
+        }
+    }
+
+    fn wait_prewarm(&self) {
+        let mut pending = self.prewarm.lock().unwrap_or_else(|e| e.into_inner());
+        while !*pending {
+            pending = self.prewarm_wakeup.wait(pending).unwrap_or_else(|e| e.into_inner());
+        }
+        *pending = false;
+    }
+}
+
+/// 正在报告进度的任务，结束（drop）时状态回到 `idle`
+pub struct Reporter<'a> {
+    progress: &'a ScanProgress,
+    _guard: MutexGuard<'a, ()>,
+}
+
+impl<'a> Reporter<'a> {
+    fn new(progress: &'a ScanProgress, guard: MutexGuard<'a, ()>) -> Self {
+        if let Ok(mut state) = progress.state.lock() {
+            state.started = Instant::now();
+            state.last_push = None;
+            state.observed = false;
+        }
+        Self {
+            progress,
+            _guard: guard,
+        }
+    }
+
+    pub fn phase(&self, phase: Phase, total: Option<usize>) {
+        self.progress.update(|state| {
+            state.phase_started = Instant::now();
+            state.status.phase = phase;
+            state.status.done = 0;
+            state.status.total = total.map(|t| t as u64);
+            state.status.eta_secs = None;
+        });
+    }
+
+    pub fn advance(&self, done: usize) {
+        self.progress.update(|state| {
+            let phase_started = state.phase_started;
+            let current = &mut state.status;
+            current.done = done as u64;
+            if current.phase == Phase::Scanning {
+                current.files_scanned = done as u64;
+            }
+            current.eta_secs = current.total.filter(|_| done > 0).map(|total| {
+                let elapsed = phase_started.elapsed().as_secs_f64();
+                (elapsed / done as f64 * total.saturating_sub(done as u64) as f64).round() as u64
+            });
+        });
+    }
+
+    /// 扫描完成，记录找到的图片数和耗时
+    pub fn scanned(&self, images: usize) {
+        self.progress.update(|state| {
+            state.status.files_scanned = images as u64;
+            state.status.last_scan_at = Some(format_time(SystemTime::now()));
+            state.status.last_scan_ms = Some(state.started.elapsed().as_millis());
+        });
+    }
+
+    pub fn thumbnail_generated(&self) {
+        self.progress.update(|state| state.status.thumbnails_generated += 1);
+    }
+}
+
+impl Drop for Reporter<'_> {
+    fn drop(&mut self) {
+        let Ok(mut state) = self.progress.state.lock() else {
+            return;
+        };
+        state.status.phase = Phase::Idle;
+        state.status.done = 0;
+        state.status.total = None;
+        state.status.eta_secs = None;
+        // 推送过进度或被查询过的任务才需要通知结束
+        let seen = std::mem::take(&mut state.observed);
+        if state.last_push.take().is_some() || seen {
+            let _ = self.progress.sender.send(state.status.clone());
+        }
+    }
+}
+
+/// 启动后台预生成缩略图的线程：每次扫描发现新图片后，为缺少或过期的缩略图生成缓存，
+/// 打开画廊时不用再等待缩略图现场生成。只用于本地图片目录；关闭预生成时仍处理[归档目录](crate::tiers)
+pub fn spawn_prewarm(config: AppConfig) {
+    thread::Builder::new()
+        .name("prewarm".to_string())
+        .spawn(move || loop {
+            config.scan.wait_prewarm();
+            // 维护结束后会重新请求预生成
+            if config.maintenance.is_enabled() {
+                continue;
+            }
+            let mut missing: Vec<String> = config
+                .index
+                .images()
+                .into_iter()
+                .filter(|relative| {
+                    if !config.settings.prewarm && !config.tiers.is_archived(relative) {
+                        return false;
+                    }
+                    // 多次失败的图片等原图被替换后再由页面请求重试
+                    if config.quarantine.contains(relative) {
+                        return false;
+                    }
+                    let thumb_path = get_thumbnail_path(&config.thumb_dir, relative);
+                    !thumbnail_is_fresh(config.settings.thumb_check, &config.fs_path(relative), &thumb_path)
+                })
+                .collect();
+            if missing.is_empty() {
+                continue;
+            }
+            config.popularity.prioritize(&mut missing);
+            // 归档目录的缩略图现场生成最慢，排在前面
+            missing.sort_by_key(|relative| !config.tiers.is_archived(relative));
+            let reporter = config.scan.report();
+            reporter.phase(Phase::Thumbnails, Some(missing.len()));
+            for (i, relative) in missing.iter().enumerate() {
+                if config.maintenance.is_enabled() {
+                    break;
+                }
+                // 有新图片加入时重新排序，新图片不用等前面积压的都处理完
+                if config.popularity.has_added() {
+                    config.scan.request_prewarm();
+                    break;
+                }
+                let src = config.fs_path(relative);
+                // 扫描之后被删除的图片跳过
+                if !src.is_file() {
+                    reporter.advance(i + 1);
+                    continue;
+                }
+                // 页面请求正在生成缩略图时先让路
+                config.limiter.wait_idle();
+                let (check, size) = (config.settings.thumb_check, config.settings.thumb_size);
+                if ensure_thumbnail(check, size, &config.thumb_dir, &src, relative, &config.quarantine).is_ok() {
+                    reporter.thumbnail_generated();
+                }
+                reporter.advance(i + 1);
+            }
+        })
+        .expect("failed to spawn prewarm thread");
+}
+
+/// `--rescan-cron` 定时完整扫描，补上文件监听漏掉的变化（网络挂载的目录通常收不到文件事件）
+pub struct ScheduledRescan {
+    cron: Cron,
+    status: Mutex<ScheduledRescanStatus>,
+}
+
+/// 定时扫描的状态，显示在 `/api/admin/info` 中
+#[derive(Serialize, Clone)]
+pub struct ScheduledRescanStatus {
+    cron: String,
+    next_run_at: Option<String>,
+    last_run_at: Option<String>,
+    last_duration_ms: Option<u128>,
+    /// 最近一次扫描找到的图片数
+    last_count: Option<usize>,
+    /// 最近一次到点时处于维护模式，没有扫描
+    last_skipped: bool,
+}
+
+impl ScheduledRescan {
+    pub fn new(cron: Cron) -> Self {
+        let next_run_at = cron.next_after(OffsetDateTime::now_utc()).map(|t| format_time(t.into()));
+        Self {
+            status: Mutex::new(ScheduledRescanStatus {
+                cron: cron.to_string(),
+                next_run_at,
+                last_run_at: None,
+                last_duration_ms: None,
+                last_count: None,
+                last_skipped: false,
+            }),
+            cron,
+        }
+    }
+
+    pub fn cron(&self) -> &Cron {
+        &self.cron
+    }
+
+    pub fn status(&self) -> ScheduledRescanStatus {
+        self.status.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn next_run_at(&self) -> Option<String> {
+        self.status().next_run_at
+    }
+
+    fn update(&self, f: impl FnOnce(&mut ScheduledRescanStatus)) {
+        f(&mut self.status.lock().unwrap_or_else(|e| e.into_inner()));
+    }
+}
+
+/// 启动定时完整扫描；维护模式下到点时跳过
+pub fn spawn_scheduled_rescan(config: AppConfig, schedule: Arc<ScheduledRescan>) {
+    scheduler::cron("定时扫描", schedule.cron.clone(), move || {
+        let started_at = SystemTime::now();
+        let started = Instant::now();
+        let skipped = config.maintenance.is_enabled();
+        if skipped {
+            log::info!("维护模式中，跳过定时扫描");
+        } else {
+            let images = config.rescan();
+            log::info!("定时扫描完成: {} 张图片 (耗时 {} 毫秒)", images.len(), started.elapsed().as_millis());
+            schedule.update(|s| {
+                s.last_run_at = Some(format_time(started_at));
+                s.last_duration_ms = Some(started.elapsed().as_millis());
+                s.last_count = Some(images.len());
+            });
+        }
+        let next = schedule.cron.next_after(OffsetDateTime::now_utc());
+        schedule.update(|s| {
+            s.last_skipped = skipped;
+            s.next_run_at = next.map(|t| format_time(t.into()));
+        });
+    });
+}
+
+#[get("/api/scan/status")]
+pub async fn status(config: web::Data<AppConfig>) -> HttpResponse {
+    HttpResponse::Ok().json(config.scan.observe())
+}
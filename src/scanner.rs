@@ -0,0 +1,179 @@
+//! 上传病毒扫描：`--clamd` 把上传的文件交给 ClamAV 守护进程 (`INSTREAM`)，`--scan-command` 交给外部命令，
+//! 发现威胁的文件被拒绝，不会进入图片目录。适合接受半信任用户上传的实例。
+//! 扫描器连不上、超时或出错时同样拒绝上传，不会因为扫描器故障放行未检查的文件
+
+use crate::hooks::PASSTHROUGH_ENV;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 单个文件扫描的超时时间
+const TIMEOUT: Duration = Duration::from_secs(60);
+/// 发送给 clamd 的块大小
+const CHUNK_SIZE: usize = 64 * 1024;
+/// 保留的威胁名称长度上限
+const MAX_THREAT_LEN: usize = 200;
+
+pub enum Scanner {
+    /// clamd 的地址：以 `/` 开头为 Unix 套接字，否则为 `host:port`
+    Clamd(String),
+    /// 外部命令，最后一个参数为文件路径；与 clamscan 相同，退出码 0 为未发现威胁，1 为发现威胁，其他为出错
+    Command(Vec<String>),
+}
+
+/// 扫描结果
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Clean,
+    /// 发现威胁，带扫描器报告的名称
+    Infected(String),
+}
+
+impl Scanner {
+    /// 扫描一个文件，会阻塞到扫描完成
+    pub fn scan(&self, path: &Path) -> io::Result<Verdict> {
+        match self {
+            Scanner::Clamd(address) => scan_clamd(address, path),
+            Scanner::Command(command) => scan_command(command, path),
+        }
+    }
+
+    /// 扫描上传的文件 `path`（`name` 用于日志），不能接受时返回给客户端的原因
+    pub fn check(&self, path: &Path, name: &str) -> Result<(), String> {
+        match self.scan(path) {
+            Ok(Verdict::Clean) => Ok(()),
+            Ok(Verdict::Infected(threat)) => {
+                log::warn!("上传的文件 {} 被扫描器标记为 {}，已拒绝", name, threat);
+                Err(format!("rejected by virus scan: {}", threat))
+            }
+            Err(e) => {
+                log::warn!("扫描上传的文件 {} 失败，已拒绝: {}", name, e);
+                Err("virus scan failed".to_string())
+            }
+        }
+    }
+}
+
+fn scan_clamd(address: &str, path: &Path) -> io::Result<Verdict> {
+    #[cfg(unix)]
+    if address.starts_with('/') {
+        let stream = std::os::unix::net::UnixStream::connect(address)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        return instream(stream, path);
+    }
+    let addr = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("cannot resolve {}", address)))?;
+    let stream = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    instream(stream, path)
+}
+
+/// `zINSTREAM`：每块前面是 4 字节大端长度，长度为 0 的块表示结束，clamd 回复后关闭连接
+fn instream(mut stream: impl Read + Write, path: &Path) -> io::Result<Verdict> {
+    stream.write_all(b"zINSTREAM\0")?;
+    let mut file = File::open(path)?;
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        stream.write_all(&(n as u32).to_be_bytes())?;
+        stream.write_all(&buf[..n])?;
+    }
+    stream.write_all(&0u32.to_be_bytes())?;
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply)?;
+    parse_reply(&String::from_utf8_lossy(&reply))
+}
+
+/// clamd 的回复：`stream: OK`、`stream: <名称> FOUND` 或 `<原因> ERROR`
+fn parse_reply(reply: &str) -> io::Result<Verdict> {
+    let reply = reply.trim_end_matches(['\0', '\n']).trim();
+    let result = reply.strip_prefix("stream:").unwrap_or(reply).trim();
+    if result == "OK" {
+        Ok(Verdict::Clean)
+    } else if let Some(name) = result.strip_suffix(" FOUND") {
+        Ok(Verdict::Infected(threat_name(name)))
+    } else {
+        Err(io::Error::other(format!("clamd: {}", result)))
+    }
+}
+
+fn threat_name(name: &str) -> String {
+    let name = name.trim();
+    let name = if name.is_empty() { "unknown threat" } else { name };
+    name.chars().take(MAX_THREAT_LEN).collect()
+}
+
+fn scan_command(command: &[String], path: &Path) -> io::Result<Verdict> {
+    let mut child = Command::new(&command[0])
+        .args(&command[1..])
+        .arg(path)
+        .env_clear()
+        .envs(PASSTHROUGH_ENV.iter().filter_map(|key| std::env::var_os(key).map(|value| (key, value))))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // 在单独的线程中读输出，避免输出较多时命令因管道写满而卡住
+    let mut stdout = child.stdout.take();
+    let reader = thread::spawn(move || {
+        let mut output = String::new();
+        if let Some(stdout) = stdout.as_mut() {
+            let _ = stdout.read_to_string(&mut output);
+        }
+        output
+    });
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if started.elapsed() >= TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "scanner timed out"));
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+    let output = reader.join().unwrap_or_default();
+    match status.code() {
+        Some(0) => Ok(Verdict::Clean),
+        Some(1) => {
+            // clamscan 的输出为 `<路径>: <名称> FOUND`，后面可能还有统计信息；其他命令取最后一行
+            let line = output
+                .lines()
+                .find(|line| line.trim_end().ends_with(" FOUND"))
+                .or_else(|| output.lines().rev().find(|line| !line.trim().is_empty()))
+                .unwrap_or("");
+            let line = line.strip_prefix(&format!("{}: ", path.display())).unwrap_or(line);
+            Ok(Verdict::Infected(threat_name(line.trim().trim_end_matches(" FOUND"))))
+        }
+        _ => Err(io::Error::other(format!("scanner exited with {}", status))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_clamd_replies() {
+        assert_eq!(parse_reply("stream: OK\0").unwrap(), Verdict::Clean);
+        assert_eq!(
+            parse_reply("stream: Win.Test.EICAR_HDB-1 FOUND\0").unwrap(),
+            Verdict::Infected("Win.Test.EICAR_HDB-1".to_string())
+        );
+        assert!(parse_reply("INSTREAM size limit exceeded. ERROR\0").is_err());
+        assert!(parse_reply("").is_err());
+    }
+}
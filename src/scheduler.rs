@@ -0,0 +1,53 @@
+//! 后台定时任务：同步、备份等周期性工作共用的调度器
+
+use crate::cron::Cron;
+use actix_web::{rt, web};
+use std::time::Duration;
+use time::OffsetDateTime;
+
+/// 等待 `delay` 后执行第一次，之后每隔 `interval` 在阻塞线程池中执行一次 `job`。
+/// 上一次执行未结束时不会开始下一次。
+pub fn every<F>(name: &'static str, delay: Duration, interval: Duration, job: F)
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let job = std::sync::Arc::new(job);
+    rt::spawn(async move {
+        let start = rt::time::Instant::now() + delay;
+        let mut ticker = rt::time::interval_at(start, interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let job = job.clone();
+            if let Err(e) = web::block(move || job()).await {
+                log::error!("{}任务异常: {}", name, e);
+            }
+        }
+    });
+}
+
+/// 按 cron 表达式（UTC）在阻塞线程池中执行 `job`。
+/// 上一次执行未结束时，期间错过的时间点不再补执行
+pub fn cron<F>(name: &'static str, schedule: Cron, job: F)
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let job = std::sync::Arc::new(job);
+    rt::spawn(async move {
+        let mut after = OffsetDateTime::now_utc();
+        loop {
+            let Some(next) = schedule.next_after(after) else {
+                log::error!("{}任务不会再执行: {}", name, schedule);
+                return;
+            };
+            let wait = Duration::try_from(next - OffsetDateTime::now_utc()).unwrap_or_default();
+            rt::time::sleep(wait).await;
+            let job = job.clone();
+            if let Err(e) = web::block(move || job()).await {
+                log::error!("{}任务异常: {}", name, e);
+            }
+            // 定时器提前醒来时不会在同一个时间点执行两次
+            after = next.max(OffsetDateTime::now_utc());
+        }
+    });
+}
@@ -0,0 +1,279 @@
+//! 浏览器登录：在 `/login` 输入管理令牌后得到一个签名的会话 Cookie，之后上传、编辑等请求不需要再带
+//! `Authorization` 头。Cookie 为 `<ID>.<过期时间>.<签名>`，签名为以管理令牌为密钥的 HMAC-SHA256，
+//! 服务器不保存会话；更换令牌后之前签发的会话全部失效。`/logout` 注销的会话 ID 记录在数据目录的
+//! `sessions.json` 中，直到原本的过期时间。
+//!
+//! 也可以用 [API 密钥](crate::api_keys)登录，这时 Cookie 为 `<ID>.<过期时间>.<密钥 ID>.<签名>`，
+//! 会话的权限与密钥相同，密钥吊销后会话随之失效
+
+use crate::admin::{AdminAuthError, Identity};
+use crate::{auth_log, base_url, format_time, mount_path, page_context, AppConfig};
+use actix_web::cookie::{self, Cookie, SameSite};
+use actix_web::http::{header, Method};
+use actix_web::{get, post, web, HttpRequest, HttpResponse, ResponseError};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const COOKIE: &str = "pic_session";
+/// 会话默认的有效期
+pub const DEFAULT_LIFETIME: u64 = 7 * 24 * 3600;
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn mac(token: &str, id: &str, expires: u64, key: Option<&str>) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(token.as_bytes()).expect("HMAC accepts any key length");
+    match key {
+        Some(key) => mac.update(format!("session:{}:{}:{}", id, expires, key).as_bytes()),
+        None => mac.update(format!("session:{}:{}", id, expires).as_bytes()),
+    }
+    mac
+}
+
+/// 会话 ID 只用来注销，不需要保密，签名保证了 Cookie 不能伪造
+fn new_id() -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = Sha256::new();
+    hasher.update(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos().to_le_bytes());
+    hasher.update(NEXT.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+    hasher.update(std::process::id().to_le_bytes());
+    hex::encode(&hasher.finalize()[..16])
+}
+
+struct Session {
+    id: String,
+    expires: u64,
+    /// 用 API 密钥登录时为密钥 ID
+    key: Option<String>,
+}
+
+/// 签发会话，返回 Cookie 的值
+fn issue(token: &str, lifetime: u64, key: Option<&str>) -> String {
+    let id = new_id();
+    let expires = now_secs().saturating_add(lifetime);
+    let sig = hex::encode(mac(token, &id, expires, key).finalize().into_bytes());
+    match key {
+        Some(key) => format!("{}.{}.{}.{}", id, expires, key, sig),
+        None => format!("{}.{}.{}", id, expires, sig),
+    }
+}
+
+/// 签名正确且没有过期的会话
+fn parse(token: &str, value: &str) -> Option<Session> {
+    let parts: Vec<&str> = value.split('.').collect();
+    let (id, expires, key, sig) = match parts[..] {
+        [id, expires, sig] => (id, expires, None, sig),
+        [id, expires, key, sig] => (id, expires, Some(key), sig),
+        _ => return None,
+    };
+    let expires = expires.parse().ok()?;
+    if expires <= now_secs() {
+        return None;
+    }
+    let sig = hex::decode(sig).ok()?;
+    mac(token, id, expires, key).verify_slice(&sig).ok()?;
+    Some(Session {
+        id: id.to_string(),
+        expires,
+        key: key.map(str::to_string),
+    })
+}
+
+/// 已注销的会话
+pub struct Sessions {
+    path: PathBuf,
+    /// 会话 ID 和原本的过期时间，过期后不再需要记录
+    revoked: Mutex<HashMap<String, u64>>,
+}
+
+impl Sessions {
+    /// 从 `<data_dir>/sessions.json` 加载
+    pub fn load(data_dir: &str) -> Self {
+        let path = Path::new(data_dir).join("sessions.json");
+        let revoked = fs::read(&path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            revoked: Mutex::new(revoked),
+        }
+    }
+
+    fn is_revoked(&self, id: &str) -> bool {
+        self.revoked.lock().map(|revoked| revoked.contains_key(id)).unwrap_or(true)
+    }
+
+    fn revoke(&self, session: &Session) -> io::Result<()> {
+        let Ok(mut revoked) = self.revoked.lock() else {
+            return Ok(());
+        };
+        let now = now_secs();
+        revoked.retain(|_, expires| *expires > now);
+        revoked.insert(session.id.clone(), session.expires);
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_vec_pretty(&*revoked).map_err(io::Error::other)?;
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+/// 请求携带的有效会话，登录用的 API 密钥被吊销后会话也不再有效
+fn current(config: &AppConfig, req: &HttpRequest) -> Option<Session> {
+    let token = config.settings.admin_token.as_deref()?;
+    let session = parse(token, req.cookie(COOKIE)?.value())?;
+    let key_valid = session.key.as_deref().is_none_or(|key| config.api_keys.get(key).is_some());
+    (key_valid && !config.sessions.is_revoked(&session.id)).then_some(session)
+}
+
+/// 请求带有 `Origin` 时必须与请求的主机相同，其他网站的页面不能借用户的 Cookie 修改图片库
+fn same_origin(req: &HttpRequest) -> bool {
+    let Some(origin) = req.headers().get(header::ORIGIN).and_then(|v| v.to_str().ok()) else {
+        return true;
+    };
+    origin.split_once("://").is_some_and(|(_, host)| host == req.connection_info().host())
+}
+
+/// 会话 Cookie 代表的身份，没有有效的会话时返回 `None`；GET 等只读请求之外还要求同源
+pub fn identify(config: &AppConfig, req: &HttpRequest) -> Option<Identity> {
+    let read_only = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    let session = current(config, req).filter(|_| read_only || same_origin(req))?;
+    match session.key {
+        Some(key) => config.api_keys.get(&key).map(Identity::Key),
+        None => Some(Identity::Owner),
+    }
+}
+
+fn cookie_path(req: &HttpRequest) -> String {
+    format!("{}/", mount_path(req))
+}
+
+fn session_cookie(req: &HttpRequest, value: String, lifetime: u64) -> Cookie<'static> {
+    Cookie::build(COOKIE, value)
+        .path(cookie_path(req))
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .secure(base_url(req).starts_with("https://"))
+        .max_age(cookie::time::Duration::seconds(lifetime.min(i64::MAX as u64) as i64))
+        .finish()
+}
+
+/// 登录后跳转的地址，只接受画廊内的路径
+fn next_path(req: &HttpRequest, next: Option<&str>) -> String {
+    let home = cookie_path(req);
+    match next {
+        Some(next) if next.starts_with(&home) && !next.starts_with("//") && !next.contains(['\\', '\r', '\n']) => {
+            next.to_string()
+        }
+        _ => home,
+    }
+}
+
+fn login_response(config: &AppConfig, next: &str, error: Option<&str>) -> HttpResponse {
+    let mut context = page_context(config);
+    context.insert("next", next);
+    context.insert("error", &error);
+    let mut res = config.templates.respond("login.html", &context, "text/html; charset=utf-8");
+    if error.is_some() && res.status().is_success() {
+        *res.status_mut() = actix_web::http::StatusCode::UNAUTHORIZED;
+    }
+    res
+}
+
+#[derive(Deserialize)]
+pub struct LoginQuery {
+    next: Option<String>,
+}
+
+#[get("/login")]
+pub async fn login_page(req: HttpRequest, query: web::Query<LoginQuery>, config: web::Data<AppConfig>) -> HttpResponse {
+    if config.settings.admin_token.is_none() {
+        return AdminAuthError::Disabled.error_response();
+    }
+    login_response(&config, &next_path(&req, query.next.as_deref()), None)
+}
+
+#[derive(Deserialize)]
+pub struct LoginForm {
+    token: String,
+    next: Option<String>,
+}
+
+#[post("/login")]
+pub async fn login(req: HttpRequest, form: web::Form<LoginForm>, config: web::Data<AppConfig>) -> HttpResponse {
+    let Some(token) = config.settings.admin_token.as_deref() else {
+        return AdminAuthError::Disabled.error_response();
+    };
+    let next = next_path(&req, form.next.as_deref());
+    let value = if crate::admin::constant_time_eq(form.token.as_bytes(), token.as_bytes()) {
+        issue(token, config.settings.session_lifetime, None)
+    } else if let Some(key) = config.api_keys.authenticate(&form.token) {
+        issue(token, config.settings.session_lifetime, Some(&key.id))
+    } else {
+        auth_log::record(&req);
+        return login_response(&config, &next, Some("Wrong admin token or API key"));
+    };
+    HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, next))
+        .cookie(session_cookie(&req, value, config.settings.session_lifetime))
+        .finish()
+}
+
+/// 注销当前会话并删除 Cookie，之后回到画廊首页
+#[post("/logout")]
+pub async fn logout(req: HttpRequest, config: web::Data<AppConfig>) -> HttpResponse {
+    if let Some(session) = current(&config, &req) {
+        let sessions = config.clone();
+        let result = web::block(move || sessions.sessions.revoke(&session)).await;
+        if let Ok(Err(e)) = result {
+            log::warn!("保存注销的会话失败: {}", e);
+        }
+    }
+    let mut removal = session_cookie(&req, String::new(), 0);
+    removal.make_removal();
+    HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, cookie_path(&req)))
+        .cookie(removal)
+        .finish()
+}
+
+#[derive(Serialize)]
+struct SessionStatus {
+    /// 是否配置了管理令牌，没有时不能登录
+    enabled: bool,
+    authenticated: bool,
+    expires_at: Option<String>,
+    /// 用属于某个用户的 API 密钥登录时为该用户
+    user: Option<String>,
+}
+
+/// 当前的登录状态，页面据此显示登录或注销按钮
+#[get("/api/session")]
+pub async fn status(req: HttpRequest, config: web::Data<AppConfig>) -> HttpResponse {
+    let session = current(&config, &req);
+    let user = session
+        .as_ref()
+        .and_then(|session| session.key.as_deref())
+        .and_then(|key| config.api_keys.get(key))
+        .and_then(|key| key.user);
+    HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(SessionStatus {
+            enabled: config.settings.admin_token.is_some(),
+            authenticated: session.is_some(),
+            expires_at: session.map(|s| format_time(UNIX_EPOCH + Duration::from_secs(s.expires))),
+            user,
+        })
+}
@@ -0,0 +1,275 @@
+//! 分享页：`/view/{path}` 为单张图片提供带 OpenGraph/Twitter Card 元数据的页面，
+//! 链接贴到聊天软件和社交网络时能显示预览；`/api/oembed` 供支持 oEmbed 的平台嵌入图片；
+//! `/api/links/{path}` 返回可直接粘贴的各种链接格式
+
+use crate::errors::ApiError;
+use crate::homes::Viewer;
+use crate::{base_url, dimensions, html_escape, sniff, url_path, AppConfig};
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpRequest, HttpResponse, ResponseError, Result};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+
+fn file_name(relative: &str) -> &str {
+    relative.rsplit('/').next().unwrap_or(relative)
+}
+
+/// 读取图片尺寸（只读取文件头），WebDAV 来源不读取
+async fn dimensions(config: &AppConfig, relative: &str) -> Result<Option<(u32, u32)>> {
+    if config.webdav.is_some() {
+        return Ok(None);
+    }
+    let file = config.fs_path(relative);
+    Ok(web::block(move || dimensions::read(&file)).await?)
+}
+
+/// 图片类型：本地图片按内容判断，WebDAV 来源按扩展名
+async fn mime_type(config: &AppConfig, relative: &str) -> Result<String> {
+    let guessed = mime_guess::from_path(relative).first_or_octet_stream().essence_str().to_string();
+    if config.webdav.is_some() {
+        return Ok(guessed);
+    }
+    let file = config.fs_path(relative);
+    let sniffed = web::block(move || sniff::file_mime(&file)).await?;
+    Ok(sniffed.ok().map_or(guessed, |mime| mime.unwrap_or(sniff::UNKNOWN).to_string()))
+}
+
+/// 按比例缩小到不超过给定宽高，不会放大
+fn fit((width, height): (u32, u32), max_width: Option<u32>, max_height: Option<u32>) -> (u32, u32) {
+    let mut ratio: f64 = 1.0;
+    if let Some(max) = max_width.filter(|&m| m > 0 && m < width) {
+        ratio = ratio.min(max as f64 / width as f64);
+    }
+    if let Some(max) = max_height.filter(|&m| m > 0 && m < height) {
+        ratio = ratio.min(max as f64 / height as f64);
+    }
+    (
+        ((width as f64 * ratio).round() as u32).max(1),
+        ((height as f64 * ratio).round() as u32).max(1),
+    )
+}
+
+#[get("/view/{path:.*}")]
+pub async fn view(
+    req: HttpRequest,
+    path: web::Path<String>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let relative = path.into_inner();
+    if !config.image_exists(&relative).await? {
+        return Ok(HttpResponse::NotFound().body("Image not found"));
+    }
+
+    let dimensions = dimensions(&config, &relative).await?;
+
+    let base = base_url(&req);
+    let encoded = url_path(&relative);
+    let page_url = format!("{}/view/{}", base, encoded);
+    let image_url = format!("{}{}", base, config.pic_url(&relative));
+    let oembed_url = format!(
+        "{}/api/oembed?url={}&format=json",
+        base,
+        utf8_percent_encode(&page_url, NON_ALPHANUMERIC)
+    );
+    let title = html_escape(file_name(&relative));
+    let mime = mime_type(&config, &relative).await?;
+    let size_meta = dimensions
+        .map(|(w, h)| {
+            format!(
+                r#"
+    <meta property="og:image:width" content="{}">
+    <meta property="og:image:height" content="{}">"#,
+                w, h
+            )
+        })
+        .unwrap_or_default();
+
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>{title}</title>
+    <meta property="og:type" content="website">
+    <meta property="og:title" content="{title}">
+    <meta property="og:url" content="{page_url}">
+    <meta property="og:image" content="{image_url}">
+    <meta property="og:image:type" content="{mime}">{size_meta}
+    <meta name="twitter:card" content="summary_large_image">
+    <meta name="twitter:title" content="{title}">
+    <meta name="twitter:image" content="{image_url}">
+    <link rel="alternate" type="application/json+oembed" href="{oembed_url}" title="{title}">
+    <style>
+        * {{ margin: 0; padding: 0; box-sizing: border-box; }}
+        body {{
+            font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif;
+            background: #0a0a0f;
+            color: #ccc;
+            min-height: 100vh;
+            display: flex;
+            flex-direction: column;
+            align-items: center;
+            justify-content: center;
+            gap: 16px;
+            padding: 20px;
+        }}
+        img {{ max-width: 100%; max-height: calc(100vh - 90px); object-fit: contain; }}
+        .bar {{ display: flex; gap: 20px; font-size: 14px; }}
+        a {{ color: #888; text-decoration: none; }}
+        a:hover {{ color: #fff; }}
+    </style>
+</head>
+<body>
+    <img src="{image_url}" alt="{title}">
+    <div class="bar">
+        <span>{title}</span>
+        <a href="{image_url}">Original</a>
+        <a href="{gallery_url}">Gallery</a>
+    </div>
+</body>
+</html>"#,
+        title = title,
+        page_url = html_escape(&page_url),
+        image_url = html_escape(&image_url),
+        gallery_url = html_escape(&format!("{}/", base)),
+        oembed_url = html_escape(&oembed_url),
+        mime = mime,
+        size_meta = size_meta,
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html))
+}
+
+#[derive(Deserialize)]
+pub struct OEmbedQuery {
+    url: String,
+    maxwidth: Option<u32>,
+    maxheight: Option<u32>,
+    format: Option<String>,
+}
+
+#[derive(Serialize)]
+struct OEmbedResponse {
+    version: &'static str,
+    /// 知道图片尺寸时为 `photo`，否则为 `link`
+    #[serde(rename = "type")]
+    kind: &'static str,
+    title: String,
+    provider_name: &'static str,
+    provider_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<u32>,
+    thumbnail_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thumbnail_width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thumbnail_height: Option<u32>,
+}
+
+/// 从分享页地址中取出图片相对路径，只看路径部分，不校验主机名
+fn view_path(url: &str) -> Option<String> {
+    let without_scheme = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    let path = &without_scheme[without_scheme.find('/')?..];
+    let path = path.split(['?', '#']).next()?;
+    // `--base-url` 可能带路径前缀，如 https://example.com/pics/view/a.png
+    let encoded = &path[path.find("/view/")? + "/view/".len()..];
+    percent_decode_str(encoded).decode_utf8().ok().map(|p| p.into_owned())
+}
+
+#[get("/api/oembed")]
+pub async fn oembed(
+    req: HttpRequest,
+    query: web::Query<OEmbedQuery>,
+    viewer: Viewer,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let query = query.into_inner();
+    if !matches!(query.format.as_deref(), None | Some("json")) {
+        return Err(ApiError::new(StatusCode::NOT_IMPLEMENTED, "only json format is supported").into());
+    }
+    let relative = match view_path(&query.url) {
+        Some(relative) if viewer.can_see(&relative) && config.image_exists(&relative).await? => relative,
+        _ => return Err(ApiError::not_found("Image not found").into()),
+    };
+
+    let base = base_url(&req);
+    let encoded = url_path(&relative);
+    let dimensions = dimensions(&config, &relative).await?;
+    let size = dimensions.map(|d| fit(d, query.maxwidth, query.maxheight));
+    let thumbnail = dimensions.map(|d| {
+        // 缩略图总是把长边缩放到 thumb_size
+        let ratio = config.settings.thumb_size as f64 / d.0.max(d.1) as f64;
+        fit(
+            (((d.0 as f64) * ratio) as u32, ((d.1 as f64) * ratio) as u32),
+            query.maxwidth,
+            query.maxheight,
+        )
+    });
+
+    Ok(HttpResponse::Ok().json(OEmbedResponse {
+        version: "1.0",
+        kind: if size.is_some() { "photo" } else { "link" },
+        title: file_name(&relative).to_string(),
+        provider_name: "pic_url",
+        provider_url: format!("{}/", base),
+        url: size.map(|_| format!("{}{}", base, config.pic_url(&relative))),
+        width: size.map(|s| s.0),
+        height: size.map(|s| s.1),
+        thumbnail_url: format!("{}/thumb/{}", base, encoded),
+        thumbnail_width: thumbnail.map(|s| s.0),
+        thumbnail_height: thumbnail.map(|s| s.1),
+    }))
+}
+
+#[derive(Serialize)]
+struct LinksResponse {
+    path: String,
+    url: String,
+    view: String,
+    thumbnail: String,
+    markdown: String,
+    html: String,
+    bbcode: String,
+    /// 短链接 `/i/{id}`，图片还没有被扫描到时没有
+    #[serde(skip_serializing_if = "Option::is_none")]
+    short: Option<String>,
+}
+
+#[get("/api/links/{path:.*}")]
+pub async fn links(
+    req: HttpRequest,
+    path: web::Path<String>,
+    config: web::Data<AppConfig>,
+) -> HttpResponse {
+    let relative = path.into_inner();
+    match config.image_exists(&relative).await {
+        Ok(true) => {}
+        Ok(false) => return ApiError::not_found("Image not found").error_response(),
+        Err(e) => return e.error_response(),
+    }
+
+    let base = base_url(&req);
+    let encoded = url_path(&relative);
+    let url = format!("{}{}", base, config.pic_url(&relative));
+    let name = file_name(&relative);
+    // Markdown 的替代文字中不能出现方括号
+    let alt = name.replace(['[', ']'], "");
+
+    HttpResponse::Ok().json(LinksResponse {
+        markdown: format!("![{}]({})", alt, url),
+        html: format!(r#"<img src="{}" alt="{}">"#, html_escape(&url), html_escape(name)),
+        bbcode: format!("[img]{}[/img]", url),
+        view: format!("{}/view/{}", base, encoded),
+        thumbnail: format!("{}/thumb/{}", base, encoded),
+        short: config.ids.id_of(&relative).map(|id| format!("{}{}", base, config.short_url(&id))),
+        path: relative,
+        url,
+    })
+}
@@ -0,0 +1,207 @@
+//! 附属文件 (`--sidecars`)：通过接口删除或移动图片时，一起处理同目录下属于它的元数据文件和
+//! RAW 文件，图库中不会留下孤立的文件。
+//!
+//! - `IMG_1.JPG.xmp`、Google Takeout 的 `IMG_1.JPG.json`、`IMG_1.JPG.supplemental-metadata.json`
+//!   （包括 Takeout 截短的名称和 `IMG_1.JPG(1).json` 这样的编号）只属于这张图片
+//! - `IMG_1.xmp`、`IMG_1.json`、`IMG_1.AAE` 和 `IMG_1.CR2` 等 RAW 文件按主文件名匹配；同目录下还有
+//!   同名的其他图片（如 `IMG_1.png`）时属于哪张不确定，不处理
+
+use crate::aliases::Aliases;
+use crate::is_image_file;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// 按主文件名匹配的元数据文件
+const METADATA_EXTENSIONS: [&str; 3] = ["xmp", "aae", "json"];
+/// 与图片成对保存的 RAW 文件
+const RAW_EXTENSIONS: [&str; 17] = [
+    "dng", "cr2", "cr3", "crw", "nef", "nrw", "arw", "srf", "sr2", "raf", "orf", "rw2", "pef", "srw", "x3f", "3fr",
+    "iiq",
+];
+/// Google Takeout 元数据文件名中图片名之后的部分，过长时会被截短
+const TAKEOUT_SUFFIX: &str = ".supplemental-metadata";
+/// 图片名很长时 Takeout 连图片名一起截短（不含 `.json` 约 46 个字符）；
+/// 至少这么长才按截短的名称匹配，避免 `IMG_1.json` 这样的短名称被当作 `IMG_1.JPG` 截短的结果
+const TRUNCATED_LEN: usize = 40;
+
+/// 删除或移动图片时如何处理附属文件
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SidecarPolicy {
+    /// 只处理图片本身
+    Off,
+    /// XMP、JSON、AAE 等元数据文件跟随图片
+    Metadata,
+    /// 元数据文件和同名的 RAW 文件都跟随图片
+    All,
+}
+
+impl SidecarPolicy {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "off" => Some(SidecarPolicy::Off),
+            "metadata" => Some(SidecarPolicy::Metadata),
+            "all" => Some(SidecarPolicy::All),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Kind {
+    /// 文件名中包含完整的图片名，只属于这张图片
+    Own,
+    /// 按主文件名匹配的元数据文件
+    Metadata,
+    Raw,
+}
+
+fn split_ext(name: &str) -> (&str, String) {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) => (stem, ext.to_ascii_lowercase()),
+        None => (name, String::new()),
+    }
+}
+
+/// `candidate` 是否是图片名为 `image` 的 XMP 或 Takeout JSON
+fn is_own(image: &str, candidate: &str) -> bool {
+    let (stem, ext) = split_ext(candidate);
+    match ext.as_str() {
+        "xmp" => stem == image,
+        "json" => {
+            stem == image
+                || (format!("{}{}", image, TAKEOUT_SUFFIX).starts_with(stem)
+                    && (stem.len() > image.len() || stem.chars().count() >= TRUNCATED_LEN))
+        }
+        _ => false,
+    }
+}
+
+/// `candidate` 与图片 `image` 的关系，两者在同一目录
+fn kind(image: &str, candidate: &str) -> Option<Kind> {
+    if candidate == image {
+        return None;
+    }
+    if is_own(image, candidate) {
+        return Some(Kind::Own);
+    }
+    // Takeout 给重名的图片编号为 `IMG_1(1).JPG`，元数据却是 `IMG_1.JPG(1).json`
+    let (image_stem, image_ext) = image.rsplit_once('.').unwrap_or((image, ""));
+    if let Some((base, number)) = image_stem.strip_suffix(')').and_then(|s| s.rsplit_once('(')) {
+        if !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()) {
+            let suffix = format!("({}).json", number);
+            if let Some(stem) = candidate.strip_suffix(suffix.as_str()) {
+                if is_own(&format!("{}.{}", base, image_ext), &format!("{}.json", stem)) {
+                    return Some(Kind::Own);
+                }
+            }
+        }
+    }
+    let (stem, ext) = split_ext(candidate);
+    if stem != image_stem {
+        None
+    } else if METADATA_EXTENSIONS.contains(&ext.as_str()) {
+        Some(Kind::Metadata)
+    } else if RAW_EXTENSIONS.contains(&ext.as_str()) {
+        Some(Kind::Raw)
+    } else {
+        None
+    }
+}
+
+/// 查找附属文件，同一批操作中每个目录只列出一次
+pub struct Finder<'a> {
+    pic_dir: &'a Path,
+    /// 图片目录中的别名，别名目录中的附属文件在别名指向的目录中查找
+    aliases: &'a Aliases,
+    policy: SidecarPolicy,
+    /// 目录 → 其中的文件名
+    listings: HashMap<String, Vec<String>>,
+}
+
+impl<'a> Finder<'a> {
+    pub fn new(pic_dir: &'a Path, aliases: &'a Aliases, policy: SidecarPolicy) -> Self {
+        Self {
+            pic_dir,
+            aliases,
+            policy,
+            listings: HashMap::new(),
+        }
+    }
+
+    fn listing(&mut self, folder: &str) -> &mut Vec<String> {
+        let dir = self.aliases.fs_path(self.pic_dir, folder);
+        self.listings.entry(folder.to_string()).or_insert_with(|| {
+            let Ok(entries) = fs::read_dir(dir) else {
+                return Vec::new();
+            };
+            entries
+                .flatten()
+                .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+                .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+                .collect()
+        })
+    }
+
+    /// 属于图片 `relative` 的附属文件的相对路径
+    pub fn find(&mut self, relative: &str) -> Vec<String> {
+        if self.policy == SidecarPolicy::Off {
+            return Vec::new();
+        }
+        let (folder, image) = relative.rsplit_once('/').unwrap_or(("", relative));
+        let policy = self.policy;
+        let listing = self.listing(folder);
+        let (image_stem, _) = split_ext(image);
+        // 同目录下有同名的其他图片时，按主文件名匹配的文件不知道属于哪一张
+        let shared = listing
+            .iter()
+            .any(|name| name != image && split_ext(name).0 == image_stem && is_image_file(Path::new(name)));
+        listing
+            .iter()
+            .filter(|name| match kind(image, name) {
+                Some(Kind::Own) => true,
+                Some(Kind::Metadata) => !shared,
+                Some(Kind::Raw) => !shared && policy == SidecarPolicy::All,
+                None => false,
+            })
+            .map(|name| if folder.is_empty() { name.clone() } else { format!("{}/{}", folder, name) })
+            .collect()
+    }
+
+    /// 文件已经删除或移走，之后的查找不再考虑它
+    pub fn forget(&mut self, relative: &str) {
+        let (folder, name) = relative.rsplit_once('/').unwrap_or(("", relative));
+        if let Some(listing) = self.listings.get_mut(folder) {
+            listing.retain(|existing| existing != name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_sidecar_names() {
+        assert_eq!(kind("IMG_1.JPG", "IMG_1.JPG.xmp"), Some(Kind::Own));
+        assert_eq!(kind("IMG_1.JPG", "IMG_1.JPG.XMP"), Some(Kind::Own));
+        assert_eq!(kind("IMG_1.JPG", "IMG_1.JPG.json"), Some(Kind::Own));
+        assert_eq!(kind("IMG_1.JPG", "IMG_1.JPG.supplemental-metadata.json"), Some(Kind::Own));
+        assert_eq!(kind("IMG_1.JPG", "IMG_1.JPG.supplemen.json"), Some(Kind::Own));
+        assert_eq!(kind("IMG_1(1).JPG", "IMG_1.JPG(1).json"), Some(Kind::Own));
+        assert_eq!(kind("IMG_1(1).JPG", "IMG_1.JPG.supplemental-metadata(1).json"), Some(Kind::Own));
+        assert_eq!(kind("IMG_1.JPG", "IMG_1.xmp"), Some(Kind::Metadata));
+        assert_eq!(kind("IMG_1.JPG", "IMG_1.AAE"), Some(Kind::Metadata));
+        assert_eq!(kind("IMG_1.JPG", "IMG_1.json"), Some(Kind::Metadata));
+        let long = "Screenshot_20200119-133542_Samsung Internet.jpg";
+        assert_eq!(kind(long, "Screenshot_20200119-133542_Samsung Inter.json"), Some(Kind::Own));
+        assert_eq!(kind("IMG_1.JPG", "IMG_1.CR2"), Some(Kind::Raw));
+        assert_eq!(kind("IMG_1.JPG", "IMG_1.JPG"), None);
+        assert_eq!(kind("IMG_1.JPG", "IMG_10.JPG.json"), None);
+        assert_eq!(kind("IMG_1.JPG", "IMG_1.JPG.other.json"), None);
+        assert_eq!(kind("IMG_1.JPG", "IMG_1.png"), None);
+        assert_eq!(kind("IMG_1(1).JPG", "IMG_1.JPG.json"), None);
+    }
+}
@@ -0,0 +1,83 @@
+//! 原图的签名地址 (`--originals-secret`)：开启后 `/pic/...` 和 `/i/{id}` 必须带
+//! `expires`（Unix 秒）和 `sig` 查询参数，像 CDN 或对象存储的签名 URL 一样限制原图的访问。
+//!
+//! 签名为 HMAC-SHA256(`"{expires}:{路径}"`) 的十六进制，路径是画廊挂载前缀之后、未转义的部分，
+//! 如 `/pic/旅行/北京.jpg`。服务器自己生成的链接把过期时间对齐到有效期的整数倍，
+//! 同一时间段内同一张图片的地址相同，CDN 可以按地址缓存
+
+use crate::url_path;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 服务器生成的链接默认的有效期
+pub const DEFAULT_TTL: u64 = 3600;
+
+#[derive(Deserialize)]
+pub struct SignedQuery {
+    expires: Option<u64>,
+    sig: Option<String>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn mac(secret: &str, path: &str, expires: u64) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("{}:{}", expires, path).as_bytes());
+    mac
+}
+
+/// `expires` 时过期的查询参数，不含 `?`
+pub fn query(secret: &str, path: &str, expires: u64) -> String {
+    format!(
+        "expires={}&sig={}",
+        expires,
+        hex::encode(mac(secret, path, expires).finalize().into_bytes())
+    )
+}
+
+/// `secs` 秒后过期
+pub fn expires_in(secs: u64) -> u64 {
+    now_secs().saturating_add(secs)
+}
+
+/// 服务器生成链接时使用的过期时间：对齐到 `ttl` 的整数倍，剩余有效期在 `ttl` 到 `2 * ttl` 之间
+pub fn expires_for(ttl: u64) -> u64 {
+    let ttl = ttl.max(1);
+    (now_secs() / ttl + 2) * ttl
+}
+
+/// 服务器生成的原图地址（不含挂载前缀），`secret` 为 `None` 时不签名
+pub fn pic_url(secret: Option<&str>, ttl: u64, relative: &str) -> String {
+    match secret {
+        Some(secret) => signed_path(secret, relative, expires_for(ttl)),
+        None => format!("/pic/{}", url_path(relative)),
+    }
+}
+
+/// 带签名的原图路径（不含挂载前缀）
+pub fn signed_path(secret: &str, relative: &str, expires: u64) -> String {
+    format!(
+        "/pic/{}?{}",
+        url_path(relative),
+        query(secret, &format!("/pic/{}", relative), expires)
+    )
+}
+
+/// 检查签名，通过时返回剩余的有效秒数
+pub fn verify(secret: &str, path: &str, query: &SignedQuery) -> Result<u64, &'static str> {
+    let (Some(expires), Some(sig)) = (query.expires, query.sig.as_deref()) else {
+        return Err("Signature required");
+    };
+    let valid = hex::decode(sig).is_ok_and(|sig| mac(secret, path, expires).verify_slice(&sig).is_ok());
+    if !valid {
+        return Err("Invalid signature");
+    }
+    match expires.checked_sub(now_secs()) {
+        Some(remaining) if remaining > 0 => Ok(remaining),
+        _ => Err("Signature expired"),
+    }
+}
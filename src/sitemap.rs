@@ -0,0 +1,131 @@
+//! 搜索引擎支持：`/sitemap.xml` 根据索引列出所有图片的分享页，`/robots.txt` 按 `--robots` 配置生成
+
+use crate::homes::Viewer;
+use crate::{base_url, html_escape, url_path, AppConfig};
+use actix_web::{get, web, HttpRequest, HttpResponse, Result};
+use std::fs;
+
+/// 单个 sitemap 文件最多包含的 URL 数（sitemaps.org 协议的上限）
+const URLS_PER_SITEMAP: usize = 50_000;
+
+/// 图片的最后修改日期 (YYYY-MM-DD)，WebDAV 来源不提供
+fn last_modified(config: &AppConfig, relative: &str) -> Option<String> {
+    if config.webdav.is_some() {
+        return None;
+    }
+    let modified = fs::metadata(config.fs_path(relative))
+        .and_then(|m| m.modified())
+        .ok()?;
+    crate::format_time(modified).get(..10).map(str::to_string)
+}
+
+fn xml_response(body: String) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/xml; charset=utf-8")
+        .body(body)
+}
+
+/// 生成包含 `images` 中图片分享页的 `<urlset>`，`with_home` 时加上画廊首页
+fn urlset(config: &AppConfig, base: &str, images: &[String], with_home: bool) -> String {
+    let mut body = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        "\n",
+        r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9" "#,
+        r#"xmlns:image="http://www.google.com/schemas/sitemap-image/1.1">"#,
+        "\n"
+    ));
+    if with_home {
+        body.push_str(&format!("  <url><loc>{}/</loc></url>\n", html_escape(base)));
+    }
+    for relative in images {
+        let encoded = url_path(relative);
+        body.push_str(&format!(
+            "  <url><loc>{}</loc>",
+            html_escape(&format!("{}/view/{}", base, encoded))
+        ));
+        if let Some(date) = last_modified(config, relative) {
+            body.push_str(&format!("<lastmod>{}</lastmod>", date));
+        }
+        // 原图需要签名时不列出：签名会过期，不适合给搜索引擎收录
+        if config.settings.originals_secret.is_none() {
+            body.push_str(&format!(
+                "<image:image><image:loc>{}</image:loc></image:image>",
+                html_escape(&format!("{}/pic/{}", base, encoded))
+            ));
+        }
+        body.push_str("</url>\n");
+    }
+    body.push_str("</urlset>\n");
+    body
+}
+
+#[get("/sitemap.xml")]
+pub async fn sitemap(req: HttpRequest, config: web::Data<AppConfig>) -> Result<HttpResponse> {
+    let base = base_url(&req);
+    let body = web::block(move || {
+        let images = Viewer::public(&config).filter(config.current_images());
+        if images.len() < URLS_PER_SITEMAP {
+            return urlset(&config, &base, &images, true);
+        }
+        // 超过上限时返回索引文件，指向分页的 /sitemap-{n}.xml
+        let mut body = String::from(concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            "\n",
+            r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#,
+            "\n"
+        ));
+        for page in 1..=images.len().div_ceil(URLS_PER_SITEMAP) {
+            body.push_str(&format!(
+                "  <sitemap><loc>{}</loc></sitemap>\n",
+                html_escape(&format!("{}/sitemap-{}.xml", base, page))
+            ));
+        }
+        body.push_str("</sitemapindex>\n");
+        body
+    })
+    .await?;
+    Ok(xml_response(body))
+}
+
+#[get("/sitemap-{page:\\d+}.xml")]
+pub async fn sitemap_page(
+    req: HttpRequest,
+    page: web::Path<usize>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let page = page.into_inner();
+    let base = base_url(&req);
+    let body = web::block(move || {
+        let images = Viewer::public(&config).filter(config.current_images());
+        let start = page.checked_sub(1)?.checked_mul(URLS_PER_SITEMAP)?;
+        let chunk = images.get(start..)?;
+        let chunk = &chunk[..chunk.len().min(URLS_PER_SITEMAP)];
+        (!chunk.is_empty()).then(|| urlset(&config, &base, chunk, page == 1))
+    })
+    .await?;
+    Ok(match body {
+        Some(body) => xml_response(body),
+        None => HttpResponse::NotFound().body("Sitemap not found"),
+    })
+}
+
+#[get("/robots.txt")]
+pub async fn robots(req: HttpRequest, config: web::Data<AppConfig>) -> Result<HttpResponse> {
+    let body = match config.settings.robots.as_str() {
+        "allow" => format!(
+            "User-agent: *\nDisallow: {mount}/api/\nDisallow: /dav/\nDisallow: {mount}/proxy\nAllow: {mount}/\n\n\
+             Sitemap: {}/sitemap.xml\n",
+            base_url(&req),
+            mount = config.settings.mount_path,
+        ),
+        "disallow" => format!("User-agent: *\nDisallow: {}/\n", config.settings.mount_path),
+        // 每次请求时读取，修改文件后不需要重启
+        file => {
+            let file = file.to_string();
+            web::block(move || fs::read_to_string(file)).await??
+        }
+    };
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .body(body))
+}
@@ -0,0 +1,261 @@
+//! 图库快照 (`GET /api/export.json`)：一次取回全部图片的路径、内容哈希、元数据、标签和智能相册，
+//! 用于备份元数据，或由静态站点生成器根据这份数据自行渲染画廊。
+//!
+//! 输出是稳定的：图片按路径排序，标签和相册按名称排序，不含生成时间，图库没有变化时内容逐字节相同，
+//! 可以直接放进版本库比较差异。`ETag` 由路径、修改时间、大小、短 ID、标签和相册算出，不必生成快照，
+//! 客户端用 `If-None-Match` 询问时没有变化直接返回 304。
+//! 与列表接口一样按请求者的身份只包含能看到的图片
+//!
+//! 内容哈希优先取去重存储 `storage.json` 中的记录，其余读取文件计算，按修改时间和大小缓存在数据目录的
+//! `hashes.json` 中，重启后不必重新计算。同一时间只生成一份快照，生成期间的其他请求返回 503 和 `Retry-After`
+
+use crate::homes::Viewer;
+use crate::listing::{self, Filter};
+use crate::{cas, format_time, limit, AppConfig};
+use actix_web::http::header;
+use actix_web::{get, web, HttpRequest, HttpResponse, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// 快照格式的版本，字段有不兼容的变化时增加
+const FORMAT: u32 = 1;
+
+/// 读取时文件的修改时间、大小和 SHA-256
+type Entry = (SystemTime, u64, String);
+
+/// 内容哈希缓存：计算需要读取整个文件，按修改时间和大小缓存，文件被替换后重新计算
+pub struct Hashes {
+    path: PathBuf,
+    cache: Mutex<HashMap<String, Entry>>,
+    dirty: AtomicBool,
+    /// 同一时间只生成一份快照
+    building: Mutex<()>,
+}
+
+impl Hashes {
+    /// 从 `<data_dir>/hashes.json` 加载
+    pub fn load(data_dir: &str) -> Self {
+        let path = Path::new(data_dir).join("hashes.json");
+        let cache = fs::read(&path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            cache: Mutex::new(cache),
+            dirty: AtomicBool::new(false),
+            building: Mutex::new(()),
+        }
+    }
+
+    /// `relative` 对应的本地文件 `file` 内容的 SHA-256（十六进制），读取失败时为 `None`
+    pub fn get(&self, file: &Path, relative: &str) -> Option<String> {
+        let meta = fs::metadata(file).ok()?;
+        let (modified, size) = (meta.modified().ok()?, meta.len());
+        if let Ok(cache) = self.cache.lock() {
+            if let Some((cached_at, cached_size, hash)) = cache.get(relative) {
+                if *cached_at == modified && *cached_size == size {
+                    return Some(hash.clone());
+                }
+            }
+        }
+        let hash = cas::hash_file(file).ok()?;
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(relative.to_string(), (modified, size, hash.clone()));
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+        Some(hash)
+    }
+
+    /// 图片删除或移走后清除缓存
+    pub fn forget(&self, relative: &str) {
+        if let Ok(mut cache) = self.cache.lock() {
+            if cache.remove(relative).is_some() {
+                self.dirty.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// 有新计算的哈希时保存
+    fn save(&self) -> io::Result<()> {
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+        let data = {
+            let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+            serde_json::to_vec(&*cache).map_err(io::Error::other)?
+        };
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+#[derive(Serialize)]
+struct Snapshot {
+    format: u32,
+    /// 生成快照的服务器版本
+    version: &'static str,
+    images: Vec<ImageEntry>,
+    folders: Vec<FolderEntry>,
+    albums: Vec<AlbumEntry>,
+    tags: Vec<TagEntry>,
+}
+
+#[derive(Serialize)]
+struct ImageEntry {
+    path: String,
+    id: Option<String>,
+    /// 内容的 SHA-256，WebDAV 来源没有
+    sha256: Option<String>,
+    bytes: Option<u64>,
+    modified: Option<String>,
+    /// EXIF 中的拍摄时间，没有时为修改时间
+    taken: Option<String>,
+    camera: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    tags: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct FolderEntry {
+    path: String,
+    /// 其中（含子目录）的图片数
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct AlbumEntry {
+    name: String,
+    /// 相册保存的筛选条件
+    filter: Filter,
+    /// 当前符合条件的图片路径
+    images: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct TagEntry {
+    name: String,
+    images: Vec<String>,
+}
+
+fn image_entry(config: &AppConfig, path: &str) -> ImageEntry {
+    let (modified, bytes) = listing::metadata(config, path);
+    let (sha256, dimensions) = match config.webdav {
+        Some(_) => (None, None),
+        None => {
+            let file = config.fs_path(path);
+            let sha256 = config.cas.as_ref().and_then(|cas| cas.hash_of(path));
+            (sha256.or_else(|| config.hashes.get(&file, path)), config.dimensions.get(&file, path))
+        }
+    };
+    let capture = config.captures.get(config, path);
+    ImageEntry {
+        path: path.to_string(),
+        id: config.ids.id_of(path),
+        sha256,
+        bytes,
+        modified: modified.map(format_time),
+        taken: capture.taken,
+        camera: capture.camera,
+        width: dimensions.map(|(width, _)| width),
+        height: dimensions.map(|(_, height)| height),
+        tags: config.tags.tags_of(path),
+    }
+}
+
+/// 排好序的 `images` 的快照；第一次需要读取每个文件计算哈希，在阻塞线程中调用
+fn snapshot(config: &AppConfig, images: Vec<String>) -> Snapshot {
+    let entries: Vec<ImageEntry> = images.iter().map(|path| image_entry(config, path)).collect();
+    let mut tags: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for entry in &entries {
+        for tag in &entry.tags {
+            tags.entry(tag.clone()).or_default().push(entry.path.clone());
+        }
+    }
+    let albums = config
+        .albums
+        .list()
+        .into_iter()
+        .map(|(name, filter)| {
+            let matcher = filter.matcher();
+            let images = images.iter().filter(|path| matcher.matches(config, path)).cloned().collect();
+            AlbumEntry { name, filter, images }
+        })
+        .collect();
+    Snapshot {
+        format: FORMAT,
+        version: env!("CARGO_PKG_VERSION"),
+        folders: listing::folders(&images)
+            .into_iter()
+            .map(|(path, count)| FolderEntry { path, count })
+            .collect(),
+        images: entries,
+        albums,
+        tags: tags.into_iter().map(|(name, images)| TagEntry { name, images }).collect(),
+    }
+}
+
+/// 排好序的 `images` 的快照的 `ETag`：由快照所依据的状态算出，不读取文件内容。
+/// 拍摄时间、相机和宽高来自文件本身，随修改时间和大小变化
+fn etag(config: &AppConfig, images: &[String]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{} {}\n", FORMAT, env!("CARGO_PKG_VERSION")));
+    for path in images {
+        let (modified, bytes) = listing::metadata(config, path);
+        let id = config.ids.id_of(path);
+        let tags = config.tags.tags_of(path);
+        hasher.update(format!("{:?} {:?} {:?} {:?} {:?}\n", path, modified, bytes, id, tags));
+    }
+    hasher.update(serde_json::to_vec(&config.albums.list()).unwrap_or_default());
+    format!("\"{}\"", hex::encode(&hasher.finalize()[..16]))
+}
+
+enum Export {
+    NotModified(String),
+    Busy,
+    Body(String, Vec<u8>),
+}
+
+/// 整个图库的快照
+#[get("/api/export.json")]
+pub async fn export_json(req: HttpRequest, viewer: Viewer, config: web::Data<AppConfig>) -> Result<HttpResponse> {
+    let if_none_match = req.headers().get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()).map(str::to_string);
+    let export = web::block(move || {
+        let mut images = viewer.filter(config.current_images());
+        images.sort();
+        let etag = etag(&config, &images);
+        if if_none_match.is_some_and(|v| v.split(',').any(|tag| tag.trim() == etag)) {
+            return Ok(Export::NotModified(etag));
+        }
+        let Some(_building) = config.hashes.building.try_lock().ok() else {
+            return Ok(Export::Busy);
+        };
+        let body = serde_json::to_vec(&snapshot(&config, images))?;
+        if let Err(e) = config.hashes.save() {
+            log::warn!("保存内容哈希缓存失败: {}", e);
+        }
+        Ok::<_, serde_json::Error>(Export::Body(etag, body))
+    })
+    .await??;
+    Ok(match export {
+        Export::NotModified(etag) => HttpResponse::NotModified().insert_header((header::ETAG, etag)).finish(),
+        Export::Busy => limit::busy(),
+        Export::Body(etag, body) => HttpResponse::Ok()
+            .content_type("application/json")
+            .insert_header((header::ETAG, etag))
+            .insert_header((header::CACHE_CONTROL, "no-cache"))
+            .body(body),
+    })
+}
@@ -0,0 +1,48 @@
+//! 按文件内容（开头的魔数）判断图片的 MIME 类型，而不是看扩展名：批量改名后扩展名不对的图片
+//! （如实际是 PNG 的 `.jpg`）仍能正常显示，扩展名像图片而内容不是图片的文件不会被当作图片返回
+
+use image::ImageFormat;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// 判断格式需要读取的字节数
+const HEAD_LEN: usize = 64;
+
+/// 不是支持的图片时使用的类型
+pub const UNKNOWN: &str = "application/octet-stream";
+
+/// 数据开头对应的图片类型，不是支持的图片格式时返回 `None`
+pub fn image_mime(data: &[u8]) -> Option<&'static str> {
+    match image::guess_format(data).ok()? {
+        ImageFormat::Jpeg => Some("image/jpeg"),
+        ImageFormat::Png => Some("image/png"),
+        ImageFormat::Gif => Some("image/gif"),
+        ImageFormat::WebP => Some("image/webp"),
+        ImageFormat::Bmp => Some("image/bmp"),
+        ImageFormat::Ico => Some("image/x-icon"),
+        _ => None,
+    }
+}
+
+/// 读取文件开头判断图片类型
+pub fn file_mime(path: &Path) -> io::Result<Option<&'static str>> {
+    let mut head = Vec::with_capacity(HEAD_LEN);
+    File::open(path)?.take(HEAD_LEN as u64).read_to_end(&mut head)?;
+    Ok(image_mime(&head))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_formats_by_content() {
+        assert_eq!(image_mime(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), Some("image/png"));
+        assert_eq!(image_mime(b"\xff\xd8\xff\xe0\0\x10JFIF"), Some("image/jpeg"));
+        assert_eq!(image_mime(b"GIF89a"), Some("image/gif"));
+        assert_eq!(image_mime(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(image_mime(b"<html><script>"), None);
+        assert_eq!(image_mime(b""), None);
+    }
+}
@@ -0,0 +1,156 @@
+//! 图库统计：`/api/stats` 汇总每月拍摄数量、各目录占用的空间、常用相机和格式，
+//! `/stats` 页面把它们画成柱状图
+
+use crate::homes::Viewer;
+use crate::listing::{self, ImageListQuery};
+use crate::{page_context, AppConfig};
+use actix_web::{get, web, HttpResponse, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// 相机列表最多返回的数量，其余的合并为 `other_cameras`
+const TOP_CAMERAS: usize = 10;
+
+#[derive(Serialize)]
+struct Stats {
+    count: usize,
+    bytes: u64,
+    /// 每月拍摄的图片数，按月份从早到晚
+    months: Vec<MonthCount>,
+    /// 没有日期的图片数
+    undated: usize,
+    /// 顶层目录占用的空间，从大到小；根目录下的图片为 `""`
+    folders: Vec<FolderUsage>,
+    /// 图片数最多的相机，从多到少
+    cameras: Vec<CameraCount>,
+    /// 不在 `cameras` 中的相机拍摄的图片数
+    other_cameras: usize,
+    /// 没有相机信息的图片数
+    unknown_camera: usize,
+    /// 各格式的图片数和大小，从多到少
+    formats: Vec<FormatUsage>,
+}
+
+#[derive(Serialize)]
+struct MonthCount {
+    month: String,
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct FolderUsage {
+    folder: String,
+    count: usize,
+    bytes: u64,
+}
+
+#[derive(Serialize)]
+struct CameraCount {
+    camera: String,
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct FormatUsage {
+    format: String,
+    count: usize,
+    bytes: u64,
+}
+
+fn collect(config: &AppConfig, images: &[String]) -> Stats {
+    let mut bytes = 0;
+    let mut months = BTreeMap::<String, usize>::new();
+    let mut undated = 0;
+    let mut folders = HashMap::<String, (usize, u64)>::new();
+    let mut cameras = HashMap::<String, usize>::new();
+    let mut unknown_camera = 0;
+    let mut formats = HashMap::<String, (usize, u64)>::new();
+    for path in images {
+        let size = listing::metadata(config, path).1.unwrap_or(0);
+        bytes += size;
+
+        let capture = config.captures.get(config, path);
+        match &capture.taken {
+            Some(taken) => *months.entry(taken[..7].to_string()).or_insert(0) += 1,
+            None => undated += 1,
+        }
+        match capture.camera {
+            Some(camera) => *cameras.entry(camera).or_insert(0) += 1,
+            None => unknown_camera += 1,
+        }
+
+        let folder = path.split_once('/').map_or("", |(folder, _)| folder);
+        let usage = folders.entry(folder.to_string()).or_insert((0, 0));
+        usage.0 += 1;
+        usage.1 += size;
+
+        let ext = Path::new(path)
+            .extension()
+            .map(|ext| listing::normalize_ext(&ext.to_string_lossy()))
+            .unwrap_or_default();
+        let usage = formats.entry(ext).or_insert((0, 0));
+        usage.0 += 1;
+        usage.1 += size;
+    }
+
+    let mut folders: Vec<FolderUsage> = folders
+        .into_iter()
+        .map(|(folder, (count, bytes))| FolderUsage { folder, count, bytes })
+        .collect();
+    folders.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.folder.cmp(&b.folder)));
+
+    let mut cameras: Vec<CameraCount> = cameras
+        .into_iter()
+        .map(|(camera, count)| CameraCount { camera, count })
+        .collect();
+    cameras.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.camera.cmp(&b.camera)));
+    let other_cameras = cameras.iter().skip(TOP_CAMERAS).map(|c| c.count).sum();
+    cameras.truncate(TOP_CAMERAS);
+
+    let mut formats: Vec<FormatUsage> = formats
+        .into_iter()
+        .map(|(format, (count, bytes))| FormatUsage { format, count, bytes })
+        .collect();
+    formats.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.format.cmp(&b.format)));
+
+    Stats {
+        count: images.len(),
+        bytes,
+        months: months
+            .into_iter()
+            .map(|(month, count)| MonthCount { month, count })
+            .collect(),
+        undated,
+        folders,
+        cameras,
+        other_cameras,
+        unknown_camera,
+        formats,
+    }
+}
+
+/// 支持与 `/api/images` 相同的筛选参数，如 `?folder=2024` 只统计该目录
+#[get("/api/stats")]
+pub async fn api_stats(
+    query: web::Query<ImageListQuery>,
+    viewer: Viewer,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    query.validate(&config)?;
+    let query = query.into_inner();
+    let config = config.into_inner();
+    let body = web::block(move || {
+        let images = query.apply(&config, viewer.filter(config.current_images()));
+        collect(&config, &images)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(body))
+}
+
+#[get("/stats")]
+pub async fn stats(config: web::Data<AppConfig>) -> HttpResponse {
+    config
+        .templates
+        .respond("stats.html", &page_context(&config), "text/html; charset=utf-8")
+}
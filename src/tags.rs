@@ -0,0 +1,126 @@
+//! 图片标签：保存在数据目录的 `tags.json` 中，通过批量操作添加或移除，
+//! 在 `/api/images` 中返回并可用 `tag` 参数筛选
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub struct Tags {
+    path: PathBuf,
+    by_path: Mutex<BTreeMap<String, BTreeSet<String>>>,
+}
+
+/// 去掉首尾空白，忽略空标签和包含逗号的标签（逗号用于分隔多个标签）
+pub fn normalize(tag: &str) -> Option<String> {
+    let tag = tag.trim();
+    (!tag.is_empty() && !tag.contains(',')).then(|| tag.to_string())
+}
+
+impl Tags {
+    /// 从 `<data_dir>/tags.json` 加载
+    pub fn load(data_dir: &str) -> Self {
+        let path = Path::new(data_dir).join("tags.json");
+        let by_path = fs::read(&path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            by_path: Mutex::new(by_path),
+        }
+    }
+
+    /// 写到 `to`：保存时是 `.json.tmp`，恢复备份时是 `.json.restore`，之后改名为数据文件
+    fn write(&self, by_path: &BTreeMap<String, BTreeSet<String>>, to: &Path) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_vec(by_path).map_err(io::Error::other)?;
+        fs::write(to, data)
+    }
+
+    fn save(&self, by_path: &BTreeMap<String, BTreeSet<String>>) -> io::Result<()> {
+        let tmp = self.path.with_extension("json.tmp");
+        self.write(by_path, &tmp)?;
+        fs::rename(&tmp, &self.path)
+    }
+
+    /// 修改后保存，没有标签的图片从文件中去掉
+    fn update(&self, f: impl FnOnce(&mut BTreeMap<String, BTreeSet<String>>)) -> io::Result<()> {
+        let mut by_path = self.by_path.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut by_path);
+        by_path.retain(|_, tags| !tags.is_empty());
+        self.save(&by_path)
+    }
+
+    pub fn add(&self, paths: &[String], tags: &[String]) -> io::Result<()> {
+        self.update(|by_path| {
+            for path in paths {
+                by_path.entry(path.clone()).or_default().extend(tags.iter().cloned());
+            }
+        })
+    }
+
+    pub fn remove(&self, paths: &[String], tags: &[String]) -> io::Result<()> {
+        self.update(|by_path| {
+            for path in paths {
+                if let Some(current) = by_path.get_mut(path) {
+                    for tag in tags {
+                        current.remove(tag);
+                    }
+                }
+            }
+        })
+    }
+
+    /// 图片改名或移动后，标签跟随到新路径
+    pub fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        self.update(|by_path| {
+            if let Some(tags) = by_path.remove(from) {
+                by_path.insert(to.to_string(), tags);
+            }
+        })
+    }
+
+    pub fn forget(&self, path: &str) -> io::Result<()> {
+        self.update(|by_path| {
+            by_path.remove(path);
+        })
+    }
+
+    pub fn tags_of(&self, path: &str) -> Vec<String> {
+        self.by_path
+            .lock()
+            .map(|by_path| by_path.get(path).map(|tags| tags.iter().cloned().collect()).unwrap_or_default())
+            .unwrap_or_default()
+    }
+
+    pub fn has(&self, path: &str, tag: &str) -> bool {
+        self.by_path
+            .lock()
+            .map(|by_path| by_path.get(path).is_some_and(|tags| tags.contains(tag)))
+            .unwrap_or(false)
+    }
+
+    /// 全部标签（图片路径 → 标签），用于[备份元数据](crate::metadb)
+    pub fn contents(&self) -> BTreeMap<String, BTreeSet<String>> {
+        self.by_path.lock().map(|by_path| by_path.clone()).unwrap_or_default()
+    }
+
+    /// 恢复备份的第一步：把备份中的标签写到临时文件，全部数据都写好后再 [`commit`](Self::commit)
+    pub fn stage(&self, contents: &BTreeMap<String, BTreeSet<String>>) -> io::Result<PathBuf> {
+        let staged = self.path.with_extension("json.restore");
+        self.write(contents, &staged)?;
+        Ok(staged)
+    }
+
+    /// 换上 [`stage`](Self::stage) 写好的文件，用备份中的内容替换全部标签
+    pub fn commit(&self, staged: &Path, contents: BTreeMap<String, BTreeSet<String>>) -> io::Result<()> {
+        let mut by_path = self.by_path.lock().unwrap_or_else(|e| e.into_inner());
+        fs::rename(staged, &self.path)?;
+        *by_path = contents;
+        Ok(())
+    }
+}
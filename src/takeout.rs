@@ -0,0 +1,190 @@
+//! Google Takeout 导出的照片 (`pic_url import --takeout`)：每张照片旁边有一份 JSON 元数据
+//! （`IMG_1.JPG.json`、`IMG_1.JPG.supplemental-metadata.json` 等，见 [`crate::sidecars`]），
+//! 其中的拍摄时间、位置和说明不一定写在图片的 EXIF 中；编辑过的照片另有一份 `IMG_1-edited.JPG`
+
+use crate::sidecars::Finder;
+use crate::{format_time, names};
+use exif::experimental::Writer;
+use exif::{Field, In, Rational, Tag, Value};
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// 编辑后的副本在文件名（不含扩展名）后加上的后缀，随 Google 相册的语言而不同
+const EDITED_SUFFIXES: [&str; 6] = ["-edited", "-bearbeitet", "-modifié", "-modificato", "-editado", "-編集済み"];
+
+#[derive(Deserialize)]
+struct Timestamp {
+    /// 秒数，Takeout 写成字符串
+    timestamp: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeoData {
+    latitude: f64,
+    longitude: f64,
+    #[serde(default)]
+    altitude: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Json {
+    #[serde(default)]
+    description: String,
+    photo_taken_time: Option<Timestamp>,
+    geo_data: Option<GeoData>,
+    geo_data_exif: Option<GeoData>,
+}
+
+/// JSON 中与图片有关的元数据
+#[derive(Debug, Default, PartialEq)]
+pub struct Meta {
+    pub taken: Option<SystemTime>,
+    pub description: Option<String>,
+    /// 纬度、经度、海拔（米）
+    pub gps: Option<(f64, f64, f64)>,
+}
+
+impl Meta {
+    pub fn parse(data: &[u8]) -> serde_json::Result<Self> {
+        let json: Json = serde_json::from_slice(data)?;
+        let taken = json
+            .photo_taken_time
+            .and_then(|t| t.timestamp.parse::<u64>().ok())
+            .filter(|&seconds| seconds > 0)
+            .map(|seconds| SystemTime::UNIX_EPOCH + Duration::from_secs(seconds));
+        let description = Some(json.description.trim().to_string()).filter(|d| !d.is_empty());
+        // 没有位置时经纬度都是 0
+        let gps = [json.geo_data, json.geo_data_exif]
+            .into_iter()
+            .flatten()
+            .find(|geo| geo.latitude != 0.0 || geo.longitude != 0.0)
+            .filter(|geo| geo.latitude.abs() <= 90.0 && geo.longitude.abs() <= 180.0)
+            .map(|geo| (geo.latitude, geo.longitude, geo.altitude));
+        Ok(Self {
+            taken,
+            description,
+            gps,
+        })
+    }
+
+    /// 图片 `relative`（相对于 `source`）的元数据；`finder` 在源目录中查找附属文件，
+    /// 没有 JSON 或无法解析时返回 `None`
+    pub fn find(finder: &mut Finder, source: &Path, relative: &str) -> Option<Self> {
+        finder
+            .find(relative)
+            .iter()
+            .filter(|sidecar| sidecar.to_ascii_lowercase().ends_with(".json"))
+            .find_map(|sidecar| {
+                let data = fs::read(names::fs_path(source, sidecar)).ok()?;
+                Self::parse(&data).ok()
+            })
+    }
+
+    /// 由这些元数据组成的 EXIF（TIFF 数据），用于写入没有 EXIF 的 JPEG；没有可写的内容时返回 `None`
+    pub fn exif_tiff(&self) -> Option<Vec<u8>> {
+        let mut fields = Vec::new();
+        let field = |tag, value| Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value,
+        };
+        if let Some(taken) = self.taken {
+            // Takeout 的时间是 UTC，写明时区
+            let taken = format_time(taken);
+            let date = taken.get(..19)?.replace('-', ":").replace('T', " ");
+            fields.push(field(Tag::DateTimeOriginal, Value::Ascii(vec![date.into_bytes()])));
+            fields.push(field(Tag::OffsetTimeOriginal, Value::Ascii(vec![b"+00:00".to_vec()])));
+        }
+        if let Some((latitude, longitude, altitude)) = self.gps {
+            let reference = |positive, negative, value: f64| if value < 0.0 { negative } else { positive };
+            fields.push(field(Tag::GPSVersionID, Value::Byte(vec![2, 3, 0, 0])));
+            fields.push(field(Tag::GPSLatitudeRef, Value::Ascii(vec![reference(b"N", b"S", latitude).to_vec()])));
+            fields.push(field(Tag::GPSLatitude, Value::Rational(dms(latitude))));
+            fields.push(field(Tag::GPSLongitudeRef, Value::Ascii(vec![reference(b"E", b"W", longitude).to_vec()])));
+            fields.push(field(Tag::GPSLongitude, Value::Rational(dms(longitude))));
+            fields.push(field(Tag::GPSAltitudeRef, Value::Byte(vec![u8::from(altitude < 0.0)])));
+            let altitude = Rational::from(((altitude.abs() * 100.0).round() as u32, 100));
+            fields.push(field(Tag::GPSAltitude, Value::Rational(vec![altitude])));
+        }
+        if let Some(description) = &self.description {
+            // EXIF 规定为 ASCII，常见的读取工具都按 UTF-8 显示
+            fields.push(field(Tag::ImageDescription, Value::Ascii(vec![description.as_bytes().to_vec()])));
+        }
+        if fields.is_empty() {
+            return None;
+        }
+        let mut writer = Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+        let mut tiff = io::Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).ok()?;
+        Some(tiff.into_inner())
+    }
+}
+
+/// 十进制的度数转为 EXIF 的度、分、秒（秒保留两位小数）
+fn dms(value: f64) -> Vec<Rational> {
+    let hundredths = (value.abs() * 360_000.0).round() as u64;
+    let degrees = hundredths / 360_000;
+    let minutes = hundredths / 6_000 % 60;
+    let seconds = hundredths % 6_000;
+    vec![
+        Rational::from((degrees as u32, 1)),
+        Rational::from((minutes as u32, 1)),
+        Rational::from((seconds as u32, 100)),
+    ]
+}
+
+/// `IMG_1-edited.JPG` 这样编辑后的副本对应的原图 `IMG_1.JPG`，不是副本时返回 `None`
+pub fn edited_original(relative: &str) -> Option<String> {
+    let (stem, ext) = relative.rsplit_once('.').filter(|(_, ext)| !ext.contains('/'))?;
+    let original = EDITED_SUFFIXES.iter().find_map(|suffix| stem.strip_suffix(suffix))?;
+    if original.is_empty() || original.ends_with('/') {
+        return None;
+    }
+    Some(format!("{}.{}", original, ext))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_takeout_metadata() {
+        let json = r#"{
+            "title": "IMG_1.JPG",
+            "description": "海边 ",
+            "photoTakenTime": { "timestamp": "1714566896", "formatted": "2024年5月1日 UTC 12:34:56" },
+            "geoData": { "latitude": 0.0, "longitude": 0.0, "altitude": 0.0 },
+            "geoDataExif": { "latitude": -33.8568, "longitude": 151.2153, "altitude": 12.5 }
+        }"#;
+        let meta = Meta::parse(json.as_bytes()).unwrap();
+        assert_eq!(meta.description.as_deref(), Some("海边"));
+        assert_eq!(meta.gps, Some((-33.8568, 151.2153, 12.5)));
+
+        let tiff = meta.exif_tiff().unwrap();
+        let exif = exif::Reader::new().read_raw(tiff).unwrap();
+        assert_eq!(crate::details::taken_at(&exif).as_deref(), Some("2024-05-01T12:34:56+00:00"));
+        let latitude = exif.get_field(Tag::GPSLatitude, In::PRIMARY).unwrap();
+        assert_eq!(latitude.display_value().to_string(), "33 deg 51 min 24.48 sec");
+        let reference = exif.get_field(Tag::GPSLatitudeRef, In::PRIMARY).unwrap();
+        assert_eq!(reference.display_value().to_string(), "S");
+
+        assert_eq!(Meta::parse(b"{}").unwrap(), Meta::default());
+        assert!(Meta::default().exif_tiff().is_none());
+    }
+
+    #[test]
+    fn finds_edited_originals() {
+        assert_eq!(edited_original("2024/IMG_1-edited.JPG").as_deref(), Some("2024/IMG_1.JPG"));
+        assert_eq!(edited_original("IMG_1-bearbeitet.jpg").as_deref(), Some("IMG_1.jpg"));
+        assert_eq!(edited_original("IMG_1.JPG"), None);
+        assert_eq!(edited_original("2024/-edited.JPG"), None);
+    }
+}
@@ -0,0 +1,50 @@
+//! 可选的 OpenTelemetry 链路追踪导出（需要 `otel` feature）
+//!
+//! 请求处理和缩略图流水线中的 `tracing` span 会通过 OTLP/HTTP 发送到收集器，
+//! 便于判断慢在磁盘读取、解码还是编码。
+
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use std::io;
+use tracing_subscriber::layer::SubscriberExt;
+
+/// 持有 tracer provider，drop 时把尚未发送的 span 刷出去
+pub struct Telemetry {
+    provider: SdkTracerProvider,
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            log::warn!("关闭链路追踪导出失败: {}", e);
+        }
+    }
+}
+
+/// 初始化 OTLP 导出，`endpoint` 为完整的 traces 地址，例如 `http://localhost:4318/v1/traces`
+pub fn init(endpoint: &str) -> io::Result<Telemetry> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()
+        .map_err(io::Error::other)?;
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(env!("CARGO_PKG_NAME"))
+                .build(),
+        )
+        .build();
+
+    let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+    let subscriber =
+        tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+    // 不使用 `init()`：它会接管 `log` 输出，与访问日志冲突
+    tracing::subscriber::set_global_default(subscriber).map_err(io::Error::other)?;
+
+    Ok(Telemetry { provider })
+}
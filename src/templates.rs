@@ -0,0 +1,87 @@
+//! 页面模板（Tera 语法）：内置模板编译进程序，`--templates-dir` 目录中的同名文件优先，
+//! 不用重新编译就能修改画廊页面的外观
+
+use actix_web::HttpResponse;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::Path;
+use tera::{Context, Tera};
+
+/// 内置模板，文件位于仓库的 `templates/` 目录
+const BUILTIN: [(&str, &str); 6] = [
+    ("index.html", include_str!("../templates/index.html")),
+    ("timeline.html", include_str!("../templates/timeline.html")),
+    ("stats.html", include_str!("../templates/stats.html")),
+    ("404.html", include_str!("../templates/404.html")),
+    ("login.html", include_str!("../templates/login.html")),
+    ("sw.js", include_str!("../templates/sw.js")),
+];
+
+pub struct Templates {
+    tera: Tera,
+}
+
+impl Templates {
+    /// 加载内置模板，再用自定义目录中的同名文件覆盖；目录中其他 `.html` 文件也会加载，可以被 `include`、`extends` 引用
+    pub fn load(dir: Option<&str>) -> io::Result<Self> {
+        let mut templates: Vec<(String, String)> = BUILTIN
+            .iter()
+            .map(|(name, content)| (name.to_string(), content.to_string()))
+            .collect();
+        if let Some(dir) = dir {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                let builtin = BUILTIN.iter().any(|(builtin, _)| *builtin == name);
+                if !path.is_file() || !(builtin || name.ends_with(".html")) {
+                    continue;
+                }
+                let content = fs::read_to_string(&path)?;
+                templates.retain(|(existing, _)| *existing != name);
+                templates.push((name, content));
+            }
+        }
+
+        let mut tera = Tera::default();
+        tera.add_raw_templates(templates)
+            .map_err(|e| io::Error::other(format!("模板加载失败: {}", describe(&e))))?;
+        Ok(Self { tera })
+    }
+
+    pub fn render(&self, name: &str, context: &Context) -> Result<String, String> {
+        self.tera.render(name, context).map_err(|e| describe(&e))
+    }
+
+    /// 渲染模板作为响应，出错时返回 500 并在日志中记录原因
+    pub fn respond(&self, name: &str, context: &Context, content_type: &str) -> HttpResponse {
+        match self.render(name, context) {
+            Ok(body) => HttpResponse::Ok().content_type(content_type).body(body),
+            Err(e) => {
+                log::error!("页面渲染失败: {}", e);
+                HttpResponse::InternalServerError().body(format!("Template error: {}", e))
+            }
+        }
+    }
+
+    /// 自定义目录中覆盖了的内置模板
+    pub fn overridden(dir: &str) -> Vec<&'static str> {
+        BUILTIN
+            .iter()
+            .map(|(name, _)| *name)
+            .filter(|name| Path::new(dir).join(name).is_file())
+            .collect()
+    }
+}
+
+/// Tera 的错误信息分在多层 source 中，拼成一行便于定位模板中的问题
+fn describe(error: &tera::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(e) = source {
+        message.push_str(": ");
+        message.push_str(&e.to_string());
+        source = e.source();
+    }
+    message
+}
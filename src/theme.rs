@@ -0,0 +1,92 @@
+//! 画廊页面的配色：内置深色和浅色两套 CSS 变量，`--theme` 设置默认主题，`--accent` 设置强调色；
+//! 页面工具栏可以切换，选择保存在浏览器中
+
+/// 可选的主题，`auto` 跟随系统
+pub const THEMES: [&str; 3] = ["dark", "light", "auto"];
+
+pub const DEFAULT_ACCENT: &str = "#3b82f6";
+
+const DARK: &str = "
+            color-scheme: dark;
+            --bg: #0a0a0f;
+            --toolbar-bg: rgba(15, 15, 20, 0.95);
+            --surface: #16161d;
+            --panel-bg: rgba(22, 22, 29, 0.95);
+            --option-bg: #0f0f14;
+            --toast-bg: #1e293b;
+            --text: #e2e8f0;
+            --muted: #64748b;
+            --subtle: #94a3b8;
+            --control: rgba(255, 255, 255, 0.05);
+            --control-hover: rgba(255, 255, 255, 0.1);
+            --control-active: rgba(255, 255, 255, 0.15);
+            --track: rgba(255, 255, 255, 0.08);
+            --border: rgba(255, 255, 255, 0.1);
+            --divider: rgba(255, 255, 255, 0.06);
+            --shadow: rgba(0, 0, 0, 0.5);
+            --success: #22c55e;
+            --danger: #f87171;
+            --accent-text: color-mix(in srgb, var(--accent) 65%, white);
+            --accent-text-hover: color-mix(in srgb, var(--accent) 40%, white);";
+
+const LIGHT: &str = "
+            color-scheme: light;
+            --bg: #f8fafc;
+            --toolbar-bg: rgba(255, 255, 255, 0.92);
+            --surface: #e2e8f0;
+            --panel-bg: rgba(255, 255, 255, 0.97);
+            --option-bg: #ffffff;
+            --toast-bg: #ffffff;
+            --text: #0f172a;
+            --muted: #64748b;
+            --subtle: #475569;
+            --control: rgba(15, 23, 42, 0.05);
+            --control-hover: rgba(15, 23, 42, 0.1);
+            --control-active: rgba(15, 23, 42, 0.15);
+            --track: rgba(15, 23, 42, 0.08);
+            --border: rgba(15, 23, 42, 0.12);
+            --divider: rgba(15, 23, 42, 0.08);
+            --shadow: rgba(15, 23, 42, 0.18);
+            --success: #16a34a;
+            --danger: #dc2626;
+            --accent-text: var(--accent);
+            --accent-text-hover: color-mix(in srgb, var(--accent) 75%, black);";
+
+/// 主题的页面背景色，用于 PWA 启动画面和浏览器地址栏；`auto` 按深色处理
+pub fn background(theme: &str) -> &'static str {
+    if theme == "light" {
+        "#f8fafc"
+    } else {
+        "#0a0a0f"
+    }
+}
+
+/// 是否为 `#rgb` 或 `#rrggbb` 格式的颜色
+pub fn is_valid_color(color: &str) -> bool {
+    color
+        .strip_prefix('#')
+        .is_some_and(|hex| (hex.len() == 3 || hex.len() == 6) && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// 生成页面 `<style>` 开头的变量定义；`html` 元素的 `data-theme` 决定使用哪一套
+pub fn variables(accent: &str) -> String {
+    format!(
+        ":root {{
+            --accent: {accent};
+            --accent-soft: color-mix(in srgb, var(--accent) 20%, transparent);
+            --accent-border: color-mix(in srgb, var(--accent) 40%, transparent);
+        }}
+
+        :root, :root[data-theme=\"dark\"] {{{DARK}
+        }}
+
+        :root[data-theme=\"light\"] {{{LIGHT}
+        }}
+
+        @media (prefers-color-scheme: light) {{
+            :root[data-theme=\"auto\"] {{{LIGHT}
+            }}
+        }}
+"
+    )
+}
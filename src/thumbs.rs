@@ -0,0 +1,658 @@
+//! 重新生成缩略图：修改缩略图尺寸、格式等设置后，不必手动删除 `.thumbnails` 目录。
+//! 服务中通过 `POST /api/thumbs/rebuild` 在后台执行，进度与预生成共用 `/api/scan/status`；
+//! 也可以运行 `pic_url thumbs [--force]`。
+//!
+//! 默认按修改时间判断缩略图是否过期；从备份恢复或用保留时间戳的工具同步的原图可能比缩略图旧，
+//! 这时可以用 `--thumb-check hash` 在缩略图旁记录原图的内容哈希，内容变了才重新生成
+
+use crate::admin::AdminAuth;
+use crate::errors::ApiError;
+use crate::homes::Viewer;
+use crate::maintenance::Writable;
+use crate::memcache::MemoryCacheStats;
+use crate::metrics::METRICS;
+use crate::proxy::PROXY_DIR;
+use crate::quarantine::{Stamp, ThumbFailure};
+use crate::scan::Phase;
+use crate::{
+    collect_images, generate_thumbnail, is_valid_relative, names, thumbnail_is_fresh, thumbnail_is_newer, AppConfig,
+};
+use actix_web::http::StatusCode;
+use actix_web::{get, post, rt, web, HttpResponse, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 判断缩略图是否过期的方式，只用于本地图片目录
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbCheck {
+    /// 缩略图不比原图旧即有效，最快
+    Mtime,
+    /// 原图的大小或修改时间与生成时不同时重新计算内容哈希，内容变了才重新生成
+    Hash,
+    /// 每次都计算原图的内容哈希，最可靠也最慢
+    Strict,
+}
+
+impl ThumbCheck {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "mtime" => Some(ThumbCheck::Mtime),
+            "hash" => Some(ThumbCheck::Hash),
+            "strict" => Some(ThumbCheck::Strict),
+            _ => None,
+        }
+    }
+}
+
+/// 生成缩略图时原图的状态，保存在缩略图旁的 `<缩略图>.src` 中
+struct Source {
+    size: u64,
+    /// 修改时间，纳秒
+    modified: u128,
+    sha256: String,
+}
+
+fn record_path(thumb_path: &Path) -> PathBuf {
+    let mut path = thumb_path.as_os_str().to_owned();
+    path.push(".src");
+    PathBuf::from(path)
+}
+
+/// 原图的大小和修改时间
+fn stat(src_path: &Path) -> Option<(u64, u128)> {
+    let meta = fs::metadata(src_path).ok()?;
+    let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+    Some((meta.len(), modified))
+}
+
+fn sha256(src_path: &Path) -> Option<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(src_path).ok()?, &mut hasher).ok()?;
+    Some(hex::encode(hasher.finalize()))
+}
+
+fn read_record(thumb_path: &Path) -> Option<Source> {
+    let content = fs::read_to_string(record_path(thumb_path)).ok()?;
+    let mut fields = content.split_whitespace();
+    Some(Source {
+        size: fields.next()?.parse().ok()?,
+        modified: fields.next()?.parse().ok()?,
+        sha256: fields.next()?.to_string(),
+    })
+}
+
+fn write_record(thumb_path: &Path, source: &Source) {
+    let content = format!("{} {} {}\n", source.size, source.modified, source.sha256);
+    if let Err(e) = fs::write(record_path(thumb_path), content) {
+        log::warn!("写入缩略图记录 {} 失败: {}", thumb_path.display(), e);
+    }
+}
+
+/// 缩略图的缓存路径：`<目录>/<文件名>.<路径哈希>.<扩展名>`，保留原图的目录结构和扩展名（决定缩略图的格式）。
+/// 哈希按完整的相对路径计算，同一目录下的 `photo.jpg` 和 `photo.png`，
+/// 以及只有大小写不同的 `Photo.jpg` 和 `photo.jpg`（WebDAV 来源的缓存放在不区分大小写的文件系统上时）
+/// 不会共用同一个缓存文件
+pub fn get_thumbnail_path(thumb_dir: &str, relative_path: &str) -> PathBuf {
+    let digest = hex::encode(Sha256::digest(relative_path.as_bytes()));
+    let key = &digest[..16];
+    let (folder, name) = relative_path.rsplit_once('/').unwrap_or(("", relative_path));
+    let name = match name.rsplit_once('.') {
+        Some((stem, ext)) => format!("{}.{}.{}", stem, key, ext),
+        None => format!("{}.{}", name, key),
+    };
+    Path::new(thumb_dir).join(folder).join(name)
+}
+
+/// 缩略图的版本，用在 `/th/{版本}/{路径}` 中：由原图路径、大小、修改时间和缩略图尺寸算出，
+/// 原图被替换或修改尺寸后随之改变。只用到原图的元数据，列表接口中不必读取或生成缩略图。
+/// 按内容哈希检查缩略图时改用 [`content_version`]
+pub fn version(relative: &str, size: Option<u64>, modified: Option<SystemTime>, thumb_size: u32) -> String {
+    let modified = modified.and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map_or(0, |d| d.as_nanos());
+    let key = format!("{}\n{}\n{}\n{}", relative, size.unwrap_or(0), modified, thumb_size);
+    hex::encode(&Sha256::digest(key.as_bytes())[..8])
+}
+
+/// 由原图内容的 SHA-256 得出的缩略图版本：内容不变时地址不变，被替换为大小和修改时间都相同的
+/// 另一张图片时地址也会改变，浏览器和 CDN 不会继续使用旧的缩略图
+pub fn content_version(relative: &str, sha256: &str, thumb_size: u32) -> String {
+    let key = format!("{}\n{}\n{}", relative, sha256, thumb_size);
+    hex::encode(&Sha256::digest(key.as_bytes())[..8])
+}
+
+/// 版本使用的原图内容哈希，与判断缩略图是否过期的方式一致：`hash` 在大小和修改时间与记录相同时
+/// 使用 `.src` 记录中的哈希，否则重新计算；`strict` 每次都重新计算；按修改时间检查时为 `None`
+pub fn content_hash(check: ThumbCheck, src_path: &Path, thumb_path: &Path) -> Option<String> {
+    match check {
+        ThumbCheck::Mtime => None,
+        ThumbCheck::Hash => {
+            let current = stat(src_path)?;
+            read_record(thumb_path)
+                .filter(|record| (record.size, record.modified) == current)
+                .map(|record| record.sha256)
+                .or_else(|| sha256(src_path))
+        }
+        ThumbCheck::Strict => sha256(src_path),
+    }
+}
+
+/// 删除图片的缩略图和它的记录
+pub fn remove(thumb_dir: &str, relative: &str) {
+    let thumb_path = get_thumbnail_path(thumb_dir, relative);
+    let _ = fs::remove_file(record_path(&thumb_path));
+    let _ = fs::remove_file(thumb_path);
+}
+
+/// 图片移动时缩略图和记录随之移动，避免重新生成
+pub fn rename(thumb_dir: &str, from: &str, to: &str) {
+    let (from, to) = (get_thumbnail_path(thumb_dir, from), get_thumbnail_path(thumb_dir, to));
+    if let Some(parent) = to.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let _ = fs::rename(record_path(&from), record_path(&to));
+    let _ = fs::rename(from, to);
+}
+
+/// 缓存目录布局的版本，写在缓存目录下；没有这个文件的是旧版本直接放在 `.thumbnails` 下的缩略图
+const LAYOUT_FILE: &str = ".layout";
+const LAYOUT_VERSION: &str = "2\n";
+/// 迁移时暂存旧缩略图的目录，迁移中断后下次启动继续
+const MIGRATING_DIR: &str = ".migrating";
+
+/// 当前尺寸的缩略图目录：缓存目录下按尺寸分开存放，修改尺寸后不会把旧尺寸的缩略图当作有效缓存
+pub fn size_dir(cache_dir: &str, size: u32) -> String {
+    format!("{}/{}", cache_dir, size)
+}
+
+/// 缓存目录下全部尺寸的缩略图目录，供不知道服务所用尺寸的命令行工具使用
+pub fn size_dirs(cache_dir: &str) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(cache_dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .filter_map(|entry| entry.file_name().to_str().and_then(parse_size))
+        .map(|size| size_dir(cache_dir, size))
+        .collect()
+}
+
+#[derive(Default)]
+pub struct Migration {
+    /// 从旧布局移到当前尺寸目录的缩略图数
+    pub moved: u64,
+    /// 删除的缩略图数和字节数
+    pub removed: u64,
+    pub removed_bytes: u64,
+}
+
+/// 迁移旧版本直接放在缓存目录 `cache_dir` 下的缩略图：长边与 `size` 相符的移到 `<cache_dir>/<size>/`，其余删除。
+/// 已经是新布局时什么也不做；远程图片代理的缓存不动
+pub fn migrate_layout(cache_dir: &Path, size: u32) -> io::Result<Migration> {
+    let mut migration = Migration::default();
+    fs::create_dir_all(cache_dir)?;
+    let staging = cache_dir.join(MIGRATING_DIR);
+    if !cache_dir.join(LAYOUT_FILE).exists() {
+        // 先把旧内容整体移开，免得名为 `200` 之类的图片目录被当作尺寸目录
+        fs::create_dir_all(&staging)?;
+        for entry in fs::read_dir(cache_dir)? {
+            let name = entry?.file_name();
+            if name != PROXY_DIR && name != MIGRATING_DIR {
+                fs::rename(cache_dir.join(&name), staging.join(&name))?;
+            }
+        }
+        fs::write(cache_dir.join(LAYOUT_FILE), LAYOUT_VERSION)?;
+    }
+    if staging.is_dir() {
+        let target = cache_dir.join(size.to_string());
+        move_flat(&staging, &target, size, &mut migration);
+        fs::remove_dir_all(&staging)?;
+    }
+    Ok(migration)
+}
+
+/// 删除修改尺寸后留下的其他尺寸的缩略图目录，计入 `migration`
+pub fn remove_other_sizes(cache_dir: &Path, size: u32, migration: &mut Migration) -> io::Result<()> {
+    let current = size.to_string();
+    for entry in fs::read_dir(cache_dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if entry.file_type()?.is_dir() && name != PROXY_DIR && name != current.as_str() {
+            cache_usage(&entry.path(), &mut migration.removed, &mut migration.removed_bytes);
+            fs::remove_dir_all(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// 迁移和清理的结果，没有变化时为 `None`
+pub fn migration_summary(migration: &Migration) -> Option<String> {
+    (migration.moved > 0 || migration.removed > 0).then(|| {
+        format!(
+            "已整理缩略图缓存: 保留 {} 张，删除 {} 张其他尺寸或无效的缩略图 ({:.1} MB)",
+            migration.moved,
+            migration.removed,
+            migration.removed_bytes as f64 / 1024.0 / 1024.0
+        )
+    })
+}
+
+/// 把 `from` 下长边为 `size` 的缩略图连同哈希记录移到 `to` 下的相同位置，其余的只计数，随后整体删除
+fn move_flat(from: &Path, to: &Path, size: u32, migration: &mut Migration) {
+    let Ok(entries) = fs::read_dir(from) else {
+        return;
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if meta.is_dir() {
+            move_flat(&path, &to.join(entry.file_name()), size, migration);
+            continue;
+        }
+        if entry.file_name().to_string_lossy().ends_with(".src") {
+            continue;
+        }
+        // 缩放时长边按浮点数计算，可能比设置的尺寸小 1
+        let fits = image::image_dimensions(&path).is_ok_and(|(w, h)| (size - 1..=size).contains(&w.max(h)));
+        let target = to.join(entry.file_name());
+        if fits && fs::create_dir_all(to).and_then(|_| fs::rename(&path, &target)).is_ok() {
+            let _ = fs::rename(record_path(&path), record_path(&target));
+            migration.moved += 1;
+        } else {
+            migration.removed += 1;
+            migration.removed_bytes += meta.len();
+        }
+    }
+}
+
+/// 按内容哈希判断缩略图是否仍与原图对应。切换检查方式之前生成的缩略图没有记录，
+/// 按修改时间判断一次并补上记录，不必全部重新生成
+pub fn matches_source(check: ThumbCheck, src_path: &Path, thumb_path: &Path) -> bool {
+    if !thumb_path.is_file() {
+        return false;
+    }
+    let Some((size, modified)) = stat(src_path) else {
+        return false;
+    };
+    let record = read_record(thumb_path);
+    if check == ThumbCheck::Hash && record.as_ref().is_some_and(|r| r.size == size && r.modified == modified) {
+        return true;
+    }
+    let Some(sha256) = sha256(src_path) else {
+        return false;
+    };
+    let fresh = match &record {
+        Some(record) => record.sha256 == sha256,
+        None => thumbnail_is_newer(src_path, thumb_path),
+    };
+    // 内容没变但时间戳变了（例如从备份恢复），更新记录，下次不用再计算哈希
+    if fresh && record.is_none_or(|r| r.size != size || r.modified != modified) {
+        write_record(thumb_path, &Source { size, modified, sha256 });
+    }
+    fresh
+}
+
+/// 刚生成缩略图后记录原图的状态；按修改时间检查时不需要记录
+pub fn remember(check: ThumbCheck, src_path: &Path, thumb_path: &Path) {
+    if check == ThumbCheck::Mtime {
+        return;
+    }
+    if let (Some((size, modified)), Some(sha256)) = (stat(src_path), sha256(src_path)) {
+        write_record(thumb_path, &Source { size, modified, sha256 });
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RebuildQuery {
+    /// 只重建这个目录（含子目录）中的图片，默认全部
+    dir: Option<String>,
+}
+
+/// `folder` 目录（含子目录）中的图片，`folder` 为空时为全部图片
+fn in_folder(images: Vec<String>, folder: &str) -> Vec<String> {
+    if folder.is_empty() {
+        return images;
+    }
+    let prefix = format!("{}/", folder);
+    images.into_iter().filter(|relative| relative.starts_with(&prefix)).collect()
+}
+
+/// 重新生成本地图片的缩略图，生成成功前旧的缩略图仍然可用；
+/// WebDAV 来源只删除缓存的缩略图，下次访问时重新下载原图生成
+fn rebuild(config: &AppConfig, images: &[String]) {
+    let reporter = config.scan.report();
+    reporter.phase(Phase::Thumbnails, Some(images.len()));
+    for (i, relative) in images.iter().enumerate() {
+        let thumb_path = get_thumbnail_path(&config.thumb_dir, relative);
+        if config.webdav.is_some() {
+            let _ = fs::remove_file(&thumb_path);
+        } else {
+            // 页面请求正在生成缩略图时先让路
+            config.limiter.wait_idle();
+            // 手动重建时多次失败的图片也再试一次
+            let src = config.fs_path(relative);
+            match generate_thumbnail(config.settings.thumb_check, config.settings.thumb_size, &src, &thumb_path) {
+                Ok(()) => {
+                    reporter.thumbnail_generated();
+                    config.quarantine.forget(relative);
+                }
+                Err(e) => {
+                    log::warn!("重新生成 {} 的缩略图失败: {}", relative, e);
+                    METRICS.thumb_failure();
+                    config.quarantine.record(relative, &e, Stamp::local(&src));
+                }
+            }
+        }
+        reporter.advance(i + 1);
+    }
+}
+
+/// 在后台重新生成缩略图，立即返回 202 和图片数
+#[post("/api/thumbs/rebuild")]
+pub async fn rebuild_thumbnails(
+    _auth: AdminAuth,
+    _writable: Writable,
+    query: web::Query<RebuildQuery>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let folder = query.dir.as_deref().unwrap_or("").trim_matches('/').to_string();
+    if !folder.is_empty() && !is_valid_relative(&folder) {
+        return Err(ApiError::bad_request(format!("invalid dir: {}", folder)).into());
+    }
+    let scan = config.clone();
+    let images = in_folder(web::block(move || scan.current_images()).await?, &folder);
+    if images.is_empty() {
+        return Err(ApiError::not_found("no images in dir").into());
+    }
+    if config.rebuilding.swap(true, Ordering::SeqCst) {
+        return Err(ApiError::new(StatusCode::CONFLICT, "thumbnail rebuild already running").into());
+    }
+    let count = images.len();
+    let config = config.into_inner();
+    rt::spawn(async move {
+        let task = config.clone();
+        if let Err(e) = web::block(move || rebuild(&task, &images)).await {
+            log::error!("重新生成缩略图任务异常: {}", e);
+        }
+        config.rebuilding.store(false, Ordering::SeqCst);
+    });
+    Ok(HttpResponse::Accepted().json(serde_json::json!({ "images": count })))
+}
+
+/// 递归统计缓存的缩略图数和占用的字节数；哈希记录计入字节数，不计入缩略图数
+fn cache_usage(dir: &Path, files: &mut u64, bytes: &mut u64) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        match entry.metadata() {
+            Ok(meta) if meta.is_dir() => cache_usage(&entry.path(), files, bytes),
+            Ok(meta) => {
+                *bytes += meta.len();
+                if !entry.file_name().to_string_lossy().ends_with(".src") {
+                    *files += 1;
+                }
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ThumbStats {
+    /// 缓存的缩略图数和占用的字节数
+    entries: u64,
+    bytes: u64,
+    thumb_size: u32,
+    /// 启动以来的缓存命中和未命中次数，以及命中率（还没有请求时为 `null`）
+    hits: u64,
+    misses: u64,
+    hit_ratio: Option<f64>,
+    /// 启动以来生成的缩略图数和平均耗时
+    generated: u64,
+    avg_generation_ms: Option<f64>,
+    failures: u64,
+    /// 这个画廊最近几次失败，最新的在前，只包含请求者能看到的图片
+    recent_failures: Vec<ThumbFailure>,
+    /// `--thumb-cache-mb` 内存缓存，未开启时为 `null`
+    memory_cache: Option<MemoryCacheStats>,
+    uptime_secs: u64,
+}
+
+/// 缩略图缓存的统计，用于调整 `--thumb-size` 和预生成
+#[get("/api/thumbs/stats")]
+pub async fn stats(viewer: Viewer, config: web::Data<AppConfig>) -> Result<HttpResponse> {
+    let thumb_dir = config.thumb_dir.clone();
+    let (entries, bytes) = web::block(move || {
+        let (mut files, mut bytes) = (0, 0);
+        cache_usage(Path::new(thumb_dir.as_str()), &mut files, &mut bytes);
+        (files, bytes)
+    })
+    .await?;
+    let metrics = METRICS.thumb_metrics();
+    let mut recent_failures = config.quarantine.recent();
+    recent_failures.retain(|failure| viewer.can_see(&failure.path));
+    let lookups = metrics.hits + metrics.misses;
+    Ok(HttpResponse::Ok().json(ThumbStats {
+        entries,
+        bytes,
+        thumb_size: config.settings.thumb_size,
+        hits: metrics.hits,
+        misses: metrics.misses,
+        hit_ratio: (lookups > 0).then(|| metrics.hits as f64 / lookups as f64),
+        generated: metrics.generated,
+        avg_generation_ms: (metrics.generated > 0)
+            .then(|| metrics.generation_secs * 1000.0 / metrics.generated as f64),
+        failures: metrics.failures,
+        recent_failures,
+        memory_cache: config.memcache.is_enabled().then(|| config.memcache.stats()),
+        uptime_secs: config.started_at.elapsed().as_secs(),
+    }))
+}
+
+/// 缩略图长边允许的像素数：16 到 2048
+pub fn is_valid_size(size: u32) -> bool {
+    (16..=2048).contains(&size)
+}
+
+pub fn parse_size(value: &str) -> Option<u32> {
+    value.parse().ok().filter(|&size| is_valid_size(size))
+}
+
+/// `pic_url thumbs [-d 目录] [--folder 子目录] [--force] [--thumb-check 方式] [--thumb-size 像素]`：
+/// 为缺少或过期的缩略图生成缓存，`--force` 时全部重新生成，完成后退出。只用于本地图片目录
+pub fn run_command(args: &[String]) -> ! {
+    let usage = || -> ! {
+        eprintln!(
+            "用法: pic_url thumbs [-d 图片目录] [--folder 子目录] [--force] [--thumb-check mtime|hash|strict] \
+             [--thumb-size 像素]"
+        );
+        std::process::exit(1);
+    };
+    let mut pic_dir = std::env::var("PIC_DIR").unwrap_or_else(|_| "./pic".to_string());
+    let mut folder = String::new();
+    let mut force = false;
+    let mut check = std::env::var("PIC_THUMB_CHECK").ok().and_then(|value| ThumbCheck::parse(&value));
+    let mut size = std::env::var("PIC_THUMB_SIZE").ok().and_then(|value| parse_size(&value));
+    let mut i = 0;
+    while i < args.len() {
+        match (args[i].as_str(), args.get(i + 1)) {
+            ("-d" | "--dir", Some(value)) => {
+                pic_dir = value.clone();
+                i += 1;
+            }
+            ("--folder", Some(value)) => {
+                folder = value.trim_matches('/').to_string();
+                i += 1;
+            }
+            ("--force", _) => force = true,
+            ("--thumb-check", Some(value)) => {
+                check = Some(ThumbCheck::parse(value).unwrap_or_else(|| usage()));
+                i += 1;
+            }
+            ("--thumb-size", Some(value)) => {
+                size = Some(parse_size(value).unwrap_or_else(|| usage()));
+                i += 1;
+            }
+            _ => usage(),
+        }
+        i += 1;
+    }
+    if !folder.is_empty() && !is_valid_relative(&folder) {
+        usage();
+    }
+    if !Path::new(&pic_dir).is_dir() {
+        eprintln!("错误: 图片目录 '{}' 不存在", pic_dir);
+        std::process::exit(1);
+    }
+
+    let check = check.unwrap_or(ThumbCheck::Mtime);
+    let size = size.unwrap_or(crate::THUMB_SIZE);
+    let base = Path::new(&pic_dir);
+    let cache_dir = format!("{}/{}", pic_dir, crate::THUMB_DIR_NAME);
+    // 其他尺寸的目录可能属于使用不同设置运行的服务，这里只迁移旧布局
+    match migrate_layout(Path::new(&cache_dir), size) {
+        Ok(migration) => {
+            if let Some(summary) = migration_summary(&migration) {
+                println!("{}", summary);
+            }
+        }
+        Err(e) => {
+            eprintln!("错误: 迁移缩略图缓存目录失败: {}", e);
+            std::process::exit(1);
+        }
+    }
+    let thumb_dir = size_dir(&cache_dir, size);
+    let mut images = Vec::new();
+    if let Err(e) = collect_images(base, base, &mut images) {
+        eprintln!("警告: 跳过无法读取的目录 {}", e);
+    }
+    images.sort();
+    let images = in_folder(images, &folder);
+    let (mut generated, mut failed) = (0, 0);
+    for (i, relative) in images.iter().enumerate() {
+        let src = names::fs_path(base, relative);
+        let thumb_path = get_thumbnail_path(&thumb_dir, relative);
+        if !force && thumbnail_is_fresh(check, &src, &thumb_path) {
+            continue;
+        }
+        match generate_thumbnail(check, size, &src, &thumb_path) {
+            Ok(()) => {
+                generated += 1;
+                println!("[{}/{}] {}", i + 1, images.len(), relative);
+            }
+            Err(e) => {
+                failed += 1;
+                eprintln!("[{}/{}] {}: 失败: {}", i + 1, images.len(), relative, e);
+            }
+        }
+    }
+    println!("完成: 共 {} 张，生成 {} 张缩略图，失败 {} 张", images.len(), generated, failed);
+    std::process::exit(if failed > 0 { 1 } else { 0 });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::THUMB_SIZE;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn same_stem_with_different_extensions_do_not_collide() {
+        let jpg = get_thumbnail_path("/cache", "相册/photo.jpg");
+        let png = get_thumbnail_path("/cache", "相册/photo.png");
+        assert_ne!(jpg, png);
+        // 缩略图按扩展名选择编码格式
+        assert_eq!(jpg.extension().unwrap(), "jpg");
+        assert_eq!(png.extension().unwrap(), "png");
+        assert_eq!(jpg.parent(), Some(Path::new("/cache/相册")));
+        assert_eq!(jpg.parent(), png.parent());
+    }
+
+    #[test]
+    fn case_differing_names_do_not_collide_on_case_insensitive_filesystems() {
+        for (a, b) in [("Photo.jpg", "photo.jpg"), ("photo.JPG", "photo.jpg"), ("Trip/a.png", "trip/a.png")] {
+            let a = get_thumbnail_path("/cache", a).to_string_lossy().to_lowercase();
+            let b = get_thumbnail_path("/cache", b).to_string_lossy().to_lowercase();
+            assert_ne!(a, b);
+        }
+    }
+
+    #[test]
+    fn path_is_stable() {
+        assert_eq!(get_thumbnail_path("/cache", "a/b.png"), get_thumbnail_path("/cache", "a/b.png"));
+        assert_ne!(get_thumbnail_path("/cache", "a/b.png"), get_thumbnail_path("/cache", "b.png"));
+        assert!(get_thumbnail_path("/cache", "README").starts_with("/cache"));
+    }
+
+    #[test]
+    fn thumbnails_of_same_stem_images_are_kept_separately() {
+        let dir = std::env::temp_dir().join(format!("pic_url-thumbs-test-{}", std::process::id()));
+        let thumb_dir = dir.join("thumbs");
+        fs::create_dir_all(&dir).unwrap();
+        let red = dir.join("photo.png");
+        let blue = dir.join("photo.bmp");
+        RgbImage::from_pixel(40, 30, Rgb([255, 0, 0])).save(&red).unwrap();
+        RgbImage::from_pixel(40, 30, Rgb([0, 0, 255])).save(&blue).unwrap();
+
+        let thumb_dir = thumb_dir.to_string_lossy();
+        let red_thumb = get_thumbnail_path(&thumb_dir, "photo.png");
+        let blue_thumb = get_thumbnail_path(&thumb_dir, "photo.bmp");
+        generate_thumbnail(ThumbCheck::Hash, THUMB_SIZE, &red, &red_thumb).unwrap();
+        generate_thumbnail(ThumbCheck::Hash, THUMB_SIZE, &blue, &blue_thumb).unwrap();
+
+        let pixel = |path: &Path| image::open(path).unwrap().to_rgb8().get_pixel(0, 0).0;
+        assert_eq!(pixel(&red_thumb), [255, 0, 0]);
+        assert_eq!(pixel(&blue_thumb), [0, 0, 255]);
+        assert!(matches_source(ThumbCheck::Strict, &red, &red_thumb));
+        assert!(!matches_source(ThumbCheck::Strict, &blue, &red_thumb));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn migrates_flat_cache_layout() {
+        let dir = std::env::temp_dir().join(format!("pic_url-layout-test-{}", std::process::id()));
+        let thumb = |relative: &str, width, height| {
+            let path = dir.join(relative);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            RgbImage::from_pixel(width, height, Rgb([0, 0, 0])).save(&path).unwrap();
+        };
+        // 旧布局：当前尺寸和其他尺寸的缩略图混在一起，`2024` 是图片目录而不是尺寸
+        thumb("a.0123456789abcdef.png", 32, 24);
+        fs::write(dir.join("a.0123456789abcdef.png.src"), "1 2 abc\n").unwrap();
+        thumb("big.0123456789abcdef.png", 64, 48);
+        thumb("2024/b.0123456789abcdef.png", 16, 31);
+        fs::create_dir_all(dir.join(".proxy/ab")).unwrap();
+        fs::write(dir.join(".proxy/ab/orig"), "cached").unwrap();
+
+        let mut migration = migrate_layout(&dir, 32).unwrap();
+        assert_eq!((migration.moved, migration.removed), (2, 1));
+        assert!(dir.join("32/a.0123456789abcdef.png").is_file());
+        assert!(dir.join("32/a.0123456789abcdef.png.src").is_file());
+        assert!(dir.join("32/2024/b.0123456789abcdef.png").is_file());
+        assert!(!dir.join("big.0123456789abcdef.png").exists());
+        assert!(!dir.join(MIGRATING_DIR).exists());
+
+        // 已经是新布局时不再迁移，名为尺寸的图片目录也不会被误删
+        let mut again = migrate_layout(&dir, 32).unwrap();
+        assert_eq!((again.moved, again.removed), (0, 0));
+        remove_other_sizes(&dir, 32, &mut again).unwrap();
+        assert!(dir.join("32/2024/b.0123456789abcdef.png").is_file());
+
+        // 修改尺寸后删除旧尺寸的目录，代理缓存保留
+        thumb("64/c.0123456789abcdef.png", 64, 64);
+        remove_other_sizes(&dir, 64, &mut migration).unwrap();
+        assert_eq!(migration.removed, 3);
+        assert!(!dir.join("32").exists());
+        assert!(dir.join("64/c.0123456789abcdef.png").is_file());
+        assert!(dir.join(".proxy/ab/orig").is_file());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,157 @@
+//! 冷存储分层 (`--archive`)：标记为归档的目录放在较慢的存储上（如网络挂载、对象存储的挂载、
+//! 休眠的硬盘），列表照常包含其中的图片；缩略图总是在后台预生成并保存在本地，已有缩略图时
+//! 请求不再访问原图。读取原图的首字节耗时按目录统计，`/api/info` 返回预计的等待时间
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// 新的耗时在平均值中所占的比重
+const WEIGHT: f64 = 0.3;
+
+/// 一个归档目录
+struct ArchiveFolder {
+    folder: String,
+    /// 配置的预计耗时 (毫秒)，还没有实际读取过时使用
+    hint: Option<u64>,
+}
+
+/// 图片所在的存储层，显示在 `/api/info` 中
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct Storage {
+    /// `hot` 或 `archive`
+    tier: &'static str,
+    /// 所属的归档目录
+    #[serde(skip_serializing_if = "Option::is_none")]
+    folder: Option<String>,
+    /// 读取原图的预计等待时间（首字节），还没有读取过也没有配置时为 `null`
+    expected_latency_ms: Option<u64>,
+}
+
+/// 归档目录的状态，显示在 `/api/admin/info` 中
+#[derive(Serialize)]
+pub struct FolderStatus {
+    folder: String,
+    expected_latency_ms: Option<u64>,
+}
+
+pub struct Tiers {
+    /// 长的在前，嵌套的归档目录按最近的一个计算
+    folders: Vec<ArchiveFolder>,
+    /// 目录（普通存储为空字符串）→ 首字节耗时的滑动平均 (毫秒)
+    observed: Mutex<HashMap<String, f64>>,
+}
+
+/// 解析 `目录` 或 `目录=毫秒`，目录无效时返回 `None`
+pub fn parse(spec: &str) -> Option<(String, Option<u64>)> {
+    let (folder, hint) = match spec.split_once('=') {
+        Some((folder, ms)) => (folder, Some(ms.trim().parse().ok()?)),
+        None => (spec, None),
+    };
+    let folder = folder.trim().trim_matches('/');
+    (!folder.is_empty() && crate::is_valid_relative(folder)).then(|| (folder.to_string(), hint))
+}
+
+impl Tiers {
+    /// 按配置创建，无效的项在读取配置时已经检查过，这里跳过
+    pub fn new(specs: &[String]) -> Self {
+        let mut folders: Vec<ArchiveFolder> = specs
+            .iter()
+            .filter_map(|spec| parse(spec))
+            .map(|(folder, hint)| ArchiveFolder { folder, hint })
+            .collect();
+        folders.sort_by_key(|archive| std::cmp::Reverse(archive.folder.len()));
+        Self {
+            folders,
+            observed: Mutex::default(),
+        }
+    }
+
+    pub fn has_archive(&self) -> bool {
+        !self.folders.is_empty()
+    }
+
+    fn archive_of(&self, relative: &str) -> Option<&ArchiveFolder> {
+        self.folders.iter().find(|archive| {
+            relative
+                .strip_prefix(archive.folder.as_str())
+                .is_some_and(|rest| rest.starts_with('/'))
+        })
+    }
+
+    /// 图片是否在归档目录中
+    pub fn is_archived(&self, relative: &str) -> bool {
+        self.archive_of(relative).is_some()
+    }
+
+    /// 记录一次读取原图的首字节耗时
+    pub fn observe(&self, relative: &str, elapsed: Duration) {
+        let key = self.archive_of(relative).map_or("", |archive| archive.folder.as_str());
+        let ms = elapsed.as_secs_f64() * 1000.0;
+        let mut observed = self.observed.lock().unwrap_or_else(|e| e.into_inner());
+        observed
+            .entry(key.to_string())
+            .and_modify(|average| *average += (ms - *average) * WEIGHT)
+            .or_insert(ms);
+    }
+
+    fn expected(&self, key: &str, hint: Option<u64>) -> Option<u64> {
+        let observed = self.observed.lock().unwrap_or_else(|e| e.into_inner());
+        observed.get(key).map(|ms| ms.round() as u64).or(hint)
+    }
+
+    /// 图片所在的存储层和预计的等待时间
+    pub fn storage(&self, relative: &str) -> Storage {
+        match self.archive_of(relative) {
+            Some(archive) => Storage {
+                tier: "archive",
+                folder: Some(archive.folder.clone()),
+                expected_latency_ms: self.expected(&archive.folder, archive.hint),
+            },
+            None => Storage {
+                tier: "hot",
+                folder: None,
+                expected_latency_ms: self.expected("", None),
+            },
+        }
+    }
+
+    pub fn status(&self) -> Vec<FolderStatus> {
+        self.folders
+            .iter()
+            .map(|archive| FolderStatus {
+                folder: archive.folder.clone(),
+                expected_latency_ms: self.expected(&archive.folder, archive.hint),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_archive_folder_wins() {
+        assert_eq!(parse("/2015/"), Some(("2015".to_string(), None)));
+        assert_eq!(parse("旅行/2015=8000"), Some(("旅行/2015".to_string(), Some(8000))));
+        assert_eq!(parse("../x"), None);
+        assert_eq!(parse("2015=slow"), None);
+
+        let tiers = Tiers::new(&["2015=8000".to_string(), "2015/raw".to_string()]);
+        assert!(tiers.is_archived("2015/a.jpg"));
+        assert!(!tiers.is_archived("2015.jpg"));
+        assert!(!tiers.is_archived("20150/a.jpg"));
+        assert_eq!(tiers.storage("2015/a.jpg").expected_latency_ms, Some(8000));
+        assert_eq!(tiers.storage("2015/raw/a.jpg").folder.as_deref(), Some("2015/raw"));
+        assert_eq!(tiers.storage("2015/raw/a.jpg").expected_latency_ms, None);
+
+        // 读取过之后使用实际的耗时
+        tiers.observe("2015/a.jpg", Duration::from_millis(2000));
+        assert_eq!(tiers.storage("2015/b.jpg").expected_latency_ms, Some(2000));
+        tiers.observe("2015/a.jpg", Duration::from_millis(3000));
+        assert_eq!(tiers.storage("2015/b.jpg").expected_latency_ms, Some(2300));
+        assert_eq!(tiers.storage("a.jpg").tier, "hot");
+    }
+}
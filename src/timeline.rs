@@ -0,0 +1,247 @@
+//! 时间线：按拍摄日期把图片分成 年 → 月 → 日。`/api/timeline` 返回各组的数量，
+//! 带 `month` 参数时返回该月的图片；`/timeline` 页面在滚动到某个月时才加载其中的图片
+
+use crate::homes::Viewer;
+use crate::listing::ImageListQuery;
+use crate::errors::ApiError;
+use crate::{details, format_time, page_context, AppConfig, ImageInfo};
+use actix_web::{get, web, HttpResponse, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// 没有日期的图片（WebDAV 来源取不到修改时间时）在 `month` 参数中的名称
+const UNDATED: &str = "undated";
+
+/// 时间线和统计使用的拍摄信息
+#[derive(Clone, Default)]
+pub struct Capture {
+    /// 拍摄时间 `YYYY-MM-DDTHH:MM:SS`：优先使用 EXIF（相机记录的本地时间），
+    /// 没有时使用文件修改时间（UTC）
+    pub taken: Option<String>,
+    /// 相机型号，如 `Canon EOS R6`
+    pub camera: Option<String>,
+}
+
+/// 读取时文件的修改时间和拍摄信息
+type Entry = (SystemTime, Capture);
+
+/// 拍摄信息缓存：读取 EXIF 需要打开文件，按修改时间缓存，文件被替换后重新读取
+#[derive(Default)]
+pub struct Captures {
+    cache: Mutex<HashMap<String, Entry>>,
+}
+
+impl Captures {
+    pub fn get(&self, config: &AppConfig, relative: &str) -> Capture {
+        if let Some(source) = &config.webdav {
+            // WebDAV 来源读取 EXIF 需要下载整个文件，只使用列目录得到的修改时间
+            return Capture {
+                taken: source.modified(relative).map(date_of),
+                camera: None,
+            };
+        }
+        let file = config.fs_path(relative);
+        let Ok(modified) = fs::metadata(&file).and_then(|m| m.modified()) else {
+            return Capture::default();
+        };
+        if let Ok(cache) = self.cache.lock() {
+            if let Some((cached_at, capture)) = cache.get(relative) {
+                if *cached_at == modified {
+                    return capture.clone();
+                }
+            }
+        }
+        let exif = details::read_exif(config, relative);
+        let taken = Some(taken_or_modified(exif.as_ref(), modified));
+        let capture = Capture {
+            taken,
+            camera: exif.as_ref().and_then(details::camera),
+        };
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(relative.to_string(), (modified, capture.clone()));
+        }
+        capture
+    }
+
+    pub fn taken(&self, config: &AppConfig, relative: &str) -> Option<String> {
+        self.get(config, relative).taken
+    }
+
+    /// 图片删除或移走后清除缓存
+    pub fn forget(&self, relative: &str) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.remove(relative);
+        }
+    }
+}
+
+/// 拍摄时间 `YYYY-MM-DDTHH:MM:SS`，EXIF 中没有或无效时使用修改时间
+pub fn taken_or_modified(exif: Option<&exif::Exif>, modified: SystemTime) -> String {
+    exif.and_then(details::taken_at)
+        .map(|taken| taken.chars().take(19).collect::<String>())
+        // 部分相机没有设置时钟时记录为 0000:00:00
+        .filter(|taken| !taken.starts_with("0000") && !taken[5..].starts_with("00"))
+        .unwrap_or_else(|| date_of(modified))
+}
+
+fn date_of(time: SystemTime) -> String {
+    format_time(time).chars().take(19).collect()
+}
+
+#[derive(Deserialize)]
+pub struct TimelineQuery {
+    /// `YYYY-MM` 或 `undated`，返回该月的图片
+    month: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Day {
+    date: String,
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct Month {
+    /// `YYYY-MM`
+    month: String,
+    count: usize,
+    days: Vec<Day>,
+}
+
+#[derive(Serialize)]
+struct Year {
+    year: String,
+    count: usize,
+    months: Vec<Month>,
+}
+
+#[derive(Serialize)]
+struct Summary {
+    count: usize,
+    years: Vec<Year>,
+    /// 没有日期的图片数
+    undated: usize,
+}
+
+#[derive(Serialize)]
+struct TimelineImage {
+    #[serde(flatten)]
+    info: ImageInfo,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    taken_at: Option<String>,
+}
+
+#[derive(Serialize)]
+struct MonthImages {
+    month: String,
+    images: Vec<TimelineImage>,
+}
+
+/// 按年、月、日统计，均为新的在前
+fn summarize(dated: &[(String, String)], undated: usize) -> Summary {
+    let mut days: BTreeMap<&str, usize> = BTreeMap::new();
+    for (taken, _) in dated {
+        *days.entry(&taken[..10]).or_insert(0) += 1;
+    }
+    let mut years: Vec<Year> = Vec::new();
+    for (date, count) in days.into_iter().rev() {
+        if years.last().map(|y| y.year.as_str()) != Some(&date[..4]) {
+            years.push(Year {
+                year: date[..4].to_string(),
+                count: 0,
+                months: Vec::new(),
+            });
+        }
+        let year = years.last_mut().expect("刚刚插入");
+        year.count += count;
+        if year.months.last().map(|m| m.month.as_str()) != Some(&date[..7]) {
+            year.months.push(Month {
+                month: date[..7].to_string(),
+                count: 0,
+                days: Vec::new(),
+            });
+        }
+        let month = year.months.last_mut().expect("刚刚插入");
+        month.count += count;
+        month.days.push(Day {
+            date: date.to_string(),
+            count,
+        });
+    }
+    Summary {
+        count: dated.len() + undated,
+        years,
+        undated,
+    }
+}
+
+#[get("/api/timeline")]
+pub async fn api_timeline(
+    filter: web::Query<ImageListQuery>,
+    query: web::Query<TimelineQuery>,
+    viewer: Viewer,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    filter.validate(&config)?;
+    let month = query.into_inner().month;
+    if let Some(month) = &month {
+        let valid = month == UNDATED
+            || (month.len() == 7
+                && month.as_bytes()[4] == b'-'
+                && month.chars().filter(|c| c.is_ascii_digit()).count() == 6);
+        if !valid {
+            return Err(ApiError::bad_request("month must be YYYY-MM or undated").into());
+        }
+    }
+
+    let filter = filter.into_inner();
+    let config = config.into_inner();
+    let body = web::block(move || {
+        let images = filter.apply(&config, viewer.filter(config.current_images()));
+        let mut dated = Vec::new();
+        let mut undated = Vec::new();
+        for path in images {
+            match config.captures.taken(&config, &path) {
+                Some(taken) => dated.push((taken, path)),
+                None => undated.push(path),
+            }
+        }
+        match month {
+            None => serde_json::to_value(summarize(&dated, undated.len())),
+            Some(month) => {
+                let mut selected: Vec<(Option<String>, String)> = if month == UNDATED {
+                    undated.into_iter().map(|path| (None, path)).collect()
+                } else {
+                    dated
+                        .into_iter()
+                        .filter(|(taken, _)| taken.starts_with(&month))
+                        .map(|(taken, path)| (Some(taken), path))
+                        .collect()
+                };
+                // 新的在前，同一时间按路径排序
+                selected.sort_by(|(a, pa), (b, pb)| b.cmp(a).then_with(|| pa.cmp(pb)));
+                let images = selected
+                    .into_iter()
+                    .map(|(taken_at, path)| TimelineImage {
+                        info: ImageInfo::new(&config, &path),
+                        taken_at,
+                    })
+                    .collect();
+                serde_json::to_value(MonthImages { month, images })
+            }
+        }
+    })
+    .await?
+    .map_err(|e| ApiError::internal(e.to_string()))?;
+    Ok(HttpResponse::Ok().json(body))
+}
+
+#[get("/timeline")]
+pub async fn timeline(config: web::Data<AppConfig>) -> HttpResponse {
+    config
+        .templates
+        .respond("timeline.html", &page_context(&config), "text/html; charset=utf-8")
+}
@@ -0,0 +1,27 @@
+//! 可选的 HTTPS 支持（需要 `tls` feature）
+//!
+//! 启用 TLS 后浏览器通过 ALPN 协商 HTTP/2，画廊的上百个缩略图请求在同一个连接上多路复用，
+//! 不再受 HTTP/1.1 每个域名 6 个连接的限制。
+
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::ServerConfig;
+use std::io;
+use std::sync::Arc;
+
+/// 从 PEM 文件加载证书链和私钥
+pub fn load(cert_path: &str, key_path: &str) -> io::Result<ServerConfig> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| io::Error::other(format!("读取证书 {} 失败: {}", cert_path, e)))?;
+    if certs.is_empty() {
+        return Err(io::Error::other(format!("证书文件 {} 中没有证书", cert_path)));
+    }
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| io::Error::other(format!("读取私钥 {} 失败: {}", key_path, e)))?;
+
+    ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+        .map_err(|e| io::Error::other(format!("证书或私钥无效: {}", e)))
+}
@@ -0,0 +1,207 @@
+//! 网页上传：`POST /api/upload`（需管理令牌），multipart 表单中的每个文件保存到图片目录；
+//! 配置了[病毒扫描](crate::scanner)时，文件写完后先扫描，通过后才改名放进图片目录
+
+use crate::admin::AdminAuth;
+use crate::errors::ApiError;
+use crate::maintenance::Writable;
+use crate::webhook::EventKind;
+use crate::{is_image_file, is_valid_relative, payload, AppConfig, ImageInfo};
+use actix_multipart::Multipart;
+use actix_web::{post, web, HttpResponse, Result};
+use actix_web::web::Bytes;
+use futures_util::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// 临时文件编号，同名文件同时上传时互不覆盖
+static NEXT_TMP: AtomicU64 = AtomicU64::new(0);
+/// 选定文件名和改名需要一起完成，否则同时上传的同名文件会互相覆盖
+static RENAME_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Deserialize)]
+pub struct UploadQuery {
+    /// 保存到的目录，默认为图片目录根目录
+    folder: Option<String>,
+}
+
+#[derive(Serialize)]
+struct UploadFailure {
+    name: String,
+    error: String,
+}
+
+#[derive(Serialize)]
+struct UploadResponse {
+    uploaded: Vec<ImageInfo>,
+    failed: Vec<UploadFailure>,
+}
+
+/// 客户端提供的文件名只保留最后一段，拒绝隐藏文件和非图片
+pub(crate) fn sanitize_name(name: &str) -> Option<String> {
+    let name = name.rsplit(['/', '\\']).next()?.trim();
+    if name.is_empty() || name.starts_with('.') || name.chars().any(|c| c.is_control()) {
+        return None;
+    }
+    is_image_file(Path::new(name)).then(|| name.to_string())
+}
+
+/// 同名文件已存在时依次尝试 `name (1).ext`、`name (2).ext`……
+pub fn unique_name(dir: &Path, name: &str) -> String {
+    if !dir.join(name).exists() {
+        return name.to_string();
+    }
+    let (stem, ext) = name.rsplit_once('.').unwrap_or((name, ""));
+    (1..)
+        .map(|n| format!("{} ({}).{}", stem, n, ext))
+        .find(|candidate| !dir.join(candidate).exists())
+        .unwrap_or_else(|| name.to_string())
+}
+
+/// 把收到的内容写入 `tmp`，超过 `limit` 字节时停止
+async fn write_chunks<S, E>(chunks: &mut S, tmp: PathBuf, limit: u64) -> std::result::Result<(), String>
+where
+    S: Stream<Item = std::result::Result<Bytes, E>> + Unpin,
+    E: ToString,
+{
+    let mut file = web::block(move || File::create(tmp))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+    let mut size = 0u64;
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk.map_err(|e| e.to_string())?;
+        size += chunk.len() as u64;
+        if size > limit {
+            return Err(format!("file is larger than {} MB", limit / 1024 / 1024));
+        }
+        file = web::block(move || file.write_all(&chunk).map(|_| file))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// 先写入临时文件，完成并通过扫描后改名为正式文件名，返回相对路径。
+/// `dir` 和 `folder` 来自 [`prepare_folder`]，`name` 需经过 [`sanitize_name`]
+pub(crate) async fn save<S, E>(
+    config: &AppConfig,
+    chunks: &mut S,
+    dir: &Path,
+    folder: &str,
+    name: &str,
+) -> std::result::Result<String, String>
+where
+    S: Stream<Item = std::result::Result<Bytes, E>> + Unpin,
+    E: ToString,
+{
+    let tmp = dir.join(format!(".{}.{}.uploading", name, NEXT_TMP.fetch_add(1, Ordering::Relaxed)));
+    if let Err(e) = write_chunks(chunks, tmp.clone(), payload::upload_limit(config)).await {
+        let _ = web::block(move || fs::remove_file(tmp)).await;
+        // 读完剩余内容，继续处理后面的文件
+        while chunks.next().await.is_some() {}
+        return Err(e);
+    }
+    if let Some(scanner) = config.scanner.clone() {
+        let (file, name) = (tmp.clone(), name.to_string());
+        let checked = web::block(move || scanner.check(&file, &name))
+            .await
+            .unwrap_or_else(|_| Err("virus scan failed".to_string()));
+        if let Err(e) = checked {
+            let _ = web::block(move || fs::remove_file(tmp)).await;
+            return Err(e);
+        }
+    }
+
+    let dir = dir.to_path_buf();
+    let name = name.to_string();
+    let name = web::block(move || {
+        let _guard = RENAME_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let name = unique_name(&dir, &name);
+        fs::rename(&tmp, dir.join(&name)).map(|_| name).inspect_err(|_| {
+            let _ = fs::remove_file(&tmp);
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())?;
+    Ok(if folder.is_empty() { name } else { format!("{}/{}", folder, name) })
+}
+
+/// 检查并创建保存到的目录，返回去掉首尾 `/` 的目录和它在磁盘上的路径
+pub(crate) async fn prepare_folder(config: &AppConfig, folder: &str) -> Result<(String, PathBuf)> {
+    if config.webdav.is_some() {
+        return Err(ApiError::bad_request("uploads are not supported for a WebDAV source").into());
+    }
+    let folder = folder.trim_matches('/').to_string();
+    if !folder.is_empty() && !is_valid_relative(&folder) {
+        return Err(ApiError::bad_request(format!("invalid folder: {}", folder)).into());
+    }
+    let dir = config.fs_path(&folder);
+    {
+        let dir = dir.clone();
+        web::block(move || fs::create_dir_all(dir)).await??;
+    }
+    Ok((folder, dir))
+}
+
+/// 立即重新扫描，分配短 ID 并推送 `image.added`，不必等文件监听
+pub(crate) async fn finish(config: web::Data<AppConfig>, uploaded: Vec<String>) -> Result<Vec<ImageInfo>> {
+    if uploaded.is_empty() {
+        return Ok(Vec::new());
+    }
+    let images = web::block(move || {
+        for path in &uploaded {
+            config.notify(EventKind::Uploaded, path);
+        }
+        config.rescan();
+        uploaded.iter().map(|path| ImageInfo::new(&config, path)).collect()
+    })
+    .await?;
+    Ok(images)
+}
+
+#[post("/api/upload")]
+pub async fn upload(
+    _auth: AdminAuth,
+    _writable: Writable,
+    query: web::Query<UploadQuery>,
+    mut payload: Multipart,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let (folder, dir) = prepare_folder(&config, query.folder.as_deref().unwrap_or("")).await?;
+
+    let mut uploaded = Vec::new();
+    let mut failed = Vec::new();
+    while let Some(field) = payload.next().await {
+        let mut field = field?;
+        let Some(original) = field.content_disposition().and_then(|cd| cd.get_filename()).map(str::to_string) else {
+            // 不是文件的字段直接跳过
+            while field.next().await.is_some() {}
+            continue;
+        };
+        let Some(name) = sanitize_name(&original) else {
+            while field.next().await.is_some() {}
+            failed.push(UploadFailure {
+                name: original,
+                error: "not a supported image file".to_string(),
+            });
+            continue;
+        };
+        match save(&config, &mut field, &dir, &folder, &name).await {
+            Ok(path) => uploaded.push(path),
+            Err(error) => failed.push(UploadFailure { name: original, error }),
+        }
+    }
+
+    let uploaded = finish(config, uploaded).await?;
+    if uploaded.is_empty() && !failed.is_empty() {
+        // 失败的文件和原因放在 `error.details` 中
+        return Err(ApiError::bad_request("no images were uploaded").with_details(&failed).into());
+    }
+    Ok(HttpResponse::Ok().json(UploadResponse { uploaded, failed }))
+}
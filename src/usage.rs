@@ -0,0 +1,57 @@
+//! 按顶层目录统计占用空间 (`GET /api/usage`)：大小在扫描时随索引一起记录，
+//! 查询时不再遍历图片目录
+
+use crate::{format_time, AppConfig};
+use actix_web::{get, web, HttpResponse};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Clone, Default)]
+pub struct FolderUsage {
+    /// 顶层目录名，直接放在图片目录下的图片为 `""`
+    folder: String,
+    /// 目录下（含子目录）全部图片的字节数
+    bytes: u64,
+    files: usize,
+}
+
+/// 按顶层目录汇总，占用最多的在前
+pub fn by_folder(images: &[String], sizes: &[u64]) -> Vec<FolderUsage> {
+    let mut totals: BTreeMap<&str, (u64, usize)> = BTreeMap::new();
+    for (path, size) in images.iter().zip(sizes) {
+        let folder = path.split_once('/').map_or("", |(first, _)| first);
+        let total = totals.entry(folder).or_default();
+        total.0 += size;
+        total.1 += 1;
+    }
+    let mut usage: Vec<FolderUsage> = totals
+        .into_iter()
+        .map(|(folder, (bytes, files))| FolderUsage {
+            folder: folder.to_string(),
+            bytes,
+            files,
+        })
+        .collect();
+    usage.sort_by_key(|f| std::cmp::Reverse(f.bytes));
+    usage
+}
+
+#[derive(Serialize)]
+struct UsageResponse {
+    bytes: u64,
+    files: usize,
+    folders: Vec<FolderUsage>,
+    /// 统计对应的扫描时间，首次扫描完成前为 `null`
+    scanned_at: Option<String>,
+}
+
+#[get("/api/usage")]
+pub async fn api_usage(config: web::Data<AppConfig>) -> HttpResponse {
+    let (folders, scanned_at) = config.index.usage();
+    HttpResponse::Ok().json(UsageResponse {
+        bytes: folders.iter().map(|f| f.bytes).sum(),
+        files: folders.iter().map(|f| f.files).sum(),
+        folders,
+        scanned_at: scanned_at.map(format_time),
+    })
+}
@@ -0,0 +1,84 @@
+//! 接口版本：JSON 接口以 `/api/v1/...` 为正式地址，以后不兼容的改动（分页方式、字段改名等）放到新的
+//! 版本中，已有的集成不会在升级后悄悄失效。
+//!
+//! - `/api/v1/<路径>` 由 `/api/<路径>` 的处理函数处理，成功的 JSON 响应包在 `{"data": ...}` 中；
+//!   出错时与其他接口一样是 `{"error": {...}}`，客户端只看顶层的键即可区分
+//! - 不带版本的 `/api/<路径>` 保留为兼容地址，响应不变，另外带上 `Deprecation` 和指向新地址的 `Link`
+
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::http::Uri;
+use actix_web::middleware::Next;
+use actix_web::web::Bytes;
+use actix_web::{error, Error};
+
+/// 当前版本的接口前缀（相对画廊地址）
+pub const V1: &str = "/api/v1";
+
+/// 中间件：把 `/api/v1/...` 交给对应的处理函数并包装响应，给不带版本的地址加上弃用说明。
+/// 需要在按路径检查权限的中间件之外
+pub async fn middleware(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let mount = crate::mount_path(req.request());
+    let Some(path) = req.path().strip_prefix(mount.as_str()).map(str::to_string) else {
+        return next.call(req).await.map(ServiceResponse::map_into_boxed_body);
+    };
+    if let Some(rest) = path.strip_prefix(V1).filter(|rest| rest.starts_with('/')) {
+        let mut uri = format!("{}/api{}", mount, rest);
+        if let Some(query) = req.uri().query() {
+            uri.push('?');
+            uri.push_str(query);
+        }
+        if let Ok(uri) = uri.parse::<Uri>() {
+            req.match_info_mut().get_mut().update(&uri);
+            req.head_mut().uri = uri;
+        }
+        return envelope(next.call(req).await?).await;
+    }
+    let successor = path.strip_prefix("/api/").filter(|_| path != V1).map(|rest| {
+        let mut successor = format!("{}{}/{}", mount, V1, rest);
+        if let Some(query) = req.uri().query() {
+            successor.push('?');
+            successor.push_str(query);
+        }
+        successor
+    });
+    let mut res = next.call(req).await?;
+    let link = successor.and_then(|url| HeaderValue::from_str(&format!("<{}>; rel=\"successor-version\"", url)).ok());
+    if let Some(link) = link {
+        let deprecation = header::HeaderName::from_static("deprecation");
+        res.headers_mut().insert(deprecation, HeaderValue::from_static("true"));
+        res.headers_mut().append(header::LINK, link);
+    }
+    Ok(res.map_into_boxed_body())
+}
+
+/// 成功的 JSON 响应包在 `{"data": ...}` 中，其他响应（错误、图片、事件流、下载的文件等）不变
+async fn envelope<B: MessageBody + 'static>(res: ServiceResponse<B>) -> Result<ServiceResponse<BoxBody>, Error> {
+    let is_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    // 元数据备份等以附件下载的 JSON 文件要原样保存，之后还要上传回来
+    let is_download = res.headers().contains_key(header::CONTENT_DISPOSITION);
+    if !res.status().is_success() || !is_json || is_download {
+        return Ok(res.map_into_boxed_body());
+    }
+    let (req, res) = res.into_parts();
+    let (mut head, body) = res.into_parts();
+    let data = body::to_bytes(body)
+        .await
+        .map_err(|e| error::ErrorInternalServerError(e.into().to_string()))?;
+    // 响应体本身是 JSON，直接拼接，不需要重新解析
+    let mut wrapped = Vec::with_capacity(data.len() + 9);
+    wrapped.extend_from_slice(b"{\"data\":");
+    wrapped.extend_from_slice(&data);
+    wrapped.push(b'}');
+    head.headers_mut().remove(header::CONTENT_LENGTH);
+    let res = head.set_body(Bytes::from(wrapped)).map_into_boxed_body();
+    Ok(ServiceResponse::new(req, res))
+}
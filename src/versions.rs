@@ -0,0 +1,234 @@
+//! 原图的历史版本：旋转、裁剪等操作覆盖原图之前，把原来的文件保存到图片目录下的
+//! `.versions/<图片路径>/<毫秒时间戳>.<扩展名>`，可以列出、查看和恢复（需管理令牌）。
+//! 转换为 WebP 的图片，原图以原来的扩展名保存为新文件的版本，恢复时改回原来的文件名
+
+use crate::admin::AdminAuth;
+use crate::errors::{ApiError, AppError};
+use crate::maintenance::Writable;
+use crate::{
+    dimensions, edit, format_time, is_valid_relative, names, sniff, thumbs, AppConfig, ImageInfo, VERSIONS_DIR_NAME,
+};
+use actix_files::NamedFile;
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Result};
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 每张图片最多保留的版本数，超出时删除最旧的
+const MAX_VERSIONS: usize = 20;
+
+#[derive(Serialize)]
+struct Version {
+    /// 保存时的毫秒时间戳
+    id: String,
+    saved_at: String,
+    size: u64,
+    /// 版本文件的扩展名，转换格式前的版本与当前图片不同
+    extension: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<u32>,
+}
+
+fn dir(pic_dir: &str, relative: &str) -> PathBuf {
+    names::fs_path(Path::new(pic_dir).join(VERSIONS_DIR_NAME), relative)
+}
+
+fn extension(relative: &str) -> &str {
+    relative.rsplit_once('.').map_or("", |(_, ext)| ext)
+}
+
+/// 版本文件的路径，`id` 不是时间戳或版本不存在时返回 `None`；版本的扩展名可能与图片不同
+fn version_path(pic_dir: &str, relative: &str, id: &str) -> Option<PathBuf> {
+    if id.is_empty() || !id.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let prefix = format!("{}.", id);
+    fs::read_dir(dir(pic_dir, relative))
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .find(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+        .map(|entry| entry.path())
+}
+
+/// 全部版本的时间戳，新的在前
+fn ids(pic_dir: &str, relative: &str) -> Vec<u64> {
+    let mut ids: Vec<u64> = fs::read_dir(dir(pic_dir, relative))
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    name.split_once('.')?.0.parse().ok()
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    ids.sort_unstable_by(|a, b| b.cmp(a));
+    ids
+}
+
+/// 把当前的原图保存为一个版本
+pub fn save(config: &AppConfig, relative: &str) -> io::Result<()> {
+    let original = config.fs_path(relative);
+    store(&config.pic_dir, relative, &original)
+}
+
+/// 把 `file` 保存为图片 `relative` 的一个版本，扩展名沿用 `file` 的：同一文件系统上用硬链接，
+/// 之后原文件被改名替换或删除时版本不受影响
+pub fn store(pic_dir: &str, relative: &str, file: &Path) -> io::Result<()> {
+    let dir = dir(pic_dir, relative);
+    fs::create_dir_all(&dir)?;
+    let ext = file.extension().unwrap_or_default().to_string_lossy().to_string();
+    let mut id = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    // 同一毫秒内保存了两次
+    while version_path(pic_dir, relative, &id.to_string()).is_some() {
+        id += 1;
+    }
+    let target = dir.join(format!("{}.{}", id, ext));
+    if fs::hard_link(file, &target).is_err() {
+        fs::copy(file, &target)?;
+    }
+    for old in ids(pic_dir, relative).into_iter().skip(MAX_VERSIONS) {
+        if let Some(path) = version_path(pic_dir, relative, &old.to_string()) {
+            let _ = fs::remove_file(path);
+        }
+    }
+    Ok(())
+}
+
+/// 图片被删除时一起删除它的版本
+pub fn forget(config: &AppConfig, relative: &str) {
+    let _ = fs::remove_dir_all(dir(&config.pic_dir, relative));
+}
+
+/// 图片被移动时版本随之移动
+pub fn rename(config: &AppConfig, from: &str, to: &str) {
+    let from = dir(&config.pic_dir, from);
+    if !from.exists() {
+        return;
+    }
+    let to = dir(&config.pic_dir, to);
+    if let Some(parent) = to.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let _ = fs::rename(from, to);
+}
+
+fn list_versions(config: &AppConfig, relative: &str) -> Vec<Version> {
+    ids(&config.pic_dir, relative)
+        .into_iter()
+        .filter_map(|id| {
+            let path = version_path(&config.pic_dir, relative, &id.to_string())?;
+            let size = fs::metadata(&path).ok()?.len();
+            let dimensions = dimensions::read(&path);
+            Some(Version {
+                id: id.to_string(),
+                saved_at: format_time(UNIX_EPOCH + Duration::from_millis(id)),
+                size,
+                extension: path.extension().unwrap_or_default().to_string_lossy().to_string(),
+                width: dimensions.map(|(w, _)| w),
+                height: dimensions.map(|(_, h)| h),
+            })
+        })
+        .collect()
+}
+
+async fn check_image(config: &AppConfig, relative: &str) -> Result<()> {
+    if config.webdav.is_some() {
+        return Err(ApiError::bad_request("versions are not supported for a WebDAV source").into());
+    }
+    if !is_valid_relative(relative) || !config.image_exists(relative).await? {
+        return Err(ApiError::not_found("Image not found").into());
+    }
+    Ok(())
+}
+
+/// 已保存的版本文件，不存在时返回 404
+async fn find_version(config: &AppConfig, relative: &str, id: &str) -> Result<PathBuf> {
+    let (pic_dir, relative, id) = (config.pic_dir.clone(), relative.to_string(), id.to_string());
+    let file = web::block(move || version_path(&pic_dir, &relative, &id).filter(|file| file.is_file())).await?;
+    file.ok_or_else(|| ApiError::not_found("version not found").into())
+}
+
+/// 图片的历史版本，新的在前
+#[get("/api/images/{path:.*}/versions")]
+pub async fn list(
+    _auth: AdminAuth,
+    path: web::Path<String>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let relative = path.into_inner();
+    check_image(&config, &relative).await?;
+    let versions = web::block(move || list_versions(&config, &relative)).await?;
+    Ok(HttpResponse::Ok().json(versions))
+}
+
+/// 某个版本的文件内容
+#[get("/api/images/{path:.*}/versions/{id}")]
+pub async fn show(
+    _auth: AdminAuth,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let (relative, id) = path.into_inner();
+    check_image(&config, &relative).await?;
+    let file = find_version(&config, &relative, &id).await?;
+    let named = NamedFile::open_async(&file).await?;
+    let mime = web::block(move || sniff::file_mime(&file)).await??;
+    let mut response = named.into_response(&req);
+    crate::typed_by_content(&mut response, mime, &relative);
+    Ok(response)
+}
+
+/// 恢复版本 `file`，返回恢复后的路径。扩展名与图片相同时覆盖原图；
+/// 不同时（转换为 WebP 之前的原图）改回原来的扩展名，当前的文件保存为新文件名下的版本后删除
+fn restore_version(config: &AppConfig, relative: &str, file: &Path) -> io::Result<String> {
+    let data = fs::read(file)?;
+    let ext = file.extension().unwrap_or_default().to_string_lossy().to_string();
+    if ext.eq_ignore_ascii_case(extension(relative)) {
+        edit::write_image(config, relative, &data)?;
+        return Ok(relative.to_string());
+    }
+    let target = format!("{}.{}", relative.rsplit_once('.').map_or(relative, |(stem, _)| stem), ext);
+    if config.fs_path(&target).exists() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", target)));
+    }
+    rename(config, relative, &target);
+    store(&config.pic_dir, &target, &config.fs_path(relative))?;
+    edit::write_image(config, &target, &data)?;
+    fs::remove_file(config.fs_path(relative))?;
+    thumbs::remove(&config.thumb_dir, relative);
+    let _ = config.tags.rename(relative, &target);
+    config.rescan();
+    Ok(target)
+}
+
+/// 用某个版本替换当前的原图；当前的原图同样先保存为一个版本，恢复也可以撤销
+#[post("/api/images/{path:.*}/versions/{id}/restore")]
+pub async fn restore(
+    _auth: AdminAuth,
+    _writable: Writable,
+    path: web::Path<(String, String)>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let (relative, id) = path.into_inner();
+    check_image(&config, &relative).await?;
+    let file = find_version(&config, &relative, &id).await?;
+    let config = config.into_inner();
+    let path = relative.clone();
+    let image = web::block(move || -> io::Result<ImageInfo> {
+        let restored = restore_version(&config, &relative, &file)?;
+        Ok(ImageInfo::new(&config, &restored))
+    })
+    .await?
+    .map_err(|e| AppError::io(&path, e))?;
+    Ok(HttpResponse::Ok().json(image))
+}
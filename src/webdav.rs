@@ -0,0 +1,283 @@
+//! WebDAV 客户端
+//!
+//! 作为图片来源时，原图从远程 WebDAV 服务器 (Nextcloud 等) 读取，缩略图缓存在本地；
+//! 同步任务也用它把文件推送到远程。
+
+use base64::Engine;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use std::collections::HashMap;
+use std::io;
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime};
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:">
+  <d:prop><d:resourcetype/><d:getlastmodified/><d:getcontentlength/></d:prop>
+</d:propfind>"#;
+
+/// 路径段中需要转义的字符
+pub const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// 原图大小上限，防止异常响应耗尽内存
+const MAX_FILE_SIZE: u64 = 512 * 1024 * 1024;
+
+struct Entry {
+    relative: String,
+    is_dir: bool,
+    modified: Option<SystemTime>,
+    size: Option<u64>,
+}
+
+pub struct WebDavSource {
+    agent: ureq::Agent,
+    base_url: String,
+    base_path: String,
+    authorization: Option<String>,
+    modified: RwLock<HashMap<String, SystemTime>>,
+    sizes: RwLock<HashMap<String, u64>>,
+}
+
+impl WebDavSource {
+    pub fn new(url: &str, user: Option<&str>, password: Option<&str>) -> Self {
+        let base_url = format!("{}/", url.trim_end_matches('/'));
+        // 去掉协议和主机部分，得到服务器返回的 href 会带上的路径前缀
+        let base_path = base_url
+            .split_once("://")
+            .and_then(|(_, rest)| rest.find('/').map(|i| rest[i..].to_string()))
+            .unwrap_or_else(|| "/".to_string());
+        let base_path = percent_decode_str(&base_path).decode_utf8_lossy().to_string();
+
+        let authorization = user.map(|user| {
+            let credentials = format!("{}:{}", user, password.unwrap_or(""));
+            format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode(credentials)
+            )
+        });
+
+        let agent = ureq::Agent::config_builder()
+            .http_status_as_error(false)
+            .allow_non_standard_methods(true)
+            .timeout_global(Some(Duration::from_secs(120)))
+            .build()
+            .into();
+
+        Self {
+            agent,
+            base_url,
+            base_path,
+            authorization,
+            modified: RwLock::new(HashMap::new()),
+            sizes: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn url_for(&self, relative: &str) -> String {
+        let encoded: Vec<String> = relative
+            .split('/')
+            .map(|segment| utf8_percent_encode(segment, SEGMENT).to_string())
+            .collect();
+        format!("{}{}", self.base_url, encoded.join("/"))
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::http::request::Builder {
+        let mut builder = ureq::http::Request::builder().method(method).uri(url);
+        if let Some(auth) = &self.authorization {
+            builder = builder.header("Authorization", auth);
+        }
+        builder
+    }
+
+    fn propfind(&self, relative: &str, depth: &str) -> io::Result<Vec<Entry>> {
+        let url = self.url_for(relative);
+        let request = self
+            .request("PROPFIND", &url)
+            .header("Depth", depth)
+            .header("Content-Type", "application/xml; charset=utf-8")
+            .body(PROPFIND_BODY)
+            .map_err(io::Error::other)?;
+        let mut response = self.agent.run(request).map_err(io::Error::other)?;
+        let status = response.status().as_u16();
+        if status != 207 {
+            return Err(io::Error::other(format!("PROPFIND {} 返回 {}", url, status)));
+        }
+        let body = response
+            .body_mut()
+            .with_config()
+            .limit(64 * 1024 * 1024)
+            .read_to_string()
+            .map_err(io::Error::other)?;
+        self.parse_multistatus(&body)
+    }
+
+    fn parse_multistatus(&self, body: &str) -> io::Result<Vec<Entry>> {
+        let doc = roxmltree::Document::parse(body)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut entries = Vec::new();
+        for response in doc
+            .descendants()
+            .filter(|n| n.has_tag_name(("DAV:", "response")))
+        {
+            let href = match response
+                .descendants()
+                .find(|n| n.has_tag_name(("DAV:", "href")))
+                .and_then(|n| n.text())
+            {
+                Some(href) => href.trim(),
+                None => continue,
+            };
+            let is_dir = response
+                .descendants()
+                .any(|n| n.has_tag_name(("DAV:", "collection")));
+            let modified = response
+                .descendants()
+                .find(|n| n.has_tag_name(("DAV:", "getlastmodified")))
+                .and_then(|n| n.text())
+                .and_then(|t| httpdate::parse_http_date(t.trim()).ok());
+            let size = response
+                .descendants()
+                .find(|n| n.has_tag_name(("DAV:", "getcontentlength")))
+                .and_then(|n| n.text())
+                .and_then(|t| t.trim().parse().ok());
+
+            if let Some(relative) = self.relative_from_href(href) {
+                entries.push(Entry {
+                    relative,
+                    is_dir,
+                    modified,
+                    size,
+                });
+            }
+        }
+        Ok(entries)
+    }
+
+    /// 把服务器返回的 href（绝对路径或完整 URL）转换成相对于根目录的路径
+    fn relative_from_href(&self, href: &str) -> Option<String> {
+        let path = match href.split_once("://") {
+            Some((_, rest)) => rest.find('/').map(|i| &rest[i..]).unwrap_or("/"),
+            None => href,
+        };
+        let decoded = percent_decode_str(path).decode_utf8_lossy();
+        let relative = decoded.strip_prefix(self.base_path.as_str())?;
+        Some(relative.trim_matches('/').to_string())
+    }
+
+    /// 递归列出所有图片的相对路径，同时记录它们的修改时间和大小
+    pub fn list_images(&self, is_image: impl Fn(&str) -> bool) -> io::Result<Vec<String>> {
+        let mut images = Vec::new();
+        let mut modified = HashMap::new();
+        let mut sizes = HashMap::new();
+        let mut pending = vec![String::new()];
+
+        while let Some(dir) = pending.pop() {
+            for entry in self.propfind(&dir, "1")? {
+                if entry.relative == dir {
+                    continue;
+                }
+                let name = entry.relative.rsplit('/').next().unwrap_or("");
+                if entry.is_dir {
+                    if !crate::is_reserved_dir(name) {
+                        pending.push(entry.relative);
+                    }
+                } else if is_image(&entry.relative) {
+                    if let Some(time) = entry.modified {
+                        modified.insert(entry.relative.clone(), time);
+                    }
+                    if let Some(size) = entry.size {
+                        sizes.insert(entry.relative.clone(), size);
+                    }
+                    images.push(entry.relative);
+                }
+            }
+        }
+
+        if let Ok(mut map) = self.modified.write() {
+            *map = modified;
+        }
+        if let Ok(mut map) = self.sizes.write() {
+            *map = sizes;
+        }
+        Ok(images)
+    }
+
+    /// 最近一次列目录时记录的修改时间
+    pub fn modified(&self, relative: &str) -> Option<SystemTime> {
+        self.modified
+            .read()
+            .ok()
+            .and_then(|map| map.get(relative).copied())
+    }
+
+    /// 最近一次列目录时记录的文件大小
+    pub fn size(&self, relative: &str) -> Option<u64> {
+        self.sizes.read().ok().and_then(|map| map.get(relative).copied())
+    }
+
+    /// 检查根目录是否可访问
+    pub fn ping(&self) -> bool {
+        self.propfind("", "0").is_ok()
+    }
+
+    /// 下载原图，文件不存在时返回 `NotFound`
+    pub fn fetch(&self, relative: &str) -> io::Result<Vec<u8>> {
+        let url = self.url_for(relative);
+        let request = self
+            .request("GET", &url)
+            .body(())
+            .map_err(io::Error::other)?;
+        let mut response = self.agent.run(request).map_err(io::Error::other)?;
+        match response.status().as_u16() {
+            200 => response
+                .body_mut()
+                .with_config()
+                .limit(MAX_FILE_SIZE)
+                .read_to_vec()
+                .map_err(io::Error::other),
+            404 => Err(io::Error::new(io::ErrorKind::NotFound, url)),
+            status => Err(io::Error::other(format!("GET {} 返回 {}", url, status))),
+        }
+    }
+
+    /// 上传文件，覆盖已存在的同名文件
+    pub fn put(&self, relative: &str, data: Vec<u8>) -> io::Result<()> {
+        let url = self.url_for(relative);
+        let request = self
+            .request("PUT", &url)
+            .body(data)
+            .map_err(io::Error::other)?;
+        let response = self.agent.run(request).map_err(io::Error::other)?;
+        match response.status().as_u16() {
+            200 | 201 | 204 => Ok(()),
+            status => Err(io::Error::other(format!("PUT {} 返回 {}", url, status))),
+        }
+    }
+
+    /// 创建目录，目录已存在 (405) 视为成功
+    pub fn mkcol(&self, relative: &str) -> io::Result<()> {
+        let url = format!("{}/", self.url_for(relative));
+        let request = self
+            .request("MKCOL", &url)
+            .body(())
+            .map_err(io::Error::other)?;
+        let response = self.agent.run(request).map_err(io::Error::other)?;
+        match response.status().as_u16() {
+            200 | 201 | 405 => Ok(()),
+            status => Err(io::Error::other(format!("MKCOL {} 返回 {}", url, status))),
+        }
+    }
+}
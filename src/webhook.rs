@@ -0,0 +1,169 @@
+//! Webhook 通知：图片新增、删除、上传时向配置的 URL 推送 JSON，
+//! 便于接入 n8n、Home Assistant 等自动化工具
+
+use crate::{format_time, signed};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 投递失败后的重试间隔，全部失败后放弃该事件
+const RETRY_DELAYS: [Duration; 3] = [
+    Duration::from_secs(2),
+    Duration::from_secs(10),
+    Duration::from_secs(60),
+];
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EventKind {
+    /// 扫描时发现的新图片
+    #[serde(rename = "image.added")]
+    Added,
+    /// 扫描时发现已被删除的图片
+    #[serde(rename = "image.removed")]
+    Removed,
+    /// 通过 `/dav` 或网页上传的图片
+    #[serde(rename = "image.uploaded")]
+    Uploaded,
+}
+
+impl EventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventKind::Added => "image.added",
+            EventKind::Removed => "image.removed",
+            EventKind::Uploaded => "image.uploaded",
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+struct Payload {
+    id: String,
+    event: EventKind,
+    path: String,
+    url: String,
+    timestamp: String,
+}
+
+/// 签名头 `X-Pic-Url-Signature: sha256=<hex>`，内容为请求体的 HMAC-SHA256
+fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+pub struct Webhooks {
+    /// 每个 URL 一个投递线程，某个地址不可用时不会拖慢其它地址
+    workers: Vec<Sender<Payload>>,
+    next_id: AtomicU64,
+    /// 进程启动时间戳，与序号一起组成投递 ID
+    boot: u64,
+    /// `--base-url`，设置后推送的 `url` 为绝对地址
+    base_url: String,
+    /// `--originals-secret` 和签名有效期，设置后推送的 `url` 带签名
+    originals_secret: Option<String>,
+    originals_ttl: u64,
+}
+
+impl Webhooks {
+    pub fn new(
+        urls: &[String],
+        secret: Option<&str>,
+        base_url: Option<&str>,
+        originals_secret: Option<&str>,
+        originals_ttl: u64,
+    ) -> Self {
+        let workers = urls
+            .iter()
+            .map(|url| {
+                let (tx, rx) = mpsc::channel::<Payload>();
+                let url = url.clone();
+                let secret = secret.map(str::to_string);
+                thread::Builder::new()
+                    .name("webhook".to_string())
+                    .spawn(move || {
+                        let agent: ureq::Agent = ureq::Agent::config_builder()
+                            .http_status_as_error(false)
+                            .timeout_global(Some(Duration::from_secs(10)))
+                            .build()
+                            .into();
+                        for payload in rx {
+                            deliver(&agent, &url, secret.as_deref(), &payload);
+                        }
+                    })
+                    .expect("failed to spawn webhook thread");
+                tx
+            })
+            .collect();
+        let boot = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Self {
+            workers,
+            next_id: AtomicU64::new(1),
+            boot,
+            base_url: base_url.unwrap_or_default().to_string(),
+            originals_secret: originals_secret.map(str::to_string),
+            originals_ttl,
+        }
+    }
+
+    /// 把事件放入投递队列，立即返回
+    pub fn emit(&self, event: EventKind, path: &str) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let payload = Payload {
+            id: format!("{:x}-{}", self.boot, id),
+            event,
+            path: path.to_string(),
+            url: format!(
+                "{}{}",
+                self.base_url,
+                signed::pic_url(self.originals_secret.as_deref(), self.originals_ttl, path)
+            ),
+            timestamp: format_time(SystemTime::now()),
+        };
+        for worker in &self.workers {
+            let _ = worker.send(payload.clone());
+        }
+    }
+}
+
+fn deliver(agent: &ureq::Agent, url: &str, secret: Option<&str>, payload: &Payload) {
+    let body = match serde_json::to_vec(payload) {
+        Ok(body) => body,
+        Err(_) => return,
+    };
+    let mut attempt = 0;
+    loop {
+        let mut request = agent
+            .post(url)
+            .header("Content-Type", "application/json")
+            .header("User-Agent", concat!("pic_url/", env!("CARGO_PKG_VERSION")))
+            .header("X-Pic-Url-Event", payload.event.as_str())
+            .header("X-Pic-Url-Delivery", &payload.id);
+        if let Some(secret) = secret {
+            request = request.header("X-Pic-Url-Signature", signature(secret, &body));
+        }
+        let error = match request.send(&body[..]) {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) => format!("返回 {}", response.status().as_u16()),
+            Err(e) => e.to_string(),
+        };
+
+        match RETRY_DELAYS.get(attempt) {
+            Some(delay) => {
+                attempt += 1;
+                thread::sleep(*delay);
+            }
+            None => {
+                log::warn!("Webhook {} 投递 {} 失败，已放弃: {}", url, payload.id, error);
+                return;
+            }
+        }
+    }
+}
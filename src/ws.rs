@@ -0,0 +1,245 @@
+//! WebSocket 接口：桌面或手机客户端通过一个 `/ws` 连接接收图库事件，
+//! 同时发送命令（订阅目录、分页获取列表、触发重新扫描），不需要轮询。
+//!
+//! 消息都是 JSON 文本帧，客户端请求中的 `id` 会原样带回响应，便于对应请求和响应。
+
+use crate::admin::AdminAuth;
+use crate::homes::Viewer;
+use crate::{AppConfig, ImageInfo};
+use actix_web::{get, rt, web, HttpRequest, HttpResponse, Result};
+use actix_ws::AggregatedMessage;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+
+/// 每页默认和最多返回的图片数
+const DEFAULT_PER_PAGE: usize = 100;
+const MAX_PER_PAGE: usize = 1000;
+/// 客户端超过这个时间没有任何消息（包括 pong）时断开连接
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(60);
+const PING_INTERVAL: Duration = Duration::from_secs(20);
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Command {
+    /// 只接收某个目录（含子目录）下的事件，空字符串表示全部
+    Subscribe {
+        id: Option<serde_json::Value>,
+        #[serde(default)]
+        folder: String,
+    },
+    /// 分页获取某个目录（含子目录）下的图片
+    List {
+        id: Option<serde_json::Value>,
+        #[serde(default)]
+        folder: String,
+        page: Option<usize>,
+        per_page: Option<usize>,
+    },
+    /// 重新扫描图片目录，需要在握手时携带管理令牌
+    Rescan { id: Option<serde_json::Value> },
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Reply {
+    Event {
+        event: &'static str,
+        path: String,
+        name: String,
+    },
+    Subscribed {
+        id: Option<serde_json::Value>,
+        folder: String,
+    },
+    List {
+        id: Option<serde_json::Value>,
+        folder: String,
+        page: usize,
+        per_page: usize,
+        total: usize,
+        images: Vec<ImageInfo>,
+    },
+    Rescan {
+        id: Option<serde_json::Value>,
+        count: usize,
+        duration_ms: u128,
+    },
+    /// 客户端错过了事件，需要重新获取列表
+    Resync,
+    Error {
+        id: Option<serde_json::Value>,
+        message: String,
+    },
+}
+
+/// 去掉首尾的 `/`，`旅行/` 和 `/旅行` 都视为 `旅行`
+fn normalize_folder(folder: &str) -> String {
+    folder.trim_matches('/').to_string()
+}
+
+pub(crate) fn in_folder(folder: &str, path: &str) -> bool {
+    folder.is_empty()
+        || path
+            .strip_prefix(folder)
+            .map(|rest| rest.starts_with('/'))
+            .unwrap_or(false)
+}
+
+async fn handle_command(
+    config: &web::Data<AppConfig>,
+    viewer: &Viewer,
+    admin: bool,
+    folder: &mut String,
+    text: &str,
+) -> Reply {
+    let command = match serde_json::from_str::<Command>(text) {
+        Ok(command) => command,
+        Err(e) => {
+            return Reply::Error {
+                id: None,
+                message: format!("invalid command: {}", e),
+            }
+        }
+    };
+    match command {
+        Command::Subscribe { id, folder: target } => {
+            *folder = normalize_folder(&target);
+            Reply::Subscribed {
+                id,
+                folder: folder.clone(),
+            }
+        }
+        Command::List {
+            id,
+            folder: target,
+            page,
+            per_page,
+        } => {
+            let target = normalize_folder(&target);
+            let page = page.unwrap_or(1).max(1);
+            let per_page = per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+            let scan = config.clone();
+            let folder = target.clone();
+            let viewer = viewer.clone();
+            let listed = web::block(move || {
+                let images = viewer.filter(scan.current_images());
+                let matching: Vec<&String> = images.iter().filter(|path| in_folder(&folder, path)).collect();
+                let page: Vec<ImageInfo> = matching
+                    .iter()
+                    .skip((page - 1).saturating_mul(per_page))
+                    .take(per_page)
+                    .map(|path| ImageInfo::new(&scan, path))
+                    .collect();
+                (matching.len(), page)
+            })
+            .await;
+            let (total, images) = match listed {
+                Ok(listed) => listed,
+                Err(e) => {
+                    return Reply::Error {
+                        id,
+                        message: e.to_string(),
+                    }
+                }
+            };
+            Reply::List {
+                id,
+                folder: target,
+                page,
+                per_page,
+                total,
+                images,
+            }
+        }
+        Command::Rescan { id } if !admin => Reply::Error {
+            id,
+            message: "rescan requires the admin token".to_string(),
+        },
+        Command::Rescan { id } => {
+            let started = Instant::now();
+            let scan = config.clone();
+            match web::block(move || scan.rescan()).await {
+                Ok(images) => Reply::Rescan {
+                    id,
+                    count: images.len(),
+                    duration_ms: started.elapsed().as_millis(),
+                },
+                Err(e) => Reply::Error {
+                    id,
+                    message: e.to_string(),
+                },
+            }
+        }
+    }
+}
+
+/// 建立 WebSocket 连接；握手请求携带管理令牌（与管理接口相同，API 密钥须为不限目录的 `full`）时允许 `rescan` 命令
+#[get("/ws")]
+pub async fn connect(
+    req: HttpRequest,
+    body: web::Payload,
+    auth: Option<AdminAuth>,
+    viewer: Viewer,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let (response, mut session, stream) = actix_ws::handle(&req, body)?;
+    let mut stream = stream.aggregate_continuations();
+    let mut events = config.events.subscribe();
+    let admin = auth.is_some_and(|auth| auth.is_full());
+
+    rt::spawn(async move {
+        let mut folder = String::new();
+        let mut last_seen = Instant::now();
+        let mut ping = rt::time::interval(PING_INTERVAL);
+        loop {
+            let reply = tokio::select! {
+                message = stream.recv() => {
+                    last_seen = Instant::now();
+                    match message {
+                        Some(Ok(AggregatedMessage::Text(text))) => {
+                            Some(handle_command(&config, &viewer, admin, &mut folder, &text).await)
+                        }
+                        Some(Ok(AggregatedMessage::Ping(bytes))) => {
+                            if session.pong(&bytes).await.is_err() {
+                                return;
+                            }
+                            None
+                        }
+                        Some(Ok(AggregatedMessage::Pong(_))) => None,
+                        Some(Ok(AggregatedMessage::Binary(_))) => Some(Reply::Error {
+                            id: None,
+                            message: "binary messages are not supported".to_string(),
+                        }),
+                        Some(Ok(AggregatedMessage::Close(_))) | Some(Err(_)) | None => break,
+                    }
+                }
+                event = events.recv() => match event {
+                    Ok(event) if in_folder(&folder, &event.path) && viewer.can_see(&event.path) => Some(Reply::Event {
+                        event: event.kind.as_str(),
+                        path: event.path,
+                        name: event.name,
+                    }),
+                    Ok(_) => None,
+                    Err(RecvError::Lagged(_)) => Some(Reply::Resync),
+                    Err(RecvError::Closed) => break,
+                },
+                _ = ping.tick() => {
+                    if last_seen.elapsed() > CLIENT_TIMEOUT || session.ping(b"").await.is_err() {
+                        break;
+                    }
+                    None
+                }
+            };
+            if let Some(reply) = reply {
+                let text = serde_json::to_string(&reply).unwrap_or_default();
+                if session.text(text).await.is_err() {
+                    return;
+                }
+            }
+        }
+        let _ = session.close(None).await;
+    });
+
+    Ok(response)
+}
@@ -0,0 +1,1274 @@
+// 画廊挂载的路径前缀（页面的 data-base），挂在根路径时为空
+const BASE = document.documentElement.dataset.base || '';
+let loadedPages = 1;
+let loadingMore = null;
+let imageList = [];
+let currentIndex = 0;
+let slideshowInterval = null;
+let progressInterval = null;
+let isPlaying = false;
+
+function updateImageList() {
+    imageList = Array.from(document.querySelectorAll('.image-item')).map(el => ({
+        path: el.dataset.path,
+        name: el.querySelector('.image-name')?.textContent || el.dataset.path,
+        url: el.dataset.url
+    }));
+}
+
+// 原图地址；服务器开启原图签名时列表中带有签名过的 url
+function picUrl(img) {
+    return BASE + (img.url || '/pic/' + img.path);
+}
+
+function openModal(src, filename) {
+    if (selecting) {
+        toggleSelected(filename);
+        return;
+    }
+    updateImageList();
+    currentIndex = imageList.findIndex(img => src.includes(img.path));
+    if (currentIndex === -1) currentIndex = 0;
+    showImage(currentIndex);
+    document.getElementById('imageModal').classList.add('active');
+    document.body.style.overflow = 'hidden';
+}
+
+function showImage(index) {
+    if (imageList.length === 0) return;
+    if (index < 0) index = imageList.length - 1;
+    if (index >= imageList.length) index = 0;
+    currentIndex = index;
+
+    const img = imageList[currentIndex];
+    const src = picUrl(img);
+
+    document.getElementById('modalImage').src = src;
+    document.getElementById('modalFileName').textContent = img.name;
+    // 带 download=1 时服务器以附件返回，有些浏览器会忽略 download 属性直接打开图片
+    document.getElementById('modalDownload').href = src + (src.includes('?') ? '&' : '?') + 'download=1';
+    document.getElementById('modalOpen').href = src;
+    document.getElementById('modalCounter').textContent = `${currentIndex + 1} / ${Math.max(totalImages, imageList.length)}`;
+    showDetails();
+    preloadAround(currentIndex);
+}
+
+// 预加载后两张和前一张原图，翻页时直接从缓存显示而不是先出现空白
+const preloaded = new Map();
+function preloadAround(index) {
+    const wanted = [];
+    for (const offset of [1, 2, -1]) {
+        let i = index + offset;
+        if (i >= imageList.length) {
+            // 后面还有没加载的图片时不绕回第一张
+            if (hasMore()) continue;
+            i -= imageList.length;
+        }
+        if (i < 0) i += imageList.length;
+        if (imageList[i] && i !== index) wanted.push(picUrl(imageList[i]));
+    }
+    wanted.forEach(src => {
+        if (preloaded.has(src)) return;
+        const img = new Image();
+        img.decoding = 'async';
+        img.src = src;
+        preloaded.set(src, img);
+    });
+    // 只保留最近几张的引用
+    for (const src of preloaded.keys()) {
+        if (preloaded.size <= 6) break;
+        if (!wanted.includes(src)) preloaded.delete(src);
+    }
+    // 快到已加载部分的末尾时提前加载下一批
+    if (index + 3 >= imageList.length && hasMore()) {
+        loadMore().then(() => {
+            if (document.getElementById('imageModal').classList.contains('active')) updateImageList();
+        });
+    }
+}
+
+// 信息面板：尺寸、文件大小和 EXIF，打开状态保存在浏览器中
+const detailsCache = {};
+let detailsOpen = localStorage.getItem('modal-details') === '1';
+
+function toggleDetails() {
+    detailsOpen = !detailsOpen;
+    localStorage.setItem('modal-details', detailsOpen ? '1' : '0');
+    showDetails();
+}
+
+async function showDetails() {
+    const panel = document.getElementById('modalDetails');
+    document.getElementById('detailsBtn').classList.toggle('active', detailsOpen);
+    panel.hidden = !detailsOpen;
+    const img = imageList[currentIndex];
+    if (!detailsOpen || !img) return;
+    const path = img.path;
+    if (!detailsCache[path]) {
+        panel.textContent = 'Loading…';
+        const encoded = path.split('/').map(encodeURIComponent).join('/');
+        try {
+            const [info, exif] = await Promise.all([
+                fetch(BASE + '/api/info/' + encoded).then(res => res.json()),
+                fetch(BASE + '/api/exif/' + encoded).then(res => res.json()),
+            ]);
+            detailsCache[path] = { info, exif };
+        } catch (e) {
+            panel.textContent = 'Failed to load details';
+            return;
+        }
+    }
+    // 加载期间已经翻到其他图片时不再显示
+    if (imageList[currentIndex]?.path !== path) return;
+    renderDetails(panel, detailsCache[path]);
+}
+
+function formatSize(bytes) {
+    const units = ['B', 'KB', 'MB', 'GB'];
+    let i = 0;
+    while (bytes >= 1024 && i < units.length - 1) {
+        bytes /= 1024;
+        i++;
+    }
+    return (i === 0 ? bytes : bytes.toFixed(1)) + ' ' + units[i];
+}
+
+// EXIF 中的文字来自图片文件，只用 textContent 填充
+function renderDetails(panel, { info, exif }) {
+    const rows = [];
+    if (info.width) {
+        const megapixels = info.width * info.height / 1e6;
+        rows.push(['Resolution', `${info.width} × ${info.height}` + (megapixels >= 0.1 ? ` (${megapixels.toFixed(1)} MP)` : '')]);
+    }
+    if (info.size != null) rows.push(['Size', formatSize(info.size)]);
+    // 归档目录的原图在慢速存储上，提示打开原图大约要等多久
+    if (info.storage?.tier === 'archive') {
+        const latency = info.storage.expected_latency_ms;
+        rows.push(['Storage', latency == null ? 'Archive' : `Archive (~${(latency / 1000).toFixed(1)} s to open)`]);
+    }
+    if (exif.taken_at) {
+        rows.push(['Taken', exif.taken_at.replace('T', ' ')]);
+    } else if (info.modified) {
+        rows.push(['Modified', new Date(info.modified).toLocaleString()]);
+    }
+    const model = exif.model || '';
+    const camera = exif.make && !model.toLowerCase().startsWith(exif.make.toLowerCase().split(' ')[0])
+        ? exif.make + ' ' + model
+        : model || exif.make;
+    if (camera) rows.push(['Camera', camera.trim()]);
+    if (exif.lens) rows.push(['Lens', exif.lens]);
+    const focal = exif.focal_length && (exif.focal_length + 'mm' + (exif.focal_length_35mm ? ` (${exif.focal_length_35mm}mm)` : ''));
+    const exposure = [focal, exif.f_number && 'ƒ/' + exif.f_number, exif.exposure_time && exif.exposure_time + 's', exif.iso && 'ISO ' + exif.iso]
+        .filter(Boolean).join(' · ');
+    if (exposure) rows.push(['Exposure', exposure]);
+
+    const list = document.createElement('dl');
+    rows.forEach(([label, value]) => {
+        const dt = document.createElement('dt');
+        dt.textContent = label;
+        const dd = document.createElement('dd');
+        dd.textContent = value;
+        list.append(dt, dd);
+    });
+    panel.replaceChildren(list);
+
+    if (exif.gps) {
+        const { latitude, longitude } = exif.gps;
+        const link = `https://www.openstreetmap.org/?mlat=${latitude}&mlon=${longitude}#map=15/${latitude}/${longitude}`;
+        const dt = document.createElement('dt');
+        dt.textContent = 'Location';
+        const dd = document.createElement('dd');
+        const a = document.createElement('a');
+        a.href = link;
+        a.target = '_blank';
+        a.rel = 'noopener';
+        a.textContent = `${latitude.toFixed(5)}, ${longitude.toFixed(5)}`;
+        dd.appendChild(a);
+        list.append(dt, dd);
+        if (MAP_TILES) panel.appendChild(mapThumbnail(latitude, longitude, link));
+    }
+}
+
+// 用 2×2 块瓦片拼出以拍摄地点为中心的小地图
+function mapThumbnail(lat, lon, link) {
+    const zoom = 13;
+    const n = 2 ** zoom;
+    const rad = lat * Math.PI / 180;
+    const x = (lon + 180) / 360 * n;
+    const y = (1 - Math.log(Math.tan(rad) + 1 / Math.cos(rad)) / Math.PI) / 2 * n;
+    const x0 = Math.floor(x - 0.5);
+    const y0 = Math.floor(y - 0.5);
+    const map = document.createElement('a');
+    map.className = 'details-map';
+    map.href = link;
+    map.target = '_blank';
+    map.rel = 'noopener';
+    const width = 232, height = 140;
+    for (let dx = 0; dx < 2; dx++) {
+        for (let dy = 0; dy < 2; dy++) {
+            const tx = x0 + dx, ty = y0 + dy;
+            if (ty < 0 || ty >= n) continue;
+            const tile = document.createElement('img');
+            tile.src = MAP_TILES.replace('{z}', zoom).replace('{x}', ((tx % n) + n) % n).replace('{y}', ty);
+            tile.alt = '';
+            tile.style.left = Math.round(width / 2 + (tx - x) * 256) + 'px';
+            tile.style.top = Math.round(height / 2 + (ty - y) * 256) + 'px';
+            map.appendChild(tile);
+        }
+    }
+    const marker = document.createElement('span');
+    marker.className = 'marker';
+    map.appendChild(marker);
+    return map;
+}
+
+const linkCache = {};
+
+async function copyLink(format, label) {
+    const img = imageList[currentIndex];
+    if (!img) return;
+    try {
+        if (!linkCache[img.path]) {
+            const encoded = img.path.split('/').map(encodeURIComponent).join('/');
+            const res = await fetch(BASE + '/api/links/' + encoded);
+            if (!res.ok) throw new Error(res.status);
+            linkCache[img.path] = await res.json();
+        }
+        const text = linkCache[img.path][format];
+        if (!text) throw new Error(format);
+        await copyText(text);
+        showToast('Copied ' + label);
+    } catch (e) {
+        showToast('Copy failed');
+    }
+}
+
+// 局域网内通过 http 访问时没有 Clipboard API，退回到 execCommand
+async function copyText(text) {
+    if (navigator.clipboard && window.isSecureContext) {
+        return navigator.clipboard.writeText(text);
+    }
+    const area = document.createElement('textarea');
+    area.value = text;
+    area.style.position = 'fixed';
+    area.style.opacity = '0';
+    document.body.appendChild(area);
+    area.select();
+    const ok = document.execCommand('copy');
+    document.body.removeChild(area);
+    if (!ok) throw new Error('copy failed');
+}
+
+function nextImage() {
+    // 到达已加载部分的末尾时先加载下一批，而不是回到第一张
+    if (currentIndex + 1 >= imageList.length && hasMore()) {
+        loadMore().then(() => {
+            updateImageList();
+            showImage(currentIndex + 1);
+        });
+    } else {
+        showImage(currentIndex + 1);
+    }
+    if (isPlaying) resetProgress();
+}
+
+function prevImage() {
+    showImage(currentIndex - 1);
+    if (isPlaying) resetProgress();
+}
+
+function closeModal() {
+    document.getElementById('imageModal').classList.remove('active');
+    document.body.style.overflow = 'auto';
+    stopSlideshow();
+}
+
+function toggleSlideshow() {
+    if (isPlaying) {
+        stopSlideshow();
+    } else {
+        startSlideshow();
+    }
+}
+
+function startSlideshow() {
+    updateImageList();
+    if (imageList.length === 0) {
+        showToast('No images');
+        return;
+    }
+
+    isPlaying = true;
+    document.getElementById('playBtn').classList.add('playing');
+    document.getElementById('playIcon').textContent = '⏸';
+    document.getElementById('playText').textContent = 'Stop';
+
+    if (!document.getElementById('imageModal').classList.contains('active')) {
+        currentIndex = 0;
+        showImage(0);
+        document.getElementById('imageModal').classList.add('active');
+        document.body.style.overflow = 'hidden';
+    }
+
+    resetProgress();
+    slideshowInterval = setInterval(() => {
+        nextImage();
+    }, 3000);
+}
+
+function stopSlideshow() {
+    isPlaying = false;
+    document.getElementById('playBtn').classList.remove('playing');
+    document.getElementById('playIcon').textContent = '▶';
+    document.getElementById('playText').textContent = 'Play';
+    document.getElementById('slideshowProgress').style.width = '0%';
+
+    if (slideshowInterval) {
+        clearInterval(slideshowInterval);
+        slideshowInterval = null;
+    }
+    if (progressInterval) {
+        clearInterval(progressInterval);
+        progressInterval = null;
+    }
+}
+
+function resetProgress() {
+    if (progressInterval) clearInterval(progressInterval);
+    let progress = 0;
+    document.getElementById('slideshowProgress').style.width = '0%';
+    progressInterval = setInterval(() => {
+        progress += 5;
+        document.getElementById('slideshowProgress').style.width = progress + '%';
+        if (progress >= 100) {
+            clearInterval(progressInterval);
+        }
+    }, 100);
+}
+
+document.getElementById('imageModal').addEventListener('click', function(e) {
+    if (e.target === this) {
+        closeModal();
+    }
+});
+
+// 手机上左右滑动切换图片；双指缩放或已放大时不切换
+(function() {
+    const modal = document.getElementById('imageModal');
+    const image = document.getElementById('modalImage');
+    let tracking = false;
+    let startX = 0, startY = 0, startTime = 0;
+    const zoomed = () => window.visualViewport && window.visualViewport.scale > 1.01;
+    const reset = () => {
+        tracking = false;
+        image.classList.remove('dragging');
+        image.style.transform = '';
+    };
+    modal.addEventListener('touchstart', e => {
+        if (e.touches.length !== 1 || zoomed() || e.target.closest('.modal-info, .modal-details')) {
+            reset();
+            return;
+        }
+        tracking = true;
+        startX = e.touches[0].clientX;
+        startY = e.touches[0].clientY;
+        startTime = Date.now();
+        image.classList.add('dragging');
+    }, { passive: true });
+    modal.addEventListener('touchmove', e => {
+        if (!tracking) return;
+        if (e.touches.length !== 1) {
+            reset();
+            return;
+        }
+        const dx = e.touches[0].clientX - startX;
+        const dy = e.touches[0].clientY - startY;
+        if (Math.abs(dx) > Math.abs(dy)) image.style.transform = `translateX(${dx}px)`;
+    }, { passive: true });
+    modal.addEventListener('touchend', e => {
+        if (!tracking) return;
+        reset();
+        const touch = e.changedTouches[0];
+        const dx = touch.clientX - startX;
+        const dy = touch.clientY - startY;
+        // 滑动超过 60px，或者快速轻扫
+        const flick = Date.now() - startTime < 300 && Math.abs(dx) > 30;
+        if (Math.abs(dx) > Math.abs(dy) * 1.5 && (Math.abs(dx) > 60 || flick)) {
+            if (dx < 0) {
+                nextImage();
+            } else {
+                prevImage();
+            }
+        }
+    });
+    modal.addEventListener('touchcancel', reset);
+})();
+
+document.addEventListener('keydown', function(e) {
+    const modal = document.getElementById('imageModal');
+    if (!modal.classList.contains('active')) {
+        if (e.key === 'Escape' && selecting) toggleSelecting(false);
+        return;
+    }
+
+    if (e.key === 'Escape') {
+        closeModal();
+    } else if (e.key === 'ArrowRight' || e.key === ' ') {
+        e.preventDefault();
+        nextImage();
+    } else if (e.key === 'ArrowLeft') {
+        prevImage();
+    }
+});
+
+function showToast(message) {
+    const toast = document.getElementById('toast');
+    toast.textContent = message;
+    toast.classList.add('show');
+    setTimeout(() => toast.classList.remove('show'), 3000);
+}
+
+function setSize(size) {
+    const gallery = document.getElementById('gallery');
+    gallery.classList.remove('size-large', 'size-medium', 'size-small');
+    gallery.classList.add('size-' + size);
+
+    document.querySelectorAll('.size-btn').forEach(btn => {
+        btn.classList.toggle('active', btn.dataset.size === size);
+    });
+
+    localStorage.setItem('gallery-size', size);
+}
+
+// 主题按 深色 → 浅色 → 跟随系统 循环切换
+const THEME_LABELS = { dark: 'Dark', light: 'Light', auto: 'Auto' };
+function setTheme(theme) {
+    document.documentElement.dataset.theme = theme;
+    document.getElementById('themeBtn').textContent = THEME_LABELS[theme];
+    localStorage.setItem('gallery-theme', theme);
+}
+
+function cycleTheme() {
+    const themes = Object.keys(THEME_LABELS);
+    const current = themes.indexOf(document.documentElement.dataset.theme);
+    setTheme(themes[(current + 1) % themes.length]);
+}
+
+document.getElementById('themeBtn').textContent = THEME_LABELS[document.documentElement.dataset.theme] || 'Dark';
+
+// 网格（正方形裁切）或按比例排版（保持原图宽高比）
+const LAYOUT_LABELS = { grid: 'Grid', justified: 'Rows' };
+function setLayout(layout) {
+    if (layout === 'justified') {
+        document.documentElement.dataset.layout = layout;
+    } else {
+        delete document.documentElement.dataset.layout;
+    }
+    document.getElementById('layoutBtn').textContent = LAYOUT_LABELS[layout];
+    localStorage.setItem('gallery-layout', layout);
+}
+
+function toggleLayout() {
+    setLayout(document.documentElement.dataset.layout === 'justified' ? 'grid' : 'justified');
+}
+
+document.getElementById('layoutBtn').textContent = LAYOUT_LABELS[document.documentElement.dataset.layout || 'grid'];
+
+// 恢复保存的尺寸设置
+(function() {
+    const savedSize = localStorage.getItem('gallery-size');
+    if (savedSize) {
+        setSize(savedSize);
+    }
+})();
+
+// 按比例排版时的宽高比，与服务端渲染的首页一致：限制在 4:1 以内
+function tileRatio(img) {
+    if (!img.width || !img.height) return null;
+    const ratio = Math.min(4, Math.max(0.25, img.width / img.height));
+    return Math.round(ratio * 1000) / 1000;
+}
+
+function createImageElement(img) {
+    const div = document.createElement('div');
+    div.className = 'image-item';
+    div.setAttribute('data-path', img.path);
+    if (img.url) div.dataset.url = img.url;
+    const ratio = tileRatio(img);
+    if (ratio) div.style.setProperty('--ratio', ratio);
+    if (selectedPaths.has(img.path)) div.classList.add('selected');
+    div.onclick = () => openModal(BASE + '/pic/' + img.path, img.path);
+    div.innerHTML = `
+        <img src="${BASE}${img.thumb || '/thumb/' + img.path}" alt="${img.path}" loading="lazy">
+        <div class="overlay"><div class="image-name">${img.name}</div></div>
+    `;
+    return div;
+}
+
+// 服务器繁忙 (503) 时缩略图加载失败，稍后重试几次
+document.addEventListener('error', e => {
+    const img = e.target;
+    if (img.tagName !== 'IMG' || !img.closest('.image-item')) return;
+    const tries = Number(img.dataset.retries || 0);
+    if (tries >= 5) return;
+    img.dataset.retries = tries + 1;
+    const src = img.src.split('?')[0];
+    setTimeout(() => { img.src = src + '?retry=' + (tries + 1); }, 2000 * (tries + 1) + Math.random() * 1000);
+}, true);
+
+// 选择模式：点击缩略图勾选而不是打开大图，手机上长按进入
+let selecting = false;
+const selectedPaths = new Set();
+
+function itemFor(path) {
+    return Array.from(document.querySelectorAll('.image-item')).find(el => el.dataset.path === path);
+}
+
+function toggleSelecting(on = !selecting) {
+    selecting = on;
+    if (!on) {
+        selectedPaths.clear();
+        document.querySelectorAll('.image-item.selected').forEach(el => el.classList.remove('selected'));
+    }
+    document.getElementById('gallery').classList.toggle('selecting', on);
+    document.getElementById('selectBtn').classList.toggle('active', on);
+    document.getElementById('selectionBar').hidden = !on;
+    updateSelection();
+}
+
+function toggleSelected(path) {
+    if (selectedPaths.has(path)) {
+        selectedPaths.delete(path);
+    } else {
+        selectedPaths.add(path);
+    }
+    itemFor(path)?.classList.toggle('selected', selectedPaths.has(path));
+    updateSelection();
+}
+
+// 全选已加载的图片
+function selectAll() {
+    document.querySelectorAll('.image-item').forEach(el => {
+        selectedPaths.add(el.dataset.path);
+        el.classList.add('selected');
+    });
+    updateSelection();
+}
+
+function updateSelection() {
+    // 已不在页面上的图片（被删除或筛掉）不再算作选中
+    const present = new Set(Array.from(document.querySelectorAll('.image-item')).map(el => el.dataset.path));
+    selectedPaths.forEach(path => { if (!present.has(path)) selectedPaths.delete(path); });
+    document.getElementById('selectionCount').textContent = `${selectedPaths.size} selected`;
+    document.querySelectorAll('.selection-bar .needs-selection').forEach(btn => { btn.disabled = selectedPaths.size === 0; });
+}
+
+(function() {
+    const gallery = document.getElementById('gallery');
+    let pressTimer = null;
+    let longPressed = false;
+    gallery.addEventListener('touchstart', e => {
+        const item = e.target.closest('.image-item');
+        if (!item || e.touches.length > 1) return;
+        longPressed = false;
+        pressTimer = setTimeout(() => {
+            longPressed = true;
+            if (!selecting) toggleSelecting(true);
+            toggleSelected(item.dataset.path);
+            if (navigator.vibrate) navigator.vibrate(30);
+        }, 500);
+    }, { passive: true });
+    const cancel = () => clearTimeout(pressTimer);
+    gallery.addEventListener('touchmove', cancel, { passive: true });
+    gallery.addEventListener('touchend', cancel);
+    gallery.addEventListener('touchcancel', cancel);
+    // 长按后松手产生的 click 不再切换一次
+    gallery.addEventListener('click', e => {
+        if (longPressed) {
+            longPressed = false;
+            e.stopPropagation();
+        }
+    }, true);
+    gallery.addEventListener('contextmenu', e => {
+        if (selecting && e.target.closest('.image-item')) e.preventDefault();
+    });
+})();
+
+// 用表单提交，浏览器直接把 ZIP 存成下载文件，不经过页面内存
+function downloadSelected() {
+    if (selectedPaths.size === 0) return;
+    const zipName = filters.album || (filters.folder ? filters.folder.split('/').pop() : 'images');
+    // 选中的不多时用链接下载，浏览器可以暂停和续传；路径太多放不进 URL 时提交表单
+    const query = new URLSearchParams({ paths: Array.from(selectedPaths).join('\n'), name: zipName });
+    const url = BASE + '/api/zip?' + query;
+    if (url.length <= 2000) {
+        const link = document.createElement('a');
+        link.href = url;
+        document.body.appendChild(link);
+        link.click();
+        link.remove();
+        return;
+    }
+    const form = document.createElement('form');
+    form.method = 'post';
+    form.action = BASE + '/api/zip';
+    form.style.display = 'none';
+    const paths = document.createElement('textarea');
+    paths.name = 'paths';
+    paths.value = Array.from(selectedPaths).join('\n');
+    const name = document.createElement('input');
+    name.name = 'name';
+    name.value = zipName;
+    form.append(paths, name);
+    document.body.appendChild(form);
+    form.submit();
+    form.remove();
+}
+
+// 登录状态，由 /api/session 加载
+let session = { enabled: false, authenticated: false };
+
+async function loadSession() {
+    try {
+        const res = await fetch(BASE + '/api/session');
+        if (res.ok) session = await res.json();
+    } catch (error) {}
+    showSession();
+}
+
+function showSession() {
+    document.getElementById('loginBtn').hidden = !session.enabled || session.authenticated;
+    document.getElementById('logoutForm').hidden = !session.authenticated;
+}
+
+function loginUrl() {
+    return BASE + '/login?' + new URLSearchParams({ next: location.pathname + location.search });
+}
+
+// 删除、移动和标签需要管理权限：登录后由会话 Cookie 认证，请求头为空；
+// 之前保存在浏览器中的管理令牌仍然可用；都没有时转到登录页，返回 null
+function adminAuth() {
+    if (session.authenticated) return {};
+    const token = localStorage.getItem('admin-token');
+    if (token) return { 'Authorization': 'Bearer ' + token };
+    location.href = loginUrl();
+    return null;
+}
+
+// 401/403 时清除失效的令牌和登录状态，返回要显示的提示
+function authFailed(status, disabled) {
+    localStorage.removeItem('admin-token');
+    if (status !== 401) return disabled;
+    session.authenticated = false;
+    showSession();
+    return 'Please log in again';
+}
+
+// 把当前图片顺时针旋转 90° 并保存到原图
+async function rotateImage() {
+    const img = imageList[currentIndex];
+    const auth = img && adminAuth();
+    if (!auth) return;
+    const encoded = img.path.split('/').map(encodeURIComponent).join('/');
+    try {
+        const res = await fetch(BASE + '/api/images/' + encoded + '/rotate', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json', ...auth },
+            body: JSON.stringify({ degrees: 90 }),
+        });
+        if (res.status === 401 || res.status === 403) {
+            showToast(authFailed(res.status, 'Editing is disabled'));
+            return;
+        }
+        const data = await res.json();
+        if (!res.ok) {
+            showToast(data.error ? data.error.message : 'Failed');
+            return;
+        }
+        // 地址没有变，加上版本参数绕过浏览器和 Service Worker 中的旧图
+        const version = 'v=' + Date.now();
+        if (imageList[currentIndex]?.path === img.path) {
+            const src = picUrl(img);
+            document.getElementById('modalImage').src = src + (src.includes('?') ? '&' : '?') + version;
+        }
+        const item = itemFor(img.path);
+        if (item) {
+            const ratio = tileRatio(data);
+            if (ratio) item.style.setProperty('--ratio', ratio);
+            item.querySelector('img').src = BASE + '/thumb/' + img.path + '?' + version;
+        }
+        delete detailsCache[img.path];
+        showDetails();
+    } catch (error) {
+        showToast('Failed');
+    }
+}
+
+async function batchAction(body) {
+    const auth = adminAuth();
+    if (!auth) return null;
+    try {
+        const res = await fetch(BASE + '/api/admin/batch', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json', ...auth },
+            body: JSON.stringify({ ...body, paths: Array.from(selectedPaths) }),
+        });
+        if (res.status === 401 || res.status === 403) {
+            showToast(authFailed(res.status, 'Batch actions are disabled'));
+            return null;
+        }
+        const data = await res.json();
+        if (!res.ok) {
+            showToast(data.error ? data.error.message : 'Failed');
+            return null;
+        }
+        if (data.failed.length > 0) {
+            console.error('批量操作失败:', data.failed);
+            showToast(`${data.ok} done, ${data.failed.length} failed: ${data.failed[0].error}`);
+        }
+        return data;
+    } catch (error) {
+        showToast('Failed');
+        return null;
+    }
+}
+
+async function deleteSelected() {
+    const count = selectedPaths.size;
+    if (count === 0 || !confirm(`Delete ${count} image${count > 1 ? 's' : ''}? This cannot be undone.`)) return;
+    const data = await batchAction({ action: 'delete' });
+    if (!data) return;
+    if (data.failed.length === 0) showToast(`Deleted ${data.ok}`);
+    toggleSelecting(false);
+    checkForUpdates();
+}
+
+async function moveSelected() {
+    if (selectedPaths.size === 0) return;
+    const to = prompt('Move to folder (empty for the root folder)', filters.folder);
+    if (to === null) return;
+    const data = await batchAction({ action: 'move', to: to.trim() });
+    if (!data) return;
+    if (data.failed.length === 0) showToast(`Moved ${data.ok} to ${to.trim() || '/'}`);
+    toggleSelecting(false);
+    checkForUpdates();
+}
+
+// 逗号分隔，以 - 开头的标签表示移除
+async function tagSelected() {
+    if (selectedPaths.size === 0) return;
+    const input = prompt('Tags, comma separated (prefix with - to remove)');
+    if (!input) return;
+    const tags = input.split(',').map(t => t.trim()).filter(t => t && t !== '-');
+    const add = tags.filter(t => !t.startsWith('-'));
+    const remove = tags.filter(t => t.startsWith('-')).map(t => t.slice(1).trim());
+    let ok = true;
+    if (add.length > 0) ok = !!(await batchAction({ action: 'tag', tags: add }));
+    if (ok && remove.length > 0) ok = !!(await batchAction({ action: 'untag', tags: remove }));
+    if (!ok) return;
+    showToast(`Tagged ${selectedPaths.size} image${selectedPaths.size > 1 ? 's' : ''}`);
+    if (filters.q.startsWith('#')) checkForUpdates();
+}
+
+// 上传：每个文件单独请求以显示各自的进度，同时最多上传 3 个，失败的可以重试
+const UPLOAD_CONCURRENCY = 3;
+const uploadQueue = [];
+const uploadTasks = [];
+let uploadsActive = 0;
+let uploadHideTimer = null;
+
+function uploadFiles(files) {
+    files = Array.from(files || []);
+    document.getElementById('uploadInput').value = '';
+    if (files.length === 0 || !adminAuth()) return;
+    clearTimeout(uploadHideTimer);
+    document.getElementById('uploadPanel').hidden = false;
+    // 上传到当前筛选的目录
+    const folder = filters.folder;
+    files.forEach(file => {
+        const row = document.createElement('div');
+        row.className = 'upload-row';
+        row.innerHTML = `<span class="upload-name"></span><span class="upload-status">Waiting</span><div class="upload-bar"><div class="upload-bar-fill"></div></div>`;
+        row.querySelector('.upload-name').textContent = file.name;
+        document.getElementById('uploadList').appendChild(row);
+        const task = { file, folder, row, state: 'waiting' };
+        uploadTasks.push(task);
+        uploadQueue.push(task);
+    });
+    pumpUploads();
+}
+
+function setUploadState(task, state, text, progress) {
+    task.state = state;
+    task.row.classList.toggle('done', state === 'done');
+    task.row.classList.toggle('failed', state === 'failed');
+    task.row.querySelector('.upload-status').textContent = text;
+    if (progress != null) task.row.querySelector('.upload-bar-fill').style.width = (progress * 100) + '%';
+    const retry = task.row.querySelector('button');
+    if (state === 'failed' && !retry) {
+        const button = document.createElement('button');
+        button.textContent = 'Retry';
+        button.onclick = () => {
+            if (!adminAuth()) return;
+            button.remove();
+            setUploadState(task, 'waiting', 'Waiting', 0);
+            uploadQueue.push(task);
+            pumpUploads();
+        };
+        task.row.querySelector('.upload-status').after(button);
+    }
+}
+
+function pumpUploads() {
+    while (uploadsActive < UPLOAD_CONCURRENCY && uploadQueue.length > 0) {
+        startUpload(uploadQueue.shift());
+    }
+    const done = uploadTasks.filter(t => t.state === 'done').length;
+    const failed = uploadTasks.filter(t => t.state === 'failed').length;
+    const title = document.getElementById('uploadTitle');
+    if (uploadsActive > 0 || uploadQueue.length > 0) {
+        title.textContent = `Uploading ${done + failed + 1} of ${uploadTasks.length}`;
+    } else {
+        title.textContent = failed > 0 ? `${done} uploaded, ${failed} failed` : `${done} uploaded`;
+        // 全部成功时稍后自动收起
+        if (failed === 0) uploadHideTimer = setTimeout(closeUploads, 3000);
+    }
+}
+
+function startUpload(task) {
+    const auth = adminAuth();
+    if (!auth) {
+        setUploadState(task, 'failed', 'Not logged in');
+        return;
+    }
+    uploadsActive++;
+    setUploadState(task, 'uploading', '0%', 0);
+    const xhr = new XMLHttpRequest();
+    const query = task.folder ? '?' + new URLSearchParams({ folder: task.folder }) : '';
+    xhr.open('POST', BASE + '/api/upload' + query);
+    Object.entries(auth).forEach(([name, value]) => xhr.setRequestHeader(name, value));
+    xhr.upload.onprogress = e => {
+        if (e.lengthComputable) setUploadState(task, 'uploading', Math.floor(e.loaded / e.total * 100) + '%', e.loaded / e.total);
+    };
+    xhr.onload = () => {
+        let data = {};
+        try { data = JSON.parse(xhr.responseText); } catch (e) {}
+        if (xhr.status === 401 || xhr.status === 403) {
+            setUploadState(task, 'failed', authFailed(xhr.status, 'Uploads are disabled'));
+        } else if (xhr.status === 200 && data.uploaded && data.uploaded.length > 0) {
+                setUploadState(task, 'done', 'Done', 1);
+            data.uploaded.forEach(insertUploaded);
+        } else {
+            // 出错时为 {"error": {"code", "message", "details"}}，details 中是每个失败文件的原因
+            const failed = data.error && data.error.details;
+            const error = (failed && failed[0] && failed[0].error) || (data.error && data.error.message) || ('HTTP ' + xhr.status);
+            setUploadState(task, 'failed', error);
+        }
+    };
+    xhr.onerror = () => setUploadState(task, 'failed', 'Network error');
+    xhr.onloadend = () => {
+        uploadsActive--;
+        pumpUploads();
+    };
+    const form = new FormData();
+    form.append('file', task.file);
+    xhr.send(form);
+}
+
+// 上传成功后立即显示新缩略图，不必等待推送事件；随后的刷新会把它排到正确的位置
+function insertUploaded(img) {
+    const gallery = document.getElementById('gallery');
+    if (itemFor(img.path)) return;
+    if (filters.folder && !img.path.startsWith(filters.folder + '/')) return;
+    // 按大小排序或有其他筛选条件时位置无法确定，交给刷新处理
+    if (filters.ext || filters.q || filters.album || filters.sort === 'size') return;
+    const el = createImageElement(img);
+    const items = Array.from(gallery.querySelectorAll('.image-item'));
+    let before = null;
+    if (filters.sort === 'name') {
+        before = items.find(item => filters.order === 'desc' ? item.dataset.path < img.path : item.dataset.path > img.path) || null;
+        // 新图片排在已加载部分之后时留给滚动加载
+        if (!before && hasMore()) return;
+    } else if (filters.order === 'desc') {
+        before = items[0] || null;
+    } else if (hasMore()) {
+        return;
+    }
+    totalImages++;
+    updateCount();
+    gallery.insertBefore(el, before);
+}
+
+function closeUploads() {
+    if (uploadsActive > 0 || uploadQueue.length > 0) return;
+    clearTimeout(uploadHideTimer);
+    document.getElementById('uploadPanel').hidden = true;
+    document.getElementById('uploadList').innerHTML = '';
+    uploadTasks.length = 0;
+}
+
+// 把图片文件拖到页面上即可上传
+document.addEventListener('dragover', e => {
+    if (!e.dataTransfer || !Array.from(e.dataTransfer.types).includes('Files')) return;
+    e.preventDefault();
+    document.body.classList.add('dragging');
+});
+document.addEventListener('dragleave', e => {
+    if (!e.relatedTarget) document.body.classList.remove('dragging');
+});
+document.addEventListener('drop', e => {
+    if (!e.dataTransfer || e.dataTransfer.files.length === 0) return;
+    e.preventDefault();
+    document.body.classList.remove('dragging');
+    uploadFiles(Array.from(e.dataTransfer.files).filter(f => f.type.startsWith('image/') || /\.(jpe?g|png|gif|webp|bmp|ico)$/i.test(f.name)));
+});
+
+// 目录页面（/gallery/{dir}）只显示该目录中的图片，目录条件固定
+const FOLDER = document.body.dataset.folder || '';
+
+// 排序和筛选条件，与尺寸设置一样保存在浏览器中
+// album 为智能相册的名称，在目录下拉框中与目录一起选择
+const DEFAULT_FILTERS = { sort: 'name', order: 'asc', folder: FOLDER, ext: '', q: '', album: '' };
+let filters = { ...DEFAULT_FILTERS };
+
+function listUrl(page, perPage) {
+    const params = new URLSearchParams({ page, per_page: perPage });
+    if (filters.sort !== 'name') params.set('sort', filters.sort);
+    if (filters.order !== 'asc') params.set('order', filters.order);
+    if (filters.folder) params.set('folder', filters.folder);
+    if (filters.album) params.set('album', filters.album);
+    if (filters.ext) params.set('ext', filters.ext);
+    // 以 # 开头的搜索按标签筛选
+    if (filters.q.startsWith('#')) {
+        params.set('tag', filters.q.slice(1).trim());
+    } else if (filters.q) {
+        params.set('q', filters.q);
+    }
+    return BASE + '/api/images?' + params;
+}
+
+// 目录下拉框的值：目录路径，或 `album:` 加相册名称
+function folderValue() {
+    return filters.album ? 'album:' + filters.album : filters.folder;
+}
+
+function ensureFolderOption(value) {
+    const select = document.getElementById('folderSelect');
+    if (value && !Array.from(select.options).some(o => o.value === value)) {
+        select.add(new Option(value.replace(/^album:/, ''), value));
+    }
+}
+
+function optionGroup(label, options) {
+    const group = document.createElement('optgroup');
+    group.label = label;
+    options.forEach(option => group.appendChild(option));
+    return group;
+}
+
+// 打开目录下拉框时获取最新的相册和目录列表
+async function loadFolders() {
+    try {
+        const [albums, folders] = await Promise.all([
+            fetch(BASE + '/api/albums').then(res => res.json()),
+            fetch(BASE + '/api/folders').then(res => res.json()),
+        ]);
+        const select = document.getElementById('folderSelect');
+        const selected = select.value;
+        while (select.children.length > 1) select.lastChild.remove();
+        const folderOptions = folders.map(f => new Option(`${f.path} (${f.count})`, f.path));
+        if (albums.length > 0) {
+            select.appendChild(optionGroup('Albums', albums.map(a => new Option(`${a.name} (${a.count})`, 'album:' + a.name))));
+            select.appendChild(optionGroup('Folders', folderOptions));
+        } else {
+            folderOptions.forEach(option => select.add(option));
+        }
+        ensureFolderOption(selected);
+        select.value = selected;
+    } catch (error) {
+        console.error('获取目录失败:', error);
+    }
+}
+
+// 把当前的筛选条件保存为智能相册，之后加入的符合条件的图片自动出现在相册中
+async function saveAlbum() {
+    const filter = {};
+    if (filters.folder) filter.folder = filters.folder;
+    if (filters.ext) filter.ext = filters.ext;
+    if (filters.q.startsWith('#')) {
+        filter.tag = filters.q.slice(1).trim();
+    } else if (filters.q) {
+        filter.q = filters.q;
+    }
+    if (Object.keys(filter).length === 0) {
+        showToast('Choose a folder, type or search first');
+        return;
+    }
+    const name = (prompt('Album name') || '').trim();
+    if (!name) return;
+    const auth = adminAuth();
+    if (!auth) return;
+    try {
+        const res = await fetch(BASE + '/api/admin/albums/' + encodeURIComponent(name), {
+            method: 'PUT',
+            headers: { 'Content-Type': 'application/json', ...auth },
+            body: JSON.stringify(filter),
+        });
+        if (res.status === 401 || res.status === 403) {
+            showToast(authFailed(res.status, 'Albums are disabled'));
+            return;
+        }
+        const data = await res.json();
+        showToast(res.ok ? `Saved album ${data.name} (${data.count})` : data.error.message);
+    } catch (error) {
+        console.error('保存相册失败:', error);
+        showToast('Failed to save album');
+    }
+}
+
+function showFilters() {
+    document.getElementById('sortSelect').value = filters.sort + ':' + filters.order;
+    if (!FOLDER) {
+        ensureFolderOption(folderValue());
+        document.getElementById('folderSelect').value = folderValue();
+    }
+    document.getElementById('extSelect').value = filters.ext;
+    document.getElementById('searchInput').value = filters.q;
+    updateFolderLink();
+}
+
+// 选择了目录时显示打开该目录页面的链接，方便收藏和分享
+function updateFolderLink() {
+    const link = document.getElementById('folderLink');
+    link.hidden = FOLDER !== '' || filters.folder === '';
+    link.href = BASE + '/gallery/' + filters.folder.split('/').map(encodeURIComponent).join('/');
+}
+
+// 保存条件；目录页面不改变首页保存的目录
+function saveFilters() {
+    let saved = filters;
+    if (FOLDER) {
+        let folder = '';
+        try {
+            folder = JSON.parse(localStorage.getItem('gallery-filters')).folder || '';
+        } catch (e) {}
+        saved = { ...filters, folder };
+    }
+    localStorage.setItem('gallery-filters', JSON.stringify(saved));
+}
+
+// 条件变化后从第一页重新加载
+function applyFilters() {
+    const [sort, order] = document.getElementById('sortSelect').value.split(':');
+    const selected = FOLDER ? '' : document.getElementById('folderSelect').value;
+    const album = selected.startsWith('album:') ? selected.slice('album:'.length) : '';
+    filters = {
+        sort,
+        order,
+        folder: FOLDER || (album ? '' : selected),
+        ext: document.getElementById('extSelect').value,
+        q: document.getElementById('searchInput').value.trim(),
+        album,
+    };
+    saveFilters();
+    updateFolderLink();
+    loadedPages = 1;
+    window.scrollTo(0, 0);
+    checkForUpdates();
+}
+
+let searchTimer = null;
+function searchChanged() {
+    clearTimeout(searchTimer);
+    searchTimer = setTimeout(applyFilters, 300);
+}
+
+function renderedCount() {
+    return document.querySelectorAll('#gallery .image-item').length;
+}
+
+function hasMore() {
+    return renderedCount() < totalImages;
+}
+
+function updateCount() {
+    document.getElementById('imageCount').textContent = totalImages;
+    const gallery = document.getElementById('gallery');
+    const emptyState = document.getElementById('emptyState');
+    if (totalImages === 0 && !emptyState) {
+        const filtered = JSON.stringify(filters) !== JSON.stringify(DEFAULT_FILTERS);
+        gallery.innerHTML = filtered
+            ? `<div class="empty-state" id="emptyState"><h2>No matching images</h2><p>Try other filters</p></div>`
+            : `<div class="empty-state" id="emptyState">
+            <h2>No images</h2>
+            <p>Add images to the directory</p>
+        </div>`;
+    } else if (totalImages > 0 && emptyState) {
+        emptyState.remove();
+    }
+}
+
+// 加载下一批图片，追加到画廊末尾
+function loadMore() {
+    if (loadingMore) return loadingMore;
+    if (!hasMore()) return Promise.resolve();
+    loadingMore = (async () => {
+        try {
+            const response = await fetch(listUrl(loadedPages + 1, PAGE_SIZE));
+            const data = await response.json();
+            const gallery = document.getElementById('gallery');
+            const rendered = new Set(Array.from(gallery.querySelectorAll('.image-item')).map(el => el.dataset.path));
+            // 滚动期间有图片增删时页边界会移动，跳过已经显示的
+            data.images
+                .filter(img => !rendered.has(img.path))
+                .forEach(img => gallery.appendChild(createImageElement(img)));
+            loadedPages++;
+            totalImages = data.count;
+            updateCount();
+        } catch (error) {
+            console.error('加载图片失败:', error);
+        } finally {
+            loadingMore = null;
+        }
+    })();
+    return loadingMore.then(fillViewport);
+}
+
+// 加载一批后底部仍在可视范围内（大屏或小尺寸缩略图）时继续加载
+function fillViewport() {
+    const sentinel = document.getElementById('loadMore');
+    if (hasMore() && sentinel.getBoundingClientRect().top < window.innerHeight + 800) {
+        return loadMore();
+    }
+}
+
+new IntersectionObserver(entries => {
+    if (entries.some(entry => entry.isIntersecting)) loadMore();
+}, { rootMargin: '800px' }).observe(document.getElementById('loadMore'));
+
+// 图片增删事件：提示后重新获取已加载的部分
+let refreshTimer = null;
+function applyChanges(added, removed) {
+    if (added.length > 0) {
+        showToast(`+${added.length} image${added.length > 1 ? 's' : ''}`);
+    }
+    if (removed.length > 0) {
+        showToast(`-${removed.length} image${removed.length > 1 ? 's' : ''}`);
+    }
+    // 一次扫描会连续推送多个事件，合并成一次请求
+    clearTimeout(refreshTimer);
+    refreshTimer = setTimeout(checkForUpdates, 300);
+}
+
+// 重新获取已加载的部分并更新页面，已有的缩略图元素原样保留；
+// 用于首次连接、断线重连和图片增删后补齐变化
+async function checkForUpdates() {
+    try {
+        const response = await fetch(listUrl(1, loadedPages * PAGE_SIZE));
+        // 保存的相册已被删除时回到全部图片
+        if (response.status === 404 && filters.album) {
+            showToast('Album not found: ' + filters.album);
+            filters.album = '';
+            saveFilters();
+            showFilters();
+            return checkForUpdates();
+        }
+        const data = await response.json();
+        const gallery = document.getElementById('gallery');
+        const existing = new Map(Array.from(gallery.querySelectorAll('.image-item')).map(el => [el.dataset.path, el]));
+        const unchanged = data.count === totalImages
+            && data.images.length === existing.size
+            && data.images.every((img, i) => gallery.children[i]?.dataset.path === img.path);
+        if (unchanged) return;
+        const fragment = document.createDocumentFragment();
+        data.images.forEach(img => fragment.appendChild(existing.get(img.path) || createImageElement(img)));
+        gallery.replaceChildren(fragment);
+        totalImages = data.count;
+        updateCount();
+        if (selecting) updateSelection();
+        fillViewport();
+    } catch (error) {
+        console.error('检查更新失败:', error);
+    }
+}
+
+function formatEta(secs) {
+    if (secs < 60) return secs + 's';
+    if (secs < 3600) return Math.round(secs / 60) + 'm';
+    return Math.floor(secs / 3600) + 'h ' + Math.round(secs % 3600 / 60) + 'm';
+}
+
+// 显示扫描和缩略图预生成的进度，结束时重新获取列表（首次扫描不产生增删事件）
+let scanRunning = false;
+function showScanStatus(status) {
+    const box = document.getElementById('scanStatus');
+    if (status.phase === 'idle') {
+        box.hidden = true;
+        if (scanRunning) checkForUpdates();
+        scanRunning = false;
+        return;
+    }
+    scanRunning = true;
+    box.hidden = false;
+    const labels = { scanning: 'Scanning', indexing: 'Indexing', thumbnails: 'Thumbnails' };
+    let text = labels[status.phase] || status.phase;
+    const bar = document.getElementById('scanBar');
+    if (status.total) {
+        bar.classList.remove('indeterminate');
+        document.getElementById('scanBarFill').style.width = (status.done / status.total * 100) + '%';
+        text += ` ${status.done.toLocaleString()} / ${status.total.toLocaleString()}`;
+        if (status.eta_secs != null) text += ` · ${formatEta(status.eta_secs)} left`;
+    } else {
+        bar.classList.add('indeterminate');
+        text += ` ${status.done.toLocaleString()} files`;
+    }
+    document.getElementById('scanText').textContent = text;
+}
+
+// 维护模式提示，进入或退出时由服务端推送
+function showMaintenance(status) {
+    document.getElementById('maintenanceBanner').hidden = !status.enabled;
+    document.getElementById('maintenanceText').textContent =
+        status.message || 'Uploads and edits are paused, browsing still works.';
+}
+
+// 恢复保存的排序和筛选条件；首页按默认条件渲染，条件不同时重新加载
+(function() {
+    try {
+        const saved = JSON.parse(localStorage.getItem('gallery-filters'));
+        if (saved) filters = { ...DEFAULT_FILTERS, ...saved };
+    } catch (e) {}
+    if (FOLDER) filters.folder = FOLDER;
+    showFilters();
+    if (JSON.stringify(filters) !== JSON.stringify(DEFAULT_FILTERS)) checkForUpdates();
+})();
+
+fetch(BASE + '/api/scan/status').then(res => res.json()).then(showScanStatus).catch(() => {});
+
+loadSession();
+
+// 服务端推送图片增删事件；不支持 EventSource 的浏览器每 3 秒轮询一次
+if (window.EventSource) {
+    const events = new EventSource(BASE + '/api/events');
+    events.onopen = () => {
+        checkForUpdates();
+        fetch(BASE + '/api/maintenance').then(res => res.json()).then(showMaintenance).catch(() => {});
+    };
+    events.addEventListener('image.added', e => applyChanges([JSON.parse(e.data)], []));
+    events.addEventListener('image.removed', e => applyChanges([], [JSON.parse(e.data).path]));
+    events.addEventListener('resync', checkForUpdates);
+    events.addEventListener('scan', e => showScanStatus(JSON.parse(e.data)));
+    events.addEventListener('maintenance', e => showMaintenance(JSON.parse(e.data)));
+} else {
+    setInterval(checkForUpdates, 3000);
+}
+
+// 注册 Service Worker（需要 HTTPS 或 localhost），缓存页面和缩略图，离线时也能浏览
+if ('serviceWorker' in navigator && window.isSecureContext) {
+    navigator.serviceWorker.register(BASE + '/sw.js').catch(error => console.error('Service Worker 注册失败:', error));
+}
+window.addEventListener('offline', () => showToast('Offline, showing cached images'));
+window.addEventListener('online', () => {
+    showToast('Back online');
+    checkForUpdates();
+});
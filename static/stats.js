@@ -0,0 +1,153 @@
+// 统计页面：/api/stats 的结果画成柱状图，地址栏的筛选参数原样传给接口
+// 画廊挂载的路径前缀（页面的 data-base），挂在根路径时为空
+const BASE = document.documentElement.dataset.base || '';
+const FILTER = new URLSearchParams(location.search);
+
+function formatSize(bytes) {
+    const units = ['B', 'KB', 'MB', 'GB', 'TB'];
+    let size = bytes;
+    let unit = 0;
+    while (size >= 1024 && unit < units.length - 1) {
+        size /= 1024;
+        unit++;
+    }
+    return (unit === 0 ? size : size.toFixed(1)) + ' ' + units[unit];
+}
+
+function monthLabel(month) {
+    const [year, number] = month.split('-').map(Number);
+    return new Date(year, number - 1, 1).toLocaleDateString('en', { month: 'short', year: 'numeric' });
+}
+
+// 从第一个月到最后一个月，没有图片的月份补 0，柱子之间的间隔才与时间对应
+function fillMonths(months) {
+    if (months.length === 0) return [];
+    const counts = new Map(months.map(m => [m.month, m.count]));
+    let [year, month] = months[0].month.split('-').map(Number);
+    const last = months[months.length - 1].month;
+    const filled = [];
+    for (;;) {
+        const key = year + '-' + String(month).padStart(2, '0');
+        filled.push({ month: key, count: counts.get(key) || 0 });
+        if (key >= last) break;
+        month++;
+        if (month > 12) {
+            month = 1;
+            year++;
+        }
+    }
+    return filled;
+}
+
+function renderMonths(months) {
+    const chart = document.getElementById('monthChart');
+    const axis = document.getElementById('monthAxis');
+    const filled = fillMonths(months);
+    const max = Math.max(1, ...filled.map(m => m.count));
+    chart.replaceChildren();
+    axis.replaceChildren();
+    filled.forEach(m => {
+        // 点击跳到时间线上的这个月
+        const bar = document.createElement('a');
+        bar.className = 'month-bar';
+        bar.href = BASE + '/timeline#' + m.month;
+        bar.title = monthLabel(m.month) + ': ' + m.count;
+        bar.style.height = (m.count / max) * 100 + '%';
+        chart.appendChild(bar);
+    });
+    // 横轴只标出每年的第一个月
+    filled.forEach((m, i) => {
+        if (i > 0 && !m.month.endsWith('-01')) return;
+        const label = document.createElement('span');
+        label.textContent = m.month.slice(0, 4);
+        label.style.left = (i / filled.length) * 100 + '%';
+        axis.appendChild(label);
+    });
+}
+
+// 横向柱状图，value 决定柱子长度，text 是右侧显示的数值
+function renderBars(id, rows) {
+    const list = document.getElementById(id);
+    list.replaceChildren();
+    if (rows.length === 0) {
+        const empty = document.createElement('div');
+        empty.className = 'bar-empty';
+        empty.textContent = 'No data';
+        list.appendChild(empty);
+        return;
+    }
+    const max = Math.max(1, ...rows.map(row => row.value));
+    rows.forEach(row => {
+        const item = document.createElement(row.href ? 'a' : 'div');
+        item.className = 'bar-row';
+        if (row.href) item.href = row.href;
+        const label = document.createElement('span');
+        label.className = 'bar-label';
+        label.textContent = row.label;
+        label.title = row.label;
+        const track = document.createElement('span');
+        track.className = 'bar-track';
+        const fill = document.createElement('span');
+        fill.className = 'bar-fill';
+        fill.style.width = (row.value / max) * 100 + '%';
+        track.appendChild(fill);
+        const value = document.createElement('span');
+        value.className = 'bar-value';
+        value.textContent = row.text;
+        item.append(label, track, value);
+        list.appendChild(item);
+    });
+}
+
+function galleryUrl(folder) {
+    return BASE + '/gallery/' + folder.split('/').map(encodeURIComponent).join('/');
+}
+
+async function loadStats() {
+    let stats;
+    try {
+        const query = FILTER.toString();
+        const response = await fetch(BASE + '/api/stats' + (query ? '?' + query : ''));
+        const data = await response.json();
+        if (!response.ok) throw new Error(data.error ? data.error.message : response.status);
+        stats = data;
+    } catch (error) {
+        console.error('加载失败:', error);
+        showToast('Failed to load statistics');
+        return;
+    }
+    document.getElementById('imageCount').textContent = stats.count;
+    document.getElementById('totalSize').textContent = formatSize(stats.bytes);
+    document.getElementById('emptyState').hidden = stats.count > 0;
+    document.getElementById('stats').hidden = stats.count === 0;
+
+    renderMonths(stats.months);
+    renderBars('folderChart', stats.folders.map(f => ({
+        label: f.folder || '(root)',
+        value: f.bytes,
+        text: formatSize(f.bytes) + ' · ' + f.count,
+        href: f.folder ? galleryUrl(f.folder) : BASE + '/'
+    })));
+    const cameras = stats.cameras.map(c => ({ label: c.camera, value: c.count, text: c.count }));
+    if (stats.other_cameras > 0) {
+        cameras.push({ label: 'Other', value: stats.other_cameras, text: stats.other_cameras });
+    }
+    if (stats.unknown_camera > 0) {
+        cameras.push({ label: 'Unknown', value: stats.unknown_camera, text: stats.unknown_camera });
+    }
+    renderBars('cameraChart', cameras);
+    renderBars('formatChart', stats.formats.map(f => ({
+        label: f.format ? f.format.toUpperCase() : '(none)',
+        value: f.count,
+        text: f.count + ' · ' + formatSize(f.bytes)
+    })));
+}
+
+function showToast(message) {
+    const toast = document.getElementById('toast');
+    toast.textContent = message;
+    toast.classList.add('show');
+    setTimeout(() => toast.classList.remove('show'), 3000);
+}
+
+loadStats();
@@ -0,0 +1,276 @@
+// 时间线页面：先取 年/月/日 的数量画出分组，月份滚动到附近时才加载其中的图片
+// 地址栏的筛选参数（folder、tag、q 等）原样传给 /api/timeline
+// 画廊挂载的路径前缀（页面的 data-base），挂在根路径时为空
+const BASE = document.documentElement.dataset.base || '';
+const FILTER = new URLSearchParams(location.search);
+FILTER.delete('month');
+// 每张缩略图的预估高度，用于在加载前给月份预留空间
+const ESTIMATED_TILE = 150;
+
+let months = [];
+let imageList = [];
+let currentIndex = 0;
+
+function apiUrl(month) {
+    const params = new URLSearchParams(FILTER);
+    if (month) params.set('month', month);
+    const query = params.toString();
+    return BASE + '/api/timeline' + (query ? '?' + query : '');
+}
+
+function monthLabel(month) {
+    if (month === 'undated') return 'Undated';
+    const [year, number] = month.split('-').map(Number);
+    return new Date(year, number - 1, 1).toLocaleDateString('en', { month: 'long', year: 'numeric' });
+}
+
+function dayLabel(date) {
+    const [year, month, day] = date.split('-').map(Number);
+    return new Date(year, month - 1, day).toLocaleDateString('en', { weekday: 'short', month: 'short', day: 'numeric' });
+}
+
+function countLabel(count) {
+    const span = document.createElement('span');
+    span.className = 'group-count';
+    span.textContent = count;
+    return span;
+}
+
+function section(className, title, count) {
+    const details = document.createElement('details');
+    details.className = className;
+    details.open = true;
+    const summary = document.createElement('summary');
+    summary.append(title, countLabel(count));
+    details.appendChild(summary);
+    return details;
+}
+
+function monthSection(month, count) {
+    const details = section('timeline-month', monthLabel(month), count);
+    details.dataset.month = month;
+    const body = document.createElement('div');
+    body.className = 'month-body loading';
+    body.style.minHeight = Math.ceil(count / 6) * ESTIMATED_TILE + 'px';
+    details.appendChild(body);
+    months.push({ month, count, year: month === 'undated' ? month : month.slice(0, 4), element: details });
+    return details;
+}
+
+// 按比例排版时的宽高比，与画廊一致：限制在 4:1 以内
+function tileRatio(img) {
+    if (!img.width || !img.height) return null;
+    const ratio = Math.min(4, Math.max(0.25, img.width / img.height));
+    return Math.round(ratio * 1000) / 1000;
+}
+
+function createImageElement(img) {
+    const div = document.createElement('div');
+    div.className = 'image-item';
+    div.dataset.path = img.path;
+    if (img.taken_at) div.dataset.taken = img.taken_at;
+    if (img.url) div.dataset.url = img.url;
+    const ratio = tileRatio(img);
+    if (ratio) div.style.setProperty('--ratio', ratio);
+    div.onclick = () => openModal(img.path);
+    const thumb = document.createElement('img');
+    thumb.src = BASE + '/thumb/' + img.path;
+    thumb.alt = img.path;
+    thumb.loading = 'lazy';
+    const overlay = document.createElement('div');
+    overlay.className = 'overlay';
+    const name = document.createElement('div');
+    name.className = 'image-name';
+    name.textContent = img.name;
+    overlay.appendChild(name);
+    div.append(thumb, overlay);
+    return div;
+}
+
+async function loadMonth(details) {
+    if (details.dataset.loaded) return;
+    details.dataset.loaded = 'true';
+    const body = details.querySelector('.month-body');
+    try {
+        const response = await fetch(apiUrl(details.dataset.month));
+        if (!response.ok) throw new Error(response.status);
+        const data = await response.json();
+        // 按天分组，接口返回的图片已经是新的在前
+        const days = new Map();
+        for (const img of data.images) {
+            const day = img.taken_at ? img.taken_at.slice(0, 10) : '';
+            if (!days.has(day)) days.set(day, []);
+            days.get(day).push(img);
+        }
+        body.replaceChildren();
+        for (const [day, images] of days) {
+            const gallery = document.createElement('div');
+            gallery.className = 'gallery size-medium';
+            images.forEach(img => gallery.appendChild(createImageElement(img)));
+            if (day) {
+                const dayDetails = section('timeline-day', dayLabel(day), images.length);
+                dayDetails.appendChild(gallery);
+                body.appendChild(dayDetails);
+            } else {
+                body.appendChild(gallery);
+            }
+        }
+        body.classList.remove('loading');
+        body.style.minHeight = '';
+    } catch (error) {
+        delete details.dataset.loaded;
+        console.error('加载失败:', error);
+        showToast('Failed to load ' + monthLabel(details.dataset.month));
+    }
+}
+
+// 月份进入视口附近时加载，提前一屏开始
+const monthObserver = new IntersectionObserver(entries => {
+    entries.forEach(entry => {
+        if (entry.isIntersecting) loadMonth(entry.target);
+    });
+}, { rootMargin: '100% 0px' });
+
+async function loadTimeline() {
+    let summary;
+    try {
+        const response = await fetch(apiUrl());
+        if (!response.ok) throw new Error(response.status);
+        summary = await response.json();
+    } catch (error) {
+        console.error('加载失败:', error);
+        showToast('Failed to load timeline');
+        return;
+    }
+    document.getElementById('imageCount').textContent = summary.count;
+    document.getElementById('emptyState').hidden = summary.count > 0;
+
+    const timeline = document.getElementById('timeline');
+    for (const year of summary.years) {
+        const yearDetails = section('timeline-year', year.year, year.count);
+        year.months.forEach(month => yearDetails.appendChild(monthSection(month.month, month.count)));
+        timeline.appendChild(yearDetails);
+    }
+    if (summary.undated > 0) {
+        timeline.appendChild(monthSection('undated', summary.undated));
+    }
+    months.forEach(month => monthObserver.observe(month.element));
+    buildScrubber(summary.count);
+    // 从统计页面链接到某个月：/timeline#2024-05
+    const target = months.find(month => '#' + month.month === location.hash);
+    if (target) target.element.scrollIntoView();
+}
+
+// 右侧滚动条：年份按图片数量的比例排列，点击或拖动跳到对应的月份
+function buildScrubber(total) {
+    const scrubber = document.getElementById('scrubber');
+    if (months.length < 2) return;
+    let before = 0;
+    let lastLabel = -1;
+    let lastYear = null;
+    for (const month of months) {
+        month.start = before / total;
+        before += month.count;
+        // 同一年的第一个月才显示年份标签
+        if (month.year === lastYear) continue;
+        lastYear = month.year;
+        // 年份太密时跳过标签，避免文字重叠
+        if (lastLabel >= 0 && month.start - lastLabel < 0.04) continue;
+        lastLabel = month.start;
+        const label = document.createElement('div');
+        label.className = 'scrubber-year';
+        label.dataset.year = month.year;
+        label.style.top = month.start * 100 + '%';
+        label.textContent = month.month === 'undated' ? '—' : month.year;
+        scrubber.appendChild(label);
+    }
+    scrubber.hidden = false;
+
+    let dragging = false;
+    const jump = e => {
+        const rect = scrubber.getBoundingClientRect();
+        const fraction = Math.min(1, Math.max(0, (e.clientY - rect.top) / rect.height));
+        let target = months[0];
+        for (const month of months) {
+            if (month.start <= fraction) target = month;
+        }
+        target.element.scrollIntoView();
+    };
+    scrubber.addEventListener('pointerdown', e => {
+        dragging = true;
+        scrubber.setPointerCapture(e.pointerId);
+        jump(e);
+    });
+    scrubber.addEventListener('pointermove', e => {
+        if (dragging) jump(e);
+    });
+    scrubber.addEventListener('pointerup', () => { dragging = false; });
+    scrubber.addEventListener('pointercancel', () => { dragging = false; });
+    window.addEventListener('scroll', updateScrubber, { passive: true });
+    updateScrubber();
+}
+
+// 标出当前看到的月份所在的位置和年份
+function updateScrubber() {
+    let current = months[0];
+    for (const month of months) {
+        if (month.element.getBoundingClientRect().top <= 70) current = month;
+    }
+    document.getElementById('scrubberMarker').style.top = current.start * 100 + '%';
+    document.querySelectorAll('.scrubber-year').forEach(label => {
+        label.classList.toggle('active', label.dataset.year === current.year);
+    });
+}
+
+function openModal(path) {
+    imageList = Array.from(document.querySelectorAll('.timeline .image-item')).map(el => ({
+        path: el.dataset.path,
+        taken: el.dataset.taken || '',
+        url: el.dataset.url
+    }));
+    currentIndex = Math.max(0, imageList.findIndex(img => img.path === path));
+    showImage(currentIndex);
+    document.getElementById('imageModal').classList.add('active');
+    document.body.style.overflow = 'hidden';
+}
+
+function showImage(index) {
+    if (imageList.length === 0) return;
+    currentIndex = (index + imageList.length) % imageList.length;
+    const img = imageList[currentIndex];
+    // 服务器开启原图签名时使用列表中签名过的地址
+    const src = BASE + (img.url || '/pic/' + img.path);
+    document.getElementById('modalImage').src = src;
+    document.getElementById('modalFileName').textContent = img.path;
+    document.getElementById('modalDate').textContent = img.taken.replace('T', ' ');
+    // 带 download=1 时服务器以附件返回，有些浏览器会忽略 download 属性直接打开图片
+    document.getElementById('modalDownload').href = src + (src.includes('?') ? '&' : '?') + 'download=1';
+    document.getElementById('modalOpen').href = src;
+    document.getElementById('modalCounter').textContent = (currentIndex + 1) + ' / ' + imageList.length;
+}
+
+function closeModal() {
+    document.getElementById('imageModal').classList.remove('active');
+    document.body.style.overflow = 'auto';
+}
+
+document.addEventListener('keydown', e => {
+    if (!document.getElementById('imageModal').classList.contains('active')) return;
+    if (e.key === 'Escape') {
+        closeModal();
+    } else if (e.key === 'ArrowRight' || e.key === ' ') {
+        e.preventDefault();
+        showImage(currentIndex + 1);
+    } else if (e.key === 'ArrowLeft') {
+        showImage(currentIndex - 1);
+    }
+});
+
+function showToast(message) {
+    const toast = document.getElementById('toast');
+    toast.textContent = message;
+    toast.classList.add('show');
+    setTimeout(() => toast.classList.remove('show'), 3000);
+}
+
+loadTimeline();
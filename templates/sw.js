@@ -0,0 +1,100 @@
+// 画廊的 Service Worker：缓存页面框架和最近浏览的缩略图，离线时也能打开画廊浏览
+// SHELL 是首页和带内容哈希的 CSS/JS 地址，由服务器生成；资源更新后这个文件随之变化，浏览器会安装新版本
+const SHELL = {{ shell | safe }};
+// 画廊挂载的路径前缀，挂在根路径时为空
+const BASE = {{ base | json_encode | safe }};
+const SHELL_CACHE = 'shell-{{ version }}';
+const THUMB_CACHE = 'thumbs';
+const API_CACHE = 'api';
+// 缓存的缩略图数量上限，超出后删除最久没有浏览的
+const MAX_THUMBS = 500;
+
+self.addEventListener('install', event => {
+    event.waitUntil(
+        caches.open(SHELL_CACHE)
+            .then(cache => cache.addAll(SHELL))
+            .then(() => self.skipWaiting())
+    );
+});
+
+self.addEventListener('activate', event => {
+    event.waitUntil(
+        caches.keys()
+            .then(keys => Promise.all(
+                keys.filter(key => key.startsWith('shell-') && key !== SHELL_CACHE).map(key => caches.delete(key))
+            ))
+            .then(() => self.clients.claim())
+    );
+});
+
+// 优先使用网络并更新缓存，离线时使用缓存
+async function networkFirst(request, cacheName, key = request) {
+    const cache = await caches.open(cacheName);
+    try {
+        const response = await fetch(request);
+        if (response.ok) cache.put(key, response.clone());
+        return response;
+    } catch (error) {
+        const cached = await cache.match(key);
+        if (cached) return cached;
+        throw error;
+    }
+}
+
+async function trimThumbnails(cache) {
+    const keys = await cache.keys();
+    for (const key of keys.slice(0, Math.max(0, keys.length - MAX_THUMBS))) {
+        await cache.delete(key);
+    }
+}
+
+// 缩略图先返回缓存，同时在后台更新；重新写入的条目排到最后，所以删除的总是最久没有浏览的
+async function thumbnail(event) {
+    const cache = await caches.open(THUMB_CACHE);
+    const cached = await cache.match(event.request);
+    const update = fetch(event.request).then(response => {
+        if (response.ok) {
+            event.waitUntil(cache.put(event.request, response.clone()).then(() => trimThumbnails(cache)));
+        }
+        return response;
+    });
+    if (cached) {
+        event.waitUntil(update.catch(() => {}));
+        return cached;
+    }
+    return update;
+}
+
+// 带版本的缩略图内容不会改变，命中缓存时不再请求
+async function versionedThumbnail(event) {
+    const cache = await caches.open(THUMB_CACHE);
+    const cached = await cache.match(event.request);
+    if (cached) return cached;
+    const response = await fetch(event.request);
+    if (response.ok && !response.redirected) {
+        event.waitUntil(cache.put(event.request, response.clone()).then(() => trimThumbnails(cache)));
+    }
+    return response;
+}
+
+self.addEventListener('fetch', event => {
+    const request = event.request;
+    const url = new URL(request.url);
+    if (request.method !== 'GET' || url.origin !== self.location.origin) return;
+    if (!url.pathname.startsWith(BASE + '/')) return;
+    const path = url.pathname.slice(BASE.length);
+
+    const page = path === '/' || path === '/timeline' || path === '/stats' || path.startsWith('/gallery/');
+    if (request.mode === 'navigate' && page) {
+        event.respondWith(networkFirst(request, SHELL_CACHE, url.pathname));
+    } else if (path.startsWith('/static/')) {
+        event.respondWith(caches.match(request).then(cached => cached || fetch(request)));
+    } else if (path.startsWith('/thumb/')) {
+        event.respondWith(thumbnail(event));
+    } else if (path.startsWith('/th/')) {
+        event.respondWith(versionedThumbnail(event));
+    } else if (['/api/images', '/api/folders', '/api/timeline', '/api/stats'].includes(path)) {
+        event.respondWith(networkFirst(request, API_CACHE));
+    }
+    // 原图、事件流和其他接口直接访问网络
+});
@@ -0,0 +1,63 @@
+//! 图片目录之外的目录作为别名目录扫描和访问 (`--alias`)
+
+mod common;
+
+use actix_web::{test, App};
+use common::Fixture;
+use pic_url::PicUrl;
+use serde_json::{json, Value};
+
+#[actix_web::test]
+async fn aliases_are_scanned_and_served_with_their_own_rules() {
+    let fixture = Fixture::new();
+    let outside = Fixture::new();
+    // 同名的真实子目录被别名遮住
+    fixture.image("shots/hidden.png", 8, 8, [1, 2, 3]);
+    let gallery = PicUrl::builder()
+        .pic_dir(fixture.root().to_string_lossy())
+        .prewarm(false)
+        .auth("secret")
+        .alias("shots", outside.root().to_string_lossy())
+        .alias_include("shots", "*.png")
+        .alias_exclude("shots", "100%*")
+        .build()
+        .unwrap();
+    let images = gallery.config().rescan();
+    assert!(images.contains(&"shots/a.png".to_string()));
+    assert!(images.contains(&"shots/空格 和 中文.png".to_string()));
+    for path in ["shots/b.jpg", "shots/hidden.png", "shots/100% #1?.png", "shots/trips/map.bmp"] {
+        assert!(!images.contains(&path.to_string()), "{}", path);
+    }
+    assert!(images.contains(&"trips/map.bmp".to_string()));
+    // 同一目录的另一个没有别名的配置不影响这个画廊
+    let _plain = PicUrl::builder().pic_dir(fixture.root().to_string_lossy()).prewarm(false).build().unwrap();
+
+    let app = test::init_service(App::new().service(gallery)).await;
+    let res = test::call_service(&app, test::TestRequest::get().uri("/pic/shots/a.png").to_request()).await;
+    assert_eq!(res.status(), 200);
+    let res = test::call_service(&app, test::TestRequest::get().uri("/pic/shots/b.jpg").to_request()).await;
+    assert_eq!(res.status(), 404);
+
+    // 删除等文件操作作用于别名指向的目录
+    let req = test::TestRequest::post()
+        .uri("/api/admin/batch")
+        .insert_header(("Authorization", "Bearer secret"))
+        .set_json(json!({"action": "delete", "paths": ["shots/a.png"]}))
+        .to_request();
+    let res: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(res["ok"], 1);
+    assert!(!outside.path("a.png").exists());
+    assert!(fixture.path("a.png").exists());
+}
+
+#[actix_web::test]
+async fn aliases_must_stay_outside_the_picture_directory() {
+    let fixture = Fixture::new();
+    let build = |builder: pic_url::PicUrlBuilder| builder.pic_dir(fixture.root().to_string_lossy()).build();
+    let inside = fixture.path("trips").to_string_lossy().to_string();
+    assert!(build(PicUrl::builder().alias("inside", &inside)).is_err());
+    assert!(build(PicUrl::builder().alias("missing", "/nonexistent/pic_url")).is_err());
+    let outside = Fixture::new();
+    let outside = outside.root().to_string_lossy().to_string();
+    assert!(build(PicUrl::builder().alias("shots", &outside).alias_include("other", "*.png")).is_err());
+}
@@ -0,0 +1,112 @@
+//! 集成测试共用的图片目录：不同格式、多层目录和特殊文件名
+
+#![allow(dead_code)]
+
+use image::{Rgb, RgbImage};
+use pic_url::PicUrl;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// 测试使用的缩略图尺寸，比默认值小，方便检查缩放结果
+pub const THUMB_SIZE: u32 = 32;
+
+pub struct Fixture {
+    dir: TempDir,
+}
+
+impl Fixture {
+    /// 图片目录中的文件：
+    ///
+    /// ```text
+    /// a.png  b.jpg  c.gif  notes.txt
+    /// 100% #1?.png  空格 和 中文.png  ~x~literal.png
+    /// trips/2024/beach.jpg  trips/2024/sunset.webp  trips/map.bmp
+    /// bad\xff.png（仅 Unix）
+    /// ```
+    pub fn new() -> Self {
+        let fixture = Self {
+            dir: tempfile::tempdir().unwrap(),
+        };
+        fixture.image("a.png", 64, 48, [255, 0, 0]);
+        fixture.image("b.jpg", 48, 64, [0, 255, 0]);
+        fixture.image("c.gif", 40, 40, [0, 0, 255]);
+        fixture.image("100% #1?.png", 20, 10, [10, 20, 30]);
+        fixture.image("空格 和 中文.png", 20, 10, [30, 20, 10]);
+        fixture.image("~x~literal.png", 20, 10, [40, 40, 40]);
+        fixture.image("trips/2024/beach.jpg", 80, 40, [200, 200, 0]);
+        fixture.image("trips/2024/sunset.webp", 40, 80, [200, 0, 200]);
+        fixture.image("trips/map.bmp", 16, 16, [0, 200, 200]);
+        fs::write(fixture.path("notes.txt"), "not an image").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            let name = std::ffi::OsStr::from_bytes(b"bad\xff.png");
+            RgbImage::from_pixel(20, 10, Rgb([50, 50, 50]))
+                .save_with_format(fixture.dir.path().join(name), image::ImageFormat::Png)
+                .unwrap();
+        }
+        fixture
+    }
+
+    pub fn root(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn path(&self, relative: &str) -> PathBuf {
+        self.dir.path().join(relative)
+    }
+
+    /// 写入一张纯色图片，格式由扩展名决定
+    pub fn image(&self, relative: &str, width: u32, height: u32, color: [u8; 3]) {
+        let path = self.path(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        RgbImage::from_pixel(width, height, Rgb(color)).save(&path).unwrap();
+    }
+
+    /// 挂在根路径下的画廊，已完成首次扫描；需要在 actix 运行时中调用
+    pub fn gallery(&self) -> PicUrl {
+        self.gallery_at("")
+    }
+
+    pub fn gallery_at(&self, mount_path: &str) -> PicUrl {
+        let gallery = PicUrl::builder()
+            .pic_dir(self.root().to_string_lossy())
+            .mount_path(mount_path)
+            .thumb_size(THUMB_SIZE)
+            .prewarm(false)
+            .build()
+            .unwrap();
+        gallery.config().rescan();
+        gallery
+    }
+
+    /// 缩略图目录中的全部文件
+    pub fn thumbnails(&self) -> Vec<PathBuf> {
+        fn walk(dir: &Path, out: &mut Vec<PathBuf>) {
+            for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    walk(&path, out);
+                } else {
+                    out.push(path);
+                }
+            }
+        }
+        let mut out = Vec::new();
+        walk(&self.path(&format!(".thumbnails/{}", THUMB_SIZE)), &mut out);
+        out
+    }
+}
+
+/// 与 `tests/golden/<name>.json` 比较；设置 `UPDATE_GOLDEN=1` 时改为写入当前结果
+pub fn assert_golden(name: &str, actual: &serde_json::Value) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{}.json", name));
+    let actual = serde_json::to_string_pretty(actual).unwrap() + "\n";
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|e| panic!("无法读取 {}: {}", path.display(), e));
+    assert_eq!(actual, expected, "与 {} 不一致，确认无误后用 UPDATE_GOLDEN=1 更新", path.display());
+}
@@ -0,0 +1,171 @@
+//! 原图和打包下载的断点续传 (`Range`)，导出选中的图片到服务器上的目录
+
+mod common;
+
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::http::StatusCode;
+use actix_web::{test, App};
+use common::Fixture;
+use pic_url::PicUrl;
+use std::fs;
+use std::io::Cursor;
+
+fn header(res: &actix_web::dev::ServiceResponse, name: &str) -> Option<String> {
+    res.headers().get(name).map(|value| value.to_str().unwrap().to_string())
+}
+
+#[actix_web::test]
+async fn originals_support_ranges() {
+    let fixture = Fixture::new();
+    let original = fs::read(fixture.path("a.png")).unwrap();
+    let app = test::init_service(App::new().service(fixture.gallery())).await;
+
+    let res = test::call_service(&app, test::TestRequest::get().uri("/pic/a.png").to_request()).await;
+    assert_eq!(header(&res, "Accept-Ranges").as_deref(), Some("bytes"));
+
+    let req = test::TestRequest::get().uri("/pic/a.png").insert_header(("Range", "bytes=10-19")).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(header(&res, "Content-Range"), Some(format!("bytes 10-19/{}", original.len())));
+    assert_eq!(test::read_body(res).await, original[10..20]);
+
+    let req = test::TestRequest::get()
+        .uri("/pic/a.png")
+        .insert_header(("Range", format!("bytes={}-", original.len() + 10)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+}
+
+#[actix_web::test]
+async fn zip_downloads_can_be_resumed() {
+    let fixture = Fixture::new();
+    let app = test::init_service(App::new().service(fixture.gallery())).await;
+    let zip = |range: Option<&str>, if_range: Option<&str>| {
+        let mut req = test::TestRequest::post()
+            .uri("/api/zip")
+            .set_form([("paths", "a.png\nb.jpg\ntrips/2024/beach.jpg\n空格 和 中文.png"), ("name", "photos")]);
+        if let Some(range) = range {
+            req = req.insert_header(("Range", range.to_string()));
+        }
+        if let Some(if_range) = if_range {
+            req = req.insert_header(("If-Range", if_range.to_string()));
+        }
+        req.to_request()
+    };
+
+    let res = test::call_service(&app, zip(None, None)).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(header(&res, "Accept-Ranges").as_deref(), Some("bytes"));
+    // 响应体的长度是事先确定的，发送时成为 Content-Length
+    let BodySize::Sized(length) = res.response().body().size() else {
+        panic!("ZIP 响应没有长度");
+    };
+    let length = length as usize;
+    let etag = header(&res, "ETag").unwrap();
+    let full = test::read_body(res).await;
+    // 实际生成的与事先给出的长度一致，且是完整的 ZIP
+    assert_eq!(full.len(), length);
+    let mut archive = zip::ZipArchive::new(Cursor::new(full.to_vec())).unwrap();
+    assert_eq!(archive.len(), 4);
+    let mut beach = Vec::new();
+    std::io::Read::read_to_end(&mut archive.by_name("trips/2024/beach.jpg").unwrap(), &mut beach).unwrap();
+    assert_eq!(beach, fs::read(fixture.path("trips/2024/beach.jpg")).unwrap());
+
+    // 从中间续传，得到的内容与完整下载的后半部分相同
+    let res = test::call_service(&app, zip(Some("bytes=100-"), Some(&etag))).await;
+    assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(header(&res, "Content-Range"), Some(format!("bytes 100-{}/{}", length - 1, length)));
+    assert_eq!(test::read_body(res).await, full[100..]);
+
+    let res = test::call_service(&app, zip(Some("bytes=0-9"), None)).await;
+    assert_eq!(test::read_body(res).await, full[..10]);
+
+    // GET 链接下载得到相同的内容
+    let req = test::TestRequest::get()
+        .uri(concat!(
+            "/api/zip?paths=a.png%0Ab.jpg%0Atrips%2F2024%2Fbeach.jpg",
+            "%0A%E7%A9%BA%E6%A0%BC%20%E5%92%8C%20%E4%B8%AD%E6%96%87.png&name=photos"
+        ))
+        .insert_header(("Range", "bytes=100-"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(test::read_body(res).await, full[100..]);
+
+    // 图片改变后 ETag 不同，按 If-Range 返回完整的新 ZIP
+    fixture.image("a.png", 30, 30, [1, 2, 3]);
+    let res = test::call_service(&app, zip(Some("bytes=100-"), Some(&etag))).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_ne!(header(&res, "ETag").unwrap(), etag);
+
+    let res = test::call_service(&app, zip(Some(&format!("bytes={}-", length * 2)), None)).await;
+    assert_eq!(res.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert!(header(&res, "Content-Range").unwrap().starts_with("bytes */"));
+}
+
+#[actix_web::test]
+async fn selections_export_to_folders_and_zips() {
+    let fixture = Fixture::new();
+    let exports = tempfile::tempdir().unwrap();
+    let gallery = PicUrl::builder()
+        .pic_dir(fixture.root().to_string_lossy())
+        .prewarm(false)
+        .auth("secret")
+        .export_dir(exports.path().to_string_lossy())
+        .build()
+        .unwrap();
+    gallery.config().rescan();
+    let app = test::init_service(App::new().service(gallery)).await;
+    let export = |body: serde_json::Value| {
+        test::TestRequest::post()
+            .uri("/api/export")
+            .insert_header(("Authorization", "Bearer secret"))
+            .set_json(body)
+            .to_request()
+    };
+    let finished = || async {
+        for _ in 0..500 {
+            let req = test::TestRequest::get()
+                .uri("/api/export")
+                .insert_header(("Authorization", "Bearer secret"))
+                .to_request();
+            let status: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+            if status["running"] == false {
+                return status["report"].clone();
+            }
+            actix_web::rt::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("导出没有完成");
+    };
+
+    // 指定路径，长边超过 40 的缩小
+    let req = export(serde_json::json!({"name": "best", "paths": ["a.png", "trips/2024/beach.jpg"], "max_size": 40}));
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::ACCEPTED);
+    let report = finished().await;
+    assert_eq!((report["exported"].as_u64(), report["resized"].as_u64()), (Some(2), Some(2)));
+    assert_eq!(image::image_dimensions(exports.path().join("best/a.png")).unwrap(), (40, 30));
+    assert_eq!(image::image_dimensions(exports.path().join("best/trips/2024/beach.jpg")).unwrap(), (40, 20));
+
+    // 按筛选条件打包为 ZIP，不缩小时内容与原图相同
+    let req = export(serde_json::json!({"name": "trips", "zip": true, "folder": "trips"}));
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::ACCEPTED);
+    assert_eq!(finished().await["target"], "trips.zip");
+    let mut archive = zip::ZipArchive::new(fs::File::open(exports.path().join("trips.zip")).unwrap()).unwrap();
+    assert_eq!(archive.len(), 3);
+    let mut map = Vec::new();
+    std::io::Read::read_to_end(&mut archive.by_name("trips/map.bmp").unwrap(), &mut map).unwrap();
+    assert_eq!(map, fs::read(fixture.path("trips/map.bmp")).unwrap());
+    assert!(!exports.path().join(".trips.zip.partial").exists());
+
+    // 目标已存在、名称无效、没有选中图片
+    let res = test::call_service(&app, export(serde_json::json!({"name": "best", "paths": ["b.jpg"]}))).await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+    let res = test::call_service(&app, export(serde_json::json!({"name": "../x", "paths": ["b.jpg"]}))).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let res = test::call_service(&app, export(serde_json::json!({"name": "none", "q": "no-such-image"}))).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let res = test::call_service(&app, export(serde_json::json!({"name": "gone", "paths": ["missing.jpg"]}))).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
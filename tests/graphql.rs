@@ -0,0 +1,56 @@
+//! GraphQL 接口 (`--features graphql`)
+
+#![cfg(feature = "graphql")]
+
+mod common;
+
+use actix_web::{test, App};
+use common::Fixture;
+use serde_json::{json, Value};
+
+#[actix_web::test]
+async fn queries_images_and_nested_folders() {
+    let fixture = Fixture::new();
+    let app = test::init_service(App::new().service(fixture.gallery_at("/photos"))).await;
+    let query = r#"
+        query ($ext: String) {
+            images(filter: { folder: "trips", ext: $ext }) { count images { path width folder { path } } }
+            folder(path: "trips") {
+                count
+                parent { path }
+                folders { path images(first: 1) { count images { name } } }
+            }
+            missing: image(path: "nope.png") { path }
+        }
+    "#;
+    let req = test::TestRequest::post()
+        .uri("/photos/graphql")
+        .set_json(json!({ "query": query, "variables": { "ext": "jpg" } }))
+        .to_request();
+    let res: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(res.get("errors").is_none(), "{}", res);
+    let data = &res["data"];
+    assert_eq!(
+        data["images"],
+        json!({
+            "count": 1,
+            "images": [{ "path": "trips/2024/beach.jpg", "width": 80, "folder": { "path": "trips/2024" } }],
+        })
+    );
+    // 只返回选择的字段
+    assert!(data["images"]["images"][0].get("tags").is_none());
+    assert_eq!(data["folder"]["count"], 3);
+    assert_eq!(data["folder"]["parent"]["path"], "");
+    assert_eq!(data["folder"]["folders"][0]["path"], "trips/2024");
+    assert_eq!(data["folder"]["folders"][0]["images"]["count"], 2);
+    assert_eq!(data["folder"]["folders"][0]["images"]["images"].as_array().unwrap().len(), 1);
+    assert!(data["missing"].is_null());
+
+    // 查询错误放在 errors 中
+    let req = test::TestRequest::post()
+        .uri("/photos/graphql")
+        .set_json(json!({ "query": "{ images(album: \"nope\") { count } }" }))
+        .to_request();
+    let res: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(res["errors"][0]["message"], "album not found: nope");
+}
@@ -0,0 +1,87 @@
+//! 同一个应用中按请求的域名选择画廊 (`PicUrlBuilder::host`)
+
+mod common;
+
+use actix_web::{test, App};
+use common::Fixture;
+use pic_url::PicUrl;
+use serde_json::Value;
+
+#[actix_web::test]
+async fn galleries_are_selected_by_host() {
+    let (family, main) = (Fixture::new(), Fixture::new());
+    family.image("family-only.png", 8, 8, [1, 2, 3]);
+    let family_gallery = PicUrl::builder()
+        .pic_dir(family.root().to_string_lossy())
+        .prewarm(false)
+        .host("family.example.com")
+        .host("www.family.example.com")
+        .auth("family-secret")
+        .build()
+        .unwrap();
+    family_gallery.config().rescan();
+    let app = test::init_service(App::new().service(family_gallery).service(main.gallery())).await;
+
+    let has_family = |host: &'static str| {
+        let app = &app;
+        async move {
+            let req = test::TestRequest::get().uri("/api/images").insert_header(("Host", host)).to_request();
+            let list: Value = test::call_and_read_body_json(app, req).await;
+            list["images"].as_array().unwrap().iter().any(|image| image["path"] == "family-only.png")
+        }
+    };
+    assert!(has_family("family.example.com").await);
+    assert!(has_family("www.family.example.com:8080").await);
+    assert!(!has_family("pics.example.com").await);
+
+    // 各自的管理令牌
+    let req = test::TestRequest::post()
+        .uri("/api/admin/rescan")
+        .insert_header(("Host", "family.example.com"))
+        .insert_header(("Authorization", "Bearer family-secret"))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    assert!(PicUrl::builder().pic_dir(main.root().to_string_lossy()).host("Bad_Host").build().is_err());
+}
+
+#[actix_web::test]
+async fn galleries_keep_their_own_index_and_rebuilds() {
+    let (family, main) = (Fixture::new(), Fixture::new());
+    family.image("family-only.png", 8, 8, [1, 2, 3]);
+    let build = |fixture: &Fixture, host: Option<&str>| {
+        let mut builder = PicUrl::builder().pic_dir(fixture.root().to_string_lossy()).prewarm(false).auth("secret");
+        if let Some(host) = host {
+            builder = builder.host(host);
+        }
+        let gallery = builder.build().unwrap();
+        gallery.config().rescan();
+        gallery
+    };
+    let family_gallery = build(&family, Some("family.example.com"));
+    let main_gallery = build(&main, None);
+    let count = main_gallery.config().current_images().len();
+    let app = test::init_service(App::new().service(family_gallery).service(main_gallery)).await;
+
+    let call = |method: test::TestRequest, uri: &'static str, host: &'static str| {
+        let app = &app;
+        async move {
+            let req = method
+                .uri(uri)
+                .insert_header(("Host", host))
+                .insert_header(("Authorization", "Bearer secret"))
+                .to_request();
+            test::call_service(app, req).await
+        }
+    };
+    // 每个画廊报告自己的索引大小
+    for (host, images) in [("family.example.com", count + 1), ("pics.example.com", count)] {
+        let body = test::read_body(call(test::TestRequest::get(), "/metrics", host).await).await;
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains(&format!("pic_url_index_images {}\n", images)), "{}", body);
+    }
+    // 一个画廊正在重建缩略图时，另一个画廊仍然可以开始重建
+    let rebuild = |host| call(test::TestRequest::post(), "/api/thumbs/rebuild", host);
+    assert_eq!(rebuild("family.example.com").await.status(), 202);
+    assert_eq!(rebuild("pics.example.com").await.status(), 202);
+}
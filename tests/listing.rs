@@ -0,0 +1,276 @@
+//! 图片列表、目录和页面
+
+mod common;
+
+use actix_web::http::header::IF_NONE_MATCH;
+use actix_web::{test, App};
+use common::{assert_golden, Fixture};
+use serde_json::Value;
+
+/// 去掉与文件内容无关、每次可能不同的字段
+fn stable(images: &Value) -> Value {
+    images
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|image| {
+            serde_json::json!({
+                "path": image["path"],
+                "name": image["name"],
+                "width": image["width"],
+                "height": image["height"],
+            })
+        })
+        .collect()
+}
+
+#[actix_web::test]
+async fn lists_images_in_all_formats_and_folders() {
+    let fixture = Fixture::new();
+    let app = test::init_service(App::new().service(fixture.gallery())).await;
+
+    let list: Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/api/images").to_request()).await;
+    let paths: Vec<&str> = list["images"].as_array().unwrap().iter().map(|i| i["path"].as_str().unwrap()).collect();
+    assert_eq!(list["count"], paths.len());
+    assert!(!paths.contains(&"notes.txt"));
+    assert!(paths.iter().all(|path| !path.starts_with(".thumbnails")));
+    if cfg!(unix) {
+        assert!(paths.contains(&"~x~bad~ff.png"));
+    }
+    assert_golden(&format!("images-{}", std::env::consts::FAMILY), &stable(&list["images"]));
+
+    let folders: Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/api/folders").to_request()).await;
+    assert_golden("folders", &folders);
+}
+
+#[actix_web::test]
+async fn filters_by_folder_and_paginates() {
+    let fixture = Fixture::new();
+    let app = test::init_service(App::new().service(fixture.gallery())).await;
+
+    let req = test::TestRequest::get().uri("/api/images?folder=trips/2024").to_request();
+    let list: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        stable(&list["images"]),
+        serde_json::json!([
+            { "path": "trips/2024/beach.jpg", "name": "beach.jpg", "width": 80, "height": 40 },
+            { "path": "trips/2024/sunset.webp", "name": "sunset.webp", "width": 40, "height": 80 },
+        ])
+    );
+
+    let req = test::TestRequest::get().uri("/api/images?page=2&per_page=2").to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    let req = test::TestRequest::get().uri("/api/images").to_request();
+    let all: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["page"], 2);
+    assert_eq!(page["count"], all["count"]);
+    assert_eq!(page["images"].as_array().unwrap()[..], all["images"].as_array().unwrap()[2..4]);
+}
+
+#[actix_web::test]
+async fn listed_paths_can_be_fetched() {
+    let fixture = Fixture::new();
+    let app = test::init_service(App::new().service(fixture.gallery())).await;
+
+    let list: Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/api/images").to_request()).await;
+    for image in list["images"].as_array().unwrap() {
+        let path = image["path"].as_str().unwrap();
+        let encoded: Vec<String> = path
+            .split('/')
+            .map(|segment| percent_encoding::utf8_percent_encode(segment, percent_encoding::NON_ALPHANUMERIC).to_string())
+            .collect();
+        for prefix in ["/pic/", "/thumb/"] {
+            let uri = format!("{}{}", prefix, encoded.join("/"));
+            let res = test::call_service(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+            assert!(res.status().is_success(), "{} 返回 {}", uri, res.status());
+        }
+    }
+}
+
+#[actix_web::test]
+async fn download_parameter_forces_attachment() {
+    let fixture = Fixture::new();
+    fixture.image("旅行/北京 \"夜\".png", 8, 8, [9, 9, 9]);
+    let app = test::init_service(App::new().service(fixture.gallery())).await;
+
+    let disposition = |res: &actix_web::dev::ServiceResponse| {
+        res.headers().get("Content-Disposition").unwrap().to_str().unwrap().to_string()
+    };
+    let res = test::call_service(&app, test::TestRequest::get().uri("/pic/a.png").to_request()).await;
+    assert!(disposition(&res).starts_with("inline"));
+    let res = test::call_service(&app, test::TestRequest::get().uri("/pic/a.png?download=1").to_request()).await;
+    assert_eq!(disposition(&res), "attachment; filename=\"a.png\"; filename*=UTF-8''a%2Epng");
+
+    let uri = "/pic/%E6%97%85%E8%A1%8C/%E5%8C%97%E4%BA%AC%20%22%E5%A4%9C%22.png?download=1";
+    let res = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+    assert_eq!(
+        disposition(&res),
+        "attachment; filename=\"__ ___.png\"; filename*=UTF-8''%E5%8C%97%E4%BA%AC%20%5F%E5%A4%9C%5F%2Epng"
+    );
+}
+
+#[actix_web::test]
+async fn content_type_comes_from_file_contents() {
+    let fixture = Fixture::new();
+    // 批量改名后扩展名不对的 PNG，和扩展名像图片的网页
+    std::fs::copy(fixture.path("a.png"), fixture.path("renamed.jpg")).unwrap();
+    std::fs::write(fixture.path("page.png"), "<html><script>alert(1)</script></html>").unwrap();
+    let app = test::init_service(App::new().service(fixture.gallery())).await;
+    let get = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+    let header = |res: &actix_web::dev::ServiceResponse, name: &str| {
+        res.headers().get(name).map(|value| value.to_str().unwrap().to_string())
+    };
+
+    let res = test::call_service(&app, get("/pic/renamed.jpg")).await;
+    assert_eq!(header(&res, "Content-Type").as_deref(), Some("image/png"));
+    assert_eq!(header(&res, "X-Content-Type-Options").as_deref(), Some("nosniff"));
+    assert!(test::call_service(&app, get("/thumb/renamed.jpg")).await.status().is_success());
+    let info: Value = test::read_body_json(test::call_service(&app, get("/api/info/renamed.jpg")).await).await;
+    assert_eq!(info["mime"], "image/png");
+    assert_eq!(info["width"], 64);
+
+    let res = test::call_service(&app, get("/pic/page.png")).await;
+    assert_eq!(header(&res, "Content-Type").as_deref(), Some("application/octet-stream"));
+    assert!(header(&res, "Content-Disposition").unwrap().starts_with("attachment"));
+}
+
+#[actix_web::test]
+async fn gallery_pages_render() {
+    let fixture = Fixture::new();
+    let app = test::init_service(App::new().service(fixture.gallery())).await;
+
+    for uri in ["/", "/gallery/trips", "/timeline", "/stats"] {
+        let res = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(res.status(), 200, "{}", uri);
+    }
+    let res = test::call_service(&app, test::TestRequest::get().uri("/gallery/nowhere").to_request()).await;
+    assert_eq!(res.status(), 404);
+}
+
+#[actix_web::test]
+async fn mounted_gallery_prefixes_links() {
+    let fixture = Fixture::new();
+    let app = test::init_service(App::new().service(fixture.gallery_at("/photos"))).await;
+
+    let res = test::call_service(&app, test::TestRequest::get().uri("/photos").to_request()).await;
+    assert_eq!(res.status(), 302);
+    assert_eq!(res.headers().get("location").unwrap(), "/photos/");
+
+    let body = test::call_and_read_body(&app, test::TestRequest::get().uri("/photos/").to_request()).await;
+    let html = String::from_utf8(body.to_vec()).unwrap();
+    assert!(html.contains(r#"data-base="&#x2F;photos""#));
+    // 缩略图使用带版本的地址
+    assert!(html.contains(r#"src="&#x2F;photos&#x2F;th&#x2F;"#));
+
+    let res = test::call_service(&app, test::TestRequest::get().uri("/photos/pic/a.png").to_request()).await;
+    assert_eq!(res.status(), 200);
+    let res = test::call_service(&app, test::TestRequest::get().uri("/pic/a.png").to_request()).await;
+    assert_eq!(res.status(), 404);
+}
+
+#[actix_web::test]
+async fn usage_sums_top_level_folders() {
+    let fixture = Fixture::new();
+    let app = test::init_service(App::new().service(fixture.gallery())).await;
+
+    let usage: Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/api/usage").to_request()).await;
+    let list: Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/api/images").to_request()).await;
+    assert_eq!(usage["files"], list["count"]);
+
+    let trips = usage["folders"].as_array().unwrap().iter().find(|f| f["folder"] == "trips").unwrap();
+    let bytes: u64 = ["trips/2024/beach.jpg", "trips/2024/sunset.webp", "trips/map.bmp"]
+        .iter()
+        .map(|path| std::fs::metadata(fixture.path(path)).unwrap().len())
+        .sum();
+    assert_eq!(trips["files"], 3);
+    assert_eq!(trips["bytes"], bytes);
+    // 直接放在图片目录下的图片归入 ""，不统计 notes.txt
+    let root = usage["folders"].as_array().unwrap().iter().find(|f| f["folder"] == "").unwrap();
+    assert_eq!(root["files"], if cfg!(unix) { 7 } else { 6 });
+    let sum: u64 = usage["folders"].as_array().unwrap().iter().map(|f| f["bytes"].as_u64().unwrap()).sum();
+    assert_eq!(usage["bytes"], sum);
+}
+
+#[actix_web::test]
+async fn api_clients_get_an_index_document() {
+    let fixture = Fixture::new();
+    let app = test::init_service(App::new().service(fixture.gallery_at("/photos"))).await;
+
+    let req = test::TestRequest::get().uri("/photos/").insert_header(("Accept", "application/json")).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.headers().get("vary").unwrap(), "Accept");
+    let index: Value = test::read_body_json(res).await;
+    assert_eq!(index["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(index["base"], "/photos");
+    assert_eq!(index["features"]["auth"], false);
+    let paths: Vec<&str> = index["endpoints"].as_array().unwrap().iter().map(|e| e["path"].as_str().unwrap()).collect();
+    assert_eq!(index["api"], "/api/v1");
+    assert!(paths.contains(&"/photos/api/v1/images"));
+    // 没有管理令牌时不列出管理接口
+    assert!(paths.iter().all(|path| !path.contains("/admin/")));
+
+    let req = test::TestRequest::get().uri("/photos/").insert_header(("Accept", "text/html,*/*;q=0.8")).to_request();
+    let res = test::call_service(&app, req).await;
+    assert!(res.headers().get("content-type").unwrap().to_str().unwrap().starts_with("text/html"));
+
+    let req = test::TestRequest::get().uri("/photos/api/v1/capabilities").to_request();
+    let res: Value = test::call_and_read_body_json(&app, req).await;
+    let capabilities = &res["data"];
+    assert_eq!(capabilities["compiled"]["heic"], false);
+    assert_eq!(capabilities["compiled"]["tls"], cfg!(feature = "tls"));
+    assert_eq!(capabilities["enabled"], index["features"]);
+    assert!(capabilities["formats"].as_array().unwrap().contains(&Value::from("webp")));
+    assert!(capabilities["search"].as_array().unwrap().contains(&Value::from("tag")));
+}
+
+#[actix_web::test]
+async fn export_json_is_a_stable_snapshot() {
+    let fixture = Fixture::new();
+    let app = test::init_service(App::new().service(fixture.gallery())).await;
+
+    let res = test::call_service(&app, test::TestRequest::get().uri("/api/export.json").to_request()).await;
+    assert_eq!(res.status(), 200);
+    let etag = res.headers().get("etag").unwrap().clone();
+    let body = test::read_body(res).await;
+    let snapshot: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(snapshot["format"], 1);
+    let images = snapshot["images"].as_array().unwrap();
+    let paths: Vec<&str> = images.iter().map(|image| image["path"].as_str().unwrap()).collect();
+    let mut sorted = paths.clone();
+    sorted.sort();
+    assert_eq!(paths, sorted);
+    let beach = images.iter().find(|image| image["path"] == "trips/2024/beach.jpg").unwrap();
+    let data = std::fs::read(fixture.path("trips/2024/beach.jpg")).unwrap();
+    assert_eq!(beach["sha256"], hex_sha256(&data));
+    assert_eq!(beach["bytes"], data.len());
+    assert_eq!((beach["width"].as_u64(), beach["height"].as_u64()), (Some(80), Some(40)));
+    let trips = snapshot["folders"].as_array().unwrap().iter().find(|f| f["path"] == "trips").unwrap();
+    assert_eq!(trips["count"], 3);
+
+    // 图库没有变化时内容逐字节相同，带 ETag 询问时返回 304
+    let again = test::call_and_read_body(&app, test::TestRequest::get().uri("/api/export.json").to_request()).await;
+    assert_eq!(again, body);
+    let req = test::TestRequest::get().uri("/api/export.json").insert_header((IF_NONE_MATCH, etag.clone()));
+    let req = req.to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 304);
+
+    // 计算过的哈希保存在数据目录中，重启后不必重新读取
+    let cached = std::fs::read_to_string(fixture.path(".pic_url/hashes.json")).unwrap();
+    assert!(cached.contains("trips/2024/beach.jpg"));
+
+    // 图片被替换后 ETag 随之变化
+    fixture.image("trips/2024/beach.jpg", 60, 40, [1, 2, 3]);
+    let req = test::TestRequest::get().uri("/api/export.json").insert_header((IF_NONE_MATCH, etag.clone()));
+    let req = req.to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+    assert_ne!(res.headers().get("etag").unwrap(), &etag);
+    let snapshot: Value = serde_json::from_slice(&test::read_body(res).await).unwrap();
+    let beach = snapshot["images"].as_array().unwrap().iter().find(|i| i["path"] == "trips/2024/beach.jpg").unwrap();
+    assert_eq!(beach["sha256"], hex_sha256(&std::fs::read(fixture.path("trips/2024/beach.jpg")).unwrap()));
+}
+
+fn hex_sha256(data: &[u8]) -> String {
+    use sha2::Digest;
+    sha2::Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,101 @@
+//! 元数据备份和恢复：标签、智能相册和短 ID 迁移到另一台服务器
+
+mod common;
+
+use actix_web::{test, App};
+use common::Fixture;
+use pic_url::PicUrl;
+use serde_json::{json, Value};
+
+fn gallery(fixture: &Fixture) -> PicUrl {
+    let gallery = PicUrl::builder()
+        .pic_dir(fixture.root().to_string_lossy())
+        .prewarm(false)
+        .auth("secret")
+        .build()
+        .unwrap();
+    gallery.config().rescan();
+    gallery
+}
+
+#[actix_web::test]
+async fn metadata_survives_a_migration() {
+    let old = Fixture::new();
+    let app = test::init_service(App::new().service(gallery(&old))).await;
+    let req = test::TestRequest::post()
+        .uri("/api/admin/batch")
+        .insert_header(("Authorization", "Bearer secret"))
+        .set_json(json!({ "action": "tag", "paths": ["a.png", "trips/map.bmp"], "tags": ["精选"] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+    let req = test::TestRequest::put()
+        .uri("/api/admin/albums/best")
+        .insert_header(("Authorization", "Bearer secret"))
+        .set_json(json!({ "tag": "精选" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+    let req = test::TestRequest::get().uri("/api/images?sort=name").to_request();
+    let images: Value = test::call_and_read_body_json(&app, req).await;
+
+    let req = test::TestRequest::get().uri("/api/admin/db/backup").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 401);
+    // 带版本的地址下载的也是原样的文件，不包在 data 中
+    let req = test::TestRequest::get()
+        .uri("/api/v1/admin/db/backup")
+        .insert_header(("Authorization", "Bearer secret"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+    assert!(res.headers().get("content-disposition").unwrap().to_str().unwrap().starts_with("attachment"));
+    let backup: Value = test::read_body_json(res).await;
+    assert_eq!(backup["format"], 1);
+
+    let new = Fixture::new();
+    let app = test::init_service(App::new().service(gallery(&new))).await;
+
+    // 有一类数据写不进去时什么都不替换，写好的临时文件也删掉
+    let blocked = new.path(".pic_url/api_keys.json.restore");
+    std::fs::create_dir_all(&blocked).unwrap();
+    let req = test::TestRequest::post()
+        .uri("/api/admin/db/restore")
+        .insert_header(("Authorization", "Bearer secret"))
+        .set_json(&backup)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 500);
+    assert!(!new.path(".pic_url/tags.json").exists());
+    assert!(!new.path(".pic_url/tags.json.restore").exists());
+    let req = test::TestRequest::get().uri("/api/albums").to_request();
+    let albums: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(albums, json!([]));
+    std::fs::remove_dir(&blocked).unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/api/admin/db/restore")
+        .insert_header(("Authorization", "Bearer secret"))
+        .set_json(&backup)
+        .to_request();
+    let report: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(report["tagged_images"], 2);
+    assert_eq!(report["albums"], 1);
+
+    let req = test::TestRequest::get().uri("/api/images?sort=name").to_request();
+    let restored: Value = test::call_and_read_body_json(&app, req).await;
+    // 新服务器上的文件修改时间不同，缩略图版本随之不同，只比较 ID 和标签
+    let metadata = |list: &Value| -> Vec<Value> {
+        let images = list["images"].as_array().unwrap();
+        images.iter().map(|image| json!([image["path"], image["id"], image["tags"]])).collect()
+    };
+    assert_eq!(metadata(&restored), metadata(&images));
+    let req = test::TestRequest::get().uri("/api/albums").to_request();
+    let albums: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(albums[0]["name"], "best");
+
+    let mut invalid = backup.clone();
+    invalid["format"] = json!(99);
+    let req = test::TestRequest::post()
+        .uri("/api/admin/db/restore")
+        .insert_header(("Authorization", "Bearer secret"))
+        .set_json(&invalid)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+}
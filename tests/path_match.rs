@@ -0,0 +1,52 @@
+//! 按路径访问时忽略 Unicode 规范化形式和大小写的差异 (`--path-match`)
+
+mod common;
+
+use actix_web::{test, App};
+use common::Fixture;
+use pic_url::{PathMatch, PicUrl};
+
+/// NFC 的 `Café/Été.png`，以 NFD 和不同大小写请求
+const NFD: &str = "/CAFE%CC%81/e%CC%81te%CC%81.PNG";
+
+#[actix_web::test]
+async fn nfd_and_case_variants_resolve_to_the_indexed_file() {
+    let fixture = Fixture::new();
+    fixture.image("Caf\u{e9}/\u{c9}t\u{e9}.png", 8, 8, [1, 2, 3]);
+    let gallery = |path_match| {
+        let gallery = PicUrl::builder()
+            .pic_dir(fixture.root().to_string_lossy())
+            .prewarm(false)
+            .path_match(path_match)
+            .build()
+            .unwrap();
+        gallery.config().rescan();
+        gallery
+    };
+
+    let app = test::init_service(App::new().service(gallery(PathMatch::Nocase))).await;
+    for uri in [
+        format!("/pic{}", NFD),
+        format!("/thumb{}", NFD),
+        "/pic/A.PNG".to_string(),
+        "/pic/Trips/2024/Beach.JPG?download=1".to_string(),
+        "/gallery/cafe%CC%81".to_string(),
+    ] {
+        let res = test::call_service(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(res.status(), 200, "{}", uri);
+    }
+    let res = test::call_service(&app, test::TestRequest::get().uri("/pic/missing.png").to_request()).await;
+    assert_eq!(res.status(), 404);
+
+    // 只做 Unicode 规范化时大小写仍然要一致
+    let app = test::init_service(App::new().service(gallery(PathMatch::Unicode))).await;
+    let uri = "/pic/Cafe%CC%81/%C3%89te%CC%81.png";
+    let res = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+    assert_eq!(res.status(), 200);
+    let res = test::call_service(&app, test::TestRequest::get().uri(&format!("/pic{}", NFD)).to_request()).await;
+    assert_eq!(res.status(), 404);
+
+    let app = test::init_service(App::new().service(gallery(PathMatch::Exact))).await;
+    let res = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+    assert_eq!(res.status(), 404);
+}
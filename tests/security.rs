@@ -0,0 +1,555 @@
+//! 不能借路径读取图片目录以外的文件
+
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::{test, App};
+use common::Fixture;
+use pic_url::PicUrl;
+use serde_json::Value;
+use std::fs;
+
+/// 图片目录旁边的文件，任何请求都不应该读到
+fn outside_file(fixture: &Fixture) {
+    let outside = fixture.root().parent().unwrap().join(format!(
+        "{}-outside.png",
+        fixture.root().file_name().unwrap().to_string_lossy()
+    ));
+    fs::copy(fixture.path("a.png"), outside).unwrap();
+}
+
+#[actix_web::test]
+async fn rejects_parent_directory_segments() {
+    let fixture = Fixture::new();
+    outside_file(&fixture);
+    let name = format!("{}-outside.png", fixture.root().file_name().unwrap().to_string_lossy());
+    let app = test::init_service(App::new().service(fixture.gallery())).await;
+
+    for prefix in ["/pic", "/thumb", "/api/info", "/api/exif", "/view"] {
+        for path in [
+            format!("../{}", name),
+            format!("%2e%2e/{}", name),
+            format!("%2E%2E/{}", name),
+            format!("trips/%2e%2e/%2e%2e/{}", name),
+            format!("..%2f{}", name),
+            format!("%2e%2e%2f{}", name),
+        ] {
+            let uri = format!("{}/{}", prefix, path);
+            let res = test::call_service(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+            assert_eq!(res.status(), StatusCode::NOT_FOUND, "{}", uri);
+        }
+    }
+    // 没有为图片目录以外的文件生成缩略图
+    assert!(fixture.thumbnails().is_empty());
+}
+
+#[actix_web::test]
+async fn rejects_absolute_and_reserved_paths() {
+    let fixture = Fixture::new();
+    let app = test::init_service(App::new().service(fixture.gallery())).await;
+    // 先生成一张缩略图，确认缩略图目录不能通过 /pic 访问
+    test::call_service(&app, test::TestRequest::get().uri("/thumb/a.png").to_request()).await;
+    let [thumb] = fixture.thumbnails().try_into().unwrap();
+    let thumb = thumb.strip_prefix(fixture.root()).unwrap().to_string_lossy().replace('\\', "/");
+
+    for uri in [
+        "/pic//etc/passwd".to_string(),
+        "/pic/%2fetc%2fpasswd".to_string(),
+        format!("/pic/{}", thumb),
+        format!("/thumb/{}", thumb),
+    ] {
+        let res = test::call_service(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND, "{}", uri);
+    }
+}
+
+#[actix_web::test]
+async fn escaped_names_must_be_canonical() {
+    let fixture = Fixture::new();
+    let app = test::init_service(App::new().service(fixture.gallery())).await;
+
+    // `~x~` 转义只接受规范形式，不能借此拼出 `..` 或 `/`
+    for uri in ["/pic/~x~2e2e", "/pic/~x~~2e~2e/a.png", "/pic/~x~a~2fb.png", "/pic/~x~bad~FF.png", "/pic/~x~a.png"] {
+        let res = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND, "{}", uri);
+    }
+}
+
+#[actix_web::test]
+async fn api_errors_use_json_envelope() {
+    let fixture = Fixture::new();
+    let app = test::init_service(App::new().service(fixture.gallery())).await;
+
+    let res = test::call_service(&app, test::TestRequest::get().uri("/api/info/%2e%2e/a.png").to_request()).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let id = res.headers().get("X-Request-Id").unwrap().to_str().unwrap().to_string();
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["error"]["code"], "not_found");
+    assert_eq!(body["error"]["request_id"], id.as_str());
+
+    // 沿用反向代理传入的请求 ID，不合法的重新生成
+    for (incoming, kept) in [("family-42", true), ("bad id", false)] {
+        let req = test::TestRequest::get()
+            .uri("/api/info/missing.png")
+            .insert_header(("X-Request-Id", incoming))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        let id = res.headers().get("X-Request-Id").unwrap().to_str().unwrap().to_string();
+        assert_eq!(id == incoming, kept, "{}", id);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["error"]["request_id"], id.as_str());
+    }
+}
+
+#[actix_web::test]
+async fn signed_originals_require_valid_signature() {
+    let fixture = Fixture::new();
+    let gallery = PicUrl::builder()
+        .pic_dir(fixture.root().to_string_lossy())
+        .prewarm(false)
+        .originals_secret("secret")
+        .build()
+        .unwrap();
+    gallery.config().rescan();
+    let app = test::init_service(App::new().service(gallery)).await;
+
+    let res = test::call_service(&app, test::TestRequest::get().uri("/pic/a.png").to_request()).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    // 列表和复制链接中的地址已经签名
+    let images: Value =
+        test::call_and_read_body_json(&app, test::TestRequest::get().uri("/api/images").to_request()).await;
+    let url = images["images"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|img| img["path"] == "a.png")
+        .unwrap()["url"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let res = test::call_service(&app, test::TestRequest::get().uri(&url).to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let cache_control = res.headers().get("Cache-Control").unwrap().to_str().unwrap();
+    assert!(cache_control.starts_with("public, max-age="), "{}", cache_control);
+    let links: Value =
+        test::call_and_read_body_json(&app, test::TestRequest::get().uri("/api/links/a.png").to_request()).await;
+    let short = links["short"].as_str().unwrap().replace("http://localhost:8080", "");
+    let res = test::call_service(&app, test::TestRequest::get().uri(&short).to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // 签名只对这张图片有效，过期时间也不能修改
+    let (_, query) = url.split_once('?').unwrap();
+    let expires: u64 = query.split('&').next().unwrap().trim_start_matches("expires=").parse().unwrap();
+    for uri in [
+        format!("/pic/b.jpg?{}", query),
+        url.replace(&format!("expires={}", expires), &format!("expires={}", expires + 1)),
+        format!("{}?{}", short.split('?').next().unwrap(), "expires=1&sig=00"),
+    ] {
+        let res = test::call_service(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN, "{}", uri);
+    }
+}
+
+#[actix_web::test]
+async fn failed_auth_is_written_to_auth_log() {
+    let fixture = Fixture::new();
+    let logs = tempfile::tempdir().unwrap();
+    let log = logs.path().join("auth.log");
+    let gallery = PicUrl::builder()
+        .pic_dir(fixture.root().to_string_lossy())
+        .prewarm(false)
+        .auth("secret")
+        .auth_log(log.to_string_lossy())
+        .build()
+        .unwrap();
+    let app = test::init_service(App::new().service(gallery)).await;
+    let rescan = |token: Option<&str>| {
+        let req = test::TestRequest::post()
+            .uri("/api/admin/rescan")
+            .peer_addr("203.0.113.7:51234".parse().unwrap());
+        match token {
+            Some(token) => req.insert_header(("Authorization", format!("Bearer {}", token))),
+            None => req,
+        }
+        .to_request()
+    };
+
+    // 只记录令牌错误的请求，没带凭据和令牌正确的不记录
+    for token in [None, Some("wrong")] {
+        assert_eq!(test::call_service(&app, rescan(token)).await.status(), StatusCode::UNAUTHORIZED);
+    }
+    assert_eq!(test::call_service(&app, rescan(Some("secret"))).await.status(), StatusCode::OK);
+
+    let content = fs::read_to_string(&log).unwrap();
+    let [line] = content.lines().collect::<Vec<_>>().try_into().unwrap();
+    let (_, rest) = line.split_once(' ').unwrap();
+    assert_eq!(rest, "pic_url auth failure: ip=203.0.113.7 method=POST route=/api/admin/rescan");
+}
+
+#[actix_web::test]
+async fn login_session_replaces_token_until_logout() {
+    let fixture = Fixture::new();
+    let gallery = PicUrl::builder()
+        .pic_dir(fixture.root().to_string_lossy())
+        .mount_path("/photos")
+        .prewarm(false)
+        .auth("secret")
+        .build()
+        .unwrap();
+    let app = test::init_service(App::new().service(gallery)).await;
+    let login = |token: &str| {
+        test::TestRequest::post()
+            .uri("/photos/login")
+            .set_form([("token", token), ("next", "/photos/gallery/trips")])
+            .to_request()
+    };
+
+    let res = test::call_service(&app, login("wrong")).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert!(res.response().cookies().next().is_none());
+
+    let res = test::call_service(&app, login("secret")).await;
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+    assert_eq!(res.headers().get("Location").unwrap(), "/photos/gallery/trips");
+    let cookie = res.response().cookies().next().unwrap().into_owned();
+    assert_eq!(cookie.path(), Some("/photos/"));
+    assert_eq!(cookie.http_only(), Some(true));
+
+    let rescan = |origin: Option<&str>| {
+        let req = test::TestRequest::post().uri("/photos/api/admin/rescan").cookie(cookie.clone());
+        match origin {
+            Some(origin) => req.insert_header(("Origin", origin)),
+            None => req,
+        }
+        .to_request()
+    };
+    assert_eq!(test::call_service(&app, rescan(None)).await.status(), StatusCode::OK);
+    // 其他网站的页面不能借用 Cookie
+    let res = test::call_service(&app, rescan(Some("https://evil.example"))).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    // 改动过期时间后签名不再匹配
+    let [id, expires, sig]: [&str; 3] = cookie.value().split('.').collect::<Vec<_>>().try_into().unwrap();
+    let forged = format!("{}.{}.{}", id, expires.parse::<u64>().unwrap() + 3600, sig);
+    let req = test::TestRequest::post()
+        .uri("/photos/api/admin/rescan")
+        .cookie(actix_web::cookie::Cookie::new("pic_session", forged))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+
+    let req = test::TestRequest::post().uri("/photos/logout").cookie(cookie.clone()).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+    // 注销后即使还留着 Cookie 也不能再用
+    assert_eq!(test::call_service(&app, rescan(None)).await.status(), StatusCode::UNAUTHORIZED);
+    let status: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get().uri("/photos/api/session").cookie(cookie.clone()).to_request(),
+    )
+    .await;
+    assert_eq!(status["authenticated"], false);
+}
+
+#[actix_web::test]
+async fn api_keys_are_scoped_and_revocable() {
+    let fixture = Fixture::new();
+    let gallery = PicUrl::builder()
+        .pic_dir(fixture.root().to_string_lossy())
+        .prewarm(false)
+        .auth("secret")
+        .build()
+        .unwrap();
+    let app = test::init_service(App::new().service(gallery)).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/admin/keys")
+        .insert_header(("Authorization", "Bearer secret"))
+        .set_json(serde_json::json!({"name": "phone", "scope": "upload", "folders": ["/trips/"]}))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let created: Value = test::read_body_json(res).await;
+    assert_eq!(created["folders"], serde_json::json!(["trips"]));
+    let key = created["key"].as_str().unwrap().to_string();
+    assert!(key.starts_with("pk_"));
+    // 数据目录中只有哈希
+    let stored = fs::read_to_string(fixture.path(".pic_url/api_keys.json")).unwrap();
+    assert!(!stored.contains(&key));
+
+    let upload = |folder: &str| {
+        let boundary = "pic-url-test";
+        let mut body = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"new.png\"\r\n\
+             Content-Type: image/png\r\n\r\n",
+            b = boundary
+        )
+        .into_bytes();
+        body.extend(fs::read(fixture.path("a.png")).unwrap());
+        body.extend(format!("\r\n--{}--\r\n", boundary).into_bytes());
+        test::TestRequest::post()
+            .uri(&format!("/api/upload?folder={}", folder))
+            .insert_header(("Authorization", format!("Bearer {}", key)))
+            .insert_header(("Content-Type", format!("multipart/form-data; boundary={}", boundary)))
+            .set_payload(body)
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, upload("trips/2024")).await.status(), StatusCode::OK);
+    assert!(fixture.path("trips/2024/new.png").exists());
+    assert_eq!(test::call_service(&app, upload("other")).await.status(), StatusCode::FORBIDDEN);
+
+    // 上传密钥不能做别的事，也不能管理密钥
+    for req in [
+        test::TestRequest::post().uri("/api/admin/rescan"),
+        test::TestRequest::get().uri("/api/admin/keys"),
+    ] {
+        let req = req.insert_header(("Authorization", format!("Bearer {}", key))).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+    }
+
+    let keys: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri("/api/admin/keys")
+            .insert_header(("Authorization", "Bearer secret"))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(keys[0]["name"], "phone");
+    assert!(keys[0]["key"].is_null());
+    assert!(keys[0]["last_used_at"].is_string());
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/admin/keys/{}", created["id"].as_str().unwrap()))
+        .insert_header(("Authorization", "Bearer secret"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+    assert_eq!(test::call_service(&app, upload("trips")).await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn user_homes_are_private_to_their_owner() {
+    let fixture = Fixture::new();
+    fixture.image("users/alice/cat.png", 20, 10, [1, 2, 3]);
+    fixture.image("users/bob/dog.png", 20, 10, [3, 2, 1]);
+    let gallery = PicUrl::builder()
+        .pic_dir(fixture.root().to_string_lossy())
+        .prewarm(false)
+        .auth("secret")
+        .user_homes(true)
+        .build()
+        .unwrap();
+    let app = test::init_service(App::new().service(gallery)).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/admin/keys")
+        .insert_header(("Authorization", "Bearer secret"))
+        .set_json(serde_json::json!({"name": "alice laptop", "scope": "read", "user": "alice"}))
+        .to_request();
+    let created: Value = test::call_and_read_body_json(&app, req).await;
+    let alice = format!("Bearer {}", created["key"].as_str().unwrap());
+
+    let get = |uri: &str, auth: Option<&str>| {
+        let req = test::TestRequest::get().uri(uri);
+        match auth {
+            Some(auth) => req.insert_header(("Authorization", auth.to_string())),
+            None => req,
+        }
+        .to_request()
+    };
+    let listed = |body: Value| -> Vec<String> {
+        let mut paths: Vec<String> = body["images"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|image| image["path"].as_str().unwrap().to_string())
+            .filter(|path| path.starts_with("users/"))
+            .collect();
+        paths.sort();
+        paths
+    };
+
+    let body = test::call_and_read_body_json(&app, get("/api/images", None)).await;
+    assert!(listed(body).is_empty());
+    let body = test::call_and_read_body_json(&app, get("/api/images", Some(&alice))).await;
+    assert_eq!(listed(body), ["users/alice/cat.png"]);
+    let body = test::call_and_read_body_json(&app, get("/api/images", Some("Bearer secret"))).await;
+    assert_eq!(listed(body), ["users/alice/cat.png", "users/bob/dog.png"]);
+
+    for (uri, auth, status) in [
+        ("/pic/users/alice/cat.png", None, StatusCode::NOT_FOUND),
+        ("/thumb/users/alice/cat.png", None, StatusCode::NOT_FOUND),
+        ("/pic/users/alice/cat.png", Some(alice.as_str()), StatusCode::OK),
+        ("/pic/users/bob/dog.png", Some(alice.as_str()), StatusCode::NOT_FOUND),
+        ("/api/info/users/bob/dog.png", Some(alice.as_str()), StatusCode::NOT_FOUND),
+        ("/gallery/users/bob", Some(alice.as_str()), StatusCode::NOT_FOUND),
+        ("/pic/users/bob/dog.png", Some("Bearer secret"), StatusCode::OK),
+    ] {
+        let res = test::call_service(&app, get(uri, auth)).await;
+        assert_eq!(res.status(), status, "{} {:?}", uri, auth);
+    }
+
+    // 短 ID 中看不出路径，同样只有主人和管理员能访问
+    let body: Value = test::call_and_read_body_json(&app, get("/api/images", Some("Bearer secret"))).await;
+    let dog = body["images"].as_array().unwrap().iter().find(|image| image["path"] == "users/bob/dog.png").unwrap();
+    let short = format!("/i/{}", dog["id"].as_str().unwrap());
+    for (auth, status) in [
+        (None, StatusCode::NOT_FOUND),
+        (Some(alice.as_str()), StatusCode::NOT_FOUND),
+        (Some("Bearer secret"), StatusCode::OK),
+    ] {
+        let res = test::call_service(&app, get(&short, auth)).await;
+        assert_eq!(res.status(), status, "{} {:?}", short, auth);
+    }
+
+    // 缩略图统计中最近的失败同样只列出能看到的图片
+    std::fs::write(fixture.path("users/bob/broken.png"), "not a png").unwrap();
+    test::call_service(&app, get("/thumb/users/bob/broken.png", Some("Bearer secret"))).await;
+    let failures = |body: Value| body["recent_failures"].as_array().unwrap().len();
+    assert_eq!(failures(test::call_and_read_body_json(&app, get("/api/thumbs/stats", None)).await), 0);
+    assert_eq!(failures(test::call_and_read_body_json(&app, get("/api/thumbs/stats", Some(&alice))).await), 0);
+    assert_eq!(failures(test::call_and_read_body_json(&app, get("/api/thumbs/stats", Some("Bearer secret"))).await), 1);
+}
+
+#[actix_web::test]
+async fn only_full_keys_can_delete_or_move() {
+    let fixture = Fixture::new();
+    let gallery = PicUrl::builder()
+        .pic_dir(fixture.root().to_string_lossy())
+        .prewarm(false)
+        .auth("secret")
+        .build()
+        .unwrap();
+    let app = test::init_service(App::new().service(gallery)).await;
+
+    let mut keys = Vec::new();
+    for scope in ["upload", "edit"] {
+        let req = test::TestRequest::post()
+            .uri("/api/admin/keys")
+            .insert_header(("Authorization", "Bearer secret"))
+            .set_json(serde_json::json!({"name": scope, "scope": scope}))
+            .to_request();
+        let created: Value = test::call_and_read_body_json(&app, req).await;
+        keys.push(format!("Bearer {}", created["key"].as_str().unwrap()));
+    }
+    let (uploader, editor) = (&keys[0], &keys[1]);
+
+    let call = |req: test::TestRequest, auth: &str| req.insert_header(("Authorization", auth.to_string())).to_request();
+    let rotate = || {
+        test::TestRequest::post()
+            .uri("/api/images/a.png/rotate")
+            .set_json(serde_json::json!({"degrees": 90}))
+    };
+    let delete = || {
+        test::TestRequest::post()
+            .uri("/api/admin/batch")
+            .set_json(serde_json::json!({"action": "delete", "paths": ["a.png"]}))
+    };
+
+    let res = test::call_service(&app, call(rotate(), uploader)).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["error"]["message"], "this operation requires the edit role");
+    let purge = test::TestRequest::post().uri("/api/admin/thumbs/purge");
+    assert_eq!(test::call_service(&app, call(purge, editor)).await.status(), StatusCode::FORBIDDEN);
+    assert_eq!(test::call_service(&app, call(rotate(), editor)).await.status(), StatusCode::OK);
+
+    let res = test::call_service(&app, call(delete(), editor)).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["error"]["message"], "this operation requires the full role");
+    assert!(fixture.path("a.png").exists());
+    assert_eq!(test::call_service(&app, call(delete(), "Bearer secret")).await.status(), StatusCode::OK);
+    assert!(!fixture.path("a.png").exists());
+}
+
+#[actix_web::test]
+async fn uploads_flagged_by_the_scanner_are_rejected() {
+    let fixture = Fixture::new();
+    let gallery = PicUrl::builder()
+        .pic_dir(fixture.root().to_string_lossy())
+        .prewarm(false)
+        .auth("secret")
+        .scan_command("sh -c 'echo \"$1: Eicar-Test-Signature FOUND\"; exit 1' scan")
+        .build()
+        .unwrap();
+    let app = test::init_service(App::new().service(gallery)).await;
+
+    let boundary = "pic-url-test";
+    let mut body = format!(
+        "--{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"new.png\"\r\n\
+         Content-Type: image/png\r\n\r\n",
+        b = boundary
+    )
+    .into_bytes();
+    body.extend(fs::read(fixture.path("a.png")).unwrap());
+    body.extend(format!("\r\n--{}--\r\n", boundary).into_bytes());
+    let req = test::TestRequest::post()
+        .uri("/api/upload")
+        .insert_header(("Authorization", "Bearer secret"))
+        .insert_header(("Content-Type", format!("multipart/form-data; boundary={}", boundary)))
+        .set_payload(body)
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["error"]["details"][0]["error"], "rejected by virus scan: Eicar-Test-Signature");
+    assert!(!fixture.path("new.png").exists());
+    // 临时文件也已删除
+    assert!(!fs::read_dir(fixture.root()).unwrap().any(|entry| {
+        entry.unwrap().file_name().to_string_lossy().ends_with(".uploading")
+    }));
+}
+
+#[actix_web::test]
+async fn uploads_and_api_bodies_have_separate_limits() {
+    let fixture = Fixture::new();
+    let gallery = PicUrl::builder()
+        .pic_dir(fixture.root().to_string_lossy())
+        .prewarm(false)
+        .auth("secret")
+        .max_upload_mb(1)
+        .max_body_kb(1)
+        .build()
+        .unwrap();
+    let app = test::init_service(App::new().service(gallery)).await;
+    let upload = |name: &str, data: Vec<u8>| {
+        let boundary = "pic-url-test";
+        let mut body = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{name}\"\r\n\
+             Content-Type: image/png\r\n\r\n",
+            b = boundary,
+            name = name
+        )
+        .into_bytes();
+        body.extend(data);
+        body.extend(format!("\r\n--{}--\r\n", boundary).into_bytes());
+        test::TestRequest::post()
+            .uri("/api/upload")
+            .insert_header(("Authorization", "Bearer secret"))
+            .insert_header(("Content-Type", format!("multipart/form-data; boundary={}", boundary)))
+            .set_payload(body)
+            .to_request()
+    };
+
+    // 上传的文件可以远大于接口请求体的上限
+    let mut medium = fs::read(fixture.path("a.png")).unwrap();
+    medium.resize(512 * 1024, 0);
+    let res = test::call_service(&app, upload("medium.png", medium)).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = test::call_service(&app, upload("large.png", vec![0; 2 * 1024 * 1024])).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["error"]["details"][0]["error"], "file is larger than 1 MB");
+
+    // 接口的 JSON 不能用上传的上限
+    let paths: Vec<String> = (0..100).map(|n| format!("photo-{}.jpg", n)).collect();
+    let req = test::TestRequest::post()
+        .uri("/api/admin/batch")
+        .insert_header(("Authorization", "Bearer secret"))
+        .set_json(serde_json::json!({"action": "tag", "paths": paths, "tags": ["x"]}))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    let body: Value = test::read_body_json(res).await;
+    assert!(body["error"]["message"].is_string());
+}
@@ -0,0 +1,63 @@
+//! 删除和移动图片时一起处理附属文件 (`--sidecars`)
+
+mod common;
+
+use actix_web::{test, App};
+use common::Fixture;
+use pic_url::{PicUrl, SidecarPolicy};
+use serde_json::{json, Value};
+use std::fs;
+
+#[actix_web::test]
+async fn sidecars_follow_deletes_and_moves() {
+    let fixture = Fixture::new();
+    for name in ["b.jpg.xmp", "b.jpg.supplemental-metadata.json", "b.CR2", "a.xmp", "a.CR2", "c.gif.json"] {
+        fs::write(fixture.path(name), "sidecar").unwrap();
+    }
+    // 同名的另一张图片存在时，按主文件名匹配的 `a.xmp` 不知道属于哪一张
+    fixture.image("a.jpg", 8, 8, [1, 2, 3]);
+    let gallery = PicUrl::builder()
+        .pic_dir(fixture.root().to_string_lossy())
+        .prewarm(false)
+        .auth("secret")
+        .sidecars(SidecarPolicy::All)
+        .build()
+        .unwrap();
+    gallery.config().rescan();
+    let app = test::init_service(App::new().service(gallery)).await;
+    let batch = |body: Value| {
+        test::TestRequest::post()
+            .uri("/api/admin/batch")
+            .insert_header(("Authorization", "Bearer secret"))
+            .set_json(body)
+            .to_request()
+    };
+
+    let req = batch(json!({"action": "move", "paths": ["b.jpg", "a.png"], "to": "moved"}));
+    let res: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(res["ok"], 2);
+    assert_eq!(res["sidecars"], 3);
+    for name in ["b.jpg.xmp", "b.jpg.supplemental-metadata.json", "b.CR2"] {
+        assert!(fixture.path(&format!("moved/{}", name)).exists(), "{}", name);
+        assert!(!fixture.path(name).exists(), "{}", name);
+    }
+    assert!(fixture.path("a.xmp").exists());
+
+    // 同名的另一张移走后，按主文件名匹配的附属文件属于剩下的这张
+    let req = batch(json!({"action": "delete", "paths": ["a.jpg", "c.gif"]}));
+    let res: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(res["ok"], 2);
+    assert_eq!(res["sidecars"], 3);
+    for name in ["a.xmp", "a.CR2", "c.gif.json"] {
+        assert!(!fixture.path(name).exists(), "{}", name);
+    }
+
+    // 目标目录中已有同名的附属文件时不移动
+    fs::write(fixture.path("trips/map.bmp.xmp"), "sidecar").unwrap();
+    fs::write(fixture.path("map.bmp.xmp"), "existing").unwrap();
+    let req = batch(json!({"action": "move", "paths": ["trips/map.bmp"], "to": ""}));
+    let res: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(res["ok"], 0);
+    assert_eq!(res["failed"][0]["error"], "map.bmp.xmp already exists");
+    assert!(fixture.path("trips/map.bmp").exists());
+}
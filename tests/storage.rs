@@ -0,0 +1,123 @@
+//! 按内容去重的存储 (`--storage cas`)
+
+#![cfg(unix)]
+
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::{test, App};
+use common::Fixture;
+use pic_url::PicUrl;
+use std::collections::BTreeMap;
+use std::fs;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+fn blob_count(fixture: &Fixture) -> usize {
+    fs::read_dir(fixture.path(".pic_url/blobs"))
+        .unwrap()
+        .map(|prefix| fs::read_dir(prefix.unwrap().path()).unwrap().count())
+        .sum()
+}
+
+fn index(fixture: &Fixture) -> BTreeMap<String, String> {
+    serde_json::from_slice(&fs::read(fixture.path(".pic_url/storage.json")).unwrap()).unwrap()
+}
+
+#[actix_web::test]
+async fn duplicates_share_one_blob() {
+    let fixture = Fixture::new();
+    fs::create_dir_all(fixture.path("copies")).unwrap();
+    fs::copy(fixture.path("a.png"), fixture.path("copies/a.png")).unwrap();
+    let ino = |relative: &str| fs::metadata(fixture.path(relative)).unwrap().ino();
+
+    let gallery = PicUrl::builder()
+        .pic_dir(fixture.root().to_string_lossy())
+        .prewarm(false)
+        .auth("secret")
+        .content_addressed(true)
+        .build()
+        .unwrap();
+    gallery.config().rescan();
+    assert_eq!(ino("a.png"), ino("copies/a.png"));
+    let blobs = blob_count(&fixture);
+    let by_path = index(&fixture);
+    assert_eq!(by_path["a.png"], by_path["copies/a.png"]);
+    assert_ne!(by_path["a.png"], by_path["b.jpg"]);
+    // 内容文件只读，外部程序不能原地修改
+    assert_eq!(fs::metadata(fixture.path("a.png")).unwrap().permissions().mode() & 0o222, 0);
+
+    // 上传相同的内容不增加内容文件
+    let app = test::init_service(App::new().service(gallery)).await;
+    let boundary = "pic-url-test";
+    let mut body = format!(
+        "--{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"again.png\"\r\n\
+         Content-Type: image/png\r\n\r\n",
+        b = boundary
+    )
+    .into_bytes();
+    body.extend(fs::read(fixture.path("a.png")).unwrap());
+    body.extend(format!("\r\n--{}--\r\n", boundary).into_bytes());
+    let req = test::TestRequest::post()
+        .uri("/api/upload")
+        .insert_header(("Authorization", "Bearer secret"))
+        .insert_header(("Content-Type", format!("multipart/form-data; boundary={}", boundary)))
+        .set_payload(body)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    assert_eq!(ino("again.png"), ino("a.png"));
+    assert_eq!(blob_count(&fixture), blobs);
+
+    // 改名只修改路径索引
+    let req = test::TestRequest::post()
+        .uri("/api/admin/batch")
+        .insert_header(("Authorization", "Bearer secret"))
+        .set_json(serde_json::json!({"action": "move", "paths": ["again.png"], "to": "trips"}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::post()
+        .uri("/api/admin/rescan")
+        .insert_header(("Authorization", "Bearer secret"))
+        .to_request();
+    test::call_service(&app, req).await;
+    assert_eq!(ino("trips/again.png"), ino("a.png"));
+    let by_path = index(&fixture);
+    assert_eq!(by_path["trips/again.png"], by_path["a.png"]);
+    assert!(!by_path.contains_key("again.png"));
+
+    // 全部副本删除后内容也删除
+    for relative in ["a.png", "copies/a.png", "trips/again.png"] {
+        fs::remove_file(fixture.path(relative)).unwrap();
+    }
+    let req = test::TestRequest::post()
+        .uri("/api/admin/rescan")
+        .insert_header(("Authorization", "Bearer secret"))
+        .to_request();
+    test::call_service(&app, req).await;
+    assert_eq!(blob_count(&fixture), blobs - 1);
+    assert!(!index(&fixture).contains_key("a.png"));
+}
+
+#[actix_web::test]
+async fn anonymous_reads_do_not_ingest() {
+    let fixture = Fixture::new();
+    let gallery = PicUrl::builder()
+        .pic_dir(fixture.root().to_string_lossy())
+        .prewarm(false)
+        .content_addressed(true)
+        .build()
+        .unwrap();
+    gallery.config().rescan();
+    let config = gallery.config().clone();
+    let app = test::init_service(App::new().service(gallery)).await;
+
+    // 外部放入的图片出现在列表中，但只有写入方的扫描才收入去重存储
+    fs::copy(fixture.path("a.png"), fixture.path("later.png")).unwrap();
+    let req = test::TestRequest::get().uri("/api/images").to_request();
+    let list: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert!(list["images"].as_array().unwrap().iter().any(|image| image["path"] == "later.png"));
+    assert!(!index(&fixture).contains_key("later.png"));
+    assert_eq!(fs::metadata(fixture.path("later.png")).unwrap().nlink(), 1);
+
+    config.rescan();
+    assert_eq!(index(&fixture)["later.png"], index(&fixture)["a.png"]);
+}
@@ -0,0 +1,289 @@
+//! 缩略图的生成、缓存和失效
+
+mod common;
+
+use actix_web::http::{header, StatusCode};
+use actix_web::{test, App};
+use common::{Fixture, THUMB_SIZE};
+use image::GenericImageView;
+use pic_url::{PicUrl, ThumbCheck};
+use std::fs;
+use std::time::{Duration, SystemTime};
+
+#[actix_web::test]
+async fn generates_thumbnails_within_configured_size() {
+    let fixture = Fixture::new();
+    let app = test::init_service(App::new().service(fixture.gallery())).await;
+
+    for (uri, expected) in [
+        ("/thumb/a.png", (32, 24)),
+        ("/thumb/b.jpg", (24, 32)),
+        ("/thumb/c.gif", (32, 32)),
+        ("/thumb/trips/2024/beach.jpg", (32, 16)),
+        ("/thumb/trips/2024/sunset.webp", (16, 32)),
+        ("/thumb/trips/map.bmp", (32, 32)),
+    ] {
+        let res = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK, "{}", uri);
+        assert!(res.headers().get(header::CONTENT_TYPE).unwrap().to_str().unwrap().starts_with("image/"));
+        let body = test::read_body(res).await;
+        let thumb = image::load_from_memory(&body).unwrap();
+        assert_eq!(thumb.dimensions(), expected, "{}", uri);
+        assert!(thumb.width() <= THUMB_SIZE && thumb.height() <= THUMB_SIZE);
+    }
+    assert_eq!(fixture.thumbnails().len(), 6);
+}
+
+#[actix_web::test]
+async fn reuses_cached_thumbnail_until_original_changes() {
+    let fixture = Fixture::new();
+    let app = test::init_service(App::new().service(fixture.gallery())).await;
+    let fetch = || test::call_and_read_body(&app, test::TestRequest::get().uri("/thumb/a.png").to_request());
+
+    let first = fetch().await;
+    let [thumb] = fixture.thumbnails().try_into().unwrap();
+    let cached_at = fs::metadata(&thumb).unwrap().modified().unwrap();
+
+    // 缓存命中时不重新生成
+    assert_eq!(fetch().await, first);
+    assert_eq!(fs::metadata(&thumb).unwrap().modified().unwrap(), cached_at);
+
+    // 原图比缩略图新时重新生成
+    fixture.image("a.png", 64, 48, [0, 0, 255]);
+    let newer = SystemTime::now() + Duration::from_secs(5);
+    fs::File::options()
+        .write(true)
+        .open(fixture.path("a.png"))
+        .unwrap()
+        .set_modified(newer)
+        .unwrap();
+    let second = fetch().await;
+    assert_ne!(second, first);
+    let pixel = image::load_from_memory(&second).unwrap().to_rgb8().get_pixel(0, 0).0;
+    assert_eq!(pixel, [0, 0, 255]);
+}
+
+#[actix_web::test]
+async fn thumbnails_of_special_names_are_kept_apart() {
+    let fixture = Fixture::new();
+    let app = test::init_service(App::new().service(fixture.gallery())).await;
+
+    let mut uris = vec!["/thumb/100%25%20%231%3F.png", "/thumb/%E7%A9%BA%E6%A0%BC%20%E5%92%8C%20%E4%B8%AD%E6%96%87.png"];
+    if cfg!(unix) {
+        uris.push("/thumb/~x~bad~ff.png");
+    }
+    // 本身以 `~x~` 开头的文件名也被转义
+    uris.push("/thumb/~x~~7ex~7eliteral.png");
+    for uri in &uris {
+        let res = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK, "{}", uri);
+    }
+    assert_eq!(fixture.thumbnails().len(), uris.len());
+}
+
+#[actix_web::test]
+async fn broken_and_missing_images() {
+    let fixture = Fixture::new();
+    fs::write(fixture.path("broken.png"), b"\x89PNG\r\n\x1a\nnot really").unwrap();
+    let app = test::init_service(App::new().service(fixture.gallery())).await;
+
+    let res = test::call_service(&app, test::TestRequest::get().uri("/thumb/broken.png").to_request()).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let res = test::call_service(&app, test::TestRequest::get().uri("/thumb/missing.png").to_request()).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let res = test::call_service(&app, test::TestRequest::get().uri("/thumb/notes.txt").to_request()).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn images_that_keep_failing_get_a_placeholder() {
+    let fixture = Fixture::new();
+    fs::write(fixture.path("broken.png"), b"\x89PNG\r\n\x1a\nnot really").unwrap();
+    let app = test::init_service(App::new().service(fixture.gallery())).await;
+    let fetch = || test::call_service(&app, test::TestRequest::get().uri("/thumb/broken.png").to_request());
+
+    for _ in 0..3 {
+        assert_eq!(fetch().await.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    // 达到上限后不再解码，返回占位图
+    let res = fetch().await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), "image/svg+xml");
+
+    let req = test::TestRequest::get().uri("/api/thumbs/failures").to_request();
+    let failures: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(failures["images"][0]["path"], "broken.png");
+    assert_eq!(failures["images"][0]["attempts"], 3);
+    assert_eq!(failures["images"][0]["quarantined"], true);
+
+    // 原图被替换后重新生成
+    fixture.image("broken.png", 64, 48, [0, 0, 255]);
+    let res = fetch().await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), "image/png");
+    let req = test::TestRequest::get().uri("/api/thumbs/failures").to_request();
+    let failures: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(failures["images"], serde_json::json!([]));
+}
+
+#[actix_web::test]
+async fn versioned_thumbnails_are_immutable() {
+    let fixture = Fixture::new();
+    let app = test::init_service(App::new().service(fixture.gallery())).await;
+    let thumb_of = |list: &serde_json::Value| {
+        let images = list["images"].as_array().unwrap();
+        let image = images.iter().find(|image| image["path"] == "a.png").unwrap();
+        image["thumb"].as_str().unwrap().to_string()
+    };
+
+    let list = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/api/images").to_request()).await;
+    let old = thumb_of(&list);
+    assert!(old.starts_with("/th/") && old.ends_with("/a.png"), "{}", old);
+    let res = test::call_service(&app, test::TestRequest::get().uri(&old).to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.headers().get(header::CACHE_CONTROL).unwrap().to_str().unwrap().contains("immutable"));
+
+    // 原图被替换后地址随之改变，旧地址跳转到新地址
+    fixture.image("a.png", 30, 30, [0, 0, 255]);
+    let list = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/api/images").to_request()).await;
+    let new = thumb_of(&list);
+    assert_ne!(new, old);
+    let res = test::call_service(&app, test::TestRequest::get().uri(&old).to_request()).await;
+    assert_eq!(res.status(), StatusCode::FOUND);
+    assert_eq!(res.headers().get(header::LOCATION).unwrap(), new.as_str());
+
+    let res = test::call_service(&app, test::TestRequest::get().uri("/th/0123456789abcdef/missing.png").to_request()).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn vanished_originals_get_a_missing_placeholder() {
+    let fixture = Fixture::new();
+    let app = test::init_service(App::new().service(fixture.gallery())).await;
+    let req = test::TestRequest::get().uri("/api/images").to_request();
+    let list: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let images = list["images"].as_array().unwrap();
+    let thumb = images.iter().find(|image| image["path"] == "a.png").unwrap()["thumb"].as_str().unwrap().to_string();
+
+    // 列表加载之后原图被删除，缩略图仍是 404，但内容是一张图片
+    fs::remove_file(fixture.path("a.png")).unwrap();
+    for uri in [thumb.as_str(), "/thumb/a.png"] {
+        let res = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND, "{}", uri);
+        assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), "image/svg+xml");
+        assert_eq!(res.headers().get(header::CACHE_CONTROL).unwrap(), "no-cache");
+        assert_eq!(res.headers().get("X-Thumbnail-Status").unwrap(), "missing");
+    }
+
+    let gallery = |missing: &str| {
+        PicUrl::builder()
+            .pic_dir(fixture.root().to_string_lossy())
+            .prewarm(false)
+            .missing_thumb(missing)
+            .build()
+    };
+    let custom = gallery(&fixture.path("c.gif").to_string_lossy()).unwrap();
+    let app = test::init_service(App::new().service(custom)).await;
+    let res = test::call_service(&app, test::TestRequest::get().uri("/thumb/a.png").to_request()).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), "image/gif");
+    assert_eq!(test::read_body(res).await, fs::read(fixture.path("c.gif")).unwrap());
+
+    let off = gallery("off").unwrap();
+    let app = test::init_service(App::new().service(off)).await;
+    let res = test::call_service(&app, test::TestRequest::get().uri("/thumb/a.png").to_request()).await;
+    assert_eq!(test::read_body(res).await, "Image not found");
+    assert!(gallery(&fixture.path("notes.txt").to_string_lossy()).is_err());
+}
+
+#[actix_web::test]
+async fn originals_support_conditional_requests() {
+    let fixture = Fixture::new();
+    let app = test::init_service(App::new().service(fixture.gallery())).await;
+
+    let res = test::call_service(&app, test::TestRequest::get().uri("/pic/a.png").to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let etag = res.headers().get(header::ETAG).unwrap().clone();
+    assert!(res.headers().contains_key(header::LAST_MODIFIED));
+
+    let req = test::TestRequest::get()
+        .uri("/pic/a.png")
+        .insert_header((header::IF_NONE_MATCH, etag))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+}
+
+#[actix_web::test]
+async fn archived_thumbnails_stay_local() {
+    let fixture = Fixture::new();
+    let gallery = PicUrl::builder()
+        .pic_dir(fixture.root().to_string_lossy())
+        .prewarm(false)
+        .archive("trips=8000")
+        .build()
+        .unwrap();
+    gallery.config().rescan();
+    let app = test::init_service(App::new().service(gallery)).await;
+    let info = |path: &'static str| test::TestRequest::get().uri(&format!("/api/info/{}", path)).to_request();
+
+    let body: serde_json::Value = test::call_and_read_body_json(&app, info("trips/2024/beach.jpg")).await;
+    assert_eq!(body["storage"], serde_json::json!({"tier": "archive", "folder": "trips", "expected_latency_ms": 8000}));
+    let body: serde_json::Value = test::call_and_read_body_json(&app, info("a.png")).await;
+    assert_eq!(body["storage"]["tier"], "hot");
+    // 读取过原图之后显示实际的耗时
+    let res = test::call_service(&app, test::TestRequest::get().uri("/pic/trips/2024/beach.jpg").to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = test::call_and_read_body_json(&app, info("trips/2024/beach.jpg")).await;
+    assert!(body["storage"]["expected_latency_ms"].as_u64().unwrap() < 8000);
+
+    // 已有缩略图时不访问原图：原图暂时不可用（存储未挂载）也能显示
+    for uri in ["/thumb/trips/2024/beach.jpg", "/thumb/a.png"] {
+        let res = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+    fs::rename(fixture.path("trips"), fixture.path("unmounted")).unwrap();
+    fs::rename(fixture.path("a.png"), fixture.path("gone.png")).unwrap();
+    let res = test::call_service(&app, test::TestRequest::get().uri("/thumb/trips/2024/beach.jpg").to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = test::call_service(&app, test::TestRequest::get().uri("/thumb/a.png").to_request()).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn content_checked_versions_follow_the_content() {
+    let fixture = Fixture::new();
+    let gallery = PicUrl::builder()
+        .pic_dir(fixture.root().to_string_lossy())
+        .thumb_size(THUMB_SIZE)
+        .thumb_check(ThumbCheck::Strict)
+        .prewarm(false)
+        .build()
+        .unwrap();
+    gallery.config().rescan();
+    let app = test::init_service(App::new().service(gallery)).await;
+    let thumb_of = |list: serde_json::Value| {
+        let images = list["images"].as_array().unwrap();
+        let image = images.iter().find(|image| image["path"] == "trips/map.bmp").unwrap();
+        image["thumb"].as_str().unwrap().to_string()
+    };
+    let list = || test::call_and_read_body_json(&app, test::TestRequest::get().uri("/api/images").to_request());
+
+    let old = thumb_of(list().await);
+    assert_eq!(test::call_service(&app, test::TestRequest::get().uri(&old).to_request()).await.status(), 200);
+
+    // 只改时间戳时地址不变
+    let path = fixture.path("trips/map.bmp");
+    let modified = fs::metadata(&path).unwrap().modified().unwrap();
+    let touch = |time: SystemTime| fs::File::options().write(true).open(&path).unwrap().set_modified(time).unwrap();
+    touch(modified - Duration::from_secs(3600));
+    assert_eq!(thumb_of(list().await), old);
+
+    // BMP 没有压缩，换一种颜色大小不变；恢复原来的修改时间后地址仍然改变
+    fixture.image("trips/map.bmp", 16, 16, [9, 9, 9]);
+    touch(modified);
+    let new = thumb_of(list().await);
+    assert_ne!(new, old);
+    let res = test::call_service(&app, test::TestRequest::get().uri(&old).to_request()).await;
+    assert_eq!(res.status(), StatusCode::FOUND);
+}
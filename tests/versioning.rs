@@ -0,0 +1,57 @@
+//! 带版本的接口 `/api/v1/...` 和不带版本的兼容地址
+
+mod common;
+
+use actix_web::{test, App};
+use common::Fixture;
+use pic_url::PicUrl;
+use serde_json::Value;
+
+#[actix_web::test]
+async fn v1_wraps_responses_and_old_paths_are_deprecated() {
+    let fixture = Fixture::new();
+    let gallery = PicUrl::builder()
+        .pic_dir(fixture.root().to_string_lossy())
+        .mount_path("/photos")
+        .prewarm(false)
+        .auth("secret")
+        .build()
+        .unwrap();
+    gallery.config().rescan();
+    let app = test::init_service(App::new().service(gallery)).await;
+
+    let req = test::TestRequest::get().uri("/photos/api/images?sort=name").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.headers().get("deprecation").unwrap(), "true");
+    assert_eq!(res.headers().get("link").unwrap(), "</photos/api/v1/images?sort=name>; rel=\"successor-version\"");
+    let old: Value = test::read_body_json(res).await;
+
+    let req = test::TestRequest::get().uri("/photos/api/v1/images?sort=name").to_request();
+    let res = test::call_service(&app, req).await;
+    assert!(res.headers().get("deprecation").is_none());
+    let new: Value = test::read_body_json(res).await;
+    assert_eq!(new["data"], old);
+
+    // 错误不包装，与其他接口相同
+    let req = test::TestRequest::get().uri("/photos/api/v1/info/missing.png").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 404);
+    let error: Value = test::read_body_json(res).await;
+    assert!(error.get("data").is_none());
+    assert!(error["error"]["message"].is_string());
+
+    // 按路径检查权限时看到的是实际的接口
+    let req = test::TestRequest::get().uri("/photos/api/v1/admin/info").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 401);
+    let req = test::TestRequest::get()
+        .uri("/photos/api/v1/admin/info")
+        .insert_header(("Authorization", "Bearer secret"))
+        .to_request();
+    let info: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(info["data"]["version"].is_string());
+
+    // 图片等非 JSON 响应不变
+    let req = test::TestRequest::get().uri("/photos/api/v1/qrcode?path=a.png").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.headers().get("content-type").unwrap(), "image/png");
+}